@@ -0,0 +1,221 @@
+use ironrdp_core::{impl_as_any, Decode, ReadCursor};
+use ironrdp_dvc::{encode_dvc_messages, DvcClientProcessor, DvcMessage, DvcProcessor};
+use ironrdp_pdu::{decode_err, encode_err, pdu_other_err, PduResult};
+use ironrdp_svc::{ChannelFlags, SvcMessage};
+use tracing::{debug, error, warn};
+
+use crate::pdu::{self, AudioFormat, ClientAudioInputPdu, ServerAudioInputPdu};
+use crate::{AudinError, CHANNEL_NAME};
+
+/// Message sent by the capture backend event loop.
+#[derive(Debug)]
+pub enum AudinMessage {
+    /// Captured audio data, encoded with the currently opened format.
+    ///
+    /// Client implementation should call [`AudinClient::encode_data`] when this message is received.
+    Data(Vec<u8>),
+    /// Failure received from the capture backend.
+    ///
+    /// Client implementation should log/display this error.
+    Error(Box<dyn AudinError>),
+}
+
+/// Proxy to send messages from the capture backend to the main application event loop.
+pub trait AudinMessageProxy: std::fmt::Debug + Send + Sync {
+    fn send_audin_message(&self, message: AudinMessage);
+}
+
+/// Audio capture backend interface.
+pub trait AudinClientHandler: Send + std::fmt::Debug {
+    /// Returns the subset of `server_formats` the backend is able to capture, in order of preference.
+    fn supported_formats(&self, server_formats: &[AudioFormat]) -> Vec<AudioFormat>;
+
+    /// Starts capturing with the given format.
+    ///
+    /// Captured data should be sent as [`AudinMessage::Data`] chunks of `frames_per_packet` frames.
+    fn open(&mut self, format: &AudioFormat, frames_per_packet: u32) -> Result<(), Box<dyn AudinError>>;
+
+    /// Switches the capture to the given format.
+    fn set_format(&mut self, format: &AudioFormat) -> Result<(), Box<dyn AudinError>>;
+
+    /// Stops capturing.
+    fn close(&mut self);
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum AudinState {
+    WaitingForVersion,
+    WaitingForFormats,
+    WaitingForOpen,
+    Opened,
+    Stop,
+}
+
+/// A client for the Audio Input Redirection Virtual Channel.
+#[derive(Debug)]
+pub struct AudinClient {
+    handler: Box<dyn AudinClientHandler>,
+    state: AudinState,
+    channel_id: Option<u32>,
+    version: Option<pdu::Version>,
+    formats: Vec<AudioFormat>,
+    format_no: Option<u32>,
+}
+
+impl AudinClient {
+    pub fn new(handler: Box<dyn AudinClientHandler>) -> Self {
+        Self {
+            handler,
+            state: AudinState::WaitingForVersion,
+            channel_id: None,
+            version: None,
+            formats: Vec::new(),
+            format_no: None,
+        }
+    }
+
+    pub fn version(&self) -> Option<pdu::Version> {
+        self.version
+    }
+
+    /// Returns the format currently used for capture, if any.
+    pub fn current_format(&self) -> Option<&AudioFormat> {
+        self.format_no.and_then(|no| self.formats.get(no as usize))
+    }
+
+    pub fn is_opened(&self) -> bool {
+        self.state == AudinState::Opened
+    }
+
+    /// Encodes captured audio `data` as Incoming Data and Data PDUs ready to be sent on the `DRDYNVC` channel.
+    pub fn encode_data(&self, data: Vec<u8>) -> PduResult<Vec<SvcMessage>> {
+        if self.state != AudinState::Opened {
+            return Err(pdu_other_err!("invalid state - audio input is not opened"));
+        }
+
+        let channel_id = self
+            .channel_id
+            .ok_or_else(|| pdu_other_err!("invalid state - no channel ID"))?;
+
+        let messages: Vec<DvcMessage> = vec![
+            Box::new(ClientAudioInputPdu::DataIncoming),
+            Box::new(ClientAudioInputPdu::Data(pdu::DataPdu { data: data.into() })),
+        ];
+
+        encode_dvc_messages(channel_id, messages, ChannelFlags::SHOW_PROTOCOL).map_err(|e| encode_err!(e))
+    }
+
+    fn open(&mut self, pdu: pdu::OpenPdu) -> Vec<DvcMessage> {
+        let result = match self.formats.get(pdu.initial_format as usize) {
+            Some(format) => match self.handler.open(format, pdu.frames_per_packet) {
+                Ok(()) => pdu::S_OK,
+                Err(error) => {
+                    error!(%error, "Failed to open audio capture");
+                    pdu::E_FAIL
+                }
+            },
+            None => {
+                error!(initial_format = pdu.initial_format, "Invalid initial format");
+                pdu::E_FAIL
+            }
+        };
+
+        let reply = ClientAudioInputPdu::OpenReply(pdu::OpenReplyPdu { result });
+        if result != pdu::S_OK {
+            return vec![Box::new(reply)];
+        }
+
+        self.state = AudinState::Opened;
+        self.format_no = Some(pdu.initial_format);
+
+        vec![
+            Box::new(ClientAudioInputPdu::FormatChange(pdu::FormatChangePdu {
+                new_format: pdu.initial_format,
+            })),
+            Box::new(reply),
+        ]
+    }
+
+    fn format_change(&mut self, pdu: pdu::FormatChangePdu) -> Vec<DvcMessage> {
+        let Some(format) = self.formats.get(pdu.new_format as usize) else {
+            error!(new_format = pdu.new_format, "Invalid format");
+            return Vec::new();
+        };
+
+        if let Err(error) = self.handler.set_format(format) {
+            error!(%error, "Failed to change audio capture format");
+            return Vec::new();
+        }
+
+        self.format_no = Some(pdu.new_format);
+
+        vec![Box::new(ClientAudioInputPdu::FormatChange(pdu))]
+    }
+}
+
+impl_as_any!(AudinClient);
+
+impl DvcProcessor for AudinClient {
+    fn channel_name(&self) -> &str {
+        CHANNEL_NAME
+    }
+
+    fn start(&mut self, channel_id: u32) -> PduResult<Vec<DvcMessage>> {
+        self.channel_id = Some(channel_id);
+        self.state = AudinState::WaitingForVersion;
+
+        Ok(Vec::new())
+    }
+
+    fn process(&mut self, _channel_id: u32, payload: &[u8]) -> PduResult<Vec<DvcMessage>> {
+        let pdu = ServerAudioInputPdu::decode(&mut ReadCursor::new(payload)).map_err(|e| decode_err!(e))?;
+
+        debug!(?pdu, ?self.state);
+        let msgs: Vec<DvcMessage> = match (self.state, pdu) {
+            (AudinState::WaitingForVersion, ServerAudioInputPdu::Version(pdu)) => {
+                let version = core::cmp::min(pdu.version, pdu::Version::V2);
+                self.version = Some(version);
+                self.state = AudinState::WaitingForFormats;
+                vec![Box::new(ClientAudioInputPdu::Version(pdu::VersionPdu { version }))]
+            }
+            (AudinState::WaitingForFormats, ServerAudioInputPdu::Formats(pdu)) => {
+                self.formats = self.handler.supported_formats(&pdu.formats);
+                if self.formats.is_empty() {
+                    warn!("No supported audio input format");
+                }
+                self.state = AudinState::WaitingForOpen;
+                vec![Box::new(ClientAudioInputPdu::Formats(pdu::FormatsPdu {
+                    formats: self.formats.clone(),
+                }))]
+            }
+            (AudinState::WaitingForOpen, ServerAudioInputPdu::Open(pdu)) => self.open(pdu),
+            (AudinState::Opened, ServerAudioInputPdu::FormatChange(pdu)) => self.format_change(pdu),
+            (state, pdu) => {
+                error!(?state, ?pdu, "Invalid PDU");
+                self.state = AudinState::Stop;
+                Vec::new()
+            }
+        };
+
+        Ok(msgs)
+    }
+
+    fn close(&mut self, _channel_id: u32) {
+        if self.state == AudinState::Opened {
+            self.handler.close();
+        }
+        self.state = AudinState::WaitingForVersion;
+        self.channel_id = None;
+        self.format_no = None;
+    }
+}
+
+impl DvcClientProcessor for AudinClient {}
+
+impl Drop for AudinClient {
+    fn drop(&mut self) {
+        if self.state == AudinState::Opened {
+            self.handler.close();
+        }
+    }
+}
@@ -0,0 +1,14 @@
+#![doc = include_str!("../README.md")]
+#![doc(
+    html_logo_url = "https://webdevolutions.blob.core.windows.net/images/projects/devolutions/logos/devolutions-icon-shadow.svg"
+)]
+
+pub const CHANNEL_NAME: &str = "AUDIO_INPUT";
+
+pub mod client;
+pub mod pdu;
+pub mod server;
+
+pub trait AudinError: std::error::Error + Send + Sync + 'static {}
+
+impl<T> AudinError for T where T: std::error::Error + Send + Sync + 'static {}
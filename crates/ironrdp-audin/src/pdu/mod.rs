@@ -0,0 +1,503 @@
+//! Audio Input Redirection Virtual Channel Protocol PDUs [MS-RDPEAI][1] implementation.
+//!
+//! [1]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpeai/d04ffa42-5a0f-4f80-abb1-cc26f71c9452
+
+use std::borrow::Cow;
+use std::fmt;
+
+use ironrdp_core::{
+    cast_length, ensure_fixed_part_size, ensure_size, invalid_field_err, Decode, DecodeError, DecodeResult, Encode,
+    EncodeResult, ReadCursor, WriteCursor,
+};
+use ironrdp_dvc::DvcEncode;
+pub use ironrdp_rdpsnd::pdu::{AudioFormat, WaveFormat};
+
+const MSG_SNDIN_VERSION: u8 = 0x01;
+const MSG_SNDIN_FORMATS: u8 = 0x02;
+const MSG_SNDIN_OPEN: u8 = 0x03;
+const MSG_SNDIN_OPEN_REPLY: u8 = 0x04;
+const MSG_SNDIN_DATA_INCOMING: u8 = 0x05;
+const MSG_SNDIN_DATA: u8 = 0x06;
+const MSG_SNDIN_FORMATCHANGE: u8 = 0x07;
+
+/// `S_OK` HRESULT, used in [`OpenReplyPdu`] to report success.
+pub const S_OK: u32 = 0x0000_0000;
+
+/// `E_FAIL` HRESULT, used in [`OpenReplyPdu`] to report an unspecified failure.
+pub const E_FAIL: u32 = 0x8000_4005;
+
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Eq, Ord)]
+pub enum Version {
+    V1 = 0x01,
+    V2 = 0x02,
+}
+
+impl TryFrom<u32> for Version {
+    type Error = DecodeError;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(Self::V1),
+            0x02 => Ok(Self::V2),
+            _ => Err(invalid_field_err!("Version", "unknown audio input version")),
+        }
+    }
+}
+
+impl From<Version> for u32 {
+    fn from(version: Version) -> Self {
+        version as u32
+    }
+}
+
+/// 2.2.2.1 Version PDU (MSG_SNDIN_VERSION)
+///
+/// Sent by the server to initiate the protocol, and by the client in response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionPdu {
+    pub version: Version,
+}
+
+impl VersionPdu {
+    const NAME: &'static str = "MSG_SNDIN_VERSION";
+
+    const FIXED_PART_SIZE: usize = 4 /* Version */;
+}
+
+impl Encode for VersionPdu {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_fixed_part_size!(in: dst);
+
+        dst.write_u32(self.version.into());
+
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE
+    }
+}
+
+impl<'de> Decode<'de> for VersionPdu {
+    fn decode(src: &mut ReadCursor<'de>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let version = Version::try_from(src.read_u32())?;
+
+        Ok(Self { version })
+    }
+}
+
+/// 2.2.2.2 Sound Formats PDU (MSG_SNDIN_FORMATS)
+///
+/// Sent by the server with the list of formats it can receive, and by the client in response with
+/// the subset it is able to capture. Indices used in subsequent PDUs refer to the client list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatsPdu {
+    pub formats: Vec<AudioFormat>,
+}
+
+impl FormatsPdu {
+    const NAME: &'static str = "MSG_SNDIN_FORMATS";
+
+    const FIXED_PART_SIZE: usize = 4 /* NumFormats */ + 4 /* cbSizeFormatsPacket */;
+}
+
+impl Encode for FormatsPdu {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(in: dst, size: self.size());
+
+        dst.write_u32(cast_length!("NumFormats", self.formats.len())?);
+        // The size of the whole PDU, header included, without the ExtraData field.
+        dst.write_u32(cast_length!(
+            "cbSizeFormatsPacket",
+            self.size().checked_add(1 /* MessageId */).expect("never overflow")
+        )?);
+        for format in self.formats.iter() {
+            format.encode(dst)?;
+        }
+
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE
+            .checked_add(self.formats.iter().map(|format| format.size()).sum::<usize>())
+            .expect("never overflow")
+    }
+}
+
+impl<'de> Decode<'de> for FormatsPdu {
+    fn decode(src: &mut ReadCursor<'de>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let n_formats = src.read_u32();
+        let _cb_size_formats_packet = src.read_u32();
+        let formats = (0..n_formats)
+            .map(|_| AudioFormat::decode(src))
+            .collect::<DecodeResult<_>>()?;
+        // The optional ExtraData field is not used by any known implementation.
+        let _extra_data = src.read_remaining();
+
+        Ok(Self { formats })
+    }
+}
+
+/// 2.2.2.3 Open PDU (MSG_SNDIN_OPEN)
+///
+/// Sent by the server to ask the client to start capturing with the given format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenPdu {
+    /// The number of audio frames the client should send in each Data PDU.
+    pub frames_per_packet: u32,
+    /// Index of the initial format in the client formats list.
+    pub initial_format: u32,
+    pub format: AudioFormat,
+}
+
+impl OpenPdu {
+    const NAME: &'static str = "MSG_SNDIN_OPEN";
+
+    const FIXED_PART_SIZE: usize = 4 /* FramesPerPacket */ + 4 /* initialFormat */;
+}
+
+impl Encode for OpenPdu {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(in: dst, size: self.size());
+
+        dst.write_u32(self.frames_per_packet);
+        dst.write_u32(self.initial_format);
+        self.format.encode(dst)?;
+
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE
+            .checked_add(self.format.size())
+            .expect("never overflow")
+    }
+}
+
+impl<'de> Decode<'de> for OpenPdu {
+    fn decode(src: &mut ReadCursor<'de>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let frames_per_packet = src.read_u32();
+        let initial_format = src.read_u32();
+        let format = AudioFormat::decode(src)?;
+
+        Ok(Self {
+            frames_per_packet,
+            initial_format,
+            format,
+        })
+    }
+}
+
+/// 2.2.2.4 Open Reply PDU (MSG_SNDIN_OPEN_REPLY)
+///
+/// Sent by the client to report the result of opening the capture device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenReplyPdu {
+    /// HRESULT of the open operation, [`S_OK`] on success.
+    pub result: u32,
+}
+
+impl OpenReplyPdu {
+    const NAME: &'static str = "MSG_SNDIN_OPEN_REPLY";
+
+    const FIXED_PART_SIZE: usize = 4 /* Result */;
+
+    pub fn is_success(&self) -> bool {
+        // Success HRESULTs have the severity bit cleared.
+        self.result & 0x8000_0000 == 0
+    }
+}
+
+impl Encode for OpenReplyPdu {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_fixed_part_size!(in: dst);
+
+        dst.write_u32(self.result);
+
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE
+    }
+}
+
+impl<'de> Decode<'de> for OpenReplyPdu {
+    fn decode(src: &mut ReadCursor<'de>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let result = src.read_u32();
+
+        Ok(Self { result })
+    }
+}
+
+/// 2.2.3.2 Data PDU (MSG_SNDIN_DATA)
+///
+/// Audio data captured by the client, encoded with the current format.
+#[derive(Clone, PartialEq, Eq)]
+pub struct DataPdu<'a> {
+    pub data: Cow<'a, [u8]>,
+}
+
+impl fmt::Debug for DataPdu<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DataPdu").field("data.len", &self.data.len()).finish()
+    }
+}
+
+impl DataPdu<'_> {
+    const NAME: &'static str = "MSG_SNDIN_DATA";
+}
+
+impl Encode for DataPdu<'_> {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(in: dst, size: self.size());
+
+        dst.write_slice(&self.data);
+
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        self.data.len()
+    }
+}
+
+impl<'de> Decode<'de> for DataPdu<'de> {
+    fn decode(src: &mut ReadCursor<'de>) -> DecodeResult<Self> {
+        let data = Cow::Borrowed(src.read_slice(src.len()));
+
+        Ok(Self { data })
+    }
+}
+
+/// 2.2.4 Sound Formats Change PDU (MSG_SNDIN_FORMATCHANGE)
+///
+/// Sent by the server to request a format change, and by the client to confirm it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatChangePdu {
+    /// Index of the new format in the client formats list.
+    pub new_format: u32,
+}
+
+impl FormatChangePdu {
+    const NAME: &'static str = "MSG_SNDIN_FORMATCHANGE";
+
+    const FIXED_PART_SIZE: usize = 4 /* NewFormat */;
+}
+
+impl Encode for FormatChangePdu {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_fixed_part_size!(in: dst);
+
+        dst.write_u32(self.new_format);
+
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE
+    }
+}
+
+impl<'de> Decode<'de> for FormatChangePdu {
+    fn decode(src: &mut ReadCursor<'de>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let new_format = src.read_u32();
+
+        Ok(Self { new_format })
+    }
+}
+
+/// Server Audio Input Channel message (PDU prefixed with `MessageId`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerAudioInputPdu {
+    Version(VersionPdu),
+    Formats(FormatsPdu),
+    Open(OpenPdu),
+    FormatChange(FormatChangePdu),
+}
+
+impl ServerAudioInputPdu {
+    const NAME: &'static str = "ServerAudioInputPdu";
+
+    const FIXED_PART_SIZE: usize = 1 /* MessageId */;
+}
+
+impl Encode for ServerAudioInputPdu {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_fixed_part_size!(in: dst);
+
+        let message_id = match self {
+            Self::Version(_) => MSG_SNDIN_VERSION,
+            Self::Formats(_) => MSG_SNDIN_FORMATS,
+            Self::Open(_) => MSG_SNDIN_OPEN,
+            Self::FormatChange(_) => MSG_SNDIN_FORMATCHANGE,
+        };
+
+        dst.write_u8(message_id);
+
+        match self {
+            Self::Version(pdu) => pdu.encode(dst),
+            Self::Formats(pdu) => pdu.encode(dst),
+            Self::Open(pdu) => pdu.encode(dst),
+            Self::FormatChange(pdu) => pdu.encode(dst),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE
+            .checked_add(match self {
+                Self::Version(pdu) => pdu.size(),
+                Self::Formats(pdu) => pdu.size(),
+                Self::Open(pdu) => pdu.size(),
+                Self::FormatChange(pdu) => pdu.size(),
+            })
+            .expect("never overflow")
+    }
+}
+
+impl<'de> Decode<'de> for ServerAudioInputPdu {
+    fn decode(src: &mut ReadCursor<'de>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let message_id = src.read_u8();
+
+        match message_id {
+            MSG_SNDIN_VERSION => Ok(Self::Version(VersionPdu::decode(src)?)),
+            MSG_SNDIN_FORMATS => Ok(Self::Formats(FormatsPdu::decode(src)?)),
+            MSG_SNDIN_OPEN => Ok(Self::Open(OpenPdu::decode(src)?)),
+            MSG_SNDIN_FORMATCHANGE => Ok(Self::FormatChange(FormatChangePdu::decode(src)?)),
+            _ => Err(invalid_field_err!(
+                "ServerAudioInputPdu::MessageId",
+                "unknown audio input PDU type"
+            )),
+        }
+    }
+}
+
+impl DvcEncode for ServerAudioInputPdu {}
+
+/// Client Audio Input Channel message (PDU prefixed with `MessageId`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientAudioInputPdu<'a> {
+    Version(VersionPdu),
+    Formats(FormatsPdu),
+    OpenReply(OpenReplyPdu),
+    /// 2.2.3.1 Incoming Data PDU (MSG_SNDIN_DATA_INCOMING)
+    ///
+    /// Sent immediately before each Data PDU.
+    DataIncoming,
+    Data(DataPdu<'a>),
+    FormatChange(FormatChangePdu),
+}
+
+impl ClientAudioInputPdu<'_> {
+    const NAME: &'static str = "ClientAudioInputPdu";
+
+    const FIXED_PART_SIZE: usize = 1 /* MessageId */;
+}
+
+impl Encode for ClientAudioInputPdu<'_> {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_fixed_part_size!(in: dst);
+
+        let message_id = match self {
+            Self::Version(_) => MSG_SNDIN_VERSION,
+            Self::Formats(_) => MSG_SNDIN_FORMATS,
+            Self::OpenReply(_) => MSG_SNDIN_OPEN_REPLY,
+            Self::DataIncoming => MSG_SNDIN_DATA_INCOMING,
+            Self::Data(_) => MSG_SNDIN_DATA,
+            Self::FormatChange(_) => MSG_SNDIN_FORMATCHANGE,
+        };
+
+        dst.write_u8(message_id);
+
+        match self {
+            Self::Version(pdu) => pdu.encode(dst),
+            Self::Formats(pdu) => pdu.encode(dst),
+            Self::OpenReply(pdu) => pdu.encode(dst),
+            Self::DataIncoming => Ok(()),
+            Self::Data(pdu) => pdu.encode(dst),
+            Self::FormatChange(pdu) => pdu.encode(dst),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE
+            .checked_add(match self {
+                Self::Version(pdu) => pdu.size(),
+                Self::Formats(pdu) => pdu.size(),
+                Self::OpenReply(pdu) => pdu.size(),
+                Self::DataIncoming => 0,
+                Self::Data(pdu) => pdu.size(),
+                Self::FormatChange(pdu) => pdu.size(),
+            })
+            .expect("never overflow")
+    }
+}
+
+impl<'de> Decode<'de> for ClientAudioInputPdu<'de> {
+    fn decode(src: &mut ReadCursor<'de>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let message_id = src.read_u8();
+
+        match message_id {
+            MSG_SNDIN_VERSION => Ok(Self::Version(VersionPdu::decode(src)?)),
+            MSG_SNDIN_FORMATS => Ok(Self::Formats(FormatsPdu::decode(src)?)),
+            MSG_SNDIN_OPEN_REPLY => Ok(Self::OpenReply(OpenReplyPdu::decode(src)?)),
+            MSG_SNDIN_DATA_INCOMING => Ok(Self::DataIncoming),
+            MSG_SNDIN_DATA => Ok(Self::Data(DataPdu::decode(src)?)),
+            MSG_SNDIN_FORMATCHANGE => Ok(Self::FormatChange(FormatChangePdu::decode(src)?)),
+            _ => Err(invalid_field_err!(
+                "ClientAudioInputPdu::MessageId",
+                "unknown audio input PDU type"
+            )),
+        }
+    }
+}
+
+impl DvcEncode for ClientAudioInputPdu<'static> {}
@@ -0,0 +1,194 @@
+use ironrdp_core::{impl_as_any, Decode, ReadCursor};
+use ironrdp_dvc::{DvcMessage, DvcProcessor, DvcServerProcessor};
+use ironrdp_pdu::{decode_err, pdu_other_err, PduResult};
+use tracing::{debug, error, warn};
+
+use crate::pdu::{self, AudioFormat, ClientAudioInputPdu, ServerAudioInputPdu};
+use crate::CHANNEL_NAME;
+
+pub trait AudinServerHandler: Send + std::fmt::Debug {
+    /// Returns the formats the server is able to receive.
+    fn get_formats(&self) -> &[AudioFormat];
+
+    /// Called with the formats supported by the client.
+    ///
+    /// Returns the index of the format to open the capture with, or `None` to not start the capture.
+    fn start(&mut self, client_formats: &[AudioFormat]) -> Option<u32>;
+
+    /// Returns the number of audio frames the client should send in each Data PDU.
+    ///
+    /// Defaults to 20 ms worth of audio.
+    fn frames_per_packet(&self, format: &AudioFormat) -> u32 {
+        format.n_samples_per_sec / 50
+    }
+
+    /// Called when the client replied to the open request.
+    fn opened(&mut self, result: u32) {
+        debug!(result, "Audio input opened");
+    }
+
+    /// Called with audio data captured by the client, encoded with `format`.
+    fn data(&mut self, format: &AudioFormat, data: &[u8]);
+
+    fn stop(&mut self);
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum AudinState {
+    Start,
+    WaitingForVersion,
+    WaitingForFormats,
+    Opening,
+    Ready,
+    Stop,
+}
+
+/// A server for the Audio Input Redirection Virtual Channel.
+#[derive(Debug)]
+pub struct AudinServer {
+    handler: Box<dyn AudinServerHandler>,
+    state: AudinState,
+    version: Option<pdu::Version>,
+    client_formats: Vec<AudioFormat>,
+    format_no: Option<u32>,
+    /// Whether the handler started, and must be stopped.
+    started: bool,
+}
+
+impl AudinServer {
+    pub fn new(handler: Box<dyn AudinServerHandler>) -> Self {
+        Self {
+            handler,
+            state: AudinState::Start,
+            version: None,
+            client_formats: Vec::new(),
+            format_no: None,
+            started: false,
+        }
+    }
+
+    pub fn version(&self) -> Option<pdu::Version> {
+        self.version
+    }
+
+    fn current_format(&self) -> PduResult<&AudioFormat> {
+        self.format_no
+            .and_then(|no| self.client_formats.get(no as usize))
+            .ok_or_else(|| pdu_other_err!("invalid state - no format"))
+    }
+
+    fn open(&mut self) -> Vec<DvcMessage> {
+        let Some(initial_format) = self.handler.start(&self.client_formats) else {
+            debug!("Audio input not started");
+            self.state = AudinState::Stop;
+            return Vec::new();
+        };
+        self.started = true;
+
+        let Some(format) = self.client_formats.get(initial_format as usize).cloned() else {
+            error!(initial_format, "Invalid initial format");
+            self.state = AudinState::Stop;
+            return Vec::new();
+        };
+
+        self.format_no = Some(initial_format);
+        self.state = AudinState::Opening;
+
+        let pdu = pdu::OpenPdu {
+            frames_per_packet: self.handler.frames_per_packet(&format),
+            initial_format,
+            format,
+        };
+
+        vec![Box::new(ServerAudioInputPdu::Open(pdu))]
+    }
+
+    fn stop(&mut self) {
+        if self.started {
+            self.started = false;
+            self.handler.stop();
+        }
+    }
+}
+
+impl_as_any!(AudinServer);
+
+impl DvcProcessor for AudinServer {
+    fn channel_name(&self) -> &str {
+        CHANNEL_NAME
+    }
+
+    fn start(&mut self, _channel_id: u32) -> PduResult<Vec<DvcMessage>> {
+        if self.state != AudinState::Start {
+            error!("Attempted to start audin channel in invalid state");
+        }
+
+        self.state = AudinState::WaitingForVersion;
+
+        Ok(vec![Box::new(ServerAudioInputPdu::Version(pdu::VersionPdu {
+            version: pdu::Version::V2,
+        }))])
+    }
+
+    fn process(&mut self, _channel_id: u32, payload: &[u8]) -> PduResult<Vec<DvcMessage>> {
+        let pdu = ClientAudioInputPdu::decode(&mut ReadCursor::new(payload)).map_err(|e| decode_err!(e))?;
+
+        debug!(?pdu, ?self.state);
+        let msgs: Vec<DvcMessage> = match (self.state, pdu) {
+            (AudinState::WaitingForVersion, ClientAudioInputPdu::Version(pdu)) => {
+                self.version = Some(pdu.version);
+                self.state = AudinState::WaitingForFormats;
+                vec![Box::new(ServerAudioInputPdu::Formats(pdu::FormatsPdu {
+                    formats: self.handler.get_formats().into(),
+                }))]
+            }
+            (AudinState::WaitingForFormats, ClientAudioInputPdu::Formats(pdu)) => {
+                self.client_formats = pdu.formats;
+                self.open()
+            }
+            (AudinState::Opening | AudinState::Ready, ClientAudioInputPdu::FormatChange(pdu)) => {
+                if (pdu.new_format as usize) < self.client_formats.len() {
+                    self.format_no = Some(pdu.new_format);
+                } else {
+                    warn!(new_format = pdu.new_format, "Invalid format change");
+                }
+                Vec::new()
+            }
+            (AudinState::Opening, ClientAudioInputPdu::OpenReply(pdu)) => {
+                self.handler.opened(pdu.result);
+                self.state = if pdu.is_success() {
+                    AudinState::Ready
+                } else {
+                    AudinState::Stop
+                };
+                Vec::new()
+            }
+            (AudinState::Ready, ClientAudioInputPdu::DataIncoming) => Vec::new(),
+            (AudinState::Ready, ClientAudioInputPdu::Data(pdu)) => {
+                let format = self.current_format()?.clone();
+                self.handler.data(&format, &pdu.data);
+                Vec::new()
+            }
+            (state, pdu) => {
+                error!(?state, ?pdu, "Invalid PDU");
+                Vec::new()
+            }
+        };
+
+        Ok(msgs)
+    }
+
+    fn close(&mut self, _channel_id: u32) {
+        self.stop();
+        self.state = AudinState::Start;
+        self.format_no = None;
+    }
+}
+
+impl DvcServerProcessor for AudinServer {}
+
+impl Drop for AudinServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
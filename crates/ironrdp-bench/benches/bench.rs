@@ -1,49 +1,76 @@
+#![allow(clippy::arithmetic_side_effects)] // Benchmark set-up with small, fixed sizes
+
 use std::num::NonZero;
 
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
 use ironrdp_graphics::color_conversion::to_64x64_ycbcr_tile;
+use ironrdp_graphics::{dwt, quantization};
 use ironrdp_pdu::codecs::rfx;
+use ironrdp_pdu::rdp::capability_sets::EntropyBits;
 use ironrdp_server::{
-    bench::encoder::rfx::{rfx_enc, rfx_enc_tile},
+    bench::encoder::rfx::{rfx_enc, rfx_enc_tile, RfxFrameEncoder},
     BitmapUpdate,
 };
 
-pub fn rfx_enc_tile_bench(c: &mut Criterion) {
-    let quant = rfx::Quant::default();
-    let algo = rfx::EntropyAlgorithm::Rlgr3;
-    let bitmap = BitmapUpdate {
+/// Returns noisy pixels, so that encoding doesn't take the shortcuts of uniform data.
+fn pixels(len: usize) -> Vec<u8> {
+    let mut state = 0x1234_5678u32;
+
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 24) as u8
+        })
+        .collect()
+}
+
+fn bitmap(width: u16, height: u16) -> BitmapUpdate {
+    let stride = usize::from(width) * 4;
+
+    BitmapUpdate {
         top: 0,
         left: 0,
-        width: NonZero::new(64).unwrap(),
-        height: NonZero::new(64).unwrap(),
+        width: NonZero::new(width).unwrap(),
+        height: NonZero::new(height).unwrap(),
         format: ironrdp_server::PixelFormat::ARgb32,
-        data: vec![0; 64 * 64 * 4],
+        data: pixels(stride * usize::from(height)),
         order: ironrdp_server::PixelOrder::BottomToTop,
-        stride: 64 * 4,
-    };
+        stride,
+    }
+}
+
+pub fn rfx_enc_tile_bench(c: &mut Criterion) {
+    let quant = rfx::Quant::default();
+    let algo = rfx::EntropyAlgorithm::Rlgr3;
+    let bitmap = bitmap(64, 64);
     c.bench_function("rfx_enc_tile", |b| b.iter(|| rfx_enc_tile(&bitmap, &quant, algo, 0, 0)));
 }
 
 pub fn rfx_enc_bench(c: &mut Criterion) {
     let quant = rfx::Quant::default();
     let algo = rfx::EntropyAlgorithm::Rlgr3;
-    let bitmap = BitmapUpdate {
-        top: 0,
-        left: 0,
-        width: NonZero::new(2048).unwrap(),
-        height: NonZero::new(2048).unwrap(),
-        format: ironrdp_server::PixelFormat::ARgb32,
-        data: vec![0; 2048 * 2048 * 4],
-        order: ironrdp_server::PixelOrder::BottomToTop,
-        stride: 64 * 4,
-    };
+    let bitmap = bitmap(2048, 2048);
     c.bench_function("rfx_enc", |b| b.iter(|| rfx_enc(&bitmap, &quant, algo)));
 }
 
+pub fn rfx_enc_frame_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rfx_enc_frame");
+
+    for (width, height) in [(1920, 1080), (3840, 2160)] {
+        let bitmap = bitmap(width, height);
+        let mut encoder = RfxFrameEncoder::new(EntropyBits::Rlgr3);
+
+        group.throughput(Throughput::Bytes(bitmap.data.len() as u64));
+        group.bench_function(format!("{width}x{height}"), |b| b.iter(|| encoder.encode(&bitmap)));
+    }
+
+    group.finish();
+}
+
 pub fn to_ycbcr_bench(c: &mut Criterion) {
     const WIDTH: usize = 64;
     const HEIGHT: usize = 64;
-    let input = vec![0; WIDTH * HEIGHT * 4];
+    let input = pixels(WIDTH * HEIGHT * 4);
     let stride = WIDTH * 4;
     let mut y = [0i16; WIDTH * HEIGHT];
     let mut cb = [0i16; WIDTH * HEIGHT];
@@ -54,5 +81,43 @@ pub fn to_ycbcr_bench(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, rfx_enc_tile_bench, rfx_enc_bench, to_ycbcr_bench);
+pub fn dwt_bench(c: &mut Criterion) {
+    let input: Vec<i16> = pixels(64 * 64 * 2)
+        .chunks_exact(2)
+        .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]) >> 4)
+        .collect();
+    let mut buffer = input.clone();
+    let mut temp = vec![0; 64 * 64];
+    c.bench_function("dwt_encode", |b| {
+        b.iter(|| {
+            buffer.copy_from_slice(&input);
+            dwt::encode(&mut buffer, &mut temp);
+        })
+    });
+}
+
+pub fn quantization_bench(c: &mut Criterion) {
+    let quant = rfx::Quant::default();
+    let input: Vec<i16> = pixels(64 * 64 * 2)
+        .chunks_exact(2)
+        .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+        .collect();
+    let mut buffer = input.clone();
+    c.bench_function("quantization_encode", |b| {
+        b.iter(|| {
+            buffer.copy_from_slice(&input);
+            quantization::encode(&mut buffer, &quant);
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    rfx_enc_tile_bench,
+    rfx_enc_bench,
+    rfx_enc_frame_bench,
+    to_ycbcr_bench,
+    dwt_bench,
+    quantization_bench
+);
 criterion_main!(benches);
@@ -27,9 +27,28 @@ pub enum ClipboardType {
     Stub,
     #[cfg(windows)]
     Windows,
+    #[cfg(target_os = "linux")]
+    X11,
     None,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum AudioTransport {
+    Static,
+    Dynamic,
+    DynamicLossy,
+}
+
+impl AudioTransport {
+    fn parse(audio_transport: AudioTransport) -> connector::AudioPlaybackTransport {
+        match audio_transport {
+            AudioTransport::Static => connector::AudioPlaybackTransport::Static,
+            AudioTransport::Dynamic => connector::AudioPlaybackTransport::Dynamic,
+            AudioTransport::DynamicLossy => connector::AudioPlaybackTransport::DynamicLossy,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 enum KeyboardType {
     IbmPcXt,
@@ -226,6 +245,10 @@ struct Args {
     /// The clipboard type
     #[clap(long, value_enum, value_parser, default_value_t = ClipboardType::Default)]
     clipboard_type: ClipboardType,
+
+    /// The virtual channel used for audio playback
+    #[clap(long, value_enum, value_parser, default_value_t = AudioTransport::Static)]
+    audio_transport: AudioTransport,
 }
 
 impl Config {
@@ -274,7 +297,15 @@ impl Config {
             {
                 ClipboardType::Windows
             }
-            #[cfg(not(windows))]
+            #[cfg(target_os = "linux")]
+            {
+                if std::env::var_os("DISPLAY").is_some() {
+                    ClipboardType::X11
+                } else {
+                    ClipboardType::None
+                }
+            }
+            #[cfg(not(any(windows, target_os = "linux")))]
             {
                 ClipboardType::None
             }
@@ -320,6 +351,7 @@ impl Config {
             autologon: args.autologon,
             pointer_software_rendering: true,
             performance_flags: PerformanceFlags::default(),
+            audio_playback_transport: AudioTransport::parse(args.audio_transport),
         };
 
         Ok(Self {
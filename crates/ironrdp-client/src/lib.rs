@@ -19,3 +19,4 @@ pub mod clipboard;
 pub mod config;
 pub mod network_client;
 pub mod rdp;
+pub mod rdpsnd;
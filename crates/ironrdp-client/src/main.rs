@@ -7,6 +7,7 @@ use anyhow::Context as _;
 use ironrdp_client::app::App;
 use ironrdp_client::config::{ClipboardType, Config};
 use ironrdp_client::rdp::{RdpClient, RdpInputEvent, RdpOutputEvent};
+use ironrdp_client::rdpsnd::ClientRdpsndMessageProxy;
 use tokio::runtime;
 use winit::event_loop::EventLoop;
 
@@ -36,6 +37,9 @@ fn main() -> anyhow::Result<()> {
     // starts and clipboard functionality will not be available.
     #[cfg(windows)]
     let _win_clipboard;
+    // Same goes for `x11_clipboard`, which stops its clipboard thread when dropped.
+    #[cfg(target_os = "linux")]
+    let _x11_clipboard;
 
     let cliprdr_factory = match config.clipboard_type {
         ClipboardType::Stub => {
@@ -50,12 +54,23 @@ fn main() -> anyhow::Result<()> {
             use ironrdp_client::clipboard::ClientClipboardMessageProxy;
             use ironrdp_cliprdr_native::WinClipboard;
 
-            let cliprdr = WinClipboard::new(ClientClipboardMessageProxy::new(input_event_sender))?;
+            let cliprdr = WinClipboard::new(ClientClipboardMessageProxy::new(input_event_sender.clone()))?;
 
             let factory = cliprdr.backend_factory();
             _win_clipboard = cliprdr;
             Some(factory)
         }
+        #[cfg(target_os = "linux")]
+        ClipboardType::X11 => {
+            use ironrdp_client::clipboard::ClientClipboardMessageProxy;
+            use ironrdp_cliprdr_native::X11Clipboard;
+
+            let cliprdr = X11Clipboard::new(ClientClipboardMessageProxy::new(input_event_sender.clone()))?;
+
+            let factory = cliprdr.backend_factory();
+            _x11_clipboard = cliprdr;
+            Some(factory)
+        }
         _ => None,
     };
 
@@ -64,6 +79,7 @@ fn main() -> anyhow::Result<()> {
         event_loop_proxy,
         input_event_receiver,
         cliprdr_factory,
+        rdpsnd_proxy: Some(ClientRdpsndMessageProxy::new(input_event_sender)),
     };
 
     debug!("Start RDP thread");
@@ -5,8 +5,11 @@ use ironrdp::displaycontrol::client::DisplayControlClient;
 use ironrdp::displaycontrol::pdu::MonitorLayoutEntry;
 use ironrdp::graphics::image_processing::PixelFormat;
 use ironrdp::pdu::input::fast_path::FastPathInputEvent;
+use ironrdp::rdpsnd::client::RdpsndMessage;
+use ironrdp::rdpsnd::pdu::WaveConfirmPdu;
 use ironrdp::session::image::DecodedImage;
 use ironrdp::session::{fast_path, ActiveStage, ActiveStageOutput, GracefulDisconnectReason, SessionResult};
+use ironrdp::svc::SvcProcessorMessages;
 use ironrdp::{cliprdr, connector, rdpdr, rdpsnd, session};
 use ironrdp_core::WriteBuf;
 use ironrdp_rdpsnd_native::cpal;
@@ -18,6 +21,7 @@ use tokio::sync::mpsc;
 use winit::event_loop::EventLoopProxy;
 
 use crate::config::Config;
+use crate::rdpsnd::ClientRdpsndMessageProxy;
 
 #[derive(Debug)]
 pub enum RdpOutputEvent {
@@ -41,6 +45,7 @@ pub enum RdpInputEvent {
     FastPath(SmallVec<[FastPathInputEvent; 2]>),
     Close,
     Clipboard(ClipboardMessage),
+    Rdpsnd(RdpsndMessage),
 }
 
 impl RdpInputEvent {
@@ -54,18 +59,20 @@ pub struct RdpClient {
     pub event_loop_proxy: EventLoopProxy<RdpOutputEvent>,
     pub input_event_receiver: mpsc::UnboundedReceiver<RdpInputEvent>,
     pub cliprdr_factory: Option<Box<dyn CliprdrBackendFactory + Send>>,
+    pub rdpsnd_proxy: Option<ClientRdpsndMessageProxy>,
 }
 
 impl RdpClient {
     pub async fn run(mut self) {
         loop {
-            let (connection_result, framed) = match connect(&self.config, self.cliprdr_factory.as_deref()).await {
-                Ok(result) => result,
-                Err(e) => {
-                    let _ = self.event_loop_proxy.send_event(RdpOutputEvent::ConnectionFailure(e));
-                    break;
-                }
-            };
+            let (connection_result, framed) =
+                match connect(&self.config, self.cliprdr_factory.as_deref(), self.rdpsnd_proxy.clone()).await {
+                    Ok(result) => result,
+                    Err(e) => {
+                        let _ = self.event_loop_proxy.send_event(RdpOutputEvent::ConnectionFailure(e));
+                        break;
+                    }
+                };
 
             match active_session(
                 framed,
@@ -102,6 +109,7 @@ type UpgradedFramed = ironrdp_tokio::TokioFramed<ironrdp_tls::TlsStream<TcpStrea
 async fn connect(
     config: &Config,
     cliprdr_factory: Option<&(dyn CliprdrBackendFactory + Send)>,
+    rdpsnd_proxy: Option<ClientRdpsndMessageProxy>,
 ) -> ConnectorResult<(ConnectionResult, UpgradedFramed)> {
     let dest = format!("{}:{}", config.destination.name(), config.destination.port());
 
@@ -115,14 +123,28 @@ async fn connect(
 
     let mut framed = ironrdp_tokio::TokioFramed::new(stream);
 
+    let rdpsnd_backend = match rdpsnd_proxy {
+        Some(proxy) => cpal::RdpsndBackend::with_message_proxy(proxy),
+        None => cpal::RdpsndBackend::new(),
+    };
+    let rdpsnd = rdpsnd::client::Rdpsnd::new(Box::new(rdpsnd_backend));
+    let mut drdynvc =
+        ironrdp::dvc::DrdynvcClient::new().with_dynamic_channel(DisplayControlClient::new(|_| Ok(Vec::new())));
+
     let mut connector = connector::ClientConnector::new(config.connector.clone())
         .with_server_addr(server_addr)
-        .with_static_channel(
-            ironrdp::dvc::DrdynvcClient::new().with_dynamic_channel(DisplayControlClient::new(|_| Ok(Vec::new()))),
-        )
-        .with_static_channel(rdpsnd::client::Rdpsnd::new(Box::new(cpal::RdpsndBackend::new())))
         .with_static_channel(rdpdr::Rdpdr::new(Box::new(NoopRdpdrBackend {}), "IronRDP".to_owned()).with_smartcard(0));
 
+    match config.connector.audio_playback_transport {
+        connector::AudioPlaybackTransport::Static => connector.attach_static_channel(rdpsnd),
+        connector::AudioPlaybackTransport::Dynamic => drdynvc = drdynvc.with_dynamic_channel(rdpsnd),
+        connector::AudioPlaybackTransport::DynamicLossy => {
+            drdynvc = drdynvc.with_dynamic_channel(rdpsnd.with_lossy_dvc());
+        }
+    }
+
+    connector.attach_static_channel(drdynvc);
+
     if let Some(builder) = cliprdr_factory {
         let backend = builder.build_cliprdr_backend();
 
@@ -224,6 +246,14 @@ async fn active_session(
                                     Some(cliprdr.initiate_paste(format)
                                         .map_err(|e| session::custom_err!("CLIPRDR", e))?)
                                 }
+                                ClipboardMessage::SendFileContentsRequest(request) => {
+                                    Some(cliprdr.request_file_contents(request)
+                                        .map_err(|e| session::custom_err!("CLIPRDR", e))?)
+                                }
+                                ClipboardMessage::SendFileContentsResponse(response) => {
+                                    Some(cliprdr.submit_file_contents(response)
+                                        .map_err(|e| session::custom_err!("CLIPRDR", e))?)
+                                }
                                 ClipboardMessage::Error(e) => {
                                     error!("Clipboard backend error: {}", e);
                                     None
@@ -241,6 +271,15 @@ async fn active_session(
                             Vec::new()
                         }
                     }
+                    RdpInputEvent::Rdpsnd(RdpsndMessage::WavePlayed(confirm)) => {
+                        match encode_wave_confirm(&mut active_stage, &confirm)? {
+                            Some(frame) => vec![ActiveStageOutput::ResponseFrame(frame)],
+                            None => {
+                                debug!("Wave played, but RDPSND is not available anymore");
+                                Vec::new()
+                            }
+                        }
+                    }
                 }
             }
         };
@@ -339,3 +378,30 @@ async fn active_session(
 
     Ok(RdpControlFlow::TerminatedGracefully(disconnect_reason))
 }
+
+/// Encodes the confirm of a played wave, on whichever channel RDPSND is running.
+fn encode_wave_confirm(active_stage: &mut ActiveStage, confirm: &WaveConfirmPdu) -> SessionResult<Option<Vec<u8>>> {
+    if let Some(rdpsnd) = active_stage.get_svc_processor_mut::<rdpsnd::client::Rdpsnd>() {
+        let messages = rdpsnd
+            .wave_confirm(confirm.timestamp, confirm.block_no)
+            .map_err(|e| session::custom_err!("RDPSND", e))?;
+        return active_stage.process_svc_processor_messages(messages).map(Some);
+    }
+
+    let Some(dvc) = active_stage.get_dvc::<rdpsnd::client::Rdpsnd>() else {
+        return Ok(None);
+    };
+    let (Some(rdpsnd), Some(channel_id)) = (
+        dvc.channel_processor_downcast_ref::<rdpsnd::client::Rdpsnd>(),
+        dvc.channel_id(),
+    ) else {
+        return Ok(None);
+    };
+    let messages = rdpsnd
+        .encode_dvc_wave_confirm(channel_id, confirm.timestamp, confirm.block_no)
+        .map_err(|e| session::custom_err!("RDPSND", e))?;
+
+    active_stage
+        .process_svc_processor_messages(SvcProcessorMessages::<ironrdp::dvc::DrdynvcClient>::new(messages))
+        .map(Some)
+}
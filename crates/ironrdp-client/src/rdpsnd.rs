@@ -0,0 +1,24 @@
+use ironrdp::rdpsnd::client::{RdpsndMessage, RdpsndMessageProxy};
+use tokio::sync::mpsc;
+
+use crate::rdp::RdpInputEvent;
+
+/// Shim for sending RDPSND backend events as `RdpInputEvent`
+#[derive(Clone, Debug)]
+pub struct ClientRdpsndMessageProxy {
+    tx: mpsc::UnboundedSender<RdpInputEvent>,
+}
+
+impl ClientRdpsndMessageProxy {
+    pub fn new(tx: mpsc::UnboundedSender<RdpInputEvent>) -> Self {
+        Self { tx }
+    }
+}
+
+impl RdpsndMessageProxy for ClientRdpsndMessageProxy {
+    fn send_rdpsnd_message(&self, message: RdpsndMessage) {
+        if self.tx.send(RdpInputEvent::Rdpsnd(message)).is_err() {
+            error!("Failed to send audio playback message, receiver is closed");
+        }
+    }
+}
@@ -17,6 +17,8 @@ pub enum BitmapError {
     Unsupported(&'static str),
     #[error("one of bitmap's dimensions is invalid")]
     InvalidSize,
+    #[error("bitmap's color table is invalid")]
+    InvalidColorTable,
     #[error("buffer size required for allocation is too big")]
     BufferTooBig,
     #[error("image width is too big")]
@@ -145,8 +147,8 @@ impl Encode for CiexyzTriple {
 
 /// Header used in `CF_DIB` formats, part of [BITMAPINFO]
 ///
-/// We don't use the optional `bmiColors` field, because it is only relevant for bitmaps with
-/// bpp < 24, which are not supported yet, therefore only fixed part of the header is implemented.
+/// Only the fixed part of the header is implemented here, the color masks and the `bmiColors`
+/// color table following it are read separately when decoding the bitmap.
 ///
 /// [BITMAPINFO]: https://learn.microsoft.com/en-us/windows/win32/api/wingdi/ns-wingdi-bitmapinfo
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -416,18 +418,27 @@ fn validate_v1_header(header: &BitmapInfoHeader) -> Result<(), BitmapError> {
         return Err(BitmapError::InvalidSize);
     }
 
-    // In the modern world bitmaps with bpp < 24 are rare, and it is even more rare for the bitmaps
-    // which are placed on the clipboard as DIBs, therefore we could safely skip the support for
-    // such bitmaps.
-    const SUPPORTED_BIT_COUNT: &[u16] = &[24, 32];
+    const SUPPORTED_BIT_COUNT: &[u16] = &[1, 4, 8, 16, 24, 32];
 
     if !SUPPORTED_BIT_COUNT.contains(&header.bit_count) {
         return Err(BitmapError::Unsupported("unsupported bit count"));
     }
 
-    // This is only relevant for bitmaps with bpp < 24, which are not supported.
-    if header.clr_used != 0 {
-        return Err(BitmapError::Unsupported("color table is not supported"));
+    let is_supported_compression = match header.compression {
+        BitmapCompression::RGB => true,
+        BitmapCompression::RLE8 => header.bit_count == 8,
+        BitmapCompression::RLE4 => header.bit_count == 4,
+        BitmapCompression::BITFIELDS => header.bit_count == 16 || header.bit_count == 32,
+        _ => false,
+    };
+
+    if !is_supported_compression {
+        return Err(BitmapError::Unsupported("unsupported compression"));
+    }
+
+    // RLE bitmaps are always bottom-up.
+    if !header.is_bottom_up() && matches!(header.compression, BitmapCompression::RLE8 | BitmapCompression::RLE4) {
+        return Err(BitmapError::Unsupported("top-down RLE bitmap"));
     }
 
     Ok(())
@@ -436,32 +447,15 @@ fn validate_v1_header(header: &BitmapInfoHeader) -> Result<(), BitmapError> {
 fn validate_v5_header(header: &BitmapV5Header) -> Result<(), BitmapError> {
     validate_v1_header(&header.v1)?;
 
-    // We support only uncompressed DIB bitmaps as it is the most common case for clipboard-copied bitmaps.
-    const DIBV5_SUPPORTED_COMPRESSION: &[BitmapCompression] = &[BitmapCompression::RGB, BitmapCompression::BITFIELDS];
-
-    if !DIBV5_SUPPORTED_COMPRESSION.contains(&header.v1.compression) {
-        return Err(BitmapError::Unsupported("unsupported compression"));
-    }
-
-    if header.v1.compression == BitmapCompression::BITFIELDS {
-        // Currently, we only support the standard order, BGRA, for the bitfields compression.
-        let is_bgr = header.red_mask == 0x00FF0000 && header.green_mask == 0x0000FF00 && header.blue_mask == 0x000000FF;
-
-        // Note: when there is no alpha channel, the mask is 0x00000000 and we support this too.
-        let is_supported_alpha = header.alpha_mask == 0 || header.alpha_mask == 0xFF000000;
-
-        if !is_bgr || !is_supported_alpha {
-            return Err(BitmapError::Unsupported(
-                "non-standard color masks for `BITFIELDS` compression are not supported",
-            ));
-        }
-    }
-
     const SUPPORTED_COLOR_SPACE: &[ColorSpace] = &[
         ColorSpace::SRGB,
         // Assume that Windows color space is sRGB, either way we don't have enough information on
         // the clipboard to convert it to other color spaces.
         ColorSpace::WINDOWS,
+        ColorSpace::CALIBRATED_RGB,
+        ColorSpace::PROFILE_EMBEDDED,
+        // The profile file is on the remote machine, assume sRGB as well.
+        ColorSpace::PROFILE_LINKED,
     ];
 
     if !SUPPORTED_COLOR_SPACE.contains(&header.color_space) {
@@ -480,7 +474,7 @@ struct PngEncoderContext {
 
 /// Computes the stride of an uncompressed RGB bitmap.
 ///
-/// INVARIANT: `width <= output (stride) <= width * 4`
+/// INVARIANT: `width / 8 <= output (stride) <= width * 4 + 3`
 ///
 /// In an uncompressed bitmap, the stride is the number of bytes needed to go from the start of one
 /// row of pixels to the start of the next row. The image format defines a minimum stride for an
@@ -505,38 +499,187 @@ fn rgb_bmp_stride(width: u16, bit_count: u16) -> usize {
     }
 }
 
-fn bgra_to_top_down_rgba(
-    header: &BitmapInfoHeader,
-    src_bitmap: &[u8],
-    preserve_alpha: bool,
-) -> Result<PngEncoderContext, BitmapError> {
-    // DIB may be encoded bottom-up, but the format we target, PNG, is top-down.
-    let should_flip_vertically = header.is_bottom_up();
+/// A channel of `BI_BITFIELDS` pixels.
+#[derive(Debug, Clone, Copy)]
+struct ChannelMask {
+    mask: u32,
+    shift: u32,
+    /// INVARIANT: `max != 0` when `mask != 0`
+    max: u32,
+}
 
-    let width = header.width();
-    let height = header.height();
+impl ChannelMask {
+    fn new(mask: u32) -> Self {
+        let shift = mask.trailing_zeros();
+        // `shift` is 32 only for an empty mask.
+        let max = mask.checked_shr(shift).unwrap_or(0);
 
-    let src_n_samples = usize::from(header.bit_count / 8);
+        Self { mask, shift, max }
+    }
 
-    let src_stride = rgb_bmp_stride(width, header.bit_count);
+    /// Extracts the channel value of `pixel`, scaled to 8 bits, or `None` for an empty mask.
+    fn extract(&self, pixel: u32) -> Option<u8> {
+        if self.mask == 0 {
+            return None;
+        }
+
+        let value = (pixel & self.mask).checked_shr(self.shift).unwrap_or(0);
+
+        // No side effects, because value <= max <= u32::MAX, and max is non-zero per invariant.
+        #[allow(clippy::arithmetic_side_effects)]
+        let scaled = u64::from(value) * 255 / u64::from(self.max);
+
+        Some(u8::try_from(scaled).unwrap_or(u8::MAX))
+    }
+}
+
+/// Color masks of a bitmap with 16 or 32 bits per pixel.
+#[derive(Debug, Clone, Copy)]
+struct ColorMasks {
+    red: ChannelMask,
+    green: ChannelMask,
+    blue: ChannelMask,
+    alpha: ChannelMask,
+}
+
+impl ColorMasks {
+    fn new(red: u32, green: u32, blue: u32, alpha: u32) -> Self {
+        Self {
+            red: ChannelMask::new(red),
+            green: ChannelMask::new(green),
+            blue: ChannelMask::new(blue),
+            alpha: ChannelMask::new(alpha),
+        }
+    }
+
+    /// Masks of uncompressed (`BI_RGB`) bitmaps.
+    fn for_rgb(bit_count: u16) -> Self {
+        if bit_count == 16 {
+            // 5 bits per channel, the most significant bit being unused
+            Self::new(0x7C00, 0x03E0, 0x001F, 0)
+        } else {
+            // DIBv1 (CF_DIB) does not have alpha channel and the fourth byte is dropped, while DIBv5
+            // (CF_DIBV5) supports alpha channel, so it is preserved.
+            Self::new(0x00FF0000, 0x0000FF00, 0x000000FF, 0xFF000000)
+        }
+    }
 
-    let (dst_color_type, dst_n_samples) = if preserve_alpha {
-        (png::ColorType::Rgba, 4)
+    fn rgba(self, pixel: u32) -> [u8; 4] {
+        [
+            self.red.extract(pixel).unwrap_or(0),
+            self.green.extract(pixel).unwrap_or(0),
+            self.blue.extract(pixel).unwrap_or(0),
+            self.alpha.extract(pixel).unwrap_or(0xFF),
+        ]
+    }
+}
+
+/// Reads the three color masks following a `BITMAPINFOHEADER` with `BI_BITFIELDS` compression.
+fn read_v1_masks(src: &mut ReadCursor<'_>) -> Result<ColorMasks, BitmapError> {
+    if src.len() < 12 {
+        return Err(BitmapError::Unsupported("missing color masks"));
+    }
+
+    let red = src.read_u32();
+    let green = src.read_u32();
+    let blue = src.read_u32();
+
+    Ok(ColorMasks::new(red, green, blue, 0))
+}
+
+/// Reads the color table (`bmiColors`) following the header.
+///
+/// For bitmaps with more than 8 bits per pixel, the color table is only an optimization hint and is
+/// skipped.
+fn read_color_table(header: &BitmapInfoHeader, src: &mut ReadCursor<'_>) -> Result<Vec<[u8; 4]>, BitmapError> {
+    const MAX_PALETTE_SIZE: u32 = 256;
+
+    let count = if header.clr_used == 0 && header.bit_count <= 8 {
+        1u32.checked_shl(u32::from(header.bit_count))
+            .ok_or(BitmapError::InvalidColorTable)?
     } else {
-        (png::ColorType::Rgb, 3)
+        header.clr_used
     };
 
-    // Per invariants: height * width * dst_n_samples <= 10_000 * 10_000 * 4 < u32::MAX
+    if header.bit_count <= 8 && count > MAX_PALETTE_SIZE {
+        return Err(BitmapError::InvalidColorTable);
+    }
+
+    let count = usize::try_from(count).map_err(|_| BitmapError::InvalidColorTable)?;
+    let size = count.checked_mul(4).ok_or(BitmapError::InvalidColorTable)?;
+
+    if src.len() < size {
+        return Err(BitmapError::InvalidColorTable);
+    }
+
+    if header.bit_count > 8 {
+        src.advance(size);
+        return Ok(Vec::new());
+    }
+
+    // RGBQUAD entries are stored as blue, green, red and a reserved byte.
+    let palette = (0..count)
+        .map(|_| {
+            let [blue, green, red, _] = src.read_array::<4>();
+            [red, green, blue, 0xFF]
+        })
+        .collect();
+
+    Ok(palette)
+}
+
+/// Pixel layout of a DIB, determined from its header.
+enum PixelFormat {
+    Indexed(Vec<[u8; 4]>),
+    Bgr,
+    Masks(ColorMasks),
+}
+
+impl PixelFormat {
+    fn palette_color(palette: &[[u8; 4]], index: u8) -> [u8; 4] {
+        // Out-of-range indices are rendered black.
+        palette.get(usize::from(index)).copied().unwrap_or([0, 0, 0, 0xFF])
+    }
+}
+
+/// Decodes the pixels of a DIB into a top-down RGBA buffer.
+fn decode_pixels(header: &BitmapInfoHeader, format: &PixelFormat, src_bitmap: &[u8]) -> Result<Vec<u8>, BitmapError> {
+    let width = header.width();
+    let height = header.height();
+
+    // Per invariants: height * width * 4 <= 10_000 * 10_000 * 4 < u32::MAX
     #[allow(clippy::arithmetic_side_effects)]
-    let dst_bitmap_len = usize::from(height) * usize::from(width) * dst_n_samples;
+    let dst_bitmap_len = usize::from(height) * usize::from(width) * 4;
 
     // Prevent allocation of huge buffers.
     ensure(dst_bitmap_len <= MAX_BUFFER_SIZE).ok_or(BitmapError::BufferTooBig)?;
 
+    let mut dst_bitmap = vec![0u8; dst_bitmap_len];
+
+    match (header.compression, format) {
+        (BitmapCompression::RLE8 | BitmapCompression::RLE4, PixelFormat::Indexed(palette)) => {
+            decode_rle(header, palette, src_bitmap, &mut dst_bitmap);
+            return Ok(dst_bitmap);
+        }
+        (BitmapCompression::RLE8 | BitmapCompression::RLE4, _) => {
+            unreachable!("RLE bitmaps are paletted, as ensured by header validation")
+        }
+        _ => {}
+    }
+
+    let src_stride = rgb_bmp_stride(width, header.bit_count);
+
+    // Ignore any data following the bitmap, such as an embedded color profile.
+    let src_bitmap = src_stride
+        .checked_mul(usize::from(height))
+        .and_then(|len| src_bitmap.get(..len))
+        .unwrap_or(src_bitmap);
+
+    // DIB may be encoded bottom-up, but the format we target, PNG, is top-down.
     let mut rows_normal;
     let mut rows_reversed;
 
-    let rows: &mut dyn Iterator<Item = &[u8]> = if should_flip_vertically {
+    let rows: &mut dyn Iterator<Item = &[u8]> = if header.is_bottom_up() {
         rows_reversed = src_bitmap.chunks_exact(src_stride).rev();
         &mut rows_reversed
     } else {
@@ -544,53 +687,242 @@ fn bgra_to_top_down_rgba(
         &mut rows_normal
     };
 
-    // DIB stores BGRA colors while PNG uses RGBA.
-    // DIBv1 (CF_DIB) does not have alpha channel, and the fourth byte is always set to 0xFF.
-    // DIBv5 (CF_DIBV5) supports alpha channel, so we should preserve it if it is present.
-    let transform: fn((&mut [u8], &[u8])) = match (header.bit_count, dst_color_type) {
-        (24 | 32, png::ColorType::Rgb) => |(pixel_out, pixel_in)| {
-            pixel_out[0] = pixel_in[2];
-            pixel_out[1] = pixel_in[1];
-            pixel_out[2] = pixel_in[0];
-        },
-        (24, png::ColorType::Rgba) => |(pixel_out, pixel_in)| {
-            pixel_out[0] = pixel_in[2];
-            pixel_out[1] = pixel_in[1];
-            pixel_out[2] = pixel_in[0];
-            pixel_out[3] = 0xFF;
-        },
-        (32, png::ColorType::Rgba) => |(pixel_out, pixel_in)| {
-            pixel_out[0] = pixel_in[2];
-            pixel_out[1] = pixel_in[1];
-            pixel_out[2] = pixel_in[0];
-            pixel_out[3] = pixel_in[3];
-        },
-        _ => unreachable!("possible values are restricted by header validation and logic above"),
-    };
-
-    // Per invariants: width * dst_n_samples <= 10_000 * 4 < u32::MAX
+    // Per invariants: width * 4 <= 10_000 * 4 < u32::MAX
     #[allow(clippy::arithmetic_side_effects)]
-    let dst_stride = usize::from(width) * dst_n_samples;
+    let dst_stride = usize::from(width) * 4;
 
-    let mut dst_bitmap = vec![0u8; dst_bitmap_len];
+    for (dst_row, src_row) in dst_bitmap.chunks_exact_mut(dst_stride).zip(rows) {
+        let dst_pixels = dst_row.chunks_exact_mut(4);
+
+        match format {
+            PixelFormat::Indexed(palette) => {
+                let indices = src_row.iter().flat_map(|byte| unpack_indices(*byte, header.bit_count));
+                for (dst_pixel, index) in dst_pixels.zip(indices) {
+                    dst_pixel.copy_from_slice(&PixelFormat::palette_color(palette, index));
+                }
+            }
+            PixelFormat::Bgr => {
+                for (dst_pixel, src_pixel) in dst_pixels.zip(src_row.chunks_exact(3)) {
+                    dst_pixel.copy_from_slice(&[src_pixel[2], src_pixel[1], src_pixel[0], 0xFF]);
+                }
+            }
+            PixelFormat::Masks(masks) if header.bit_count == 16 => {
+                for (dst_pixel, src_pixel) in dst_pixels.zip(src_row.chunks_exact(2)) {
+                    let pixel = u32::from(u16::from_le_bytes([src_pixel[0], src_pixel[1]]));
+                    dst_pixel.copy_from_slice(&masks.rgba(pixel));
+                }
+            }
+            PixelFormat::Masks(masks) => {
+                for (dst_pixel, src_pixel) in dst_pixels.zip(src_row.chunks_exact(4)) {
+                    let pixel = u32::from_le_bytes([src_pixel[0], src_pixel[1], src_pixel[2], src_pixel[3]]);
+                    dst_pixel.copy_from_slice(&masks.rgba(pixel));
+                }
+            }
+        }
+    }
+
+    Ok(dst_bitmap)
+}
+
+/// Splits a byte of a paletted bitmap into color indices, leftmost pixel first.
+fn unpack_indices(byte: u8, bit_count: u16) -> impl Iterator<Item = u8> {
+    let (count, bits, mask): (u8, u8, u8) = match bit_count {
+        1 => (8, 1, 0b1),
+        4 => (2, 4, 0b1111),
+        _ => (1, 8, 0xFF),
+    };
 
-    dst_bitmap
-        .chunks_exact_mut(dst_stride)
-        .zip(rows)
-        .for_each(|(dst_row, src_row)| {
-            let dst_pixels = dst_row.chunks_exact_mut(dst_n_samples);
-            let src_pixels = src_row.chunks_exact(src_n_samples);
-            dst_pixels.zip(src_pixels).for_each(transform);
-        });
-
-    Ok(PngEncoderContext {
-        bitmap: dst_bitmap,
-        width,
-        height,
-        color_type: dst_color_type,
+    (1..=count).map(move |position| {
+        // No side effects, because position * bits <= 8.
+        #[allow(clippy::arithmetic_side_effects)]
+        let shift = 8 - position * bits;
+        byte.checked_shr(u32::from(shift)).unwrap_or(0) & mask
     })
 }
 
+/// Decodes a `BI_RLE8` or `BI_RLE4` compressed bitmap into a top-down RGBA buffer.
+///
+/// Pixels skipped by delta and end-of-line escapes are left transparent.
+fn decode_rle(header: &BitmapInfoHeader, palette: &[[u8; 4]], mut src: &[u8], dst_bitmap: &mut [u8]) {
+    let width = usize::from(header.width());
+    let height = usize::from(header.height());
+    let is_rle4 = header.compression == BitmapCompression::RLE4;
+
+    let mut x = 0usize;
+    // Rows are counted from the bottom.
+    let mut y = 0usize;
+
+    let mut put = |x: usize, y: usize, index: u8| {
+        if x >= width || y >= height {
+            return;
+        }
+
+        // Per invariants and the above check: ((height - 1 - y) * width + x) * 4 < height * width * 4 < u32::MAX
+        #[allow(clippy::arithmetic_side_effects)]
+        let offset = ((height - 1 - y) * width + x) * 4;
+
+        dst_bitmap[offset..][..4].copy_from_slice(&PixelFormat::palette_color(palette, index));
+    };
+
+    while let [first, second, rest @ ..] = src {
+        src = rest;
+
+        match (*first, *second) {
+            // Encoded mode: a run of pixels
+            (count @ 1.., value) => {
+                for i in 0..count {
+                    let index = match (is_rle4, i % 2) {
+                        (false, _) => value,
+                        (true, 0) => value >> 4,
+                        (true, _) => value & 0x0F,
+                    };
+                    put(x, y, index);
+                    x = x.saturating_add(1);
+                }
+            }
+            // End of line
+            (0, 0) => {
+                x = 0;
+                y = y.saturating_add(1);
+            }
+            // End of bitmap
+            (0, 1) => break,
+            // Delta
+            (0, 2) => {
+                let [dx, dy, rest @ ..] = src else {
+                    break;
+                };
+                x = x.saturating_add(usize::from(*dx));
+                y = y.saturating_add(usize::from(*dy));
+                src = rest;
+            }
+            // Absolute mode: literal pixels, padded to a 16-bit boundary
+            (0, count) => {
+                let count = usize::from(count);
+
+                // No side effects, because count <= 255.
+                #[allow(clippy::arithmetic_side_effects)]
+                let (byte_count, padded_byte_count) = if is_rle4 {
+                    ((count + 1) / 2, (count + 3) / 4 * 2)
+                } else {
+                    (count, (count + 1) / 2 * 2)
+                };
+
+                let Some(bytes) = src.get(..byte_count) else {
+                    break;
+                };
+
+                let indices: &mut dyn Iterator<Item = u8> = if is_rle4 {
+                    &mut bytes.iter().flat_map(|byte| [byte >> 4, byte & 0x0F])
+                } else {
+                    &mut bytes.iter().copied()
+                };
+
+                for index in indices.take(count) {
+                    put(x, y, index);
+                    x = x.saturating_add(1);
+                }
+
+                src = src.get(padded_byte_count..).unwrap_or_default();
+            }
+        }
+    }
+}
+
+/// Converts the pixels of a DIBv5 to sRGB, according to its color space.
+///
+/// Embedded profiles which can't be parsed are ignored, assuming sRGB, rather than failing the
+/// whole conversion.
+fn convert_to_srgb(header: &BitmapV5Header, input: &[u8], rgba: &mut [u8]) {
+    let profile = match header.color_space {
+        ColorSpace::CALIBRATED_RGB => calibrated_rgb_profile(header),
+        ColorSpace::PROFILE_EMBEDDED => {
+            // bV5ProfileData is the offset of the profile from the beginning of the header.
+            let profile = usize::try_from(header.profile_data)
+                .ok()
+                .zip(usize::try_from(header.profile_size).ok())
+                .and_then(|(offset, size)| input.get(offset..)?.get(..size));
+
+            profile.and_then(|profile| qcms::Profile::new_from_slice(profile, false))
+        }
+        _ => None,
+    };
+
+    let Some(profile) = profile else {
+        return;
+    };
+
+    let intent = match header.intent {
+        BitmapIntent::LCS_GM_ABS_COLORIMETRIC => qcms::Intent::AbsoluteColorimetric,
+        BitmapIntent::LCS_GM_BUSINESS => qcms::Intent::Saturation,
+        BitmapIntent::LCS_GM_GRAPHICS => qcms::Intent::RelativeColorimetric,
+        _ => qcms::Intent::Perceptual,
+    };
+
+    let srgb = qcms::Profile::new_sRGB();
+
+    if let Some(transform) = qcms::Transform::new(&profile, &srgb, qcms::DataType::RGBA8, intent) {
+        transform.apply(rgba);
+    }
+}
+
+/// Builds a color profile from the endpoints and gamma values of a `LCS_CALIBRATED_RGB` bitmap.
+///
+/// Returns `None` when the endpoints are unset, in which case sRGB is assumed.
+fn calibrated_rgb_profile(header: &BitmapV5Header) -> Option<Box<qcms::Profile>> {
+    // D65, the white point of sRGB
+    const WHITE_POINT: qcms::CIE_xyY = qcms::CIE_xyY {
+        x: 0.3127,
+        y: 0.3290,
+        Y: 1.0,
+    };
+
+    fn to_xyy(xyz: &Ciexyz) -> Option<qcms::CIE_xyY> {
+        // FXPT2DOT30 values, signed fixed-point numbers with 30 fractional bits
+        let fixed = |value: u32| f64::from(i32::from_ne_bytes(value.to_ne_bytes())) / f64::from(1u32 << 30);
+
+        let (x, y, z) = (fixed(xyz.x), fixed(xyz.y), fixed(xyz.z));
+        let sum = x + y + z;
+
+        (sum > 0.0).then(|| qcms::CIE_xyY {
+            x: x / sum,
+            y: y / sum,
+            Y: y,
+        })
+    }
+
+    fn to_gamma(value: u32) -> f32 {
+        // Unsigned 16.16 fixed-point numbers; unset values fall back on the approximate sRGB gamma.
+        if value == 0 {
+            2.2
+        } else {
+            let [int_high, int_low, fraction_high, fraction_low] = value.to_be_bytes();
+            f32::from(u16::from_be_bytes([int_high, int_low]))
+                + f32::from(u16::from_be_bytes([fraction_high, fraction_low])) / 65536.0
+        }
+    }
+
+    let primaries = qcms::CIE_xyYTRIPLE {
+        red: to_xyy(&header.endpoints.red)?,
+        green: to_xyy(&header.endpoints.green)?,
+        blue: to_xyy(&header.endpoints.blue)?,
+    };
+
+    qcms::Profile::new_rgb_with_gamma_set(
+        WHITE_POINT,
+        primaries,
+        to_gamma(header.gamma_red),
+        to_gamma(header.gamma_green),
+        to_gamma(header.gamma_blue),
+    )
+}
+
+fn rgba_to_rgb(rgba: &[u8]) -> Vec<u8> {
+    rgba.chunks_exact(4)
+        .flat_map(|pixel| [pixel[0], pixel[1], pixel[2]])
+        .collect()
+}
+
 fn encode_png(ctx: &PngEncoderContext) -> Result<Vec<u8>, BitmapError> {
     let mut output: Vec<u8> = Vec::new();
 
@@ -609,34 +941,98 @@ fn encode_png(ctx: &PngEncoderContext) -> Result<Vec<u8>, BitmapError> {
 }
 
 /// Converts `CF_DIB` to PNG.
+///
+/// Paletted (1, 4 and 8 bits per pixel, including `BI_RLE4` and `BI_RLE8` compression), 16, 24
+/// and 32-bit bitmaps are supported, with `BI_BITFIELDS` masks for the latter two.
 pub fn dib_to_png(input: &[u8]) -> Result<Vec<u8>, BitmapError> {
     let mut src = ReadCursor::new(input);
     let header = BitmapInfoHeader::decode(&mut src).map_err(BitmapError::Decode)?;
 
     validate_v1_header(&header)?;
 
-    // We support only uncompressed DIB bitmaps as it is the most common case for clipboard-copied bitmaps.
-    // However, for DIBv1 specifically, BitmapCompression::BITFIELDS is not supported even when the order is BGRA,
-    // because there is an additional variable-sized header holding the color masks that we don’t support yet.
-    const DIBV1_SUPPORTED_COMPRESSION: &[BitmapCompression] = &[BitmapCompression::RGB];
+    // For `BITMAPINFOHEADER`, the color masks are stored right after the header.
+    let masks = match header.compression {
+        BitmapCompression::BITFIELDS => Some(read_v1_masks(&mut src)?),
+        _ => None,
+    };
 
-    if !DIBV1_SUPPORTED_COMPRESSION.contains(&header.compression) {
-        return Err(BitmapError::Unsupported("unsupported compression"));
-    }
+    let format = pixel_format(&header, masks, &mut src)?;
+    let rgba = decode_pixels(&header, &format, src.remaining())?;
 
-    let png_ctx = bgra_to_top_down_rgba(&header, src.remaining(), false)?;
-    encode_png(&png_ctx)
+    encode_png(&PngEncoderContext {
+        bitmap: rgba_to_rgb(&rgba),
+        width: header.width(),
+        height: header.height(),
+        color_type: png::ColorType::Rgb,
+    })
 }
 
-/// Converts `CF_DIB` to PNG.
+/// Converts `CF_DIBV5` to PNG.
+///
+/// In addition to the bitmaps supported by [`dib_to_png`], the alpha channel is preserved, and
+/// bitmaps with calibrated color spaces or embedded ICC profiles are converted to sRGB.
 pub fn dibv5_to_png(input: &[u8]) -> Result<Vec<u8>, BitmapError> {
     let mut src = ReadCursor::new(input);
     let header = BitmapV5Header::decode(&mut src).map_err(BitmapError::Decode)?;
 
     validate_v5_header(&header)?;
 
-    let png_ctx = bgra_to_top_down_rgba(&header.v1, src.remaining(), true)?;
-    encode_png(&png_ctx)
+    let masks = match header.v1.compression {
+        BitmapCompression::BITFIELDS => {
+            // Windows repeats the masks after the header when synthesizing `CF_DIBV5` from `CF_DIB`.
+            let mut repeated_masks = [0u8; 12];
+            repeated_masks[..4].copy_from_slice(&header.red_mask.to_le_bytes());
+            repeated_masks[4..8].copy_from_slice(&header.green_mask.to_le_bytes());
+            repeated_masks[8..].copy_from_slice(&header.blue_mask.to_le_bytes());
+
+            let image_size = rgb_bmp_stride(header.v1.width(), header.v1.bit_count)
+                .checked_mul(usize::from(header.v1.height()))
+                .ok_or(BitmapError::InvalidSize)?;
+
+            if src.remaining().starts_with(&repeated_masks)
+                && src.len() >= image_size.checked_add(12).ok_or(BitmapError::InvalidSize)?
+            {
+                src.advance(12);
+            }
+
+            Some(ColorMasks::new(
+                header.red_mask,
+                header.green_mask,
+                header.blue_mask,
+                header.alpha_mask,
+            ))
+        }
+        _ => None,
+    };
+
+    let format = pixel_format(&header.v1, masks, &mut src)?;
+    let mut rgba = decode_pixels(&header.v1, &format, src.remaining())?;
+
+    convert_to_srgb(&header, input, &mut rgba);
+
+    encode_png(&PngEncoderContext {
+        bitmap: rgba,
+        width: header.v1.width(),
+        height: header.v1.height(),
+        color_type: png::ColorType::Rgba,
+    })
+}
+
+/// Determines the pixel format, reading the color table if any.
+fn pixel_format(
+    header: &BitmapInfoHeader,
+    masks: Option<ColorMasks>,
+    src: &mut ReadCursor<'_>,
+) -> Result<PixelFormat, BitmapError> {
+    let palette = read_color_table(header, src)?;
+
+    let format = match header.bit_count {
+        1 | 4 | 8 => PixelFormat::Indexed(palette),
+        24 => PixelFormat::Bgr,
+        _ => PixelFormat::Masks(masks.unwrap_or_else(|| ColorMasks::for_rgb(header.bit_count))),
+    };
+
+    Ok(format)
 }
 
 fn top_down_rgba_to_bottom_up_bgra(
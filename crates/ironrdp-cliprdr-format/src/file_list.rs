@@ -0,0 +1,154 @@
+//! Conversions between the `FileGroupDescriptorW` registered format and `text/uri-list`
+//! ([RFC 2483]), the format used by most desktop environments to copy files.
+//!
+//! Only `file://` URIs of local files are supported.
+//!
+//! [RFC 2483]: https://www.rfc-editor.org/rfc/rfc2483#section-5
+
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use ironrdp_cliprdr::file_transfer::LocalFileList;
+use ironrdp_cliprdr::pdu::PackedFileList;
+
+/// Lists the local files of `text/uri-list` data, along with the contents of the copied
+/// directories, see [`LocalFileList::from_paths`].
+///
+/// The `FileGroupDescriptorW` data is then obtained with [`LocalFileList::format_data`].
+pub fn uri_list_to_file_list(uri_list: &[u8]) -> io::Result<LocalFileList> {
+    LocalFileList::from_paths(uri_list_to_paths(uri_list))
+}
+
+/// Builds `text/uri-list` data for remote files pasted into `directory`, for instance with
+/// `FileDownload`.
+///
+/// Only the top-level files and directories are listed, the other ones being contained in
+/// directories. Names escaping `directory` are refused.
+pub fn file_list_to_uri_list(list: &PackedFileList, directory: &Path) -> io::Result<String> {
+    let mut paths = Vec::new();
+
+    for file in &list.files {
+        if file.name.contains(['\\', '/']) {
+            continue;
+        }
+
+        let mut components = Path::new(&file.name).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(name)), None) if !file.name.contains(':') => paths.push(directory.join(name)),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid file name in clipboard file list: {}", file.name),
+                ))
+            }
+        }
+    }
+
+    Ok(paths_to_uri_list(paths))
+}
+
+/// Parses the local paths of `text/uri-list` data, skipping comments and URIs other than
+/// `file://` ones.
+pub fn uri_list_to_paths(uri_list: &[u8]) -> Vec<PathBuf> {
+    uri_list
+        .split(|byte| *byte == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .filter(|line| !line.is_empty() && !line.starts_with(b"#"))
+        .filter_map(|line| line.strip_prefix(b"file://"))
+        .filter_map(|line| {
+            // Only local files are supported, the host part is ignored
+            let path = &line[line.iter().position(|byte| *byte == b'/')?..];
+            path_from_uri_path(percent_decode(path))
+        })
+        .collect()
+}
+
+/// Builds `text/uri-list` data, with CRLF line endings, for local paths.
+pub fn paths_to_uri_list<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> String {
+    paths
+        .into_iter()
+        .map(|path| {
+            let mut uri = file_uri(path.as_ref());
+            uri.push_str("\r\n");
+            uri
+        })
+        .collect()
+}
+
+/// Returns the `file://` URI of a local path.
+pub fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+
+    for byte in uri_path_from_path(path) {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            uri.push(char::from(byte));
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+
+    uri
+}
+
+#[cfg(unix)]
+fn uri_path_from_path(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt as _;
+
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn uri_path_from_path(path: &Path) -> Vec<u8> {
+    let path = path.to_string_lossy().replace('\\', "/");
+
+    // Drive letters are preceded by a slash, e.g. `file:///C:/Users`
+    if path.starts_with('/') {
+        path.into_bytes()
+    } else {
+        format!("/{path}").into_bytes()
+    }
+}
+
+#[cfg(unix)]
+fn path_from_uri_path(path: Vec<u8>) -> Option<PathBuf> {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt as _;
+
+    Some(PathBuf::from(OsString::from_vec(path)))
+}
+
+#[cfg(not(unix))]
+fn path_from_uri_path(path: Vec<u8>) -> Option<PathBuf> {
+    let path = String::from_utf8(path).ok()?;
+
+    let path = match path.strip_prefix('/') {
+        Some(rest) if rest.get(1..2) == Some(":") => rest,
+        _ => &path,
+    };
+
+    Some(PathBuf::from(path))
+}
+
+fn percent_decode(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        if let (b'%', [high, low, tail @ ..]) = (byte, tail) {
+            let decoded = core::str::from_utf8(&[*high, *low])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+            if let Some(decoded) = decoded {
+                output.push(decoded);
+                rest = tail;
+                continue;
+            }
+        }
+
+        output.push(byte);
+        rest = tail;
+    }
+
+    output
+}
@@ -4,4 +4,7 @@
 )]
 
 pub mod bitmap;
+pub mod file_list;
 pub mod html;
+pub mod rtf;
+pub mod text;
@@ -0,0 +1,360 @@
+//! Conversions between the `Rich Text Format` registered format and plain text.
+//!
+//! Only the text content of RTF documents is extracted; formatting, pictures and embedded objects
+//! are dropped.
+
+use thiserror::Error;
+
+use crate::text::{to_lf, CodePage};
+
+#[derive(Debug, Error)]
+pub enum RtfError {
+    #[error("invalid RTF header")]
+    InvalidHeader,
+}
+
+/// Destinations which do not hold document text.
+const IGNORED_DESTINATIONS: &[&[u8]] = &[
+    b"author",
+    b"buptim",
+    b"colortbl",
+    b"comment",
+    b"creatim",
+    b"datastore",
+    b"doccomm",
+    b"fldinst",
+    b"filetbl",
+    b"fonttbl",
+    b"footer",
+    b"footerf",
+    b"footerl",
+    b"footerr",
+    b"footnote",
+    b"generator",
+    b"header",
+    b"headerf",
+    b"headerl",
+    b"headerr",
+    b"info",
+    b"keywords",
+    b"latentstyles",
+    b"listoverridetable",
+    b"listtable",
+    b"object",
+    b"operator",
+    b"pict",
+    b"printim",
+    b"private",
+    b"revtbl",
+    b"rsidtbl",
+    b"stylesheet",
+    b"subject",
+    b"themedata",
+    b"title",
+    b"xmlnstbl",
+];
+
+#[derive(Debug, Clone, Copy)]
+struct GroupState {
+    ignored: bool,
+    /// Number of fallback characters following a `\uN` control word (`\ucN`).
+    unicode_skip: usize,
+}
+
+/// Extracts plain text, with LF line endings, from a `Rich Text Format` document.
+///
+/// The data is read up to the end of the outermost group, or to the first NUL byte. Text encoded
+/// in a code page which is not supported by [`crate::text`] is decoded as Windows-1252.
+pub fn rtf_to_plain_text(input: &[u8]) -> Result<String, RtfError> {
+    if !input.starts_with(b"{\\rtf") {
+        return Err(RtfError::InvalidHeader);
+    }
+
+    let mut output = Output {
+        text: String::new(),
+        bytes: Vec::new(),
+        high_surrogate: None,
+        code_page: CodePage::WINDOWS_1252,
+    };
+
+    let mut stack = Vec::new();
+    let mut state = GroupState {
+        ignored: false,
+        unicode_skip: 1,
+    };
+    // Fallback characters of the last `\uN` control word left to skip
+    let mut skip = 0usize;
+    // Whether the next control word is the first one of a group, i.e. possibly a destination
+    let mut group_start = false;
+
+    let mut cursor = input;
+
+    while let Some((&byte, rest)) = cursor.split_first() {
+        cursor = rest;
+
+        let first_in_group = core::mem::take(&mut group_start);
+
+        match byte {
+            0 => break,
+            b'{' => {
+                stack.push(state);
+                group_start = true;
+                skip = 0;
+            }
+            b'}' => {
+                match stack.pop() {
+                    Some(outer) => state = outer,
+                    None => break,
+                }
+                if stack.is_empty() {
+                    break;
+                }
+                skip = 0;
+            }
+            b'\\' => {
+                let Some((&next, rest)) = cursor.split_first() else {
+                    break;
+                };
+
+                if next.is_ascii_alphabetic() {
+                    let (word, parameter, rest) = read_control_word(cursor);
+                    cursor = rest;
+
+                    if first_in_group && IGNORED_DESTINATIONS.contains(&word) {
+                        state.ignored = true;
+                    }
+
+                    if consume_fallback(&mut skip) || state.ignored {
+                        if word == b"bin" {
+                            cursor = skip_binary(cursor, parameter);
+                        }
+                        continue;
+                    }
+
+                    match word {
+                        b"ansicpg" => {
+                            let candidate = parameter.and_then(|p| u16::try_from(p).ok()).map(CodePage);
+                            if let Some(candidate) = candidate.filter(|candidate| candidate.is_supported()) {
+                                output.code_page = candidate;
+                            }
+                        }
+                        b"uc" => {
+                            state.unicode_skip = parameter.and_then(|p| usize::try_from(p).ok()).unwrap_or(1);
+                        }
+                        b"u" => {
+                            if let Some(parameter) = parameter {
+                                // Parameters are signed 16-bit integers, negative for code units above 0x7FFF
+                                let unit = i16::try_from(parameter)
+                                    .map(|parameter| u16::from_ne_bytes(parameter.to_ne_bytes()))
+                                    .or_else(|_| u16::try_from(parameter))
+                                    .unwrap_or_else(|_| u16::from(b'?'));
+                                output.push_utf16(unit);
+                                skip = state.unicode_skip;
+                            }
+                        }
+                        b"bin" => cursor = skip_binary(cursor, parameter),
+                        _ => {
+                            if let Some(text) = control_word_text(word) {
+                                output.push_str(text);
+                            }
+                        }
+                    }
+                } else {
+                    cursor = rest;
+
+                    match next {
+                        b'*' if first_in_group => state.ignored = true,
+                        b'\'' => {
+                            let hex = cursor.get(..2).and_then(|hex| core::str::from_utf8(hex).ok());
+                            let value = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                            if let Some(value) = value {
+                                cursor = &cursor[2..];
+                                if !consume_fallback(&mut skip) && !state.ignored {
+                                    output.push_byte(value);
+                                }
+                            }
+                        }
+                        _ if consume_fallback(&mut skip) || state.ignored => {}
+                        b'\\' | b'{' | b'}' => output.push_char(char::from(next)),
+                        b'~' => output.push_char('\u{a0}'),
+                        b'_' => output.push_char('\u{2011}'),
+                        // Paragraph mark, as in `\par`
+                        b'\r' | b'\n' => output.push_char('\n'),
+                        // Optional hyphen and other control symbols are not rendered
+                        _ => {}
+                    }
+                }
+            }
+            // Line endings are not significant in RTF
+            b'\r' | b'\n' => {}
+            _ if consume_fallback(&mut skip) || state.ignored => {}
+            _ if byte.is_ascii() => output.push_char(char::from(byte)),
+            _ => output.push_byte(byte),
+        }
+    }
+
+    output.flush();
+
+    Ok(to_lf(&output.text))
+}
+
+/// Wraps plain text into a minimal `Rich Text Format` document.
+pub fn plain_text_to_rtf(text: &str) -> String {
+    let mut output = String::from("{\\rtf1\\ansi\\ansicpg1252\\deff0{\\fonttbl{\\f0\\fnil Segoe UI;}}\\uc1\\f0 ");
+
+    for c in to_lf(text).chars() {
+        match c {
+            '\\' | '{' | '}' => {
+                output.push('\\');
+                output.push(c);
+            }
+            '\n' => output.push_str("\\par\n"),
+            '\t' => output.push_str("\\tab "),
+            _ if c.is_ascii_control() => {}
+            _ if c.is_ascii() => output.push(c),
+            _ => {
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    let parameter = i16::from_ne_bytes(unit.to_ne_bytes());
+                    output.push_str(&format!("\\u{parameter}?"));
+                }
+            }
+        }
+    }
+
+    output.push('}');
+
+    output
+}
+
+/// Reads a control word, starting right after the backslash, returning its name, its parameter
+/// and the remaining input.
+fn read_control_word(input: &[u8]) -> (&[u8], Option<i32>, &[u8]) {
+    let name_length = input.iter().take_while(|byte| byte.is_ascii_alphabetic()).count();
+    let (word, mut rest) = input.split_at(name_length);
+
+    let negative = rest.first() == Some(&b'-');
+    let digits_start = usize::from(negative);
+    let digits_length = rest[digits_start..]
+        .iter()
+        .take_while(|byte| byte.is_ascii_digit())
+        .count();
+
+    let parameter = if digits_length > 0 {
+        let digits_end = digits_start.saturating_add(digits_length);
+        let parameter = core::str::from_utf8(&rest[..digits_end])
+            .ok()
+            .and_then(|digits| digits.parse::<i32>().ok());
+        rest = &rest[digits_end..];
+        parameter
+    } else {
+        None
+    };
+
+    // A space delimiting the control word is part of it
+    if let Some(tail) = rest.strip_prefix(b" ") {
+        rest = tail;
+    }
+
+    (word, parameter, rest)
+}
+
+fn skip_binary(input: &[u8], length: Option<i32>) -> &[u8] {
+    let length = length.and_then(|length| usize::try_from(length).ok()).unwrap_or(0);
+    input.get(length..).unwrap_or_default()
+}
+
+/// Consumes one of the fallback characters following a `\uN` control word, if any.
+fn consume_fallback(skip: &mut usize) -> bool {
+    match skip.checked_sub(1) {
+        Some(remaining) => {
+            *skip = remaining;
+            true
+        }
+        None => false,
+    }
+}
+
+fn control_word_text(word: &[u8]) -> Option<&'static str> {
+    let text = match word {
+        b"par" | b"line" | b"row" | b"sect" | b"page" => "\n",
+        b"tab" | b"cell" => "\t",
+        b"emdash" => "\u{2014}",
+        b"endash" => "\u{2013}",
+        b"emspace" | b"enspace" | b"qmspace" => " ",
+        b"bullet" => "\u{2022}",
+        b"lquote" => "\u{2018}",
+        b"rquote" => "\u{2019}",
+        b"ldblquote" => "\u{201c}",
+        b"rdblquote" => "\u{201d}",
+        _ => return None,
+    };
+
+    Some(text)
+}
+
+/// Decoded text, accumulating the bytes and UTF-16 code units which need to be combined.
+struct Output {
+    text: String,
+    /// Bytes of `\'hh` escapes and raw 8-bit characters, in the document code page
+    bytes: Vec<u8>,
+    high_surrogate: Option<u16>,
+    code_page: CodePage,
+}
+
+impl Output {
+    fn push_str(&mut self, text: &str) {
+        self.flush();
+        self.text.push_str(text);
+    }
+
+    fn push_char(&mut self, c: char) {
+        self.flush();
+        self.text.push(c);
+    }
+
+    fn push_byte(&mut self, byte: u8) {
+        self.flush_utf16();
+        self.bytes.push(byte);
+    }
+
+    fn push_utf16(&mut self, unit: u16) {
+        self.flush_bytes();
+
+        let units = match self.high_surrogate.take() {
+            Some(high) => vec![high, unit],
+            None if (0xD800..=0xDBFF).contains(&unit) => {
+                self.high_surrogate = Some(unit);
+                return;
+            }
+            None => vec![unit],
+        };
+
+        self.text
+            .extend(char::decode_utf16(units).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)));
+    }
+
+    fn flush(&mut self) {
+        self.flush_bytes();
+        self.flush_utf16();
+    }
+
+    fn flush_bytes(&mut self) {
+        if self.bytes.is_empty() {
+            return;
+        }
+
+        // The code page is supported, as checked when reading `\ansicpg`
+        if let Ok(text) = crate::text::cf_text_to_string(&self.bytes, self.code_page) {
+            self.text.push_str(&text);
+        }
+
+        self.bytes.clear();
+    }
+
+    fn flush_utf16(&mut self) {
+        if self.high_surrogate.take().is_some() {
+            self.text.push(char::REPLACEMENT_CHARACTER);
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! Conversions for the text clipboard formats: `CF_UNICODETEXT`, `CF_TEXT` and `CF_OEMTEXT`.
+//!
+//! Text on the Windows clipboard is NUL-terminated and uses CRLF line endings, while the decoded
+//! strings returned by this module are using LF line endings and carry no terminator.
+//!
+//! `CF_TEXT` and `CF_OEMTEXT` are respectively encoded with the ANSI and OEM code pages of the
+//! locale advertised in `CF_LOCALE`, see [`lcid_from_cf_locale`] and [`CodePage`].
+
+mod code_pages;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum TextError {
+    #[error("unsupported code page {0}")]
+    UnsupportedCodePage(u16),
+    #[error("invalid CF_LOCALE format")]
+    InvalidLocale,
+}
+
+/// Windows code page identifier, used by `CF_TEXT` and `CF_OEMTEXT`.
+///
+/// Single-byte code pages and UTF-8 are supported; multi-byte code pages such as Shift JIS (932)
+/// are not, and `CF_UNICODETEXT` should be used instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CodePage(pub u16);
+
+impl CodePage {
+    /// OEM United States, the OEM code page used when no locale is known.
+    pub const IBM437: Self = Self(437);
+    /// OEM Multilingual Latin 1.
+    pub const IBM850: Self = Self(850);
+    /// ANSI Latin 1, the ANSI code page used when no locale is known.
+    pub const WINDOWS_1252: Self = Self(1252);
+    pub const UTF8: Self = Self(65001);
+
+    /// Returns the ANSI code page of a locale, as used by `CF_TEXT`.
+    pub fn ansi_for_locale(lcid: u32) -> Self {
+        Self(code_pages_for_locale(lcid).0)
+    }
+
+    /// Returns the OEM code page of a locale, as used by `CF_OEMTEXT`.
+    pub fn oem_for_locale(lcid: u32) -> Self {
+        Self(code_pages_for_locale(lcid).1)
+    }
+
+    /// Returns `true` if text can be converted to and from this code page.
+    pub fn is_supported(self) -> bool {
+        self == Self::UTF8 || code_pages::upper_half(self.0).is_some()
+    }
+}
+
+/// Returns the default (ANSI, OEM) code pages of a Windows locale.
+fn code_pages_for_locale(lcid: u32) -> (u16, u16) {
+    const LANG_ENGLISH_US: u32 = 0x0409;
+    const SUBLANG_SERBIAN_CYRILLIC: [u32; 2] = [0x0C1A, 0x1C1A];
+    const SUBLANG_CHINESE_TRADITIONAL: [u32; 3] = [0x0404, 0x0C04, 0x1404];
+
+    let langid = lcid & 0xFFFF;
+
+    match langid & 0x03FF {
+        // Primary language IDs, see [MS-LCID]
+        0x09 if langid == LANG_ENGLISH_US => (1252, 437),
+        0x1A if SUBLANG_SERBIAN_CYRILLIC.contains(&langid) => (1251, 855),
+        0x04 if SUBLANG_CHINESE_TRADITIONAL.contains(&langid) => (950, 950),
+        // Czech, Hungarian, Polish, Romanian, Croatian, Slovak, Albanian, Slovenian
+        0x05 | 0x0E | 0x15 | 0x18 | 0x1A | 0x1B | 0x1C | 0x24 => (1250, 852),
+        // Bulgarian, Russian, Ukrainian, Belarusian, Macedonian, Kazakh, Kyrgyz, Tatar, Mongolian
+        0x02 | 0x19 | 0x22 | 0x23 | 0x2F | 0x3F | 0x40 | 0x44 | 0x50 => (1251, 866),
+        0x08 => (1253, 737),
+        // Turkish, Azerbaijani
+        0x1F | 0x2C => (1254, 857),
+        0x0D => (1255, 862),
+        // Arabic, Urdu, Persian
+        0x01 | 0x20 | 0x29 => (1256, 720),
+        // Estonian, Latvian, Lithuanian
+        0x25..=0x27 => (1257, 775),
+        0x2A => (1258, 1258),
+        0x1E => (874, 874),
+        0x11 => (932, 932),
+        0x04 => (936, 936),
+        0x12 => (949, 949),
+        _ => (1252, 850),
+    }
+}
+
+/// Reads the locale identifier (LCID) from `CF_LOCALE` data.
+pub fn lcid_from_cf_locale(input: &[u8]) -> Result<u32, TextError> {
+    match input {
+        [a, b, c, d, ..] => Ok(u32::from_le_bytes([*a, *b, *c, *d])),
+        _ => Err(TextError::InvalidLocale),
+    }
+}
+
+/// Encodes a locale identifier (LCID) as `CF_LOCALE` data.
+pub fn lcid_to_cf_locale(lcid: u32) -> Vec<u8> {
+    lcid.to_le_bytes().to_vec()
+}
+
+/// Converts `CF_UNICODETEXT` data to a string with LF line endings.
+///
+/// The data is read up to the first NUL character, ignoring the padding which often follows it.
+/// Invalid UTF-16 sequences are replaced with U+FFFD.
+pub fn cf_unicodetext_to_string(input: &[u8]) -> String {
+    let units = input
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .take_while(|unit| *unit != 0)
+        .collect::<Vec<_>>();
+
+    to_lf(&String::from_utf16_lossy(&units))
+}
+
+/// Converts a string to NUL-terminated `CF_UNICODETEXT` data with CRLF line endings.
+///
+/// Any text following a NUL character in `text` is dropped, since it would not be read back.
+pub fn string_to_cf_unicodetext(text: &str) -> Vec<u8> {
+    let text = until_nul(text);
+
+    to_crlf(text)
+        .encode_utf16()
+        .chain(core::iter::once(0))
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
+
+/// Converts `CF_TEXT` or `CF_OEMTEXT` data, encoded with `code_page`, to a string with LF line
+/// endings.
+///
+/// The data is read up to the first NUL byte.
+pub fn cf_text_to_string(input: &[u8], code_page: CodePage) -> Result<String, TextError> {
+    let input = input.split(|byte| *byte == 0).next().unwrap_or_default();
+
+    let text = if code_page == CodePage::UTF8 {
+        String::from_utf8_lossy(input).into_owned()
+    } else {
+        let upper_half = code_pages::upper_half(code_page.0).ok_or(TextError::UnsupportedCodePage(code_page.0))?;
+        input
+            .iter()
+            .map(|byte| match byte.checked_sub(0x80) {
+                Some(index) => upper_half[usize::from(index)],
+                None => char::from(*byte),
+            })
+            .collect()
+    };
+
+    Ok(to_lf(&text))
+}
+
+/// Converts a string to NUL-terminated `CF_TEXT` or `CF_OEMTEXT` data with CRLF line endings,
+/// encoded with `code_page`.
+///
+/// Characters which can’t be represented in the code page are replaced with `?`, as Windows does.
+pub fn string_to_cf_text(text: &str, code_page: CodePage) -> Result<Vec<u8>, TextError> {
+    let text = to_crlf(until_nul(text));
+
+    let mut output = if code_page == CodePage::UTF8 {
+        text.into_bytes()
+    } else {
+        let upper_half = code_pages::upper_half(code_page.0).ok_or(TextError::UnsupportedCodePage(code_page.0))?;
+        text.chars()
+            .map(|c| match u8::try_from(c) {
+                Ok(byte) if byte.is_ascii() => byte,
+                _ => upper_half
+                    .iter()
+                    .position(|candidate| *candidate == c)
+                    .and_then(|index| u8::try_from(index).ok())
+                    .map_or(b'?', |index| index | 0x80),
+            })
+            .collect()
+    };
+
+    output.push(0);
+
+    Ok(output)
+}
+
+/// Normalizes line endings to LF, converting both CRLF and lone CR.
+pub fn to_lf(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Normalizes line endings to CRLF, converting both LF and lone CR.
+pub fn to_crlf(text: &str) -> String {
+    to_lf(text).replace('\n', "\r\n")
+}
+
+fn until_nul(text: &str) -> &str {
+    text.split('\0').next().unwrap_or_default()
+}
@@ -17,5 +17,10 @@ mod windows;
 #[cfg(windows)]
 pub use crate::windows::{WinClipboard, WinCliprdrError, WinCliprdrResult, HWND};
 
+#[cfg(target_os = "linux")]
+mod x11;
+#[cfg(target_os = "linux")]
+pub use crate::x11::{X11Clipboard, X11CliprdrError, X11CliprdrResult};
+
 mod stub;
 pub use crate::stub::{StubClipboard, StubCliprdrBackend};
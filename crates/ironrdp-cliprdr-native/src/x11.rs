@@ -0,0 +1,248 @@
+mod clipboard_impl;
+mod cliprdr_backend;
+mod formats;
+
+use std::sync::{mpsc as mpsc_sync, Arc};
+use std::thread::JoinHandle;
+
+use ironrdp_cliprdr::backend::{ClipboardMessageProxy, CliprdrBackend, CliprdrBackendFactory};
+use ironrdp_cliprdr::pdu::{
+    ClipboardFormat, ClipboardGeneralCapabilityFlags, FileContentsRequest, FileContentsResponse, FormatDataRequest,
+    FormatDataResponse, LockDataId,
+};
+use ironrdp_cliprdr_format::bitmap::BitmapError;
+use ironrdp_cliprdr_format::html::HtmlError;
+use thiserror::Error;
+use tracing::error;
+use x11rb::connection::Connection as _;
+use x11rb::errors::{ConnectError, ConnectionError, ReplyError, ReplyOrIdError};
+use x11rb::protocol::xfixes::{ConnectionExt as _, SelectionEventMask};
+use x11rb::protocol::xproto::{
+    Atom, ClientMessageEvent, ConnectionExt as _, CreateWindowAux, EventMask, Window, WindowClass,
+};
+use x11rb::rust_connection::RustConnection;
+
+use self::clipboard_impl::X11ClipboardImpl;
+use self::cliprdr_backend::X11CliprdrBackend;
+
+const BACKEND_CHANNEL_SIZE: usize = 8;
+
+pub type X11CliprdrResult<T> = Result<T, X11CliprdrError>;
+
+#[derive(Debug, Error)]
+pub enum X11CliprdrError {
+    #[error("failed to connect to the X server")]
+    Connect(#[from] ConnectError),
+
+    #[error("X11 connection error")]
+    Connection(#[from] ConnectionError),
+
+    #[error("X11 request failed")]
+    Reply(#[from] ReplyError),
+
+    #[error("failed to create X11 resource")]
+    ReplyOrId(#[from] ReplyOrIdError),
+
+    #[error("failed to start the clipboard thread")]
+    Thread(#[source] std::io::Error),
+
+    #[error("invalid clipboard file list")]
+    FileList,
+
+    #[error("failed to read copied files")]
+    Files(#[source] std::io::Error),
+
+    #[error("failed to convert HTML clipboard data")]
+    Html(#[from] HtmlError),
+
+    #[error("failed to convert bitmap clipboard data")]
+    Bitmap(#[from] BitmapError),
+}
+
+/// Sent from the clipboard backend shim to the clipboard thread
+#[derive(Debug)]
+pub(crate) enum BackendEvent {
+    DowngradedCapabilities(ClipboardGeneralCapabilityFlags),
+    RemoteFormatList(Vec<ClipboardFormat>),
+    FormatDataRequest(FormatDataRequest),
+    FormatDataResponse(FormatDataResponse<'static>),
+    FileContentsRequest(FileContentsRequest),
+    FileContentsResponse(FileContentsResponse<'static>),
+    Lock(LockDataId),
+    Unlock(LockDataId),
+    RemoteRequestsFormatList,
+
+    /// Sent when [`X11Clipboard`] is dropped
+    Shutdown,
+}
+
+x11rb::atom_manager! {
+    pub(crate) Atoms: AtomsCookie {
+        CLIPBOARD,
+        TARGETS,
+        TIMESTAMP,
+        INCR,
+        UTF8_STRING,
+        TEXT_PLAIN_UTF8: b"text/plain;charset=utf-8",
+        TEXT_HTML: b"text/html",
+        IMAGE_PNG: b"image/png",
+        TEXT_URI_LIST: b"text/uri-list",
+        GNOME_COPIED_FILES: b"x-special/gnome-copied-files",
+        IRONRDP_SELECTION: b"_IRONRDP_SELECTION",
+        IRONRDP_WAKEUP: b"_IRONRDP_WAKEUP",
+    }
+}
+
+/// Sends [`BackendEvent`]s to the clipboard thread, waking it up with a client message sent to
+/// the clipboard window.
+#[derive(Debug, Clone)]
+pub(crate) struct BackendEventSender {
+    conn: Arc<RustConnection>,
+    window: Window,
+    wakeup: Atom,
+    tx: mpsc_sync::SyncSender<BackendEvent>,
+}
+
+impl BackendEventSender {
+    pub(crate) fn send(&self, event: BackendEvent) {
+        if self.tx.send(event).is_err() {
+            // Channel is closed, clipboard thread is dead
+            return;
+        }
+
+        let message = ClientMessageEvent::new(32, self.window, self.wakeup, [0u32; 5]);
+        let result = self
+            .conn
+            .send_event(false, self.window, EventMask::NO_EVENT, message)
+            .and_then(|_| self.conn.flush());
+
+        if let Err(err) = result {
+            error!("Failed to wake up the clipboard thread: {}", err);
+        }
+    }
+}
+
+/// X11 RDP client clipboard implementation.
+///
+/// A hidden window owns the `CLIPBOARD` selection while the remote clipboard has data, and
+/// watches other X clients taking the selection over using the XFIXES extension. Remote formats
+/// are offered as MIME targets:
+///
+/// - `CF_UNICODETEXT` as `UTF8_STRING` and `text/plain;charset=utf-8`
+/// - `HTML Format` as `text/html`
+/// - `CF_DIBV5` and `CF_DIB` as `image/png`
+/// - `FileGroupDescriptorW` as `text/uri-list` and `x-special/gnome-copied-files`, pasted files
+///   being downloaded into [`CliprdrBackend::temporary_directory`] first
+///
+/// Large data is transferred with the INCR protocol.
+///
+/// X11 events and backend events are processed on a dedicated thread, which is stopped when
+/// [`X11Clipboard`] is dropped, so the instance should be kept alive during the whole lifetime of
+/// the application. Backend factory returned by [`X11Clipboard::backend_factory`] can be used in
+/// other threads.
+pub struct X11Clipboard {
+    events: BackendEventSender,
+    temporary_directory: String,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl X11Clipboard {
+    /// Creates new clipboard instance, connecting to the X server designated by the `DISPLAY`
+    /// environment variable.
+    pub fn new(message_proxy: impl ClipboardMessageProxy + 'static) -> X11CliprdrResult<Self> {
+        let (conn, screen_num) = x11rb::connect(None)?;
+        let root = conn.setup().roots[screen_num].root;
+
+        let window = conn.generate_id()?;
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            window,
+            root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            x11rb::COPY_FROM_PARENT,
+            &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )?;
+
+        let atoms = Atoms::new(&conn)?.reply()?;
+
+        // Notifies us when other X clients take over the clipboard
+        conn.xfixes_query_version(5, 0)?.reply()?;
+        conn.xfixes_select_selection_input(
+            window,
+            atoms.CLIPBOARD,
+            SelectionEventMask::SET_SELECTION_OWNER
+                | SelectionEventMask::SELECTION_WINDOW_DESTROY
+                | SelectionEventMask::SELECTION_CLIENT_CLOSE,
+        )?;
+        conn.flush()?;
+
+        let conn = Arc::new(conn);
+        let temporary_directory = std::env::temp_dir().join(format!("ironrdp-cliprdr-{}", std::process::id()));
+
+        let (backend_tx, backend_rx) = mpsc_sync::sync_channel(BACKEND_CHANNEL_SIZE);
+
+        let ctx = X11ClipboardImpl::new(
+            Arc::clone(&conn),
+            window,
+            atoms,
+            message_proxy,
+            backend_rx,
+            temporary_directory.clone(),
+        );
+
+        let thread = std::thread::Builder::new()
+            .name("ironrdp-x11-clipboard".to_owned())
+            .spawn(move || ctx.run())
+            .map_err(X11CliprdrError::Thread)?;
+
+        Ok(Self {
+            events: BackendEventSender {
+                conn,
+                window,
+                wakeup: atoms.IRONRDP_WAKEUP,
+                tx: backend_tx,
+            },
+            temporary_directory: temporary_directory.to_string_lossy().into_owned(),
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns clipboard backend factory suitable for making backend instances for `CLIPRDR` SVC.
+    pub fn backend_factory(&self) -> Box<dyn CliprdrBackendFactory + Send> {
+        Box::new(X11CliprdrBackendFactory {
+            events: self.events.clone(),
+            temporary_directory: self.temporary_directory.clone(),
+        })
+    }
+}
+
+impl Drop for X11Clipboard {
+    fn drop(&mut self) {
+        self.events.send(BackendEvent::Shutdown);
+
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("Clipboard thread panicked");
+            }
+        }
+    }
+}
+
+struct X11CliprdrBackendFactory {
+    events: BackendEventSender,
+    temporary_directory: String,
+}
+
+impl CliprdrBackendFactory for X11CliprdrBackendFactory {
+    fn build_cliprdr_backend(&self) -> Box<dyn CliprdrBackend> {
+        Box::new(X11CliprdrBackend::new(
+            self.events.clone(),
+            self.temporary_directory.clone(),
+        ))
+    }
+}
@@ -0,0 +1,870 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc};
+
+use ironrdp_cliprdr::backend::{ClipboardMessage, ClipboardMessageProxy};
+use ironrdp_cliprdr::file_transfer::{FileDownload, FileUpload, LocalFileList};
+use ironrdp_cliprdr::pdu::{
+    ClipboardFormat, ClipboardFormatId, ClipboardGeneralCapabilityFlags, FileContentsResponse, FormatDataResponse,
+    OwnedFormatDataResponse,
+};
+use tracing::{debug, warn};
+use x11rb::connection::{Connection as _, RequestConnection as _};
+use x11rb::protocol::xfixes;
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ChangeWindowAttributesAux, ConnectionExt as _, EventMask, PropMode, Property, PropertyNotifyEvent,
+    SelectionClearEvent, SelectionNotifyEvent, SelectionRequestEvent, Timestamp, Window, SELECTION_NOTIFY_EVENT,
+};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::{CURRENT_TIME, NONE};
+
+use crate::x11::formats::{self, FormatKind};
+use crate::x11::{Atoms, BackendEvent, X11CliprdrError, X11CliprdrResult};
+
+/// Maximum size of the data written to a property at once, larger data is sent with INCR.
+const MAX_CHUNK_SIZE: usize = 256 * 1024;
+/// Room left for the request header when writing a property.
+const CHANGE_PROPERTY_OVERHEAD: usize = 64;
+/// Number of 32-bit units read from a property at once.
+const GET_PROPERTY_LENGTH: u32 = 0x4000;
+
+/// Conversion of the local clipboard, owned by another X client, into one of its targets.
+#[derive(Debug, Clone, Copy)]
+enum Conversion {
+    Targets,
+    Data { kind: FormatKind, target: Atom },
+}
+
+#[derive(Debug)]
+struct Incoming {
+    conversion: Conversion,
+    /// Data received so far when the owner sends it in chunks with INCR.
+    incr: Option<Vec<u8>>,
+}
+
+/// Data sent in chunks with INCR to a requestor of the remote clipboard.
+#[derive(Debug)]
+struct Outgoing {
+    requestor: Window,
+    property: Atom,
+    target: Atom,
+    data: Vec<u8>,
+    position: usize,
+}
+
+/// Local target of a format available on the remote clipboard.
+#[derive(Debug, Clone, Copy)]
+struct RemoteTarget {
+    target: Atom,
+    format: ClipboardFormatId,
+    kind: FormatKind,
+}
+
+#[derive(Debug)]
+enum RemoteData {
+    Data(Vec<u8>),
+    /// Downloaded files, as paths to the top-level files and directories.
+    Files(Vec<PathBuf>),
+    Unavailable,
+}
+
+/// Internal implementation of the clipboard processing logic, running on the clipboard thread.
+pub(crate) struct X11ClipboardImpl {
+    conn: Arc<RustConnection>,
+    window: Window,
+    atoms: Atoms,
+    message_proxy: Box<dyn ClipboardMessageProxy>,
+    backend_rx: mpsc::Receiver<BackendEvent>,
+    temporary_directory: PathBuf,
+    capabilities: ClipboardGeneralCapabilityFlags,
+    chunk_size: usize,
+
+    // Local clipboard, owned by another X client
+    owner_timestamp: Timestamp,
+    local_targets: Vec<Atom>,
+    incoming: Option<Incoming>,
+    // Targets need to be queried again once the current conversion is done
+    targets_outdated: bool,
+    // Format data requests of the remote waiting for the current conversion
+    format_data_requests: VecDeque<ClipboardFormatId>,
+    upload: FileUpload,
+
+    // Remote clipboard, offered by our window when it owns the selection
+    ownership: Option<Timestamp>,
+    remote_targets: Vec<RemoteTarget>,
+    remote_data: HashMap<ClipboardFormatId, RemoteData>,
+    // Selection requests waiting for remote data
+    selection_requests: Vec<SelectionRequestEvent>,
+    requested_format: Option<ClipboardFormatId>,
+    // Response to the pending format data request belongs to a previous remote copy
+    discard_response: bool,
+    download: Option<(ClipboardFormatId, FileDownload)>,
+    download_count: u32,
+    outgoing: Vec<Outgoing>,
+}
+
+impl X11ClipboardImpl {
+    pub(crate) fn new(
+        conn: Arc<RustConnection>,
+        window: Window,
+        atoms: Atoms,
+        message_proxy: impl ClipboardMessageProxy + 'static,
+        backend_rx: mpsc::Receiver<BackendEvent>,
+        temporary_directory: PathBuf,
+    ) -> Self {
+        let chunk_size = conn
+            .maximum_request_bytes()
+            .saturating_sub(CHANGE_PROPERTY_OVERHEAD)
+            .min(MAX_CHUNK_SIZE);
+
+        Self {
+            conn,
+            window,
+            atoms,
+            message_proxy: Box::new(message_proxy),
+            backend_rx,
+            temporary_directory,
+            capabilities: ClipboardGeneralCapabilityFlags::empty(),
+            chunk_size,
+            owner_timestamp: CURRENT_TIME,
+            local_targets: Vec::new(),
+            incoming: None,
+            targets_outdated: false,
+            format_data_requests: VecDeque::new(),
+            upload: FileUpload::new(),
+            ownership: None,
+            remote_targets: Vec::new(),
+            remote_data: HashMap::new(),
+            selection_requests: Vec::new(),
+            requested_format: None,
+            discard_response: false,
+            download: None,
+            download_count: 0,
+            outgoing: Vec::new(),
+        }
+    }
+
+    /// Processes X11 and backend events until [`BackendEvent::Shutdown`] is received.
+    pub(crate) fn run(mut self) {
+        loop {
+            let event = match self.conn.wait_for_event() {
+                Ok(event) => event,
+                Err(err) => {
+                    self.report(Err(err.into()));
+                    return;
+                }
+            };
+
+            let result = match event {
+                // A message was sent by the `Cliprdr` backend shim
+                Event::ClientMessage(message) if message.type_ == self.atoms.IRONRDP_WAKEUP => {
+                    let mut result = Ok(());
+
+                    while let Ok(event) = self.backend_rx.try_recv() {
+                        if let BackendEvent::Shutdown = event {
+                            self.shutdown();
+                            return;
+                        }
+
+                        result = result.and(self.handle_backend_event(event));
+                    }
+
+                    result
+                }
+                event => self.handle_x11_event(event),
+            };
+
+            self.report(result.and_then(|()| Ok(self.conn.flush()?)));
+        }
+    }
+
+    fn report(&self, result: X11CliprdrResult<()>) {
+        if let Err(err) = result {
+            self.message_proxy
+                .send_clipboard_message(ClipboardMessage::Error(Box::new(err)));
+        }
+    }
+
+    fn send(&self, message: ClipboardMessage) {
+        self.message_proxy.send_clipboard_message(message);
+    }
+
+    fn shutdown(&mut self) {
+        if let Some((_, mut download)) = self.download.take() {
+            download.cancel();
+        }
+
+        let result = self.conn.destroy_window(self.window).and_then(|_| self.conn.flush());
+
+        if let Err(err) = result {
+            warn!("Failed to destroy clipboard window: {}", err);
+        }
+    }
+
+    fn handle_backend_event(&mut self, event: BackendEvent) -> X11CliprdrResult<()> {
+        match event {
+            BackendEvent::DowngradedCapabilities(capabilities) => {
+                self.capabilities = capabilities;
+                Ok(())
+            }
+            BackendEvent::RemoteFormatList(formats) => self.on_remote_format_list(&formats),
+            BackendEvent::FormatDataRequest(request) => {
+                self.format_data_requests.push_back(request.format);
+                self.start_next_conversion()
+            }
+            BackendEvent::FormatDataResponse(response) => self.on_format_data_response(&response),
+            BackendEvent::FileContentsRequest(request) => {
+                let response = self.upload.handle_request(&request);
+                self.send(ClipboardMessage::SendFileContentsResponse(response));
+                Ok(())
+            }
+            BackendEvent::FileContentsResponse(response) => self.on_file_contents_response(&response),
+            BackendEvent::Lock(data_id) => {
+                self.upload.lock(&data_id);
+                Ok(())
+            }
+            BackendEvent::Unlock(data_id) => {
+                self.upload.unlock(&data_id);
+                Ok(())
+            }
+            BackendEvent::RemoteRequestsFormatList => self.on_request_format_list(),
+            BackendEvent::Shutdown => Ok(()),
+        }
+    }
+
+    fn handle_x11_event(&mut self, event: Event) -> X11CliprdrResult<()> {
+        match event {
+            Event::XfixesSelectionNotify(event) => self.on_owner_changed(&event),
+            Event::SelectionNotify(event) => self.on_selection_notify(&event),
+            Event::SelectionRequest(event) => self.on_selection_request(event),
+            Event::SelectionClear(event) => self.on_selection_clear(&event),
+            Event::PropertyNotify(event) => self.on_property_notify(&event),
+            Event::Error(err) => {
+                debug!(?err, "X11 error");
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn file_lists_enabled(&self) -> bool {
+        self.capabilities
+            .contains(ClipboardGeneralCapabilityFlags::STREAM_FILECLIP_ENABLED)
+    }
+
+    // -- Local clipboard --
+
+    fn on_request_format_list(&mut self) -> X11CliprdrResult<()> {
+        let owner = self.conn.get_selection_owner(self.atoms.CLIPBOARD)?.reply()?.owner;
+
+        if owner == NONE || owner == self.window {
+            self.send(ClipboardMessage::SendInitiateCopy(Vec::new()));
+            return Ok(());
+        }
+
+        self.targets_outdated = true;
+        self.start_next_conversion()
+    }
+
+    fn on_owner_changed(&mut self, event: &xfixes::SelectionNotifyEvent) -> X11CliprdrResult<()> {
+        if event.selection != self.atoms.CLIPBOARD {
+            return Ok(());
+        }
+
+        if event.owner == self.window {
+            // We've just acquired the selection for the remote clipboard
+            if self.ownership.is_some() {
+                self.ownership = Some(event.selection_timestamp);
+            }
+            return Ok(());
+        }
+
+        self.owner_timestamp = event.selection_timestamp;
+
+        // Conversions from the previous owner are not going to complete
+        if let Some(Incoming {
+            conversion: Conversion::Data { .. },
+            ..
+        }) = self.incoming.take()
+        {
+            self.send(ClipboardMessage::SendFormatData(FormatDataResponse::new_error()));
+        }
+        let pending_requests = self.format_data_requests.len();
+        self.format_data_requests.clear();
+        for _ in 0..pending_requests {
+            self.send(ClipboardMessage::SendFormatData(FormatDataResponse::new_error()));
+        }
+
+        self.local_targets.clear();
+        self.upload.clear();
+
+        if event.owner == NONE {
+            // The clipboard is now empty
+            self.targets_outdated = false;
+            self.send(ClipboardMessage::SendInitiateCopy(Vec::new()));
+            return Ok(());
+        }
+
+        self.targets_outdated = true;
+        self.start_next_conversion()
+    }
+
+    fn start_next_conversion(&mut self) -> X11CliprdrResult<()> {
+        if self.incoming.is_some() {
+            return Ok(());
+        }
+
+        if self.targets_outdated {
+            self.targets_outdated = false;
+            return self.convert(Conversion::Targets);
+        }
+
+        while let Some(format) = self.format_data_requests.pop_front() {
+            let conversion = FormatKind::from_local_id(format).and_then(|kind| {
+                let target = kind
+                    .targets(&self.atoms)
+                    .into_iter()
+                    .find(|target| self.local_targets.contains(target))?;
+                Some(Conversion::Data { kind, target })
+            });
+
+            match conversion {
+                Some(conversion) => return self.convert(conversion),
+                None => {
+                    // Format is not available on the local clipboard
+                    self.send(ClipboardMessage::SendFormatData(FormatDataResponse::new_error()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn convert(&mut self, conversion: Conversion) -> X11CliprdrResult<()> {
+        let target = match conversion {
+            Conversion::Targets => self.atoms.TARGETS,
+            Conversion::Data { target, .. } => target,
+        };
+
+        self.incoming = Some(Incoming { conversion, incr: None });
+
+        self.conn.convert_selection(
+            self.window,
+            self.atoms.CLIPBOARD,
+            target,
+            self.atoms.IRONRDP_SELECTION,
+            self.owner_timestamp,
+        )?;
+
+        Ok(())
+    }
+
+    fn on_selection_notify(&mut self, event: &SelectionNotifyEvent) -> X11CliprdrResult<()> {
+        if event.requestor != self.window || event.selection != self.atoms.CLIPBOARD || self.incoming.is_none() {
+            return Ok(());
+        }
+
+        if event.property == NONE {
+            // The owner refused the conversion
+            return self.complete_conversion(None);
+        }
+
+        let (type_, data) = match self.take_property(event.property) {
+            Ok(property) => property,
+            Err(err) => {
+                self.complete_conversion(None)?;
+                return Err(err);
+            }
+        };
+
+        if type_ == self.atoms.INCR {
+            // The owner sends data in chunks, each of them replacing the property once we deleted it
+            if let Some(incoming) = &mut self.incoming {
+                incoming.incr = Some(Vec::new());
+            }
+            return Ok(());
+        }
+
+        self.complete_conversion(Some(data))
+    }
+
+    fn on_incoming_chunk(&mut self, property: Atom) -> X11CliprdrResult<()> {
+        let chunk = match self.take_property(property) {
+            Ok((_, chunk)) => chunk,
+            Err(err) => {
+                self.complete_conversion(None)?;
+                return Err(err);
+            }
+        };
+
+        let Some(buffer) = self.incoming.as_mut().and_then(|incoming| incoming.incr.as_mut()) else {
+            return Ok(());
+        };
+
+        if chunk.is_empty() {
+            // Zero-length chunk terminates the transfer
+            let data = std::mem::take(buffer);
+            self.complete_conversion(Some(data))
+        } else {
+            buffer.extend_from_slice(&chunk);
+            Ok(())
+        }
+    }
+
+    /// Reads and deletes a property of our window, returning its type and contents.
+    fn take_property(&self, property: Atom) -> X11CliprdrResult<(Atom, Vec<u8>)> {
+        let mut data = Vec::new();
+        let mut offset = 0u32;
+
+        loop {
+            let reply = self
+                .conn
+                .get_property(false, self.window, property, AtomEnum::ANY, offset, GET_PROPERTY_LENGTH)?
+                .reply()?;
+
+            data.extend_from_slice(&reply.value);
+
+            if reply.bytes_after == 0 {
+                self.conn.delete_property(self.window, property)?;
+                return Ok((reply.type_, data));
+            }
+
+            offset = offset.saturating_add(GET_PROPERTY_LENGTH);
+        }
+    }
+
+    fn complete_conversion(&mut self, data: Option<Vec<u8>>) -> X11CliprdrResult<()> {
+        let Some(incoming) = self.incoming.take() else {
+            return Ok(());
+        };
+
+        let result = match incoming.conversion {
+            Conversion::Targets => {
+                self.on_local_targets(data.as_deref().unwrap_or_default());
+                Ok(())
+            }
+            Conversion::Data { kind, target } => {
+                let response = match data {
+                    Some(data) => self.local_format_data(kind, target, &data),
+                    None => Ok(FormatDataResponse::new_error()),
+                };
+
+                match response {
+                    Ok(response) => {
+                        self.send(ClipboardMessage::SendFormatData(response));
+                        Ok(())
+                    }
+                    Err(err) => {
+                        self.send(ClipboardMessage::SendFormatData(FormatDataResponse::new_error()));
+                        Err(err)
+                    }
+                }
+            }
+        };
+
+        result.and(self.start_next_conversion())
+    }
+
+    fn on_local_targets(&mut self, data: &[u8]) {
+        self.local_targets = data
+            .chunks_exact(4)
+            .map(|atom| u32::from_ne_bytes([atom[0], atom[1], atom[2], atom[3]]))
+            .collect();
+
+        let formats = formats::local_formats(&self.local_targets, &self.atoms, self.file_lists_enabled());
+
+        self.send(ClipboardMessage::SendInitiateCopy(formats));
+    }
+
+    fn local_format_data(
+        &mut self,
+        kind: FormatKind,
+        target: Atom,
+        data: &[u8],
+    ) -> X11CliprdrResult<OwnedFormatDataResponse> {
+        if kind != FormatKind::FileList {
+            return Ok(FormatDataResponse::new_data(formats::to_remote(kind, data)?));
+        }
+
+        let paths = formats::parse_uri_list(data, target == self.atoms.GNOME_COPIED_FILES);
+        let files = LocalFileList::from_paths(paths).map_err(X11CliprdrError::Files)?;
+        let response = files.format_data().map_err(|_| X11CliprdrError::FileList)?;
+        self.upload.set_files(files);
+
+        Ok(response)
+    }
+
+    // -- Remote clipboard --
+
+    fn on_remote_format_list(&mut self, formats: &[ClipboardFormat]) -> X11CliprdrResult<()> {
+        self.clear_remote_clipboard()?;
+
+        let kinds = formats
+            .iter()
+            .filter_map(|format| Some((format.id(), FormatKind::from_remote(format)?)))
+            .filter(|(_, kind)| *kind != FormatKind::FileList || self.file_lists_enabled())
+            .collect::<Vec<_>>();
+
+        // CF_DIBV5 keeps the alpha channel, prefer it over CF_DIB for `image/png`
+        let has_dibv5 = kinds.iter().any(|(_, kind)| *kind == FormatKind::DibV5);
+
+        for (format, kind) in kinds {
+            if kind == FormatKind::Dib && has_dibv5 {
+                continue;
+            }
+
+            for target in kind.targets(&self.atoms) {
+                if !self.remote_targets.iter().any(|remote| remote.target == target) {
+                    self.remote_targets.push(RemoteTarget { target, format, kind });
+                }
+            }
+        }
+
+        if self.remote_targets.is_empty() {
+            // Nothing we can offer, leave the local clipboard untouched
+            return Ok(());
+        }
+
+        self.conn
+            .set_selection_owner(self.window, self.atoms.CLIPBOARD, CURRENT_TIME)?;
+        self.ownership = Some(CURRENT_TIME);
+
+        Ok(())
+    }
+
+    /// Forgets about the remote clipboard, e.g. when another X client took the selection over.
+    fn clear_remote_clipboard(&mut self) -> X11CliprdrResult<()> {
+        self.ownership = None;
+        self.remote_targets.clear();
+        self.remote_data.clear();
+
+        if self.requested_format.is_some() {
+            self.discard_response = true;
+        }
+
+        if let Some((_, mut download)) = self.download.take() {
+            download.cancel();
+        }
+
+        for request in std::mem::take(&mut self.selection_requests) {
+            self.notify(&request, NONE)?;
+        }
+
+        Ok(())
+    }
+
+    fn on_selection_clear(&mut self, event: &SelectionClearEvent) -> X11CliprdrResult<()> {
+        if event.selection != self.atoms.CLIPBOARD || event.owner != self.window {
+            return Ok(());
+        }
+
+        self.clear_remote_clipboard()
+    }
+
+    fn on_selection_request(&mut self, event: SelectionRequestEvent) -> X11CliprdrResult<()> {
+        // Obsolete clients don't specify the property
+        let property = requested_property(&event);
+
+        let Some(timestamp) = self.ownership.filter(|_| event.selection == self.atoms.CLIPBOARD) else {
+            return self.notify(&event, NONE);
+        };
+
+        if event.target == self.atoms.TARGETS {
+            let mut targets = vec![self.atoms.TARGETS, self.atoms.TIMESTAMP];
+            targets.extend(self.remote_targets.iter().map(|remote| remote.target));
+
+            self.conn
+                .change_property32(PropMode::REPLACE, event.requestor, property, AtomEnum::ATOM, &targets)?;
+            return self.notify(&event, property);
+        }
+
+        if event.target == self.atoms.TIMESTAMP {
+            self.conn.change_property32(
+                PropMode::REPLACE,
+                event.requestor,
+                property,
+                AtomEnum::INTEGER,
+                &[timestamp],
+            )?;
+            return self.notify(&event, property);
+        }
+
+        let Some(remote) = self.remote_target(event.target) else {
+            return self.notify(&event, NONE);
+        };
+
+        if self.remote_data.contains_key(&remote.format) {
+            return self.send_remote_data(&event, remote);
+        }
+
+        self.selection_requests.push(event);
+        self.request_remote_data();
+
+        Ok(())
+    }
+
+    fn remote_target(&self, target: Atom) -> Option<RemoteTarget> {
+        self.remote_targets
+            .iter()
+            .find(|remote| remote.target == target)
+            .copied()
+    }
+
+    /// Requests remote data needed by the first waiting selection request, unless a request is already in flight.
+    fn request_remote_data(&mut self) {
+        if self.requested_format.is_some() || self.download.is_some() {
+            return;
+        }
+
+        let format = self
+            .selection_requests
+            .iter()
+            .filter_map(|request| self.remote_target(request.target))
+            .map(|remote| remote.format)
+            .find(|format| !self.remote_data.contains_key(format));
+
+        if let Some(format) = format {
+            self.requested_format = Some(format);
+            self.send(ClipboardMessage::SendInitiatePaste(format));
+        }
+    }
+
+    fn on_format_data_response(&mut self, response: &FormatDataResponse<'_>) -> X11CliprdrResult<()> {
+        let Some(format) = self.requested_format.take() else {
+            warn!("Unexpected format data response");
+            return Ok(());
+        };
+
+        if std::mem::take(&mut self.discard_response) {
+            self.request_remote_data();
+            return Ok(());
+        }
+
+        let kind = self
+            .remote_targets
+            .iter()
+            .find(|remote| remote.format == format)
+            .map(|remote| remote.kind);
+
+        let data = match kind {
+            _ if response.is_error() => RemoteData::Unavailable,
+            Some(FormatKind::FileList) => return self.start_download(format, response),
+            _ => RemoteData::Data(response.data().to_vec()),
+        };
+
+        self.remote_data.insert(format, data);
+        self.answer_selection_requests()
+    }
+
+    fn start_download(&mut self, format: ClipboardFormatId, response: &FormatDataResponse<'_>) -> X11CliprdrResult<()> {
+        let Ok(list) = response.to_file_list() else {
+            self.remote_data.insert(format, RemoteData::Unavailable);
+            self.answer_selection_requests()?;
+            return Err(X11CliprdrError::FileList);
+        };
+
+        // Each paste gets its own directory so that files of different copies don't mix
+        self.download_count = self.download_count.wrapping_add(1);
+        let directory = self.temporary_directory.join(self.download_count.to_string());
+        let mut download = FileDownload::new(&directory, list);
+
+        match download.start() {
+            Ok(Some(request)) => {
+                self.download = Some((format, download));
+                self.send(ClipboardMessage::SendFileContentsRequest(request));
+                Ok(())
+            }
+            Ok(None) => {
+                self.remote_data
+                    .insert(format, RemoteData::Files(top_level_paths(&download, &directory)));
+                self.answer_selection_requests()
+            }
+            Err(err) => {
+                self.remote_data.insert(format, RemoteData::Unavailable);
+                self.answer_selection_requests()?;
+                Err(X11CliprdrError::Files(err))
+            }
+        }
+    }
+
+    fn on_file_contents_response(&mut self, response: &FileContentsResponse<'_>) -> X11CliprdrResult<()> {
+        let Some((format, download)) = &mut self.download else {
+            debug!("Ignoring file contents response without download");
+            return Ok(());
+        };
+        let format = *format;
+
+        match download.handle_response(response) {
+            Ok(Some(request)) => {
+                self.send(ClipboardMessage::SendFileContentsRequest(request));
+                Ok(())
+            }
+            Ok(None) => {
+                let directory = self.temporary_directory.join(self.download_count.to_string());
+                let paths = top_level_paths(download, &directory);
+
+                self.download = None;
+                self.remote_data.insert(format, RemoteData::Files(paths));
+                self.answer_selection_requests()
+            }
+            Err(err) => {
+                self.download = None;
+                self.remote_data.insert(format, RemoteData::Unavailable);
+                self.answer_selection_requests()?;
+                Err(X11CliprdrError::Files(err))
+            }
+        }
+    }
+
+    /// Answers the selection requests for which remote data is now available.
+    fn answer_selection_requests(&mut self) -> X11CliprdrResult<()> {
+        let requests = std::mem::take(&mut self.selection_requests);
+        let mut result = Ok(());
+
+        for request in requests {
+            match self.remote_target(request.target) {
+                Some(remote) if self.remote_data.contains_key(&remote.format) => {
+                    result = result.and(self.send_remote_data(&request, remote));
+                }
+                Some(_) => self.selection_requests.push(request),
+                None => result = result.and(self.notify(&request, NONE)),
+            }
+        }
+
+        self.request_remote_data();
+
+        result
+    }
+
+    fn send_remote_data(&mut self, event: &SelectionRequestEvent, remote: RemoteTarget) -> X11CliprdrResult<()> {
+        let data = match self.remote_data.get(&remote.format) {
+            Some(RemoteData::Data(data)) => formats::to_local(remote.kind, data),
+            Some(RemoteData::Files(paths)) => {
+                Ok(formats::uri_list(paths, remote.target == self.atoms.GNOME_COPIED_FILES))
+            }
+            Some(RemoteData::Unavailable) | None => return self.notify(event, NONE),
+        };
+
+        let data = match data {
+            Ok(data) => data,
+            Err(err) => {
+                self.notify(event, NONE)?;
+                return Err(err);
+            }
+        };
+
+        let property = requested_property(event);
+
+        if data.len() > self.chunk_size {
+            // Too large for a single request, start an INCR transfer. Chunks are sent each time
+            // the requestor deletes the property.
+            self.conn.change_window_attributes(
+                event.requestor,
+                &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+            )?;
+
+            let size = u32::try_from(data.len()).unwrap_or(u32::MAX);
+            self.conn
+                .change_property32(PropMode::REPLACE, event.requestor, property, self.atoms.INCR, &[size])?;
+
+            self.outgoing
+                .retain(|transfer| transfer.requestor != event.requestor || transfer.property != property);
+            self.outgoing.push(Outgoing {
+                requestor: event.requestor,
+                property,
+                target: event.target,
+                data,
+                position: 0,
+            });
+        } else {
+            self.conn
+                .change_property8(PropMode::REPLACE, event.requestor, property, event.target, &data)?;
+        }
+
+        self.notify(event, property)
+    }
+
+    fn on_property_notify(&mut self, event: &PropertyNotifyEvent) -> X11CliprdrResult<()> {
+        if event.window == self.window {
+            let incr_in_progress = self.incoming.as_ref().is_some_and(|incoming| incoming.incr.is_some());
+
+            if event.atom == self.atoms.IRONRDP_SELECTION && event.state == Property::NEW_VALUE && incr_in_progress {
+                return self.on_incoming_chunk(event.atom);
+            }
+
+            return Ok(());
+        }
+
+        if event.state != Property::DELETE {
+            return Ok(());
+        }
+
+        let Some(index) = self
+            .outgoing
+            .iter()
+            .position(|transfer| transfer.requestor == event.window && transfer.property == event.atom)
+        else {
+            return Ok(());
+        };
+
+        let transfer = &mut self.outgoing[index];
+        let end = transfer
+            .position
+            .saturating_add(self.chunk_size)
+            .min(transfer.data.len());
+        let chunk = &transfer.data[transfer.position..end];
+
+        self.conn.change_property8(
+            PropMode::REPLACE,
+            transfer.requestor,
+            transfer.property,
+            transfer.target,
+            chunk,
+        )?;
+
+        if chunk.is_empty() {
+            // Zero-length chunk terminates the transfer
+            self.outgoing.swap_remove(index);
+        } else {
+            transfer.position = end;
+        }
+
+        Ok(())
+    }
+
+    fn notify(&self, event: &SelectionRequestEvent, property: Atom) -> X11CliprdrResult<()> {
+        let notify = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: event.time,
+            requestor: event.requestor,
+            selection: event.selection,
+            target: event.target,
+            property,
+        };
+
+        self.conn
+            .send_event(false, event.requestor, EventMask::NO_EVENT, notify)?;
+
+        Ok(())
+    }
+}
+
+fn requested_property(event: &SelectionRequestEvent) -> Atom {
+    if event.property == NONE {
+        event.target
+    } else {
+        event.property
+    }
+}
+
+fn top_level_paths(download: &FileDownload, directory: &std::path::Path) -> Vec<PathBuf> {
+    download
+        .paths()
+        .iter()
+        .filter(|path| path.parent() == Some(directory))
+        .cloned()
+        .collect()
+}
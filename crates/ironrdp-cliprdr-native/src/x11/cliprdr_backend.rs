@@ -0,0 +1,76 @@
+use ironrdp_cliprdr::backend::CliprdrBackend;
+use ironrdp_cliprdr::pdu::{
+    ClipboardFormat, ClipboardGeneralCapabilityFlags, FileContentsRequest, FileContentsResponse, FormatDataRequest,
+    FormatDataResponse, LockDataId,
+};
+use ironrdp_core::{impl_as_any, IntoOwned};
+
+use crate::x11::{BackendEvent, BackendEventSender};
+
+#[derive(Debug)]
+pub(crate) struct X11CliprdrBackend {
+    events: BackendEventSender,
+    temporary_directory: String,
+}
+
+impl_as_any!(X11CliprdrBackend);
+
+impl X11CliprdrBackend {
+    pub(crate) fn new(events: BackendEventSender, temporary_directory: String) -> Self {
+        Self {
+            events,
+            temporary_directory,
+        }
+    }
+}
+
+impl CliprdrBackend for X11CliprdrBackend {
+    fn temporary_directory(&self) -> &str {
+        &self.temporary_directory
+    }
+
+    fn client_capabilities(&self) -> ClipboardGeneralCapabilityFlags {
+        ClipboardGeneralCapabilityFlags::STREAM_FILECLIP_ENABLED
+            | ClipboardGeneralCapabilityFlags::FILECLIP_NO_FILE_PATHS
+            | ClipboardGeneralCapabilityFlags::CAN_LOCK_CLIPDATA
+    }
+
+    fn on_process_negotiated_capabilities(&mut self, capabilities: ClipboardGeneralCapabilityFlags) {
+        self.events.send(BackendEvent::DowngradedCapabilities(capabilities))
+    }
+
+    fn on_remote_copy(&mut self, available_formats: &[ClipboardFormat]) {
+        self.events
+            .send(BackendEvent::RemoteFormatList(available_formats.to_vec()));
+    }
+
+    fn on_format_data_request(&mut self, request: FormatDataRequest) {
+        self.events.send(BackendEvent::FormatDataRequest(request));
+    }
+
+    fn on_format_data_response(&mut self, response: FormatDataResponse<'_>) {
+        self.events
+            .send(BackendEvent::FormatDataResponse(response.into_owned()));
+    }
+
+    fn on_file_contents_request(&mut self, request: FileContentsRequest) {
+        self.events.send(BackendEvent::FileContentsRequest(request));
+    }
+
+    fn on_file_contents_response(&mut self, response: FileContentsResponse<'_>) {
+        self.events
+            .send(BackendEvent::FileContentsResponse(response.into_owned()));
+    }
+
+    fn on_lock(&mut self, data_id: LockDataId) {
+        self.events.send(BackendEvent::Lock(data_id));
+    }
+
+    fn on_unlock(&mut self, data_id: LockDataId) {
+        self.events.send(BackendEvent::Unlock(data_id));
+    }
+
+    fn on_request_format_list(&mut self) {
+        self.events.send(BackendEvent::RemoteRequestsFormatList);
+    }
+}
@@ -0,0 +1,151 @@
+use std::path::PathBuf;
+
+use ironrdp_cliprdr::pdu::{ClipboardFormat, ClipboardFormatId, ClipboardFormatName};
+use ironrdp_cliprdr_format::bitmap::{dib_to_png, dibv5_to_png, png_to_cf_dibv5};
+use ironrdp_cliprdr_format::file_list::{file_uri, paths_to_uri_list, uri_list_to_paths};
+use ironrdp_cliprdr_format::html::{cf_html_to_plain_html, plain_html_to_cf_html};
+use ironrdp_cliprdr_format::text::{cf_unicodetext_to_string, string_to_cf_unicodetext};
+use x11rb::protocol::xproto::Atom;
+
+use crate::x11::{Atoms, X11CliprdrError, X11CliprdrResult};
+
+/// Local ID of the `HTML Format` registered format.
+const FORMAT_HTML_ID: ClipboardFormatId = ClipboardFormatId(0xC001);
+/// Local ID of the `FileGroupDescriptorW` registered format.
+const FORMAT_FILE_LIST_ID: ClipboardFormatId = ClipboardFormatId(0xC002);
+
+/// Clipboard formats exchanged between X11 and the remote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FormatKind {
+    Text,
+    Html,
+    Dib,
+    DibV5,
+    FileList,
+}
+
+impl FormatKind {
+    /// Identifies a format advertised by the remote.
+    pub(crate) fn from_remote(format: &ClipboardFormat) -> Option<Self> {
+        match format.id() {
+            ClipboardFormatId::CF_UNICODETEXT => Some(Self::Text),
+            ClipboardFormatId::CF_DIB => Some(Self::Dib),
+            ClipboardFormatId::CF_DIBV5 => Some(Self::DibV5),
+            _ => match format.name() {
+                Some(name) if *name == ClipboardFormatName::HTML => Some(Self::Html),
+                Some(name) if *name == ClipboardFormatName::FILE_LIST => Some(Self::FileList),
+                _ => None,
+            },
+        }
+    }
+
+    /// Identifies a format requested by the remote, as advertised by [`local_formats`].
+    pub(crate) fn from_local_id(id: ClipboardFormatId) -> Option<Self> {
+        match id {
+            ClipboardFormatId::CF_UNICODETEXT => Some(Self::Text),
+            ClipboardFormatId::CF_DIB => Some(Self::Dib),
+            ClipboardFormatId::CF_DIBV5 => Some(Self::DibV5),
+            FORMAT_HTML_ID => Some(Self::Html),
+            FORMAT_FILE_LIST_ID => Some(Self::FileList),
+            _ => None,
+        }
+    }
+
+    /// X11 targets this format is converted from and to, in order of preference.
+    pub(crate) fn targets(self, atoms: &Atoms) -> Vec<Atom> {
+        match self {
+            Self::Text => vec![atoms.UTF8_STRING, atoms.TEXT_PLAIN_UTF8],
+            Self::Html => vec![atoms.TEXT_HTML],
+            Self::Dib | Self::DibV5 => vec![atoms.IMAGE_PNG],
+            Self::FileList => vec![atoms.TEXT_URI_LIST, atoms.GNOME_COPIED_FILES],
+        }
+    }
+}
+
+/// Returns the formats to advertise to the remote for the targets of the local clipboard.
+pub(crate) fn local_formats(targets: &[Atom], atoms: &Atoms, file_lists: bool) -> Vec<ClipboardFormat> {
+    let available = |kind: FormatKind| kind.targets(atoms).iter().any(|target| targets.contains(target));
+
+    let mut formats = Vec::new();
+
+    if available(FormatKind::Text) {
+        formats.push(ClipboardFormat::new(ClipboardFormatId::CF_UNICODETEXT));
+    }
+
+    if available(FormatKind::Html) {
+        formats.push(ClipboardFormat::new(FORMAT_HTML_ID).with_name(ClipboardFormatName::HTML));
+    }
+
+    if available(FormatKind::DibV5) {
+        // CF_DIB is synthesized from CF_DIBV5 on the remote side.
+        formats.push(ClipboardFormat::new(ClipboardFormatId::CF_DIBV5));
+    }
+
+    if file_lists && available(FormatKind::FileList) {
+        formats.push(ClipboardFormat::new(FORMAT_FILE_LIST_ID).with_name(ClipboardFormatName::FILE_LIST));
+    }
+
+    formats
+}
+
+/// Converts local clipboard data, received as `kind`, to the remote format.
+///
+/// File lists are handled separately, see [`parse_uri_list`].
+pub(crate) fn to_remote(kind: FormatKind, data: &[u8]) -> X11CliprdrResult<Vec<u8>> {
+    match kind {
+        FormatKind::Text => Ok(string_to_cf_unicodetext(&String::from_utf8_lossy(data))),
+        FormatKind::Html => Ok(plain_html_to_cf_html(&decode_html(data)).into_bytes()),
+        FormatKind::Dib | FormatKind::DibV5 => Ok(png_to_cf_dibv5(data)?),
+        FormatKind::FileList => Err(X11CliprdrError::FileList),
+    }
+}
+
+/// Converts remote clipboard data, in `kind` format, to the local representation.
+///
+/// File lists are handled separately, see [`uri_list`].
+pub(crate) fn to_local(kind: FormatKind, data: &[u8]) -> X11CliprdrResult<Vec<u8>> {
+    match kind {
+        FormatKind::Text => Ok(cf_unicodetext_to_string(data).into_bytes()),
+        FormatKind::Html => Ok(cf_html_to_plain_html(data)?.as_bytes().to_vec()),
+        FormatKind::Dib => Ok(dib_to_png(data)?),
+        FormatKind::DibV5 => Ok(dibv5_to_png(data)?),
+        FormatKind::FileList => Err(X11CliprdrError::FileList),
+    }
+}
+
+/// Decodes `text/html` data, which some applications provide as UTF-16 with a byte order mark.
+fn decode_html(data: &[u8]) -> String {
+    match data {
+        [0xFF, 0xFE, rest @ ..] => {
+            let units = rest
+                .chunks_exact(2)
+                .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                .collect::<Vec<_>>();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(data).into_owned(),
+    }
+}
+
+/// Parses local paths out of `text/uri-list` or `x-special/gnome-copied-files` data.
+pub(crate) fn parse_uri_list(data: &[u8], gnome: bool) -> Vec<PathBuf> {
+    // GNOME lists start with the operation, `copy` or `cut`
+    let data = match data.iter().position(|byte| *byte == b'\n') {
+        Some(end) if gnome => &data[end..],
+        None if gnome => &[],
+        _ => data,
+    };
+
+    uri_list_to_paths(data)
+}
+
+/// Builds `text/uri-list` or `x-special/gnome-copied-files` data for local paths.
+pub(crate) fn uri_list(paths: &[PathBuf], gnome: bool) -> Vec<u8> {
+    if gnome {
+        let mut lines = vec![String::from("copy")];
+        lines.extend(paths.iter().map(|path| file_uri(path)));
+        lines.join("\n").into_bytes()
+    } else {
+        paths_to_uri_list(paths).into_bytes()
+    }
+}
@@ -33,6 +33,19 @@ pub enum ClipboardMessage {
     /// received.
     SendInitiatePaste(ClipboardFormatId),
 
+    /// Sent by clipboard backend when file contents need to be received from the remote, e.g.
+    /// while downloading pasted files with [`crate::file_transfer::FileDownload`].
+    ///
+    /// Client implementation should send file contents request on `CLIPRDR` SVC when this message
+    /// is received.
+    SendFileContentsRequest(FileContentsRequest),
+
+    /// Sent by clipboard backend when file contents requested by the remote are ready to be sent.
+    ///
+    /// Client implementation should submit file contents to `CLIPRDR` SVC when this message is
+    /// received.
+    SendFileContentsResponse(FileContentsResponse<'static>),
+
     /// Failure received from the OS clipboard event loop.
     ///
     /// Client implementation should log/display this error.
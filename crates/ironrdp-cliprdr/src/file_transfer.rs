@@ -0,0 +1,615 @@
+//! Helpers for transferring files through the clipboard.
+//!
+//! Files copied to the clipboard are advertised with the `FileGroupDescriptorW` format, the data of
+//! which is a [`PackedFileList`]. Their contents are then transferred separately, by range, using
+//! File Contents Request and Response PDUs.
+//!
+//! - [`LocalFileList`] and [`FileUpload`] implement the local side of a copy, answering the file
+//!   contents requests of the remote.
+//! - [`FileDownload`] implements a paste of remote files into a local directory, usually
+//!   [`CliprdrBackend::temporary_directory`], issuing the file contents requests.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read as _, Seek as _, SeekFrom, Write as _};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use ironrdp_core::EncodeResult;
+use tracing::{debug, warn};
+
+use crate::backend::CliprdrBackend;
+use crate::pdu::{
+    ClipboardFileAttributes, FileContentsFlags, FileContentsRequest, FileContentsResponse, FileDescriptor,
+    FormatDataResponse, LockDataId, OwnedFormatDataResponse, PackedFileList,
+};
+
+/// Default number of bytes requested at once by a [`FileDownload`].
+pub const DEFAULT_CHUNK_SIZE: u32 = 64 * 1024;
+
+/// Number of 100-nanosecond intervals between 1601-01-01 and 1970-01-01, the origins of FILETIME and UNIX time.
+const FILETIME_UNIX_EPOCH: u64 = 116_444_736_000_000_000;
+
+/// Maximum depth of the directories listed by a [`LocalFileList`], below the copied items.
+pub const MAX_DIRECTORY_DEPTH: usize = 64;
+
+/// Progress of a file transfer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransferProgress {
+    /// Number of bytes of file contents transferred so far.
+    pub transferred_bytes: u64,
+    /// Total size of the files, as far as it is known.
+    pub total_bytes: u64,
+    /// Number of files and directories transferred completely.
+    pub completed_files: usize,
+    pub total_files: usize,
+}
+
+/// A local file or directory copied to the clipboard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalFile {
+    pub path: PathBuf,
+    /// Description of the file as sent to the remote, named relatively to the copied items.
+    pub descriptor: FileDescriptor,
+}
+
+/// Local files and directories copied to the clipboard, in the order of their descriptors.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LocalFileList {
+    files: Vec<LocalFile>,
+}
+
+impl LocalFileList {
+    /// Lists `paths`, along with the contents of the directories among them.
+    ///
+    /// Directories are listed before their contents, so that they can be created first when pasted. Symbolic links
+    /// found in them are followed to files only, not to directories, which could contain themselves.
+    pub fn from_paths<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> io::Result<Self> {
+        let mut list = Self::default();
+
+        for path in paths {
+            let path = path.as_ref();
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no valid file name"))?;
+            list.push(path, name.to_owned(), 0)?;
+        }
+
+        Ok(list)
+    }
+
+    fn push(&mut self, path: &Path, name: String, depth: usize) -> io::Result<()> {
+        let metadata = if depth == 0 {
+            fs::metadata(path)?
+        } else {
+            let metadata = fs::symlink_metadata(path)?;
+            if !metadata.file_type().is_symlink() {
+                metadata
+            } else {
+                match fs::metadata(path) {
+                    Ok(metadata) if !metadata.is_dir() => metadata,
+                    _ => {
+                        warn!(path = %path.display(), "Skipping symbolic link to a directory or a missing file");
+                        return Ok(());
+                    }
+                }
+            }
+        };
+        let is_dir = metadata.is_dir();
+
+        if is_dir && depth > MAX_DIRECTORY_DEPTH {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("directory {} is nested too deeply", path.display()),
+            ));
+        }
+
+        let attributes = if is_dir {
+            ClipboardFileAttributes::DIRECTORY
+        } else if metadata.permissions().readonly() {
+            ClipboardFileAttributes::READONLY
+        } else {
+            ClipboardFileAttributes::NORMAL
+        };
+
+        self.files.push(LocalFile {
+            path: path.to_owned(),
+            descriptor: FileDescriptor {
+                attributes: Some(attributes),
+                last_write_time: metadata.modified().ok().map(filetime_from_system_time),
+                file_size: Some(if is_dir { 0 } else { metadata.len() }),
+                name: name.clone(),
+            },
+        });
+
+        if is_dir {
+            let mut entries = fs::read_dir(path)?.collect::<io::Result<Vec<_>>>()?;
+            entries.sort_by_key(|entry| entry.file_name());
+
+            for entry in entries {
+                let Some(entry_name) = entry.file_name().to_str().map(str::to_owned) else {
+                    warn!(path = %entry.path().display(), "Skipping file with a non UTF-8 name");
+                    continue;
+                };
+                self.push(&entry.path(), format!("{name}\\{entry_name}"), depth + 1)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn files(&self) -> &[LocalFile] {
+        &self.files
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Returns the `FileGroupDescriptorW` file list describing the files.
+    pub fn to_packed(&self) -> PackedFileList {
+        PackedFileList {
+            files: self.files.iter().map(|file| file.descriptor.clone()).collect(),
+        }
+    }
+
+    /// Returns the response to a format data request for the `FileGroupDescriptorW` format.
+    pub fn format_data(&self) -> EncodeResult<OwnedFormatDataResponse> {
+        FormatDataResponse::new_file_list(&self.to_packed())
+    }
+}
+
+/// Answers the file contents requests of the remote for local files copied to the clipboard.
+///
+/// The remote may lock the clipboard data before requesting contents, in which case the files are
+/// kept available under the lock ID even if other files are copied in the meantime.
+#[derive(Debug, Default)]
+pub struct FileUpload {
+    files: Option<Arc<LocalFileList>>,
+    locked: HashMap<u32, Arc<LocalFileList>>,
+    progress: TransferProgress,
+    cancelled: bool,
+}
+
+impl FileUpload {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the files currently copied to the clipboard.
+    pub fn set_files(&mut self, files: LocalFileList) {
+        self.progress = TransferProgress {
+            total_bytes: files.files.iter().filter_map(|file| file.descriptor.file_size).sum(),
+            total_files: files.files.len(),
+            ..TransferProgress::default()
+        };
+        self.files = Some(Arc::new(files));
+        self.cancelled = false;
+    }
+
+    /// Clears the files, e.g. when something else is copied to the clipboard.
+    pub fn clear(&mut self) {
+        self.files = None;
+        self.progress = TransferProgress::default();
+    }
+
+    /// Keeps the current files available under `id`, see [`CliprdrBackend::on_lock`].
+    pub fn lock(&mut self, id: &LockDataId) {
+        match &self.files {
+            Some(files) => {
+                self.locked.insert(id.0, Arc::clone(files));
+            }
+            None => warn!(id = id.0, "Attempted to lock clipboard data without files"),
+        }
+    }
+
+    /// Releases the files locked under `id`, see [`CliprdrBackend::on_unlock`].
+    pub fn unlock(&mut self, id: &LockDataId) {
+        if self.locked.remove(&id.0).is_none() {
+            warn!(id = id.0, "Attempted to unlock clipboard data which is not locked");
+        }
+    }
+
+    /// Answers the remote with errors until other files are set.
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+
+    pub fn progress(&self) -> TransferProgress {
+        self.progress
+    }
+
+    /// Returns the response to `request`, to be submitted with [`crate::Cliprdr::submit_file_contents`].
+    pub fn handle_request(&mut self, request: &FileContentsRequest) -> FileContentsResponse<'static> {
+        if self.cancelled {
+            return FileContentsResponse::new_error(request.stream_id);
+        }
+
+        match self.read(request) {
+            Ok(response) => response,
+            Err(error) => {
+                warn!(%error, ?request, "Failed to read the requested file contents");
+                FileContentsResponse::new_error(request.stream_id)
+            }
+        }
+    }
+
+    fn read(&mut self, request: &FileContentsRequest) -> io::Result<FileContentsResponse<'static>> {
+        let files = match request.data_id {
+            Some(id) => self.locked.get(&id),
+            None => self.files.as_ref(),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no files available"))?;
+
+        let file = usize::try_from(request.index)
+            .ok()
+            .and_then(|index| files.files.get(index))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "file index out of range"))?;
+
+        let metadata = fs::metadata(&file.path)?;
+        if metadata.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "requested contents of a directory",
+            ));
+        }
+
+        if request.flags.contains(FileContentsFlags::SIZE) {
+            return Ok(FileContentsResponse::new_size_response(
+                request.stream_id,
+                metadata.len(),
+            ));
+        }
+
+        let mut reader = fs::File::open(&file.path)?;
+        reader.seek(SeekFrom::Start(request.position))?;
+
+        let mut data = Vec::new();
+        reader.take(u64::from(request.requested_size)).read_to_end(&mut data)?;
+
+        let read = u64::try_from(data.len()).expect("read at most u32::MAX bytes");
+        self.progress.transferred_bytes = self.progress.transferred_bytes.saturating_add(read);
+        if request.position.saturating_add(read) >= metadata.len() {
+            self.progress.completed_files = self.progress.completed_files.saturating_add(1);
+        }
+
+        Ok(FileContentsResponse::new_data_response(request.stream_id, data))
+    }
+}
+
+#[derive(Debug)]
+enum DownloadState {
+    Idle,
+    Size {
+        stream_id: u32,
+    },
+    Data {
+        stream_id: u32,
+        file: fs::File,
+        position: u64,
+        size: u64,
+    },
+    Finished,
+    Cancelled,
+}
+
+/// Downloads remote files pasted from the clipboard into a local directory.
+///
+/// The caller sends the requests returned by [`Self::start`] and [`Self::handle_response`] with
+/// [`crate::Cliprdr::request_file_contents`] and forwards the matching responses, until the
+/// download is finished. If the remote supports it, the clipboard data should be locked for the
+/// duration of the download, see [`Self::with_clip_data_id`].
+#[derive(Debug)]
+pub struct FileDownload {
+    directory: PathBuf,
+    files: Vec<FileDescriptor>,
+    clip_data_id: Option<u32>,
+    chunk_size: u32,
+    next_stream_id: u32,
+    /// Index of the file being downloaded.
+    current: usize,
+    state: DownloadState,
+    paths: Vec<PathBuf>,
+    progress: TransferProgress,
+}
+
+impl FileDownload {
+    pub fn new(directory: impl Into<PathBuf>, list: PackedFileList) -> Self {
+        let progress = TransferProgress {
+            total_bytes: list.files.iter().filter_map(|file| file.file_size).sum(),
+            total_files: list.files.len(),
+            ..TransferProgress::default()
+        };
+
+        Self {
+            directory: directory.into(),
+            files: list.files,
+            clip_data_id: None,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            next_stream_id: 1,
+            current: 0,
+            state: DownloadState::Idle,
+            paths: Vec::new(),
+            progress,
+        }
+    }
+
+    /// Downloads the files into the temporary directory of `backend`.
+    pub fn in_temporary_directory(backend: &dyn CliprdrBackend, list: PackedFileList) -> Self {
+        Self::new(backend.temporary_directory(), list)
+    }
+
+    /// Requests the contents of the files locked under `id`, see [`crate::Cliprdr::lock_clipboard`].
+    #[must_use]
+    pub fn with_clip_data_id(mut self, id: u32) -> Self {
+        self.clip_data_id = Some(id);
+        self
+    }
+
+    /// Sets the maximum number of bytes requested at once.
+    #[must_use]
+    pub fn with_chunk_size(mut self, chunk_size: u32) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Returns the ID under which the clipboard data should be locked, if any.
+    pub fn clip_data_id(&self) -> Option<LockDataId> {
+        self.clip_data_id.map(LockDataId)
+    }
+
+    pub fn progress(&self) -> TransferProgress {
+        self.progress
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self.state, DownloadState::Finished)
+    }
+
+    /// Returns the local paths of the files and directories downloaded so far.
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Starts the download, returning the first request to send.
+    ///
+    /// Returns `None` if there is no file contents to request, e.g. when only directories were pasted.
+    pub fn start(&mut self) -> io::Result<Option<FileContentsRequest>> {
+        if !matches!(self.state, DownloadState::Idle) {
+            return Err(io::Error::new(io::ErrorKind::Other, "download already started"));
+        }
+
+        // Refuse the whole list up front rather than leaving a partial download behind.
+        for file in &self.files {
+            local_path(&self.directory, &file.name)?;
+        }
+
+        self.next_file()
+    }
+
+    /// Processes the response to the last request, returning the next request to send.
+    ///
+    /// Returns `None` once the download is finished. On error, the download is cancelled.
+    pub fn handle_response(&mut self, response: &FileContentsResponse<'_>) -> io::Result<Option<FileContentsRequest>> {
+        let result = self.process_response(response);
+        if result.is_err() {
+            self.cancel();
+        }
+        result
+    }
+
+    /// Cancels the download, removing the partially downloaded file.
+    pub fn cancel(&mut self) {
+        if let DownloadState::Data { .. } = std::mem::replace(&mut self.state, DownloadState::Cancelled) {
+            if let Ok(path) = self.current_path() {
+                if let Err(error) = fs::remove_file(&path) {
+                    warn!(%error, path = %path.display(), "Failed to remove partially downloaded file");
+                }
+            }
+        }
+    }
+
+    fn process_response(&mut self, response: &FileContentsResponse<'_>) -> io::Result<Option<FileContentsRequest>> {
+        let expected_stream_id = match &self.state {
+            DownloadState::Size { stream_id } | DownloadState::Data { stream_id, .. } => *stream_id,
+            _ => return Err(io::Error::new(io::ErrorKind::Other, "no file contents requested")),
+        };
+
+        if response.stream_id() != expected_stream_id {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected stream ID"));
+        }
+
+        if response.is_error() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "remote failed to provide the file contents",
+            ));
+        }
+
+        match std::mem::replace(&mut self.state, DownloadState::Idle) {
+            DownloadState::Size { .. } => {
+                let size = response
+                    .data_as_size()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                self.progress.total_bytes = self.progress.total_bytes.saturating_add(size);
+                self.open_file(size)
+            }
+            DownloadState::Data {
+                mut file,
+                position,
+                size,
+                ..
+            } => {
+                let data = response.data();
+                if data.is_empty() {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "remote file is truncated"));
+                }
+
+                file.write_all(data)?;
+
+                let received = u64::try_from(data.len()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                let position = position.saturating_add(received);
+                self.progress.transferred_bytes = self.progress.transferred_bytes.saturating_add(received);
+
+                if position >= size {
+                    self.finish_file(file)?;
+                    self.next_file()
+                } else {
+                    Ok(Some(self.request_data(file, position, size)))
+                }
+            }
+            _ => unreachable!("checked above"),
+        }
+    }
+
+    fn current_path(&self) -> io::Result<PathBuf> {
+        local_path(&self.directory, &self.files[self.current].name)
+    }
+
+    /// Moves on to the next file needing its contents to be requested, creating directories along the way.
+    fn next_file(&mut self) -> io::Result<Option<FileContentsRequest>> {
+        while self.current < self.files.len() {
+            let descriptor = &self.files[self.current];
+            let path = self.current_path()?;
+
+            if descriptor
+                .attributes
+                .is_some_and(|attributes| attributes.contains(ClipboardFileAttributes::DIRECTORY))
+            {
+                fs::create_dir_all(&path)?;
+                self.paths.push(path);
+                self.progress.completed_files = self.progress.completed_files.saturating_add(1);
+                self.current = self.current.saturating_add(1);
+                continue;
+            }
+
+            return match descriptor.file_size {
+                Some(size) => self.open_file(size),
+                None => {
+                    let stream_id = self.next_stream_id();
+                    self.state = DownloadState::Size { stream_id };
+                    Ok(Some(FileContentsRequest {
+                        stream_id,
+                        index: self.index()?,
+                        flags: FileContentsFlags::SIZE,
+                        position: 0,
+                        requested_size: 8,
+                        data_id: self.clip_data_id,
+                    }))
+                }
+            };
+        }
+
+        debug!(directory = %self.directory.display(), files = self.files.len(), "Clipboard file download finished");
+        self.state = DownloadState::Finished;
+        Ok(None)
+    }
+
+    fn open_file(&mut self, size: u64) -> io::Result<Option<FileContentsRequest>> {
+        let path = self.current_path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = fs::File::create(&path)?;
+
+        if size == 0 {
+            self.finish_file(file)?;
+            return self.next_file();
+        }
+
+        Ok(Some(self.request_data(file, 0, size)))
+    }
+
+    fn request_data(&mut self, file: fs::File, position: u64, size: u64) -> FileContentsRequest {
+        let stream_id = self.next_stream_id();
+        let requested_size = u32::try_from(size.saturating_sub(position))
+            .map_or(self.chunk_size, |remaining| remaining.min(self.chunk_size));
+
+        self.state = DownloadState::Data {
+            stream_id,
+            file,
+            position,
+            size,
+        };
+
+        FileContentsRequest {
+            stream_id,
+            index: u32::try_from(self.current).expect("file count fits in the u32 cItems field"),
+            flags: FileContentsFlags::DATA,
+            position,
+            requested_size,
+            data_id: self.clip_data_id,
+        }
+    }
+
+    fn finish_file(&mut self, file: fs::File) -> io::Result<()> {
+        let descriptor = &self.files[self.current];
+        if let Some(modified) = descriptor.last_write_time.and_then(system_time_from_filetime) {
+            if let Err(error) = file.set_modified(modified) {
+                debug!(%error, "Failed to set the modification time of a downloaded file");
+            }
+        }
+
+        self.paths.push(self.current_path()?);
+        self.progress.completed_files = self.progress.completed_files.saturating_add(1);
+        self.current = self.current.saturating_add(1);
+        Ok(())
+    }
+
+    fn index(&self) -> io::Result<u32> {
+        u32::try_from(self.current).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    fn next_stream_id(&mut self) -> u32 {
+        let stream_id = self.next_stream_id;
+        self.next_stream_id = self.next_stream_id.wrapping_add(1);
+        stream_id
+    }
+}
+
+/// Resolves a file name of a `FileGroupDescriptorW` list inside `directory`.
+///
+/// The name is relative, with `\` separators. Names escaping `directory` are refused.
+fn local_path(directory: &Path, name: &str) -> io::Result<PathBuf> {
+    let mut path = directory.to_owned();
+    let mut is_empty = true;
+
+    for component in name.split(['\\', '/']).filter(|component| !component.is_empty()) {
+        let mut components = Path::new(component).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(normal)), None) if !component.contains(':') => path.push(normal),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid file name in clipboard file list: {name}"),
+                ))
+            }
+        }
+        is_empty = false;
+    }
+
+    if is_empty {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "empty file name in clipboard file list",
+        ));
+    }
+
+    Ok(path)
+}
+
+fn filetime_from_system_time(time: SystemTime) -> u64 {
+    let intervals = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos() / 100);
+    u64::try_from(intervals)
+        .unwrap_or(u64::MAX)
+        .saturating_add(FILETIME_UNIX_EPOCH)
+}
+
+fn system_time_from_filetime(filetime: u64) -> Option<SystemTime> {
+    let intervals = filetime.checked_sub(FILETIME_UNIX_EPOCH)?;
+    SystemTime::UNIX_EPOCH.checked_add(Duration::from_nanos(intervals.checked_mul(100)?))
+}
@@ -9,7 +9,9 @@
 #![allow(clippy::cast_sign_loss)] // FIXME: remove
 
 pub mod backend;
+pub mod file_transfer;
 pub mod pdu;
+pub mod policy;
 
 use backend::CliprdrBackend;
 use ironrdp_core::{decode, AsAny, EncodeResult};
@@ -22,8 +24,8 @@ use ironrdp_svc::{
 };
 use pdu::{
     Capabilities, ClientTemporaryDirectory, ClipboardFormat, ClipboardFormatId, ClipboardGeneralCapabilityFlags,
-    ClipboardPdu, ClipboardProtocolVersion, FileContentsResponse, FormatDataRequest, FormatListResponse,
-    OwnedFormatDataResponse,
+    ClipboardPdu, ClipboardProtocolVersion, FileContentsRequest, FileContentsResponse, FormatDataRequest,
+    FormatListResponse, LockDataId, OwnedFormatDataResponse,
 };
 use thiserror::Error;
 use tracing::{error, info};
@@ -216,6 +218,41 @@ impl<R: Role> Cliprdr<R> {
         Ok(vec![into_cliprdr_message(pdu)].into())
     }
 
+    /// Requests the size or a range of the contents of a remote file, returning a [`CliprdrSvcMessages`] to send on
+    /// the channel.
+    ///
+    /// The remote answers with a [`FileContentsResponse`] passed to [`CliprdrBackend::on_file_contents_response`].
+    /// See [`file_transfer::FileDownload`] for a helper downloading whole files.
+    pub fn request_file_contents(&self, request: FileContentsRequest) -> PduResult<CliprdrSvcMessages<R>> {
+        ready_guard!(self, request_file_contents);
+
+        let pdu = ClipboardPdu::FileContentsRequest(request);
+
+        Ok(vec![into_cliprdr_message(pdu)].into())
+    }
+
+    /// Locks the remote clipboard data under `id`, returning a [`CliprdrSvcMessages`] to send on the channel.
+    ///
+    /// File contents can then be requested under this ID even after the remote clipboard has changed, until
+    /// [`Self::unlock_clipboard`] is called. Requires [`ClipboardGeneralCapabilityFlags::CAN_LOCK_CLIPDATA`].
+    pub fn lock_clipboard(&self, id: LockDataId) -> PduResult<CliprdrSvcMessages<R>> {
+        ready_guard!(self, lock_clipboard);
+
+        let pdu = ClipboardPdu::LockData(id);
+
+        Ok(vec![into_cliprdr_message(pdu)].into())
+    }
+
+    /// Unlocks the remote clipboard data previously locked under `id`, returning a [`CliprdrSvcMessages`] to send
+    /// on the channel.
+    pub fn unlock_clipboard(&self, id: LockDataId) -> PduResult<CliprdrSvcMessages<R>> {
+        ready_guard!(self, unlock_clipboard);
+
+        let pdu = ClipboardPdu::UnlockData(id);
+
+        Ok(vec![into_cliprdr_message(pdu)].into())
+    }
+
     pub fn capabilities(&self) -> PduResult<SvcMessage> {
         let pdu = ClipboardPdu::Capabilities(self.capabilities.clone());
 
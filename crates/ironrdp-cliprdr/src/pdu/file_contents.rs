@@ -88,6 +88,10 @@ impl<'a> FileContentsResponse<'a> {
         self.stream_id
     }
 
+    pub fn is_error(&self) -> bool {
+        self.is_error
+    }
+
     pub fn data(&self) -> &[u8] {
         &self.data
     }
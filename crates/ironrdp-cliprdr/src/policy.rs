@@ -0,0 +1,604 @@
+//! Direction, format and size controls over clipboard transfers.
+//!
+//! A [`ClipboardPolicy`] is enforced on both paths between a [`CliprdrBackend`] and the remote:
+//!
+//! - [`PolicyBackend`] wraps the backend, filtering what the remote sends (format lists, format
+//!   data and file contents) and answering denied requests of the remote with failure responses.
+//! - [`PolicyMessageProxy`] wraps the [`ClipboardMessageProxy`] used by the backend, filtering what
+//!   the backend sends to the remote. Applications which do not use a proxy can call
+//!   [`ClipboardPolicy::filter_message`] directly.
+//!
+//! Both should share the same policy instance, which keeps track of the formats offered on each
+//! side, and each clipboard session needs its own, see [`ClipboardPolicy::new_session`]. Directions are relative to the backend: "local" is the side of the backend, which is the
+//! client machine for clients and the server machine for servers.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use ironrdp_core::impl_as_any;
+use tracing::{debug, warn};
+
+use crate::backend::{ClipboardMessage, ClipboardMessageProxy, CliprdrBackend, CliprdrBackendFactory};
+use crate::pdu::{
+    ClipboardFormat, ClipboardFormatId, ClipboardFormatName, ClipboardGeneralCapabilityFlags, FileContentsRequest,
+    FileContentsResponse, FormatDataRequest, FormatDataResponse, LockDataId,
+};
+
+/// Direction of a clipboard transfer, relative to the backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransferDirection {
+    /// Copy on the local side, paste on the remote side.
+    LocalToRemote,
+    /// Copy on the remote side, paste on the local side.
+    RemoteToLocal,
+}
+
+/// Why a policy denied a transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DenialReason {
+    /// Transfers in this direction are disabled.
+    Direction,
+    /// The format is not allowed, or was not offered.
+    Format,
+    /// File transfers are disabled.
+    Files,
+    /// The payload exceeds the maximum size.
+    Size,
+}
+
+/// Outcome of a transfer checked by a policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PolicyOutcome {
+    Allowed,
+    Denied(DenialReason),
+}
+
+/// Event reported to [`ClipboardAuditor`]s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClipboardAuditEvent {
+    /// Formats offered by a copy, split between the ones passed on and the filtered out ones.
+    FormatList {
+        direction: TransferDirection,
+        allowed: Vec<ClipboardFormat>,
+        denied: Vec<(ClipboardFormat, DenialReason)>,
+    },
+    /// Format data, requested or sent.
+    ///
+    /// The format is `None` when the data does not match a known request.
+    FormatData {
+        direction: TransferDirection,
+        format: Option<ClipboardFormatId>,
+        size: usize,
+        outcome: PolicyOutcome,
+    },
+    /// File contents, requested or sent.
+    FileContents {
+        direction: TransferDirection,
+        stream_id: u32,
+        size: usize,
+        outcome: PolicyOutcome,
+    },
+}
+
+/// Receives the transfers checked by a [`ClipboardPolicy`], e.g. for compliance logging.
+pub trait ClipboardAuditor: std::fmt::Debug + Send + Sync {
+    fn audit(&self, event: &ClipboardAuditEvent);
+}
+
+/// Formats last offered on each side, after filtering.
+#[derive(Debug, Default)]
+struct PolicyState {
+    local_formats: Vec<ClipboardFormat>,
+    remote_formats: Vec<ClipboardFormat>,
+    /// Format of the last data request of the remote.
+    local_request: Option<ClipboardFormatId>,
+    /// Format of the last data request of the backend.
+    remote_request: Option<ClipboardFormatId>,
+}
+
+/// Rules restricting clipboard transfers.
+///
+/// Everything is allowed by default.
+#[derive(Debug, Default)]
+pub struct ClipboardPolicy {
+    deny_local_to_remote: bool,
+    deny_remote_to_local: bool,
+    deny_files: bool,
+    max_data_size: Option<usize>,
+    allowed_format_ids: Option<Vec<ClipboardFormatId>>,
+    allowed_format_names: Option<Vec<String>>,
+    auditors: Vec<Arc<dyn ClipboardAuditor>>,
+    state: Mutex<PolicyState>,
+}
+
+impl ClipboardPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enables or disables transfers in `direction`.
+    #[must_use]
+    pub fn with_direction(mut self, direction: TransferDirection, allowed: bool) -> Self {
+        match direction {
+            TransferDirection::LocalToRemote => self.deny_local_to_remote = !allowed,
+            TransferDirection::RemoteToLocal => self.deny_remote_to_local = !allowed,
+        }
+        self
+    }
+
+    /// Enables or disables file transfers, in both directions.
+    ///
+    /// When disabled, the file list formats are filtered out and file transfer capabilities are
+    /// not advertised.
+    #[must_use]
+    pub fn with_files(mut self, allowed: bool) -> Self {
+        self.deny_files = !allowed;
+        self
+    }
+
+    /// Denies format data and file contents payloads larger than `size` bytes.
+    ///
+    /// File contents are transferred by chunks, each of which is checked separately.
+    #[must_use]
+    pub fn with_max_data_size(mut self, size: usize) -> Self {
+        self.max_data_size = Some(size);
+        self
+    }
+
+    /// Allows the standard format `id`.
+    ///
+    /// Once a format is allowed with this method or [`Self::with_allowed_format_name`], only the
+    /// allowed formats are transferred.
+    #[must_use]
+    pub fn with_allowed_format_id(mut self, id: ClipboardFormatId) -> Self {
+        self.allowed_format_ids.get_or_insert_with(Vec::new).push(id);
+        self.allowed_format_names.get_or_insert_with(Vec::new);
+        self
+    }
+
+    /// Allows the registered format named `name`, e.g. `HTML Format`.
+    ///
+    /// IDs of registered formats differ between the local and remote sides, so they are matched
+    /// by name, case-insensitively.
+    #[must_use]
+    pub fn with_allowed_format_name(mut self, name: impl Into<String>) -> Self {
+        self.allowed_format_names.get_or_insert_with(Vec::new).push(name.into());
+        self.allowed_format_ids.get_or_insert_with(Vec::new);
+        self
+    }
+
+    /// Returns a policy with the same rules and auditors, for another clipboard session.
+    ///
+    /// The formats offered and requested so far are not shared with the new policy.
+    #[must_use]
+    pub fn new_session(&self) -> Self {
+        Self {
+            deny_local_to_remote: self.deny_local_to_remote,
+            deny_remote_to_local: self.deny_remote_to_local,
+            deny_files: self.deny_files,
+            max_data_size: self.max_data_size,
+            allowed_format_ids: self.allowed_format_ids.clone(),
+            allowed_format_names: self.allowed_format_names.clone(),
+            auditors: self.auditors.clone(),
+            state: Mutex::default(),
+        }
+    }
+
+    /// Reports checked transfers to `auditor`.
+    #[must_use]
+    pub fn with_auditor(mut self, auditor: Arc<dyn ClipboardAuditor>) -> Self {
+        self.auditors.push(auditor);
+        self
+    }
+
+    /// Checks a format against the direction, file and format rules.
+    pub fn check_format(&self, direction: TransferDirection, format: &ClipboardFormat) -> PolicyOutcome {
+        if !self.is_direction_allowed(direction) {
+            return PolicyOutcome::Denied(DenialReason::Direction);
+        }
+
+        let is_file_list = format.id() == ClipboardFormatId::CF_HDROP
+            || format
+                .name()
+                .is_some_and(|name| *name == ClipboardFormatName::FILE_LIST);
+
+        if self.deny_files && is_file_list {
+            return PolicyOutcome::Denied(DenialReason::Files);
+        }
+
+        let (Some(ids), Some(names)) = (&self.allowed_format_ids, &self.allowed_format_names) else {
+            return PolicyOutcome::Allowed;
+        };
+
+        let is_allowed = match format.name() {
+            Some(name) => names.iter().any(|allowed| allowed.eq_ignore_ascii_case(name.value())),
+            None => ids.contains(&format.id()),
+        };
+
+        if is_allowed {
+            PolicyOutcome::Allowed
+        } else {
+            PolicyOutcome::Denied(DenialReason::Format)
+        }
+    }
+
+    /// Filters a message sent by the backend to the remote.
+    ///
+    /// Denied data is replaced with failure responses, while denied requests of the backend are
+    /// dropped, returning `None`.
+    pub fn filter_message(&self, message: ClipboardMessage) -> Option<ClipboardMessage> {
+        const DIRECTION: TransferDirection = TransferDirection::LocalToRemote;
+
+        match message {
+            ClipboardMessage::SendInitiateCopy(formats) => {
+                let allowed = self.filter_formats(DIRECTION, &formats);
+                self.state().local_formats = allowed.clone();
+                Some(ClipboardMessage::SendInitiateCopy(allowed))
+            }
+            ClipboardMessage::SendFormatData(response) => {
+                let format = self.state().local_request.take();
+                let outcome = self.check_data(DIRECTION, format.is_some(), response.data().len());
+                self.audit_format_data(DIRECTION, format, response.data().len(), outcome);
+
+                match outcome {
+                    PolicyOutcome::Allowed => Some(ClipboardMessage::SendFormatData(response)),
+                    PolicyOutcome::Denied(_) => Some(ClipboardMessage::SendFormatData(FormatDataResponse::new_error())),
+                }
+            }
+            ClipboardMessage::SendFileContentsResponse(response) => {
+                let outcome = self.check_file_contents(DIRECTION, response.data().len());
+                self.audit_file_contents(DIRECTION, response.stream_id(), response.data().len(), outcome);
+
+                match outcome {
+                    PolicyOutcome::Allowed => Some(ClipboardMessage::SendFileContentsResponse(response)),
+                    PolicyOutcome::Denied(_) => Some(ClipboardMessage::SendFileContentsResponse(
+                        FileContentsResponse::new_error(response.stream_id()),
+                    )),
+                }
+            }
+            ClipboardMessage::SendInitiatePaste(format_id) => {
+                let outcome = self.check_offered(TransferDirection::RemoteToLocal, format_id);
+                if outcome != PolicyOutcome::Allowed {
+                    self.audit_format_data(TransferDirection::RemoteToLocal, Some(format_id), 0, outcome);
+                    return None;
+                }
+
+                self.state().remote_request = Some(format_id);
+                Some(ClipboardMessage::SendInitiatePaste(format_id))
+            }
+            ClipboardMessage::SendFileContentsRequest(request) => {
+                let outcome = self.check_file_contents(TransferDirection::RemoteToLocal, 0);
+                if outcome != PolicyOutcome::Allowed {
+                    self.audit_file_contents(TransferDirection::RemoteToLocal, request.stream_id, 0, outcome);
+                    return None;
+                }
+
+                Some(ClipboardMessage::SendFileContentsRequest(request))
+            }
+            ClipboardMessage::Error(error) => Some(ClipboardMessage::Error(error)),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, PolicyState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn is_direction_allowed(&self, direction: TransferDirection) -> bool {
+        match direction {
+            TransferDirection::LocalToRemote => !self.deny_local_to_remote,
+            TransferDirection::RemoteToLocal => !self.deny_remote_to_local,
+        }
+    }
+
+    fn filter_formats(&self, direction: TransferDirection, formats: &[ClipboardFormat]) -> Vec<ClipboardFormat> {
+        let mut allowed = Vec::new();
+        let mut denied = Vec::new();
+
+        for format in formats {
+            match self.check_format(direction, format) {
+                PolicyOutcome::Allowed => allowed.push(format.clone()),
+                PolicyOutcome::Denied(reason) => denied.push((format.clone(), reason)),
+            }
+        }
+
+        if !denied.is_empty() {
+            debug!(?direction, ?denied, "Filtered out clipboard formats");
+        }
+
+        self.audit(&ClipboardAuditEvent::FormatList {
+            direction,
+            allowed: allowed.clone(),
+            denied,
+        });
+
+        allowed
+    }
+
+    /// Checks a data request for a format, which must have been offered (and allowed) before.
+    fn check_offered(&self, direction: TransferDirection, format_id: ClipboardFormatId) -> PolicyOutcome {
+        let state = self.state();
+        let offered = match direction {
+            TransferDirection::LocalToRemote => &state.local_formats,
+            TransferDirection::RemoteToLocal => &state.remote_formats,
+        };
+
+        match offered.iter().find(|format| format.id() == format_id) {
+            Some(format) => self.check_format(direction, format),
+            None if !self.is_direction_allowed(direction) => PolicyOutcome::Denied(DenialReason::Direction),
+            None => PolicyOutcome::Denied(DenialReason::Format),
+        }
+    }
+
+    fn check_data(&self, direction: TransferDirection, is_requested: bool, size: usize) -> PolicyOutcome {
+        if !self.is_direction_allowed(direction) {
+            PolicyOutcome::Denied(DenialReason::Direction)
+        } else if !is_requested {
+            PolicyOutcome::Denied(DenialReason::Format)
+        } else if self.max_data_size.is_some_and(|max| size > max) {
+            PolicyOutcome::Denied(DenialReason::Size)
+        } else {
+            PolicyOutcome::Allowed
+        }
+    }
+
+    fn check_file_contents(&self, direction: TransferDirection, size: usize) -> PolicyOutcome {
+        if self.deny_files {
+            PolicyOutcome::Denied(DenialReason::Files)
+        } else {
+            self.check_data(direction, true, size)
+        }
+    }
+
+    fn audit(&self, event: &ClipboardAuditEvent) {
+        for auditor in &self.auditors {
+            auditor.audit(event);
+        }
+    }
+
+    fn audit_format_data(
+        &self,
+        direction: TransferDirection,
+        format: Option<ClipboardFormatId>,
+        size: usize,
+        outcome: PolicyOutcome,
+    ) {
+        if let PolicyOutcome::Denied(reason) = outcome {
+            warn!(?direction, ?format, ?reason, "Denied clipboard format data");
+        }
+
+        self.audit(&ClipboardAuditEvent::FormatData {
+            direction,
+            format,
+            size,
+            outcome,
+        });
+    }
+
+    fn audit_file_contents(&self, direction: TransferDirection, stream_id: u32, size: usize, outcome: PolicyOutcome) {
+        if let PolicyOutcome::Denied(reason) = outcome {
+            warn!(?direction, stream_id, ?reason, "Denied clipboard file contents");
+        }
+
+        self.audit(&ClipboardAuditEvent::FileContents {
+            direction,
+            stream_id,
+            size,
+            outcome,
+        });
+    }
+}
+
+/// Capabilities which enable file transfers.
+const FILE_CAPABILITIES: ClipboardGeneralCapabilityFlags = ClipboardGeneralCapabilityFlags::STREAM_FILECLIP_ENABLED
+    .union(ClipboardGeneralCapabilityFlags::FILECLIP_NO_FILE_PATHS)
+    .union(ClipboardGeneralCapabilityFlags::CAN_LOCK_CLIPDATA);
+
+/// [`CliprdrBackend`] enforcing a [`ClipboardPolicy`] on what the remote sends.
+///
+/// Denied requests of the remote are answered with failure responses sent through `replies`,
+/// without reaching the wrapped backend, while denied data is replaced with failure responses
+/// before reaching it.
+#[derive(Debug)]
+pub struct PolicyBackend {
+    inner: Box<dyn CliprdrBackend>,
+    policy: Arc<ClipboardPolicy>,
+    replies: Box<dyn ClipboardMessageProxy>,
+}
+
+impl_as_any!(PolicyBackend);
+
+impl PolicyBackend {
+    pub fn new(
+        inner: Box<dyn CliprdrBackend>,
+        policy: Arc<ClipboardPolicy>,
+        replies: Box<dyn ClipboardMessageProxy>,
+    ) -> Self {
+        Self { inner, policy, replies }
+    }
+
+    pub fn inner(&self) -> &dyn CliprdrBackend {
+        self.inner.as_ref()
+    }
+
+    pub fn inner_mut(&mut self) -> &mut dyn CliprdrBackend {
+        self.inner.as_mut()
+    }
+
+    fn capabilities(&self, capabilities: ClipboardGeneralCapabilityFlags) -> ClipboardGeneralCapabilityFlags {
+        if self.policy.deny_files {
+            capabilities.difference(FILE_CAPABILITIES)
+        } else {
+            capabilities
+        }
+    }
+}
+
+impl CliprdrBackend for PolicyBackend {
+    fn temporary_directory(&self) -> &str {
+        self.inner.temporary_directory()
+    }
+
+    fn client_capabilities(&self) -> ClipboardGeneralCapabilityFlags {
+        self.capabilities(self.inner.client_capabilities())
+    }
+
+    fn on_request_format_list(&mut self) {
+        self.inner.on_request_format_list();
+    }
+
+    fn on_format_list_received(&mut self) {
+        self.inner.on_format_list_received();
+    }
+
+    fn on_process_negotiated_capabilities(&mut self, capabilities: ClipboardGeneralCapabilityFlags) {
+        let capabilities = self.capabilities(capabilities);
+        self.inner.on_process_negotiated_capabilities(capabilities);
+    }
+
+    fn on_remote_copy(&mut self, available_formats: &[ClipboardFormat]) {
+        let allowed = self
+            .policy
+            .filter_formats(TransferDirection::RemoteToLocal, available_formats);
+        self.policy.state().remote_formats = allowed.clone();
+        self.inner.on_remote_copy(&allowed);
+    }
+
+    fn on_format_data_request(&mut self, request: FormatDataRequest) {
+        const DIRECTION: TransferDirection = TransferDirection::LocalToRemote;
+
+        let outcome = self.policy.check_offered(DIRECTION, request.format);
+
+        if outcome != PolicyOutcome::Allowed {
+            self.policy
+                .audit_format_data(DIRECTION, Some(request.format), 0, outcome);
+            self.replies
+                .send_clipboard_message(ClipboardMessage::SendFormatData(FormatDataResponse::new_error()));
+            return;
+        }
+
+        self.policy.state().local_request = Some(request.format);
+        self.inner.on_format_data_request(request);
+    }
+
+    fn on_format_data_response(&mut self, response: FormatDataResponse<'_>) {
+        const DIRECTION: TransferDirection = TransferDirection::RemoteToLocal;
+
+        let format = self.policy.state().remote_request.take();
+
+        if response.is_error() {
+            self.inner.on_format_data_response(response);
+            return;
+        }
+
+        let outcome = self
+            .policy
+            .check_data(DIRECTION, format.is_some(), response.data().len());
+        self.policy
+            .audit_format_data(DIRECTION, format, response.data().len(), outcome);
+
+        match outcome {
+            PolicyOutcome::Allowed => self.inner.on_format_data_response(response),
+            PolicyOutcome::Denied(_) => self.inner.on_format_data_response(FormatDataResponse::new_error()),
+        }
+    }
+
+    fn on_file_contents_request(&mut self, request: FileContentsRequest) {
+        const DIRECTION: TransferDirection = TransferDirection::LocalToRemote;
+
+        let outcome = self.policy.check_file_contents(DIRECTION, 0);
+
+        if outcome != PolicyOutcome::Allowed {
+            self.policy
+                .audit_file_contents(DIRECTION, request.stream_id, 0, outcome);
+            self.replies
+                .send_clipboard_message(ClipboardMessage::SendFileContentsResponse(
+                    FileContentsResponse::new_error(request.stream_id),
+                ));
+            return;
+        }
+
+        self.inner.on_file_contents_request(request);
+    }
+
+    fn on_file_contents_response(&mut self, response: FileContentsResponse<'_>) {
+        const DIRECTION: TransferDirection = TransferDirection::RemoteToLocal;
+
+        if response.is_error() {
+            self.inner.on_file_contents_response(response);
+            return;
+        }
+
+        let outcome = self.policy.check_file_contents(DIRECTION, response.data().len());
+        self.policy
+            .audit_file_contents(DIRECTION, response.stream_id(), response.data().len(), outcome);
+
+        match outcome {
+            PolicyOutcome::Allowed => self.inner.on_file_contents_response(response),
+            PolicyOutcome::Denied(_) => self
+                .inner
+                .on_file_contents_response(FileContentsResponse::new_error(response.stream_id())),
+        }
+    }
+
+    fn on_lock(&mut self, data_id: LockDataId) {
+        self.inner.on_lock(data_id);
+    }
+
+    fn on_unlock(&mut self, data_id: LockDataId) {
+        self.inner.on_unlock(data_id);
+    }
+}
+
+/// [`ClipboardMessageProxy`] enforcing a [`ClipboardPolicy`] on what the backend sends, see
+/// [`ClipboardPolicy::filter_message`].
+#[derive(Debug)]
+pub struct PolicyMessageProxy<P> {
+    inner: P,
+    policy: Arc<ClipboardPolicy>,
+}
+
+impl<P: ClipboardMessageProxy> PolicyMessageProxy<P> {
+    pub fn new(inner: P, policy: Arc<ClipboardPolicy>) -> Self {
+        Self { inner, policy }
+    }
+}
+
+impl<P: ClipboardMessageProxy> ClipboardMessageProxy for PolicyMessageProxy<P> {
+    fn send_clipboard_message(&self, message: ClipboardMessage) {
+        if let Some(message) = self.policy.filter_message(message) {
+            self.inner.send_clipboard_message(message);
+        }
+    }
+}
+
+/// [`CliprdrBackendFactory`] wrapping the built backends into [`PolicyBackend`]s.
+pub struct PolicyBackendFactory<F, P> {
+    inner: F,
+    policy: Arc<ClipboardPolicy>,
+    replies: P,
+}
+
+impl<F, P> PolicyBackendFactory<F, P>
+where
+    F: CliprdrBackendFactory,
+    P: ClipboardMessageProxy + Clone + 'static,
+{
+    /// `replies` is used to answer denied requests of the remote, usually the proxy given to the
+    /// backend, without the [`PolicyMessageProxy`] wrapper.
+    pub fn new(inner: F, policy: Arc<ClipboardPolicy>, replies: P) -> Self {
+        Self { inner, policy, replies }
+    }
+}
+
+impl<F, P> CliprdrBackendFactory for PolicyBackendFactory<F, P>
+where
+    F: CliprdrBackendFactory,
+    P: ClipboardMessageProxy + Clone + 'static,
+{
+    fn build_cliprdr_backend(&self) -> Box<dyn CliprdrBackend> {
+        Box::new(PolicyBackend::new(
+            self.inner.build_cliprdr_backend(),
+            Arc::clone(&self.policy),
+            Box::new(self.replies.clone()),
+        ))
+    }
+}
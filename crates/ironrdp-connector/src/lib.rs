@@ -49,6 +49,19 @@ pub struct BitmapConfig {
     pub color_depth: u32,
 }
 
+/// Virtual channel used to redirect audio output (MS-RDPEA)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum AudioPlaybackTransport {
+    /// The `rdpsnd` static virtual channel
+    #[default]
+    Static,
+    /// The `AUDIO_PLAYBACK_DVC` dynamic virtual channel
+    Dynamic,
+    /// The `AUDIO_PLAYBACK_LOSSY_DVC` dynamic virtual channel, intended to be used over UDP transport
+    DynamicLossy,
+}
+
 #[derive(Debug, Clone)]
 pub struct SmartCardIdentity {
     /// DER-encoded X509 certificate
@@ -170,6 +183,8 @@ pub struct Config {
     pub no_server_pointer: bool,
     pub pointer_software_rendering: bool,
     pub performance_flags: PerformanceFlags,
+    /// Whether audio output should be received over the static or the dynamic virtual channel
+    pub audio_playback_transport: AudioPlaybackTransport,
 }
 
 ironrdp_core::assert_impl!(Config: Send, Sync);
@@ -141,6 +141,9 @@ impl SvcProcessor for DrdynvcClient {
             }
             DrdynvcServerPdu::Close(close_request) => {
                 debug!("Got DVC Close Request PDU: {close_request:?}");
+                if let Some(dynamic_channel) = self.dynamic_channels.get_by_channel_id_mut(&close_request.channel_id) {
+                    dynamic_channel.close();
+                }
                 self.dynamic_channels.remove_by_channel_id(&close_request.channel_id);
 
                 let close_response = DrdynvcClientPdu::Close(ClosePdu::new(close_request.channel_id));
@@ -151,6 +151,12 @@ impl DynamicVirtualChannel {
         }
     }
 
+    fn close(&mut self) {
+        if let Some(channel_id) = self.channel_id.take() {
+            self.channel_processor.close(channel_id);
+        }
+    }
+
     fn channel_name(&self) -> &str {
         self.channel_processor.channel_name()
     }
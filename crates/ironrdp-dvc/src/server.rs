@@ -158,6 +158,7 @@ impl SvcProcessor for DrdynvcServer {
                     return Err(pdu_other_err!("invalid channel state"));
                 }
                 c.state = ChannelState::Closed;
+                c.processor.close(close_resp.channel_id);
             }
             DrdynvcClientPdu::Data(data) => {
                 let channel_id = data.channel_id();
@@ -89,7 +89,11 @@ pub fn pdu_decode(data: &[u8]) {
     let _ = decode::<ironrdp_displaycontrol::pdu::DisplayControlPdu>(data);
 
     let _ = decode::<ironrdp_rdpsnd::pdu::ServerAudioOutputPdu<'_>>(data);
+    let _ = decode::<ironrdp_rdpsnd::pdu::ServerAudioOutputUdpPdu<'_>>(data);
     let _ = decode::<ironrdp_rdpsnd::pdu::ClientAudioOutputPdu>(data);
+
+    let _ = decode::<ironrdp_audin::pdu::ServerAudioInputPdu>(data);
+    let _ = decode::<ironrdp_audin::pdu::ClientAudioInputPdu<'_>>(data);
 }
 
 pub fn rle_decompress_bitmap(input: BitmapInput<'_>) {
@@ -135,6 +139,8 @@ pub fn rdp6_decode_bitmap_stream_to_rgb24(input: &BitmapInput<'_>) {
 pub fn cliprdr_format(input: &[u8]) {
     use ironrdp_cliprdr_format::bitmap::{dib_to_png, dibv5_to_png, png_to_cf_dib, png_to_cf_dibv5};
     use ironrdp_cliprdr_format::html::{cf_html_to_plain_html, plain_html_to_cf_html};
+    use ironrdp_cliprdr_format::rtf::{plain_text_to_rtf, rtf_to_plain_text};
+    use ironrdp_cliprdr_format::text::{cf_text_to_string, cf_unicodetext_to_string, string_to_cf_text, CodePage};
 
     let _ = png_to_cf_dib(input);
     let _ = png_to_cf_dibv5(input);
@@ -144,8 +150,14 @@ pub fn cliprdr_format(input: &[u8]) {
 
     let _ = cf_html_to_plain_html(input);
 
+    let _ = cf_unicodetext_to_string(input);
+    let _ = cf_text_to_string(input, CodePage::WINDOWS_1252);
+    let _ = rtf_to_plain_text(input);
+
     if let Ok(input) = core::str::from_utf8(input) {
         let _ = plain_html_to_cf_html(input);
+        let _ = string_to_cf_text(input, CodePage::IBM437);
+        let _ = plain_text_to_rtf(input);
     }
 }
 
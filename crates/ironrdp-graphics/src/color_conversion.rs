@@ -1,10 +1,25 @@
 use std::cmp::min;
 use std::io::{self, Write};
 
+use wide::{i16x8, i32x8};
+
 use crate::image_processing::PixelFormat;
 
 const ALPHA: u8 = 255;
 
+// We scale the factors of the RGB to YCbCr conversion by << 15 into 32-bit integers in order to avoid slower floating
+// point multiplications.  Since the terms need to be scaled by << 5 we simply scale the final sum by >> 10
+const YCBCR_DIVISOR: f32 = (1 << 15) as f32;
+const Y_R: i32 = (0.299 * YCBCR_DIVISOR) as i32;
+const Y_G: i32 = (0.587 * YCBCR_DIVISOR) as i32;
+const Y_B: i32 = (0.114 * YCBCR_DIVISOR) as i32;
+const CB_R: i32 = (0.168_935 * YCBCR_DIVISOR) as i32;
+const CB_G: i32 = (0.331_665 * YCBCR_DIVISOR) as i32;
+const CB_B: i32 = (0.500_59 * YCBCR_DIVISOR) as i32;
+const CR_R: i32 = (0.499_813 * YCBCR_DIVISOR) as i32;
+const CR_G: i32 = (0.418_531 * YCBCR_DIVISOR) as i32;
+const CR_B: i32 = (0.081_282 * YCBCR_DIVISOR) as i32;
+
 pub fn ycbcr_to_bgra(input: YCbCrBuffer<'_>, mut output: &mut [u8]) -> io::Result<()> {
     for ycbcr in input {
         let pixel = Rgb::from(ycbcr);
@@ -93,86 +108,157 @@ pub fn to_ycbcr(
     }
 }
 
-struct TileIterator<'a> {
-    slice: &'a [u8],
-    x: usize,
-    y: usize,
+#[allow(clippy::too_many_arguments)]
+pub fn to_64x64_ycbcr_tile(
+    input: &[u8],
     width: usize,
     height: usize,
     stride: usize,
-    bpp: usize,
+    format: PixelFormat,
+    y: &mut [i16; 64 * 64],
+    cb: &mut [i16; 64 * 64],
+    cr: &mut [i16; 64 * 64],
+) {
+    assert!((1..=64).contains(&width));
+    assert!((1..=64).contains(&height));
+
+    let bpp = format.bytes_per_pixel() as usize;
+    let tile = Tile {
+        input,
+        width,
+        height,
+        stride,
+        bpp,
+    };
+
+    match format {
+        PixelFormat::ARgb32 | PixelFormat::XRgb32 => tile.to_ycbcr(y, cb, cr, xrgb_to_rgb),
+        PixelFormat::ABgr32 | PixelFormat::XBgr32 => tile.to_ycbcr(y, cb, cr, xbgr_to_rgb),
+        PixelFormat::BgrA32 | PixelFormat::BgrX32 => tile.to_ycbcr(y, cb, cr, bgrx_to_rgb),
+        PixelFormat::RgbA32 | PixelFormat::RgbX32 => tile.to_ycbcr(y, cb, cr, rgbx_to_rgb),
+    };
 }
 
-impl<'a> TileIterator<'a> {
-    fn new(slice: &'a [u8], width: usize, height: usize, stride: usize, bpp: usize) -> Self {
-        assert!(width >= 1);
-        assert!(height >= 1);
+struct Tile<'a> {
+    input: &'a [u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+    bpp: usize,
+}
 
-        Self {
-            slice,
-            x: 0,
-            y: 0,
-            width,
-            height,
-            stride,
-            bpp,
+impl Tile<'_> {
+    fn to_ycbcr<C>(&self, y: &mut [i16; 64 * 64], cb: &mut [i16; 64 * 64], cr: &mut [i16; 64 * 64], conv: C)
+    where
+        C: Fn(&[u8]) -> Rgb,
+    {
+        let mut r = [0; 64];
+        let mut g = [0; 64];
+        let mut b = [0; 64];
+
+        for row in 0..64 {
+            // repeat the last column & line if necessary
+            let line = &self.input[min(row, self.height - 1) * self.stride..];
+            for col in 0..64 {
+                let pos = min(col, self.width - 1) * self.bpp;
+                let pixel = conv(&line[pos..pos + self.bpp]);
+
+                r[col] = i16::from(pixel.r);
+                g[col] = i16::from(pixel.g);
+                b[col] = i16::from(pixel.b);
+            }
+
+            let samples = row * 64..(row + 1) * 64;
+            rgb_to_ycbcr(
+                &r,
+                &g,
+                &b,
+                &mut y[samples.clone()],
+                &mut cb[samples.clone()],
+                &mut cr[samples],
+            );
         }
     }
 }
 
-impl<'a> Iterator for TileIterator<'a> {
-    type Item = &'a [u8];
+/// Converts planar RGB samples to YCbCr, 8 at a time, with the same results as `YCbCr::from(Rgb)`.
+fn rgb_to_ycbcr(r: &[i16], g: &[i16], b: &[i16], y: &mut [i16], cb: &mut [i16], cr: &mut [i16]) {
+    assert_eq!(r.len() % 8, 0);
 
-    fn next(&mut self) -> Option<Self::Item> {
-        // create 64x64 tiles
-        if self.y >= 64 {
-            return None;
-        }
+    let factor = |factor: i32| i16x8::splat(factor as i16);
+    let clamp = |value: i32x8| i16x8::from_i32x8_truncate(value.max(i32x8::splat(-4096)).min(i32x8::splat(4095)));
 
-        // repeat the last column & line if necessary
-        let y = min(self.y, self.height - 1);
-        let x = min(self.x, self.width - 1);
-        let pos = y * self.stride + x * self.bpp;
+    for i in (0..r.len()).step_by(8) {
+        let r = i16x8::from_slice_unaligned(&r[i..]);
+        let g = i16x8::from_slice_unaligned(&g[i..]);
+        let b = i16x8::from_slice_unaligned(&b[i..]);
 
-        self.x += 1;
-        if self.x >= 64 {
-            self.x = 0;
-            self.y += 1;
-        }
+        let luma = (r.mul_widen(factor(Y_R)) + g.mul_widen(factor(Y_G)) + b.mul_widen(factor(Y_B))) >> 10;
+        let blue = (b.mul_widen(factor(CB_B)) - g.mul_widen(factor(CB_G)) - r.mul_widen(factor(CB_R))) >> 10;
+        let red = (r.mul_widen(factor(CR_R)) - g.mul_widen(factor(CR_G)) - b.mul_widen(factor(CR_B))) >> 10;
 
-        Some(&self.slice[pos..pos + self.bpp])
+        y[i..i + 8].copy_from_slice(clamp(luma - i32x8::splat(4096)).as_array_ref());
+        cb[i..i + 8].copy_from_slice(clamp(blue).as_array_ref());
+        cr[i..i + 8].copy_from_slice(clamp(red).as_array_ref());
     }
 }
 
-impl ExactSizeIterator for TileIterator<'_> {
-    fn len(&self) -> usize {
-        64 * 64
-    }
+/// Planes of a YUV 4:2:0 image, as consumed by H.264 encoders
+///
+/// The chroma planes have one sample per 2x2 block of pixels.
+#[derive(Debug)]
+pub struct Yuv420Buffer<'a> {
+    pub y: &'a mut [u8],
+    pub u: &'a mut [u8],
+    pub v: &'a mut [u8],
+    pub y_stride: usize,
+    pub uv_stride: usize,
 }
 
-#[allow(clippy::too_many_arguments)]
-pub fn to_64x64_ycbcr_tile(
+/// Converts an image to full range BT.709 YUV 4:2:0, as expected by the AVC420 codec of the graphics pipeline.
+///
+/// The chroma samples are the average of their 2x2 block. When `width` or `height` is odd, the last column or row is
+/// repeated to complete the blocks.
+pub fn to_yuv420(
     input: &[u8],
     width: usize,
     height: usize,
     stride: usize,
     format: PixelFormat,
-    y: &mut [i16; 64 * 64],
-    cb: &mut [i16; 64 * 64],
-    cr: &mut [i16; 64 * 64],
+    output: Yuv420Buffer<'_>,
 ) {
-    assert!(width <= 64);
-    assert!(height <= 64);
+    if width == 0 || height == 0 {
+        return;
+    }
 
+    let to_rgb = pixel_format_to_rgb_fn(format);
     let bpp = format.bytes_per_pixel() as usize;
 
-    let input = TileIterator::new(input, width, height, stride, bpp);
-    match format {
-        PixelFormat::ARgb32 | PixelFormat::XRgb32 => iter_to_ycbcr(input, y, cb, cr, xrgb_to_rgb),
-        PixelFormat::ABgr32 | PixelFormat::XBgr32 => iter_to_ycbcr(input, y, cb, cr, xbgr_to_rgb),
-        PixelFormat::BgrA32 | PixelFormat::BgrX32 => iter_to_ycbcr(input, y, cb, cr, bgrx_to_rgb),
-        PixelFormat::RgbA32 | PixelFormat::RgbX32 => iter_to_ycbcr(input, y, cb, cr, rgbx_to_rgb),
-    };
+    for row in (0..height).step_by(2) {
+        let rows = [row, min(row + 1, height - 1)];
+
+        for col in (0..width).step_by(2) {
+            let cols = [col, min(col + 1, width - 1)];
+            let mut u = 0;
+            let mut v = 0;
+
+            for row in rows {
+                for col in cols {
+                    let pos = row * stride + col * bpp;
+                    let Rgb { r, g, b } = to_rgb(&input[pos..pos + bpp]);
+                    let (r, g, b) = (i32::from(r), i32::from(g), i32::from(b));
+
+                    output.y[row * output.y_stride + col] = ((54 * r + 183 * g + 18 * b) >> 8) as u8;
+                    u += ((-29 * r - 99 * g + 128 * b) >> 8) + 128;
+                    v += ((128 * r - 116 * g - 12 * b) >> 8) + 128;
+                }
+            }
+
+            let pos = row / 2 * output.uv_stride + col / 2;
+            output.u[pos] = clip(u / 4);
+            output.v[pos] = clip(v / 4);
+        }
+    }
 }
 
 /// Convert a 16-bit RDP color to RGB representation. Input value should be represented in
@@ -259,21 +345,6 @@ impl From<YCbCr> for Rgb {
 
 impl From<Rgb> for YCbCr {
     fn from(Rgb { r, g, b }: Rgb) -> Self {
-        // We scale the factors by << 15 into 32-bit integers in order
-        // to avoid slower floating point multiplications.  Since the
-        // terms need to be scaled by << 5 we simply scale the final
-        // sum by >> 10
-        const DIVISOR: f32 = (1 << 15) as f32;
-        const Y_R: i32 = (0.299 * DIVISOR) as i32;
-        const Y_G: i32 = (0.587 * DIVISOR) as i32;
-        const Y_B: i32 = (0.114 * DIVISOR) as i32;
-        const CB_R: i32 = (0.168_935 * DIVISOR) as i32;
-        const CB_G: i32 = (0.331_665 * DIVISOR) as i32;
-        const CB_B: i32 = (0.500_59 * DIVISOR) as i32;
-        const CR_R: i32 = (0.499_813 * DIVISOR) as i32;
-        const CR_G: i32 = (0.418_531 * DIVISOR) as i32;
-        const CR_B: i32 = (0.081_282 * DIVISOR) as i32;
-
         let r = i32::from(r);
         let g = i32::from(g);
         let b = i32::from(b);
@@ -1,4 +1,5 @@
 use ironrdp_pdu::utils::SplitTo as _;
+use wide::{i16x8, i32x8};
 
 pub fn encode(buffer: &mut [i16], temp_buffer: &mut [i16]) {
     encode_block::<32>(&mut *buffer, temp_buffer);
@@ -12,27 +13,36 @@ fn encode_block<const SUBBAND_WIDTH: usize>(buffer: &mut [i16], temp_buffer: &mu
 }
 
 // DWT in vertical direction, results in 2 sub-bands in L, H order in tmp buffer dwt.
+// Columns are independent, so they are transformed 8 at a time.
 fn dwt_vertical<const SUBBAND_WIDTH: usize>(buffer: &[i16], dwt: &mut [i16]) {
     let total_width = SUBBAND_WIDTH * 2;
+    let load = |index: usize| i32x8::from_i16x8(i16x8::from_slice_unaligned(&buffer[index..]));
+
+    for x in (0..total_width).step_by(8) {
+        let mut previous_h = i32x8::default();
 
-    for x in 0..total_width {
         for n in 0..SUBBAND_WIDTH {
             let y = n * 2;
             let l_index = n * total_width + x;
             let h_index = l_index + SUBBAND_WIDTH * total_width;
             let src_index = y * total_width + x;
 
-            dwt[h_index] = ((i32::from(buffer[src_index + total_width])
-                - ((i32::from(buffer[src_index])
-                    + i32::from(buffer[src_index + if n < SUBBAND_WIDTH - 1 { 2 * total_width } else { 0 }]))
-                    >> 1))
-                >> 1) as i16;
-            dwt[l_index] = (i32::from(buffer[src_index])
-                + if n == 0 {
-                    i32::from(dwt[h_index])
-                } else {
-                    (i32::from(dwt[h_index - total_width]) + i32::from(dwt[h_index])) >> 1
-                }) as i16;
+            let even = load(src_index);
+            let odd = load(src_index + total_width);
+            let next = if n < SUBBAND_WIDTH - 1 {
+                load(src_index + 2 * total_width)
+            } else {
+                even
+            };
+
+            let h = i16x8::from_i32x8_truncate((odd - ((even + next) >> 1)) >> 1);
+            let h_wide = i32x8::from_i16x8(h);
+            let l = even + if n == 0 { h_wide } else { (previous_h + h_wide) >> 1 };
+
+            dwt[h_index..h_index + 8].copy_from_slice(h.as_array_ref());
+            dwt[l_index..l_index + 8].copy_from_slice(i16x8::from_i32x8_truncate(l).as_array_ref());
+
+            previous_h = h_wide;
         }
     }
 }
@@ -186,3 +196,66 @@ fn inverse_vertical(mut buffer: &mut [i16], mut temp_buffer: &[i16], subband_wid
         buffer = &mut buffer[1..];
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scalar_dwt_vertical<const SUBBAND_WIDTH: usize>(buffer: &[i16], dwt: &mut [i16]) {
+        let total_width = SUBBAND_WIDTH * 2;
+
+        for x in 0..total_width {
+            for n in 0..SUBBAND_WIDTH {
+                let y = n * 2;
+                let l_index = n * total_width + x;
+                let h_index = l_index + SUBBAND_WIDTH * total_width;
+                let src_index = y * total_width + x;
+
+                dwt[h_index] = ((i32::from(buffer[src_index + total_width])
+                    - ((i32::from(buffer[src_index])
+                        + i32::from(buffer[src_index + if n < SUBBAND_WIDTH - 1 { 2 * total_width } else { 0 }]))
+                        >> 1))
+                    >> 1) as i16;
+                dwt[l_index] = (i32::from(buffer[src_index])
+                    + if n == 0 {
+                        i32::from(dwt[h_index])
+                    } else {
+                        (i32::from(dwt[h_index - total_width]) + i32::from(dwt[h_index])) >> 1
+                    }) as i16;
+            }
+        }
+    }
+
+    fn pseudo_random_buffer(seed: u32, min: i16, max: i16) -> Vec<i16> {
+        let range = i32::from(max) - i32::from(min) + 1;
+        let mut state = seed;
+
+        (0..64 * 64)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (i32::from(min) + (state >> 8) as i32 % range) as i16
+            })
+            .collect()
+    }
+
+    fn assert_vertical_matches_scalar<const SUBBAND_WIDTH: usize>(buffer: &[i16]) {
+        let mut expected = vec![0; 64 * 64];
+        let mut actual = vec![0; 64 * 64];
+
+        scalar_dwt_vertical::<SUBBAND_WIDTH>(buffer, &mut expected);
+        dwt_vertical::<SUBBAND_WIDTH>(buffer, &mut actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn vertical_dwt_matches_scalar_implementation() {
+        for (seed, (min, max)) in [(-4096, 4095), (-32768, 32767), (0, 255)].into_iter().enumerate() {
+            let buffer = pseudo_random_buffer(seed as u32, min, max);
+
+            assert_vertical_matches_scalar::<32>(&buffer);
+            assert_vertical_matches_scalar::<16>(&buffer);
+            assert_vertical_matches_scalar::<8>(&buffer);
+        }
+    }
+}
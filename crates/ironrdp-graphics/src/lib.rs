@@ -22,17 +22,18 @@ pub mod zgfx;
 
 mod utils;
 
+/// Encodes a 64x64 tile component, using `temp` as scratch space for the DWT.
 pub fn rfx_encode_component(
     input: &mut [i16],
     output: &mut [u8],
+    temp: &mut [i16],
     quant: &ironrdp_pdu::codecs::rfx::Quant,
     mode: ironrdp_pdu::codecs::rfx::EntropyAlgorithm,
 ) -> Result<usize, rlgr::RlgrError> {
     assert_eq!(input.len(), 64 * 64);
+    assert_eq!(temp.len(), 64 * 64);
 
-    let mut temp = [0; 64 * 64]; // size = 8k, too big?
-
-    dwt::encode(input, temp.as_mut_slice());
+    dwt::encode(input, temp);
     quantization::encode(input, quant);
     subband_reconstruction::encode(&mut input[4032..]);
     rlgr::encode(mode, input, output)
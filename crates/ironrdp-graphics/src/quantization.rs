@@ -1,4 +1,5 @@
 use ironrdp_pdu::codecs::rfx::Quant;
+use wide::i16x8;
 
 const FIRST_LEVEL_SIZE: usize = 1024;
 const SECOND_LEVEL_SIZE: usize = 256;
@@ -39,8 +40,10 @@ fn decode_block(buffer: &mut [i16], factor: i16) {
 
 fn encode_block(buffer: &mut [i16], factor: i16) {
     if factor > 0 {
-        for value in buffer {
-            *value >>= factor;
+        // Sub-band sizes are multiples of 8
+        for values in buffer.chunks_exact_mut(8) {
+            let quantized = i16x8::from_slice_unaligned(values) >> factor;
+            values.copy_from_slice(quantized.as_array_ref());
         }
     }
 }
@@ -113,6 +116,19 @@ mod tests {
         assert_eq!(expected, buffer.as_ref());
     }
 
+    #[test]
+    fn encode_block_matches_scalar_shift() {
+        let values = (i16::MIN..=i16::MAX).step_by(7).take(1024).collect::<Vec<_>>();
+
+        for factor in 1..15 {
+            let mut buffer = values.clone();
+            encode_block(&mut buffer, factor);
+
+            let expected = values.iter().map(|value| value >> factor).collect::<Vec<_>>();
+            assert_eq!(expected, buffer);
+        }
+    }
+
     #[test]
     fn decode_does_not_change_buffer_with_null_quant_values() {
         let mut buffer = QUANTIZED_BUFFER;
@@ -59,8 +59,13 @@ impl<'a> BitStream<'a> {
         self.idx += num_bits;
     }
 
-    fn len(&self) -> usize {
-        self.idx.div_ceil(8)
+    /// Clears the padding bits of the last byte, and returns the length of the stream in bytes.
+    ///
+    /// The output may be a reused buffer, holding stale data.
+    fn finish(self) -> usize {
+        let len = self.idx.div_ceil(8);
+        self.bits[self.idx..len * 8].fill(false);
+        len
     }
 }
 
@@ -141,7 +146,7 @@ pub fn encode(mode: EntropyAlgorithm, input: &[i16], tile: &mut [u8]) -> Result<
         }
     }
 
-    Ok(bits.len())
+    Ok(bits.finish())
 }
 
 fn get_2magsign(val: i16) -> u32 {
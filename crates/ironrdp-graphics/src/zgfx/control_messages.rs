@@ -1,6 +1,6 @@
 use bit_field::BitField;
 use bitflags::bitflags;
-use byteorder::{LittleEndian, ReadBytesExt as _};
+use byteorder::{LittleEndian, ReadBytesExt as _, WriteBytesExt as _};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive as _;
 
@@ -64,6 +64,40 @@ impl<'a> BulkEncodedData<'a> {
     }
 }
 
+/// Maximum size of the data of a single bulk-encoded segment
+const MAX_SEGMENT_SIZE: usize = 65535;
+
+/// Wraps `data` into an uncompressed RDP_SEGMENTED_DATA structure, as sent over the graphics pipeline channel
+///
+/// Data larger than a single segment is split into a multipart structure. Returns the number of bytes written.
+pub fn wrap_uncompressed(data: &[u8], output: &mut Vec<u8>) -> Result<usize, ZgfxError> {
+    let start = output.len();
+    let bulk_header = CompressionType::Rdp8 as u8;
+
+    if data.len() <= MAX_SEGMENT_SIZE {
+        output.write_u8(SegmentedDescriptor::Single as u8)?;
+        output.write_u8(bulk_header)?;
+        output.extend_from_slice(data);
+    } else {
+        let segment_count =
+            u16::try_from(data.len().div_ceil(MAX_SEGMENT_SIZE)).map_err(|_| ZgfxError::DataTooLarge)?;
+        let uncompressed_size = u32::try_from(data.len()).map_err(|_| ZgfxError::DataTooLarge)?;
+
+        output.write_u8(SegmentedDescriptor::Multipart as u8)?;
+        output.write_u16::<LittleEndian>(segment_count)?;
+        output.write_u32::<LittleEndian>(uncompressed_size)?;
+
+        for segment in data.chunks(MAX_SEGMENT_SIZE) {
+            // The size includes the bulk header byte, and fits since segments are limited to 65535 bytes
+            output.write_u32::<LittleEndian>(u32::try_from(segment.len() + 1).expect("segment size fits in u32"))?;
+            output.write_u8(bulk_header)?;
+            output.extend_from_slice(segment);
+        }
+    }
+
+    Ok(output.len() - start)
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, FromPrimitive)]
 enum SegmentedDescriptor {
     Single = 0xe0,
@@ -145,6 +179,45 @@ mod test {
         );
     }
 
+    #[test]
+    fn wrap_uncompressed_round_trips_single_segment() {
+        let data = b"The quick brown fox jumps over the lazy dog";
+
+        let mut buffer = Vec::new();
+        let written = wrap_uncompressed(data, &mut buffer).unwrap();
+
+        assert_eq!(written, data.len() + 2);
+        assert_eq!(
+            SegmentedDataPdu::from_buffer(&buffer).unwrap(),
+            SegmentedDataPdu::Single(BulkEncodedData {
+                compression_flags: CompressionFlags::empty(),
+                data,
+            })
+        );
+    }
+
+    #[test]
+    fn wrap_uncompressed_splits_large_data_into_segments() {
+        let data = (0..=u8::MAX)
+            .cycle()
+            .take(MAX_SEGMENT_SIZE * 2 + 10)
+            .collect::<Vec<_>>();
+
+        let mut buffer = Vec::new();
+        wrap_uncompressed(&data, &mut buffer).unwrap();
+
+        let SegmentedDataPdu::Multipart {
+            uncompressed_size,
+            segments,
+        } = SegmentedDataPdu::from_buffer(&buffer).unwrap()
+        else {
+            panic!("expected a multipart PDU");
+        };
+        assert_eq!(uncompressed_size, data.len());
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments.iter().flat_map(|s| s.data).copied().collect::<Vec<_>>(), data);
+    }
+
     #[test]
     fn from_buffer_correctly_parses_zgfx_multipart_segmented_data_pdu() {
         let buffer = MULTIPART_SEGMENTED_DATA_PDU_BUFFER.as_ref();
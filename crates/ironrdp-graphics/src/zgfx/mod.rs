@@ -13,6 +13,7 @@ use byteorder::WriteBytesExt;
 use thiserror::Error;
 
 use self::circular_buffer::FixedCircularBuffer;
+pub use self::control_messages::wrap_uncompressed;
 use self::control_messages::{BulkEncodedData, CompressionFlags, SegmentedDataPdu};
 use crate::utils::Bits;
 
@@ -445,6 +446,8 @@ pub enum ZgfxError {
     },
     #[error("token bits not found")]
     TokenBitsNotFound,
+    #[error("data is too large for a segmented data PDU")]
+    DataTooLarge,
 }
 
 #[cfg(test)]
@@ -483,6 +486,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn zgfx_decompresses_wrapped_uncompressed_data() {
+        let data = "The quick brown fox jumps over the lazy dog".repeat(2000);
+
+        let mut wrapped = Vec::new();
+        wrap_uncompressed(data.as_bytes(), &mut wrapped).unwrap();
+
+        let mut zgfx = Decompressor::new();
+        let mut decompressed = Vec::new();
+        zgfx.decompress(&wrapped, &mut decompressed).unwrap();
+        assert_eq!(decompressed, data.as_bytes());
+    }
+
     #[test]
     fn zgfx_decopresses_only_one_literal() {
         let buffer = [0b1100_1000, 0x03];
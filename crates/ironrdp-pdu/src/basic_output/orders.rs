@@ -0,0 +1,203 @@
+#[cfg(test)]
+mod tests;
+
+use bitflags::bitflags;
+
+use ironrdp_core::{cast_length, ensure_fixed_part_size, ensure_size, invalid_field_err, ReadCursor, WriteCursor};
+use ironrdp_core::{Decode, DecodeResult, Encode, EncodeResult};
+
+/// Raster operation copying the source to the destination (SRCCOPY)
+pub const ROP_SRCCOPY: u8 = 0xCC;
+
+const SCR_BLT_ORDER_TYPE: u8 = 0x02;
+
+// TS_FP_UPDATE_ORDERS
+//
+// Only primary drawing orders carrying all their fields are supported: each order is self-contained, and doesn't
+// depend on the order state kept by the client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DrawingOrdersPdu {
+    pub orders: Vec<PrimaryDrawingOrder>,
+}
+
+impl DrawingOrdersPdu {
+    const NAME: &'static str = "TS_FP_UPDATE_ORDERS";
+    const FIXED_PART_SIZE: usize = 2 /* numberOrders */;
+}
+
+impl Encode for DrawingOrdersPdu {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(in: dst, size: self.size());
+
+        dst.write_u16(cast_length!("numberOrders", self.orders.len())?);
+        for order in &self.orders {
+            order.encode(dst)?;
+        }
+
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE + self.orders.iter().map(Encode::size).sum::<usize>()
+    }
+}
+
+impl<'de> Decode<'de> for DrawingOrdersPdu {
+    fn decode(src: &mut ReadCursor<'de>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let number_orders = src.read_u16();
+        let orders = (0..number_orders)
+            .map(|_| PrimaryDrawingOrder::decode(src))
+            .collect::<DecodeResult<_>>()?;
+
+        Ok(Self { orders })
+    }
+}
+
+// Primary drawing order (MS-RDPEGDI 2.2.2.2.1.1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrimaryDrawingOrder {
+    ScrBlt(ScrBltOrder),
+}
+
+impl PrimaryDrawingOrder {
+    const NAME: &'static str = "PRIMARY_DRAWING_ORDER";
+    const FIXED_PART_SIZE: usize = 1 /* controlFlags */ + 1 /* orderType */ + 1 /* fieldFlags */;
+}
+
+impl Encode for PrimaryDrawingOrder {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(in: dst, size: self.size());
+
+        dst.write_u8((ControlFlags::STANDARD | ControlFlags::TYPE_CHANGE).bits());
+        match self {
+            Self::ScrBlt(order) => {
+                dst.write_u8(SCR_BLT_ORDER_TYPE);
+                dst.write_u8(ScrBltOrder::FIELD_FLAGS);
+                order.encode(dst)
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE
+            + match self {
+                Self::ScrBlt(order) => order.size(),
+            }
+    }
+}
+
+impl<'de> Decode<'de> for PrimaryDrawingOrder {
+    fn decode(src: &mut ReadCursor<'de>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let control_flags = ControlFlags::from_bits_retain(src.read_u8());
+        if control_flags != ControlFlags::STANDARD | ControlFlags::TYPE_CHANGE {
+            return Err(invalid_field_err!(
+                "controlFlags",
+                "only primary orders with their type and no bounds nor delta coordinates are supported"
+            ));
+        }
+
+        let order_type = src.read_u8();
+        let field_flags = src.read_u8();
+        match order_type {
+            SCR_BLT_ORDER_TYPE => {
+                if field_flags != ScrBltOrder::FIELD_FLAGS {
+                    return Err(invalid_field_err!(
+                        "fieldFlags",
+                        "only ScrBlt orders with all fields are supported"
+                    ));
+                }
+
+                Ok(Self::ScrBlt(ScrBltOrder::decode(src)?))
+            }
+            _ => Err(invalid_field_err!("orderType", "unsupported primary order")),
+        }
+    }
+}
+
+// SCRBLT_ORDER (MS-RDPEGDI 2.2.2.2.1.1.2.7)
+//
+// Copies the screen rectangle at (`src_x`, `src_y`) to the destination rectangle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScrBltOrder {
+    pub left: i16,
+    pub top: i16,
+    pub width: i16,
+    pub height: i16,
+    pub rop: u8,
+    pub src_x: i16,
+    pub src_y: i16,
+}
+
+impl ScrBltOrder {
+    const NAME: &'static str = "SCRBLT_ORDER";
+    const FIXED_PART_SIZE: usize = 2 /* nLeftRect */ + 2 /* nTopRect */ + 2 /* nWidth */ + 2 /* nHeight */ + 1 /* bRop */ + 2 /* nXSrc */ + 2 /* nYSrc */;
+
+    /// All seven fields are present
+    const FIELD_FLAGS: u8 = 0x7F;
+}
+
+impl Encode for ScrBltOrder {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_fixed_part_size!(in: dst);
+
+        dst.write_i16(self.left);
+        dst.write_i16(self.top);
+        dst.write_i16(self.width);
+        dst.write_i16(self.height);
+        dst.write_u8(self.rop);
+        dst.write_i16(self.src_x);
+        dst.write_i16(self.src_y);
+
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE
+    }
+}
+
+impl<'de> Decode<'de> for ScrBltOrder {
+    fn decode(src: &mut ReadCursor<'de>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        Ok(Self {
+            left: src.read_i16(),
+            top: src.read_i16(),
+            width: src.read_i16(),
+            height: src.read_i16(),
+            rop: src.read_u8(),
+            src_x: src.read_i16(),
+            src_y: src.read_i16(),
+        })
+    }
+}
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct ControlFlags: u8 {
+        const STANDARD = 0x01;
+        const SECONDARY = 0x02;
+        const BOUNDS = 0x04;
+        const TYPE_CHANGE = 0x08;
+        const DELTA_COORDINATES = 0x10;
+        const ZERO_BOUNDS_DELTAS = 0x20;
+        const ZERO_FIELD_BYTE_BIT0 = 0x40;
+        const ZERO_FIELD_BYTE_BIT1 = 0x80;
+    }
+}
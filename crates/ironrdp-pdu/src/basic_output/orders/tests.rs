@@ -0,0 +1,62 @@
+use lazy_static::lazy_static;
+
+use super::*;
+use ironrdp_core::{decode, encode};
+
+const SCR_BLT_BUFFER: [u8; 18] = [
+    0x01, 0x00, // numberOrders
+    0x09, // controlFlags
+    0x02, // orderType
+    0x7f, // fieldFlags
+    0x0a, 0x00, // nLeftRect
+    0x14, 0x00, // nTopRect
+    0x80, 0x02, // nWidth
+    0xe0, 0x01, // nHeight
+    0xcc, // bRop
+    0x0a, 0x00, // nXSrc
+    0x24, 0x00, // nYSrc
+];
+
+lazy_static! {
+    static ref SCR_BLT_PDU: DrawingOrdersPdu = DrawingOrdersPdu {
+        orders: vec![PrimaryDrawingOrder::ScrBlt(ScrBltOrder {
+            left: 10,
+            top: 20,
+            width: 640,
+            height: 480,
+            rop: ROP_SRCCOPY,
+            src_x: 10,
+            src_y: 36,
+        })],
+    };
+}
+
+#[test]
+fn from_buffer_correctly_parses_scr_blt_order() {
+    assert_eq!(
+        *SCR_BLT_PDU,
+        decode::<DrawingOrdersPdu>(SCR_BLT_BUFFER.as_ref()).unwrap()
+    );
+}
+
+#[test]
+fn to_buffer_correctly_serializes_scr_blt_order() {
+    let expected = SCR_BLT_BUFFER.as_ref();
+    let mut buffer = vec![0; expected.len()];
+
+    encode(&*SCR_BLT_PDU, buffer.as_mut_slice()).unwrap();
+    assert_eq!(expected, buffer.as_slice());
+}
+
+#[test]
+fn buffer_length_is_correct_for_scr_blt_order() {
+    assert_eq!(SCR_BLT_BUFFER.len(), SCR_BLT_PDU.size());
+}
+
+#[test]
+fn from_buffer_rejects_order_with_missing_fields() {
+    let mut buffer = SCR_BLT_BUFFER;
+    buffer[4] = 0x3f;
+
+    assert!(decode::<DrawingOrdersPdu>(buffer.as_ref()).is_err());
+}
@@ -1,13 +1,13 @@
 mod graphics_messages;
 
 pub use graphics_messages::{
-    Avc420BitmapStream, Avc444BitmapStream, CacheImportReplyPdu, CacheToSurfacePdu, CapabilitiesAdvertisePdu,
-    CapabilitiesConfirmPdu, CapabilitiesV103Flags, CapabilitiesV104Flags, CapabilitiesV107Flags, CapabilitiesV10Flags,
-    CapabilitiesV81Flags, CapabilitiesV8Flags, CapabilitySet, Codec1Type, Codec2Type, Color, CreateSurfacePdu,
-    DeleteEncodingContextPdu, DeleteSurfacePdu, Encoding, EndFramePdu, EvictCacheEntryPdu, FrameAcknowledgePdu,
-    MapSurfaceToOutputPdu, MapSurfaceToScaledOutputPdu, MapSurfaceToScaledWindowPdu, PixelFormat, Point, QuantQuality,
-    QueueDepth, ResetGraphicsPdu, SolidFillPdu, StartFramePdu, SurfaceToCachePdu, SurfaceToSurfacePdu, Timestamp,
-    WireToSurface1Pdu, WireToSurface2Pdu,
+    Avc420BitmapStream, Avc444BitmapStream, CacheEntryMetadata, CacheImportOfferPdu, CacheImportReplyPdu,
+    CacheToSurfacePdu, CapabilitiesAdvertisePdu, CapabilitiesConfirmPdu, CapabilitiesV103Flags, CapabilitiesV104Flags,
+    CapabilitiesV107Flags, CapabilitiesV10Flags, CapabilitiesV81Flags, CapabilitiesV8Flags, CapabilitySet, Codec1Type,
+    Codec2Type, Color, CreateSurfacePdu, DeleteEncodingContextPdu, DeleteSurfacePdu, Encoding, EndFramePdu,
+    EvictCacheEntryPdu, FrameAcknowledgePdu, MapSurfaceToOutputPdu, MapSurfaceToScaledOutputPdu,
+    MapSurfaceToScaledWindowPdu, PixelFormat, Point, QuantQuality, QueueDepth, ResetGraphicsPdu, SolidFillPdu,
+    StartFramePdu, SurfaceToCachePdu, SurfaceToSurfacePdu, Timestamp, WireToSurface1Pdu, WireToSurface2Pdu,
 };
 use num_derive::{FromPrimitive, ToPrimitive};
 use num_traits::{FromPrimitive as _, ToPrimitive as _};
@@ -161,6 +161,7 @@ impl<'a> Decode<'a> for ServerPdu {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ClientPdu {
     FrameAcknowledge(FrameAcknowledgePdu),
+    CacheImportOffer(CacheImportOfferPdu),
     CapabilitiesAdvertise(CapabilitiesAdvertisePdu),
 }
 
@@ -180,6 +181,7 @@ impl Encode for ClientPdu {
 
         match self {
             ClientPdu::FrameAcknowledge(pdu) => pdu.encode(dst),
+            ClientPdu::CacheImportOffer(pdu) => pdu.encode(dst),
             ClientPdu::CapabilitiesAdvertise(pdu) => pdu.encode(dst),
         }
     }
@@ -192,6 +194,7 @@ impl Encode for ClientPdu {
         Self::FIXED_PART_SIZE
             + match self {
                 ClientPdu::FrameAcknowledge(pdu) => pdu.size(),
+                ClientPdu::CacheImportOffer(pdu) => pdu.size(),
                 ClientPdu::CapabilitiesAdvertise(pdu) => pdu.size(),
             }
     }
@@ -206,6 +209,7 @@ impl<'a> Decode<'a> for ClientPdu {
 
         let client_pdu = match pdu_type {
             ClientPduType::FrameAcknowledge => ClientPdu::FrameAcknowledge(FrameAcknowledgePdu::decode(src)?),
+            ClientPduType::CacheImportOffer => ClientPdu::CacheImportOffer(CacheImportOfferPdu::decode(src)?),
             ClientPduType::CapabilitiesAdvertise => {
                 ClientPdu::CapabilitiesAdvertise(CapabilitiesAdvertisePdu::decode(src)?)
             }
@@ -232,6 +236,7 @@ impl<'a> From<&'a ClientPdu> for ClientPduType {
     fn from(c: &'a ClientPdu) -> Self {
         match c {
             ClientPdu::FrameAcknowledge(_) => Self::FrameAcknowledge,
+            ClientPdu::CacheImportOffer(_) => Self::CacheImportOffer,
             ClientPdu::CapabilitiesAdvertise(_) => Self::CapabilitiesAdvertise,
         }
     }
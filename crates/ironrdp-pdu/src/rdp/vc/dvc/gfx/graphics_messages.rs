@@ -8,7 +8,10 @@ use num_traits::{FromPrimitive as _, ToPrimitive as _};
 
 #[rustfmt::skip] // do not re-order this
 pub use avc_messages::{Avc420BitmapStream, Avc444BitmapStream, Encoding, QuantQuality};
-pub use client::{CacheImportReplyPdu, CapabilitiesAdvertisePdu, FrameAcknowledgePdu, QueueDepth};
+pub use client::{
+    CacheEntryMetadata, CacheImportOfferPdu, CacheImportReplyPdu, CapabilitiesAdvertisePdu, FrameAcknowledgePdu,
+    QueueDepth,
+};
 pub use server::{
     CacheToSurfacePdu, CapabilitiesConfirmPdu, Codec1Type, Codec2Type, CreateSurfacePdu, DeleteEncodingContextPdu,
     DeleteSurfacePdu, EndFramePdu, EvictCacheEntryPdu, MapSurfaceToOutputPdu, MapSurfaceToScaledOutputPdu,
@@ -1,5 +1,5 @@
 use super::CapabilitySet;
-use ironrdp_core::{cast_length, ensure_fixed_part_size, ensure_size, ReadCursor, WriteCursor};
+use ironrdp_core::{cast_length, ensure_fixed_part_size, ensure_size, invalid_field_err, ReadCursor, WriteCursor};
 use ironrdp_core::{Decode, DecodeResult, Encode, EncodeResult};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -143,6 +143,111 @@ impl<'a> Decode<'a> for CacheImportReplyPdu {
     }
 }
 
+/// 2.2.2.16 RDPGFX_CACHE_IMPORT_OFFER_PDU
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheImportOfferPdu {
+    pub cache_entries: Vec<CacheEntryMetadata>,
+}
+
+impl CacheImportOfferPdu {
+    const NAME: &'static str = "CacheImportOfferPdu";
+
+    const FIXED_PART_SIZE: usize = 2 /* Count */;
+
+    /// Maximum number of cache entries offered by the client.
+    pub const MAX_CACHE_ENTRIES: usize = 5462;
+}
+
+impl Encode for CacheImportOfferPdu {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(in: dst, size: self.size());
+
+        if self.cache_entries.len() > Self::MAX_CACHE_ENTRIES {
+            return Err(invalid_field_err!("cacheEntriesCount", "too many cache entries"));
+        }
+        dst.write_u16(cast_length!("Count", self.cache_entries.len())?);
+
+        for entry in self.cache_entries.iter() {
+            entry.encode(dst)?;
+        }
+
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE + self.cache_entries.len() * CacheEntryMetadata::FIXED_PART_SIZE
+    }
+}
+
+impl<'a> Decode<'a> for CacheImportOfferPdu {
+    fn decode(src: &mut ReadCursor<'a>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let entries_count = usize::from(src.read_u16());
+        if entries_count > Self::MAX_CACHE_ENTRIES {
+            return Err(invalid_field_err!("cacheEntriesCount", "too many cache entries"));
+        }
+
+        ensure_size!(in: src, size: entries_count * CacheEntryMetadata::FIXED_PART_SIZE);
+
+        let cache_entries = (0..entries_count)
+            .map(|_| CacheEntryMetadata::decode(src))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { cache_entries })
+    }
+}
+
+/// 2.2.2.16.1 RDPGFX_CACHE_ENTRY_METADATA
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CacheEntryMetadata {
+    pub cache_key: u64,
+    pub bitmap_length: u32,
+}
+
+impl CacheEntryMetadata {
+    const NAME: &'static str = "CacheEntryMetadata";
+
+    const FIXED_PART_SIZE: usize = 8 /* CacheKey */ + 4 /* BitmapLength */;
+}
+
+impl Encode for CacheEntryMetadata {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_fixed_part_size!(in: dst);
+
+        dst.write_u64(self.cache_key);
+        dst.write_u32(self.bitmap_length);
+
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE
+    }
+}
+
+impl<'a> Decode<'a> for CacheEntryMetadata {
+    fn decode(src: &mut ReadCursor<'a>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let cache_key = src.read_u64();
+        let bitmap_length = src.read_u32();
+
+        Ok(Self {
+            cache_key,
+            bitmap_length,
+        })
+    }
+}
+
 #[repr(u32)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum QueueDepth {
@@ -0,0 +1,188 @@
+use std::fs::{self, File, FileTimes, Metadata, Permissions};
+use std::io;
+use std::os::unix::fs::{FileExt as _, MetadataExt as _, PermissionsExt as _};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{components, DriveFile, DriveFilesystem, FileStat, OpenOptions, VolumeStat};
+
+/// A [`DriveFilesystem`] exposing a directory of the host.
+///
+/// The paths containing `.` or `..` components are rejected. The symbolic links are followed wherever they lead,
+/// see [`SandboxedFilesystem`](super::SandboxedFilesystem) to keep the drive within the directory.
+#[derive(Debug, Clone)]
+pub struct HostFilesystem {
+    root: PathBuf,
+}
+
+impl HostFilesystem {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn host(&self, path: &str) -> io::Result<PathBuf> {
+        let mut host = self.root.clone();
+        host.extend(components(path)?);
+        Ok(host)
+    }
+}
+
+impl DriveFilesystem for HostFilesystem {
+    fn open(&self, path: &str, options: &OpenOptions) -> io::Result<Box<dyn DriveFile>> {
+        let file = fs::OpenOptions::new()
+            .read(options.read)
+            .write(options.write)
+            .create(options.create)
+            .create_new(options.create_new)
+            .truncate(options.truncate)
+            .open(self.host(path)?)?;
+        Ok(Box::new(HostFile(file)))
+    }
+
+    fn stat(&self, path: &str) -> io::Result<FileStat> {
+        fs::metadata(self.host(path)?).map(|meta| host_stat(&meta))
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        fs::read_dir(self.host(path)?)?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+            .collect()
+    }
+
+    fn create_dir(&self, path: &str) -> io::Result<()> {
+        fs::create_dir(self.host(path)?)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        fs::rename(self.host(from)?, self.host(to)?)
+    }
+
+    fn remove_file(&self, path: &str) -> io::Result<()> {
+        fs::remove_file(self.host(path)?)
+    }
+
+    fn remove_dir(&self, path: &str) -> io::Result<()> {
+        fs::remove_dir(self.host(path)?)
+    }
+
+    fn volume_stat(&self) -> io::Result<VolumeStat> {
+        let statvfs = nix::sys::statvfs::statvfs(&self.root)?;
+        // The block counts are 32-bit on some platforms.
+        #[allow(clippy::useless_conversion)]
+        let stat = VolumeStat {
+            block_size: u32::try_from(statvfs.fragment_size()).unwrap_or(u32::MAX),
+            total_blocks: u64::from(statvfs.blocks()),
+            free_blocks: u64::from(statvfs.blocks_free()),
+            available_blocks: u64::from(statvfs.blocks_available()),
+        };
+        Ok(stat)
+    }
+
+    /// Fails with [`io::ErrorKind::PermissionDenied`] if the links lead outside of the root directory.
+    fn canonicalize(&self, path: &str) -> io::Result<String> {
+        let canonical = fs::canonicalize(self.host(path)?)?;
+        let root = fs::canonicalize(&self.root)?;
+        let relative = canonical
+            .strip_prefix(root)
+            .map_err(|_| io::Error::new(io::ErrorKind::PermissionDenied, "outside of the drive"))?;
+        let relative = relative
+            .to_str()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "path is not valid UTF-8"))?;
+        Ok(format!("/{relative}"))
+    }
+
+    fn is_symlink(&self, path: &str) -> bool {
+        self.host(path)
+            .and_then(fs::symlink_metadata)
+            .is_ok_and(|meta| meta.is_symlink())
+    }
+
+    fn host_path(&self, path: &str) -> Option<PathBuf> {
+        self.host(path).ok()
+    }
+}
+
+#[derive(Debug)]
+struct HostFile(File);
+
+impl DriveFile for HostFile {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let mut length = 0;
+        while length < buf.len() {
+            let position = offset
+                .checked_add(length as u64)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+            match self.0.read_at(&mut buf[length..], position) {
+                Ok(0) => break,
+                // read_at never returns more than the remaining space
+                #[allow(clippy::arithmetic_side_effects)]
+                Ok(read) => length += read,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(length)
+    }
+
+    fn write_at(&self, offset: u64, data: &[u8]) -> io::Result<()> {
+        self.0.write_all_at(data, offset)
+    }
+
+    fn stat(&self) -> io::Result<FileStat> {
+        self.0.metadata().map(|meta| host_stat(&meta))
+    }
+
+    fn set_len(&self, len: u64) -> io::Result<()> {
+        self.0.set_len(len)
+    }
+
+    fn set_times(&self, last_access_time: Option<SystemTime>, last_write_time: Option<SystemTime>) -> io::Result<()> {
+        let mut times = FileTimes::new();
+        if let Some(time) = last_access_time {
+            times = times.set_accessed(time);
+        }
+        if let Some(time) = last_write_time {
+            times = times.set_modified(time);
+        }
+        self.0.set_times(times)
+    }
+
+    fn set_readonly(&self, readonly: bool) -> io::Result<()> {
+        let mode = self.0.metadata()?.mode();
+        let mode = if readonly { mode & !0o222 } else { mode | 0o200 };
+        self.0.set_permissions(Permissions::from_mode(mode))
+    }
+
+    fn host_file(&self) -> Option<&File> {
+        Some(&self.0)
+    }
+}
+
+fn host_stat(meta: &Metadata) -> FileStat {
+    let change_time = unix_time(meta.ctime(), meta.ctime_nsec());
+    FileStat {
+        is_dir: meta.is_dir(),
+        size: meta.size(),
+        readonly: meta.permissions().readonly(),
+        // The birth time is not available on every file system.
+        creation_time: meta.created().unwrap_or(change_time),
+        last_access_time: unix_time(meta.atime(), meta.atime_nsec()),
+        last_write_time: unix_time(meta.mtime(), meta.mtime_nsec()),
+        change_time,
+    }
+}
+
+fn unix_time(secs: i64, nsecs: i64) -> SystemTime {
+    let secs_since = Duration::from_secs(secs.unsigned_abs());
+    let time = if secs >= 0 {
+        UNIX_EPOCH.checked_add(secs_since)
+    } else {
+        UNIX_EPOCH.checked_sub(secs_since)
+    };
+    time.and_then(|time| time.checked_add(Duration::from_nanos(u64::try_from(nsecs).unwrap_or(0))))
+        .unwrap_or(UNIX_EPOCH)
+}
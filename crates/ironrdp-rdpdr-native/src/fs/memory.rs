@@ -0,0 +1,356 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::SystemTime;
+
+use super::{join, normalize, parent, DriveFile, DriveFilesystem, FileStat, OpenOptions, VolumeStat};
+
+/// Size of the volume reported for the files kept in memory.
+const CAPACITY: u64 = 1 << 30;
+
+const BLOCK_SIZE: u32 = 4096;
+
+/// A [`DriveFilesystem`] keeping its files in memory.
+///
+/// Clones share the same files, so that the application can look at what the server wrote, or provide the files
+/// to read, while the drive is redirected.
+#[derive(Debug, Clone)]
+pub struct MemoryFilesystem {
+    nodes: Arc<Mutex<BTreeMap<String, Arc<Mutex<Node>>>>>,
+}
+
+impl MemoryFilesystem {
+    /// Creates an empty file system, with only its root directory.
+    pub fn new() -> Self {
+        let mut nodes = BTreeMap::new();
+        nodes.insert("/".to_owned(), Node::new(None));
+        Self {
+            nodes: Arc::new(Mutex::new(nodes)),
+        }
+    }
+
+    fn nodes(&self) -> MutexGuard<'_, BTreeMap<String, Arc<Mutex<Node>>>> {
+        self.nodes.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for MemoryFilesystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DriveFilesystem for MemoryFilesystem {
+    fn open(&self, path: &str, options: &OpenOptions) -> io::Result<Box<dyn DriveFile>> {
+        let path = normalize(path)?;
+        let mut nodes = self.nodes();
+
+        let node = match nodes.get(&path) {
+            Some(_) if options.create_new => return Err(io::ErrorKind::AlreadyExists.into()),
+            Some(node) => {
+                let mut guard = lock(node);
+                if guard.data.is_none() && options.modifies() {
+                    return Err(is_a_directory());
+                }
+                if guard.readonly && options.modifies() {
+                    return Err(io::ErrorKind::PermissionDenied.into());
+                }
+                if options.truncate {
+                    guard.set_len(0);
+                }
+                drop(guard);
+                Arc::clone(node)
+            }
+            None if options.create || options.create_new => {
+                check_parent(&nodes, &path)?;
+                let node = Node::new(Some(Vec::new()));
+                nodes.insert(path, Arc::clone(&node));
+                node
+            }
+            None => return Err(io::ErrorKind::NotFound.into()),
+        };
+
+        Ok(Box::new(MemoryFile {
+            node,
+            read: options.read,
+            write: options.write,
+        }))
+    }
+
+    fn stat(&self, path: &str) -> io::Result<FileStat> {
+        let path = normalize(path)?;
+        let nodes = self.nodes();
+        let node = nodes.get(&path).ok_or(io::ErrorKind::NotFound)?;
+        let stat = lock(node).stat();
+        Ok(stat)
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        let path = normalize(path)?;
+        let nodes = self.nodes();
+        let node = nodes.get(&path).ok_or(io::ErrorKind::NotFound)?;
+        if lock(node).data.is_some() {
+            return Err(not_a_directory());
+        }
+        Ok(children(&nodes, &path)
+            .filter(|child| !child.contains('/'))
+            .map(str::to_owned)
+            .collect())
+    }
+
+    fn create_dir(&self, path: &str) -> io::Result<()> {
+        let path = normalize(path)?;
+        let mut nodes = self.nodes();
+        if nodes.contains_key(&path) {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        check_parent(&nodes, &path)?;
+        nodes.insert(path, Node::new(None));
+        Ok(())
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        let from = normalize(from)?;
+        let to = normalize(to)?;
+        let mut nodes = self.nodes();
+
+        let node = nodes.get(&from).ok_or(io::ErrorKind::NotFound)?;
+        let is_dir = lock(node).data.is_none();
+        if from == to {
+            return Ok(());
+        }
+        if from == "/" || to.starts_with(&join(&from, "")) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot move a directory into itself",
+            ));
+        }
+        if let Some(target) = nodes.get(&to) {
+            // Like on Windows, only files can be replaced.
+            if is_dir || lock(target).data.is_none() {
+                return Err(io::ErrorKind::AlreadyExists.into());
+            }
+        }
+        check_parent(&nodes, &to)?;
+
+        let moved: Vec<String> = children(&nodes, &from).map(|child| join(&from, child)).collect();
+        for old in moved {
+            if let Some(child) = nodes.remove(&old) {
+                nodes.insert(format!("{to}{}", &old[from.len()..]), child);
+            }
+        }
+        if let Some(node) = nodes.remove(&from) {
+            lock(&node).change_time = SystemTime::now();
+            nodes.insert(to, node);
+        }
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &str) -> io::Result<()> {
+        let path = normalize(path)?;
+        let mut nodes = self.nodes();
+        let node = nodes.get(&path).ok_or(io::ErrorKind::NotFound)?;
+        if lock(node).data.is_none() {
+            return Err(is_a_directory());
+        }
+        nodes.remove(&path);
+        Ok(())
+    }
+
+    fn remove_dir(&self, path: &str) -> io::Result<()> {
+        let path = normalize(path)?;
+        let mut nodes = self.nodes();
+        let node = nodes.get(&path).ok_or(io::ErrorKind::NotFound)?;
+        if lock(node).data.is_some() {
+            return Err(not_a_directory());
+        }
+        if path == "/" {
+            return Err(io::ErrorKind::PermissionDenied.into());
+        }
+        if children(&nodes, &path).next().is_some() {
+            return Err(io::Error::new(io::ErrorKind::Other, "directory not empty"));
+        }
+        nodes.remove(&path);
+        Ok(())
+    }
+
+    /// The volume is reported with a nominal size of 1 GiB, which the files are not prevented from exceeding.
+    fn volume_stat(&self) -> io::Result<VolumeStat> {
+        let used: u64 = self
+            .nodes()
+            .values()
+            .map(|node| lock(node).data.as_ref().map_or(0, |data| data.len() as u64))
+            .fold(0, u64::saturating_add);
+        let total_blocks = CAPACITY / u64::from(BLOCK_SIZE);
+        let free_blocks = CAPACITY.saturating_sub(used) / u64::from(BLOCK_SIZE);
+        Ok(VolumeStat {
+            block_size: BLOCK_SIZE,
+            total_blocks,
+            free_blocks,
+            available_blocks: free_blocks,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Node {
+    /// The content of the file, `None` for directories.
+    data: Option<Vec<u8>>,
+    readonly: bool,
+    creation_time: SystemTime,
+    last_access_time: SystemTime,
+    last_write_time: SystemTime,
+    change_time: SystemTime,
+}
+
+impl Node {
+    fn new(data: Option<Vec<u8>>) -> Arc<Mutex<Self>> {
+        let now = SystemTime::now();
+        Arc::new(Mutex::new(Self {
+            data,
+            readonly: false,
+            creation_time: now,
+            last_access_time: now,
+            last_write_time: now,
+            change_time: now,
+        }))
+    }
+
+    fn stat(&self) -> FileStat {
+        FileStat {
+            is_dir: self.data.is_none(),
+            size: self.data.as_ref().map_or(0, |data| data.len() as u64),
+            readonly: self.readonly,
+            creation_time: self.creation_time,
+            last_access_time: self.last_access_time,
+            last_write_time: self.last_write_time,
+            change_time: self.change_time,
+        }
+    }
+
+    fn modified(&mut self) {
+        let now = SystemTime::now();
+        self.last_write_time = now;
+        self.change_time = now;
+    }
+
+    fn set_len(&mut self, len: usize) {
+        if let Some(data) = self.data.as_mut() {
+            data.resize(len, 0);
+            self.modified();
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MemoryFile {
+    node: Arc<Mutex<Node>>,
+    read: bool,
+    write: bool,
+}
+
+impl MemoryFile {
+    fn node(&self, access: bool) -> io::Result<MutexGuard<'_, Node>> {
+        if !access {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "file not opened with this access",
+            ));
+        }
+        Ok(lock(&self.node))
+    }
+}
+
+impl DriveFile for MemoryFile {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let mut node = self.node(self.read)?;
+        let data = node.data.as_ref().ok_or_else(is_a_directory)?;
+        let start = usize::try_from(offset).unwrap_or(usize::MAX).min(data.len());
+        let length = buf.len().min(data.len().saturating_sub(start));
+        buf[..length].copy_from_slice(&data[start..][..length]);
+        node.last_access_time = SystemTime::now();
+        Ok(length)
+    }
+
+    fn write_at(&self, offset: u64, data: &[u8]) -> io::Result<()> {
+        let mut node = self.node(self.write)?;
+        let content = node.data.as_mut().ok_or_else(is_a_directory)?;
+        let start = usize::try_from(offset).map_err(|_| too_large())?;
+        let end = start.checked_add(data.len()).ok_or_else(too_large)?;
+        if content.len() < end {
+            content.resize(end, 0);
+        }
+        content[start..end].copy_from_slice(data);
+        node.modified();
+        Ok(())
+    }
+
+    fn stat(&self) -> io::Result<FileStat> {
+        Ok(lock(&self.node).stat())
+    }
+
+    fn set_len(&self, len: u64) -> io::Result<()> {
+        let mut node = self.node(self.write)?;
+        if node.data.is_none() {
+            return Err(is_a_directory());
+        }
+        node.set_len(usize::try_from(len).map_err(|_| too_large())?);
+        Ok(())
+    }
+
+    fn set_times(&self, last_access_time: Option<SystemTime>, last_write_time: Option<SystemTime>) -> io::Result<()> {
+        let mut node = lock(&self.node);
+        if let Some(time) = last_access_time {
+            node.last_access_time = time;
+        }
+        if let Some(time) = last_write_time {
+            node.last_write_time = time;
+        }
+        node.change_time = SystemTime::now();
+        Ok(())
+    }
+
+    fn set_readonly(&self, readonly: bool) -> io::Result<()> {
+        let mut node = lock(&self.node);
+        node.readonly = readonly;
+        node.change_time = SystemTime::now();
+        Ok(())
+    }
+}
+
+fn lock(node: &Mutex<Node>) -> MutexGuard<'_, Node> {
+    node.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Returns the paths below the directory `dir`, relative to it.
+fn children<'a>(nodes: &'a BTreeMap<String, Arc<Mutex<Node>>>, dir: &str) -> impl Iterator<Item = &'a str> {
+    let prefix = join(dir, "");
+    let prefix_len = prefix.len();
+    nodes
+        .range(prefix.clone()..)
+        .map(|(path, _)| path.as_str())
+        .take_while(move |path| path.starts_with(&prefix))
+        .map(move |path| &path[prefix_len..])
+        // The root directory is its own prefix.
+        .filter(|child| !child.is_empty())
+}
+
+fn check_parent(nodes: &BTreeMap<String, Arc<Mutex<Node>>>, path: &str) -> io::Result<()> {
+    let parent = nodes.get(parent(path)).ok_or(io::ErrorKind::NotFound)?;
+    if lock(parent).data.is_some() {
+        return Err(not_a_directory());
+    }
+    Ok(())
+}
+
+fn is_a_directory() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "is a directory")
+}
+
+fn not_a_directory() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "not a directory")
+}
+
+fn too_large() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "file too large")
+}
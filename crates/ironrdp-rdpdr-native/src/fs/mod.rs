@@ -0,0 +1,189 @@
+//! File systems exposed through drive redirection.
+//!
+//! The drive backend does not access the host file system directly, it goes through a [`DriveFilesystem`]. Besides
+//! the host file system, the files can be kept in memory with [`MemoryFilesystem`], and any file system can be made
+//! read-only with [`ReadOnlyFilesystem`] or confined to one of its directories with [`SandboxedFilesystem`].
+//!
+//! Paths are relative to the root of the drive, use `/` as separator, and start with `/`, the root itself being `/`.
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+mod host;
+mod memory;
+mod read_only;
+mod sandbox;
+
+use std::fs::File;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use std::{fmt, io};
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+pub use self::host::HostFilesystem;
+pub use self::memory::MemoryFilesystem;
+pub use self::read_only::ReadOnlyFilesystem;
+pub use self::sandbox::SandboxedFilesystem;
+
+/// A file system exposed as a redirected drive.
+pub trait DriveFilesystem: fmt::Debug + Send {
+    /// Opens the file or directory at `path`, directories being opened for reading only.
+    fn open(&self, path: &str, options: &OpenOptions) -> io::Result<Box<dyn DriveFile>>;
+
+    /// Returns the metadata of the file or directory at `path`.
+    fn stat(&self, path: &str) -> io::Result<FileStat>;
+
+    /// Returns the names of the entries of the directory at `path`.
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>>;
+
+    /// Creates a directory, its parent has to exist.
+    fn create_dir(&self, path: &str) -> io::Result<()>;
+
+    /// Moves a file or directory, replacing the file at `to` if any.
+    fn rename(&self, from: &str, to: &str) -> io::Result<()>;
+
+    fn remove_file(&self, path: &str) -> io::Result<()>;
+
+    /// Removes an empty directory.
+    fn remove_dir(&self, path: &str) -> io::Result<()>;
+
+    /// Returns the size of the volume holding the files.
+    fn volume_stat(&self) -> io::Result<VolumeStat>;
+
+    /// Resolves the symbolic links of the existing file or directory at `path`.
+    ///
+    /// File systems without links return the path as is.
+    fn canonicalize(&self, path: &str) -> io::Result<String> {
+        self.stat(path)?;
+        Ok(path.to_owned())
+    }
+
+    /// Whether `path` is a symbolic link, even if it points to nothing.
+    fn is_symlink(&self, _path: &str) -> bool {
+        false
+    }
+
+    /// Returns where `path` is on the host, for the file systems backed by it.
+    ///
+    /// The directory change notifications are only supported for the files on the host.
+    fn host_path(&self, _path: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// A file or directory opened on a [`DriveFilesystem`].
+pub trait DriveFile: fmt::Debug + Send {
+    /// Reads at `offset` until `buf` is full, returning fewer bytes only at the end of the file.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// Writes all of `data` at `offset`, extending the file if needed.
+    fn write_at(&self, offset: u64, data: &[u8]) -> io::Result<()>;
+
+    fn stat(&self) -> io::Result<FileStat>;
+
+    /// Truncates or extends the file to `len` bytes.
+    fn set_len(&self, len: u64) -> io::Result<()>;
+
+    /// Sets the last access and last write times, `None` leaving them unchanged.
+    fn set_times(&self, last_access_time: Option<SystemTime>, last_write_time: Option<SystemTime>) -> io::Result<()>;
+
+    fn set_readonly(&self, readonly: bool) -> io::Result<()>;
+
+    /// Returns the file opened on the host, for the file systems backed by it.
+    ///
+    /// Byte-range locks are only enforced on the files of the host.
+    fn host_file(&self) -> Option<&File> {
+        None
+    }
+}
+
+/// How a file is opened, as with [`std::fs::OpenOptions`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpenOptions {
+    pub read: bool,
+    pub write: bool,
+    /// Creates the file if it does not exist.
+    pub create: bool,
+    /// Creates the file, failing if it already exists.
+    pub create_new: bool,
+    pub truncate: bool,
+}
+
+impl OpenOptions {
+    /// Whether opening the file this way may modify the file system.
+    pub fn modifies(&self) -> bool {
+        self.write || self.create || self.create_new || self.truncate
+    }
+}
+
+/// Metadata of a file or directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStat {
+    pub is_dir: bool,
+    pub size: u64,
+    pub readonly: bool,
+    pub creation_time: SystemTime,
+    pub last_access_time: SystemTime,
+    pub last_write_time: SystemTime,
+    pub change_time: SystemTime,
+}
+
+/// Size of a volume, in blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VolumeStat {
+    pub block_size: u32,
+    pub total_blocks: u64,
+    pub free_blocks: u64,
+    /// Free blocks available to the user, some may be reserved.
+    pub available_blocks: u64,
+}
+
+/// Returns the parent directory of `path`, the root being its own parent.
+pub(crate) fn parent(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(0) | None => "/",
+        Some(index) => &path[..index],
+    }
+}
+
+/// Returns the last component of `path`.
+pub(crate) fn file_name(path: &str) -> &str {
+    match path.rfind('/') {
+        // '/' is one byte long
+        #[allow(clippy::arithmetic_side_effects)]
+        Some(index) => &path[index + 1..],
+        None => path,
+    }
+}
+
+/// Appends `name` to the directory `dir`.
+pub(crate) fn join(dir: &str, name: &str) -> String {
+    let mut path = dir.to_owned();
+    if !path.ends_with('/') {
+        path.push('/');
+    }
+    path.push_str(name);
+    path
+}
+
+/// Splits `path` into its components, rejecting the `.` and `..` ones.
+pub(crate) fn components(path: &str) -> io::Result<Vec<&str>> {
+    path.split('/')
+        .filter(|component| !component.is_empty())
+        .map(|component| match component {
+            "." | ".." => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("relative path component in {path}"),
+            )),
+            component => Ok(component),
+        })
+        .collect()
+}
+
+/// Returns `path` with its components separated by exactly one `/`.
+pub(crate) fn normalize(path: &str) -> io::Result<String> {
+    let components = components(path)?;
+    Ok(format!("/{}", components.join("/")))
+}
+
+fn read_only_error() -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, "read-only file system")
+}
@@ -0,0 +1,111 @@
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use super::{read_only_error, DriveFile, DriveFilesystem, FileStat, OpenOptions, VolumeStat};
+
+/// A [`DriveFilesystem`] rejecting any modification of the wrapped one.
+///
+/// The files are reported as read-only, and opening them for writing is denied.
+#[derive(Debug, Clone)]
+pub struct ReadOnlyFilesystem<F> {
+    inner: F,
+}
+
+impl<F: DriveFilesystem> ReadOnlyFilesystem<F> {
+    pub fn new(inner: F) -> Self {
+        Self { inner }
+    }
+
+    pub fn into_inner(self) -> F {
+        self.inner
+    }
+}
+
+impl<F: DriveFilesystem> DriveFilesystem for ReadOnlyFilesystem<F> {
+    fn open(&self, path: &str, options: &OpenOptions) -> io::Result<Box<dyn DriveFile>> {
+        if options.modifies() {
+            return Err(read_only_error());
+        }
+        let file = self.inner.open(path, options)?;
+        Ok(Box::new(ReadOnlyFile(file)))
+    }
+
+    fn stat(&self, path: &str) -> io::Result<FileStat> {
+        self.inner.stat(path).map(read_only_stat)
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        self.inner.read_dir(path)
+    }
+
+    fn create_dir(&self, _path: &str) -> io::Result<()> {
+        Err(read_only_error())
+    }
+
+    fn rename(&self, _from: &str, _to: &str) -> io::Result<()> {
+        Err(read_only_error())
+    }
+
+    fn remove_file(&self, _path: &str) -> io::Result<()> {
+        Err(read_only_error())
+    }
+
+    fn remove_dir(&self, _path: &str) -> io::Result<()> {
+        Err(read_only_error())
+    }
+
+    fn volume_stat(&self) -> io::Result<VolumeStat> {
+        self.inner.volume_stat()
+    }
+
+    fn canonicalize(&self, path: &str) -> io::Result<String> {
+        self.inner.canonicalize(path)
+    }
+
+    fn is_symlink(&self, path: &str) -> bool {
+        self.inner.is_symlink(path)
+    }
+
+    fn host_path(&self, path: &str) -> Option<PathBuf> {
+        self.inner.host_path(path)
+    }
+}
+
+#[derive(Debug)]
+struct ReadOnlyFile(Box<dyn DriveFile>);
+
+impl DriveFile for ReadOnlyFile {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read_at(offset, buf)
+    }
+
+    fn write_at(&self, _offset: u64, _data: &[u8]) -> io::Result<()> {
+        Err(read_only_error())
+    }
+
+    fn stat(&self) -> io::Result<FileStat> {
+        self.0.stat().map(read_only_stat)
+    }
+
+    fn set_len(&self, _len: u64) -> io::Result<()> {
+        Err(read_only_error())
+    }
+
+    fn set_times(&self, _last_access_time: Option<SystemTime>, _last_write_time: Option<SystemTime>) -> io::Result<()> {
+        Err(read_only_error())
+    }
+
+    fn set_readonly(&self, _readonly: bool) -> io::Result<()> {
+        Err(read_only_error())
+    }
+
+    fn host_file(&self) -> Option<&File> {
+        self.0.host_file()
+    }
+}
+
+fn read_only_stat(stat: FileStat) -> FileStat {
+    FileStat { readonly: true, ..stat }
+}
@@ -0,0 +1,133 @@
+use std::io;
+use std::path::PathBuf;
+
+use super::{components, join, normalize, parent, DriveFile, DriveFilesystem, FileStat, OpenOptions, VolumeStat};
+
+/// A [`DriveFilesystem`] confining the drive to a directory of the wrapped one, like `chroot`.
+///
+/// The paths containing `.` or `..` components are rejected, as well as those leading outside of the directory
+/// through a symbolic link, including dangling ones which could otherwise be followed to create a file anywhere.
+/// The links are resolved before the wrapped file system is accessed, so a link swapped in between by another
+/// process on the host is not detected.
+#[derive(Debug, Clone)]
+pub struct SandboxedFilesystem<F> {
+    inner: F,
+    root: String,
+}
+
+impl<F: DriveFilesystem> SandboxedFilesystem<F> {
+    /// Exposes the `root` directory of `inner` as the root of the drive.
+    pub fn new(inner: F, root: &str) -> io::Result<Self> {
+        let root = normalize(root)?;
+        if !inner.stat(&root)?.is_dir {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "sandbox root is not a directory",
+            ));
+        }
+        Ok(Self { inner, root })
+    }
+
+    pub fn into_inner(self) -> F {
+        self.inner
+    }
+
+    /// Returns the path of `path` in the wrapped file system, without resolving the links.
+    fn join(&self, path: &str) -> io::Result<String> {
+        let mut joined = self.root.clone();
+        for component in components(path)? {
+            joined = join(&joined, component);
+        }
+        Ok(joined)
+    }
+
+    /// Returns the path of `path` in the wrapped file system, checking that it stays within the root.
+    fn resolve(&self, path: &str) -> io::Result<String> {
+        let joined = self.join(path)?;
+        let root = self.inner.canonicalize(&self.root)?;
+
+        // The path may not exist yet, in which case its closest existing ancestor is checked.
+        let mut existing = joined.as_str();
+        loop {
+            match self.inner.canonicalize(existing) {
+                Ok(canonical) if is_within(&canonical, &root) => return Ok(joined),
+                Ok(_) => return Err(escape_error(path)),
+                Err(error) if error.kind() == io::ErrorKind::NotFound && existing != self.root => {
+                    if self.inner.is_symlink(existing) {
+                        return Err(escape_error(path));
+                    }
+                    existing = parent(existing);
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+impl<F: DriveFilesystem> DriveFilesystem for SandboxedFilesystem<F> {
+    fn open(&self, path: &str, options: &OpenOptions) -> io::Result<Box<dyn DriveFile>> {
+        self.inner.open(&self.resolve(path)?, options)
+    }
+
+    fn stat(&self, path: &str) -> io::Result<FileStat> {
+        self.inner.stat(&self.resolve(path)?)
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        self.inner.read_dir(&self.resolve(path)?)
+    }
+
+    fn create_dir(&self, path: &str) -> io::Result<()> {
+        self.inner.create_dir(&self.resolve(path)?)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        self.inner.rename(&self.resolve(from)?, &self.resolve(to)?)
+    }
+
+    fn remove_file(&self, path: &str) -> io::Result<()> {
+        self.inner.remove_file(&self.resolve(path)?)
+    }
+
+    fn remove_dir(&self, path: &str) -> io::Result<()> {
+        let path = self.resolve(path)?;
+        if path == self.root {
+            return Err(io::ErrorKind::PermissionDenied.into());
+        }
+        self.inner.remove_dir(&path)
+    }
+
+    fn volume_stat(&self) -> io::Result<VolumeStat> {
+        self.inner.volume_stat()
+    }
+
+    fn canonicalize(&self, path: &str) -> io::Result<String> {
+        let canonical = self.inner.canonicalize(&self.resolve(path)?)?;
+        let root = self.inner.canonicalize(&self.root)?;
+        match canonical.strip_prefix(root.as_str()) {
+            Some(relative) if root == "/" => Ok(format!("/{relative}")),
+            Some("") => Ok("/".to_owned()),
+            Some(relative) if relative.starts_with('/') => Ok(relative.to_owned()),
+            _ => Err(escape_error(path)),
+        }
+    }
+
+    fn is_symlink(&self, path: &str) -> bool {
+        self.join(path).is_ok_and(|path| self.inner.is_symlink(&path))
+    }
+
+    fn host_path(&self, path: &str) -> Option<PathBuf> {
+        self.inner.host_path(&self.resolve(path).ok()?)
+    }
+}
+
+fn is_within(path: &str, root: &str) -> bool {
+    root == "/" || path == root || path.starts_with(&join(root, ""))
+}
+
+fn escape_error(path: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!("{path} leads outside of the sandbox"),
+    )
+}
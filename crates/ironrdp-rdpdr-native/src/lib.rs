@@ -9,11 +9,14 @@
 #![warn(clippy::fn_to_numeric_cast_any)]
 #![warn(clippy::ptr_cast_constness)]
 
-#[cfg(any(target_os = "macos", target_os = "linux"))]
 #[macro_use]
 extern crate tracing;
 
 #[cfg(any(target_os = "macos", target_os = "linux"))]
 mod nix;
 #[cfg(any(target_os = "macos", target_os = "linux"))]
-pub use nix::backend;
+pub use nix::{backend, tty};
+
+pub mod fs;
+pub mod printer;
+pub mod scard;
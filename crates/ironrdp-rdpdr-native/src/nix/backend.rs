@@ -3,31 +3,68 @@ use ironrdp_pdu::{encode_err, PduResult};
 use ironrdp_rdpdr::pdu::efs::*;
 use ironrdp_rdpdr::pdu::esc::{ScardCall, ScardIoCtlCode};
 use ironrdp_rdpdr::pdu::RdpdrPdu;
-use ironrdp_rdpdr::RdpdrBackend;
-use ironrdp_svc::SvcMessage;
-use nix::dir::{Dir, OwningIter};
-use std::ffi::CString;
-use std::io::Read;
-use std::io::{Seek, SeekFrom, Write};
-use std::os::fd::{AsFd, AsRawFd};
-use std::os::unix::fs::MetadataExt;
-
-#[derive(Debug, Default)]
+use ironrdp_rdpdr::{RdpdrBackend, RdpdrMessageProxy};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::lock::FileLocks;
+#[cfg(target_os = "linux")]
+use super::notify::DirectoryWatcher;
+use crate::fs::{self, DriveFile, DriveFilesystem, FileStat, HostFilesystem, OpenOptions};
+
+/// FILE_DEVICE_DISK
+const FILE_DEVICE_DISK: u32 = 0x0000_0007;
+
+/// Drive backend exposing a [`DriveFilesystem`], the host one by default.
+#[derive(Debug)]
 pub struct NixRdpdrBackend {
     file_id: u32,
-    file_base: String,
-    file_map: std::collections::HashMap<u32, std::fs::File>,
-    file_path_map: std::collections::HashMap<u32, String>,
-    file_dir_map: std::collections::HashMap<u32, OwningIter>,
+    fs: Box<dyn DriveFilesystem>,
+    file_map: HashMap<u32, Box<dyn DriveFile>>,
+    /// Paths of the opened files, relative to the root of the drive.
+    file_path_map: HashMap<u32, String>,
+    file_dir_map: HashMap<u32, std::vec::IntoIter<String>>,
+    /// Files to delete once closed.
+    delete_on_close: HashSet<u32>,
+    locks: FileLocks,
+    proxy: Option<Arc<dyn RdpdrMessageProxy>>,
+    #[cfg(target_os = "linux")]
+    watcher: Option<DirectoryWatcher>,
 }
 
 impl NixRdpdrBackend {
+    /// Exposes the `file_base` directory of the host.
     pub fn new(file_base: String) -> Self {
+        Self::from_filesystem(HostFilesystem::new(file_base))
+    }
+
+    /// Exposes `fs`, e.g. a [`MemoryFilesystem`](crate::fs::MemoryFilesystem) or a
+    /// [`SandboxedFilesystem`](crate::fs::SandboxedFilesystem).
+    pub fn from_filesystem(fs: impl DriveFilesystem + 'static) -> Self {
         Self {
-            file_base,
-            ..Default::default()
+            file_id: 0,
+            fs: Box::new(fs),
+            file_map: HashMap::new(),
+            file_path_map: HashMap::new(),
+            file_dir_map: HashMap::new(),
+            delete_on_close: HashSet::new(),
+            locks: FileLocks::default(),
+            proxy: None,
+            #[cfg(target_os = "linux")]
+            watcher: None,
         }
     }
+
+    /// Sets the proxy used to complete the directory change notifications and the blocking lock requests.
+    ///
+    /// Without a proxy, the changes are only reported when the server asks for them again, and locks which
+    /// cannot be granted right away are refused.
+    #[must_use]
+    pub fn with_message_proxy(mut self, proxy: impl RdpdrMessageProxy + 'static) -> Self {
+        self.proxy = Some(Arc::new(proxy));
+        self
+    }
 }
 
 impl_as_any!(NixRdpdrBackend);
@@ -36,19 +73,22 @@ impl RdpdrBackend for NixRdpdrBackend {
     fn handle_server_device_announce_response(&mut self, _pdu: ServerDeviceAnnounceResponse) -> PduResult<()> {
         Ok(())
     }
-    fn handle_scard_call(&mut self, _req: DeviceControlRequest<ScardIoCtlCode>, _call: ScardCall) -> PduResult<()> {
-        Ok(())
+    fn handle_scard_call(
+        &mut self,
+        _req: DeviceControlRequest<ScardIoCtlCode>,
+        _call: ScardCall,
+    ) -> PduResult<Vec<RdpdrPdu>> {
+        Ok(Vec::new())
     }
-    fn handle_drive_io_request(&mut self, req: ServerDriveIoRequest) -> PduResult<Vec<SvcMessage>> {
+    fn handle_drive_io_request(&mut self, req: ServerDriveIoRequest) -> PduResult<Vec<RdpdrPdu>> {
         debug!("handle_drive_io_request:{:?}", req);
         match req {
             ServerDriveIoRequest::DeviceWriteRequest(req_inner) => write_device(self, req_inner),
             ServerDriveIoRequest::ServerCreateDriveRequest(req_inner) => create_drive(self, req_inner),
             ServerDriveIoRequest::DeviceReadRequest(req_inner) => read_device(self, req_inner),
             ServerDriveIoRequest::DeviceCloseRequest(req_inner) => close_device(self, req_inner),
-            ServerDriveIoRequest::ServerDriveNotifyChangeDirectoryRequest(_) => {
-                // TODO
-                Ok(Vec::new())
+            ServerDriveIoRequest::ServerDriveNotifyChangeDirectoryRequest(req_inner) => {
+                notify_change_directory(self, req_inner)
             }
             ServerDriveIoRequest::ServerDriveQueryDirectoryRequest(req_inner) => query_directory(self, req_inner),
             ServerDriveIoRequest::ServerDriveQueryInformationRequest(req_inner) => query_information(self, req_inner),
@@ -56,22 +96,27 @@ impl RdpdrBackend for NixRdpdrBackend {
                 query_volume_information(self, req_inner)
             }
             ServerDriveIoRequest::ServerDriveSetInformationRequest(req_inner) => set_information(self, req_inner),
-            ServerDriveIoRequest::DeviceControlRequest(req_inner) => Ok(vec![SvcMessage::from(
-                RdpdrPdu::DeviceControlResponse(DeviceControlResponse {
+            ServerDriveIoRequest::DeviceControlRequest(req_inner) => {
+                Ok(vec![RdpdrPdu::DeviceControlResponse(DeviceControlResponse {
                     device_io_reply: DeviceIoResponse::new(req_inner.header, NtStatus::SUCCESS),
                     output_buffer: None,
-                }),
-            )]),
-            ServerDriveIoRequest::ServerDriveLockControlRequest(_) => {
-                // TODO
-                Ok(Vec::new())
+                })])
             }
+            ServerDriveIoRequest::ServerDriveLockControlRequest(req_inner) => lock_control(self, req_inner),
+        }
+    }
+
+    fn cancel_io_request(&mut self, req: &DeviceIoRequest) {
+        self.locks.cancel(req.completion_id);
+        #[cfg(target_os = "linux")]
+        if let Some(watcher) = self.watcher.as_mut() {
+            watcher.cancel(req.completion_id);
         }
     }
 }
 
-pub(crate) fn write_device(backend: &mut NixRdpdrBackend, req_inner: DeviceWriteRequest) -> PduResult<Vec<SvcMessage>> {
-    return process_dependent_file(
+pub(crate) fn write_device(backend: &mut NixRdpdrBackend, req_inner: DeviceWriteRequest) -> PduResult<Vec<RdpdrPdu>> {
+    process_dependent_file(
         backend,
         req_inner.device_io_request,
         |request| {
@@ -79,50 +124,26 @@ pub(crate) fn write_device(backend: &mut NixRdpdrBackend, req_inner: DeviceWrite
                 device_io_reply: DeviceIoResponse::new(request, NtStatus::NO_SUCH_FILE),
                 length: 0u32,
             });
-            Ok(vec![SvcMessage::from(res)])
+            Ok(vec![res])
         },
-        |file, request| match write_inner(file, req_inner.offset, &req_inner.write_data) {
-            Ok(length) => {
-                if length == req_inner.write_data.len() {
-                    Ok(vec![SvcMessage::from(RdpdrPdu::DeviceWriteResponse(
-                        DeviceWriteResponse {
-                            device_io_reply: DeviceIoResponse::new(request, NtStatus::SUCCESS),
-                            length: u32::try_from(req_inner.write_data.len()).unwrap(),
-                        },
-                    ))])
-                } else {
-                    warn!(
-                        "Written content len:{} is not equal to {}",
-                        length,
-                        req_inner.write_data.len()
-                    );
-                    let res = RdpdrPdu::DeviceWriteResponse(DeviceWriteResponse {
-                        device_io_reply: DeviceIoResponse::new(request, NtStatus::UNSUCCESSFUL),
-                        length: 0u32,
-                    });
-                    Ok(vec![SvcMessage::from(res)])
-                }
-            }
+        |file, request| match file.write_at(req_inner.offset, &req_inner.write_data) {
+            Ok(()) => Ok(vec![RdpdrPdu::DeviceWriteResponse(DeviceWriteResponse {
+                device_io_reply: DeviceIoResponse::new(request, NtStatus::SUCCESS),
+                length: u32::try_from(req_inner.write_data.len()).unwrap(),
+            })]),
             Err(error) => {
                 warn!(%error, "Write error");
                 let res = RdpdrPdu::DeviceWriteResponse(DeviceWriteResponse {
-                    device_io_reply: DeviceIoResponse::new(request, NtStatus::UNSUCCESSFUL),
+                    device_io_reply: DeviceIoResponse::new(request, io_status(&error)),
                     length: 0u32,
                 });
-                Ok(vec![SvcMessage::from(res)])
+                Ok(vec![res])
             }
         },
-    );
-    fn write_inner(file: &mut std::fs::File, offset: u64, write_data: &[u8]) -> std::io::Result<usize> {
-        let sf = SeekFrom::Start(offset);
-        file.seek(sf)?;
-        let length = file.write(write_data)?;
-        file.flush()?;
-        Ok(length)
-    }
+    )
 }
 
-pub(crate) fn read_device(backend: &mut NixRdpdrBackend, req_inner: DeviceReadRequest) -> PduResult<Vec<SvcMessage>> {
+pub(crate) fn read_device(backend: &mut NixRdpdrBackend, req_inner: DeviceReadRequest) -> PduResult<Vec<RdpdrPdu>> {
     return process_dependent_file(
         backend,
         req_inner.device_io_request,
@@ -131,7 +152,7 @@ pub(crate) fn read_device(backend: &mut NixRdpdrBackend, req_inner: DeviceReadRe
                 device_io_reply: DeviceIoResponse::new(request, NtStatus::NO_SUCH_FILE),
                 read_data: Vec::new(),
             });
-            Ok(vec![SvcMessage::from(res)])
+            Ok(vec![res])
         },
         |file, request| match read_inner(file, req_inner.offset, usize::try_from(req_inner.length).unwrap()) {
             Ok(buf) => {
@@ -139,86 +160,146 @@ pub(crate) fn read_device(backend: &mut NixRdpdrBackend, req_inner: DeviceReadRe
                     device_io_reply: DeviceIoResponse::new(request, NtStatus::SUCCESS),
                     read_data: buf,
                 });
-                Ok(vec![SvcMessage::from(res)])
+                Ok(vec![res])
             }
             Err(error) => {
                 warn!(?error, "Read error");
                 let res = RdpdrPdu::DeviceReadResponse(DeviceReadResponse {
-                    device_io_reply: DeviceIoResponse::new(request, NtStatus::UNSUCCESSFUL),
+                    device_io_reply: DeviceIoResponse::new(request, io_status(&error)),
                     read_data: Vec::new(),
                 });
-                Ok(vec![SvcMessage::from(res)])
+                Ok(vec![res])
             }
         },
     );
-    fn read_inner(file: &mut std::fs::File, offset: u64, length: usize) -> std::io::Result<Vec<u8>> {
-        let sf = SeekFrom::Start(offset);
-        file.seek(sf)?;
+    fn read_inner(file: &dyn DriveFile, offset: u64, length: usize) -> std::io::Result<Vec<u8>> {
         let mut buf = vec![0; length];
-
-        let length = file.read(&mut buf)?;
-        buf.resize(length, 0u8);
+        let length = file.read_at(offset, &mut buf)?;
+        buf.truncate(length);
         Ok(buf)
     }
 }
 
-pub(crate) fn close_device(backend: &mut NixRdpdrBackend, req_inner: DeviceCloseRequest) -> PduResult<Vec<SvcMessage>> {
-    backend.file_map.remove(&req_inner.device_io_request.file_id);
-    backend.file_path_map.remove(&req_inner.device_io_request.file_id);
-    backend.file_dir_map.remove(&req_inner.device_io_request.file_id);
+pub(crate) fn close_device(backend: &mut NixRdpdrBackend, req_inner: DeviceCloseRequest) -> PduResult<Vec<RdpdrPdu>> {
+    let mut messages = Vec::new();
+    let file_id = req_inner.device_io_request.file_id;
+    let file = backend.file_map.remove(&file_id);
+    let path = backend.file_path_map.remove(&file_id);
+    backend.file_dir_map.remove(&file_id);
+    backend.locks.close(file_id);
+    #[cfg(target_os = "linux")]
+    if let Some(watcher) = backend.watcher.as_mut() {
+        // Closing the handle cancels the pending change notification.
+        if let Some(res) = watcher.close(file_id) {
+            messages.push(RdpdrPdu::ClientDriveNotifyChangeDirectoryResponse(res));
+        }
+    }
+    if let (true, Some(path)) = (backend.delete_on_close.remove(&file_id), path) {
+        let is_dir = file.and_then(|file| file.stat().ok()).is_some_and(|stat| stat.is_dir);
+        let result = if is_dir {
+            backend.fs.remove_dir(&path)
+        } else {
+            backend.fs.remove_file(&path)
+        };
+        if let Err(error) = result {
+            warn!(%error, %path, "Failed to delete file on close");
+        }
+    }
     let res = RdpdrPdu::DeviceCloseResponse(DeviceCloseResponse {
         device_io_response: DeviceIoResponse::new(req_inner.device_io_request, NtStatus::SUCCESS),
     });
-    Ok(vec![SvcMessage::from(res)])
+    messages.push(res);
+    Ok(messages)
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn notify_change_directory(
+    backend: &mut NixRdpdrBackend,
+    req_inner: ServerDriveNotifyChangeDirectoryRequest,
+) -> PduResult<Vec<RdpdrPdu>> {
+    let Some(path) = backend.file_path_map.get(&req_inner.device_io_request.file_id) else {
+        warn!("no directory to watch");
+        let res = ClientDriveNotifyChangeDirectoryResponse::new(&req_inner, NtStatus::NO_SUCH_FILE);
+        return Ok(vec![RdpdrPdu::ClientDriveNotifyChangeDirectoryResponse(res)]);
+    };
+    let Some(path) = backend.fs.host_path(path) else {
+        debug!("Directory change notifications are only supported on the host file system");
+        let res = ClientDriveNotifyChangeDirectoryResponse::new(&req_inner, NtStatus::NOT_SUPPORTED);
+        return Ok(vec![RdpdrPdu::ClientDriveNotifyChangeDirectoryResponse(res)]);
+    };
+
+    let watcher = match backend.watcher.as_mut() {
+        Some(watcher) => watcher,
+        None => match DirectoryWatcher::new(backend.proxy.clone()) {
+            Ok(watcher) => backend.watcher.insert(watcher),
+            Err(error) => {
+                warn!(%error, "Failed to create the directory watcher");
+                let res = ClientDriveNotifyChangeDirectoryResponse::new(&req_inner, NtStatus::NOT_SUPPORTED);
+                return Ok(vec![RdpdrPdu::ClientDriveNotifyChangeDirectoryResponse(res)]);
+            }
+        },
+    };
+
+    Ok(watcher
+        .notify_change(&path, req_inner)
+        .into_iter()
+        .map(RdpdrPdu::ClientDriveNotifyChangeDirectoryResponse)
+        .collect())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn notify_change_directory(
+    _backend: &mut NixRdpdrBackend,
+    req_inner: ServerDriveNotifyChangeDirectoryRequest,
+) -> PduResult<Vec<RdpdrPdu>> {
+    let res = ClientDriveNotifyChangeDirectoryResponse::new(&req_inner, NtStatus::NOT_SUPPORTED);
+    Ok(vec![RdpdrPdu::ClientDriveNotifyChangeDirectoryResponse(res)])
 }
 
 pub(crate) fn query_information(
     backend: &mut NixRdpdrBackend,
     req_inner: ServerDriveQueryInformationRequest,
-) -> PduResult<Vec<SvcMessage>> {
+) -> PduResult<Vec<RdpdrPdu>> {
     match backend.file_map.get(&req_inner.device_io_request.file_id) {
-        Some(file) => match file.metadata() {
-            Ok(meta) => {
+        Some(file) => match file.stat() {
+            Ok(stat) => {
                 let path = backend
                     .file_path_map
                     .get(&req_inner.device_io_request.file_id)
                     .cloned()
                     .unwrap_or_default();
-                let name_index = match path.rfind('/') {
-                    // in fact, index only needs to be different for existing requests
-                    #[allow(clippy::arithmetic_side_effects)]
-                    Some(index) => index + 1,
-                    None => 0,
-                };
-                let name = &path[name_index..];
-                let file_attribute = get_file_attributes(&meta, name);
+                let file_attribute = get_file_attributes(&stat, fs::file_name(&path));
                 if FileInformationClassLevel::FILE_BASIC_INFORMATION == req_inner.file_info_class_lvl {
                     let basic_info = FileBasicInformation {
-                        creation_time: transform_to_filetime(meta.ctime()),
-                        last_access_time: transform_to_filetime(meta.atime()),
-                        last_write_time: transform_to_filetime(meta.mtime()),
-                        change_time: transform_to_filetime(meta.ctime()),
+                        creation_time: transform_to_filetime(stat.creation_time),
+                        last_access_time: transform_to_filetime(stat.last_access_time),
+                        last_write_time: transform_to_filetime(stat.last_write_time),
+                        change_time: transform_to_filetime(stat.change_time),
                         file_attributes: file_attribute,
                     };
                     let res = RdpdrPdu::ClientDriveQueryInformationResponse(ClientDriveQueryInformationResponse {
                         device_io_response: DeviceIoResponse::new(req_inner.device_io_request, NtStatus::SUCCESS),
                         buffer: Some(FileInformationClass::Basic(basic_info)),
                     });
-                    Ok(vec![SvcMessage::from(res)])
+                    Ok(vec![res])
                 } else if FileInformationClassLevel::FILE_STANDARD_INFORMATION == req_inner.file_info_class_lvl {
-                    let dir = if meta.is_dir() { Boolean::True } else { Boolean::False };
+                    let dir = if stat.is_dir { Boolean::True } else { Boolean::False };
                     let standard_info = FileStandardInformation {
-                        allocation_size: i64::try_from(meta.size()).unwrap(),
-                        end_of_file: i64::try_from(meta.size()).unwrap(),
-                        number_of_links: u32::try_from(meta.nlink()).unwrap(),
-                        delete_pending: Boolean::False,
+                        allocation_size: i64::try_from(stat.size).unwrap(),
+                        end_of_file: i64::try_from(stat.size).unwrap(),
+                        number_of_links: 1,
+                        delete_pending: if backend.delete_on_close.contains(&req_inner.device_io_request.file_id) {
+                            Boolean::True
+                        } else {
+                            Boolean::False
+                        },
                         directory: dir,
                     };
                     let res = RdpdrPdu::ClientDriveQueryInformationResponse(ClientDriveQueryInformationResponse {
                         device_io_response: DeviceIoResponse::new(req_inner.device_io_request, NtStatus::SUCCESS),
                         buffer: Some(FileInformationClass::Standard(standard_info)),
                     });
-                    Ok(vec![SvcMessage::from(res)])
+                    Ok(vec![res])
                 } else if FileInformationClassLevel::FILE_ATTRIBUTE_TAG_INFORMATION == req_inner.file_info_class_lvl {
                     let info = FileAttributeTagInformation {
                         file_attributes: file_attribute,
@@ -228,14 +309,14 @@ pub(crate) fn query_information(
                         device_io_response: DeviceIoResponse::new(req_inner.device_io_request, NtStatus::SUCCESS),
                         buffer: Some(FileInformationClass::AttributeTag(info)),
                     });
-                    Ok(vec![SvcMessage::from(res)])
+                    Ok(vec![res])
                 } else {
                     warn!("unsupported file class");
                     let res = RdpdrPdu::ClientDriveQueryInformationResponse(ClientDriveQueryInformationResponse {
                         device_io_response: DeviceIoResponse::new(req_inner.device_io_request, NtStatus::UNSUCCESSFUL),
                         buffer: None,
                     });
-                    Ok(vec![SvcMessage::from(res)])
+                    Ok(vec![res])
                 }
             }
             Err(error) => {
@@ -244,7 +325,7 @@ pub(crate) fn query_information(
                     device_io_response: DeviceIoResponse::new(req_inner.device_io_request, NtStatus::UNSUCCESSFUL),
                     buffer: None,
                 });
-                Ok(vec![SvcMessage::from(res)])
+                Ok(vec![res])
             }
         },
         None => {
@@ -253,7 +334,7 @@ pub(crate) fn query_information(
                 device_io_response: DeviceIoResponse::new(req_inner.device_io_request, NtStatus::NO_SUCH_FILE),
                 buffer: None,
             });
-            Ok(vec![SvcMessage::from(res)])
+            Ok(vec![res])
         }
     }
 }
@@ -261,95 +342,90 @@ pub(crate) fn query_information(
 pub(crate) fn query_volume_information(
     backend: &mut NixRdpdrBackend,
     req_inner: ServerDriveQueryVolumeInformationRequest,
-) -> PduResult<Vec<SvcMessage>> {
+) -> PduResult<Vec<RdpdrPdu>> {
     match backend.file_map.get(&req_inner.device_io_request.file_id) {
         Some(file) => {
-            if let Ok(statvfs) = nix::sys::statvfs::fstatvfs(file.as_fd()) {
+            if let Ok(volume) = backend.fs.volume_stat() {
                 if FileSystemInformationClassLevel::FILE_FS_FULL_SIZE_INFORMATION == req_inner.fs_info_class_lvl {
-                    #[cfg_attr(target_os = "macos", expect(clippy::unnecessary_fallible_conversions))]
                     let info = FileFsFullSizeInformation {
-                        total_alloc_units: i64::try_from(statvfs.blocks()).unwrap(),
-                        caller_available_alloc_units: i64::try_from(statvfs.blocks_available()).unwrap(),
-                        actual_available_alloc_units: i64::try_from(statvfs.blocks_available()).unwrap(),
-                        sectors_per_alloc_unit: u32::try_from(statvfs.fragment_size()).unwrap(),
+                        total_alloc_units: i64::try_from(volume.total_blocks).unwrap_or(i64::MAX),
+                        caller_available_alloc_units: i64::try_from(volume.available_blocks).unwrap_or(i64::MAX),
+                        actual_available_alloc_units: i64::try_from(volume.available_blocks).unwrap_or(i64::MAX),
+                        sectors_per_alloc_unit: volume.block_size,
                         bytes_per_sector: 1,
                     };
 
-                    Ok(vec![SvcMessage::from(
-                        RdpdrPdu::ClientDriveQueryVolumeInformationResponse(
-                            ClientDriveQueryVolumeInformationResponse {
-                                device_io_reply: DeviceIoResponse::new(req_inner.device_io_request, NtStatus::SUCCESS),
-                                buffer: Some(FileSystemInformationClass::FileFsFullSizeInformation(info)),
-                            },
-                        ),
+                    Ok(vec![RdpdrPdu::ClientDriveQueryVolumeInformationResponse(
+                        ClientDriveQueryVolumeInformationResponse {
+                            device_io_reply: DeviceIoResponse::new(req_inner.device_io_request, NtStatus::SUCCESS),
+                            buffer: Some(FileSystemInformationClass::FileFsFullSizeInformation(info)),
+                        },
                     )])
                 } else if FileSystemInformationClassLevel::FILE_FS_ATTRIBUTE_INFORMATION == req_inner.fs_info_class_lvl
                 {
-                    Ok(vec![SvcMessage::from(
-                        RdpdrPdu::ClientDriveQueryVolumeInformationResponse(
-                            ClientDriveQueryVolumeInformationResponse {
-                                device_io_reply: DeviceIoResponse::new(req_inner.device_io_request, NtStatus::SUCCESS),
-                                buffer: Some(FileSystemInformationClass::FileFsAttributeInformation(
-                                    FileFsAttributeInformation {
-                                        file_system_attributes: FileSystemAttributes::FILE_CASE_SENSITIVE_SEARCH
-                                            | FileSystemAttributes::FILE_CASE_PRESERVED_NAMES
-                                            | FileSystemAttributes::FILE_UNICODE_ON_DISK,
-                                        max_component_name_len: 260,
-                                        file_system_name: "FAT32".to_owned(),
-                                    },
-                                )),
-                            },
-                        ),
+                    Ok(vec![RdpdrPdu::ClientDriveQueryVolumeInformationResponse(
+                        ClientDriveQueryVolumeInformationResponse {
+                            device_io_reply: DeviceIoResponse::new(req_inner.device_io_request, NtStatus::SUCCESS),
+                            buffer: Some(FileSystemInformationClass::FileFsAttributeInformation(
+                                FileFsAttributeInformation {
+                                    file_system_attributes: FileSystemAttributes::FILE_CASE_SENSITIVE_SEARCH
+                                        | FileSystemAttributes::FILE_CASE_PRESERVED_NAMES
+                                        | FileSystemAttributes::FILE_UNICODE_ON_DISK,
+                                    max_component_name_len: 260,
+                                    file_system_name: "FAT32".to_owned(),
+                                },
+                            )),
+                        },
                     )])
                 } else if FileSystemInformationClassLevel::FILE_FS_VOLUME_INFORMATION == req_inner.fs_info_class_lvl {
-                    Ok(vec![SvcMessage::from(
-                        RdpdrPdu::ClientDriveQueryVolumeInformationResponse(
-                            ClientDriveQueryVolumeInformationResponse {
-                                device_io_reply: DeviceIoResponse::new(req_inner.device_io_request, NtStatus::SUCCESS),
-                                buffer: Some(FileSystemInformationClass::FileFsVolumeInformation(
-                                    FileFsVolumeInformation {
-                                        volume_creation_time: transform_to_filetime(file.metadata().unwrap().ctime()),
-                                        // blocks_available() may have different integer type on different platforms.
-                                        // so we need to cast it to u32 uniformly. so if it is u32, it will emit 'useless conversion'
-                                        // warning, i choose to mute it.
-                                        #[allow(clippy::useless_conversion)]
-                                        volume_serial_number: u32::try_from(statvfs.blocks_available()).unwrap(),
-                                        supports_objects: Boolean::False,
-                                        volume_label: "IRON_RDP".to_owned(),
-                                    },
-                                )),
-                            },
-                        ),
+                    Ok(vec![RdpdrPdu::ClientDriveQueryVolumeInformationResponse(
+                        ClientDriveQueryVolumeInformationResponse {
+                            device_io_reply: DeviceIoResponse::new(req_inner.device_io_request, NtStatus::SUCCESS),
+                            buffer: Some(FileSystemInformationClass::FileFsVolumeInformation(
+                                FileFsVolumeInformation {
+                                    volume_creation_time: transform_to_filetime(
+                                        file.stat().map_or(UNIX_EPOCH, |stat| stat.creation_time),
+                                    ),
+                                    volume_serial_number: u32::try_from(volume.available_blocks).unwrap_or(u32::MAX),
+                                    supports_objects: Boolean::False,
+                                    volume_label: "IRON_RDP".to_owned(),
+                                },
+                            )),
+                        },
                     )])
                 } else if FileSystemInformationClassLevel::FILE_FS_SIZE_INFORMATION == req_inner.fs_info_class_lvl {
-                    Ok(vec![SvcMessage::from(
-                        RdpdrPdu::ClientDriveQueryVolumeInformationResponse(
-                            ClientDriveQueryVolumeInformationResponse {
-                                device_io_reply: DeviceIoResponse::new(req_inner.device_io_request, NtStatus::SUCCESS),
-                                #[cfg_attr(target_os = "macos", expect(clippy::unnecessary_fallible_conversions))]
-                                buffer: Some(FileSystemInformationClass::FileFsSizeInformation(
-                                    FileFsSizeInformation {
-                                        total_alloc_units: i64::try_from(statvfs.blocks()).unwrap(),
-                                        available_alloc_units: i64::try_from(statvfs.blocks_free()).unwrap(),
-                                        sectors_per_alloc_unit: u32::try_from(statvfs.fragment_size()).unwrap(),
-                                        bytes_per_sector: 1,
-                                    },
-                                )),
-                            },
-                        ),
+                    Ok(vec![RdpdrPdu::ClientDriveQueryVolumeInformationResponse(
+                        ClientDriveQueryVolumeInformationResponse {
+                            device_io_reply: DeviceIoResponse::new(req_inner.device_io_request, NtStatus::SUCCESS),
+                            buffer: Some(FileSystemInformationClass::FileFsSizeInformation(
+                                FileFsSizeInformation {
+                                    total_alloc_units: i64::try_from(volume.total_blocks).unwrap_or(i64::MAX),
+                                    available_alloc_units: i64::try_from(volume.free_blocks).unwrap_or(i64::MAX),
+                                    sectors_per_alloc_unit: volume.block_size,
+                                    bytes_per_sector: 1,
+                                },
+                            )),
+                        },
+                    )])
+                } else if FileSystemInformationClassLevel::FILE_FS_DEVICE_INFORMATION == req_inner.fs_info_class_lvl {
+                    Ok(vec![RdpdrPdu::ClientDriveQueryVolumeInformationResponse(
+                        ClientDriveQueryVolumeInformationResponse {
+                            device_io_reply: DeviceIoResponse::new(req_inner.device_io_request, NtStatus::SUCCESS),
+                            buffer: Some(FileSystemInformationClass::FileFsDeviceInformation(
+                                FileFsDeviceInformation {
+                                    device_type: FILE_DEVICE_DISK,
+                                    characteristics: Characteristics::FILE_REMOTE_DEVICE,
+                                },
+                            )),
+                        },
                     )])
                 } else {
                     warn!("unsupported volume class");
-                    Ok(vec![SvcMessage::from(
-                        RdpdrPdu::ClientDriveQueryVolumeInformationResponse(
-                            ClientDriveQueryVolumeInformationResponse {
-                                device_io_reply: DeviceIoResponse::new(
-                                    req_inner.device_io_request,
-                                    NtStatus::UNSUCCESSFUL,
-                                ),
-                                buffer: None,
-                            },
-                        ),
+                    Ok(vec![RdpdrPdu::ClientDriveQueryVolumeInformationResponse(
+                        ClientDriveQueryVolumeInformationResponse {
+                            device_io_reply: DeviceIoResponse::new(req_inner.device_io_request, NtStatus::UNSUCCESSFUL),
+                            buffer: None,
+                        },
                     )])
                 }
             } else {
@@ -358,7 +434,7 @@ pub(crate) fn query_volume_information(
                     device_io_response: DeviceIoResponse::new(req_inner.device_io_request, NtStatus::NO_SUCH_FILE),
                     buffer: None,
                 });
-                Ok(vec![SvcMessage::from(res)])
+                Ok(vec![res])
             }
         }
         None => {
@@ -367,7 +443,7 @@ pub(crate) fn query_volume_information(
                 device_io_response: DeviceIoResponse::new(req_inner.device_io_request, NtStatus::NO_SUCH_FILE),
                 buffer: None,
             });
-            Ok(vec![SvcMessage::from(res)])
+            Ok(vec![res])
         }
     }
 }
@@ -375,87 +451,237 @@ pub(crate) fn query_volume_information(
 pub(crate) fn set_information(
     backend: &mut NixRdpdrBackend,
     req_inner: ServerDriveSetInformationRequest,
-) -> PduResult<Vec<SvcMessage>> {
-    match backend.file_path_map.get(&req_inner.device_io_request.file_id) {
-        Some(file) => {
-            match &req_inner.set_buffer {
-                FileInformationClass::Rename(info) => {
-                    let mut to = backend.file_base.clone();
-                    to.push_str(&info.file_name.replace('\\', "/"));
-                    if let Err(error) = std::fs::rename(file, to) {
-                        warn!(?error, "Rename file error");
-                        let res = RdpdrPdu::ClientDriveSetInformationResponse(
-                            ClientDriveSetInformationResponse::new(&req_inner, NtStatus::UNSUCCESSFUL)
-                                .map_err(|e| encode_err!(e))?,
-                        );
-                        return Ok(vec![SvcMessage::from(res)]);
-                    }
-                }
-                FileInformationClass::Allocation(_) => {
-                    //nothing to do
-                }
-                FileInformationClass::Disposition(_) => {
-                    if let Err(error) = std::fs::remove_file(file) {
-                        warn!(?error, "Remove file error");
-                        let res = RdpdrPdu::ClientDriveSetInformationResponse(
-                            ClientDriveSetInformationResponse::new(&req_inner, NtStatus::UNSUCCESSFUL)
-                                .map_err(|e| encode_err!(e))?,
-                        );
-                        return Ok(vec![SvcMessage::from(res)]);
-                    }
-                }
-                FileInformationClass::EndOfFile(info) => {
-                    if let Some(file) = backend.file_map.get(&req_inner.device_io_request.file_id) {
-                        // SAFETY: the file must has been opened with write access in the last steps, since rdp prepares to set information. In addition it is a regular file.
-                        let set_end_res = unsafe { nix::libc::ftruncate(file.as_raw_fd(), info.end_of_file) };
-                        if set_end_res < 0 {
-                            let error = nix::errno::Errno::last();
-                            warn!(%error, "Failed to set end of file");
-                            let res = RdpdrPdu::ClientDriveSetInformationResponse(
-                                ClientDriveSetInformationResponse::new(&req_inner, NtStatus::UNSUCCESSFUL)
-                                    .map_err(|e| encode_err!(e))?,
-                            );
-                            return Ok(vec![SvcMessage::from(res)]);
-                        }
-                    } else {
-                        warn!("no such file");
-                        let res = RdpdrPdu::ClientDriveSetInformationResponse(
-                            ClientDriveSetInformationResponse::new(&req_inner, NtStatus::NO_SUCH_FILE)
-                                .map_err(|e| encode_err!(e))?,
-                        );
-                        return Ok(vec![SvcMessage::from(res)]);
-                    }
-                }
-                _ => {
-                    // TODO
-                }
+) -> PduResult<Vec<RdpdrPdu>> {
+    let file_id = req_inner.device_io_request.file_id;
+    let status = match (
+        backend.file_map.get(&file_id),
+        backend.file_path_map.get(&file_id).cloned(),
+    ) {
+        (Some(file), Some(path)) => match &req_inner.set_buffer {
+            FileInformationClass::Basic(info) => set_basic_information(file.as_ref(), info),
+            FileInformationClass::EndOfFile(info) => set_end_of_file(file.as_ref(), info.end_of_file),
+            FileInformationClass::Allocation(info) => set_allocation_size(file.as_ref(), info.allocation_size),
+            FileInformationClass::Disposition(info) => {
+                set_delete_on_close(backend, file_id, &path, info.delete_pending != 0)
             }
-        }
-        None => {
+            FileInformationClass::Rename(info) => rename(backend, &path, info),
+            _ => {
+                warn!("unsupported file class");
+                NtStatus::NOT_SUPPORTED
+            }
+        },
+        _ => {
             warn!("no such file");
-            let res = RdpdrPdu::ClientDriveSetInformationResponse(
-                ClientDriveSetInformationResponse::new(&req_inner, NtStatus::NO_SUCH_FILE)
-                    .map_err(|e| encode_err!(e))?,
-            );
-            return Ok(vec![SvcMessage::from(res)]);
+            NtStatus::NO_SUCH_FILE
+        }
+    };
+
+    Ok(vec![RdpdrPdu::ClientDriveSetInformationResponse(
+        ClientDriveSetInformationResponse::new(&req_inner, status).map_err(|e| encode_err!(e))?,
+    )])
+}
+
+fn set_basic_information(file: &dyn DriveFile, info: &FileBasicInformation) -> NtStatus {
+    // Zero leaves a timestamp unchanged, and -1 only disables its automatic updates.
+    // The creation and change times cannot be set.
+    let time = |time: i64| match time {
+        0 | -1 => None,
+        time => Some(transform_from_filetime(time)),
+    };
+    let last_access_time = time(info.last_access_time);
+    let last_write_time = time(info.last_write_time);
+    if last_access_time.is_some() || last_write_time.is_some() {
+        if let Err(error) = file.set_times(last_access_time, last_write_time) {
+            warn!(%error, "Failed to set file times");
+            return io_status(&error);
+        }
+    }
+
+    // Zero leaves the attributes unchanged, and only the read-only attribute maps to the permissions.
+    if info.file_attributes.is_empty() {
+        return NtStatus::SUCCESS;
+    }
+    let stat = match file.stat() {
+        Ok(stat) => stat,
+        Err(error) => {
+            warn!(%error, "Get file metadata error");
+            return io_status(&error);
+        }
+    };
+    let readonly = info.file_attributes.contains(FileAttributes::FILE_ATTRIBUTE_READONLY);
+    if stat.is_dir || stat.readonly == readonly {
+        return NtStatus::SUCCESS;
+    }
+    match file.set_readonly(readonly) {
+        Ok(()) => NtStatus::SUCCESS,
+        Err(error) => {
+            warn!(%error, "Failed to set file permissions");
+            io_status(&error)
+        }
+    }
+}
+
+fn set_end_of_file(file: &dyn DriveFile, end_of_file: i64) -> NtStatus {
+    let Ok(end_of_file) = u64::try_from(end_of_file) else {
+        return NtStatus::INVALID_PARAMETER;
+    };
+    match file.set_len(end_of_file) {
+        Ok(()) => NtStatus::SUCCESS,
+        Err(error) => {
+            warn!(%error, "Failed to set end of file");
+            io_status(&error)
         }
     }
-    Ok(vec![SvcMessage::from(RdpdrPdu::ClientDriveSetInformationResponse(
-        ClientDriveSetInformationResponse::new(&req_inner, NtStatus::SUCCESS).map_err(|e| encode_err!(e))?,
-    ))])
 }
 
-// in fact, it is time in secs which is very small
+fn set_allocation_size(file: &dyn DriveFile, allocation_size: i64) -> NtStatus {
+    let Ok(allocation_size) = u64::try_from(allocation_size) else {
+        return NtStatus::INVALID_PARAMETER;
+    };
+    // Allocation is left to the file system, but the file is truncated if it does not fit anymore.
+    match file.stat() {
+        Ok(stat) if stat.size > allocation_size => set_end_of_file(file, i64::try_from(allocation_size).unwrap()),
+        Ok(_) => NtStatus::SUCCESS,
+        Err(error) => {
+            warn!(%error, "Get file metadata error");
+            io_status(&error)
+        }
+    }
+}
+
+fn set_delete_on_close(backend: &mut NixRdpdrBackend, file_id: u32, path: &str, delete: bool) -> NtStatus {
+    if !delete {
+        backend.delete_on_close.remove(&file_id);
+        return NtStatus::SUCCESS;
+    }
+
+    // Directories can only be deleted when empty.
+    if let Ok(entries) = backend.fs.read_dir(path) {
+        if !entries.is_empty() {
+            return NtStatus::DIRECTORY_NOT_EMPTY;
+        }
+    }
+    backend.delete_on_close.insert(file_id);
+    NtStatus::SUCCESS
+}
+
+fn rename(backend: &mut NixRdpdrBackend, from: &str, info: &FileRenameInformation) -> NtStatus {
+    let to = match drive_path(&info.file_name) {
+        Ok(to) => to,
+        Err(error) => {
+            warn!(%error, "Invalid rename path");
+            return io_status(&error);
+        }
+    };
+
+    if to != from {
+        if let Ok(stat) = backend.fs.stat(&to) {
+            if info.replace_if_exists == Boolean::False {
+                return NtStatus::OBJECT_NAME_COLLISION;
+            }
+            // Only files can be replaced.
+            if stat.is_dir {
+                return NtStatus::ACCESS_DENIED;
+            }
+        }
+    }
+
+    if let Err(error) = backend.fs.rename(from, &to) {
+        warn!(%error, "Rename file error");
+        return io_status(&error);
+    }
+
+    // The files opened below a renamed directory move along with it.
+    for path in backend.file_path_map.values_mut() {
+        if path == from {
+            path.clone_from(&to);
+        } else if let Some(rest) = path.strip_prefix(from).filter(|rest| rest.starts_with('/')) {
+            *path = format!("{to}{rest}");
+        }
+    }
+    NtStatus::SUCCESS
+}
+
+pub(crate) fn lock_control(
+    backend: &mut NixRdpdrBackend,
+    req_inner: ServerDriveLockControlRequest,
+) -> PduResult<Vec<RdpdrPdu>> {
+    let Some(file) = backend.file_map.get(&req_inner.device_io_request.file_id) else {
+        warn!("no such file");
+        let res = ClientDriveLockControlResponse::new(&req_inner, NtStatus::NO_SUCH_FILE);
+        return Ok(vec![RdpdrPdu::ClientDriveLockControlResponse(res)]);
+    };
+
+    let Some(file) = file.host_file() else {
+        // The other files can only be accessed through this drive, so there is nobody to conflict with
+        // but the server itself, which keeps track of its own locks.
+        let res = ClientDriveLockControlResponse::new(&req_inner, NtStatus::SUCCESS);
+        return Ok(vec![RdpdrPdu::ClientDriveLockControlResponse(res)]);
+    };
+
+    Ok(backend
+        .locks
+        .lock_control(file, req_inner, backend.proxy.as_ref())
+        .map(RdpdrPdu::ClientDriveLockControlResponse)
+        .into_iter()
+        .collect())
+}
+
+/// Maps an I/O error to the closest status.
+fn io_status(error: &std::io::Error) -> NtStatus {
+    match error.kind() {
+        std::io::ErrorKind::NotFound => NtStatus::NO_SUCH_FILE,
+        std::io::ErrorKind::PermissionDenied => NtStatus::ACCESS_DENIED,
+        std::io::ErrorKind::AlreadyExists => NtStatus::OBJECT_NAME_COLLISION,
+        _ => match error.raw_os_error().map(nix::errno::Errno::from_raw) {
+            Some(nix::errno::Errno::ENOTEMPTY) => NtStatus::DIRECTORY_NOT_EMPTY,
+            // The file is not open for writing.
+            Some(nix::errno::Errno::EBADF | nix::errno::Errno::EINVAL) => NtStatus::ACCESS_DENIED,
+            _ => NtStatus::UNSUCCESSFUL,
+        },
+    }
+}
+
+/// FILETIME of the UNIX epoch, in 100-nanosecond intervals since January 1, 1601.
+const UNIX_EPOCH_FILETIME: i64 = 116444736000000000;
+
+// 100-nanosecond intervals of any SystemTime fit in an i128
 #[allow(clippy::arithmetic_side_effects)]
-pub(crate) fn transform_to_filetime(time_in_secs: i64) -> i64 {
-    let mut time = time_in_secs * 10000000;
-    time += 116444736000000000;
-    time
+pub(crate) fn transform_to_filetime(time: SystemTime) -> i64 {
+    let intervals = match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => i128::try_from(since.as_nanos() / 100).unwrap_or(i128::MAX),
+        Err(error) => -i128::try_from(error.duration().as_nanos() / 100).unwrap_or(i128::MAX),
+    };
+    let filetime = intervals.saturating_add(i128::from(UNIX_EPOCH_FILETIME));
+    i64::try_from(filetime.max(0)).unwrap_or(i64::MAX)
 }
 
-pub(crate) fn get_file_attributes(meta: &std::fs::Metadata, file_name: &str) -> FileAttributes {
+// the remainder is below 10^7, so it does not overflow once converted to nanoseconds
+#[allow(clippy::arithmetic_side_effects)]
+fn transform_from_filetime(filetime: i64) -> SystemTime {
+    let intervals = filetime.saturating_sub(UNIX_EPOCH_FILETIME);
+    let since = Duration::new(
+        intervals.unsigned_abs() / 10000000,
+        u32::try_from(intervals.unsigned_abs() % 10000000).unwrap() * 100,
+    );
+    let time = if intervals >= 0 {
+        UNIX_EPOCH.checked_add(since)
+    } else {
+        UNIX_EPOCH.checked_sub(since)
+    };
+    time.unwrap_or(UNIX_EPOCH)
+}
+
+/// Converts a path from the server, using `\\` as separator, to a normalized path of the drive.
+///
+/// The paths containing `.` or `..` components are rejected, so that they can't lead outside of the drive.
+fn drive_path(path: &str) -> std::io::Result<String> {
+    fs::normalize(&path.replace('\\', "/"))
+}
+
+pub(crate) fn get_file_attributes(stat: &FileStat, file_name: &str) -> FileAttributes {
     let mut file_attribute = FileAttributes::empty();
-    if meta.is_dir() {
+    if stat.is_dir {
         file_attribute |= FileAttributes::FILE_ATTRIBUTE_DIRECTORY;
     }
     if file_attribute.is_empty() {
@@ -465,77 +691,103 @@ pub(crate) fn get_file_attributes(meta: &std::fs::Metadata, file_name: &str) ->
     if file_name.len() > 1 && file_name.starts_with('.') && file_name.as_bytes()[1] != b'.' {
         file_attribute |= FileAttributes::FILE_ATTRIBUTE_HIDDEN;
     }
-    if meta.permissions().readonly() {
+    if stat.readonly {
         file_attribute |= FileAttributes::FILE_ATTRIBUTE_READONLY;
     }
     file_attribute
 }
 
 pub(crate) fn make_query_dir_resp(
+    fs: &dyn DriveFilesystem,
     find_file_name: Option<String>,
     device_io_request: DeviceIoRequest,
     file_class: FileInformationClassLevel,
     initial_query: bool,
-) -> PduResult<Vec<SvcMessage>> {
+) -> PduResult<Vec<RdpdrPdu>> {
     let not_found_status = if initial_query {
         NtStatus::NO_SUCH_FILE
     } else {
         NtStatus::NO_MORE_FILES
     };
     match find_file_name {
-        None => Ok(vec![SvcMessage::from(RdpdrPdu::ClientDriveQueryDirectoryResponse(
+        None => Ok(vec![RdpdrPdu::ClientDriveQueryDirectoryResponse(
             ClientDriveQueryDirectoryResponse {
                 device_io_reply: DeviceIoResponse::new(device_io_request, not_found_status),
                 buffer: None,
             },
-        ))]),
+        )]),
         Some(file_full_path) => {
-            // in fact, it represents file name, so it is not very large
-            #[allow(clippy::arithmetic_side_effects)]
-            let file_last_slash = if let Some(index) = file_full_path.rfind('/') {
-                index + 1
-            } else {
-                0
-            };
-            let file_name = &file_full_path[file_last_slash..];
-            match std::fs::metadata(&file_full_path) {
-                Ok(meta) => {
-                    let file_attribute = get_file_attributes(&meta, file_name);
-                    if file_class == FileInformationClassLevel::FILE_BOTH_DIRECTORY_INFORMATION {
-                        let info = FileBothDirectoryInformation::new(
-                            transform_to_filetime(meta.ctime()),
-                            transform_to_filetime(meta.ctime()),
-                            transform_to_filetime(meta.atime()),
-                            transform_to_filetime(meta.mtime()),
-                            i64::try_from(meta.size()).unwrap(),
-                            file_attribute,
-                            file_name.to_owned(),
-                        );
-                        let info2 = FileInformationClass::BothDirectory(info);
-                        Ok(vec![SvcMessage::from(RdpdrPdu::ClientDriveQueryDirectoryResponse(
-                            ClientDriveQueryDirectoryResponse {
-                                device_io_reply: DeviceIoResponse::new(device_io_request, NtStatus::SUCCESS),
-                                buffer: Some(info2),
-                            },
-                        ))])
-                    } else {
-                        warn!("unsupported file class for query directory");
-                        Ok(vec![SvcMessage::from(RdpdrPdu::ClientDriveQueryDirectoryResponse(
-                            ClientDriveQueryDirectoryResponse {
-                                device_io_reply: DeviceIoResponse::new(device_io_request, NtStatus::NOT_SUPPORTED),
-                                buffer: None,
-                            },
-                        ))])
-                    }
+            let file_name = fs::file_name(&file_full_path);
+            match fs.stat(&file_full_path) {
+                Ok(stat) => {
+                    let file_attribute = get_file_attributes(&stat, file_name);
+                    let creation_time = transform_to_filetime(stat.creation_time);
+                    let last_access_time = transform_to_filetime(stat.last_access_time);
+                    let last_write_time = transform_to_filetime(stat.last_write_time);
+                    let change_time = transform_to_filetime(stat.change_time);
+                    let file_size = i64::try_from(stat.size).unwrap();
+                    let buffer = match file_class {
+                        FileInformationClassLevel::FILE_BOTH_DIRECTORY_INFORMATION => {
+                            FileInformationClass::BothDirectory(FileBothDirectoryInformation::new(
+                                creation_time,
+                                last_access_time,
+                                last_write_time,
+                                change_time,
+                                file_size,
+                                file_attribute,
+                                file_name.to_owned(),
+                            ))
+                        }
+                        FileInformationClassLevel::FILE_FULL_DIRECTORY_INFORMATION => {
+                            FileInformationClass::FullDirectory(FileFullDirectoryInformation::new(
+                                creation_time,
+                                last_access_time,
+                                last_write_time,
+                                change_time,
+                                file_size,
+                                file_attribute,
+                                file_name.to_owned(),
+                            ))
+                        }
+                        FileInformationClassLevel::FILE_DIRECTORY_INFORMATION => {
+                            FileInformationClass::Directory(FileDirectoryInformation::new(
+                                creation_time,
+                                last_access_time,
+                                last_write_time,
+                                change_time,
+                                file_size,
+                                file_attribute,
+                                file_name.to_owned(),
+                            ))
+                        }
+                        FileInformationClassLevel::FILE_NAMES_INFORMATION => {
+                            FileInformationClass::Names(FileNamesInformation::new(file_name.to_owned()))
+                        }
+                        _ => {
+                            warn!("unsupported file class for query directory");
+                            return Ok(vec![RdpdrPdu::ClientDriveQueryDirectoryResponse(
+                                ClientDriveQueryDirectoryResponse {
+                                    device_io_reply: DeviceIoResponse::new(device_io_request, NtStatus::NOT_SUPPORTED),
+                                    buffer: None,
+                                },
+                            )]);
+                        }
+                    };
+                    Ok(vec![RdpdrPdu::ClientDriveQueryDirectoryResponse(
+                        ClientDriveQueryDirectoryResponse {
+                            device_io_reply: DeviceIoResponse::new(device_io_request, NtStatus::SUCCESS),
+                            buffer: Some(buffer),
+                        },
+                    )])
                 }
                 Err(error) => {
                     warn!(%error, "Get metadata error");
-                    Ok(vec![SvcMessage::from(RdpdrPdu::ClientDriveQueryDirectoryResponse(
+                    Ok(vec![RdpdrPdu::ClientDriveQueryDirectoryResponse(
                         ClientDriveQueryDirectoryResponse {
                             device_io_reply: DeviceIoResponse::new(device_io_request, not_found_status),
                             buffer: None,
                         },
-                    ))])
+                    )])
                 }
             }
         }
@@ -545,44 +797,27 @@ pub(crate) fn make_query_dir_resp(
 pub(crate) fn query_directory(
     backend: &mut NixRdpdrBackend,
     req_inner: ServerDriveQueryDirectoryRequest,
-) -> PduResult<Vec<SvcMessage>> {
+) -> PduResult<Vec<RdpdrPdu>> {
     match backend.file_path_map.get(&req_inner.device_io_request.file_id) {
         Some(parent_pos_for_next) => {
             let mut find_file_name = None;
             if req_inner.initial_query > 0 {
-                if req_inner.path.ends_with('*') {
-                    let mut parent = backend.file_base.clone();
-                    let query_path = req_inner.path.replace('\\', "/");
-                    let len = query_path.len();
-                    // path ends with *, so its len > 0
-                    #[allow(clippy::arithmetic_side_effects)]
-                    parent.push_str(&query_path[0..len - 1]);
-                    if let Ok(dirp) = Dir::open(
-                        parent.as_str(),
-                        nix::fcntl::OFlag::O_RDONLY,
-                        nix::sys::stat::Mode::empty(),
-                    ) {
-                        let mut iter = dirp.into_iter();
-                        while let Some(Ok(first)) = iter.next() {
-                            let file_name = first.file_name();
-                            if CString::new(".").unwrap().as_c_str() == file_name
-                                || CString::new("..").unwrap().as_c_str() == file_name
-                            {
-                                continue;
+                match drive_path(&req_inner.path) {
+                    Ok(query_path) => {
+                        if let Some(parent) = query_path.strip_suffix('*') {
+                            if let Ok(entries) = backend.fs.read_dir(parent) {
+                                let mut iter = entries.into_iter();
+                                find_file_name = iter.next().map(|name| fs::join(parent, &name));
+                                backend.file_dir_map.insert(req_inner.device_io_request.file_id, iter);
                             }
-                            parent.push_str(file_name.to_string_lossy().into_owned().as_str());
-                            find_file_name = Some(parent);
-                            break;
+                        } else {
+                            find_file_name = Some(query_path);
                         }
-                        backend.file_dir_map.insert(req_inner.device_io_request.file_id, iter);
                     }
-                } else {
-                    let mut full_path = backend.file_base.clone();
-                    let query_path = req_inner.path.replace('\\', "/");
-                    full_path.push_str(&query_path);
-                    find_file_name = Some(full_path);
+                    Err(error) => warn!(%error, "Invalid query path"),
                 }
                 make_query_dir_resp(
+                    backend.fs.as_ref(),
                     find_file_name,
                     req_inner.device_io_request,
                     req_inner.file_info_class_lvl,
@@ -590,17 +825,10 @@ pub(crate) fn query_directory(
                 )
             } else {
                 if let Some(dirp_iter) = backend.file_dir_map.get_mut(&req_inner.device_io_request.file_id) {
-                    if let Some(Ok(next)) = dirp_iter.next() {
-                        let file_name = next.file_name();
-                        let mut full_path = parent_pos_for_next.clone();
-                        if !full_path.ends_with('/') {
-                            full_path.push('/');
-                        }
-                        full_path.push_str(file_name.to_string_lossy().into_owned().as_str());
-                        find_file_name = Some(full_path);
-                    }
+                    find_file_name = dirp_iter.next().map(|name| fs::join(parent_pos_for_next, &name));
                 }
                 make_query_dir_resp(
+                    backend.fs.as_ref(),
                     find_file_name,
                     req_inner.device_io_request,
                     req_inner.file_info_class_lvl,
@@ -610,12 +838,12 @@ pub(crate) fn query_directory(
         }
         None => {
             warn!("no file to query directory");
-            Ok(vec![SvcMessage::from(RdpdrPdu::ClientDriveQueryDirectoryResponse(
+            Ok(vec![RdpdrPdu::ClientDriveQueryDirectoryResponse(
                 ClientDriveQueryDirectoryResponse {
                     device_io_reply: DeviceIoResponse::new(req_inner.device_io_request, NtStatus::NO_SUCH_FILE),
                     buffer: None,
                 },
-            ))])
+            )])
         }
     }
 }
@@ -624,7 +852,7 @@ fn make_create_drive_resp(
     device_io_request: DeviceIoRequest,
     create_disposation: CreateDisposition,
     file_id: u32,
-) -> PduResult<Vec<SvcMessage>> {
+) -> PduResult<Vec<RdpdrPdu>> {
     let io_response = DeviceIoResponse::new(device_io_request, NtStatus::SUCCESS);
     let information = match create_disposation {
         CreateDisposition::FILE_CREATE
@@ -640,22 +868,30 @@ fn make_create_drive_resp(
         file_id,
         information,
     });
-    Ok(vec![SvcMessage::from(res)])
+    Ok(vec![res])
 }
 // in fact, index only needs to be different, so it is ok
 #[allow(clippy::arithmetic_side_effects)]
-pub(crate) fn create_drive(
-    backend: &mut NixRdpdrBackend,
-    req_inner: DeviceCreateRequest,
-) -> PduResult<Vec<SvcMessage>> {
+pub(crate) fn create_drive(backend: &mut NixRdpdrBackend, req_inner: DeviceCreateRequest) -> PduResult<Vec<RdpdrPdu>> {
     let file_id = backend.file_id;
     backend.file_id += 1;
-    let mut path = String::from(backend.file_base.as_str());
-    path.push_str(&req_inner.path.replace('\\', "/"));
+    let path = match drive_path(&req_inner.path) {
+        Ok(path) => path,
+        Err(error) => {
+            warn!(%error, "Invalid create path");
+            let io_response = DeviceIoResponse::new(req_inner.device_io_request, io_status(&error));
+            let res = RdpdrPdu::DeviceCreateResponse(DeviceCreateResponse {
+                device_io_reply: io_response,
+                file_id,
+                information: Information::empty(),
+            });
+            return Ok(vec![res]);
+        }
+    };
     // first process directory
-    match std::fs::metadata(&path) {
-        Ok(meta) => {
-            if meta.is_dir() {
+    match backend.fs.stat(&path) {
+        Ok(stat) => {
+            if stat.is_dir {
                 if req_inner.create_disposition == CreateDisposition::FILE_CREATE {
                     warn!("Attempt to create directory, but it exists");
                     let io_response = DeviceIoResponse::new(req_inner.device_io_request, NtStatus::UNSUCCESSFUL);
@@ -664,7 +900,7 @@ pub(crate) fn create_drive(
                         file_id,
                         information: Information::empty(),
                     });
-                    return Ok(vec![SvcMessage::from(res)]);
+                    return Ok(vec![res]);
                 }
                 if req_inner.create_options.bits() & CreateOptions::FILE_NON_DIRECTORY_FILE.bits() != 0 {
                     warn!("Attempt to create a file, but it is a directory");
@@ -674,7 +910,7 @@ pub(crate) fn create_drive(
                         file_id,
                         information: Information::empty(),
                     });
-                    return Ok(vec![SvcMessage::from(res)]);
+                    return Ok(vec![res]);
                 }
                 // Return afterwards
                 // This can be unified with the condition for opening the file.
@@ -686,17 +922,20 @@ pub(crate) fn create_drive(
                     file_id,
                     information: Information::empty(),
                 });
-                return Ok(vec![SvcMessage::from(res)]);
+                return Ok(vec![res]);
             }
         }
         Err(_) => {
             if req_inner.create_options.bits() & CreateOptions::FILE_DIRECTORY_FILE.bits() != 0 {
                 if (req_inner.create_disposition == CreateDisposition::FILE_CREATE
                     || req_inner.create_disposition == CreateDisposition::FILE_OPEN_IF)
-                    && std::fs::create_dir_all(path.as_str()).is_ok()
+                    && backend.fs.create_dir(&path).is_ok()
                 {
-                    let mut fs = std::fs::OpenOptions::new();
-                    match fs.read(true).open(&path) {
+                    let options = OpenOptions {
+                        read: true,
+                        ..OpenOptions::default()
+                    };
+                    match backend.fs.open(&path, &options) {
                         Ok(file) => {
                             debug!("create drive file_id:{},path:{}", file_id, path);
                             backend.file_map.insert(file_id, file);
@@ -720,47 +959,55 @@ pub(crate) fn create_drive(
                     file_id,
                     information: Information::empty(),
                 });
-                return Ok(vec![SvcMessage::from(res)]);
+                return Ok(vec![res]);
             }
         }
     }
 
-    let mut fs = std::fs::OpenOptions::new();
-    if CreateDisposition::FILE_OPEN_IF == req_inner.create_disposition {
-        fs.create(true).write(true).read(true);
-    }
-    if CreateDisposition::FILE_CREATE == req_inner.create_disposition {
-        fs.create_new(true).write(true).read(true);
-    }
-    if CreateDisposition::FILE_SUPERSEDE == req_inner.create_disposition {
-        fs.create(true).write(true).append(true).read(true);
-    }
-    if CreateDisposition::FILE_OPEN == req_inner.create_disposition {
-        fs.read(true);
-    }
-    if CreateDisposition::FILE_OVERWRITE == req_inner.create_disposition {
-        fs.write(true).truncate(true).read(true);
-    }
-    if CreateDisposition::FILE_OVERWRITE_IF == req_inner.create_disposition {
-        fs.write(true).truncate(true).create(true).read(true);
+    let mut options = OpenOptions {
+        read: true,
+        ..OpenOptions::default()
+    };
+    match req_inner.create_disposition {
+        CreateDisposition::FILE_OPEN_IF => {
+            options.create = true;
+            options.write = true;
+        }
+        CreateDisposition::FILE_CREATE => {
+            options.create_new = true;
+            options.write = true;
+        }
+        CreateDisposition::FILE_SUPERSEDE | CreateDisposition::FILE_OVERWRITE_IF => {
+            options.create = true;
+            options.write = true;
+            options.truncate = true;
+        }
+        CreateDisposition::FILE_OVERWRITE => {
+            options.write = true;
+            options.truncate = true;
+        }
+        _ => {}
     }
 
-    match fs.open(&path) {
+    match backend.fs.open(&path, &options) {
         Ok(file) => {
             debug!("create drive file_id:{},path:{}", file_id, path);
+            if req_inner.create_options.contains(CreateOptions::FILE_DELETE_ON_CLOSE) {
+                backend.delete_on_close.insert(file_id);
+            }
             backend.file_map.insert(file_id, file);
             backend.file_path_map.insert(file_id, path.clone());
             make_create_drive_resp(req_inner.device_io_request, req_inner.create_disposition, file_id)
         }
         Err(error) => {
             warn!(?error, "Open file error for path:{}", path);
-            let io_response = DeviceIoResponse::new(req_inner.device_io_request, NtStatus::UNSUCCESSFUL);
+            let io_response = DeviceIoResponse::new(req_inner.device_io_request, io_status(&error));
             let res = RdpdrPdu::DeviceCreateResponse(DeviceCreateResponse {
                 device_io_reply: io_response,
                 file_id,
                 information: Information::empty(),
             });
-            Ok(vec![SvcMessage::from(res)])
+            Ok(vec![res])
         }
     }
 }
@@ -768,11 +1015,11 @@ pub(crate) fn create_drive(
 pub(crate) fn process_dependent_file(
     backend: &mut NixRdpdrBackend,
     request: DeviceIoRequest,
-    error_fx: impl Fn(DeviceIoRequest) -> PduResult<Vec<SvcMessage>>,
-    fx: impl Fn(&mut std::fs::File, DeviceIoRequest) -> PduResult<Vec<SvcMessage>>,
-) -> PduResult<Vec<SvcMessage>> {
-    match backend.file_map.get_mut(&request.file_id) {
+    error_fx: impl Fn(DeviceIoRequest) -> PduResult<Vec<RdpdrPdu>>,
+    fx: impl Fn(&dyn DriveFile, DeviceIoRequest) -> PduResult<Vec<RdpdrPdu>>,
+) -> PduResult<Vec<RdpdrPdu>> {
+    match backend.file_map.get(&request.file_id) {
         None => error_fx(request),
-        Some(file) => fx(file, request),
+        Some(file) => fx(file.as_ref(), request),
     }
 }
@@ -0,0 +1,276 @@
+//! Byte-range locks, backed by `fcntl`.
+//!
+//! On Linux, open file description locks are used so that locks taken through different handles conflict
+//! with each other, like on Windows. Elsewhere, the locks are owned by the process and only conflict with
+//! the locks of other processes.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::os::fd::AsRawFd;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use ironrdp_rdpdr::pdu::efs::{
+    ClientDriveLockControlResponse, LockOperation, NtStatus, RdpLockInfo, ServerDriveLockControlRequest,
+};
+use ironrdp_rdpdr::pdu::RdpdrPdu;
+use ironrdp_rdpdr::{RdpdrMessage, RdpdrMessageProxy};
+use nix::errno::Errno;
+use nix::fcntl::{fcntl, FcntlArg};
+use nix::libc;
+
+/// How often a request waiting for conflicting locks to be released is retried.
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Keeps track of the locks held by the files opened by the server.
+#[derive(Debug, Default)]
+pub(crate) struct FileLocks {
+    state: Arc<Mutex<LockState>>,
+}
+
+impl FileLocks {
+    /// Handles a lock control request on `file`.
+    ///
+    /// Returns `None` if the request waits for conflicting locks to be released, it is then completed through
+    /// `proxy`. Without a proxy, such requests fail right away.
+    pub(crate) fn lock_control(
+        &mut self,
+        file: &File,
+        req: ServerDriveLockControlRequest,
+        proxy: Option<&Arc<dyn RdpdrMessageProxy>>,
+    ) -> Option<ClientDriveLockControlResponse> {
+        let file_id = req.device_io_request.file_id;
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let result = match req.operation {
+            LockOperation::SHARED_LOCK | LockOperation::EXCLUSIVE_LOCK => {
+                let exclusive = req.operation == LockOperation::EXCLUSIVE_LOCK;
+                match state.lock(file, file_id, &req.locks, exclusive) {
+                    Err(NtStatus::LOCK_NOT_GRANTED) if req.wait => match proxy {
+                        Some(proxy) => {
+                            let completion_id = req.device_io_request.completion_id;
+                            match self.wait(file, req.clone(), exclusive, Arc::clone(proxy)) {
+                                Ok(()) => {
+                                    state.waiting.insert(completion_id);
+                                    return None;
+                                }
+                                Err(error) => {
+                                    warn!(%error, "Failed to wait for the lock");
+                                    Err(NtStatus::LOCK_NOT_GRANTED)
+                                }
+                            }
+                        }
+                        None => {
+                            debug!("No message proxy to wait for the lock");
+                            Err(NtStatus::LOCK_NOT_GRANTED)
+                        }
+                    },
+                    result => result,
+                }
+            }
+            LockOperation::UNLOCK | LockOperation::UNLOCK_MULTIPLE => state.unlock(file, file_id, &req.locks),
+            operation => {
+                warn!(?operation, "Unsupported lock operation");
+                Err(NtStatus::INVALID_PARAMETER)
+            }
+        };
+
+        let status = result.err().unwrap_or(NtStatus::SUCCESS);
+        Some(ClientDriveLockControlResponse::new(&req, status))
+    }
+
+    /// Stops waiting for the locks of the request identified by `completion_id`, which has been cancelled.
+    pub(crate) fn cancel(&mut self, completion_id: u32) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.waiting.remove(&completion_id);
+    }
+
+    /// Forgets the locks of the file opened as `file_id`, which are released when the file is closed.
+    pub(crate) fn close(&mut self, file_id: u32) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.held.remove(&file_id);
+    }
+
+    /// Retries the request from another thread until the locks are granted or the request is cancelled.
+    ///
+    /// The caller registers the request as waiting, which happens before the thread looks at it since the state
+    /// is locked.
+    fn wait(
+        &self,
+        file: &File,
+        req: ServerDriveLockControlRequest,
+        exclusive: bool,
+        proxy: Arc<dyn RdpdrMessageProxy>,
+    ) -> std::io::Result<()> {
+        let file = file.try_clone()?;
+        let state = Arc::clone(&self.state);
+        thread::Builder::new().name("rdpdr-lock".to_owned()).spawn(move || {
+            let file_id = req.device_io_request.file_id;
+            let completion_id = req.device_io_request.completion_id;
+            loop {
+                thread::sleep(RETRY_INTERVAL);
+
+                let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+                if !state.waiting.contains(&completion_id) {
+                    // The request has been cancelled.
+                    return;
+                }
+
+                let status = match state.lock(&file, file_id, &req.locks, exclusive) {
+                    Ok(()) => NtStatus::SUCCESS,
+                    Err(NtStatus::LOCK_NOT_GRANTED) => continue,
+                    Err(status) => status,
+                };
+                state.waiting.remove(&completion_id);
+                drop(state);
+
+                proxy.send_rdpdr_message(RdpdrMessage::IoCompleted(RdpdrPdu::ClientDriveLockControlResponse(
+                    ClientDriveLockControlResponse::new(&req, status),
+                )));
+                return;
+            }
+        })?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct LockState {
+    /// Locks held through each file, by FileId.
+    held: HashMap<u32, Vec<HeldLock>>,
+    /// Requests waiting for conflicting locks to be released, by CompletionId.
+    waiting: HashSet<u32>,
+}
+
+impl LockState {
+    /// Takes all the locks, or none of them.
+    fn lock(&mut self, file: &File, file_id: u32, ranges: &[RdpLockInfo], exclusive: bool) -> Result<(), NtStatus> {
+        let kind = if exclusive {
+            LockKind::Exclusive
+        } else {
+            LockKind::Shared
+        };
+        for (index, range) in ranges.iter().enumerate() {
+            if let Err(status) = set_lock(file, *range, kind) {
+                for range in &ranges[..index] {
+                    self.release(file, file_id, *range);
+                }
+                return Err(status);
+            }
+        }
+
+        self.held
+            .entry(file_id)
+            .or_default()
+            .extend(ranges.iter().map(|range| HeldLock {
+                range: *range,
+                exclusive,
+            }));
+        Ok(())
+    }
+
+    /// Releases locks previously taken, each range having to match exactly one of them.
+    fn unlock(&mut self, file: &File, file_id: u32, ranges: &[RdpLockInfo]) -> Result<(), NtStatus> {
+        for range in ranges {
+            let held = self.held.entry(file_id).or_default();
+            let Some(index) = held.iter().position(|lock| lock.range == *range) else {
+                return Err(NtStatus::RANGE_NOT_LOCKED);
+            };
+            held.remove(index);
+            self.release(file, file_id, *range);
+        }
+        Ok(())
+    }
+
+    fn release(&self, file: &File, file_id: u32, range: RdpLockInfo) {
+        if let Err(status) = set_lock(file, range, LockKind::Unlock) {
+            warn!(?status, ?range, "Failed to release lock");
+        }
+
+        // Overlapping locks are merged by fcntl, those still held must be taken again.
+        for lock in self
+            .held
+            .get(&file_id)
+            .into_iter()
+            .flatten()
+            .filter(|lock| overlaps(lock.range, range))
+        {
+            let kind = if lock.exclusive {
+                LockKind::Exclusive
+            } else {
+                LockKind::Shared
+            };
+            if let Err(status) = set_lock(file, lock.range, kind) {
+                warn!(?status, ?lock, "Failed to restore lock");
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct HeldLock {
+    range: RdpLockInfo,
+    exclusive: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LockKind {
+    Shared,
+    Exclusive,
+    Unlock,
+}
+
+impl LockKind {
+    fn l_type(self) -> libc::c_short {
+        let l_type = match self {
+            LockKind::Shared => libc::F_RDLCK,
+            LockKind::Exclusive => libc::F_WRLCK,
+            LockKind::Unlock => libc::F_UNLCK,
+        };
+        // The constants are already `c_short` on some platforms.
+        #[cfg_attr(target_os = "macos", expect(clippy::unnecessary_fallible_conversions))]
+        libc::c_short::try_from(l_type).unwrap()
+    }
+}
+
+fn overlaps(a: RdpLockInfo, b: RdpLockInfo) -> bool {
+    a.offset < b.offset.saturating_add(b.length) && b.offset < a.offset.saturating_add(a.length)
+}
+
+fn set_lock(file: &File, range: RdpLockInfo, kind: LockKind) -> Result<(), NtStatus> {
+    // An empty range does not lock anything, while it would extend to the end of the file with fcntl.
+    if range.length == 0 {
+        return Ok(());
+    }
+
+    let start = libc::off_t::try_from(range.offset).map_err(|_| NtStatus::INVALID_PARAMETER)?;
+    // Ranges extending past the largest offset are locked up to the end of the file, whatever its size.
+    let len = libc::off_t::try_from(range.length)
+        .ok()
+        .filter(|len| start.checked_add(*len).is_some())
+        .unwrap_or(0);
+    let flock = libc::flock {
+        l_type: kind.l_type(),
+        l_whence: libc::c_short::try_from(libc::SEEK_SET).unwrap(),
+        l_start: start,
+        l_len: len,
+        l_pid: 0,
+    };
+
+    #[cfg(target_os = "linux")]
+    let result = fcntl(file.as_raw_fd(), FcntlArg::F_OFD_SETLK(&flock));
+    #[cfg(not(target_os = "linux"))]
+    let result = fcntl(file.as_raw_fd(), FcntlArg::F_SETLK(&flock));
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(Errno::EAGAIN | Errno::EACCES) => Err(NtStatus::LOCK_NOT_GRANTED),
+        // Exclusive locks require write access, a shared lock is the closest a read-only file can get.
+        Err(Errno::EBADF) if kind == LockKind::Exclusive => set_lock(file, range, LockKind::Shared),
+        Err(error) => {
+            warn!(%error, "Failed to set lock");
+            Err(NtStatus::UNSUCCESSFUL)
+        }
+    }
+}
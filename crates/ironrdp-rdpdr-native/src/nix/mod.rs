@@ -1 +1,5 @@
 pub mod backend;
+mod lock;
+#[cfg(target_os = "linux")]
+mod notify;
+pub mod tty;
@@ -0,0 +1,420 @@
+//! Directory change notifications, backed by inotify.
+
+use std::collections::HashMap;
+use std::io;
+use std::os::fd::AsFd;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+use std::thread;
+
+use ironrdp_rdpdr::pdu::efs::{
+    ClientDriveNotifyChangeDirectoryResponse, CompletionFilter, FileAction, FileNotifyInformation, NtStatus,
+    ServerDriveNotifyChangeDirectoryRequest,
+};
+use ironrdp_rdpdr::pdu::RdpdrPdu;
+use ironrdp_rdpdr::{RdpdrMessage, RdpdrMessageProxy};
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, InotifyEvent, WatchDescriptor};
+
+/// Changes buffered for a directory before the server is asked to enumerate it again.
+const MAX_BUFFERED_CHANGES: usize = 256;
+
+/// Subdirectories watched for a single notification request when watching a whole tree.
+const MAX_WATCHES_PER_DIRECTORY: usize = 1024;
+
+/// Watched events, the same directory being possibly watched by several requests with different filters.
+///
+/// Last access changes are not reported, as they would be triggered by the server reading the files.
+const WATCH_FLAGS: AddWatchFlags = AddWatchFlags::IN_ONLYDIR
+    .union(AddWatchFlags::IN_CREATE)
+    .union(AddWatchFlags::IN_DELETE)
+    .union(AddWatchFlags::IN_MOVE)
+    .union(AddWatchFlags::IN_MODIFY)
+    .union(AddWatchFlags::IN_ATTRIB);
+
+/// How often the watcher thread checks whether the backend is gone.
+const POLL_TIMEOUT_MS: u16 = 500;
+
+/// Watches the directories opened by the server for changes.
+///
+/// Each directory handle has at most one pending notification request. It is completed through the message
+/// proxy as soon as a change is reported by inotify, or on the next request if the changes happened in between.
+#[derive(Debug)]
+pub(crate) struct DirectoryWatcher {
+    inotify: Arc<Inotify>,
+    state: Arc<Mutex<WatchState>>,
+}
+
+impl DirectoryWatcher {
+    pub(crate) fn new(proxy: Option<Arc<dyn RdpdrMessageProxy>>) -> io::Result<Self> {
+        let inotify = Arc::new(Inotify::init(InitFlags::IN_CLOEXEC | InitFlags::IN_NONBLOCK)?);
+        let state = Arc::new(Mutex::new(WatchState {
+            proxy,
+            directories: HashMap::new(),
+            watches: HashMap::new(),
+        }));
+
+        let thread_inotify = Arc::clone(&inotify);
+        let thread_state = Arc::downgrade(&state);
+        thread::Builder::new()
+            .name("rdpdr-notify".to_owned())
+            .spawn(move || watch_loop(&thread_inotify, &thread_state))?;
+
+        Ok(Self { inotify, state })
+    }
+
+    /// Handles a notification request for the directory opened as `file_id` at `path`.
+    ///
+    /// Returns the response right away if changes are already buffered. A previous request still pending for
+    /// the same directory is cancelled.
+    pub(crate) fn notify_change(
+        &mut self,
+        path: &Path,
+        req: ServerDriveNotifyChangeDirectoryRequest,
+    ) -> Vec<ClientDriveNotifyChangeDirectoryResponse> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let file_id = req.device_io_request.file_id;
+        let mut responses = Vec::new();
+
+        let mut directory = match state.directories.remove(&file_id) {
+            Some(directory) => directory,
+            None => {
+                let mut directory = WatchedDirectory {
+                    path: path.to_owned(),
+                    watch_tree: req.watch_tree != 0,
+                    filter: req.completion_filter,
+                    descriptors: Vec::new(),
+                    pending: None,
+                    changes: Vec::new(),
+                    overflowed: false,
+                };
+                if let Err(error) = state.watch(&self.inotify, file_id, &mut directory, path, String::new()) {
+                    warn!(%error, ?path, "Failed to watch directory");
+                    return vec![ClientDriveNotifyChangeDirectoryResponse::new(
+                        &req,
+                        NtStatus::UNSUCCESSFUL,
+                    )];
+                }
+                directory
+            }
+        };
+
+        if let Some(previous) = directory.pending.take() {
+            responses.push(ClientDriveNotifyChangeDirectoryResponse::new(
+                &previous,
+                NtStatus::CANCELLED,
+            ));
+        }
+        directory.pending = Some(req);
+        responses.extend(directory.complete());
+        state.directories.insert(file_id, directory);
+        responses
+    }
+
+    /// Forgets the pending request identified by `completion_id`, which has been cancelled.
+    pub(crate) fn cancel(&mut self, completion_id: u32) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        for directory in state.directories.values_mut() {
+            if directory
+                .pending
+                .as_ref()
+                .is_some_and(|req| req.device_io_request.completion_id == completion_id)
+            {
+                directory.pending = None;
+            }
+        }
+    }
+
+    /// Stops watching the directory opened as `file_id`, cancelling its pending request if any.
+    pub(crate) fn close(&mut self, file_id: u32) -> Option<ClientDriveNotifyChangeDirectoryResponse> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let directory = state.directories.remove(&file_id)?;
+        for wd in &directory.descriptors {
+            state.release(&self.inotify, *wd, file_id);
+        }
+        directory
+            .pending
+            .map(|req| ClientDriveNotifyChangeDirectoryResponse::new(&req, NtStatus::CANCELLED))
+    }
+}
+
+#[derive(Debug)]
+struct WatchState {
+    proxy: Option<Arc<dyn RdpdrMessageProxy>>,
+    /// Watched directories, by file id.
+    directories: HashMap<u32, WatchedDirectory>,
+    /// File ids of the watched directories and path relative to them of each inotify watch.
+    ///
+    /// The same directory may be watched through several handles, inotify returning the same descriptor.
+    watches: HashMap<WatchDescriptor, Vec<(u32, String)>>,
+}
+
+impl WatchState {
+    /// Watches `path`, and its subdirectories if the whole tree is watched.
+    fn watch(
+        &mut self,
+        inotify: &Inotify,
+        file_id: u32,
+        directory: &mut WatchedDirectory,
+        path: &Path,
+        relative_path: String,
+    ) -> io::Result<()> {
+        let mut queue = vec![(path.to_owned(), relative_path)];
+        while let Some((path, relative_path)) = queue.pop() {
+            if directory.descriptors.len() >= MAX_WATCHES_PER_DIRECTORY {
+                debug!(path = ?directory.path, "Too many subdirectories to watch");
+                break;
+            }
+
+            let wd = inotify.add_watch(&path, WATCH_FLAGS)?;
+            directory.descriptors.push(wd);
+            self.watches
+                .entry(wd)
+                .or_default()
+                .push((file_id, relative_path.clone()));
+
+            if !directory.watch_tree {
+                break;
+            }
+            for entry in std::fs::read_dir(&path)?.flatten() {
+                if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    queue.push((entry.path(), join(&relative_path, &name)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Stops watching `wd` on behalf of the directory opened as `file_id`.
+    fn release(&mut self, inotify: &Inotify, wd: WatchDescriptor, file_id: u32) {
+        let Some(users) = self.watches.get_mut(&wd) else {
+            return;
+        };
+        users.retain(|(id, _)| *id != file_id);
+        if users.is_empty() {
+            self.watches.remove(&wd);
+            // The watch is already gone if the directory has been removed.
+            let _ = inotify.rm_watch(wd);
+        }
+    }
+
+    fn handle_events(&mut self, inotify: &Inotify, events: Vec<InotifyEvent>) {
+        for (i, event) in events.iter().enumerate() {
+            if event.mask.contains(AddWatchFlags::IN_Q_OVERFLOW) {
+                for directory in self.directories.values_mut() {
+                    directory.overflowed = true;
+                }
+                continue;
+            }
+
+            if event.mask.contains(AddWatchFlags::IN_IGNORED) {
+                for (file_id, relative_path) in self.watches.remove(&event.wd).unwrap_or_default() {
+                    let Some(directory) = self.directories.get_mut(&file_id) else {
+                        continue;
+                    };
+                    directory.descriptors.retain(|wd| *wd != event.wd);
+                    if relative_path.is_empty() {
+                        // The watched directory itself is gone.
+                        if let Some(req) = directory.pending.take() {
+                            send(
+                                self.proxy.as_deref(),
+                                ClientDriveNotifyChangeDirectoryResponse::new(&req, NtStatus::NOTIFY_CLEANUP),
+                            );
+                        }
+                    }
+                }
+                continue;
+            }
+
+            let Some(name) = &event.name else {
+                continue;
+            };
+            let name = name.to_string_lossy();
+            let is_dir = event.mask.contains(AddWatchFlags::IN_ISDIR);
+            let is_paired_move = |other: &InotifyEvent| event.cookie != 0 && other.cookie == event.cookie;
+
+            let action = if event.mask.contains(AddWatchFlags::IN_CREATE) {
+                FileAction::ADDED
+            } else if event.mask.contains(AddWatchFlags::IN_DELETE) {
+                FileAction::REMOVED
+            } else if event.mask.contains(AddWatchFlags::IN_MOVED_FROM) {
+                if events[i..].iter().skip(1).any(is_paired_move) {
+                    FileAction::RENAMED_OLD_NAME
+                } else {
+                    FileAction::REMOVED
+                }
+            } else if event.mask.contains(AddWatchFlags::IN_MOVED_TO) {
+                if events[..i].iter().any(is_paired_move) {
+                    FileAction::RENAMED_NEW_NAME
+                } else {
+                    FileAction::ADDED
+                }
+            } else {
+                FileAction::MODIFIED
+            };
+
+            let users = self.watches.get(&event.wd).cloned().unwrap_or_default();
+            for (file_id, relative_path) in users {
+                let Some(mut directory) = self.directories.remove(&file_id) else {
+                    continue;
+                };
+                let name = join(&relative_path, &name);
+
+                if is_dir && directory.watch_tree {
+                    if action == FileAction::ADDED || action == FileAction::RENAMED_NEW_NAME {
+                        let path = directory.path.join(name.replace('\\', "/"));
+                        if let Err(error) = self.watch(inotify, file_id, &mut directory, &path, name.clone()) {
+                            debug!(%error, ?path, "Failed to watch subdirectory");
+                        }
+                    } else if action == FileAction::REMOVED || action == FileAction::RENAMED_OLD_NAME {
+                        self.unwatch(inotify, file_id, &mut directory, &name);
+                    }
+                }
+
+                if is_reported(directory.filter, is_dir, event.mask) {
+                    directory.push_change(FileNotifyInformation::new(action, name));
+                }
+
+                self.directories.insert(file_id, directory);
+            }
+        }
+
+        for directory in self.directories.values_mut() {
+            if let Some(response) = directory.complete() {
+                send(self.proxy.as_deref(), response);
+            }
+        }
+    }
+
+    /// Stops watching the moved or removed subdirectory `relative_path` and everything below it.
+    fn unwatch(&mut self, inotify: &Inotify, file_id: u32, directory: &mut WatchedDirectory, relative_path: &str) {
+        let prefix = format!("{relative_path}\\");
+        let (removed, kept) = directory.descriptors.iter().partition(|wd| {
+            self.watches.get(wd).is_some_and(|users| {
+                users
+                    .iter()
+                    .any(|(id, path)| *id == file_id && (path == relative_path || path.starts_with(&prefix)))
+            })
+        });
+        directory.descriptors = kept;
+        for wd in removed {
+            self.release(inotify, wd, file_id);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct WatchedDirectory {
+    path: PathBuf,
+    watch_tree: bool,
+    filter: CompletionFilter,
+    descriptors: Vec<WatchDescriptor>,
+    pending: Option<ServerDriveNotifyChangeDirectoryRequest>,
+    changes: Vec<FileNotifyInformation>,
+    /// Set when changes were lost, the server then needs to enumerate the directory again.
+    overflowed: bool,
+}
+
+impl WatchedDirectory {
+    fn push_change(&mut self, change: FileNotifyInformation) {
+        // Writing a file generates a burst of identical modifications.
+        if self.changes.last() == Some(&change) {
+            return;
+        }
+        if self.changes.len() >= MAX_BUFFERED_CHANGES {
+            self.overflowed = true;
+            self.changes.clear();
+        }
+        if !self.overflowed {
+            self.changes.push(change);
+        }
+    }
+
+    /// Completes the pending request, if any, with the buffered changes.
+    fn complete(&mut self) -> Option<ClientDriveNotifyChangeDirectoryResponse> {
+        if self.changes.is_empty() && !self.overflowed {
+            return None;
+        }
+        let req = self.pending.take()?;
+
+        let response = if self.overflowed {
+            ClientDriveNotifyChangeDirectoryResponse::new(&req, NtStatus::NOTIFY_ENUM_DIR)
+        } else {
+            let mut response = ClientDriveNotifyChangeDirectoryResponse::new(&req, NtStatus::SUCCESS);
+            response.buffer = std::mem::take(&mut self.changes);
+            response
+        };
+        self.changes.clear();
+        self.overflowed = false;
+        Some(response)
+    }
+}
+
+fn watch_loop(inotify: &Inotify, state: &Weak<Mutex<WatchState>>) {
+    loop {
+        let mut fds = [PollFd::new(inotify.as_fd(), PollFlags::POLLIN)];
+        match poll(&mut fds, PollTimeout::from(POLL_TIMEOUT_MS)) {
+            Ok(_) | Err(Errno::EINTR) => {}
+            Err(error) => {
+                warn!(%error, "Failed to poll inotify");
+                return;
+            }
+        }
+
+        // The backend is gone.
+        let Some(state) = state.upgrade() else {
+            return;
+        };
+
+        let events = match inotify.read_events() {
+            Ok(events) => events,
+            Err(Errno::EAGAIN) => continue,
+            Err(error) => {
+                warn!(%error, "Failed to read inotify events");
+                return;
+            }
+        };
+
+        trace!(?events);
+        state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .handle_events(inotify, events);
+    }
+}
+
+fn send(proxy: Option<&dyn RdpdrMessageProxy>, response: ClientDriveNotifyChangeDirectoryResponse) {
+    match proxy {
+        Some(proxy) => proxy.send_rdpdr_message(RdpdrMessage::IoCompleted(
+            RdpdrPdu::ClientDriveNotifyChangeDirectoryResponse(response),
+        )),
+        None => debug!(?response, "No message proxy to complete the notification request"),
+    }
+}
+
+/// Whether a change matches the filter of the request.
+fn is_reported(filter: CompletionFilter, is_dir: bool, mask: AddWatchFlags) -> bool {
+    if mask.contains(AddWatchFlags::IN_MODIFY) {
+        filter.intersects(CompletionFilter::FILE_NOTIFY_CHANGE_SIZE | CompletionFilter::FILE_NOTIFY_CHANGE_LAST_WRITE)
+    } else if mask.contains(AddWatchFlags::IN_ATTRIB) {
+        filter.intersects(
+            CompletionFilter::FILE_NOTIFY_CHANGE_ATTRIBUTES
+                | CompletionFilter::FILE_NOTIFY_CHANGE_SECURITY
+                | CompletionFilter::FILE_NOTIFY_CHANGE_EA,
+        )
+    } else if is_dir {
+        filter.contains(CompletionFilter::FILE_NOTIFY_CHANGE_DIR_NAME)
+    } else {
+        filter.contains(CompletionFilter::FILE_NOTIFY_CHANGE_FILE_NAME)
+    }
+}
+
+fn join(relative_path: &str, name: &str) -> String {
+    if relative_path.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{relative_path}\\{name}")
+    }
+}
@@ -0,0 +1,595 @@
+//! Serial port backend mapping the redirected COM ports to Unix ttys.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read as _, Write as _};
+use std::os::fd::{AsFd, AsRawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use ironrdp_core::impl_as_any;
+use ironrdp_rdpdr::pdu::esp::{
+    ControlHandshake, DtrRts, FlowReplace, ModemStatus, Parity, PurgeMask, SerialChars, SerialHandflow,
+    SerialLineControl, SerialRequest, SerialResponse, SerialStatus, SerialTimeouts, StopBits, WaitMask,
+};
+use ironrdp_rdpdr::PortBackend;
+use nix::errno::Errno;
+use nix::libc;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::sys::termios::{
+    self, BaudRate, ControlFlags, FlowArg, FlushArg, InputFlags, SetArg, SpecialCharacterIndices, Termios,
+};
+
+/// Longest time a request may block the channel while waiting for the device.
+///
+/// Requests are processed synchronously, so waiting for longer would stall all the other redirected devices.
+const MAX_BLOCKING: Duration = Duration::from_millis(500);
+
+const BAUD_RATES: &[(u32, BaudRate)] = &[
+    (50, BaudRate::B50),
+    (75, BaudRate::B75),
+    (110, BaudRate::B110),
+    (134, BaudRate::B134),
+    (150, BaudRate::B150),
+    (200, BaudRate::B200),
+    (300, BaudRate::B300),
+    (600, BaudRate::B600),
+    (1200, BaudRate::B1200),
+    (1800, BaudRate::B1800),
+    (2400, BaudRate::B2400),
+    (4800, BaudRate::B4800),
+    (9600, BaudRate::B9600),
+    (19200, BaudRate::B19200),
+    (38400, BaudRate::B38400),
+    (57600, BaudRate::B57600),
+    (115_200, BaudRate::B115200),
+    (230_400, BaudRate::B230400),
+    #[cfg(target_os = "linux")]
+    (460_800, BaudRate::B460800),
+    #[cfg(target_os = "linux")]
+    (921_600, BaudRate::B921600),
+];
+
+nix::ioctl_read_bad!(tiocmget, libc::TIOCMGET, libc::c_int);
+nix::ioctl_write_ptr_bad!(tiocmbis, libc::TIOCMBIS, libc::c_int);
+nix::ioctl_write_ptr_bad!(tiocmbic, libc::TIOCMBIC, libc::c_int);
+nix::ioctl_none_bad!(tiocsbrk, libc::TIOCSBRK);
+nix::ioctl_none_bad!(tioccbrk, libc::TIOCCBRK);
+nix::ioctl_read_bad!(fionread, libc::FIONREAD, libc::c_int);
+nix::ioctl_read_bad!(tiocoutq, libc::TIOCOUTQ, libc::c_int);
+
+/// A [`PortBackend`] giving access to local ttys, such as `/dev/ttyUSB0`, as redirected serial ports.
+///
+/// Parallel ports can be redirected the same way (e.g. `/dev/lp0`), in which case only reads and writes are used.
+#[derive(Debug)]
+pub struct TtyPortBackend {
+    /// Path of the tty of each redirected port, by device ID.
+    ports: HashMap<u32, PathBuf>,
+    next_file_id: u32,
+    open_ports: HashMap<u32, OpenPort>,
+}
+
+impl_as_any!(TtyPortBackend);
+
+#[derive(Debug)]
+struct OpenPort {
+    file: File,
+    timeouts: SerialTimeouts,
+    wait_mask: WaitMask,
+    chars: SerialChars,
+    handflow: SerialHandflow,
+    /// The state of the DTR and RTS lines, as set by the server.
+    dtr_rts: DtrRts,
+    /// The modem status reported by the last wait, to detect changes.
+    modem_status: ModemStatus,
+}
+
+impl TtyPortBackend {
+    /// Creates a backend for the given ports, given as the device ID they are announced with and the path of their tty.
+    pub fn new(ports: Vec<(u32, PathBuf)>) -> Self {
+        Self {
+            ports: ports.into_iter().collect(),
+            next_file_id: 1,
+            open_ports: HashMap::new(),
+        }
+    }
+
+    fn port(&mut self, file_id: u32) -> io::Result<&mut OpenPort> {
+        self.open_ports
+            .get_mut(&file_id)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such open port"))
+    }
+}
+
+impl PortBackend for TtyPortBackend {
+    fn open(&mut self, device_id: u32) -> io::Result<u32> {
+        let path = self
+            .ports
+            .get(&device_id)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such port"))?;
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+            .open(path)?;
+
+        // Like a Windows COM port, the tty passes bytes through unmodified.
+        let mut attrs = termios::tcgetattr(&file)?;
+        termios::cfmakeraw(&mut attrs);
+        attrs.control_flags |= ControlFlags::CLOCAL | ControlFlags::CREAD;
+        attrs.control_chars[SpecialCharacterIndices::VMIN as usize] = 0;
+        attrs.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+        termios::tcsetattr(&file, SetArg::TCSANOW, &attrs)?;
+
+        let mut port = OpenPort {
+            file,
+            timeouts: SerialTimeouts::default(),
+            wait_mask: WaitMask::empty(),
+            chars: SerialChars::default(),
+            handflow: SerialHandflow {
+                control_handshake: ControlHandshake::DTR_CONTROL,
+                flow_replace: FlowReplace::RTS_CONTROL,
+                xon_limit: 0,
+                xoff_limit: 0,
+            },
+            // Opening a tty raises DTR and RTS.
+            dtr_rts: DtrRts::DTR | DtrRts::RTS,
+            modem_status: ModemStatus::empty(),
+        };
+        port.modem_status = port.modem_status();
+
+        let file_id = self.next_file_id;
+        self.next_file_id = self.next_file_id.wrapping_add(1).max(1);
+        self.open_ports.insert(file_id, port);
+
+        debug!(device_id, file_id, path = %path.display(), "Opened port");
+
+        Ok(file_id)
+    }
+
+    fn close(&mut self, file_id: u32) -> io::Result<()> {
+        self.open_ports
+            .remove(&file_id)
+            .map(drop)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such open port"))
+    }
+
+    fn read(&mut self, file_id: u32, length: u32) -> io::Result<Vec<u8>> {
+        self.port(file_id)?.read(length)
+    }
+
+    fn write(&mut self, file_id: u32, data: &[u8]) -> io::Result<()> {
+        self.port(file_id)?.write(data)
+    }
+
+    fn serial_control(&mut self, file_id: u32, request: SerialRequest) -> io::Result<SerialResponse> {
+        let port = self.port(file_id)?;
+
+        let response = match request {
+            SerialRequest::SetBaudRate(baud_rate) => {
+                let (_, speed) = BAUD_RATES
+                    .iter()
+                    .find(|(rate, _)| *rate == baud_rate)
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "unsupported baud rate"))?;
+                port.update_attrs(|attrs| Ok(termios::cfsetspeed(attrs, *speed)?))?;
+                SerialResponse::None
+            }
+            SerialRequest::GetBaudRate => {
+                let attrs = termios::tcgetattr(&port.file)?;
+                let baud_rate = BAUD_RATES
+                    .iter()
+                    .find(|(_, speed)| speed_matches(&attrs, *speed))
+                    .map(|(rate, _)| *rate)
+                    .unwrap_or(0);
+                SerialResponse::BaudRate(baud_rate)
+            }
+            SerialRequest::SetLineControl(line_control) => {
+                port.update_attrs(|attrs| set_line_control(attrs, line_control))?;
+                SerialResponse::None
+            }
+            SerialRequest::GetLineControl => {
+                let attrs = termios::tcgetattr(&port.file)?;
+                SerialResponse::LineControl(line_control(&attrs))
+            }
+            SerialRequest::SetHandflow(handflow) => {
+                port.update_attrs(|attrs| {
+                    let hardware = handflow.control_handshake.contains(ControlHandshake::CTS_HANDSHAKE)
+                        || handflow.flow_replace.contains(FlowReplace::RTS_HANDSHAKE);
+                    attrs.control_flags.set(ControlFlags::CRTSCTS, hardware);
+                    attrs.input_flags.set(
+                        InputFlags::IXON,
+                        handflow.flow_replace.contains(FlowReplace::AUTO_TRANSMIT),
+                    );
+                    attrs.input_flags.set(
+                        InputFlags::IXOFF,
+                        handflow.flow_replace.contains(FlowReplace::AUTO_RECEIVE),
+                    );
+                    Ok(())
+                })?;
+                port.handflow = handflow;
+                SerialResponse::None
+            }
+            SerialRequest::GetHandflow => SerialResponse::Handflow(port.handflow),
+            SerialRequest::SetTimeouts(timeouts) => {
+                port.timeouts = timeouts;
+                SerialResponse::None
+            }
+            SerialRequest::GetTimeouts => SerialResponse::Timeouts(port.timeouts),
+            SerialRequest::SetChars(chars) => {
+                port.update_attrs(|attrs| {
+                    attrs.control_chars[SpecialCharacterIndices::VSTART as usize] = chars.xon_char;
+                    attrs.control_chars[SpecialCharacterIndices::VSTOP as usize] = chars.xoff_char;
+                    Ok(())
+                })?;
+                port.chars = chars;
+                SerialResponse::None
+            }
+            SerialRequest::GetChars => SerialResponse::Chars(port.chars),
+            // The tty driver manages its own queues.
+            SerialRequest::SetQueueSize(_) | SerialRequest::ResetDevice => SerialResponse::None,
+            SerialRequest::ConfigSize => SerialResponse::ConfigSize(0),
+            SerialRequest::SetWaitMask(mask) => {
+                port.wait_mask = mask;
+                SerialResponse::None
+            }
+            SerialRequest::GetWaitMask => SerialResponse::WaitMask(port.wait_mask),
+            SerialRequest::WaitOnMask => SerialResponse::WaitMask(port.wait()?),
+            SerialRequest::Purge(mask) => {
+                let rx = mask.contains(PurgeMask::RXCLEAR);
+                let tx = mask.contains(PurgeMask::TXCLEAR);
+                let queue = match (rx, tx) {
+                    (true, true) => Some(FlushArg::TCIOFLUSH),
+                    (true, false) => Some(FlushArg::TCIFLUSH),
+                    (false, true) => Some(FlushArg::TCOFLUSH),
+                    // There is no pending read or write to abort, requests complete synchronously.
+                    (false, false) => None,
+                };
+                if let Some(queue) = queue {
+                    termios::tcflush(&port.file, queue)?;
+                }
+                SerialResponse::None
+            }
+            SerialRequest::SetDtr => port.set_lines(DtrRts::DTR, true)?,
+            SerialRequest::ClrDtr => port.set_lines(DtrRts::DTR, false)?,
+            SerialRequest::SetRts => port.set_lines(DtrRts::RTS, true)?,
+            SerialRequest::ClrRts => port.set_lines(DtrRts::RTS, false)?,
+            SerialRequest::GetDtrRts => SerialResponse::DtrRts(port.dtr_rts),
+            SerialRequest::SetXon => {
+                termios::tcflow(&port.file, FlowArg::TCOON)?;
+                SerialResponse::None
+            }
+            SerialRequest::SetXoff => {
+                termios::tcflow(&port.file, FlowArg::TCOOFF)?;
+                SerialResponse::None
+            }
+            SerialRequest::SetBreakOn => {
+                // SAFETY: The file descriptor is valid for the lifetime of the port.
+                unsafe { tiocsbrk(port.file.as_raw_fd()) }?;
+                SerialResponse::None
+            }
+            SerialRequest::SetBreakOff => {
+                // SAFETY: The file descriptor is valid for the lifetime of the port.
+                unsafe { tioccbrk(port.file.as_raw_fd()) }?;
+                SerialResponse::None
+            }
+            SerialRequest::ImmediateChar(c) => {
+                port.write(&[c])?;
+                SerialResponse::None
+            }
+            SerialRequest::GetModemStatus => SerialResponse::ModemStatus(port.modem_status()),
+            SerialRequest::GetCommStatus => SerialResponse::CommStatus(SerialStatus {
+                amount_in_in_queue: port.queued(QueueDirection::Input),
+                amount_in_out_queue: port.queued(QueueDirection::Output),
+                ..SerialStatus::default()
+            }),
+        };
+
+        Ok(response)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum QueueDirection {
+    Input,
+    Output,
+}
+
+impl OpenPort {
+    fn update_attrs(&self, f: impl FnOnce(&mut Termios) -> io::Result<()>) -> io::Result<()> {
+        let mut attrs = termios::tcgetattr(&self.file)?;
+        f(&mut attrs)?;
+        termios::tcsetattr(&self.file, SetArg::TCSANOW, &attrs)?;
+        Ok(())
+    }
+
+    /// Reads according to the semantics of `COMMTIMEOUTS`, within the [`MAX_BLOCKING`] limit.
+    fn read(&mut self, length: u32) -> io::Result<Vec<u8>> {
+        let length = usize::try_from(length).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+        let timeouts = self.timeouts;
+
+        let immediate = timeouts.read_interval_timeout == u32::MAX
+            && timeouts.read_total_timeout_multiplier == 0
+            && timeouts.read_total_timeout_constant == 0;
+        let total = timeouts
+            .read_total_timeout_multiplier
+            .saturating_mul(u32::try_from(length).unwrap_or(u32::MAX))
+            .saturating_add(timeouts.read_total_timeout_constant);
+        let interval = match timeouts.read_interval_timeout {
+            0 | u32::MAX => None,
+            interval => Some(Duration::from_millis(u64::from(interval))),
+        };
+
+        let start = Instant::now();
+        let deadline = start
+            + MAX_BLOCKING.min(match total {
+                0 => MAX_BLOCKING,
+                total => Duration::from_millis(u64::from(total)),
+            });
+
+        let mut data = vec![0; length];
+        let mut filled = 0;
+        let mut last_byte = None;
+
+        while filled < length {
+            match self.file.read(&mut data[filled..]) {
+                Ok(0) => break,
+                Ok(n) => {
+                    filled += n;
+                    last_byte = Some(Instant::now());
+                    continue;
+                }
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => {}
+                Err(error) => return Err(error),
+            }
+
+            if immediate {
+                break;
+            }
+
+            let mut wait_until = deadline;
+            if let (Some(interval), Some(last_byte)) = (interval, last_byte) {
+                wait_until = wait_until.min(last_byte + interval);
+            }
+            let now = Instant::now();
+            if now >= wait_until {
+                break;
+            }
+
+            self.poll(PollFlags::POLLIN, wait_until - now)?;
+        }
+
+        data.truncate(filled);
+        Ok(data)
+    }
+
+    fn write(&mut self, mut data: &[u8]) -> io::Result<()> {
+        let timeouts = self.timeouts;
+        let total = timeouts
+            .write_total_timeout_multiplier
+            .saturating_mul(u32::try_from(data.len()).unwrap_or(u32::MAX))
+            .saturating_add(timeouts.write_total_timeout_constant);
+        let deadline = Instant::now()
+            + match total {
+                0 => MAX_BLOCKING,
+                total => Duration::from_millis(u64::from(total)),
+            };
+
+        while !data.is_empty() {
+            match self.file.write(data) {
+                Ok(n) => data = &data[n..],
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(io::ErrorKind::TimedOut.into());
+                    }
+                    self.poll(PollFlags::POLLOUT, deadline - now)?;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Waits for one of the events of the wait mask, and returns the events that occurred, if any.
+    fn wait(&mut self) -> io::Result<WaitMask> {
+        let mask = self.wait_mask;
+        if mask.is_empty() {
+            return Ok(WaitMask::empty());
+        }
+
+        let deadline = Instant::now() + MAX_BLOCKING;
+        loop {
+            let mut events = WaitMask::empty();
+
+            if mask.contains(WaitMask::RXCHAR) && self.queued(QueueDirection::Input) > 0 {
+                events |= WaitMask::RXCHAR;
+            }
+            if mask.contains(WaitMask::TXEMPTY) && self.queued(QueueDirection::Output) == 0 {
+                events |= WaitMask::TXEMPTY;
+            }
+
+            let modem_status = self.modem_status();
+            let changed = modem_status ^ self.modem_status;
+            self.modem_status = modem_status;
+            for (line, event) in [
+                (ModemStatus::CTS, WaitMask::CTS),
+                (ModemStatus::DSR, WaitMask::DSR),
+                (ModemStatus::DCD, WaitMask::RLSD),
+                (ModemStatus::RI, WaitMask::RING),
+            ] {
+                if mask.contains(event) && changed.contains(line) {
+                    events |= event;
+                }
+            }
+
+            let now = Instant::now();
+            if !events.is_empty() || now >= deadline {
+                return Ok(events);
+            }
+
+            // Modem line changes are not pollable, check them periodically.
+            self.poll(PollFlags::POLLIN, (deadline - now).min(Duration::from_millis(20)))?;
+        }
+    }
+
+    fn poll(&self, flags: PollFlags, timeout: Duration) -> io::Result<()> {
+        let mut fds = [PollFd::new(self.file.as_fd(), flags)];
+        let timeout = PollTimeout::try_from(timeout).unwrap_or(PollTimeout::MAX);
+        match poll(&mut fds, timeout) {
+            Ok(_) | Err(Errno::EINTR) => Ok(()),
+            Err(errno) => Err(errno.into()),
+        }
+    }
+
+    fn set_lines(&mut self, lines: DtrRts, on: bool) -> io::Result<SerialResponse> {
+        let mut bits: libc::c_int = 0;
+        if lines.contains(DtrRts::DTR) {
+            bits |= libc::TIOCM_DTR;
+        }
+        if lines.contains(DtrRts::RTS) {
+            bits |= libc::TIOCM_RTS;
+        }
+
+        let fd = self.file.as_raw_fd();
+        let result = if on {
+            // SAFETY: The file descriptor is valid for the lifetime of the port, and `bits` outlives the call.
+            unsafe { tiocmbis(fd, &bits) }
+        } else {
+            // SAFETY: The file descriptor is valid for the lifetime of the port, and `bits` outlives the call.
+            unsafe { tiocmbic(fd, &bits) }
+        };
+
+        match result {
+            Ok(_) => {}
+            // Pseudo-terminals have no modem lines, keep track of the state regardless.
+            Err(Errno::ENOTTY | Errno::EINVAL) => trace!("tty has no modem control lines"),
+            Err(errno) => return Err(errno.into()),
+        }
+
+        self.dtr_rts.set(lines, on);
+        Ok(SerialResponse::None)
+    }
+
+    fn modem_status(&self) -> ModemStatus {
+        let mut bits: libc::c_int = 0;
+        // SAFETY: The file descriptor is valid for the lifetime of the port, and `bits` outlives the call.
+        if unsafe { tiocmget(self.file.as_raw_fd(), &mut bits) }.is_err() {
+            // Pseudo-terminals have no modem lines.
+            return ModemStatus::empty();
+        }
+
+        let mut status = ModemStatus::empty();
+        status.set(ModemStatus::CTS, bits & libc::TIOCM_CTS != 0);
+        status.set(ModemStatus::DSR, bits & libc::TIOCM_DSR != 0);
+        status.set(ModemStatus::RI, bits & libc::TIOCM_RI != 0);
+        status.set(ModemStatus::DCD, bits & libc::TIOCM_CD != 0);
+        status
+    }
+
+    fn queued(&self, direction: QueueDirection) -> u32 {
+        let mut count: libc::c_int = 0;
+        let fd = self.file.as_raw_fd();
+        let result = match direction {
+            // SAFETY: The file descriptor is valid for the lifetime of the port, and `count` outlives the call.
+            QueueDirection::Input => unsafe { fionread(fd, &mut count) },
+            // SAFETY: The file descriptor is valid for the lifetime of the port, and `count` outlives the call.
+            QueueDirection::Output => unsafe { tiocoutq(fd, &mut count) },
+        };
+
+        match result {
+            Ok(_) => u32::try_from(count).unwrap_or(0),
+            Err(_) => 0,
+        }
+    }
+}
+
+fn set_line_control(attrs: &mut Termios, line_control: SerialLineControl) -> io::Result<()> {
+    let word_length = match line_control.word_length {
+        5 => ControlFlags::CS5,
+        6 => ControlFlags::CS6,
+        7 => ControlFlags::CS7,
+        8 => ControlFlags::CS8,
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "unsupported word length")),
+    };
+    attrs.control_flags.remove(ControlFlags::CSIZE);
+    attrs.control_flags.insert(word_length);
+
+    // 1.5 stop bits are only used with 5 data bits, in which case CSTOPB means 1.5.
+    attrs
+        .control_flags
+        .set(ControlFlags::CSTOPB, line_control.stop_bits != StopBits::One);
+
+    attrs.control_flags.remove(ControlFlags::PARENB | ControlFlags::PARODD);
+    #[cfg(target_os = "linux")]
+    attrs.control_flags.remove(ControlFlags::CMSPAR);
+    match line_control.parity {
+        Parity::None => {}
+        Parity::Odd => attrs.control_flags.insert(ControlFlags::PARENB | ControlFlags::PARODD),
+        Parity::Even => attrs.control_flags.insert(ControlFlags::PARENB),
+        #[cfg(target_os = "linux")]
+        Parity::Mark => attrs
+            .control_flags
+            .insert(ControlFlags::PARENB | ControlFlags::PARODD | ControlFlags::CMSPAR),
+        #[cfg(target_os = "linux")]
+        Parity::Space => attrs.control_flags.insert(ControlFlags::PARENB | ControlFlags::CMSPAR),
+        #[cfg(not(target_os = "linux"))]
+        Parity::Mark | Parity::Space => {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "unsupported parity"));
+        }
+    }
+
+    Ok(())
+}
+
+fn line_control(attrs: &Termios) -> SerialLineControl {
+    let flags = attrs.control_flags;
+
+    let word_length = match flags & ControlFlags::CSIZE {
+        ControlFlags::CS5 => 5,
+        ControlFlags::CS6 => 6,
+        ControlFlags::CS7 => 7,
+        _ => 8,
+    };
+
+    let stop_bits = match (flags.contains(ControlFlags::CSTOPB), word_length) {
+        (false, _) => StopBits::One,
+        (true, 5) => StopBits::OnePointFive,
+        (true, _) => StopBits::Two,
+    };
+
+    #[cfg(target_os = "linux")]
+    let stick = flags.contains(ControlFlags::CMSPAR);
+    #[cfg(not(target_os = "linux"))]
+    let stick = false;
+
+    let parity = match (
+        flags.contains(ControlFlags::PARENB),
+        flags.contains(ControlFlags::PARODD),
+        stick,
+    ) {
+        (false, _, _) => Parity::None,
+        (true, true, false) => Parity::Odd,
+        (true, false, false) => Parity::Even,
+        (true, true, true) => Parity::Mark,
+        (true, false, true) => Parity::Space,
+    };
+
+    SerialLineControl {
+        stop_bits,
+        parity,
+        word_length,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn speed_matches(attrs: &Termios, speed: BaudRate) -> bool {
+    termios::cfgetospeed(attrs) == speed
+}
+
+#[cfg(not(target_os = "linux"))]
+fn speed_matches(attrs: &Termios, speed: BaudRate) -> bool {
+    termios::cfgetospeed(attrs) == u32::from(speed)
+}
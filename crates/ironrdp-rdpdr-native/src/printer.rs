@@ -0,0 +1,250 @@
+//! Printer backend spooling the print jobs to a directory or to a command.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+
+use ironrdp_core::impl_as_any;
+use ironrdp_rdpdr::pdu::epc::PrinterCacheData;
+use ironrdp_rdpdr::PrinterBackend;
+
+/// Where the print jobs are sent.
+#[derive(Debug, Clone)]
+pub enum SpoolTarget {
+    /// Each job is written to a new file in this directory.
+    ///
+    /// The file extension is chosen from the content of the job: `.xps`, `.ps`, `.pcl`, or `.prn` when unknown.
+    Directory(PathBuf),
+    /// Each job is piped to the standard input of a new instance of this command, e.g. `lpr`.
+    ///
+    /// The `IRONRDP_PRINTER_ID` environment variable is set to the device ID of the printer.
+    Command { program: PathBuf, args: Vec<String> },
+}
+
+/// A [`PrinterBackend`] spooling the documents printed on the server to a [`SpoolTarget`].
+///
+/// The document is passed as produced by the printer driver the printer was announced with,
+/// so a PostScript or PCL driver should be used unless the local printing system accepts XPS.
+#[derive(Debug)]
+pub struct SpoolPrinterBackend {
+    target: SpoolTarget,
+    next_job_id: u32,
+    jobs: HashMap<u32, Job>,
+    xps_printers: HashSet<u32>,
+    cached_configs: HashMap<String, Vec<u8>>,
+}
+
+impl_as_any!(SpoolPrinterBackend);
+
+#[derive(Debug)]
+enum Job {
+    File {
+        device_id: u32,
+        path: PathBuf,
+        file: File,
+        /// First bytes of the job, used to detect its format.
+        magic: Vec<u8>,
+    },
+    Command {
+        child: Child,
+    },
+}
+
+impl SpoolPrinterBackend {
+    const MAGIC_LEN: usize = 4;
+
+    pub fn new(target: SpoolTarget) -> Self {
+        Self {
+            target,
+            next_job_id: 1,
+            jobs: HashMap::new(),
+            xps_printers: HashSet::new(),
+            cached_configs: HashMap::new(),
+        }
+    }
+
+    /// Restores the configuration cached by the server in a previous session, see [`Self::cached_configs`].
+    #[must_use]
+    pub fn with_cached_configs(mut self, cached_configs: HashMap<String, Vec<u8>>) -> Self {
+        self.cached_configs = cached_configs;
+        self
+    }
+
+    /// Returns the configuration cached by the server for the given printer,
+    /// to be announced with the printer in the next sessions.
+    pub fn cached_config(&self, printer_name: &str) -> Option<&[u8]> {
+        self.cached_configs.get(printer_name).map(Vec::as_slice)
+    }
+
+    /// Returns the configuration cached by the server for all printers.
+    pub fn cached_configs(&self) -> &HashMap<String, Vec<u8>> {
+        &self.cached_configs
+    }
+
+    fn allocate_job_id(&mut self) -> u32 {
+        let job_id = self.next_job_id;
+        self.next_job_id = self.next_job_id.wrapping_add(1).max(1);
+        job_id
+    }
+
+    fn create_spool_file(&mut self, directory: &Path, device_id: u32) -> io::Result<(u32, PathBuf, File)> {
+        fs::create_dir_all(directory)?;
+
+        // Do not overwrite the jobs spooled by a previous session.
+        loop {
+            let job_id = self.allocate_job_id();
+            let path = directory.join(format!("job-{device_id}-{job_id}.part"));
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => return Ok((job_id, path, file)),
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+impl PrinterBackend for SpoolPrinterBackend {
+    fn start_job(&mut self, device_id: u32) -> io::Result<u32> {
+        let (job_id, job) = match self.target.clone() {
+            SpoolTarget::Directory(directory) => {
+                let (job_id, path, file) = self.create_spool_file(&directory, device_id)?;
+                (
+                    job_id,
+                    Job::File {
+                        device_id,
+                        path,
+                        file,
+                        magic: Vec::with_capacity(Self::MAGIC_LEN),
+                    },
+                )
+            }
+            SpoolTarget::Command { program, args } => {
+                let child = Command::new(program)
+                    .args(args)
+                    .env("IRONRDP_PRINTER_ID", device_id.to_string())
+                    .stdin(Stdio::piped())
+                    .spawn()?;
+                (self.allocate_job_id(), Job::Command { child })
+            }
+        };
+
+        debug!(device_id, job_id, "Started print job");
+        self.jobs.insert(job_id, job);
+
+        Ok(job_id)
+    }
+
+    fn write_job(&mut self, job_id: u32, data: &[u8]) -> io::Result<()> {
+        match self.jobs.get_mut(&job_id) {
+            Some(Job::File { file, magic, .. }) => {
+                let missing = Self::MAGIC_LEN.saturating_sub(magic.len()).min(data.len());
+                magic.extend_from_slice(&data[..missing]);
+                file.write_all(data)
+            }
+            Some(Job::Command { child }) => match child.stdin.as_mut() {
+                Some(stdin) => stdin.write_all(data),
+                None => Err(io::Error::new(io::ErrorKind::BrokenPipe, "print command has no stdin")),
+            },
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "no such print job")),
+        }
+    }
+
+    fn end_job(&mut self, job_id: u32) -> io::Result<()> {
+        match self.jobs.remove(&job_id) {
+            Some(Job::File {
+                device_id,
+                path,
+                file,
+                magic,
+            }) => {
+                file.sync_all()?;
+                drop(file);
+
+                let extension = if self.xps_printers.contains(&device_id) {
+                    "xps"
+                } else {
+                    detect_extension(&magic)
+                };
+                let spooled = path.with_extension(extension);
+                fs::rename(&path, &spooled)?;
+
+                info!(path = %spooled.display(), "Spooled print job");
+                Ok(())
+            }
+            Some(Job::Command { mut child }) => {
+                // Closing stdin signals the end of the document.
+                drop(child.stdin.take());
+
+                // Do not block the channel while the document is being printed.
+                std::thread::spawn(move || match child.wait() {
+                    Ok(status) if status.success() => info!(job_id, "Printed print job"),
+                    Ok(status) => warn!(job_id, %status, "Print command failed"),
+                    Err(error) => warn!(job_id, %error, "Failed to wait for print command"),
+                });
+
+                Ok(())
+            }
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "no such print job")),
+        }
+    }
+
+    fn handle_cache_data(&mut self, data: PrinterCacheData) -> io::Result<()> {
+        match data {
+            PrinterCacheData::Add(data) => {
+                self.cached_configs.insert(data.printer_name, data.cached_config);
+            }
+            PrinterCacheData::Update(data) => {
+                self.cached_configs.insert(data.printer_name, data.config_data);
+            }
+            PrinterCacheData::Delete(data) => {
+                self.cached_configs.remove(&data.printer_name);
+            }
+            PrinterCacheData::Rename(data) => {
+                if let Some(config) = self.cached_configs.remove(&data.old_printer_name) {
+                    self.cached_configs.insert(data.new_printer_name, config);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn set_xps_mode(&mut self, device_id: u32) {
+        self.xps_printers.insert(device_id);
+    }
+}
+
+impl Drop for SpoolPrinterBackend {
+    fn drop(&mut self) {
+        // Jobs interrupted by the end of the session are incomplete.
+        for job in self.jobs.drain().map(|(_, job)| job) {
+            match job {
+                Job::File { path, file, .. } => {
+                    drop(file);
+                    if let Err(error) = fs::remove_file(&path) {
+                        warn!(%error, path = %path.display(), "Failed to remove incomplete print job");
+                    }
+                }
+                Job::Command { mut child } => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                }
+            }
+        }
+    }
+}
+
+/// Returns the file extension matching the format of a print job, from its first bytes.
+fn detect_extension(magic: &[u8]) -> &'static str {
+    if magic.starts_with(b"%!") {
+        "ps"
+    } else if magic.starts_with(b"PK\x03\x04") {
+        // XPS documents are ZIP archives.
+        "xps"
+    } else if magic.starts_with(b"\x1b") || magic.starts_with(b"@PJL") {
+        "pcl"
+    } else {
+        "prn"
+    }
+}
@@ -0,0 +1,388 @@
+//! Software smart card, emulating a reader with a PIV card always inserted.
+
+pub mod piv;
+
+use std::collections::{HashMap, HashSet};
+
+use ironrdp_core::impl_as_any;
+use ironrdp_pdu::utils::CharacterSet;
+use ironrdp_pdu::PduResult;
+use ironrdp_rdpdr::pdu::efs::{
+    DeviceControlRequest, DeviceControlResponse, DeviceIoRequest, NtStatus, ServerDeviceAnnounceResponse,
+    ServerDriveIoRequest,
+};
+use ironrdp_rdpdr::pdu::esc::{
+    rpce, CardProtocol, CardState, CardStateFlags, ConnectReturn, EstablishContextReturn, GetDeviceTypeIdReturn,
+    GetReaderIconReturn, GetStatusChangeCall, GetStatusChangeReturn, ListReadersReturn, LongReturn, ReadCacheReturn,
+    ReaderStateCommonCall, ReturnCode, ScardCall, ScardContext, ScardHandle, ScardIoCtlCode, StatusReturn,
+    TransmitReturn,
+};
+use ironrdp_rdpdr::pdu::RdpdrPdu;
+use ironrdp_rdpdr::{NoopRdpdrBackend, RdpdrBackend};
+
+pub use self::piv::PivCard;
+
+/// Name of the pseudo reader used by the server to be notified of new readers.
+const PNP_NOTIFICATION_READER: &str = "\\\\?PnP?\\Notification";
+
+/// `SCARD_READER_TYPE_USB`
+const READER_TYPE_USB: u32 = 0x20;
+
+/// `SCARD_RESET_CARD`
+const DISPOSITION_RESET_CARD: u32 = 1;
+/// `SCARD_UNPOWER_CARD`
+const DISPOSITION_UNPOWER_CARD: u32 = 2;
+
+/// `GetStatusChange` timeout meaning to wait until a change happens.
+const INFINITE_TIMEOUT: u32 = 0xFFFF_FFFF;
+
+/// An [`RdpdrBackend`] answering the smart card calls with a single reader holding a virtual [`PivCard`].
+///
+/// This allows smart card logon without any hardware. Drive requests are forwarded to another backend,
+/// [`NoopRdpdrBackend`] by default.
+///
+/// As calls are answered synchronously, a `GetStatusChange` call with a finite timeout returns
+/// `SCARD_E_TIMEOUT` right away when nothing changed, instead of waiting. Calls with an infinite timeout are
+/// answered once cancelled.
+#[derive(Debug)]
+pub struct VirtualSmartCardBackend {
+    reader_name: String,
+    card: PivCard,
+    drive_backend: Box<dyn RdpdrBackend>,
+    next_id: u32,
+    contexts: HashSet<u32>,
+    /// Connected card handles, with their context.
+    handles: HashMap<u32, u32>,
+    /// Smart card cache, as written by the server, with the freshness counter of each item.
+    cache: HashMap<String, (u32, Vec<u8>)>,
+    /// `GetStatusChange` calls waiting for a card event.
+    pending_status_changes: Vec<(DeviceControlRequest<ScardIoCtlCode>, GetStatusChangeCall)>,
+}
+
+impl_as_any!(VirtualSmartCardBackend);
+
+impl VirtualSmartCardBackend {
+    pub fn new(reader_name: String, card: PivCard) -> Self {
+        Self {
+            reader_name,
+            card,
+            drive_backend: Box::new(NoopRdpdrBackend),
+            next_id: 1,
+            contexts: HashSet::new(),
+            handles: HashMap::new(),
+            cache: HashMap::new(),
+            pending_status_changes: Vec::new(),
+        }
+    }
+
+    /// Forwards the drive requests to `backend`.
+    #[must_use]
+    pub fn with_drive_backend(mut self, backend: Box<dyn RdpdrBackend>) -> Self {
+        self.drive_backend = backend;
+        self
+    }
+
+    pub fn card(&self) -> &PivCard {
+        &self.card
+    }
+
+    fn next_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+        id
+    }
+
+    fn is_valid_handle(&self, handle: &ScardHandle) -> bool {
+        self.handles.get(&handle.value) == Some(&handle.context.value)
+    }
+
+    fn atr(&self) -> ([u8; 36], u32) {
+        let atr = self.card.atr();
+        let mut padded = [0; 36];
+        padded[..atr.len()].copy_from_slice(atr);
+        (
+            padded,
+            u32::try_from(atr.len()).expect("ATRs are at most 33 bytes long"),
+        )
+    }
+
+    /// Computes the reader states to return, and whether any of them changed.
+    fn reader_states(&self, call: &GetStatusChangeCall) -> (Vec<ReaderStateCommonCall>, bool) {
+        let (atr, atr_length) = self.atr();
+        let mut any_changed = false;
+
+        let states = call
+            .states
+            .iter()
+            .map(|state| {
+                let current = state.common.current_state;
+
+                let (event_state, changed) = if state.reader == self.reader_name {
+                    let known = current.contains(CardStateFlags::SCARD_STATE_PRESENT);
+                    (CardStateFlags::SCARD_STATE_PRESENT, !known)
+                } else if state.reader == PNP_NOTIFICATION_READER {
+                    // The high word holds the number of readers.
+                    let readers = CardStateFlags::from_bits_retain(1 << 16);
+                    (readers, (current.bits() >> 16) != 1)
+                } else {
+                    let known = current.contains(CardStateFlags::SCARD_STATE_UNKNOWN);
+                    (CardStateFlags::SCARD_STATE_UNKNOWN, !known)
+                };
+
+                let ignored = current.contains(CardStateFlags::SCARD_STATE_IGNORE);
+                let changed = changed && !ignored;
+                any_changed |= changed;
+
+                let event_state = if changed {
+                    event_state | CardStateFlags::SCARD_STATE_CHANGED
+                } else {
+                    event_state
+                };
+
+                ReaderStateCommonCall {
+                    current_state: current,
+                    event_state,
+                    atr_length: if state.reader == self.reader_name {
+                        atr_length
+                    } else {
+                        0
+                    },
+                    atr: if state.reader == self.reader_name { atr } else { [0; 36] },
+                }
+            })
+            .collect();
+
+        (states, any_changed)
+    }
+
+    fn handle_get_status_change(
+        &mut self,
+        req: DeviceControlRequest<ScardIoCtlCode>,
+        call: GetStatusChangeCall,
+    ) -> Vec<RdpdrPdu> {
+        if !self.contexts.contains(&call.context.value) {
+            return vec![response(
+                req,
+                GetStatusChangeReturn::new(ReturnCode::InvalidHandle, Vec::new()),
+            )];
+        }
+
+        let (states, changed) = self.reader_states(&call);
+
+        if changed {
+            vec![response(req, GetStatusChangeReturn::new(ReturnCode::Success, states))]
+        } else if call.timeout == INFINITE_TIMEOUT {
+            // The card is never removed, this will only complete when cancelled.
+            self.pending_status_changes.push((req, call));
+            Vec::new()
+        } else {
+            vec![response(req, GetStatusChangeReturn::new(ReturnCode::Timeout, states))]
+        }
+    }
+
+    /// Completes the pending `GetStatusChange` calls of `context` with `SCARD_E_CANCELLED`.
+    fn cancel(&mut self, context: u32) -> Vec<RdpdrPdu> {
+        let (cancelled, pending) = core::mem::take(&mut self.pending_status_changes)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(_, call)| call.context.value == context);
+        self.pending_status_changes = pending;
+
+        cancelled
+            .into_iter()
+            .map(|(req, call)| {
+                let (states, _) = self.reader_states(&call);
+                response(req, GetStatusChangeReturn::new(ReturnCode::Cancelled, states))
+            })
+            .collect()
+    }
+}
+
+impl RdpdrBackend for VirtualSmartCardBackend {
+    fn handle_server_device_announce_response(&mut self, pdu: ServerDeviceAnnounceResponse) -> PduResult<()> {
+        self.drive_backend.handle_server_device_announce_response(pdu)
+    }
+
+    fn handle_scard_call(
+        &mut self,
+        req: DeviceControlRequest<ScardIoCtlCode>,
+        call: ScardCall,
+    ) -> PduResult<Vec<RdpdrPdu>> {
+        let messages = match call {
+            ScardCall::AccessStartedEventCall(_) => vec![response(req, LongReturn::new(ReturnCode::Success))],
+            ScardCall::EstablishContextCall(_) => {
+                let context = self.next_id();
+                self.contexts.insert(context);
+                vec![response(
+                    req,
+                    EstablishContextReturn::new(ReturnCode::Success, ScardContext::new(context)),
+                )]
+            }
+            ScardCall::ListReadersCall(call) => {
+                let return_code = if self.contexts.contains(&call.context.value) {
+                    ReturnCode::Success
+                } else {
+                    ReturnCode::InvalidHandle
+                };
+                let readers = vec![self.reader_name.clone()];
+                vec![response(req, ListReadersReturn::new(return_code, readers))]
+            }
+            ScardCall::GetStatusChangeCall(call) => self.handle_get_status_change(req, call),
+            ScardCall::ConnectCall(call) => {
+                let context = call.common.context;
+                let preferred_protocols = call.common.preferred_protocols;
+                let (return_code, handle, protocol) = if !self.contexts.contains(&context.value) {
+                    (ReturnCode::InvalidHandle, 0, CardProtocol::empty())
+                } else if call.reader != self.reader_name {
+                    (ReturnCode::UnknownReader, 0, CardProtocol::empty())
+                } else if !preferred_protocols.is_empty()
+                    && !preferred_protocols
+                        .intersects(CardProtocol::SCARD_PROTOCOL_T1 | CardProtocol::SCARD_PROTOCOL_DEFAULT)
+                {
+                    (ReturnCode::ProtoMismatch, 0, CardProtocol::empty())
+                } else {
+                    let handle = self.next_id();
+                    self.handles.insert(handle, context.value);
+                    (ReturnCode::Success, handle, CardProtocol::SCARD_PROTOCOL_T1)
+                };
+                vec![response(
+                    req,
+                    ConnectReturn::new(return_code, ScardHandle::new(context, handle), protocol),
+                )]
+            }
+            ScardCall::HCardAndDispositionCall(call) => {
+                let return_code = if self.is_valid_handle(&call.handle) {
+                    if matches!(call.disposition, DISPOSITION_RESET_CARD | DISPOSITION_UNPOWER_CARD) {
+                        self.card.reset();
+                    }
+                    if req.io_control_code == ScardIoCtlCode::Disconnect {
+                        self.handles.remove(&call.handle.value);
+                    }
+                    ReturnCode::Success
+                } else {
+                    ReturnCode::InvalidHandle
+                };
+                vec![response(req, LongReturn::new(return_code))]
+            }
+            ScardCall::TransmitCall(call) => {
+                let (return_code, recv_buffer) = if self.is_valid_handle(&call.handle) {
+                    (ReturnCode::Success, self.card.transmit(&call.send_buffer))
+                } else {
+                    (ReturnCode::InvalidHandle, Vec::new())
+                };
+                vec![response(req, TransmitReturn::new(return_code, None, recv_buffer))]
+            }
+            ScardCall::StatusCall(call) => {
+                let encoding = if req.io_control_code == ScardIoCtlCode::StatusA {
+                    CharacterSet::Ansi
+                } else {
+                    CharacterSet::Unicode
+                };
+                let (atr, atr_length) = self.atr();
+                let mut status_atr = [0; 32];
+                status_atr.copy_from_slice(&atr[..32]);
+
+                let status = if self.is_valid_handle(&call.handle) {
+                    StatusReturn::new(
+                        ReturnCode::Success,
+                        vec![self.reader_name.clone()],
+                        CardState::SpecificMode,
+                        CardProtocol::SCARD_PROTOCOL_T1,
+                        status_atr,
+                        atr_length,
+                        encoding,
+                    )
+                } else {
+                    StatusReturn::new(
+                        ReturnCode::InvalidHandle,
+                        Vec::new(),
+                        CardState::Unknown,
+                        CardProtocol::empty(),
+                        [0; 32],
+                        0,
+                        encoding,
+                    )
+                };
+                vec![response(req, status)]
+            }
+            ScardCall::ContextCall(call) => {
+                let context = call.context.value;
+                let valid = self.contexts.contains(&context);
+
+                let mut messages = Vec::new();
+                match req.io_control_code {
+                    ScardIoCtlCode::ReleaseContext if valid => {
+                        messages = self.cancel(context);
+                        self.contexts.remove(&context);
+                        self.handles.retain(|_, handle_context| *handle_context != context);
+                    }
+                    ScardIoCtlCode::Cancel if valid => messages = self.cancel(context),
+                    _ => {}
+                }
+
+                let return_code = if valid {
+                    ReturnCode::Success
+                } else {
+                    ReturnCode::InvalidHandle
+                };
+                messages.push(response(req, LongReturn::new(return_code)));
+                messages
+            }
+            ScardCall::GetDeviceTypeIdCall(call) => {
+                let return_code = if call.reader_name == self.reader_name {
+                    ReturnCode::Success
+                } else {
+                    ReturnCode::UnknownReader
+                };
+                vec![response(req, GetDeviceTypeIdReturn::new(return_code, READER_TYPE_USB))]
+            }
+            ScardCall::ReadCacheCall(call) => {
+                let result = match self.cache.get(&call.lookup_name) {
+                    Some((freshness, data)) if *freshness == call.common.freshness_counter => {
+                        (ReturnCode::Success, data.clone())
+                    }
+                    Some(_) => (ReturnCode::CacheItemStale, Vec::new()),
+                    None => (ReturnCode::CacheItemNotFound, Vec::new()),
+                };
+                vec![response(req, ReadCacheReturn::new(result.0, result.1))]
+            }
+            ScardCall::WriteCacheCall(call) => {
+                self.cache
+                    .insert(call.lookup_name, (call.common.freshness_counter, call.common.data));
+                vec![response(req, LongReturn::new(ReturnCode::Success))]
+            }
+            ScardCall::GetReaderIconCall(_) => {
+                vec![response(
+                    req,
+                    GetReaderIconReturn::new(ReturnCode::UnsupportedFeature, Vec::new()),
+                )]
+            }
+            ScardCall::Unsupported => {
+                debug!(?req.io_control_code, "Unsupported smart card call");
+                vec![response(req, LongReturn::new(ReturnCode::UnsupportedFeature))]
+            }
+        };
+
+        Ok(messages)
+    }
+
+    fn handle_drive_io_request(&mut self, req: ServerDriveIoRequest) -> PduResult<Vec<RdpdrPdu>> {
+        self.drive_backend.handle_drive_io_request(req)
+    }
+
+    fn cancel_io_request(&mut self, req: &DeviceIoRequest) {
+        let before = self.pending_status_changes.len();
+        self.pending_status_changes
+            .retain(|(pending, _)| pending.header.completion_id != req.completion_id);
+        if self.pending_status_changes.len() == before {
+            self.drive_backend.cancel_io_request(req);
+        }
+    }
+}
+
+fn response(req: DeviceControlRequest<ScardIoCtlCode>, output: impl rpce::Encode + 'static) -> RdpdrPdu {
+    RdpdrPdu::DeviceControlResponse(DeviceControlResponse::new(
+        req,
+        NtStatus::SUCCESS,
+        Some(Box::new(output)),
+    ))
+}
@@ -0,0 +1,470 @@
+//! In-memory PIV card, as specified in NIST SP 800-73-4.
+//!
+//! Only the subset needed for smart card logon is implemented: the PIV Authentication key (`9A`) and its
+//! certificate, the CHUID, the discovery object and the PIV application PIN.
+
+use std::io;
+
+use rsa::hazmat::rsa_decrypt_and_check;
+use rsa::pkcs1::DecodeRsaPrivateKey as _;
+use rsa::pkcs8::DecodePrivateKey as _;
+use rsa::rand_core::OsRng;
+use rsa::traits::PublicKeyParts as _;
+use rsa::{BigUint, RsaPrivateKey};
+
+/// PIV application identifier, including the version.
+const PIV_AID: [u8; 11] = [0xA0, 0x00, 0x00, 0x03, 0x08, 0x00, 0x00, 0x10, 0x00, 0x01, 0x00];
+/// The part of [`PIV_AID`] which is enough to select the application.
+const PIV_AID_PREFIX_LEN: usize = 5;
+
+/// Maximum number of consecutive wrong PIN attempts before the PIN is blocked.
+const PIN_RETRIES: u8 = 3;
+/// PINs are padded to this length with `0xFF`.
+const PIN_LENGTH: usize = 8;
+
+/// Maximum length of a certificate, so that the certificate object fits in a TLV with a 16-bit length along with
+/// the certificate TLV header (4 bytes), the CertInfo (3 bytes) and the error detection code (2 bytes).
+const MAX_CERTIFICATE_LEN: usize = 0xFFFF - 4 - 3 - 2;
+
+/// Maximum length of a response without chaining through GET RESPONSE.
+const MAX_RESPONSE_CHUNK: usize = 256;
+
+const INS_VERIFY: u8 = 0x20;
+const INS_GENERAL_AUTHENTICATE: u8 = 0x87;
+const INS_SELECT: u8 = 0xA4;
+const INS_GET_RESPONSE: u8 = 0xC0;
+const INS_GET_DATA: u8 = 0xCB;
+
+/// Command chaining bit of the class byte.
+const CLA_CHAINING: u8 = 0x10;
+
+/// Key reference of the PIV Authentication key.
+const KEY_PIV_AUTHENTICATION: u8 = 0x9A;
+/// Key reference of the PIV application PIN.
+const PIV_APPLICATION_PIN: u8 = 0x80;
+
+const ALGORITHM_RSA_1024: u8 = 0x06;
+const ALGORITHM_RSA_2048: u8 = 0x07;
+
+const TAG_DISCOVERY_OBJECT: &[u8] = &[0x7E];
+const TAG_CHUID: &[u8] = &[0x5F, 0xC1, 0x02];
+const TAG_PIV_AUTHENTICATION_CERTIFICATE: &[u8] = &[0x5F, 0xC1, 0x05];
+
+/// FASC-N of the CHUID, as found on test cards (agency code 9999).
+const FASC_N: [u8; 25] = [
+    0xD4, 0xE7, 0x39, 0xDA, 0x73, 0x9C, 0xED, 0x39, 0xCE, 0x73, 0x9D, 0x83, 0x68, 0x58, 0x21, 0x08, 0x42, 0x10, 0x84,
+    0x21, 0xC8, 0x42, 0x10, 0xC3, 0xEB,
+];
+
+/// Status words.
+mod sw {
+    pub(super) const SUCCESS: [u8; 2] = [0x90, 0x00];
+    pub(super) const WRONG_LENGTH: [u8; 2] = [0x67, 0x00];
+    pub(super) const SECURITY_STATUS_NOT_SATISFIED: [u8; 2] = [0x69, 0x82];
+    pub(super) const AUTHENTICATION_METHOD_BLOCKED: [u8; 2] = [0x69, 0x83];
+    pub(super) const CONDITIONS_NOT_SATISFIED: [u8; 2] = [0x69, 0x85];
+    pub(super) const INCORRECT_DATA: [u8; 2] = [0x6A, 0x80];
+    pub(super) const NOT_FOUND: [u8; 2] = [0x6A, 0x82];
+    pub(super) const INCORRECT_P1_P2: [u8; 2] = [0x6A, 0x86];
+    pub(super) const REFERENCE_NOT_FOUND: [u8; 2] = [0x6A, 0x88];
+    pub(super) const INS_NOT_SUPPORTED: [u8; 2] = [0x6D, 0x00];
+    pub(super) const CLA_NOT_SUPPORTED: [u8; 2] = [0x6E, 0x00];
+    pub(super) const NO_PRECISE_DIAGNOSIS: [u8; 2] = [0x6F, 0x00];
+}
+
+/// A PIV card holding a single certificate and its RSA private key, used for PIV Authentication.
+pub struct PivCard {
+    certificate: Vec<u8>,
+    private_key: RsaPrivateKey,
+    guid: [u8; 16],
+    pin: [u8; PIN_LENGTH],
+    pin_retries: u8,
+    pin_verified: bool,
+    selected: bool,
+    /// Data of the chained command being received.
+    chained_data: Vec<u8>,
+    /// Remaining data of the last response, to be retrieved with GET RESPONSE.
+    pending_response: Vec<u8>,
+}
+
+impl core::fmt::Debug for PivCard {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PivCard")
+            .field("guid", &self.guid)
+            .field("pin_retries", &self.pin_retries)
+            .field("pin_verified", &self.pin_verified)
+            .field("selected", &self.selected)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PivCard {
+    /// Creates a card from a DER-encoded X.509 certificate and the matching DER-encoded RSA private key,
+    /// in either PKCS#1 or PKCS#8 format.
+    ///
+    /// The PIN must be 6 to 8 characters long. Only 1024 and 2048-bit keys are supported by PIV.
+    pub fn new(certificate: Vec<u8>, private_key: &[u8], pin: &str) -> io::Result<Self> {
+        if certificate.len() > MAX_CERTIFICATE_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the certificate is too large for the certificate object",
+            ));
+        }
+
+        let private_key = RsaPrivateKey::from_pkcs1_der(private_key)
+            .or_else(|_| RsaPrivateKey::from_pkcs8_der(private_key))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        if !matches!(private_key.size(), 128 | 256) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "only 1024 and 2048-bit RSA keys are supported",
+            ));
+        }
+
+        if !(6..=PIN_LENGTH).contains(&pin.len()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the PIN must be 6 to 8 characters long",
+            ));
+        }
+        let mut padded_pin = [0xFF; PIN_LENGTH];
+        padded_pin[..pin.len()].copy_from_slice(pin.as_bytes());
+
+        // The GUID only needs to be stable for a given card, derive it from the key.
+        let modulus = private_key.n().to_bytes_be();
+        let mut guid = [0; 16];
+        guid.copy_from_slice(&modulus[modulus.len() - 16..]);
+
+        Ok(Self {
+            certificate,
+            private_key,
+            guid,
+            pin: padded_pin,
+            pin_retries: PIN_RETRIES,
+            pin_verified: false,
+            selected: false,
+            chained_data: Vec::new(),
+            pending_response: Vec::new(),
+        })
+    }
+
+    /// The answer to reset of the card.
+    ///
+    /// It advertises T=1 and no historical bytes.
+    pub fn atr(&self) -> &'static [u8] {
+        &[0x3B, 0x80, 0x80, 0x01, 0x01]
+    }
+
+    /// Resets the card, as when it is powered off, dropping the PIN verification status.
+    pub fn reset(&mut self) {
+        self.pin_verified = false;
+        self.selected = false;
+        self.chained_data.clear();
+        self.pending_response.clear();
+    }
+
+    /// Processes a command APDU and returns the response APDU.
+    pub fn transmit(&mut self, apdu: &[u8]) -> Vec<u8> {
+        let Some(command) = Command::parse(apdu) else {
+            return sw::WRONG_LENGTH.to_vec();
+        };
+
+        if command.cla & !CLA_CHAINING != 0 {
+            return sw::CLA_NOT_SUPPORTED.to_vec();
+        }
+
+        if command.ins != INS_GET_RESPONSE {
+            self.pending_response.clear();
+        }
+
+        if command.cla & CLA_CHAINING != 0 {
+            self.chained_data.extend_from_slice(command.data);
+            return sw::SUCCESS.to_vec();
+        }
+
+        let chained_data;
+        let data = if self.chained_data.is_empty() {
+            command.data
+        } else {
+            self.chained_data.extend_from_slice(command.data);
+            chained_data = core::mem::take(&mut self.chained_data);
+            &chained_data
+        };
+
+        let result = match command.ins {
+            INS_SELECT => self.select(command.p1, data),
+            INS_GET_RESPONSE if self.pending_response.is_empty() => Err(sw::CONDITIONS_NOT_SATISFIED),
+            INS_GET_RESPONSE => return self.get_response(),
+            _ if !self.selected => Err(sw::CONDITIONS_NOT_SATISFIED),
+            INS_GET_DATA => self.get_data(command.p1, command.p2, data),
+            INS_VERIFY => self.verify(command.p1, command.p2, data),
+            INS_GENERAL_AUTHENTICATE => self.general_authenticate(command.p1, command.p2, data),
+            _ => Err(sw::INS_NOT_SUPPORTED),
+        };
+
+        match result {
+            Ok(data) => {
+                self.pending_response = data;
+                self.get_response()
+            }
+            Err(status) => status.to_vec(),
+        }
+    }
+
+    /// Returns the next chunk of the pending response, with `61xx` if more data remains.
+    fn get_response(&mut self) -> Vec<u8> {
+        let chunk_len = self.pending_response.len().min(MAX_RESPONSE_CHUNK);
+        let mut response = self.pending_response.drain(..chunk_len).collect::<Vec<u8>>();
+
+        match self.pending_response.len() {
+            0 => response.extend_from_slice(&sw::SUCCESS),
+            // A length of 0 means 256 bytes or more.
+            remaining => response.extend_from_slice(&[0x61, u8::try_from(remaining).unwrap_or(0)]),
+        }
+
+        response
+    }
+
+    fn select(&mut self, p1: u8, aid: &[u8]) -> Result<Vec<u8>, [u8; 2]> {
+        // Selection by DF name.
+        if p1 != 0x04 {
+            return Err(sw::INCORRECT_P1_P2);
+        }
+
+        if aid.len() < PIV_AID_PREFIX_LEN || !PIV_AID.starts_with(aid) {
+            self.selected = false;
+            return Err(sw::NOT_FOUND);
+        }
+
+        self.selected = true;
+
+        // Application property template: application identifier and coexistent tag allocation authority.
+        let mut template = tlv(&[0x4F], &PIV_AID[PIV_AID_PREFIX_LEN..])?;
+        template.extend(tlv(&[0x79], &tlv(&[0x4F], &PIV_AID[..PIV_AID_PREFIX_LEN])?)?);
+        tlv(&[0x61], &template)
+    }
+
+    fn get_data(&self, p1: u8, p2: u8, data: &[u8]) -> Result<Vec<u8>, [u8; 2]> {
+        if (p1, p2) != (0x3F, 0xFF) {
+            return Err(sw::INCORRECT_P1_P2);
+        }
+
+        // Tag list.
+        let tag = match parse_tlvs(data).as_deref() {
+            Some([(0x5C, tag)]) => *tag,
+            _ => return Err(sw::INCORRECT_DATA),
+        };
+
+        let object = match tag {
+            TAG_DISCOVERY_OBJECT => {
+                // PIV AID, and PIN usage policy: PIV application PIN only.
+                let mut discovery = tlv(&[0x4F], &PIV_AID)?;
+                discovery.extend(tlv(&[0x5F, 0x2F], &[0x40, 0x00])?);
+                return tlv(TAG_DISCOVERY_OBJECT, &discovery);
+            }
+            TAG_CHUID => {
+                let mut chuid = tlv(&[0x30], &FASC_N)?;
+                chuid.extend(tlv(&[0x34], &self.guid)?);
+                chuid.extend(tlv(&[0x35], b"20991231")?);
+                chuid.extend(tlv(&[0x3E], &[])?);
+                chuid
+            }
+            TAG_PIV_AUTHENTICATION_CERTIFICATE => {
+                let mut certificate = tlv(&[0x70], &self.certificate)?;
+                // Uncompressed.
+                certificate.extend(tlv(&[0x71], &[0x00])?);
+                certificate
+            }
+            _ => return Err(sw::NOT_FOUND),
+        };
+
+        let mut object = object;
+        // Error detection code.
+        object.extend(tlv(&[0xFE], &[])?);
+        tlv(&[0x53], &object)
+    }
+
+    fn verify(&mut self, p1: u8, p2: u8, data: &[u8]) -> Result<Vec<u8>, [u8; 2]> {
+        if p2 != PIV_APPLICATION_PIN {
+            return Err(sw::REFERENCE_NOT_FOUND);
+        }
+
+        match (p1, data.len()) {
+            // Reset of the security status.
+            (0xFF, 0) => {
+                self.pin_verified = false;
+                Ok(Vec::new())
+            }
+            // Query of the security status.
+            (0x00, 0) if self.pin_verified => Ok(Vec::new()),
+            (0x00, 0) => Err(self.pin_retries_status()),
+            (0x00, PIN_LENGTH) => {
+                if self.pin_retries == 0 {
+                    return Err(sw::AUTHENTICATION_METHOD_BLOCKED);
+                }
+
+                if data == self.pin {
+                    self.pin_retries = PIN_RETRIES;
+                    self.pin_verified = true;
+                    Ok(Vec::new())
+                } else {
+                    self.pin_retries -= 1;
+                    self.pin_verified = false;
+                    Err(self.pin_retries_status())
+                }
+            }
+            (0x00, _) => Err(sw::WRONG_LENGTH),
+            _ => Err(sw::INCORRECT_P1_P2),
+        }
+    }
+
+    fn pin_retries_status(&self) -> [u8; 2] {
+        match self.pin_retries {
+            0 => sw::AUTHENTICATION_METHOD_BLOCKED,
+            retries => [0x63, 0xC0 | retries],
+        }
+    }
+
+    fn general_authenticate(&self, algorithm: u8, key: u8, data: &[u8]) -> Result<Vec<u8>, [u8; 2]> {
+        if key != KEY_PIV_AUTHENTICATION {
+            return Err(sw::REFERENCE_NOT_FOUND);
+        }
+
+        let key_size = self.private_key.size();
+        let expected_algorithm = if key_size == 256 {
+            ALGORITHM_RSA_2048
+        } else {
+            ALGORITHM_RSA_1024
+        };
+        if algorithm != expected_algorithm {
+            return Err(sw::INCORRECT_P1_P2);
+        }
+
+        // Dynamic authentication template.
+        let template = match parse_tlvs(data).as_deref() {
+            Some([(0x7C, template)]) => parse_tlvs(template).ok_or(sw::INCORRECT_DATA)?,
+            _ => return Err(sw::INCORRECT_DATA),
+        };
+
+        let response_requested = template.iter().any(|(tag, value)| *tag == 0x82 && value.is_empty());
+        let challenge = template
+            .iter()
+            .find(|(tag, _)| *tag == 0x81)
+            .map(|(_, value)| *value)
+            .ok_or(sw::INCORRECT_DATA)?;
+
+        if !response_requested || challenge.len() != key_size {
+            return Err(sw::INCORRECT_DATA);
+        }
+
+        if !self.pin_verified {
+            return Err(sw::SECURITY_STATUS_NOT_SATISFIED);
+        }
+
+        // The challenge is already padded by the host, the card only applies the raw private key operation.
+        let signature = rsa_decrypt_and_check(&self.private_key, Some(&mut OsRng), &BigUint::from_bytes_be(challenge))
+            .map_err(|_| sw::INCORRECT_DATA)?
+            .to_bytes_be();
+
+        let mut response = vec![0; key_size - signature.len()];
+        response.extend_from_slice(&signature);
+
+        tlv(&[0x7C], &tlv(&[0x82], &response)?)
+    }
+}
+
+/// A command APDU, either short or extended.
+struct Command<'a> {
+    cla: u8,
+    ins: u8,
+    p1: u8,
+    p2: u8,
+    data: &'a [u8],
+}
+
+impl<'a> Command<'a> {
+    fn parse(apdu: &'a [u8]) -> Option<Self> {
+        let header: [u8; 4] = apdu.get(..4)?.try_into().ok()?;
+        let [cla, ins, p1, p2] = header;
+        let body = &apdu[4..];
+
+        let data = match body {
+            // Case 1, or case 2 with Le.
+            [] | [_] => &[][..],
+            // Extended case 2.
+            [0, _, _] => &[][..],
+            [0, lc_hi, lc_lo, rest @ ..] => {
+                let lc = usize::from(u16::from_be_bytes([*lc_hi, *lc_lo]));
+                match rest.len().checked_sub(lc)? {
+                    // Extended case 3, or extended case 4 with a two-byte Le.
+                    0 | 2 => &rest[..lc],
+                    _ => return None,
+                }
+            }
+            [lc, rest @ ..] => {
+                let lc = usize::from(*lc);
+                match rest.len().checked_sub(lc)? {
+                    // Short case 3, or short case 4 with Le.
+                    0 | 1 => &rest[..lc],
+                    _ => return None,
+                }
+            }
+        };
+
+        Some(Self { cla, ins, p1, p2, data })
+    }
+}
+
+/// Encodes a BER-TLV, with a length of at most 16 bits.
+fn tlv(tag: &[u8], value: &[u8]) -> Result<Vec<u8>, [u8; 2]> {
+    let mut encoded = tag.to_vec();
+
+    match (u8::try_from(value.len()), u16::try_from(value.len())) {
+        (Ok(len @ 0..=0x7F), _) => encoded.push(len),
+        (Ok(len), _) => encoded.extend_from_slice(&[0x81, len]),
+        (Err(_), Ok(len)) => {
+            encoded.push(0x82);
+            encoded.extend_from_slice(&len.to_be_bytes());
+        }
+        (Err(_), Err(_)) => {
+            error!(len = value.len(), "TLV value too long");
+            return Err(sw::NO_PRECISE_DIAGNOSIS);
+        }
+    }
+
+    encoded.extend_from_slice(value);
+    Ok(encoded)
+}
+
+/// Parses a sequence of BER-TLVs, returning the tag and value of each.
+///
+/// Only single-byte tags are supported, which is enough for the templates found in PIV commands.
+fn parse_tlvs(mut data: &[u8]) -> Option<Vec<(u8, &[u8])>> {
+    let mut tlvs = Vec::new();
+
+    while let Some((&tag, rest)) = data.split_first() {
+        let (&len, rest) = rest.split_first()?;
+        let (len, rest) = match len {
+            0..=0x7F => (usize::from(len), rest),
+            0x81 => {
+                let (&len, rest) = rest.split_first()?;
+                (usize::from(len), rest)
+            }
+            0x82 => match rest {
+                [hi, lo, rest @ ..] => (usize::from(u16::from_be_bytes([*hi, *lo])), rest),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        if rest.len() < len {
+            return None;
+        }
+        let (value, rest) = rest.split_at(len);
+        tlvs.push((tag, value));
+        data = rest;
+    }
+
+    Some(tlvs)
+}
@@ -1,17 +1,57 @@
 pub mod noop;
+pub mod port;
+pub mod printer;
 
 use core::fmt;
 
 use ironrdp_core::AsAny;
 use ironrdp_pdu::PduResult;
 
-use crate::pdu::efs::{DeviceControlRequest, ServerDeviceAnnounceResponse, ServerDriveIoRequest};
+use crate::pdu::efs::{DeviceControlRequest, DeviceIoRequest, ServerDeviceAnnounceResponse, ServerDriveIoRequest};
 use crate::pdu::esc::{ScardCall, ScardIoCtlCode};
-use ironrdp_svc::SvcMessage;
+use crate::pdu::RdpdrPdu;
+
+/// Message sent by a backend to the application.
+#[derive(Debug)]
+pub enum RdpdrMessage {
+    /// An I/O request left pending by the backend has completed.
+    ///
+    /// The response carries the CompletionId of the request. The application should pass it to
+    /// [`Rdpdr::io_completed`](crate::Rdpdr::io_completed), and send the resulting messages to the server.
+    IoCompleted(RdpdrPdu),
+}
+
+/// Proxy used by a backend to send messages to the application, from any thread.
+pub trait RdpdrMessageProxy: Send + Sync + fmt::Debug {
+    fn send_rdpdr_message(&self, message: RdpdrMessage);
+}
 
 /// OS-specific device redirection backend interface.
+///
+/// Each I/O request must be answered exactly once, with a response carrying its CompletionId. A request that
+/// cannot complete right away is left pending by not returning its response, and is completed later, either
+/// in the responses to another request or through a [`RdpdrMessageProxy`]. Slow operations can be completed
+/// from another thread this way, instead of blocking the session.
 pub trait RdpdrBackend: AsAny + fmt::Debug + Send {
     fn handle_server_device_announce_response(&mut self, pdu: ServerDeviceAnnounceResponse) -> PduResult<()>;
-    fn handle_scard_call(&mut self, req: DeviceControlRequest<ScardIoCtlCode>, call: ScardCall) -> PduResult<()>;
-    fn handle_drive_io_request(&mut self, req: ServerDriveIoRequest) -> PduResult<Vec<SvcMessage>>;
+    /// Handles a smart card call, returning the responses to send back to the server.
+    ///
+    /// A call that cannot complete yet (e.g. `GetStatusChange` waiting for a card event) may be left pending.
+    fn handle_scard_call(
+        &mut self,
+        req: DeviceControlRequest<ScardIoCtlCode>,
+        call: ScardCall,
+    ) -> PduResult<Vec<RdpdrPdu>>;
+    /// Handles a drive I/O request, returning the responses to send back to the server.
+    ///
+    /// A request that cannot complete yet (e.g. a directory change notification) may be left pending.
+    fn handle_drive_io_request(&mut self, req: ServerDriveIoRequest) -> PduResult<Vec<RdpdrPdu>>;
+    /// Called when a pending request is cancelled, either explicitly with
+    /// [`Rdpdr::cancel_io_request`](crate::Rdpdr::cancel_io_request) or because its file is being closed.
+    ///
+    /// The cancelled request is answered by [`Rdpdr`](crate::Rdpdr), the backend should only release the associated
+    /// resources. Any later completion of the request is discarded.
+    fn cancel_io_request(&mut self, req: &DeviceIoRequest) {
+        let _ = req;
+    }
 }
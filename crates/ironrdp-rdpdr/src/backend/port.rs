@@ -0,0 +1,31 @@
+use core::fmt;
+use std::io;
+
+use ironrdp_core::AsAny;
+
+use crate::pdu::esp::{SerialRequest, SerialResponse};
+
+/// The kind of a redirected port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortKind {
+    Serial,
+    Parallel,
+}
+
+/// Gives access to the serial and parallel ports redirected to the server.
+///
+/// Errors are reported to the server as `STATUS_NOT_SUPPORTED` for [`io::ErrorKind::Unsupported`],
+/// `STATUS_INVALID_PARAMETER` for [`io::ErrorKind::InvalidInput`], `STATUS_TIMEOUT` for
+/// [`io::ErrorKind::TimedOut`], and `STATUS_UNSUCCESSFUL` otherwise.
+pub trait PortBackend: AsAny + fmt::Debug + Send {
+    /// Opens the port with the given device ID, and returns an identifier for the open port.
+    fn open(&mut self, device_id: u32) -> io::Result<u32>;
+    /// Closes the port.
+    fn close(&mut self, file_id: u32) -> io::Result<()>;
+    /// Reads at most `length` bytes from the port, honoring the timeouts set with [`SerialRequest::SetTimeouts`].
+    fn read(&mut self, file_id: u32, length: u32) -> io::Result<Vec<u8>>;
+    /// Writes `data` to the port.
+    fn write(&mut self, file_id: u32, data: &[u8]) -> io::Result<()>;
+    /// Handles a serial port IOCTL.
+    fn serial_control(&mut self, file_id: u32, request: SerialRequest) -> io::Result<SerialResponse>;
+}
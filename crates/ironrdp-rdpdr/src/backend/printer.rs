@@ -0,0 +1,31 @@
+use core::fmt;
+use std::io;
+
+use ironrdp_core::AsAny;
+
+use crate::pdu::epc::PrinterCacheData;
+
+/// Receives the print jobs sent by the server to the redirected printers.
+///
+/// A job is started when the server opens the printer, receives the spooled document
+/// (in the format produced by the printer driver, e.g. XPS, PCL or PostScript) in order,
+/// and is ended when the server closes the printer.
+pub trait PrinterBackend: AsAny + fmt::Debug + Send {
+    /// Starts a new print job on the printer with the given device ID, and returns an identifier for it.
+    fn start_job(&mut self, device_id: u32) -> io::Result<u32>;
+    /// Appends `data` to the print job.
+    fn write_job(&mut self, job_id: u32, data: &[u8]) -> io::Result<()>;
+    /// Ends the print job, after which it can be printed.
+    fn end_job(&mut self, job_id: u32) -> io::Result<()>;
+
+    /// Called when the server updates the cached user configuration of a printer.
+    ///
+    /// Backends willing to keep the configuration across sessions should store it,
+    /// and announce it back with the printer in the next sessions.
+    fn handle_cache_data(&mut self, _data: PrinterCacheData) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called when the server will send the jobs of the printer as XPS documents.
+    fn set_xps_mode(&mut self, _device_id: u32) {}
+}
@@ -11,8 +11,11 @@
 #[macro_use]
 extern crate tracing;
 
+use std::collections::HashMap;
+
 use ironrdp_core::decode_cursor;
 use ironrdp_core::impl_as_any;
+use ironrdp_core::EncodeResult;
 use ironrdp_core::ReadCursor;
 use ironrdp_pdu::decode_err;
 use ironrdp_pdu::gcc::ChannelName;
@@ -20,18 +23,25 @@ use ironrdp_pdu::pdu_other_err;
 use ironrdp_pdu::PduResult;
 use ironrdp_svc::{CompressionCondition, SvcClientProcessor, SvcMessage, SvcProcessor};
 use pdu::efs::{
-    Capabilities, ClientDeviceListAnnounce, ClientNameRequest, ClientNameRequestUnicodeFlag, CoreCapability,
-    CoreCapabilityKind, DeviceControlRequest, DeviceIoRequest, DeviceType, Devices, ServerDeviceAnnounceResponse,
-    VersionAndIdPdu, VersionAndIdPduKind,
+    Capabilities, ClientDeviceListAnnounce, ClientDriveLockControlResponse, ClientDriveNotifyChangeDirectoryResponse,
+    ClientNameRequest, ClientNameRequestUnicodeFlag, CoreCapability, CoreCapabilityKind, DeviceCloseResponse,
+    DeviceControlRequest, DeviceControlResponse, DeviceCreateResponse, DeviceIoRequest, DeviceIoResponse,
+    DeviceReadResponse, DeviceType, DeviceWriteResponse, Devices, Information, MajorFunction, NtStatus,
+    ServerDeviceAnnounceResponse, VersionAndIdPdu, VersionAndIdPduKind,
 };
-use pdu::esc::{ScardCall, ScardIoCtlCode};
+use pdu::epc::{PrinterCacheData, PrinterDeviceAnnounce, PrinterUsingXps, ServerPrinterIoRequest};
+use pdu::esc::{rpce, ScardCall, ScardIoCtlCode};
+use pdu::esp::ServerPortIoRequest;
 use pdu::RdpdrPdu;
 
 pub mod backend;
 pub mod pdu;
+pub mod server;
 
 pub use self::backend::noop::NoopRdpdrBackend;
-pub use self::backend::RdpdrBackend;
+pub use self::backend::port::{PortBackend, PortKind};
+pub use self::backend::printer::PrinterBackend;
+pub use self::backend::{RdpdrBackend, RdpdrMessage, RdpdrMessageProxy};
 use crate::pdu::efs::ServerDriveIoRequest;
 
 /// The RDPDR channel as specified in [\[MS-RDPEFS\]].
@@ -55,6 +65,14 @@ pub struct Rdpdr {
     /// All devices not of the type [`DeviceType::Filesystem`] must be declared here.
     device_list: Devices,
     backend: Box<dyn RdpdrBackend>,
+    /// Receives the print jobs of the redirected printers, if any.
+    printer_backend: Option<Box<dyn PrinterBackend>>,
+    /// Gives access to the redirected serial and parallel ports, if any.
+    port_backend: Option<Box<dyn PortBackend>>,
+    /// I/O requests received from the server and not answered yet, by CompletionId.
+    outstanding_requests: HashMap<u32, DeviceIoRequest>,
+    /// Data representation of the server's smart card calls, which their returns are encoded in.
+    scard_endianness: rpce::Endianness,
 }
 
 impl_as_any!(Rdpdr);
@@ -69,6 +87,10 @@ impl Rdpdr {
             capabilities: Capabilities::new(),
             device_list: Devices::new(),
             backend,
+            printer_backend: None,
+            port_backend: None,
+            outstanding_requests: HashMap::new(),
+            scard_endianness: rpce::Endianness::default(),
         }
     }
 
@@ -95,6 +117,40 @@ impl Rdpdr {
         self
     }
 
+    /// Adds printer redirection capability, announcing the given `printers` to the server.
+    ///
+    /// The print jobs sent by the server to any of these printers are passed to `backend`.
+    pub fn with_printers(
+        mut self,
+        backend: Box<dyn PrinterBackend>,
+        printers: Vec<(u32, PrinterDeviceAnnounce)>,
+    ) -> EncodeResult<Self> {
+        self.capabilities.add_printer();
+        for (device_id, printer) in printers {
+            self.device_list.add_printer(device_id, &printer)?;
+        }
+        self.printer_backend = Some(backend);
+        Ok(self)
+    }
+
+    /// Adds serial and parallel port redirection capability, announcing the given `ports` to the server.
+    ///
+    /// Each port is announced with its name as seen by the server, such as "COM1" or "LPT1",
+    /// and is accessed through `backend`.
+    #[must_use]
+    pub fn with_ports(mut self, backend: Box<dyn PortBackend>, ports: Vec<(u32, PortKind, String)>) -> Self {
+        self.capabilities.add_port();
+        for (device_id, kind, name) in ports {
+            let device_type = match kind {
+                PortKind::Serial => DeviceType::Serial,
+                PortKind::Parallel => DeviceType::Parallel,
+            };
+            self.device_list.add_port(device_id, device_type, name);
+        }
+        self.port_backend = Some(backend);
+        self
+    }
+
     /// Users should call this method to announce a new drive to the server. It's the caller's responsibility
     /// to take the returned [`ClientDeviceListAnnounce`] and send it to the server.
     pub fn add_drive(&mut self, device_id: u32, name: String) -> ClientDeviceListAnnounce {
@@ -102,6 +158,34 @@ impl Rdpdr {
         ClientDeviceListAnnounce::new_drive(device_id, name)
     }
 
+    /// Encodes the response to an I/O request completed asynchronously by a backend,
+    /// see [`RdpdrMessage::IoCompleted`].
+    ///
+    /// The response is discarded if the request is not outstanding anymore, e.g. because it has been cancelled.
+    pub fn io_completed(&mut self, pdu: RdpdrPdu) -> Vec<SvcMessage> {
+        self.complete(vec![pdu])
+    }
+
+    /// Cancels the outstanding I/O request identified by `completion_id`, answering it with `STATUS_CANCELLED`.
+    ///
+    /// Returns no message if there is no such request.
+    pub fn cancel_io_request(&mut self, completion_id: u32) -> Vec<SvcMessage> {
+        let Some(req) = self.outstanding_requests.remove(&completion_id) else {
+            return Vec::new();
+        };
+
+        debug!(?req, "Cancelling I/O request");
+        self.backend.cancel_io_request(&req);
+        let res = cancelled_response(req);
+        trace!("sending {:?}", res);
+        vec![SvcMessage::from(res)]
+    }
+
+    /// Returns the I/O requests which have not been answered yet.
+    pub fn outstanding_io_requests(&self) -> impl Iterator<Item = &DeviceIoRequest> {
+        self.outstanding_requests.values()
+    }
+
     pub fn downcast_backend<T: RdpdrBackend>(&self) -> Option<&T> {
         self.backend.as_any().downcast_ref::<T>()
     }
@@ -110,6 +194,22 @@ impl Rdpdr {
         self.backend.as_any_mut().downcast_mut::<T>()
     }
 
+    pub fn downcast_port_backend<T: PortBackend>(&self) -> Option<&T> {
+        self.port_backend.as_ref()?.as_any().downcast_ref::<T>()
+    }
+
+    pub fn downcast_port_backend_mut<T: PortBackend>(&mut self) -> Option<&mut T> {
+        self.port_backend.as_mut()?.as_any_mut().downcast_mut::<T>()
+    }
+
+    pub fn downcast_printer_backend<T: PrinterBackend>(&self) -> Option<&T> {
+        self.printer_backend.as_ref()?.as_any().downcast_ref::<T>()
+    }
+
+    pub fn downcast_printer_backend_mut<T: PrinterBackend>(&mut self) -> Option<&mut T> {
+        self.printer_backend.as_mut()?.as_any_mut().downcast_mut::<T>()
+    }
+
     fn handle_server_announce(&mut self, req: VersionAndIdPdu) -> PduResult<Vec<SvcMessage>> {
         let client_announce_reply =
             RdpdrPdu::VersionAndIdPdu(VersionAndIdPdu::new_client_announce_reply(req).map_err(|e| decode_err!(e))?);
@@ -154,7 +254,31 @@ impl Rdpdr {
         dev_io_req: DeviceIoRequest,
         src: &mut ReadCursor<'_>,
     ) -> PduResult<Vec<SvcMessage>> {
-        match self
+        let mut messages = Vec::new();
+        if dev_io_req.major_function == MajorFunction::Close {
+            // Closing a file cancels the requests still pending on it.
+            let pending = self
+                .outstanding_requests
+                .values()
+                .filter(|req| req.device_id == dev_io_req.device_id && req.file_id == dev_io_req.file_id)
+                .map(|req| req.completion_id)
+                .collect::<Vec<_>>();
+            for completion_id in pending {
+                messages.extend(self.cancel_io_request(completion_id));
+            }
+        }
+
+        if let Some(previous) = self
+            .outstanding_requests
+            .insert(dev_io_req.completion_id, dev_io_req.clone())
+        {
+            warn!(
+                ?previous,
+                "Received a request reusing the CompletionId of an outstanding request"
+            );
+        }
+
+        let responses = match self
             .device_list
             .for_device_type(dev_io_req.device_id)
             .map_err(|e| decode_err!(e))?
@@ -162,28 +286,230 @@ impl Rdpdr {
             DeviceType::Smartcard => {
                 let req =
                     DeviceControlRequest::<ScardIoCtlCode>::decode(dev_io_req, src).map_err(|e| decode_err!(e))?;
+                if let Ok(endianness) = rpce::Endianness::peek(src) {
+                    self.scard_endianness = endianness;
+                }
                 let call = ScardCall::decode(req.io_control_code, src).map_err(|e| decode_err!(e))?;
 
                 debug!(?req);
                 debug!(?req.io_control_code, ?call);
 
-                self.backend.handle_scard_call(req, call)?;
-
-                Ok(Vec::new())
+                self.backend.handle_scard_call(req, call)?
             }
             DeviceType::Filesystem => {
                 let req = ServerDriveIoRequest::decode(dev_io_req, src).map_err(|e| decode_err!(e))?;
 
                 debug!(?req);
 
-                Ok(self.backend.handle_drive_io_request(req)?)
+                self.backend.handle_drive_io_request(req)?
+            }
+            DeviceType::Print => {
+                let req = ServerPrinterIoRequest::decode(dev_io_req, src).map_err(|e| decode_err!(e))?;
+
+                debug!(?req);
+
+                self.handle_printer_io_request(req)?
             }
-            _ => {
-                // This should never happen, as we only announce devices that we support.
-                warn!(?dev_io_req, "received packet for unsupported device type");
-                Ok(Vec::new())
+            DeviceType::Serial | DeviceType::Parallel => {
+                let req = ServerPortIoRequest::decode(dev_io_req, src).map_err(|e| decode_err!(e))?;
+
+                debug!(?req);
+
+                self.handle_port_io_request(req)?
+            }
+        };
+
+        messages.extend(self.complete(responses));
+        Ok(messages)
+    }
+
+    /// Encodes the responses to outstanding requests, discarding the others.
+    fn complete(&mut self, responses: Vec<RdpdrPdu>) -> Vec<SvcMessage> {
+        responses
+            .into_iter()
+            .filter_map(|mut res| {
+                if let RdpdrPdu::DeviceControlResponse(DeviceControlResponse {
+                    output_buffer: Some(output),
+                    ..
+                }) = &mut res
+                {
+                    output.set_endianness(self.scard_endianness);
+                }
+                if let Some(reply) = res.device_io_response() {
+                    if self.outstanding_requests.remove(&reply.completion_id).is_none() {
+                        debug!(?res, "Discarding the response to a request which is not outstanding");
+                        return None;
+                    }
+                }
+                trace!("sending {:?}", res);
+                Some(SvcMessage::from(res))
+            })
+            .collect()
+    }
+
+    fn handle_printer_io_request(&mut self, req: ServerPrinterIoRequest) -> PduResult<Vec<RdpdrPdu>> {
+        let Some(backend) = self.printer_backend.as_mut() else {
+            // Printers are only announced along with a backend.
+            warn!(?req, "received printer request without a printer backend");
+            return Ok(Vec::new());
+        };
+
+        let res = match req {
+            ServerPrinterIoRequest::Create(req) => {
+                let device_io_request = req.device_io_request;
+                let (io_status, file_id) = match backend.start_job(device_io_request.device_id) {
+                    Ok(job_id) => (NtStatus::SUCCESS, job_id),
+                    Err(error) => {
+                        warn!(%error, "Failed to start print job");
+                        (NtStatus::UNSUCCESSFUL, 0)
+                    }
+                };
+                RdpdrPdu::DeviceCreateResponse(DeviceCreateResponse {
+                    device_io_reply: DeviceIoResponse::new(device_io_request, io_status),
+                    file_id,
+                    information: Information::empty(),
+                })
+            }
+            ServerPrinterIoRequest::Write(req) => {
+                let device_io_request = req.device_io_request;
+                let (io_status, length) = match backend.write_job(device_io_request.file_id, &req.write_data) {
+                    Ok(()) => (NtStatus::SUCCESS, req.write_data.len() as u32),
+                    Err(error) => {
+                        warn!(%error, "Failed to write print job");
+                        (NtStatus::UNSUCCESSFUL, 0)
+                    }
+                };
+                RdpdrPdu::DeviceWriteResponse(DeviceWriteResponse {
+                    device_io_reply: DeviceIoResponse::new(device_io_request, io_status),
+                    length,
+                })
+            }
+            ServerPrinterIoRequest::Close(req) => {
+                let device_io_request = req.device_io_request;
+                let io_status = match backend.end_job(device_io_request.file_id) {
+                    Ok(()) => NtStatus::SUCCESS,
+                    Err(error) => {
+                        warn!(%error, "Failed to end print job");
+                        NtStatus::UNSUCCESSFUL
+                    }
+                };
+                RdpdrPdu::DeviceCloseResponse(DeviceCloseResponse {
+                    device_io_response: DeviceIoResponse::new(device_io_request, io_status),
+                })
+            }
+            ServerPrinterIoRequest::Read(req) => {
+                // Printers are write-only.
+                RdpdrPdu::DeviceReadResponse(DeviceReadResponse {
+                    device_io_reply: DeviceIoResponse::new(req.device_io_request, NtStatus::NOT_SUPPORTED),
+                    read_data: Vec::new(),
+                })
+            }
+            ServerPrinterIoRequest::DeviceControl(req) => {
+                // The spooler may query the port, there is nothing to report.
+                RdpdrPdu::DeviceControlResponse(DeviceControlResponse::new(req, NtStatus::SUCCESS, None))
+            }
+        };
+
+        Ok(vec![res])
+    }
+
+    fn handle_port_io_request(&mut self, req: ServerPortIoRequest) -> PduResult<Vec<RdpdrPdu>> {
+        let Some(backend) = self.port_backend.as_mut() else {
+            // Ports are only announced along with a backend.
+            warn!(?req, "received port request without a port backend");
+            return Ok(Vec::new());
+        };
+
+        let res = match req {
+            ServerPortIoRequest::Create(req) => {
+                let device_io_request = req.device_io_request;
+                let (io_status, file_id) = match backend.open(device_io_request.device_id) {
+                    Ok(file_id) => (NtStatus::SUCCESS, file_id),
+                    Err(error) => {
+                        warn!(%error, "Failed to open port");
+                        (io_error_status(&error), 0)
+                    }
+                };
+                RdpdrPdu::DeviceCreateResponse(DeviceCreateResponse {
+                    device_io_reply: DeviceIoResponse::new(device_io_request, io_status),
+                    file_id,
+                    information: Information::empty(),
+                })
+            }
+            ServerPortIoRequest::Close(req) => {
+                let device_io_request = req.device_io_request;
+                let io_status = match backend.close(device_io_request.file_id) {
+                    Ok(()) => NtStatus::SUCCESS,
+                    Err(error) => {
+                        warn!(%error, "Failed to close port");
+                        io_error_status(&error)
+                    }
+                };
+                RdpdrPdu::DeviceCloseResponse(DeviceCloseResponse {
+                    device_io_response: DeviceIoResponse::new(device_io_request, io_status),
+                })
             }
+            ServerPortIoRequest::Read(req) => {
+                let device_io_request = req.device_io_request;
+                let (io_status, read_data) = match backend.read(device_io_request.file_id, req.length) {
+                    Ok(read_data) => (NtStatus::SUCCESS, read_data),
+                    Err(error) => {
+                        debug!(%error, "Failed to read from port");
+                        (io_error_status(&error), Vec::new())
+                    }
+                };
+                RdpdrPdu::DeviceReadResponse(DeviceReadResponse {
+                    device_io_reply: DeviceIoResponse::new(device_io_request, io_status),
+                    read_data,
+                })
+            }
+            ServerPortIoRequest::Write(req) => {
+                let device_io_request = req.device_io_request;
+                let (io_status, length) = match backend.write(device_io_request.file_id, &req.write_data) {
+                    Ok(()) => (NtStatus::SUCCESS, req.write_data.len() as u32),
+                    Err(error) => {
+                        debug!(%error, "Failed to write to port");
+                        (io_error_status(&error), 0)
+                    }
+                };
+                RdpdrPdu::DeviceWriteResponse(DeviceWriteResponse {
+                    device_io_reply: DeviceIoResponse::new(device_io_request, io_status),
+                    length,
+                })
+            }
+            ServerPortIoRequest::SerialControl(req, call) => {
+                let (io_status, output_buffer) = match backend.serial_control(req.header.file_id, call) {
+                    Ok(response) => (NtStatus::SUCCESS, response.into_output_buffer()),
+                    Err(error) => {
+                        debug!(%error, ?req.io_control_code, "Serial port IOCTL failed");
+                        (io_error_status(&error), None)
+                    }
+                };
+                RdpdrPdu::DeviceControlResponse(DeviceControlResponse::new(req, io_status, output_buffer))
+            }
+            ServerPortIoRequest::UnsupportedControl(req) => {
+                debug!(?req.io_control_code, "Unsupported port IOCTL");
+                RdpdrPdu::DeviceControlResponse(DeviceControlResponse::new(req, NtStatus::NOT_SUPPORTED, None))
+            }
+        };
+
+        Ok(vec![res])
+    }
+
+    fn handle_printer_cache_data(&mut self, pdu: PrinterCacheData) -> PduResult<Vec<SvcMessage>> {
+        if let Some(backend) = self.printer_backend.as_mut() {
+            backend
+                .handle_cache_data(pdu)
+                .map_err(|e| pdu_other_err!("Rdpdr", source: e))?;
+        }
+        Ok(Vec::new())
+    }
+
+    fn handle_printer_using_xps(&mut self, pdu: PrinterUsingXps) -> PduResult<Vec<SvcMessage>> {
+        if let Some(backend) = self.printer_backend.as_mut() {
+            backend.set_xps_mode(pdu.printer_id);
         }
+        Ok(Vec::new())
     }
 }
 
@@ -212,11 +538,17 @@ impl SvcProcessor for Rdpdr {
                 self.handle_client_id_confirm()
             }
             RdpdrPdu::ServerDeviceAnnounceResponse(pdu) => self.handle_server_device_announce_response(pdu),
+            // Devices are announced right away, so there is nothing left to do once the user is logged on.
+            RdpdrPdu::UserLoggedOn => Ok(Vec::new()),
             RdpdrPdu::DeviceIoRequest(pdu) => self.handle_device_io_request(pdu, &mut src),
+            RdpdrPdu::PrinterCacheData(pdu) => self.handle_printer_cache_data(pdu),
+            RdpdrPdu::PrinterUsingXps(pdu) => self.handle_printer_using_xps(pdu),
             // TODO: This can eventually become a `_ => {}` block, but being explicit for now
             // to make sure we don't miss handling new RdpdrPdu variants here during active development.
             RdpdrPdu::ClientNameRequest(_)
             | RdpdrPdu::ClientDeviceListAnnounce(_)
+            | RdpdrPdu::ClientDeviceListRemove(_)
+            | RdpdrPdu::ServerDeviceIoRequest(_)
             | RdpdrPdu::VersionAndIdPdu(_)
             | RdpdrPdu::CoreCapability(_)
             | RdpdrPdu::DeviceControlResponse(_)
@@ -228,9 +560,59 @@ impl SvcProcessor for Rdpdr {
             | RdpdrPdu::DeviceReadResponse(_)
             | RdpdrPdu::DeviceWriteResponse(_)
             | RdpdrPdu::ClientDriveSetInformationResponse(_)
+            | RdpdrPdu::ClientDriveNotifyChangeDirectoryResponse(_)
+            | RdpdrPdu::ClientDriveLockControlResponse(_)
             | RdpdrPdu::EmptyResponse => Err(pdu_other_err!("Rdpdr", "received unexpected packet")),
         }
     }
 }
 
 impl SvcClientProcessor for Rdpdr {}
+
+/// Maps an error returned by a device backend to the status reported to the server.
+fn io_error_status(error: &std::io::Error) -> NtStatus {
+    match error.kind() {
+        std::io::ErrorKind::Unsupported => NtStatus::NOT_SUPPORTED,
+        std::io::ErrorKind::InvalidInput => NtStatus::INVALID_PARAMETER,
+        std::io::ErrorKind::TimedOut => NtStatus::TIMEOUT,
+        _ => NtStatus::UNSUCCESSFUL,
+    }
+}
+
+/// Builds the response to a cancelled request.
+fn cancelled_response(req: DeviceIoRequest) -> RdpdrPdu {
+    let major_function = req.major_function;
+    let device_io_reply = DeviceIoResponse::new(req, NtStatus::CANCELLED);
+    match major_function {
+        MajorFunction::Create => RdpdrPdu::DeviceCreateResponse(DeviceCreateResponse {
+            device_io_reply,
+            file_id: 0,
+            information: Information::empty(),
+        }),
+        MajorFunction::Close => RdpdrPdu::DeviceCloseResponse(DeviceCloseResponse {
+            device_io_response: device_io_reply,
+        }),
+        MajorFunction::Read => RdpdrPdu::DeviceReadResponse(DeviceReadResponse {
+            device_io_reply,
+            read_data: Vec::new(),
+        }),
+        MajorFunction::Write => RdpdrPdu::DeviceWriteResponse(DeviceWriteResponse {
+            device_io_reply,
+            length: 0,
+        }),
+        MajorFunction::DirectoryControl => {
+            RdpdrPdu::ClientDriveNotifyChangeDirectoryResponse(ClientDriveNotifyChangeDirectoryResponse {
+                device_io_reply,
+                buffer: Vec::new(),
+            })
+        }
+        MajorFunction::LockControl => {
+            RdpdrPdu::ClientDriveLockControlResponse(ClientDriveLockControlResponse { device_io_reply })
+        }
+        // The other responses start with a 32-bit length, the rest being ignored on failure.
+        _ => RdpdrPdu::DeviceControlResponse(DeviceControlResponse {
+            device_io_reply,
+            output_buffer: None,
+        }),
+    }
+}
@@ -14,6 +14,7 @@ use ironrdp_core::{
 use ironrdp_pdu::utils::{decode_string, encoded_str_len, from_utf16_bytes, write_string_to_cursor, CharacterSet};
 use ironrdp_pdu::{read_padding, write_padding, PduError};
 
+use super::epc::PrinterDeviceAnnounce;
 use super::esc::rpce;
 use super::{PacketId, SharedHeader};
 
@@ -75,6 +76,30 @@ impl VersionAndIdPdu {
         })
     }
 
+    /// Creates a new [`DR_CORE_SERVER_ANNOUNCE_REQ`] assigning `client_id` to the client.
+    ///
+    /// [`DR_CORE_SERVER_ANNOUNCE_REQ`]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpefs/046047aa-62d8-49f9-bf16-7fe41880aaf4
+    pub fn new_server_announce(client_id: u32) -> Self {
+        Self {
+            version_major: VERSION_MAJOR,
+            version_minor: VERSION_MINOR_12,
+            client_id,
+            kind: VersionAndIdPduKind::ServerAnnounceRequest,
+        }
+    }
+
+    /// Creates a new [`DR_CORE_SERVER_CLIENTID_CONFIRM`] confirming the `client_id` of the client.
+    ///
+    /// [`DR_CORE_SERVER_CLIENTID_CONFIRM`]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpefs/bbbb9666-6994-4cf6-8e65-0d46eb319c6e
+    pub fn new_server_client_id_confirm(client_id: u32) -> Self {
+        Self {
+            version_major: VERSION_MAJOR,
+            version_minor: VERSION_MINOR_12,
+            client_id,
+            kind: VersionAndIdPduKind::ServerClientIdConfirm,
+        }
+    }
+
     pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
         ensure_size!(ctx: self.name(), in: dst, size: Self::FIXED_PART_SIZE);
         dst.write_u16(self.version_major);
@@ -83,6 +108,7 @@ impl VersionAndIdPdu {
         Ok(())
     }
 
+    /// Decodes a PDU sent by the server.
     pub fn decode(header: SharedHeader, src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
         let kind = match header.packet_id {
             PacketId::CoreServerAnnounce => VersionAndIdPduKind::ServerAnnounceRequest,
@@ -96,6 +122,17 @@ impl VersionAndIdPdu {
             }
         };
 
+        Self::decode_kind(kind, src)
+    }
+
+    /// Decodes a [`DR_CORE_CLIENT_ANNOUNCE_RSP`], which shares its PacketId with the Server Client ID Confirm.
+    ///
+    /// [`DR_CORE_CLIENT_ANNOUNCE_RSP`]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpefs/d6fe6d1b-c145-4a6f-99aa-4fe3cdcea398
+    pub fn decode_client_announce_reply(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        Self::decode_kind(VersionAndIdPduKind::ClientAnnounceReply, src)
+    }
+
+    fn decode_kind(kind: VersionAndIdPduKind, src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
         ensure_size!(ctx: kind.name(), in: src, size: Self::FIXED_PART_SIZE);
         let version_major = src.read_u16();
         let version_minor = src.read_u16();
@@ -145,13 +182,6 @@ impl ClientNameRequest {
         }
     }
 
-    fn computer_name(&self) -> &str {
-        match self {
-            ClientNameRequest::Ascii(name) => name,
-            ClientNameRequest::Unicode(name) => name,
-        }
-    }
-
     pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: self.size());
         dst.write_u32(self.unicode_flag().into());
@@ -160,6 +190,31 @@ impl ClientNameRequest {
         write_string_to_cursor(dst, self.computer_name(), self.unicode_flag().into(), true)
     }
 
+    pub fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+        let kind = match src.read_u32() {
+            0 => ClientNameRequestUnicodeFlag::Ascii,
+            _ => ClientNameRequestUnicodeFlag::Unicode,
+        };
+        let _code_page = src.read_u32();
+        let computer_name_len = cast_length!("ClientNameRequest", "ComputerNameLen", src.read_u32())?;
+
+        ensure_size!(in: src, size: computer_name_len);
+        let computer_name = decode_string(src.read_slice(computer_name_len), kind.into(), false)?
+            .trim_end_matches('\0')
+            .to_owned();
+
+        Ok(Self::new(computer_name, kind))
+    }
+
+    /// Returns the name of the client computer.
+    pub fn computer_name(&self) -> &str {
+        match self {
+            ClientNameRequest::Ascii(name) => name,
+            ClientNameRequest::Unicode(name) => name,
+        }
+    }
+
     pub fn name(&self) -> &'static str {
         Self::NAME
     }
@@ -215,6 +270,24 @@ impl CoreCapability {
         }
     }
 
+    /// Creates a new [`DR_CORE_CAPABILITY_REQ`] with the given `capabilities`.
+    ///
+    /// [`DR_CORE_CAPABILITY_REQ`]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpefs/702789c3-b924-4bc2-9280-3221bc7d6797
+    pub fn new_request(capabilities: Vec<CapabilityMessage>) -> Self {
+        Self {
+            capabilities,
+            kind: CoreCapabilityKind::ServerCoreCapabilityRequest,
+        }
+    }
+
+    /// Returns whether the general capability set allows the server to send a Server User Logged On packet.
+    pub fn user_logged_on_supported(&self) -> bool {
+        self.capabilities.iter().any(|cap| match &cap.capability_data {
+            CapabilityData::General(general) => general.extended_pdu.contains(ExtendedPdu::RDPDR_USER_LOGGEDON_PDU),
+            _ => false,
+        })
+    }
+
     pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
         ensure_size!(ctx: self.name(), in: dst, size: self.size());
         dst.write_u16(cast_length!(
@@ -294,6 +367,21 @@ impl Capabilities {
         this
     }
 
+    /// Creates the capabilities of a server accepting every device type.
+    pub fn new_server() -> Self {
+        let mut general = CapabilityMessage::new_general(0);
+        if let CapabilityData::General(general) = &mut general.capability_data {
+            general.extended_pdu |= ExtendedPdu::RDPDR_USER_LOGGEDON_PDU;
+        }
+        Self(vec![
+            general,
+            CapabilityMessage::new_printer(),
+            CapabilityMessage::new_port(),
+            CapabilityMessage::new_drive(),
+            CapabilityMessage::new_smartcard(),
+        ])
+    }
+
     pub fn clone_inner(&mut self) -> Vec<CapabilityMessage> {
         self.0.clone()
     }
@@ -307,6 +395,14 @@ impl Capabilities {
         self.push(CapabilityMessage::new_drive());
     }
 
+    pub fn add_printer(&mut self) {
+        self.push(CapabilityMessage::new_printer());
+    }
+
+    pub fn add_port(&mut self) {
+        self.push(CapabilityMessage::new_port());
+    }
+
     fn add_general(&mut self, special_type_device_cap: u32) {
         self.push(CapabilityMessage::new_general(special_type_device_cap));
     }
@@ -392,6 +488,22 @@ impl CapabilityMessage {
         }
     }
 
+    /// Creates a new `PRINTER_CAPS_SET`.
+    pub fn new_printer() -> Self {
+        Self {
+            header: CapabilityHeader::new_printer(),
+            capability_data: CapabilityData::Printer,
+        }
+    }
+
+    /// Creates a new `PORT_CAPS_SET`, for serial and parallel ports.
+    pub fn new_port() -> Self {
+        Self {
+            header: CapabilityHeader::new_port(),
+            capability_data: CapabilityData::Port,
+        }
+    }
+
     fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: self.size());
         self.header.encode(dst)?;
@@ -450,6 +562,22 @@ impl CapabilityHeader {
         }
     }
 
+    fn new_printer() -> Self {
+        Self {
+            cap_type: CapabilityType::Printer,
+            length: Self::SIZE as u16,
+            version: PRINT_CAPABILITY_VERSION_01,
+        }
+    }
+
+    fn new_port() -> Self {
+        Self {
+            cap_type: CapabilityType::Port,
+            length: Self::SIZE as u16,
+            version: PORT_CAPABILITY_VERSION_01,
+        }
+    }
+
     fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
         ensure_size!(in: src, size: Self::SIZE);
         let cap_type: CapabilityType = src.read_u16().try_into()?;
@@ -499,6 +627,10 @@ pub const GENERAL_CAPABILITY_VERSION_02: u32 = 0x0000_0002;
 pub const SMARTCARD_CAPABILITY_VERSION_01: u32 = 0x0000_0001;
 /// DRIVE_CAPABILITY_VERSION_02
 pub const DRIVE_CAPABILITY_VERSION_02: u32 = 0x0000_0002;
+/// PRINT_CAPABILITY_VERSION_01
+pub const PRINT_CAPABILITY_VERSION_01: u32 = 0x0000_0001;
+/// PORT_CAPABILITY_VERSION_01
+pub const PORT_CAPABILITY_VERSION_01: u32 = 0x0000_0001;
 
 impl TryFrom<u16> for CapabilityType {
     type Error = DecodeError;
@@ -771,6 +903,16 @@ impl ClientDeviceListAnnounce {
         Ok(())
     }
 
+    pub fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_size!(ctx: "ClientDeviceListAnnounce", in: src, size: Self::FIXED_PART_SIZE);
+        let device_count = src.read_u32();
+        let device_list = (0..device_count)
+            .map(|_| DeviceAnnounceHeader::decode(src))
+            .collect::<DecodeResult<_>>()?;
+
+        Ok(Self { device_list })
+    }
+
     pub fn name(&self) -> &'static str {
         "DR_CORE_DEVICELIST_ANNOUNCE_REQ"
     }
@@ -796,6 +938,16 @@ impl Devices {
         self.push(DeviceAnnounceHeader::new_drive(device_id, name));
     }
 
+    pub fn add_printer(&mut self, device_id: u32, printer: &PrinterDeviceAnnounce) -> EncodeResult<()> {
+        self.push(DeviceAnnounceHeader::new_printer(device_id, printer)?);
+        Ok(())
+    }
+
+    /// Adds a serial or parallel port, `device_type` being either [`DeviceType::Serial`] or [`DeviceType::Parallel`].
+    pub fn add_port(&mut self, device_id: u32, device_type: DeviceType, name: String) {
+        self.push(DeviceAnnounceHeader::new_port(device_id, device_type, name));
+    }
+
     /// Returns the [`DeviceType`] for the given device ID.
     pub fn for_device_type(&self, device_id: u32) -> DecodeResult<DeviceType> {
         if let Some(device_type) = self.0.iter().find(|d| d.device_id == device_id).map(|d| d.device_type) {
@@ -824,6 +976,48 @@ impl Default for Devices {
     }
 }
 
+/// 2.2.3.2 Client Drive Device List Remove (DR_DEVICELIST_REMOVE)
+#[derive(Debug, PartialEq, Clone)]
+pub struct ClientDeviceListRemove {
+    pub device_ids: Vec<u32>,
+}
+
+impl ClientDeviceListRemove {
+    const NAME: &'static str = "DR_DEVICELIST_REMOVE";
+    const FIXED_PART_SIZE: usize = size_of::<u32>(); // DeviceCount
+
+    pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(in: dst, size: self.size());
+        dst.write_u32(cast_length!(
+            "ClientDeviceListRemove",
+            "DeviceCount",
+            self.device_ids.len()
+        )?);
+        for device_id in self.device_ids.iter() {
+            dst.write_u32(*device_id);
+        }
+        Ok(())
+    }
+
+    pub fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+        let device_count = cast_length!("ClientDeviceListRemove", "DeviceCount", src.read_u32())?;
+
+        ensure_size!(in: src, size: device_count * size_of::<u32>());
+        let device_ids = (0..device_count).map(|_| src.read_u32()).collect();
+
+        Ok(Self { device_ids })
+    }
+
+    pub fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    pub fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE + self.device_ids.len() * size_of::<u32>()
+    }
+}
+
 /// [2.2.1.3] Device Announce Header (DEVICE_ANNOUNCE)
 ///
 /// [2.2.1.3]: https://docs.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpefs/32e34332-774b-4ead-8c9d-5d64720d6bf9
@@ -867,6 +1061,57 @@ impl DeviceAnnounceHeader {
         }
     }
 
+    fn new_printer(device_id: u32, printer: &PrinterDeviceAnnounce) -> EncodeResult<Self> {
+        let mut device_data = vec![0; printer.size()];
+        printer.encode(&mut WriteCursor::new(&mut device_data))?;
+
+        Ok(Self {
+            device_type: DeviceType::Print,
+            device_id,
+            // The server uses the printer name from the DeviceData, this is only displayed as the port name.
+            preferred_dos_name: PreferredDosName(format!("PRN{device_id}")),
+            device_data,
+        })
+    }
+
+    fn new_port(device_id: u32, device_type: DeviceType, name: String) -> Self {
+        Self {
+            device_type,
+            device_id,
+            // The port name, such as "COM1" or "LPT1", as seen by the server.
+            preferred_dos_name: PreferredDosName(name),
+            device_data: Vec::new(),
+        }
+    }
+
+    pub fn device_type(&self) -> DeviceType {
+        self.device_type
+    }
+
+    pub fn device_id(&self) -> u32 {
+        self.device_id
+    }
+
+    pub fn preferred_dos_name(&self) -> &str {
+        &self.preferred_dos_name.0
+    }
+
+    pub fn device_data(&self) -> &[u8] {
+        &self.device_data
+    }
+
+    /// Returns the name of the device, which is the full name found in the device data for drives.
+    pub fn name(&self) -> String {
+        match self.device_type {
+            // Like in `new_drive`, the full name is expected as null terminated UTF-8.
+            DeviceType::Filesystem if !self.device_data.is_empty() => {
+                let name = self.device_data.split(|&b| b == 0).next().unwrap_or_default();
+                String::from_utf8_lossy(name).into_owned()
+            }
+            _ => self.preferred_dos_name.0.clone(),
+        }
+    }
+
     fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
         dst.write_u32(self.device_type.into());
         dst.write_u32(self.device_id);
@@ -880,6 +1125,24 @@ impl DeviceAnnounceHeader {
         Ok(())
     }
 
+    fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_size!(ctx: "DeviceAnnounceHeader", in: src, size: Self::FIXED_PART_SIZE);
+        let device_type = DeviceType::try_from(src.read_u32())?;
+        let device_id = src.read_u32();
+        let preferred_dos_name = PreferredDosName::decode(src)?;
+        let device_data_length = cast_length!("DeviceAnnounceHeader", "DeviceDataLength", src.read_u32())?;
+
+        ensure_size!(ctx: "DeviceAnnounceHeader", in: src, size: device_data_length);
+        let device_data = src.read_slice(device_data_length).to_vec();
+
+        Ok(Self {
+            device_type,
+            device_id,
+            preferred_dos_name,
+            device_data,
+        })
+    }
+
     fn size(&self) -> usize {
         Self::FIXED_PART_SIZE + self.device_data.len()
     }
@@ -904,6 +1167,11 @@ impl PreferredDosName {
         write_string_to_cursor(dst, &self.format(), CharacterSet::Ansi, false)
     }
 
+    fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        let name = decode_string(src.read_slice(8), CharacterSet::Ansi, false)?;
+        Ok(Self(name.trim_end_matches('\0').to_owned()))
+    }
+
     /// Returns the underlying String with a maximum length of 7 characters plus a null terminator.
     fn format(&self) -> String {
         let mut name: &str = &self.0;
@@ -1017,6 +1285,20 @@ impl NtStatus {
     pub const NOT_SUPPORTED: Self = Self(0xC000_00BB);
     /// STATUS_DIRECTORY_NOT_EMPTY
     pub const DIRECTORY_NOT_EMPTY: Self = Self(0xC000_0101);
+    /// STATUS_INVALID_PARAMETER
+    pub const INVALID_PARAMETER: Self = Self(0xC000_000D);
+    /// STATUS_TIMEOUT
+    pub const TIMEOUT: Self = Self(0x0000_0102);
+    /// STATUS_CANCELLED
+    pub const CANCELLED: Self = Self(0xC000_0120);
+    /// STATUS_NOTIFY_CLEANUP
+    pub const NOTIFY_CLEANUP: Self = Self(0x0000_010B);
+    /// STATUS_NOTIFY_ENUM_DIR
+    pub const NOTIFY_ENUM_DIR: Self = Self(0x0000_010C);
+    /// STATUS_LOCK_NOT_GRANTED
+    pub const LOCK_NOT_GRANTED: Self = Self(0xC000_0055);
+    /// STATUS_RANGE_NOT_LOCKED
+    pub const RANGE_NOT_LOCKED: Self = Self(0xC000_007E);
 }
 
 impl Debug for NtStatus {
@@ -1032,6 +1314,13 @@ impl Debug for NtStatus {
             NtStatus::NO_SUCH_FILE => write!(f, "STATUS_NO_SUCH_FILE"),
             NtStatus::NOT_SUPPORTED => write!(f, "STATUS_NOT_SUPPORTED"),
             NtStatus::DIRECTORY_NOT_EMPTY => write!(f, "STATUS_DIRECTORY_NOT_EMPTY"),
+            NtStatus::INVALID_PARAMETER => write!(f, "STATUS_INVALID_PARAMETER"),
+            NtStatus::TIMEOUT => write!(f, "STATUS_TIMEOUT"),
+            NtStatus::CANCELLED => write!(f, "STATUS_CANCELLED"),
+            NtStatus::NOTIFY_CLEANUP => write!(f, "STATUS_NOTIFY_CLEANUP"),
+            NtStatus::NOTIFY_ENUM_DIR => write!(f, "STATUS_NOTIFY_ENUM_DIR"),
+            NtStatus::LOCK_NOT_GRANTED => write!(f, "STATUS_LOCK_NOT_GRANTED"),
+            NtStatus::RANGE_NOT_LOCKED => write!(f, "STATUS_RANGE_NOT_LOCKED"),
             _ => write!(f, "NtStatus({:#010X})", self.0),
         }
     }
@@ -1534,6 +1823,78 @@ impl From<ServerDriveLockControlRequest> for ServerDriveIoRequest {
     }
 }
 
+/// A device I/O request sent by a server, the counterpart of [`ServerDriveIoRequest`].
+///
+/// Only the requests needed to access redirected drives and smart cards can be encoded.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ServerDeviceIoRequest {
+    Create(DeviceCreateRequest),
+    Close(DeviceCloseRequest),
+    Read(DeviceReadRequest),
+    Write(DeviceWriteRequest),
+    QueryInformation(ServerDriveQueryInformationRequest),
+    QueryDirectory(ServerDriveQueryDirectoryRequest),
+    DeviceControl {
+        request: DeviceControlRequest<AnyIoCtlCode>,
+        input_buffer: Vec<u8>,
+    },
+}
+
+impl ServerDeviceIoRequest {
+    const NAME: &'static str = "DR_DEVICE_IOREQUEST";
+
+    pub fn device_io_request(&self) -> &DeviceIoRequest {
+        match self {
+            Self::Create(req) => &req.device_io_request,
+            Self::Close(req) => &req.device_io_request,
+            Self::Read(req) => &req.device_io_request,
+            Self::Write(req) => &req.device_io_request,
+            Self::QueryInformation(req) => &req.device_io_request,
+            Self::QueryDirectory(req) => &req.device_io_request,
+            Self::DeviceControl { request, .. } => &request.header,
+        }
+    }
+
+    pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        match self {
+            Self::Create(req) => req.encode(dst),
+            Self::Close(req) => req.encode(dst),
+            Self::Read(req) => req.encode(dst),
+            Self::Write(req) => req.encode(dst),
+            Self::QueryInformation(req) => req.encode(dst),
+            Self::QueryDirectory(req) => req.encode(dst),
+            Self::DeviceControl { request, input_buffer } => {
+                ensure_size!(ctx: Self::NAME, in: dst, size: self.size());
+                request.header.encode(dst)?;
+                dst.write_u32(request.output_buffer_length);
+                dst.write_u32(cast_length!(Self::NAME, "InputBufferLength", input_buffer.len())?);
+                dst.write_u32(request.io_control_code.0);
+                write_padding!(dst, 20);
+                dst.write_slice(input_buffer);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Create(req) => req.size(),
+            Self::Close(req) => req.size(),
+            Self::Read(req) => req.size(),
+            Self::Write(req) => req.size(),
+            Self::QueryInformation(req) => req.size(),
+            Self::QueryDirectory(req) => req.size(),
+            Self::DeviceControl { request, input_buffer } => {
+                request.header.size() + DeviceControlRequest::<AnyIoCtlCode>::HEADERLESS_SIZE + input_buffer.len()
+            }
+        }
+    }
+}
+
 /// [2.2.3.3.1] Server Create Drive Request (DR_DRIVE_CREATE_REQ)
 /// and [2.2.1.4.1] Device Create Request (DR_CREATE_REQ)
 ///
@@ -1561,7 +1922,7 @@ impl DeviceCreateRequest {
                                  + 4  // CreateOptions
                                  + 4; // PathLength
 
-    fn decode(dev_io_req: DeviceIoRequest, src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+    pub fn decode(dev_io_req: DeviceIoRequest, src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
         ensure_size!(ctx: "DeviceCreateRequest", in: src, size: Self::FIXED_PART_SIZE);
         let desired_access = DesiredAccess::from_bits_retain(src.read_u32());
         let allocation_size = src.read_u64();
@@ -1587,6 +1948,27 @@ impl DeviceCreateRequest {
             path,
         })
     }
+
+    pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(ctx: "DeviceCreateRequest", in: dst, size: self.size());
+        self.device_io_request.encode(dst)?;
+        dst.write_u32(self.desired_access.bits());
+        dst.write_u64(self.allocation_size);
+        dst.write_u32(self.file_attributes.bits());
+        dst.write_u32(self.shared_access.bits());
+        dst.write_u32(self.create_disposition.bits());
+        dst.write_u32(self.create_options.bits());
+        dst.write_u32(cast_length!(
+            "DeviceCreateRequest",
+            "PathLength",
+            encoded_str_len(&self.path, CharacterSet::Unicode, true)
+        )?);
+        write_string_to_cursor(dst, &self.path, CharacterSet::Unicode, true)
+    }
+
+    pub fn size(&self) -> usize {
+        self.device_io_request.size() + Self::FIXED_PART_SIZE + encoded_str_len(&self.path, CharacterSet::Unicode, true)
+    }
 }
 
 bitflags! {
@@ -1767,6 +2149,23 @@ impl DeviceCreateResponse {
         Ok(())
     }
 
+    pub fn decode(device_io_reply: DeviceIoResponse, src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_size!(ctx: Self::NAME, in: src, size: 4);
+        let file_id = src.read_u32();
+        // Some clients omit the Information field when the request fails.
+        let information = if src.is_empty() {
+            Information::empty()
+        } else {
+            Information::from_bits_retain(src.read_u8())
+        };
+
+        Ok(Self {
+            device_io_reply,
+            file_id,
+            information,
+        })
+    }
+
     pub fn size(&self) -> usize {
         self.device_io_reply.size() // DeviceIoReply
         + 4 // FileId
@@ -1811,12 +2210,29 @@ impl ServerDriveQueryInformationRequest {
             file_info_class_lvl,
         })
     }
+
+    /// Encodes the request with an empty query buffer.
+    pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(ctx: "ServerDriveQueryInformationRequest", in: dst, size: self.size());
+        self.device_io_request.encode(dst)?;
+        dst.write_u32(self.file_info_class_lvl.into());
+        dst.write_u32(0); // Length
+        write_padding!(dst, 24);
+        Ok(())
+    }
+
+    pub fn size(&self) -> usize {
+        self.device_io_request.size()
+            + 4 // FsInformationClass
+            + 4 // Length
+            + 24 // Padding
+    }
 }
 
 /// [2.4] File Information Classes \[MS-FSCC\]
 ///
 /// [2.4]: https://docs.microsoft.com/en-us/openspecs/windows_protocols/ms-fscc/4718fc40-e539-4014-8e33-b675af74e3e1
-#[derive(PartialEq, Eq, Clone)]
+#[derive(PartialEq, Eq, Clone, Copy)]
 pub struct FileInformationClassLevel(u32);
 
 impl FileInformationClassLevel {
@@ -1924,6 +2340,19 @@ impl ClientDriveQueryInformationResponse {
         Ok(())
     }
 
+    /// Decodes the response to a request for the `file_info_class_lvl` information class.
+    pub fn decode(
+        device_io_response: DeviceIoResponse,
+        file_info_class_lvl: FileInformationClassLevel,
+        src: &mut ReadCursor<'_>,
+    ) -> DecodeResult<Self> {
+        let buffer = decode_information_buffer(Self::NAME, file_info_class_lvl, src)?;
+        Ok(Self {
+            device_io_response,
+            buffer,
+        })
+    }
+
     pub fn size(&self) -> usize {
         self.device_io_response.size() // DeviceIoResponse
         + 4 // Length
@@ -1935,6 +2364,25 @@ impl ClientDriveQueryInformationResponse {
     }
 }
 
+/// Decodes the Length-prefixed buffer of a query response, which is absent if the Length is 0.
+///
+/// The buffer is decoded on its own, ignoring the trailing bytes such as the reserved fields not sent by every client.
+fn decode_information_buffer(
+    ctx: &'static str,
+    file_info_class_lvl: FileInformationClassLevel,
+    src: &mut ReadCursor<'_>,
+) -> DecodeResult<Option<FileInformationClass>> {
+    ensure_size!(ctx: ctx, in: src, size: 4);
+    let length = cast_length!(ctx, "Length", src.read_u32())?;
+    if length == 0 {
+        return Ok(None);
+    }
+
+    ensure_size!(ctx: ctx, in: src, size: length);
+    let mut buffer = ReadCursor::new(src.read_slice(length));
+    FileInformationClass::decode(file_info_class_lvl, length, &mut buffer).map(Some)
+}
+
 /// [2.4] File Information Classes \[MS-FSCC\]
 ///
 /// [2.4]: https://docs.microsoft.com/en-us/openspecs/windows_protocols/ms-fscc/4718fc40-e539-4014-8e33-b675af74e3e1
@@ -1979,6 +2427,18 @@ impl FileInformationClass {
     ) -> DecodeResult<Self> {
         match file_info_class_level {
             FileInformationClassLevel::FILE_BASIC_INFORMATION => Ok(FileBasicInformation::decode(src)?.into()),
+            FileInformationClassLevel::FILE_STANDARD_INFORMATION => Ok(FileStandardInformation::decode(src)?.into()),
+            FileInformationClassLevel::FILE_ATTRIBUTE_TAG_INFORMATION => {
+                Ok(FileAttributeTagInformation::decode(src)?.into())
+            }
+            FileInformationClassLevel::FILE_BOTH_DIRECTORY_INFORMATION => {
+                Ok(FileBothDirectoryInformation::decode(src)?.into())
+            }
+            FileInformationClassLevel::FILE_FULL_DIRECTORY_INFORMATION => {
+                Ok(FileFullDirectoryInformation::decode(src)?.into())
+            }
+            FileInformationClassLevel::FILE_NAMES_INFORMATION => Ok(FileNamesInformation::decode(src)?.into()),
+            FileInformationClassLevel::FILE_DIRECTORY_INFORMATION => Ok(FileDirectoryInformation::decode(src)?.into()),
             FileInformationClassLevel::FILE_END_OF_FILE_INFORMATION => {
                 Ok(FileEndOfFileInformation::decode(src)?.into())
             }
@@ -2165,6 +2625,22 @@ pub struct FileStandardInformation {
 }
 
 impl FileStandardInformation {
+    fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_size!(ctx: "FileStandardInformation", in: src, size: Self::size());
+        let allocation_size = src.read_i64();
+        let end_of_file = src.read_i64();
+        let number_of_links = src.read_u32();
+        let delete_pending = Boolean::from(src.read_u8());
+        let directory = Boolean::from(src.read_u8());
+        Ok(Self {
+            allocation_size,
+            end_of_file,
+            number_of_links,
+            delete_pending,
+            directory,
+        })
+    }
+
     fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: Self::size());
         dst.write_i64(self.allocation_size);
@@ -2222,6 +2698,16 @@ pub struct FileAttributeTagInformation {
 }
 
 impl FileAttributeTagInformation {
+    fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_size!(ctx: "FileAttributeTagInformation", in: src, size: Self::size());
+        let file_attributes = FileAttributes::from_bits_retain(src.read_u32());
+        let reparse_tag = src.read_u32();
+        Ok(Self {
+            file_attributes,
+            reparse_tag,
+        })
+    }
+
     fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: Self::size());
         dst.write_u32(self.file_attributes.bits());
@@ -2286,6 +2772,40 @@ impl FileBothDirectoryInformation {
         }
     }
 
+    fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_size!(ctx: "FileBothDirectoryInformation", in: src, size: 93);
+        let next_entry_offset = src.read_u32();
+        let file_index = src.read_u32();
+        let creation_time = src.read_i64();
+        let last_access_time = src.read_i64();
+        let last_write_time = src.read_i64();
+        let change_time = src.read_i64();
+        let end_of_file = src.read_i64();
+        let allocation_size = src.read_i64();
+        let file_attributes = FileAttributes::from_bits_retain(src.read_u32());
+        let file_name_length = cast_length!("FileBothDirectoryInformation", "FileNameLength", src.read_u32())?;
+        let ea_size = src.read_u32();
+        let short_name_length = i8::from_le_bytes([src.read_u8()]);
+        // Mirrors the encoding, without the reserved byte.
+        let short_name = src.read_array();
+        let file_name = decode_file_name("FileBothDirectoryInformation", file_name_length, src)?;
+        Ok(Self {
+            next_entry_offset,
+            file_index,
+            creation_time,
+            last_access_time,
+            last_write_time,
+            change_time,
+            end_of_file,
+            allocation_size,
+            file_attributes,
+            ea_size,
+            short_name_length,
+            short_name,
+            file_name,
+        })
+    }
+
     fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: self.size());
         dst.write_u32(self.next_entry_offset);
@@ -2374,10 +2894,39 @@ impl FileFullDirectoryInformation {
         }
     }
 
-    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
-        ensure_size!(in: dst, size: self.size());
-        dst.write_u32(self.next_entry_offset);
-        dst.write_u32(self.file_index);
+    fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_size!(ctx: "FileFullDirectoryInformation", in: src, size: 68);
+        let next_entry_offset = src.read_u32();
+        let file_index = src.read_u32();
+        let creation_time = src.read_i64();
+        let last_access_time = src.read_i64();
+        let last_write_time = src.read_i64();
+        let change_time = src.read_i64();
+        let end_of_file = src.read_i64();
+        let allocation_size = src.read_i64();
+        let file_attributes = FileAttributes::from_bits_retain(src.read_u32());
+        let file_name_length = cast_length!("FileFullDirectoryInformation", "FileNameLength", src.read_u32())?;
+        let ea_size = src.read_u32();
+        let file_name = decode_file_name("FileFullDirectoryInformation", file_name_length, src)?;
+        Ok(Self {
+            next_entry_offset,
+            file_index,
+            creation_time,
+            last_access_time,
+            last_write_time,
+            change_time,
+            end_of_file,
+            allocation_size,
+            file_attributes,
+            ea_size,
+            file_name,
+        })
+    }
+
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(in: dst, size: self.size());
+        dst.write_u32(self.next_entry_offset);
+        dst.write_u32(self.file_index);
         dst.write_i64(self.creation_time);
         dst.write_i64(self.last_access_time);
         dst.write_i64(self.last_write_time);
@@ -2432,6 +2981,19 @@ impl FileNamesInformation {
         }
     }
 
+    fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_size!(ctx: "FileNamesInformation", in: src, size: 12);
+        let next_entry_offset = src.read_u32();
+        let file_index = src.read_u32();
+        let file_name_length = cast_length!("FileNamesInformation", "FileNameLength", src.read_u32())?;
+        let file_name = decode_file_name("FileNamesInformation", file_name_length, src)?;
+        Ok(Self {
+            next_entry_offset,
+            file_index,
+            file_name,
+        })
+    }
+
     fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: self.size());
         dst.write_u32(self.next_entry_offset);
@@ -2496,6 +3058,33 @@ impl FileDirectoryInformation {
         }
     }
 
+    fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_size!(ctx: "FileDirectoryInformation", in: src, size: 64);
+        let next_entry_offset = src.read_u32();
+        let file_index = src.read_u32();
+        let creation_time = src.read_i64();
+        let last_access_time = src.read_i64();
+        let last_write_time = src.read_i64();
+        let change_time = src.read_i64();
+        let end_of_file = src.read_i64();
+        let allocation_size = src.read_i64();
+        let file_attributes = FileAttributes::from_bits_retain(src.read_u32());
+        let file_name_length = cast_length!("FileDirectoryInformation", "FileNameLength", src.read_u32())?;
+        let file_name = decode_file_name("FileDirectoryInformation", file_name_length, src)?;
+        Ok(Self {
+            next_entry_offset,
+            file_index,
+            creation_time,
+            last_access_time,
+            last_write_time,
+            change_time,
+            end_of_file,
+            allocation_size,
+            file_attributes,
+            file_name,
+        })
+    }
+
     fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: self.size());
         dst.write_u32(self.next_entry_offset);
@@ -2531,6 +3120,12 @@ impl FileDirectoryInformation {
     }
 }
 
+/// Decodes the UTF-16 FileName of a directory entry, which is not null-terminated.
+fn decode_file_name(ctx: &'static str, file_name_length: usize, src: &mut ReadCursor<'_>) -> DecodeResult<String> {
+    ensure_size!(ctx: ctx, in: src, size: file_name_length);
+    decode_string(src.read_slice(file_name_length), CharacterSet::Unicode, false)
+}
+
 /// [2.2.1.4.2] Device Close Request (DR_CLOSE_REQ)
 ///
 /// [2.2.1.4.2]: https://docs.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpefs/3ec6627f-9e0f-4941-a828-3fc6ed63d9e7
@@ -2547,6 +3142,17 @@ impl DeviceCloseRequest {
             device_io_request: dev_io_req,
         }
     }
+
+    pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(ctx: "DeviceCloseRequest", in: dst, size: self.size());
+        self.device_io_request.encode(dst)?;
+        write_padding!(dst, 32);
+        Ok(())
+    }
+
+    pub fn size(&self) -> usize {
+        self.device_io_request.size() + 32 // Padding
+    }
 }
 
 /// [2.2.1.5.2] Device Close Response (DR_CLOSE_RSP)
@@ -2572,6 +3178,11 @@ impl DeviceCloseResponse {
         Ok(())
     }
 
+    /// The padding is ignored, and may be omitted.
+    pub fn decode(device_io_response: DeviceIoResponse) -> Self {
+        Self { device_io_response }
+    }
+
     pub fn size(&self) -> usize {
         self.device_io_response.size() // DeviceIoResponse
         + 4 // Padding
@@ -2626,6 +3237,36 @@ impl ServerDriveQueryDirectoryRequest {
             path,
         })
     }
+
+    pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(ctx: "ServerDriveQueryDirectoryRequest", in: dst, size: self.size());
+        self.device_io_request.encode(dst)?;
+        dst.write_u32(self.file_info_class_lvl.into());
+        dst.write_u8(self.initial_query);
+        dst.write_u32(cast_length!(
+            "ServerDriveQueryDirectoryRequest",
+            "PathLength",
+            self.path_length()
+        )?);
+        write_padding!(dst, 23);
+        if self.path_length() != 0 {
+            write_string_to_cursor(dst, &self.path, CharacterSet::Unicode, true)?;
+        }
+        Ok(())
+    }
+
+    pub fn size(&self) -> usize {
+        self.device_io_request.size() + Self::FIXED_PART_SIZE + self.path_length()
+    }
+
+    /// The path is only sent along with the initial query.
+    fn path_length(&self) -> usize {
+        if self.initial_query == 0 {
+            0
+        } else {
+            encoded_str_len(&self.path, CharacterSet::Unicode, true)
+        }
+    }
 }
 
 /// 2.2.3.3.11 Server Drive NotifyChange Directory Request (DR_DRIVE_NOTIFY_CHANGE_DIRECTORY_REQ)
@@ -2635,7 +3276,7 @@ impl ServerDriveQueryDirectoryRequest {
 pub struct ServerDriveNotifyChangeDirectoryRequest {
     pub device_io_request: DeviceIoRequest,
     pub watch_tree: u8,
-    pub completion_filter: u32,
+    pub completion_filter: CompletionFilter,
 }
 
 impl ServerDriveNotifyChangeDirectoryRequest {
@@ -2644,7 +3285,7 @@ impl ServerDriveNotifyChangeDirectoryRequest {
     fn decode(device_io_request: DeviceIoRequest, src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
         ensure_fixed_part_size!(in: src);
         let watch_tree = src.read_u8();
-        let completion_filter = src.read_u32();
+        let completion_filter = CompletionFilter::from_bits_retain(src.read_u32());
         // Padding (27 bytes): An array of 27 bytes. This field is unused and MUST be ignored.
         read_padding!(src, 27);
 
@@ -2656,6 +3297,229 @@ impl ServerDriveNotifyChangeDirectoryRequest {
     }
 }
 
+bitflags! {
+    /// The changes to watch, as specified by the CompletionFilter field of [2.2.35] SMB2 CHANGE_NOTIFY Request \[MS-SMB2\]
+    ///
+    /// [2.2.35]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-smb2/
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub struct CompletionFilter: u32 {
+        const FILE_NOTIFY_CHANGE_FILE_NAME = 0x00000001;
+        const FILE_NOTIFY_CHANGE_DIR_NAME = 0x00000002;
+        const FILE_NOTIFY_CHANGE_ATTRIBUTES = 0x00000004;
+        const FILE_NOTIFY_CHANGE_SIZE = 0x00000008;
+        const FILE_NOTIFY_CHANGE_LAST_WRITE = 0x00000010;
+        const FILE_NOTIFY_CHANGE_LAST_ACCESS = 0x00000020;
+        const FILE_NOTIFY_CHANGE_CREATION = 0x00000040;
+        const FILE_NOTIFY_CHANGE_EA = 0x00000080;
+        const FILE_NOTIFY_CHANGE_SECURITY = 0x00000100;
+        const FILE_NOTIFY_CHANGE_STREAM_NAME = 0x00000200;
+        const FILE_NOTIFY_CHANGE_STREAM_SIZE = 0x00000400;
+        const FILE_NOTIFY_CHANGE_STREAM_WRITE = 0x00000800;
+
+        const _ = !0;
+    }
+}
+
+/// The Action field of [2.7.1] FILE_NOTIFY_INFORMATION \[MS-FSCC\]
+///
+/// [2.7.1]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-fscc/
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct FileAction(u32);
+
+impl FileAction {
+    /// FILE_ACTION_ADDED
+    pub const ADDED: Self = Self(0x0000_0001);
+    /// FILE_ACTION_REMOVED
+    pub const REMOVED: Self = Self(0x0000_0002);
+    /// FILE_ACTION_MODIFIED
+    pub const MODIFIED: Self = Self(0x0000_0003);
+    /// FILE_ACTION_RENAMED_OLD_NAME
+    pub const RENAMED_OLD_NAME: Self = Self(0x0000_0004);
+    /// FILE_ACTION_RENAMED_NEW_NAME
+    pub const RENAMED_NEW_NAME: Self = Self(0x0000_0005);
+}
+
+impl Debug for FileAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            FileAction::ADDED => write!(f, "FILE_ACTION_ADDED"),
+            FileAction::REMOVED => write!(f, "FILE_ACTION_REMOVED"),
+            FileAction::MODIFIED => write!(f, "FILE_ACTION_MODIFIED"),
+            FileAction::RENAMED_OLD_NAME => write!(f, "FILE_ACTION_RENAMED_OLD_NAME"),
+            FileAction::RENAMED_NEW_NAME => write!(f, "FILE_ACTION_RENAMED_NEW_NAME"),
+            _ => write!(f, "FileAction({:#010X})", self.0),
+        }
+    }
+}
+
+impl From<u32> for FileAction {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<FileAction> for u32 {
+    fn from(action: FileAction) -> Self {
+        action.0
+    }
+}
+
+/// [2.7.1] FILE_NOTIFY_INFORMATION \[MS-FSCC\]
+///
+/// A change to a file in the watched directory, whose name is relative to that directory.
+///
+/// [2.7.1]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-fscc/
+#[derive(Debug, PartialEq, Clone)]
+pub struct FileNotifyInformation {
+    pub action: FileAction,
+    pub file_name: String,
+}
+
+impl FileNotifyInformation {
+    const FIXED_PART_SIZE: usize = 4 /* NextEntryOffset */ + 4 /* Action */ + 4 /* FileNameLength */;
+
+    pub fn new(action: FileAction, file_name: String) -> Self {
+        Self { action, file_name }
+    }
+
+    /// Encodes a list of changes, each entry being aligned on a 4-byte boundary.
+    pub fn encode_list(list: &[Self], dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(ctx: "FileNotifyInformation", in: dst, size: Self::list_size(list));
+        for (i, info) in list.iter().enumerate() {
+            let is_last = i + 1 == list.len();
+            let next_entry_offset = if is_last { 0 } else { info.aligned_size() };
+            dst.write_u32(cast_length!(
+                "FileNotifyInformation::encode",
+                "next_entry_offset",
+                next_entry_offset
+            )?);
+            dst.write_u32(info.action.into());
+            dst.write_u32(cast_length!(
+                "FileNotifyInformation::encode",
+                "file_name_length",
+                encoded_str_len(&info.file_name, CharacterSet::Unicode, false)
+            )?);
+            write_string_to_cursor(dst, &info.file_name, CharacterSet::Unicode, false)?;
+            if !is_last {
+                write_padding!(dst, info.aligned_size() - info.size());
+            }
+        }
+        Ok(())
+    }
+
+    /// Decodes a list of changes, as encoded by [`Self::encode_list`].
+    pub fn decode_list(src: &mut ReadCursor<'_>) -> DecodeResult<Vec<Self>> {
+        let mut list = Vec::new();
+        loop {
+            ensure_size!(ctx: "FileNotifyInformation", in: src, size: Self::FIXED_PART_SIZE);
+            let next_entry_offset: usize =
+                cast_length!("FileNotifyInformation::decode", "next_entry_offset", src.read_u32())?;
+            let action = FileAction::from(src.read_u32());
+            let file_name_length: usize =
+                cast_length!("FileNotifyInformation::decode", "file_name_length", src.read_u32())?;
+            ensure_size!(ctx: "FileNotifyInformation", in: src, size: file_name_length);
+            let file_name = from_utf16_bytes(src.read_slice(file_name_length));
+            list.push(Self { action, file_name });
+
+            if next_entry_offset == 0 {
+                return Ok(list);
+            }
+            let padding = next_entry_offset
+                .checked_sub(Self::FIXED_PART_SIZE + file_name_length)
+                .ok_or_else(|| {
+                    invalid_field_err!(
+                        "FileNotifyInformation::decode",
+                        "NextEntryOffset",
+                        "overlaps the current entry"
+                    )
+                })?;
+            ensure_size!(ctx: "FileNotifyInformation", in: src, size: padding);
+            src.advance(padding);
+        }
+    }
+
+    /// Returns the encoded size of a list of changes.
+    pub fn list_size(list: &[Self]) -> usize {
+        match list.split_last() {
+            Some((last, others)) => others.iter().map(Self::aligned_size).sum::<usize>() + last.size(),
+            None => 0,
+        }
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE + encoded_str_len(&self.file_name, CharacterSet::Unicode, false)
+    }
+
+    fn aligned_size(&self) -> usize {
+        self.size().next_multiple_of(4)
+    }
+}
+
+/// [2.2.3.4.11] Client Drive NotifyChange Directory Response (DR_DRIVE_NOTIFY_CHANGE_DIRECTORY_RSP)
+///
+/// [2.2.3.4.11]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpefs/
+#[derive(Debug, PartialEq, Clone)]
+pub struct ClientDriveNotifyChangeDirectoryResponse {
+    pub device_io_reply: DeviceIoResponse,
+    pub buffer: Vec<FileNotifyInformation>,
+}
+
+impl ClientDriveNotifyChangeDirectoryResponse {
+    const NAME: &'static str = "DR_DRIVE_NOTIFY_CHANGE_DIRECTORY_RSP";
+
+    pub fn new(req: &ServerDriveNotifyChangeDirectoryRequest, io_status: NtStatus) -> Self {
+        Self {
+            device_io_reply: DeviceIoResponse::new(req.device_io_request.clone(), io_status),
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(in: dst, size: self.size());
+        self.device_io_reply.encode(dst)?;
+        dst.write_u32(cast_length!(
+            "ClientDriveNotifyChangeDirectoryResponse",
+            "length",
+            FileNotifyInformation::list_size(&self.buffer)
+        )?);
+        if self.buffer.is_empty() {
+            write_padding!(dst, 1);
+        } else {
+            FileNotifyInformation::encode_list(&self.buffer, dst)?;
+        }
+        Ok(())
+    }
+
+    pub fn decode(device_io_reply: DeviceIoResponse, src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_size!(ctx: "ClientDriveNotifyChangeDirectoryResponse", in: src, size: 4);
+        let length: usize = cast_length!("ClientDriveNotifyChangeDirectoryResponse", "length", src.read_u32())?;
+        let buffer = if length == 0 {
+            Vec::new()
+        } else {
+            ensure_size!(ctx: "ClientDriveNotifyChangeDirectoryResponse", in: src, size: length);
+            FileNotifyInformation::decode_list(&mut ReadCursor::new(src.read_slice(length)))?
+        };
+        Ok(Self {
+            device_io_reply,
+            buffer,
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.device_io_reply.size() // DeviceIoResponse
+        + 4 // Length
+        + if self.buffer.is_empty() {
+            1 // Padding
+        } else {
+            FileNotifyInformation::list_size(&self.buffer) // Buffer
+        }
+    }
+}
+
 /// [2.2.3.4.10] Client Drive Query Directory Response (DR_DRIVE_QUERY_DIRECTORY_RSP)
 ///
 /// [2.2.3.4.10]: https://docs.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpefs/9c929407-a833-4893-8f20-90c984756140
@@ -2692,6 +3556,19 @@ impl ClientDriveQueryDirectoryResponse {
         Ok(())
     }
 
+    /// Decodes the response to a query for the `file_info_class_lvl` information class.
+    pub fn decode(
+        device_io_reply: DeviceIoResponse,
+        file_info_class_lvl: FileInformationClassLevel,
+        src: &mut ReadCursor<'_>,
+    ) -> DecodeResult<Self> {
+        let buffer = decode_information_buffer(Self::NAME, file_info_class_lvl, src)?;
+        Ok(Self {
+            device_io_reply,
+            buffer,
+        })
+    }
+
     pub fn size(&self) -> usize {
         self.device_io_reply.size() // DeviceIoResponse
         + 4 // Length
@@ -3145,11 +4022,25 @@ impl DeviceReadRequest {
             offset,
         })
     }
+
+    pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(ctx: "DeviceReadRequest", in: dst, size: self.size());
+        self.device_io_request.encode(dst)?;
+        dst.write_u32(self.length);
+        dst.write_u64(self.offset);
+        write_padding!(dst, 20);
+        Ok(())
+    }
+
+    pub fn size(&self) -> usize {
+        self.device_io_request.size() + Self::FIXED_PART_SIZE
+    }
 }
 
 /// [2.2.1.5.3] Device Read Response (DR_READ_RSP)
 ///
 /// [2.2.1.5.3]: https://docs.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpefs/d35d3f91-fc5b-492b-80be-47f483ad1dc9
+#[derive(PartialEq, Clone)]
 pub struct DeviceReadResponse {
     pub device_io_reply: DeviceIoResponse,
     pub read_data: Vec<u8>,
@@ -3166,6 +4057,19 @@ impl DeviceReadResponse {
         Ok(())
     }
 
+    pub fn decode(device_io_reply: DeviceIoResponse, src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_size!(ctx: Self::NAME, in: src, size: 4);
+        let length = cast_length!("DeviceReadResponse", "length", src.read_u32())?;
+
+        ensure_size!(ctx: Self::NAME, in: src, size: length);
+        let read_data = src.read_slice(length).to_vec();
+
+        Ok(Self {
+            device_io_reply,
+            read_data,
+        })
+    }
+
     pub fn name(&self) -> &'static str {
         Self::NAME
     }
@@ -3215,6 +4119,20 @@ impl DeviceWriteRequest {
             write_data,
         })
     }
+
+    pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(ctx: "DeviceWriteRequest", in: dst, size: self.size());
+        self.device_io_request.encode(dst)?;
+        dst.write_u32(cast_length!("DeviceWriteRequest", "Length", self.write_data.len())?);
+        dst.write_u64(self.offset);
+        write_padding!(dst, 20);
+        dst.write_slice(&self.write_data);
+        Ok(())
+    }
+
+    pub fn size(&self) -> usize {
+        self.device_io_request.size() + Self::FIXED_PART_SIZE + self.write_data.len()
+    }
 }
 
 impl Debug for DeviceWriteRequest {
@@ -3251,6 +4169,17 @@ impl DeviceWriteResponse {
         Ok(())
     }
 
+    /// The padding is ignored, and may be omitted.
+    pub fn decode(device_io_reply: DeviceIoResponse, src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_size!(ctx: Self::NAME, in: src, size: 4);
+        let length = src.read_u32();
+
+        Ok(Self {
+            device_io_reply,
+            length,
+        })
+    }
+
     pub fn size(&self) -> usize {
         self.device_io_reply.size() // DeviceIoResponse
         + 4 // Length
@@ -3412,7 +4341,7 @@ impl FileAllocationInformation {
 /// [2.2.3.4.9]: https://docs.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpefs/16b893d5-5d8b-49d1-8dcb-ee21e7612970
 #[derive(Debug, PartialEq, Clone)]
 pub struct ClientDriveSetInformationResponse {
-    device_io_reply: DeviceIoResponse,
+    pub(crate) device_io_reply: DeviceIoResponse,
     /// This field MUST be equal to the Length field in the Server Drive Set Information Request (section 2.2.3.3.9).
     length: u32,
 }
@@ -3444,22 +4373,131 @@ impl ClientDriveSetInformationResponse {
     }
 }
 
-/// 2.2.3.3.12 Server Drive Lock Control Request (DR_DRIVE_LOCK_REQ)
+/// [2.2.3.3.12] Server Drive Lock Control Request (DR_DRIVE_LOCK_REQ)
 ///
 /// [2.2.3.3.12]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpefs/a96fe85c-620c-40ce-8858-a6bc38609b0a
 #[derive(Debug, PartialEq, Clone)]
 pub struct ServerDriveLockControlRequest {
     pub device_io_request: DeviceIoRequest,
+    pub operation: LockOperation,
+    /// Whether the client must wait for the lock to be granted, instead of failing right away.
+    pub wait: bool,
+    pub locks: Vec<RdpLockInfo>,
 }
 
 impl ServerDriveLockControlRequest {
+    const FIXED_PART_SIZE: usize = 4 /* Operation */ + 4 /* F + Padding */ + 4 /* NumLocks */ + 20 /* Padding2 */;
+
     fn decode(dev_io_req: DeviceIoRequest, src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
-        // It's not quite clear why this is done this way, but it's what FreeRDP does:
-        // https://github.com/FreeRDP/FreeRDP/blob/dfa231c0a55b005af775b833f92f6bcd30363d77/channels/drive/client/drive_main.c#L600
-        ensure_size!(in: src, size: 4);
-        let _ = src.read_u32();
+        ensure_fixed_part_size!(in: src);
+        let operation = LockOperation::from(src.read_u32());
+        let wait = src.read_u32() & 0x1 != 0;
+        let num_locks = cast_length!("ServerDriveLockControlRequest", "num_locks", src.read_u32())?;
+        read_padding!(src, 20); // Padding2
+
+        ensure_size!(in: src, size: num_locks * RdpLockInfo::FIXED_PART_SIZE);
+        let locks = (0..num_locks)
+            .map(|_| RdpLockInfo::decode(src))
+            .collect::<DecodeResult<_>>()?;
+
         Ok(Self {
             device_io_request: dev_io_req,
+            operation,
+            wait,
+            locks,
         })
     }
 }
+
+/// Operation of a [`ServerDriveLockControlRequest`].
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct LockOperation(u32);
+
+impl LockOperation {
+    /// RDP_LOWIO_OP_SHAREDLOCK
+    pub const SHARED_LOCK: Self = Self(0x0000_0002);
+    /// RDP_LOWIO_OP_EXCLUSIVELOCK
+    pub const EXCLUSIVE_LOCK: Self = Self(0x0000_0003);
+    /// RDP_LOWIO_OP_UNLOCK
+    pub const UNLOCK: Self = Self(0x0000_0004);
+    /// RDP_LOWIO_OP_UNLOCK_MULTIPLE
+    pub const UNLOCK_MULTIPLE: Self = Self(0x0000_0005);
+}
+
+impl Debug for LockOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            LockOperation::SHARED_LOCK => write!(f, "RDP_LOWIO_OP_SHAREDLOCK"),
+            LockOperation::EXCLUSIVE_LOCK => write!(f, "RDP_LOWIO_OP_EXCLUSIVELOCK"),
+            LockOperation::UNLOCK => write!(f, "RDP_LOWIO_OP_UNLOCK"),
+            LockOperation::UNLOCK_MULTIPLE => write!(f, "RDP_LOWIO_OP_UNLOCK_MULTIPLE"),
+            _ => write!(f, "LockOperation({:#010X})", self.0),
+        }
+    }
+}
+
+impl From<u32> for LockOperation {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<LockOperation> for u32 {
+    fn from(operation: LockOperation) -> Self {
+        operation.0
+    }
+}
+
+/// [2.2.1.6] RDP_LOCK_INFO
+///
+/// [2.2.1.6]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpefs/
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RdpLockInfo {
+    pub length: u64,
+    pub offset: u64,
+}
+
+impl RdpLockInfo {
+    const FIXED_PART_SIZE: usize = 8 /* Length */ + 8 /* Offset */;
+
+    fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+        let length = src.read_u64();
+        let offset = src.read_u64();
+        Ok(Self { length, offset })
+    }
+}
+
+/// [2.2.3.4.12] Client Drive Lock Control Response (DR_DRIVE_LOCK_RSP)
+///
+/// [2.2.3.4.12]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpefs/
+#[derive(Debug, PartialEq, Clone)]
+pub struct ClientDriveLockControlResponse {
+    pub device_io_reply: DeviceIoResponse,
+}
+
+impl ClientDriveLockControlResponse {
+    const NAME: &'static str = "DR_DRIVE_LOCK_RSP";
+
+    pub fn new(req: &ServerDriveLockControlRequest, io_status: NtStatus) -> Self {
+        Self {
+            device_io_reply: DeviceIoResponse::new(req.device_io_request.clone(), io_status),
+        }
+    }
+
+    pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(in: dst, size: self.size());
+        self.device_io_reply.encode(dst)?;
+        write_padding!(dst, 5); // Padding
+        Ok(())
+    }
+
+    pub fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    pub fn size(&self) -> usize {
+        self.device_io_reply.size() // DeviceIoResponse
+        + 5 // Padding
+    }
+}
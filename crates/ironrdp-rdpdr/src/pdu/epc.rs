@@ -0,0 +1,470 @@
+//! PDUs for [\[MS-RDPEPC\]: Remote Desktop Protocol: Print Virtual Channel Extension]
+//!
+//! [\[MS-RDPEPC\]: Remote Desktop Protocol: Print Virtual Channel Extension]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpepc/
+
+use std::fmt::{self, Debug};
+use std::mem::size_of;
+
+use bitflags::bitflags;
+use ironrdp_core::{
+    cast_length, ensure_fixed_part_size, ensure_size, invalid_field_err, unsupported_value_err, DecodeResult,
+    EncodeResult, ReadCursor, WriteCursor,
+};
+use ironrdp_pdu::utils::{encoded_str_len, from_utf16_bytes, write_string_to_cursor, CharacterSet};
+
+use super::efs::{
+    AnyIoCtlCode, DeviceCloseRequest, DeviceControlRequest, DeviceCreateRequest, DeviceIoRequest, DeviceReadRequest,
+    DeviceWriteRequest, MajorFunction,
+};
+
+bitflags! {
+    /// Flags of the [`PrinterDeviceAnnounce`].
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub struct PrinterAnnounceFlags: u32 {
+        /// RDPDR_PRINTER_ANNOUNCE_FLAG_ASCII
+        const ASCII = 0x0000_0001;
+        /// RDPDR_PRINTER_ANNOUNCE_FLAG_DEFAULTPRINTER
+        const DEFAULT_PRINTER = 0x0000_0002;
+        /// RDPDR_PRINTER_ANNOUNCE_FLAG_NETWORKPRINTER
+        const NETWORK_PRINTER = 0x0000_0004;
+        /// RDPDR_PRINTER_ANNOUNCE_FLAG_TSPRINTER
+        const TS_PRINTER = 0x0000_0008;
+        /// RDPDR_PRINTER_ANNOUNCE_FLAG_XPSFORMAT
+        const XPS_FORMAT = 0x0000_0010;
+    }
+}
+
+/// Printer Device Announce (DR_PRN_DEVICE_ANNOUNCE), the `DeviceData` of a redirected printer.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PrinterDeviceAnnounce {
+    pub flags: PrinterAnnounceFlags,
+    /// Plug and Play identifier of the printer, may be empty.
+    pub pnp_name: String,
+    /// Name of the driver the server should use for the printer, e.g. "MS Publisher Imagesetter".
+    pub driver_name: String,
+    /// Name of the printer, as displayed to the user.
+    pub printer_name: String,
+    /// Opaque configuration data previously sent by the server, see [`PrinterCacheData::Update`].
+    pub cached_config: Vec<u8>,
+}
+
+impl PrinterDeviceAnnounce {
+    const NAME: &'static str = "DR_PRN_DEVICE_ANNOUNCE";
+    const FIXED_PART_SIZE: usize = size_of::<u32>() * 6; // Flags, CodePage, PnPNameLen, DriverNameLen, PrintNameLen, CachedFieldsLen
+
+    pub fn new(driver_name: String, printer_name: String) -> Self {
+        Self {
+            flags: PrinterAnnounceFlags::empty(),
+            pnp_name: String::new(),
+            driver_name,
+            printer_name,
+            cached_config: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(in: dst, size: self.size());
+        dst.write_u32(self.flags.bits());
+        dst.write_u32(0); // CodePage
+        dst.write_u32(cast_length!("PnPNameLen", unicode_len(&self.pnp_name))?);
+        dst.write_u32(cast_length!("DriverNameLen", unicode_len(&self.driver_name))?);
+        dst.write_u32(cast_length!("PrintNameLen", unicode_len(&self.printer_name))?);
+        dst.write_u32(cast_length!("CachedFieldsLen", self.cached_config.len())?);
+        write_unicode(dst, &self.pnp_name)?;
+        write_unicode(dst, &self.driver_name)?;
+        write_unicode(dst, &self.printer_name)?;
+        dst.write_slice(&self.cached_config);
+        Ok(())
+    }
+
+    pub fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+        let flags = PrinterAnnounceFlags::from_bits_retain(src.read_u32());
+        let _code_page = src.read_u32();
+        let pnp_name_len = cast_length!("PnPNameLen", src.read_u32())?;
+        let driver_name_len = cast_length!("DriverNameLen", src.read_u32())?;
+        let printer_name_len = cast_length!("PrintNameLen", src.read_u32())?;
+        let cached_fields_len = cast_length!("CachedFieldsLen", src.read_u32())?;
+
+        let pnp_name = read_unicode(src, pnp_name_len)?;
+        let driver_name = read_unicode(src, driver_name_len)?;
+        let printer_name = read_unicode(src, printer_name_len)?;
+        ensure_size!(in: src, size: cached_fields_len);
+        let cached_config = src.read_slice(cached_fields_len).to_vec();
+
+        Ok(Self {
+            flags,
+            pnp_name,
+            driver_name,
+            printer_name,
+            cached_config,
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE
+            + unicode_len(&self.pnp_name)
+            + unicode_len(&self.driver_name)
+            + unicode_len(&self.printer_name)
+            + self.cached_config.len()
+    }
+}
+
+/// I/O requests the server sends to a redirected printer.
+///
+/// A print job is made of a Create request, followed by Write requests carrying the spooled document,
+/// and ends with a Close request.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ServerPrinterIoRequest {
+    Create(DeviceCreateRequest),
+    Read(DeviceReadRequest),
+    Write(DeviceWriteRequest),
+    Close(DeviceCloseRequest),
+    DeviceControl(DeviceControlRequest<AnyIoCtlCode>),
+}
+
+impl ServerPrinterIoRequest {
+    pub fn decode(dev_io_req: DeviceIoRequest, src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        match dev_io_req.major_function {
+            MajorFunction::Create => Ok(Self::Create(DeviceCreateRequest::decode(dev_io_req, src)?)),
+            MajorFunction::Read => Ok(Self::Read(DeviceReadRequest::decode(dev_io_req, src)?)),
+            MajorFunction::Write => Ok(Self::Write(DeviceWriteRequest::decode(dev_io_req, src)?)),
+            MajorFunction::Close => Ok(Self::Close(DeviceCloseRequest::decode(dev_io_req))),
+            MajorFunction::DeviceControl => Ok(Self::DeviceControl(DeviceControlRequest::<AnyIoCtlCode>::decode(
+                dev_io_req, src,
+            )?)),
+            major_function => Err(unsupported_value_err!(
+                "ServerPrinterIoRequest::decode",
+                "MajorFunction",
+                format!("{major_function:?}")
+            )),
+        }
+    }
+
+    pub fn device_io_request(&self) -> &DeviceIoRequest {
+        match self {
+            Self::Create(req) => &req.device_io_request,
+            Self::Read(req) => &req.device_io_request,
+            Self::Write(req) => &req.device_io_request,
+            Self::Close(req) => &req.device_io_request,
+            Self::DeviceControl(req) => &req.header,
+        }
+    }
+}
+
+/// Add Printer Cachedata (DR_PRN_ADD_CACHEDATA), Update Printer Cachedata (DR_PRN_UPDATE_CACHEDATA),
+/// Delete Printer Cachedata (DR_PRN_DELETE_CACHEDATA) and Rename Printer Cachedata (DR_PRN_RENAME_CACHEDATA)
+///
+/// The server sends these PDUs so that the client stores the user configuration of its printers,
+/// and announces it back in [`PrinterDeviceAnnounce::cached_config`] in the following sessions.
+#[derive(Debug, PartialEq, Clone)]
+pub enum PrinterCacheData {
+    Add(AddPrinterCacheData),
+    Update(UpdatePrinterCacheData),
+    Delete(DeletePrinterCacheData),
+    Rename(RenamePrinterCacheData),
+}
+
+impl PrinterCacheData {
+    const NAME: &'static str = "DR_PRN_CACHE_DATA";
+    const FIXED_PART_SIZE: usize = size_of::<u32>(); // EventId
+
+    /// RDPDR_ADD_PRINTER_EVENT
+    const ADD_PRINTER_EVENT: u32 = 0x0000_0001;
+    /// RDPDR_UPDATE_PRINTER_EVENT
+    const UPDATE_PRINTER_EVENT: u32 = 0x0000_0002;
+    /// RDPDR_DELETE_PRINTER_EVENT
+    const DELETE_PRINTER_EVENT: u32 = 0x0000_0003;
+    /// RDPDR_RENAME_PRINTER_EVENT
+    const RENAME_PRINTER_EVENT: u32 = 0x0000_0004;
+
+    pub fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(in: dst, size: self.size());
+        match self {
+            Self::Add(data) => {
+                dst.write_u32(Self::ADD_PRINTER_EVENT);
+                data.encode(dst)
+            }
+            Self::Update(data) => {
+                dst.write_u32(Self::UPDATE_PRINTER_EVENT);
+                data.encode(dst)
+            }
+            Self::Delete(data) => {
+                dst.write_u32(Self::DELETE_PRINTER_EVENT);
+                data.encode(dst)
+            }
+            Self::Rename(data) => {
+                dst.write_u32(Self::RENAME_PRINTER_EVENT);
+                data.encode(dst)
+            }
+        }
+    }
+
+    pub fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+        match src.read_u32() {
+            Self::ADD_PRINTER_EVENT => Ok(Self::Add(AddPrinterCacheData::decode(src)?)),
+            Self::UPDATE_PRINTER_EVENT => Ok(Self::Update(UpdatePrinterCacheData::decode(src)?)),
+            Self::DELETE_PRINTER_EVENT => Ok(Self::Delete(DeletePrinterCacheData::decode(src)?)),
+            Self::RENAME_PRINTER_EVENT => Ok(Self::Rename(RenamePrinterCacheData::decode(src)?)),
+            _ => Err(invalid_field_err!("EventId", "invalid printer cache event")),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE
+            + match self {
+                Self::Add(data) => data.size(),
+                Self::Update(data) => data.size(),
+                Self::Delete(data) => data.size(),
+                Self::Rename(data) => data.size(),
+            }
+    }
+}
+
+/// See [`PrinterCacheData::Add`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct AddPrinterCacheData {
+    /// Name of the port the printer is attached to, at most 7 ASCII characters.
+    pub port_dos_name: String,
+    pub pnp_name: String,
+    pub driver_name: String,
+    pub printer_name: String,
+    pub cached_config: Vec<u8>,
+}
+
+impl AddPrinterCacheData {
+    const FIXED_PART_SIZE: usize = 8 /* PortDosName */ + size_of::<u32>() * 4; // PnPNameLen, DriverNameLen, PrinterNameLen, CachedFieldsLen
+
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        let mut port_dos_name = [0u8; 8];
+        for (dst, src) in port_dos_name.iter_mut().take(7).zip(self.port_dos_name.bytes()) {
+            *dst = src;
+        }
+        dst.write_array(port_dos_name);
+        dst.write_u32(cast_length!("PnPNameLen", unicode_len(&self.pnp_name))?);
+        dst.write_u32(cast_length!("DriverNameLen", unicode_len(&self.driver_name))?);
+        dst.write_u32(cast_length!("PrinterNameLen", unicode_len(&self.printer_name))?);
+        dst.write_u32(cast_length!("CachedFieldsLen", self.cached_config.len())?);
+        write_unicode(dst, &self.pnp_name)?;
+        write_unicode(dst, &self.driver_name)?;
+        write_unicode(dst, &self.printer_name)?;
+        dst.write_slice(&self.cached_config);
+        Ok(())
+    }
+
+    fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+        let port_dos_name = src.read_array::<8>();
+        let port_dos_name = port_dos_name
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| char::from(c))
+            .collect();
+        let pnp_name_len = cast_length!("PnPNameLen", src.read_u32())?;
+        let driver_name_len = cast_length!("DriverNameLen", src.read_u32())?;
+        let printer_name_len = cast_length!("PrinterNameLen", src.read_u32())?;
+        let cached_fields_len = cast_length!("CachedFieldsLen", src.read_u32())?;
+
+        let pnp_name = read_unicode(src, pnp_name_len)?;
+        let driver_name = read_unicode(src, driver_name_len)?;
+        let printer_name = read_unicode(src, printer_name_len)?;
+        ensure_size!(in: src, size: cached_fields_len);
+        let cached_config = src.read_slice(cached_fields_len).to_vec();
+
+        Ok(Self {
+            port_dos_name,
+            pnp_name,
+            driver_name,
+            printer_name,
+            cached_config,
+        })
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE
+            + unicode_len(&self.pnp_name)
+            + unicode_len(&self.driver_name)
+            + unicode_len(&self.printer_name)
+            + self.cached_config.len()
+    }
+}
+
+/// See [`PrinterCacheData::Update`].
+#[derive(PartialEq, Clone)]
+pub struct UpdatePrinterCacheData {
+    pub printer_name: String,
+    pub config_data: Vec<u8>,
+}
+
+impl UpdatePrinterCacheData {
+    const FIXED_PART_SIZE: usize = size_of::<u32>() * 2; // PrinterNameLen, ConfigDataLen
+
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        dst.write_u32(cast_length!("PrinterNameLen", unicode_len(&self.printer_name))?);
+        dst.write_u32(cast_length!("ConfigDataLen", self.config_data.len())?);
+        write_unicode(dst, &self.printer_name)?;
+        dst.write_slice(&self.config_data);
+        Ok(())
+    }
+
+    fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+        let printer_name_len = cast_length!("PrinterNameLen", src.read_u32())?;
+        let config_data_len = cast_length!("ConfigDataLen", src.read_u32())?;
+
+        let printer_name = read_unicode(src, printer_name_len)?;
+        ensure_size!(in: src, size: config_data_len);
+        let config_data = src.read_slice(config_data_len).to_vec();
+
+        Ok(Self {
+            printer_name,
+            config_data,
+        })
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE + unicode_len(&self.printer_name) + self.config_data.len()
+    }
+}
+
+impl Debug for UpdatePrinterCacheData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UpdatePrinterCacheData")
+            .field("printer_name", &self.printer_name)
+            .field("config_data", &format!("Vec<u8> of length {}", self.config_data.len()))
+            .finish()
+    }
+}
+
+/// See [`PrinterCacheData::Delete`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct DeletePrinterCacheData {
+    pub printer_name: String,
+}
+
+impl DeletePrinterCacheData {
+    const FIXED_PART_SIZE: usize = size_of::<u32>(); // PrinterNameLen
+
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        dst.write_u32(cast_length!("PrinterNameLen", unicode_len(&self.printer_name))?);
+        write_unicode(dst, &self.printer_name)
+    }
+
+    fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+        let printer_name_len = cast_length!("PrinterNameLen", src.read_u32())?;
+        let printer_name = read_unicode(src, printer_name_len)?;
+
+        Ok(Self { printer_name })
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE + unicode_len(&self.printer_name)
+    }
+}
+
+/// See [`PrinterCacheData::Rename`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct RenamePrinterCacheData {
+    pub old_printer_name: String,
+    pub new_printer_name: String,
+}
+
+impl RenamePrinterCacheData {
+    const FIXED_PART_SIZE: usize = size_of::<u32>() * 2; // OldPrinterNameLen, NewPrinterNameLen
+
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        dst.write_u32(cast_length!("OldPrinterNameLen", unicode_len(&self.old_printer_name))?);
+        dst.write_u32(cast_length!("NewPrinterNameLen", unicode_len(&self.new_printer_name))?);
+        write_unicode(dst, &self.old_printer_name)?;
+        write_unicode(dst, &self.new_printer_name)
+    }
+
+    fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+        let old_printer_name_len = cast_length!("OldPrinterNameLen", src.read_u32())?;
+        let new_printer_name_len = cast_length!("NewPrinterNameLen", src.read_u32())?;
+        let old_printer_name = read_unicode(src, old_printer_name_len)?;
+        let new_printer_name = read_unicode(src, new_printer_name_len)?;
+
+        Ok(Self {
+            old_printer_name,
+            new_printer_name,
+        })
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE + unicode_len(&self.old_printer_name) + unicode_len(&self.new_printer_name)
+    }
+}
+
+/// Server Printer Set XPS Mode (DR_PRN_USING_XPS)
+///
+/// Sent by the server when it accepted to print to a printer announced with
+/// [`PrinterAnnounceFlags::XPS_FORMAT`], meaning print jobs will be sent as XPS documents.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PrinterUsingXps {
+    pub printer_id: u32,
+}
+
+impl PrinterUsingXps {
+    const NAME: &'static str = "DR_PRN_USING_XPS";
+    const FIXED_PART_SIZE: usize = size_of::<u32>() * 2; // PrinterId, Flags
+
+    pub fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(in: dst, size: self.size());
+        dst.write_u32(self.printer_id);
+        dst.write_u32(0); // Flags
+        Ok(())
+    }
+
+    pub fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+        let printer_id = src.read_u32();
+        let _flags = src.read_u32();
+
+        Ok(Self { printer_id })
+    }
+
+    pub fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE
+    }
+}
+
+/// Length in bytes of a null-terminated UTF-16 string, or zero for an empty string.
+fn unicode_len(value: &str) -> usize {
+    if value.is_empty() {
+        0
+    } else {
+        encoded_str_len(value, CharacterSet::Unicode, true)
+    }
+}
+
+fn write_unicode(dst: &mut WriteCursor<'_>, value: &str) -> EncodeResult<()> {
+    if value.is_empty() {
+        return Ok(());
+    }
+    write_string_to_cursor(dst, value, CharacterSet::Unicode, true)
+}
+
+fn read_unicode(src: &mut ReadCursor<'_>, len: usize) -> DecodeResult<String> {
+    ensure_size!(in: src, size: len);
+    let mut value = from_utf16_bytes(src.read_slice(len));
+    while value.ends_with('\0') {
+        value.pop();
+    }
+    Ok(value)
+}
@@ -10,14 +10,11 @@ use std::mem::size_of;
 use bitflags::bitflags;
 use ironrdp_core::{
     cast_length, ensure_size, invalid_field_err, other_err, DecodeError, DecodeResult, EncodeResult, ReadCursor,
-    WriteCursor,
-};
-use ironrdp_pdu::utils::{
-    encoded_multistring_len, read_multistring_from_cursor, write_multistring_to_cursor, CharacterSet,
 };
+use ironrdp_pdu::utils::{encoded_multistring_len, CharacterSet};
 
 use super::efs::IoCtlCode;
-use crate::pdu::esc::ndr::{Decode as _, Encode as _};
+use crate::pdu::esc::ndr::{Decode as _, Encode as _, NdrReadCursor, NdrWriteCursor};
 
 /// [2.2.2] TS Server-Generated Structures
 ///
@@ -132,11 +129,11 @@ impl ScardContext {
 }
 
 impl ndr::Encode for ScardContext {
-    fn encode_ptr(&self, index: &mut u32, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+    fn encode_ptr(&self, index: &mut u32, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()> {
         ndr::encode_ptr(Some(Self::VALUE_LENGTH), index, dst)
     }
 
-    fn encode_value(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+    fn encode_value(&self, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: self.size_value());
         dst.write_u32(Self::VALUE_LENGTH);
         dst.write_u32(self.value);
@@ -153,7 +150,7 @@ impl ndr::Encode for ScardContext {
 }
 
 impl ndr::Decode for ScardContext {
-    fn decode_ptr(src: &mut ReadCursor<'_>, index: &mut u32) -> DecodeResult<Self>
+    fn decode_ptr(src: &mut NdrReadCursor<'_, '_>, index: &mut u32) -> DecodeResult<Self>
     where
         Self: Sized,
     {
@@ -171,7 +168,7 @@ impl ndr::Decode for ScardContext {
         Ok(Self { value: 0 })
     }
 
-    fn decode_value(&mut self, src: &mut ReadCursor<'_>, charset: Option<CharacterSet>) -> DecodeResult<()> {
+    fn decode_value(&mut self, src: &mut NdrReadCursor<'_, '_>, charset: Option<CharacterSet>) -> DecodeResult<()> {
         expect_no_charset(charset)?;
         ensure_size!(in: src, size: size_of::<u32>() * 2);
         let length = src.read_u32();
@@ -197,7 +194,7 @@ pub struct ReaderState {
 }
 
 impl ndr::Decode for ReaderState {
-    fn decode_ptr(src: &mut ReadCursor<'_>, index: &mut u32) -> DecodeResult<Self> {
+    fn decode_ptr(src: &mut NdrReadCursor<'_, '_>, index: &mut u32) -> DecodeResult<Self> {
         let _reader_ptr = ndr::decode_ptr(src, index)?;
         let common = ReaderStateCommonCall::decode(src)?;
         Ok(Self {
@@ -206,7 +203,7 @@ impl ndr::Decode for ReaderState {
         })
     }
 
-    fn decode_value(&mut self, src: &mut ReadCursor<'_>, charset: Option<CharacterSet>) -> DecodeResult<()> {
+    fn decode_value(&mut self, src: &mut NdrReadCursor<'_, '_>, charset: Option<CharacterSet>) -> DecodeResult<()> {
         let charset = expect_charset(charset)?;
         self.reader = ndr::read_string_from_cursor(src, charset)?;
         Ok(())
@@ -406,12 +403,12 @@ impl LongReturn {
     const NAME: &'static str = "Long_Return";
 
     pub fn new(return_code: ReturnCode) -> rpce::Pdu<Self> {
-        rpce::Pdu(Self { return_code })
+        rpce::Pdu::new(Self { return_code })
     }
 }
 
 impl rpce::HeaderlessEncode for LongReturn {
-    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+    fn encode(&self, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: self.size());
         dst.write_u32(self.return_code.into());
         Ok(())
@@ -599,7 +596,7 @@ impl EstablishContextCall {
 }
 
 impl rpce::HeaderlessDecode for EstablishContextCall {
-    fn decode(src: &mut ReadCursor<'_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
+    fn decode(src: &mut NdrReadCursor<'_, '_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
         expect_no_charset(charset)?;
         ensure_size!(in: src, size: Self::size());
         let scope = Scope::try_from(src.read_u32())?;
@@ -650,12 +647,12 @@ impl EstablishContextReturn {
     const NAME: &'static str = "EstablishContext_Return";
 
     pub fn new(return_code: ReturnCode, context: ScardContext) -> rpce::Pdu<Self> {
-        rpce::Pdu(Self { return_code, context })
+        rpce::Pdu::new(Self { return_code, context })
     }
 }
 
 impl rpce::HeaderlessEncode for EstablishContextReturn {
-    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+    fn encode(&self, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: self.size());
         dst.write_u32(self.return_code.into());
         let mut index = 0;
@@ -694,7 +691,7 @@ impl ListReadersCall {
 }
 
 impl rpce::HeaderlessDecode for ListReadersCall {
-    fn decode(src: &mut ReadCursor<'_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
+    fn decode(src: &mut NdrReadCursor<'_, '_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
         let charset = expect_charset(charset)?;
         let mut index = 0;
         let mut context = ScardContext::decode_ptr(src, &mut index)?;
@@ -731,7 +728,7 @@ impl rpce::HeaderlessDecode for ListReadersCall {
             ));
         }
 
-        let groups = read_multistring_from_cursor(src, charset)?;
+        let groups = ndr::read_multistring_from_cursor(src, charset)?;
 
         Ok(Self {
             context,
@@ -758,12 +755,12 @@ impl ListReadersReturn {
     const NAME: &'static str = "ListReaders_Return";
 
     pub fn new(return_code: ReturnCode, readers: Vec<String>) -> rpce::Pdu<Self> {
-        rpce::Pdu(Self { return_code, readers })
+        rpce::Pdu::new(Self { return_code, readers })
     }
 }
 
 impl rpce::HeaderlessEncode for ListReadersReturn {
-    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+    fn encode(&self, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: self.size());
         dst.write_u32(self.return_code.into());
         let readers_length: u32 = cast_length!(
@@ -774,7 +771,7 @@ impl rpce::HeaderlessEncode for ListReadersReturn {
         let mut index = 0;
         ndr::encode_ptr(Some(readers_length), &mut index, dst)?;
         dst.write_u32(readers_length);
-        write_multistring_to_cursor(dst, &self.readers, CharacterSet::Unicode)?;
+        ndr::write_multistring_to_cursor(dst, &self.readers, CharacterSet::Unicode)?;
         Ok(())
     }
 
@@ -810,7 +807,7 @@ impl GetStatusChangeCall {
 }
 
 impl rpce::HeaderlessDecode for GetStatusChangeCall {
-    fn decode(src: &mut ReadCursor<'_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
+    fn decode(src: &mut NdrReadCursor<'_, '_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
         let mut index = 0;
         let mut context = ScardContext::decode_ptr(src, &mut index)?;
 
@@ -859,7 +856,7 @@ pub struct ReaderStateCommonCall {
 impl ReaderStateCommonCall {
     const FIXED_PART_SIZE: usize = size_of::<u32>() * 3 /* dwCurrentState, dwEventState, cbAtr */ + 36 /* rgbAtr */;
 
-    fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+    fn decode(src: &mut NdrReadCursor<'_, '_>) -> DecodeResult<Self> {
         ensure_size!(in: src, size: Self::FIXED_PART_SIZE);
         let current_state = CardStateFlags::from_bits_retain(src.read_u32());
         let event_state = CardStateFlags::from_bits_retain(src.read_u32());
@@ -874,7 +871,7 @@ impl ReaderStateCommonCall {
         })
     }
 
-    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+    fn encode(&self, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()> {
         dst.write_u32(self.current_state.bits());
         dst.write_u32(self.event_state.bits());
         dst.write_u32(self.atr_length);
@@ -918,7 +915,7 @@ impl GetStatusChangeReturn {
     const NAME: &'static str = "GetStatusChange_Return";
 
     pub fn new(return_code: ReturnCode, reader_states: Vec<ReaderStateCommonCall>) -> rpce::Pdu<Self> {
-        rpce::Pdu(Self {
+        rpce::Pdu::new(Self {
             return_code,
             reader_states,
         })
@@ -926,7 +923,7 @@ impl GetStatusChangeReturn {
 }
 
 impl rpce::HeaderlessEncode for GetStatusChangeReturn {
-    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+    fn encode(&self, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: self.size());
         dst.write_u32(self.return_code.into());
         let reader_states_len = cast_length!("GetStatusChangeReturn", "reader_states", self.reader_states.len())?;
@@ -967,7 +964,7 @@ impl ConnectCall {
 }
 
 impl rpce::HeaderlessDecode for ConnectCall {
-    fn decode(src: &mut ReadCursor<'_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
+    fn decode(src: &mut NdrReadCursor<'_, '_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
         let charset = expect_charset(charset)?;
         let mut index = 0;
         let _reader_ptr = ndr::decode_ptr(src, &mut index)?;
@@ -989,7 +986,7 @@ pub struct ConnectCommon {
 }
 
 impl ndr::Decode for ConnectCommon {
-    fn decode_ptr(src: &mut ReadCursor<'_>, index: &mut u32) -> DecodeResult<Self>
+    fn decode_ptr(src: &mut NdrReadCursor<'_, '_>, index: &mut u32) -> DecodeResult<Self>
     where
         Self: Sized,
     {
@@ -1004,7 +1001,7 @@ impl ndr::Decode for ConnectCommon {
         })
     }
 
-    fn decode_value(&mut self, src: &mut ReadCursor<'_>, charset: Option<CharacterSet>) -> DecodeResult<()> {
+    fn decode_value(&mut self, src: &mut NdrReadCursor<'_, '_>, charset: Option<CharacterSet>) -> DecodeResult<()> {
         expect_no_charset(charset)?;
         self.context.decode_value(src, None)
     }
@@ -1047,7 +1044,7 @@ impl ScardHandle {
 }
 
 impl ndr::Decode for ScardHandle {
-    fn decode_ptr(src: &mut ReadCursor<'_>, index: &mut u32) -> DecodeResult<Self>
+    fn decode_ptr(src: &mut NdrReadCursor<'_, '_>, index: &mut u32) -> DecodeResult<Self>
     where
         Self: Sized,
     {
@@ -1065,7 +1062,7 @@ impl ndr::Decode for ScardHandle {
         Ok(Self { context, value: 0 })
     }
 
-    fn decode_value(&mut self, src: &mut ReadCursor<'_>, charset: Option<CharacterSet>) -> DecodeResult<()> {
+    fn decode_value(&mut self, src: &mut NdrReadCursor<'_, '_>, charset: Option<CharacterSet>) -> DecodeResult<()> {
         expect_no_charset(charset)?;
         self.context.decode_value(src, None)?;
         ensure_size!(in: src, size: size_of::<u32>());
@@ -1084,13 +1081,13 @@ impl ndr::Decode for ScardHandle {
 }
 
 impl ndr::Encode for ScardHandle {
-    fn encode_ptr(&self, index: &mut u32, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+    fn encode_ptr(&self, index: &mut u32, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()> {
         self.context.encode_ptr(index, dst)?;
         ndr::encode_ptr(Some(Self::VALUE_LENGTH), index, dst)?;
         Ok(())
     }
 
-    fn encode_value(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+    fn encode_value(&self, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: self.size_value());
         self.context.encode_value(dst)?;
         dst.write_u32(Self::VALUE_LENGTH);
@@ -1121,7 +1118,7 @@ impl ConnectReturn {
     const NAME: &'static str = "Connect_Return";
 
     pub fn new(return_code: ReturnCode, handle: ScardHandle, active_protocol: CardProtocol) -> rpce::Pdu<Self> {
-        rpce::Pdu(Self {
+        rpce::Pdu::new(Self {
             return_code,
             handle,
             active_protocol,
@@ -1130,7 +1127,7 @@ impl ConnectReturn {
 }
 
 impl rpce::HeaderlessEncode for ConnectReturn {
-    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+    fn encode(&self, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: self.size());
         dst.write_u32(self.return_code.into());
         let mut index = 0;
@@ -1165,7 +1162,7 @@ impl HCardAndDispositionCall {
 }
 
 impl rpce::HeaderlessDecode for HCardAndDispositionCall {
-    fn decode(src: &mut ReadCursor<'_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
+    fn decode(src: &mut NdrReadCursor<'_, '_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
         expect_no_charset(charset)?;
         let mut index = 0;
         let mut handle = ScardHandle::decode_ptr(src, &mut index)?;
@@ -1197,7 +1194,7 @@ impl TransmitCall {
 }
 
 impl rpce::HeaderlessDecode for TransmitCall {
-    fn decode(src: &mut ReadCursor<'_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
+    fn decode(src: &mut NdrReadCursor<'_, '_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
         expect_no_charset(charset)?;
         let mut index = 0;
         let mut handle = ScardHandle::decode_ptr(src, &mut index)?;
@@ -1250,7 +1247,7 @@ pub struct SCardIORequest {
 }
 
 impl ndr::Decode for SCardIORequest {
-    fn decode_ptr(src: &mut ReadCursor<'_>, index: &mut u32) -> DecodeResult<Self>
+    fn decode_ptr(src: &mut NdrReadCursor<'_, '_>, index: &mut u32) -> DecodeResult<Self>
     where
         Self: Sized,
     {
@@ -1266,7 +1263,7 @@ impl ndr::Decode for SCardIORequest {
         })
     }
 
-    fn decode_value(&mut self, src: &mut ReadCursor<'_>, charset: Option<CharacterSet>) -> DecodeResult<()> {
+    fn decode_value(&mut self, src: &mut NdrReadCursor<'_, '_>, charset: Option<CharacterSet>) -> DecodeResult<()> {
         expect_no_charset(charset)?;
         let extra_bytes_length: usize = cast_length!("TransmitCall", "extra_bytes_length", self.extra_bytes_length)?;
         ensure_size!(in: src, size: extra_bytes_length);
@@ -1276,13 +1273,13 @@ impl ndr::Decode for SCardIORequest {
 }
 
 impl ndr::Encode for SCardIORequest {
-    fn encode_ptr(&self, index: &mut u32, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+    fn encode_ptr(&self, index: &mut u32, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: self.size_ptr());
         dst.write_u32(self.protocol.bits());
         ndr::encode_ptr(Some(self.extra_bytes_length), index, dst)
     }
 
-    fn encode_value(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+    fn encode_value(&self, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: self.size_value());
         dst.write_slice(&self.extra_bytes);
         Ok(())
@@ -1311,7 +1308,7 @@ impl TransmitReturn {
     const NAME: &'static str = "Transmit_Return";
 
     pub fn new(return_code: ReturnCode, recv_pci: Option<SCardIORequest>, recv_buffer: Vec<u8>) -> rpce::Pdu<Self> {
-        rpce::Pdu(Self {
+        rpce::Pdu::new(Self {
             return_code,
             recv_pci,
             recv_buffer,
@@ -1320,7 +1317,7 @@ impl TransmitReturn {
 }
 
 impl rpce::HeaderlessEncode for TransmitReturn {
-    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+    fn encode(&self, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: self.size());
         dst.write_u32(self.return_code.into());
 
@@ -1375,7 +1372,7 @@ impl StatusCall {
 }
 
 impl rpce::HeaderlessDecode for StatusCall {
-    fn decode(src: &mut ReadCursor<'_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
+    fn decode(src: &mut NdrReadCursor<'_, '_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
         expect_no_charset(charset)?;
         let mut index = 0;
         let mut handle = ScardHandle::decode_ptr(src, &mut index)?;
@@ -1420,7 +1417,7 @@ impl StatusReturn {
         atr_length: u32,
         encoding: CharacterSet,
     ) -> rpce::Pdu<Self> {
-        rpce::Pdu(Self {
+        rpce::Pdu::new(Self {
             return_code,
             reader_names,
             state,
@@ -1433,7 +1430,7 @@ impl StatusReturn {
 }
 
 impl rpce::HeaderlessEncode for StatusReturn {
-    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+    fn encode(&self, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: self.size());
         dst.write_u32(self.return_code.into());
         let mut index = 0;
@@ -1448,7 +1445,7 @@ impl rpce::HeaderlessEncode for StatusReturn {
         dst.write_slice(&self.atr);
         dst.write_u32(self.atr_length);
         dst.write_u32(reader_names_length);
-        write_multistring_to_cursor(dst, &self.reader_names, self.encoding)?;
+        ndr::write_multistring_to_cursor(dst, &self.reader_names, self.encoding)?;
         Ok(())
     }
 
@@ -1460,7 +1457,7 @@ impl rpce::HeaderlessEncode for StatusReturn {
         size_of::<u32>() * 5 // dst.write_u32(self.return_code.into()); dst.write_u32(self.state.into()); dst.write_u32(self.protocol.bits()); dst.write_slice(&self.atr); dst.write_u32(self.atr_length);
         + ndr::ptr_size(true) // ndr::encode_ptr(Some(reader_names_length), &mut index, dst)?;
         + self.atr.len() // dst.write_slice(&self.atr);
-        + encoded_multistring_len(&self.reader_names, self.encoding) // write_multistring_to_cursor(dst, &self.reader_names, self.encoding)?;
+        + encoded_multistring_len(&self.reader_names, self.encoding) // ndr::write_multistring_to_cursor(dst, &self.reader_names, self.encoding)?;
     }
 }
 
@@ -1506,7 +1503,7 @@ impl ContextCall {
 }
 
 impl rpce::HeaderlessDecode for ContextCall {
-    fn decode(src: &mut ReadCursor<'_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
+    fn decode(src: &mut NdrReadCursor<'_, '_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
         expect_no_charset(charset)?;
         let mut index = 0;
         let mut context = ScardContext::decode_ptr(src, &mut index)?;
@@ -1532,7 +1529,7 @@ impl GetDeviceTypeIdCall {
 }
 
 impl rpce::HeaderlessDecode for GetDeviceTypeIdCall {
-    fn decode(src: &mut ReadCursor<'_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
+    fn decode(src: &mut NdrReadCursor<'_, '_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
         expect_no_charset(charset)?;
         let mut index = 0;
         let mut context = ScardContext::decode_ptr(src, &mut index)?;
@@ -1560,7 +1557,7 @@ impl GetDeviceTypeIdReturn {
     const NAME: &'static str = "GetDeviceTypeId_Return";
 
     pub fn new(return_code: ReturnCode, device_type_id: u32) -> rpce::Pdu<Self> {
-        rpce::Pdu(Self {
+        rpce::Pdu::new(Self {
             return_code,
             device_type_id,
         })
@@ -1568,7 +1565,7 @@ impl GetDeviceTypeIdReturn {
 }
 
 impl rpce::HeaderlessEncode for GetDeviceTypeIdReturn {
-    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+    fn encode(&self, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: self.size());
         dst.write_u32(self.return_code.into());
         dst.write_u32(self.device_type_id);
@@ -1601,7 +1598,7 @@ impl ReadCacheCall {
 }
 
 impl rpce::HeaderlessDecode for ReadCacheCall {
-    fn decode(src: &mut ReadCursor<'_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
+    fn decode(src: &mut NdrReadCursor<'_, '_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
         let charset = expect_charset(charset)?;
         let mut index = 0;
         let _lookup_name_ptr = ndr::decode_ptr(src, &mut index)?;
@@ -1625,7 +1622,7 @@ pub struct ReadCacheCommon {
 }
 
 impl ndr::Decode for ReadCacheCommon {
-    fn decode_ptr(src: &mut ReadCursor<'_>, index: &mut u32) -> DecodeResult<Self>
+    fn decode_ptr(src: &mut NdrReadCursor<'_, '_>, index: &mut u32) -> DecodeResult<Self>
     where
         Self: Sized,
     {
@@ -1645,7 +1642,7 @@ impl ndr::Decode for ReadCacheCommon {
         })
     }
 
-    fn decode_value(&mut self, src: &mut ReadCursor<'_>, charset: Option<CharacterSet>) -> DecodeResult<()> {
+    fn decode_value(&mut self, src: &mut NdrReadCursor<'_, '_>, charset: Option<CharacterSet>) -> DecodeResult<()> {
         expect_no_charset(charset)?;
         self.context.decode_value(src, None)?;
         ensure_size!(in: src, size: 16);
@@ -1667,12 +1664,12 @@ impl ReadCacheReturn {
     const NAME: &'static str = "ReadCache_Return";
 
     pub fn new(return_code: ReturnCode, data: Vec<u8>) -> rpce::Pdu<Self> {
-        rpce::Pdu(Self { return_code, data })
+        rpce::Pdu::new(Self { return_code, data })
     }
 }
 
 impl rpce::HeaderlessEncode for ReadCacheReturn {
-    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+    fn encode(&self, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: self.size());
         dst.write_u32(self.return_code.into());
         let mut index = 0;
@@ -1711,7 +1708,7 @@ impl WriteCacheCall {
 }
 
 impl rpce::HeaderlessDecode for WriteCacheCall {
-    fn decode(src: &mut ReadCursor<'_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
+    fn decode(src: &mut NdrReadCursor<'_, '_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
         let charset = expect_charset(charset)?;
         let mut index = 0;
         let _lookup_name_ptr = ndr::decode_ptr(src, &mut index)?;
@@ -1734,7 +1731,7 @@ pub struct WriteCacheCommon {
 }
 
 impl ndr::Decode for WriteCacheCommon {
-    fn decode_ptr(src: &mut ReadCursor<'_>, index: &mut u32) -> DecodeResult<Self>
+    fn decode_ptr(src: &mut NdrReadCursor<'_, '_>, index: &mut u32) -> DecodeResult<Self>
     where
         Self: Sized,
     {
@@ -1753,7 +1750,7 @@ impl ndr::Decode for WriteCacheCommon {
         })
     }
 
-    fn decode_value(&mut self, src: &mut ReadCursor<'_>, charset: Option<CharacterSet>) -> DecodeResult<()> {
+    fn decode_value(&mut self, src: &mut NdrReadCursor<'_, '_>, charset: Option<CharacterSet>) -> DecodeResult<()> {
         expect_no_charset(charset)?;
         self.context.decode_value(src, None)?;
         ensure_size!(in: src, size: 16);
@@ -1782,7 +1779,7 @@ impl GetReaderIconCall {
 }
 
 impl rpce::HeaderlessDecode for GetReaderIconCall {
-    fn decode(src: &mut ReadCursor<'_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
+    fn decode(src: &mut NdrReadCursor<'_, '_>, charset: Option<CharacterSet>) -> DecodeResult<Self> {
         expect_no_charset(charset)?;
         let mut index = 0;
         let mut context = ScardContext::decode_ptr(src, &mut index)?;
@@ -1808,12 +1805,12 @@ impl GetReaderIconReturn {
     const NAME: &'static str = "GetReaderIcon_Return";
 
     pub fn new(return_code: ReturnCode, data: Vec<u8>) -> rpce::Pdu<Self> {
-        rpce::Pdu(Self { return_code, data })
+        rpce::Pdu::new(Self { return_code, data })
     }
 }
 
 impl rpce::HeaderlessEncode for GetReaderIconReturn {
-    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+    fn encode(&self, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: self.size());
         dst.write_u32(self.return_code.into());
         let data_len: u32 = cast_length!("GetReaderIconReturn", "data_len", self.data.len())?;
@@ -17,6 +17,11 @@
 //! have encode_ptr/decode_ptr and encode_value/decode_value methods. Messages are parsed linearly,
 //! so decode_ptr/decode_value are called at different stages (same for encoding).
 //!
+//! All integers (including pointers, lengths and UTF-16 code units) are encoded in the data
+//! representation announced by the [MS-RPCE] stream header, which is little-endian for Windows
+//! peers but may be big-endian for others. [`NdrReadCursor`] and [`NdrWriteCursor`] take care of
+//! it, byte arrays being left as is.
+//!
 //! Most of the above was reverse-engineered from FreeRDP: [smartcard_pack.c]
 //!
 //! [smartcard_pack.c]: https://github.com/FreeRDP/FreeRDP/blob/ff303a9bda911c54ffc1b9f2471acd79c897b075/libfreerdp/utils/smartcard_pack.c
@@ -26,16 +31,118 @@ use std::mem::size_of;
 use ironrdp_core::{ensure_size, invalid_field_err, DecodeResult, EncodeResult, ReadCursor, WriteCursor};
 use ironrdp_pdu::utils::{self, CharacterSet};
 
+use super::rpce::Endianness;
+
+/// A [`ReadCursor`] reading NDR integers in the data representation of the stream.
+#[derive(Debug)]
+pub struct NdrReadCursor<'a, 'b> {
+    cursor: &'a mut ReadCursor<'b>,
+    endianness: Endianness,
+}
+
+impl<'a, 'b> NdrReadCursor<'a, 'b> {
+    pub fn new(cursor: &'a mut ReadCursor<'b>, endianness: Endianness) -> Self {
+        Self { cursor, endianness }
+    }
+
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    pub fn len(&self) -> usize {
+        self.cursor.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cursor.is_empty()
+    }
+
+    pub fn read_u16(&mut self) -> u16 {
+        match self.endianness {
+            Endianness::LittleEndian => self.cursor.read_u16(),
+            Endianness::BigEndian => self.cursor.read_u16_be(),
+        }
+    }
+
+    pub fn read_u32(&mut self) -> u32 {
+        match self.endianness {
+            Endianness::LittleEndian => self.cursor.read_u32(),
+            Endianness::BigEndian => self.cursor.read_u32_be(),
+        }
+    }
+
+    pub fn read_i32(&mut self) -> i32 {
+        match self.endianness {
+            Endianness::LittleEndian => self.cursor.read_i32(),
+            Endianness::BigEndian => self.cursor.read_i32_be(),
+        }
+    }
+
+    /// Reads a byte array, which is not subject to the data representation.
+    pub fn read_slice(&mut self, n: usize) -> &'b [u8] {
+        self.cursor.read_slice(n)
+    }
+
+    /// Reads a byte array, which is not subject to the data representation.
+    pub fn read_array<const N: usize>(&mut self) -> [u8; N] {
+        self.cursor.read_array()
+    }
+}
+
+/// A [`WriteCursor`] writing NDR integers in a given data representation.
+#[derive(Debug)]
+pub struct NdrWriteCursor<'a, 'b> {
+    cursor: &'a mut WriteCursor<'b>,
+    endianness: Endianness,
+}
+
+impl<'a, 'b> NdrWriteCursor<'a, 'b> {
+    pub fn new(cursor: &'a mut WriteCursor<'b>, endianness: Endianness) -> Self {
+        Self { cursor, endianness }
+    }
+
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    pub fn len(&self) -> usize {
+        self.cursor.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cursor.is_empty()
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        match self.endianness {
+            Endianness::LittleEndian => self.cursor.write_u16(value),
+            Endianness::BigEndian => self.cursor.write_u16_be(value),
+        }
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        match self.endianness {
+            Endianness::LittleEndian => self.cursor.write_u32(value),
+            Endianness::BigEndian => self.cursor.write_u32_be(value),
+        }
+    }
+
+    /// Writes a byte array, which is not subject to the data representation.
+    pub fn write_slice(&mut self, slice: &[u8]) {
+        self.cursor.write_slice(slice)
+    }
+}
+
 pub trait Decode {
-    fn decode_ptr(src: &mut ReadCursor<'_>, index: &mut u32) -> DecodeResult<Self>
+    fn decode_ptr(src: &mut NdrReadCursor<'_, '_>, index: &mut u32) -> DecodeResult<Self>
     where
         Self: Sized;
-    fn decode_value(&mut self, src: &mut ReadCursor<'_>, charset: Option<CharacterSet>) -> DecodeResult<()>;
+    fn decode_value(&mut self, src: &mut NdrReadCursor<'_, '_>, charset: Option<CharacterSet>) -> DecodeResult<()>;
 }
 
 pub trait Encode {
-    fn encode_ptr(&self, index: &mut u32, dst: &mut WriteCursor<'_>) -> EncodeResult<()>;
-    fn encode_value(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()>;
+    fn encode_ptr(&self, index: &mut u32, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()>;
+    fn encode_value(&self, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()>;
     fn size_ptr(&self) -> usize;
     fn size_value(&self) -> usize;
     fn size(&self) -> usize {
@@ -43,7 +150,7 @@ pub trait Encode {
     }
 }
 
-pub fn encode_ptr(length: Option<u32>, index: &mut u32, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+pub fn encode_ptr(length: Option<u32>, index: &mut u32, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()> {
     ensure_size!(ctx: "encode_ptr", in: dst, size: ptr_size(length.is_some()));
     if let Some(length) = length {
         dst.write_u32(length);
@@ -54,7 +161,7 @@ pub fn encode_ptr(length: Option<u32>, index: &mut u32, dst: &mut WriteCursor<'_
     Ok(())
 }
 
-pub fn decode_ptr(src: &mut ReadCursor<'_>, index: &mut u32) -> DecodeResult<u32> {
+pub fn decode_ptr(src: &mut NdrReadCursor<'_, '_>, index: &mut u32) -> DecodeResult<u32> {
     ensure_size!(ctx: "decode_ptr", in: src, size: size_of::<u32>());
     let ptr = src.read_u32();
     if ptr == 0 {
@@ -81,13 +188,13 @@ pub fn ptr_size(with_length: bool) -> usize {
 /// A special read_string_from_cursor which reads and ignores the additional length and
 /// offset fields prefixing the string, as well as any extra padding for a 4-byte aligned
 /// NULL-terminated string.
-pub fn read_string_from_cursor(cursor: &mut ReadCursor<'_>, charset: CharacterSet) -> DecodeResult<String> {
+pub fn read_string_from_cursor(cursor: &mut NdrReadCursor<'_, '_>, charset: CharacterSet) -> DecodeResult<String> {
     ensure_size!(ctx: "ndr::read_string_from_cursor", in: cursor, size: size_of::<u32>() * 3);
     let length = cursor.read_u32();
     let _offset = cursor.read_u32();
     let _length2 = cursor.read_u32();
 
-    let string = utils::read_string_from_cursor(cursor, charset, true)?;
+    let string = read_null_terminated_string(cursor, charset)?;
 
     // Skip padding for 4-byte aligned NULL-terminated string.
     if length % 2 != 0 {
@@ -97,3 +204,76 @@ pub fn read_string_from_cursor(cursor: &mut ReadCursor<'_>, charset: CharacterSe
 
     Ok(string)
 }
+
+/// Reads a multi-string, i.e. a sequence of NULL-terminated strings ending with an empty one.
+pub fn read_multistring_from_cursor(
+    cursor: &mut NdrReadCursor<'_, '_>,
+    charset: CharacterSet,
+) -> DecodeResult<Vec<String>> {
+    let mut strings = Vec::new();
+
+    loop {
+        let string = read_null_terminated_string(cursor, charset)?;
+        if string.is_empty() {
+            break;
+        }
+
+        strings.push(string);
+    }
+
+    Ok(strings)
+}
+
+/// Writes a multi-string, see [`utils::encoded_multistring_len`] for its size.
+pub fn write_multistring_to_cursor(
+    cursor: &mut NdrWriteCursor<'_, '_>,
+    strings: &[String],
+    charset: CharacterSet,
+) -> EncodeResult<()> {
+    ensure_size!(ctx: "ndr::write_multistring_to_cursor", in: cursor, size: utils::encoded_multistring_len(strings, charset));
+
+    match charset {
+        CharacterSet::Unicode => {
+            for string in strings {
+                for code_unit in string.encode_utf16() {
+                    cursor.write_u16(code_unit);
+                }
+                cursor.write_u16(0);
+            }
+            cursor.write_u16(0);
+        }
+        CharacterSet::Ansi => {
+            for string in strings {
+                cursor.write_slice(string.as_bytes());
+                cursor.write_slice(&[0]);
+            }
+            cursor.write_slice(&[0]);
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a NULL-terminated string, or the rest of the buffer if the terminator is missing.
+fn read_null_terminated_string(cursor: &mut NdrReadCursor<'_, '_>, charset: CharacterSet) -> DecodeResult<String> {
+    match (charset, cursor.endianness()) {
+        (CharacterSet::Unicode, Endianness::BigEndian) => {
+            // UTF-16 code units are integers too, swap them before handing them over.
+            let remaining = cursor.cursor.remaining();
+            let code_units = remaining
+                .chunks_exact(2)
+                .position(|chunk| chunk == [0, 0])
+                .map(|null_terminator_pos| null_terminator_pos + 1)
+                .unwrap_or(remaining.len() / 2);
+
+            let swapped = cursor
+                .read_slice(code_units * 2)
+                .chunks_exact(2)
+                .flat_map(|chunk| [chunk[1], chunk[0]])
+                .collect::<Vec<u8>>();
+
+            utils::read_string_from_cursor(&mut ReadCursor::new(&swapped), charset, true)
+        }
+        _ => utils::read_string_from_cursor(cursor.cursor, charset, true),
+    }
+}
@@ -9,6 +9,8 @@ use ironrdp_core::{
 };
 use ironrdp_pdu::utils::CharacterSet;
 
+use super::ndr::{NdrReadCursor, NdrWriteCursor};
+
 /// Wrapper struct for [MS-RPCE] PDUs that allows for common [`Encode`], [`Encode`], and [`Self::decode`] implementations.
 ///
 /// Structs which are meant to be encoded into an [MS-RPCE] message should typically implement [`HeaderlessEncode`],
@@ -23,13 +25,13 @@ use ironrdp_pdu::utils::CharacterSet;
 /// impl RpceEncodePdu {
 ///     /// `new` returns a `Pdu` wrapping the underlying struct.
 ///     pub fn new(example_field: u32) -> rpce::Pdu<Self> {
-///         rpce::Pdu(Self { example_field })
+///         rpce::Pdu::new(Self { example_field })
 ///     }
 /// }
 ///
 /// /// The underlying struct should implement `HeaderlessEncode`.
 /// impl rpce::HeaderlessEncode for RpceEncodePdu {
-///     fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+///     fn encode(&self, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()> {
 ///         ensure_size!(in: dst, size: self.size());
 ///         dst.write_u32(self.return_code.into());
 ///         Ok(())
@@ -57,8 +59,8 @@ use ironrdp_pdu::utils::CharacterSet;
 ///
 /// impl RpceDecodePdu {
 ///     /// `decode` returns a `Pdu` wrapping the underlying struct.
-///     pub fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<rpce::Pdu<Self>> {
-///         Ok(rpce::Pdu::<Self>::decode(src)?.into_inner())
+///     pub fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+///         Ok(rpce::Pdu::<Self>::decode(src, None)?.into_inner())
 ///     }
 ///
 ///     fn size() -> usize {
@@ -68,10 +70,7 @@ use ironrdp_pdu::utils::CharacterSet;
 ///
 /// /// The underlying struct should implement `HeaderlessDecode`.
 /// impl rpce::HeaderlessDecode for RpceDecodePdu {
-///    fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self>
-///    where
-///         Self: Sized,
-///    {
+///    fn decode(src: &mut NdrReadCursor<'_, '_>, _charset: Option<CharacterSet>) -> DecodeResult<Self> {
 ///        ensure_size!(in: src, size: Self::size());
 ///        let example_field = src.read_u32();
 ///        Ok(Self { example_field })
@@ -80,16 +79,39 @@ use ironrdp_pdu::utils::CharacterSet;
 /// ```
 ///
 /// See [`super::EstablishContextCall`] for a live example of a decodable PDU.
+///
+/// Integers are encoded little-endian unless requested otherwise with [`Self::with_endianness`],
+/// and decoded according to the stream header.
 #[derive(Debug)]
-pub struct Pdu<T>(pub T);
+pub struct Pdu<T> {
+    inner: T,
+    endianness: Endianness,
+}
 
 impl<T> Pdu<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            endianness: Endianness::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_endianness(mut self, endianness: Endianness) -> Self {
+        self.endianness = endianness;
+        self
+    }
+
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
     pub fn into_inner(self) -> T {
-        self.0
+        self.inner
     }
 
     pub fn into_inner_ref(&self) -> &T {
-        &self.0
+        &self.inner
     }
 }
 
@@ -99,25 +121,27 @@ impl<T: HeaderlessDecode> Pdu<T> {
         // We expect `StreamHeader::decode`, `TypeHeader::decode`, and `T::decode` to each
         // call `ensure_size!` to ensure that the buffer is large enough, so we can safely
         // omit that check here.
-        let _stream_header = StreamHeader::decode(src)?;
-        let _type_header = TypeHeader::decode(src)?;
-        let pdu = T::decode(src, charset)?;
-        Ok(Self(pdu))
+        let stream_header = StreamHeader::decode(src)?;
+        let mut src = NdrReadCursor::new(src, stream_header.endianness);
+        let _type_header = TypeHeader::decode(&mut src)?;
+        let pdu = T::decode(&mut src, charset)?;
+        Ok(Self::new(pdu).with_endianness(stream_header.endianness))
     }
 }
 
 impl<T: HeaderlessEncode> ironrdp_core::Encode for Pdu<T> {
     fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
         ensure_size!(ctx: self.name(), in: dst, size: self.size());
-        let stream_header = StreamHeader::default();
+        let stream_header = StreamHeader::new(self.endianness);
         let type_header = TypeHeader::new(cast_length!("Pdu<T>", "size", self.size())?);
 
-        stream_header.encode(dst)?;
-        type_header.encode(dst)?;
-        HeaderlessEncode::encode(&self.0, dst)?;
+        let mut dst = NdrWriteCursor::new(dst, self.endianness);
+        stream_header.encode(&mut dst)?;
+        type_header.encode(&mut dst)?;
+        HeaderlessEncode::encode(&self.inner, &mut dst)?;
 
         // Pad response to be 8-byte aligned.
-        let padding_size = padding_size(&self.0);
+        let padding_size = padding_size(&self.inner);
         if padding_size > 0 {
             dst.write_slice(&vec![0; padding_size]);
         }
@@ -126,28 +150,37 @@ impl<T: HeaderlessEncode> ironrdp_core::Encode for Pdu<T> {
     }
 
     fn name(&self) -> &'static str {
-        self.0.name()
+        self.inner.name()
     }
 
     fn size(&self) -> usize {
-        StreamHeader::size() + TypeHeader::size() + HeaderlessEncode::size(&self.0) + padding_size(&self.0)
+        StreamHeader::size() + TypeHeader::size() + HeaderlessEncode::size(&self.inner) + padding_size(&self.inner)
     }
 }
 
-impl<T: HeaderlessEncode> Encode for Pdu<T> {}
+impl<T: HeaderlessEncode> Encode for Pdu<T> {
+    fn set_endianness(&mut self, endianness: Endianness) {
+        self.endianness = endianness;
+    }
+}
 
 /// Trait for types that can be encoded into an [MS-RPCE] message.
 ///
 /// Implementers should typically avoid implementing this trait directly
 /// and instead implement [`HeaderlessEncode`], and wrap it in a [`Pdu`].
-pub trait Encode: ironrdp_core::Encode + Send + std::fmt::Debug {}
+pub trait Encode: ironrdp_core::Encode + Send + std::fmt::Debug {
+    /// Sets the data representation of the message, e.g. to match the one of the call it answers.
+    ///
+    /// Does nothing for messages which are not encoded as NDR.
+    fn set_endianness(&mut self, _endianness: Endianness) {}
+}
 
 /// Trait for types that can be encoded into an [MS-RPCE] message.
 ///
 /// Implementers should typically implement this trait instead of [`Encode`].
 pub trait HeaderlessEncode: Send + std::fmt::Debug {
     /// Encodes the instance into a buffer sans its headers.
-    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()>;
+    fn encode(&self, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()>;
     /// Returns the name associated with this RPCE PDU.
     fn name(&self) -> &'static str;
     /// Returns the size of the instance sans its headers.
@@ -165,7 +198,7 @@ pub trait HeaderlessDecode: Sized {
     /// `charset` is an optional parameter that can be used to specify the character set
     /// when relevant. This is useful for accounting for the "A" vs "W" variants of certain
     /// opcodes e.g. [`ListReadersA`][`super::ScardIoCtlCode::ListReadersA`] vs [`ListReadersW`][`super::ScardIoCtlCode::ListReadersW`].
-    fn decode(src: &mut ReadCursor<'_>, charset: Option<CharacterSet>) -> DecodeResult<Self>;
+    fn decode(src: &mut NdrReadCursor<'_, '_>, charset: Option<CharacterSet>) -> DecodeResult<Self>;
 }
 
 /// [2.2.6.1] Common Type Header for the Serialization Stream
@@ -178,22 +211,19 @@ struct StreamHeader {
     filler: u32,
 }
 
-impl Default for StreamHeader {
-    fn default() -> Self {
+impl StreamHeader {
+    fn new(endianness: Endianness) -> Self {
         Self {
             version: 1,
-            endianness: Endianness::LittleEndian,
+            endianness,
             common_header_length: 8,
             filler: 0xCCCC_CCCC,
         }
     }
-}
 
-impl StreamHeader {
-    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+    fn encode(&self, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: Self::size());
-        dst.write_u8(self.version);
-        dst.write_u8(self.endianness.into());
+        dst.write_slice(&[self.version, self.endianness.into()]);
         dst.write_u16(self.common_header_length);
         dst.write_u32(self.filler);
         Ok(())
@@ -203,22 +233,18 @@ impl StreamHeader {
         ensure_size!(in: src, size: Self::size());
         let version = src.read_u8();
         let endianness = Endianness::try_from(src.read_u8())?;
+
+        // The rest of the header is already in the announced data representation.
+        let mut src = NdrReadCursor::new(src, endianness);
         let common_header_length = src.read_u16();
         let filler = src.read_u32();
-        if endianness == Endianness::LittleEndian {
-            Ok(Self {
-                version,
-                endianness,
-                common_header_length,
-                filler,
-            })
-        } else {
-            Err(invalid_field_err!(
-                "decode",
-                "StreamHeader",
-                "server returned big-endian data, parsing not implemented"
-            ))
-        }
+
+        Ok(Self {
+            version,
+            endianness,
+            common_header_length,
+            filler,
+        })
     }
 
     fn size() -> usize {
@@ -226,13 +252,23 @@ impl StreamHeader {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+/// Integer data representation of an [MS-RPCE] serialization stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 #[repr(u8)]
-enum Endianness {
+pub enum Endianness {
     BigEndian = 0x00,
+    #[default]
     LittleEndian = 0x10,
 }
 
+impl Endianness {
+    /// Returns the data representation announced by the stream header at the start of `src`, without consuming it.
+    pub fn peek(src: &ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_size!(ctx: "Endianness::peek", in: src, size: StreamHeader::size());
+        Self::try_from(src.remaining()[1])
+    }
+}
+
 impl TryFrom<u8> for Endianness {
     type Error = DecodeError;
 
@@ -268,14 +304,14 @@ impl TypeHeader {
         }
     }
 
-    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+    fn encode(&self, dst: &mut NdrWriteCursor<'_, '_>) -> EncodeResult<()> {
         ensure_size!(in: dst, size: Self::size());
         dst.write_u32(self.object_buffer_length);
         dst.write_u32(self.filler);
         Ok(())
     }
 
-    fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+    fn decode(src: &mut NdrReadCursor<'_, '_>) -> DecodeResult<Self> {
         ensure_size!(in: src, size: Self::size());
         let object_buffer_length = src.read_u32();
         let filler = src.read_u32();
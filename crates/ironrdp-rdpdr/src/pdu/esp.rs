@@ -0,0 +1,816 @@
+//! PDUs for [\[MS-RDPESP\]: Remote Desktop Protocol: Serial and Parallel Port Virtual Channel Extension]
+//!
+//! [\[MS-RDPESP\]: Remote Desktop Protocol: Serial and Parallel Port Virtual Channel Extension]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpesp/
+
+use std::mem::size_of;
+
+use bitflags::bitflags;
+use ironrdp_core::{
+    ensure_fixed_part_size, ensure_size, invalid_field_err, unsupported_value_err, DecodeError, DecodeResult, Encode,
+    EncodeResult, ReadCursor, WriteCursor,
+};
+
+use super::efs::{
+    AnyIoCtlCode, DeviceCloseRequest, DeviceControlRequest, DeviceCreateRequest, DeviceIoRequest, DeviceReadRequest,
+    DeviceWriteRequest, IoCtlCode, MajorFunction,
+};
+use super::esc::rpce;
+
+/// I/O requests the server sends to a redirected serial or parallel port.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ServerPortIoRequest {
+    Create(DeviceCreateRequest),
+    Close(DeviceCloseRequest),
+    Read(DeviceReadRequest),
+    Write(DeviceWriteRequest),
+    /// A serial port IOCTL, see [`SerialIoCtlCode`].
+    SerialControl(DeviceControlRequest<SerialIoCtlCode>, SerialRequest),
+    /// Any other IOCTL, such as the parallel port ones, which are not supported.
+    UnsupportedControl(DeviceControlRequest<AnyIoCtlCode>),
+}
+
+impl ServerPortIoRequest {
+    pub fn decode(dev_io_req: DeviceIoRequest, src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        match dev_io_req.major_function {
+            MajorFunction::Create => Ok(Self::Create(DeviceCreateRequest::decode(dev_io_req, src)?)),
+            MajorFunction::Close => Ok(Self::Close(DeviceCloseRequest::decode(dev_io_req))),
+            MajorFunction::Read => Ok(Self::Read(DeviceReadRequest::decode(dev_io_req, src)?)),
+            MajorFunction::Write => Ok(Self::Write(DeviceWriteRequest::decode(dev_io_req, src)?)),
+            MajorFunction::DeviceControl => {
+                let req = DeviceControlRequest::<AnyIoCtlCode>::decode(dev_io_req, src)?;
+                let Ok(io_control_code) = SerialIoCtlCode::try_from(req.io_control_code.0) else {
+                    return Ok(Self::UnsupportedControl(req));
+                };
+
+                let call = SerialRequest::decode(io_control_code, src)?;
+                let req = DeviceControlRequest {
+                    header: req.header,
+                    output_buffer_length: req.output_buffer_length,
+                    input_buffer_length: req.input_buffer_length,
+                    io_control_code,
+                };
+
+                Ok(Self::SerialControl(req, call))
+            }
+            major_function => Err(unsupported_value_err!(
+                "ServerPortIoRequest::decode",
+                "MajorFunction",
+                format!("{major_function:?}")
+            )),
+        }
+    }
+
+    pub fn device_io_request(&self) -> &DeviceIoRequest {
+        match self {
+            Self::Create(req) => &req.device_io_request,
+            Self::Close(req) => &req.device_io_request,
+            Self::Read(req) => &req.device_io_request,
+            Self::Write(req) => &req.device_io_request,
+            Self::SerialControl(req, _) => &req.header,
+            Self::UnsupportedControl(req) => &req.header,
+        }
+    }
+}
+
+/// Serial port IOCTL codes, as used in [`ServerPortIoRequest::SerialControl`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum SerialIoCtlCode {
+    /// IOCTL_SERIAL_SET_BAUD_RATE
+    SetBaudRate = 0x001B_0004,
+    /// IOCTL_SERIAL_SET_QUEUE_SIZE
+    SetQueueSize = 0x001B_0008,
+    /// IOCTL_SERIAL_SET_LINE_CONTROL
+    SetLineControl = 0x001B_000C,
+    /// IOCTL_SERIAL_SET_BREAK_ON
+    SetBreakOn = 0x001B_0010,
+    /// IOCTL_SERIAL_SET_BREAK_OFF
+    SetBreakOff = 0x001B_0014,
+    /// IOCTL_SERIAL_IMMEDIATE_CHAR
+    ImmediateChar = 0x001B_0018,
+    /// IOCTL_SERIAL_SET_TIMEOUTS
+    SetTimeouts = 0x001B_001C,
+    /// IOCTL_SERIAL_GET_TIMEOUTS
+    GetTimeouts = 0x001B_0020,
+    /// IOCTL_SERIAL_SET_DTR
+    SetDtr = 0x001B_0024,
+    /// IOCTL_SERIAL_CLR_DTR
+    ClrDtr = 0x001B_0028,
+    /// IOCTL_SERIAL_RESET_DEVICE
+    ResetDevice = 0x001B_002C,
+    /// IOCTL_SERIAL_SET_RTS
+    SetRts = 0x001B_0030,
+    /// IOCTL_SERIAL_CLR_RTS
+    ClrRts = 0x001B_0034,
+    /// IOCTL_SERIAL_SET_XOFF
+    SetXoff = 0x001B_0038,
+    /// IOCTL_SERIAL_SET_XON
+    SetXon = 0x001B_003C,
+    /// IOCTL_SERIAL_GET_WAIT_MASK
+    GetWaitMask = 0x001B_0040,
+    /// IOCTL_SERIAL_SET_WAIT_MASK
+    SetWaitMask = 0x001B_0044,
+    /// IOCTL_SERIAL_WAIT_ON_MASK
+    WaitOnMask = 0x001B_0048,
+    /// IOCTL_SERIAL_PURGE
+    Purge = 0x001B_004C,
+    /// IOCTL_SERIAL_GET_BAUD_RATE
+    GetBaudRate = 0x001B_0050,
+    /// IOCTL_SERIAL_GET_LINE_CONTROL
+    GetLineControl = 0x001B_0054,
+    /// IOCTL_SERIAL_SET_CHARS
+    SetChars = 0x001B_0058,
+    /// IOCTL_SERIAL_GET_CHARS
+    GetChars = 0x001B_005C,
+    /// IOCTL_SERIAL_GET_HANDFLOW
+    GetHandflow = 0x001B_0060,
+    /// IOCTL_SERIAL_SET_HANDFLOW
+    SetHandflow = 0x001B_0064,
+    /// IOCTL_SERIAL_GET_MODEMSTATUS
+    GetModemStatus = 0x001B_0068,
+    /// IOCTL_SERIAL_GET_COMMSTATUS
+    GetCommStatus = 0x001B_006C,
+    /// IOCTL_SERIAL_GET_DTRRTS
+    GetDtrRts = 0x001B_0078,
+    /// IOCTL_SERIAL_CONFIG_SIZE
+    ConfigSize = 0x001B_0080,
+}
+
+impl TryFrom<u32> for SerialIoCtlCode {
+    type Error = DecodeError;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0x001B_0004 => Ok(Self::SetBaudRate),
+            0x001B_0008 => Ok(Self::SetQueueSize),
+            0x001B_000C => Ok(Self::SetLineControl),
+            0x001B_0010 => Ok(Self::SetBreakOn),
+            0x001B_0014 => Ok(Self::SetBreakOff),
+            0x001B_0018 => Ok(Self::ImmediateChar),
+            0x001B_001C => Ok(Self::SetTimeouts),
+            0x001B_0020 => Ok(Self::GetTimeouts),
+            0x001B_0024 => Ok(Self::SetDtr),
+            0x001B_0028 => Ok(Self::ClrDtr),
+            0x001B_002C => Ok(Self::ResetDevice),
+            0x001B_0030 => Ok(Self::SetRts),
+            0x001B_0034 => Ok(Self::ClrRts),
+            0x001B_0038 => Ok(Self::SetXoff),
+            0x001B_003C => Ok(Self::SetXon),
+            0x001B_0040 => Ok(Self::GetWaitMask),
+            0x001B_0044 => Ok(Self::SetWaitMask),
+            0x001B_0048 => Ok(Self::WaitOnMask),
+            0x001B_004C => Ok(Self::Purge),
+            0x001B_0050 => Ok(Self::GetBaudRate),
+            0x001B_0054 => Ok(Self::GetLineControl),
+            0x001B_0058 => Ok(Self::SetChars),
+            0x001B_005C => Ok(Self::GetChars),
+            0x001B_0060 => Ok(Self::GetHandflow),
+            0x001B_0064 => Ok(Self::SetHandflow),
+            0x001B_0068 => Ok(Self::GetModemStatus),
+            0x001B_006C => Ok(Self::GetCommStatus),
+            0x001B_0078 => Ok(Self::GetDtrRts),
+            0x001B_0080 => Ok(Self::ConfigSize),
+            _ => Err(invalid_field_err!("try_from", "SerialIoCtlCode", "unsupported value")),
+        }
+    }
+}
+
+impl From<SerialIoCtlCode> for u32 {
+    fn from(code: SerialIoCtlCode) -> Self {
+        code as u32
+    }
+}
+
+impl IoCtlCode for SerialIoCtlCode {}
+
+/// The input buffer of a serial port IOCTL.
+#[derive(Debug, PartialEq, Clone)]
+pub enum SerialRequest {
+    SetBaudRate(u32),
+    GetBaudRate,
+    SetLineControl(SerialLineControl),
+    GetLineControl,
+    SetHandflow(SerialHandflow),
+    GetHandflow,
+    SetTimeouts(SerialTimeouts),
+    GetTimeouts,
+    SetChars(SerialChars),
+    GetChars,
+    SetQueueSize(SerialQueueSize),
+    SetWaitMask(WaitMask),
+    GetWaitMask,
+    WaitOnMask,
+    Purge(PurgeMask),
+    SetDtr,
+    ClrDtr,
+    SetRts,
+    ClrRts,
+    GetDtrRts,
+    SetXon,
+    SetXoff,
+    SetBreakOn,
+    SetBreakOff,
+    ImmediateChar(u8),
+    ResetDevice,
+    GetModemStatus,
+    GetCommStatus,
+    ConfigSize,
+}
+
+impl SerialRequest {
+    pub fn decode(io_control_code: SerialIoCtlCode, src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        match io_control_code {
+            SerialIoCtlCode::SetBaudRate => {
+                ensure_size!(ctx: "SERIAL_BAUD_RATE", in: src, size: size_of::<u32>());
+                Ok(Self::SetBaudRate(src.read_u32()))
+            }
+            SerialIoCtlCode::GetBaudRate => Ok(Self::GetBaudRate),
+            SerialIoCtlCode::SetLineControl => Ok(Self::SetLineControl(SerialLineControl::decode(src)?)),
+            SerialIoCtlCode::GetLineControl => Ok(Self::GetLineControl),
+            SerialIoCtlCode::SetHandflow => Ok(Self::SetHandflow(SerialHandflow::decode(src)?)),
+            SerialIoCtlCode::GetHandflow => Ok(Self::GetHandflow),
+            SerialIoCtlCode::SetTimeouts => Ok(Self::SetTimeouts(SerialTimeouts::decode(src)?)),
+            SerialIoCtlCode::GetTimeouts => Ok(Self::GetTimeouts),
+            SerialIoCtlCode::SetChars => Ok(Self::SetChars(SerialChars::decode(src)?)),
+            SerialIoCtlCode::GetChars => Ok(Self::GetChars),
+            SerialIoCtlCode::SetQueueSize => Ok(Self::SetQueueSize(SerialQueueSize::decode(src)?)),
+            SerialIoCtlCode::SetWaitMask => {
+                ensure_size!(ctx: "WaitMask", in: src, size: size_of::<u32>());
+                Ok(Self::SetWaitMask(WaitMask::from_bits_retain(src.read_u32())))
+            }
+            SerialIoCtlCode::GetWaitMask => Ok(Self::GetWaitMask),
+            SerialIoCtlCode::WaitOnMask => Ok(Self::WaitOnMask),
+            SerialIoCtlCode::Purge => {
+                ensure_size!(ctx: "PurgeMask", in: src, size: size_of::<u32>());
+                Ok(Self::Purge(PurgeMask::from_bits_retain(src.read_u32())))
+            }
+            SerialIoCtlCode::SetDtr => Ok(Self::SetDtr),
+            SerialIoCtlCode::ClrDtr => Ok(Self::ClrDtr),
+            SerialIoCtlCode::SetRts => Ok(Self::SetRts),
+            SerialIoCtlCode::ClrRts => Ok(Self::ClrRts),
+            SerialIoCtlCode::GetDtrRts => Ok(Self::GetDtrRts),
+            SerialIoCtlCode::SetXon => Ok(Self::SetXon),
+            SerialIoCtlCode::SetXoff => Ok(Self::SetXoff),
+            SerialIoCtlCode::SetBreakOn => Ok(Self::SetBreakOn),
+            SerialIoCtlCode::SetBreakOff => Ok(Self::SetBreakOff),
+            SerialIoCtlCode::ImmediateChar => {
+                ensure_size!(ctx: "ImmediateChar", in: src, size: size_of::<u8>());
+                Ok(Self::ImmediateChar(src.read_u8()))
+            }
+            SerialIoCtlCode::ResetDevice => Ok(Self::ResetDevice),
+            SerialIoCtlCode::GetModemStatus => Ok(Self::GetModemStatus),
+            SerialIoCtlCode::GetCommStatus => Ok(Self::GetCommStatus),
+            SerialIoCtlCode::ConfigSize => Ok(Self::ConfigSize),
+        }
+    }
+
+    /// Returns the IOCTL code of the request.
+    pub fn io_control_code(&self) -> SerialIoCtlCode {
+        match self {
+            Self::SetBaudRate(_) => SerialIoCtlCode::SetBaudRate,
+            Self::GetBaudRate => SerialIoCtlCode::GetBaudRate,
+            Self::SetLineControl(_) => SerialIoCtlCode::SetLineControl,
+            Self::GetLineControl => SerialIoCtlCode::GetLineControl,
+            Self::SetHandflow(_) => SerialIoCtlCode::SetHandflow,
+            Self::GetHandflow => SerialIoCtlCode::GetHandflow,
+            Self::SetTimeouts(_) => SerialIoCtlCode::SetTimeouts,
+            Self::GetTimeouts => SerialIoCtlCode::GetTimeouts,
+            Self::SetChars(_) => SerialIoCtlCode::SetChars,
+            Self::GetChars => SerialIoCtlCode::GetChars,
+            Self::SetQueueSize(_) => SerialIoCtlCode::SetQueueSize,
+            Self::SetWaitMask(_) => SerialIoCtlCode::SetWaitMask,
+            Self::GetWaitMask => SerialIoCtlCode::GetWaitMask,
+            Self::WaitOnMask => SerialIoCtlCode::WaitOnMask,
+            Self::Purge(_) => SerialIoCtlCode::Purge,
+            Self::SetDtr => SerialIoCtlCode::SetDtr,
+            Self::ClrDtr => SerialIoCtlCode::ClrDtr,
+            Self::SetRts => SerialIoCtlCode::SetRts,
+            Self::ClrRts => SerialIoCtlCode::ClrRts,
+            Self::GetDtrRts => SerialIoCtlCode::GetDtrRts,
+            Self::SetXon => SerialIoCtlCode::SetXon,
+            Self::SetXoff => SerialIoCtlCode::SetXoff,
+            Self::SetBreakOn => SerialIoCtlCode::SetBreakOn,
+            Self::SetBreakOff => SerialIoCtlCode::SetBreakOff,
+            Self::ImmediateChar(_) => SerialIoCtlCode::ImmediateChar,
+            Self::ResetDevice => SerialIoCtlCode::ResetDevice,
+            Self::GetModemStatus => SerialIoCtlCode::GetModemStatus,
+            Self::GetCommStatus => SerialIoCtlCode::GetCommStatus,
+            Self::ConfigSize => SerialIoCtlCode::ConfigSize,
+        }
+    }
+
+    /// Encodes the input buffer of the request.
+    pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(ctx: "SerialRequest", in: dst, size: self.size());
+        match self {
+            Self::SetBaudRate(baud_rate) => dst.write_u32(*baud_rate),
+            Self::SetLineControl(line_control) => line_control.encode(dst)?,
+            Self::SetHandflow(handflow) => handflow.encode(dst)?,
+            Self::SetTimeouts(timeouts) => timeouts.encode(dst)?,
+            Self::SetChars(chars) => chars.encode(dst)?,
+            Self::SetQueueSize(queue_size) => queue_size.encode(dst)?,
+            Self::SetWaitMask(mask) => dst.write_u32(mask.bits()),
+            Self::Purge(mask) => dst.write_u32(mask.bits()),
+            Self::ImmediateChar(c) => dst.write_u8(*c),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Returns the size of the input buffer of the request.
+    pub fn size(&self) -> usize {
+        match self {
+            Self::SetBaudRate(_) | Self::SetWaitMask(_) | Self::Purge(_) => size_of::<u32>(),
+            Self::SetLineControl(_) => SerialLineControl::FIXED_PART_SIZE,
+            Self::SetHandflow(_) => SerialHandflow::FIXED_PART_SIZE,
+            Self::SetTimeouts(_) => SerialTimeouts::FIXED_PART_SIZE,
+            Self::SetChars(_) => SerialChars::FIXED_PART_SIZE,
+            Self::SetQueueSize(_) => SerialQueueSize::FIXED_PART_SIZE,
+            Self::ImmediateChar(_) => size_of::<u8>(),
+            _ => 0,
+        }
+    }
+}
+
+/// The output buffer of a serial port IOCTL.
+#[derive(Debug, PartialEq, Clone)]
+pub enum SerialResponse {
+    /// The IOCTL has no output.
+    None,
+    BaudRate(u32),
+    LineControl(SerialLineControl),
+    Handflow(SerialHandflow),
+    Timeouts(SerialTimeouts),
+    Chars(SerialChars),
+    WaitMask(WaitMask),
+    ModemStatus(ModemStatus),
+    DtrRts(DtrRts),
+    CommStatus(SerialStatus),
+    ConfigSize(u32),
+}
+
+impl SerialResponse {
+    const NAME: &'static str = "SerialResponse";
+
+    /// Returns the response as the output buffer of a [`DeviceControlResponse`](super::efs::DeviceControlResponse).
+    pub fn into_output_buffer(self) -> Option<Box<dyn rpce::Encode>> {
+        match self {
+            Self::None => None,
+            response => Some(Box::new(response)),
+        }
+    }
+
+    pub fn decode(io_control_code: SerialIoCtlCode, src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        let read_u32 = |src: &mut ReadCursor<'_>| -> DecodeResult<u32> {
+            ensure_size!(ctx: Self::NAME, in: src, size: size_of::<u32>());
+            Ok(src.read_u32())
+        };
+
+        match io_control_code {
+            SerialIoCtlCode::GetBaudRate => Ok(Self::BaudRate(read_u32(src)?)),
+            SerialIoCtlCode::GetLineControl => Ok(Self::LineControl(SerialLineControl::decode(src)?)),
+            SerialIoCtlCode::GetHandflow => Ok(Self::Handflow(SerialHandflow::decode(src)?)),
+            SerialIoCtlCode::GetTimeouts => Ok(Self::Timeouts(SerialTimeouts::decode(src)?)),
+            SerialIoCtlCode::GetChars => Ok(Self::Chars(SerialChars::decode(src)?)),
+            SerialIoCtlCode::GetWaitMask | SerialIoCtlCode::WaitOnMask => {
+                Ok(Self::WaitMask(WaitMask::from_bits_retain(read_u32(src)?)))
+            }
+            SerialIoCtlCode::GetModemStatus => Ok(Self::ModemStatus(ModemStatus::from_bits_retain(read_u32(src)?))),
+            SerialIoCtlCode::GetDtrRts => Ok(Self::DtrRts(DtrRts::from_bits_retain(read_u32(src)?))),
+            SerialIoCtlCode::GetCommStatus => Ok(Self::CommStatus(SerialStatus::decode(src)?)),
+            SerialIoCtlCode::ConfigSize => Ok(Self::ConfigSize(read_u32(src)?)),
+            _ => Ok(Self::None),
+        }
+    }
+}
+
+impl Encode for SerialResponse {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(in: dst, size: self.size());
+        match self {
+            Self::None => {}
+            Self::BaudRate(value) | Self::ConfigSize(value) => dst.write_u32(*value),
+            Self::LineControl(line_control) => line_control.encode(dst)?,
+            Self::Handflow(handflow) => handflow.encode(dst)?,
+            Self::Timeouts(timeouts) => timeouts.encode(dst)?,
+            Self::Chars(chars) => chars.encode(dst)?,
+            Self::WaitMask(mask) => dst.write_u32(mask.bits()),
+            Self::ModemStatus(status) => dst.write_u32(status.bits()),
+            Self::DtrRts(state) => dst.write_u32(state.bits()),
+            Self::CommStatus(status) => status.encode(dst)?,
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Self::None => 0,
+            Self::BaudRate(_) | Self::ConfigSize(_) | Self::WaitMask(_) | Self::ModemStatus(_) | Self::DtrRts(_) => {
+                size_of::<u32>()
+            }
+            Self::LineControl(_) => SerialLineControl::FIXED_PART_SIZE,
+            Self::Handflow(_) => SerialHandflow::FIXED_PART_SIZE,
+            Self::Timeouts(_) => SerialTimeouts::FIXED_PART_SIZE,
+            Self::Chars(_) => SerialChars::FIXED_PART_SIZE,
+            Self::CommStatus(_) => SerialStatus::FIXED_PART_SIZE,
+        }
+    }
+}
+
+impl rpce::Encode for SerialResponse {}
+
+/// SERIAL_LINE_CONTROL
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SerialLineControl {
+    pub stop_bits: StopBits,
+    pub parity: Parity,
+    /// Number of data bits, from 5 to 8.
+    pub word_length: u8,
+}
+
+impl SerialLineControl {
+    const FIXED_PART_SIZE: usize = size_of::<u8>() * 3;
+
+    pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_fixed_part_size!(in: dst);
+        dst.write_u8(self.stop_bits as u8);
+        dst.write_u8(self.parity as u8);
+        dst.write_u8(self.word_length);
+        Ok(())
+    }
+
+    pub fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+        let stop_bits = StopBits::try_from(src.read_u8())?;
+        let parity = Parity::try_from(src.read_u8())?;
+        let word_length = src.read_u8();
+
+        Ok(Self {
+            stop_bits,
+            parity,
+            word_length,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[repr(u8)]
+pub enum StopBits {
+    /// STOP_BIT_1
+    One = 0,
+    /// STOP_BITS_1_5
+    OnePointFive = 1,
+    /// STOP_BITS_2
+    Two = 2,
+}
+
+impl TryFrom<u8> for StopBits {
+    type Error = DecodeError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::One),
+            1 => Ok(Self::OnePointFive),
+            2 => Ok(Self::Two),
+            _ => Err(invalid_field_err!("try_from", "StopBits", "invalid value")),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[repr(u8)]
+pub enum Parity {
+    /// NO_PARITY
+    None = 0,
+    /// ODD_PARITY
+    Odd = 1,
+    /// EVEN_PARITY
+    Even = 2,
+    /// MARK_PARITY
+    Mark = 3,
+    /// SPACE_PARITY
+    Space = 4,
+}
+
+impl TryFrom<u8> for Parity {
+    type Error = DecodeError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Odd),
+            2 => Ok(Self::Even),
+            3 => Ok(Self::Mark),
+            4 => Ok(Self::Space),
+            _ => Err(invalid_field_err!("try_from", "Parity", "invalid value")),
+        }
+    }
+}
+
+/// SERIAL_HANDFLOW
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SerialHandflow {
+    pub control_handshake: ControlHandshake,
+    pub flow_replace: FlowReplace,
+    pub xon_limit: u32,
+    pub xoff_limit: u32,
+}
+
+impl SerialHandflow {
+    const FIXED_PART_SIZE: usize = size_of::<u32>() * 4;
+
+    pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_fixed_part_size!(in: dst);
+        dst.write_u32(self.control_handshake.bits());
+        dst.write_u32(self.flow_replace.bits());
+        dst.write_u32(self.xon_limit);
+        dst.write_u32(self.xoff_limit);
+        Ok(())
+    }
+
+    pub fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+        Ok(Self {
+            control_handshake: ControlHandshake::from_bits_retain(src.read_u32()),
+            flow_replace: FlowReplace::from_bits_retain(src.read_u32()),
+            xon_limit: src.read_u32(),
+            xoff_limit: src.read_u32(),
+        })
+    }
+}
+
+bitflags! {
+    /// `ControlHandShake` field of [`SerialHandflow`].
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub struct ControlHandshake: u32 {
+        /// SERIAL_DTR_CONTROL
+        const DTR_CONTROL = 0x0000_0001;
+        /// SERIAL_DTR_HANDSHAKE
+        const DTR_HANDSHAKE = 0x0000_0002;
+        /// SERIAL_CTS_HANDSHAKE
+        const CTS_HANDSHAKE = 0x0000_0008;
+        /// SERIAL_DSR_HANDSHAKE
+        const DSR_HANDSHAKE = 0x0000_0010;
+        /// SERIAL_DCD_HANDSHAKE
+        const DCD_HANDSHAKE = 0x0000_0020;
+        /// SERIAL_DSR_SENSITIVITY
+        const DSR_SENSITIVITY = 0x0000_0040;
+        /// SERIAL_ERROR_ABORT
+        const ERROR_ABORT = 0x8000_0000;
+    }
+}
+
+bitflags! {
+    /// `FlowReplace` field of [`SerialHandflow`].
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub struct FlowReplace: u32 {
+        /// SERIAL_AUTO_TRANSMIT
+        const AUTO_TRANSMIT = 0x0000_0001;
+        /// SERIAL_AUTO_RECEIVE
+        const AUTO_RECEIVE = 0x0000_0002;
+        /// SERIAL_ERROR_CHAR
+        const ERROR_CHAR = 0x0000_0004;
+        /// SERIAL_NULL_STRIPPING
+        const NULL_STRIPPING = 0x0000_0008;
+        /// SERIAL_BREAK_CHAR
+        const BREAK_CHAR = 0x0000_0010;
+        /// SERIAL_RTS_CONTROL
+        const RTS_CONTROL = 0x0000_0040;
+        /// SERIAL_RTS_HANDSHAKE
+        const RTS_HANDSHAKE = 0x0000_0080;
+        /// SERIAL_XOFF_CONTINUE
+        const XOFF_CONTINUE = 0x8000_0000;
+    }
+}
+
+/// SERIAL_TIMEOUTS, in milliseconds.
+///
+/// As for `COMMTIMEOUTS`, a `read_interval_timeout` of `u32::MAX` with both read total fields set to zero
+/// means that a read returns immediately with the bytes already received.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct SerialTimeouts {
+    pub read_interval_timeout: u32,
+    pub read_total_timeout_multiplier: u32,
+    pub read_total_timeout_constant: u32,
+    pub write_total_timeout_multiplier: u32,
+    pub write_total_timeout_constant: u32,
+}
+
+impl SerialTimeouts {
+    const FIXED_PART_SIZE: usize = size_of::<u32>() * 5;
+
+    pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_fixed_part_size!(in: dst);
+        dst.write_u32(self.read_interval_timeout);
+        dst.write_u32(self.read_total_timeout_multiplier);
+        dst.write_u32(self.read_total_timeout_constant);
+        dst.write_u32(self.write_total_timeout_multiplier);
+        dst.write_u32(self.write_total_timeout_constant);
+        Ok(())
+    }
+
+    pub fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+        Ok(Self {
+            read_interval_timeout: src.read_u32(),
+            read_total_timeout_multiplier: src.read_u32(),
+            read_total_timeout_constant: src.read_u32(),
+            write_total_timeout_multiplier: src.read_u32(),
+            write_total_timeout_constant: src.read_u32(),
+        })
+    }
+}
+
+/// SERIAL_CHARS
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SerialChars {
+    pub eof_char: u8,
+    pub error_char: u8,
+    pub break_char: u8,
+    pub event_char: u8,
+    pub xon_char: u8,
+    pub xoff_char: u8,
+}
+
+impl SerialChars {
+    const FIXED_PART_SIZE: usize = size_of::<u8>() * 6;
+
+    pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_fixed_part_size!(in: dst);
+        dst.write_u8(self.eof_char);
+        dst.write_u8(self.error_char);
+        dst.write_u8(self.break_char);
+        dst.write_u8(self.event_char);
+        dst.write_u8(self.xon_char);
+        dst.write_u8(self.xoff_char);
+        Ok(())
+    }
+
+    pub fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+        Ok(Self {
+            eof_char: src.read_u8(),
+            error_char: src.read_u8(),
+            break_char: src.read_u8(),
+            event_char: src.read_u8(),
+            xon_char: src.read_u8(),
+            xoff_char: src.read_u8(),
+        })
+    }
+}
+
+impl Default for SerialChars {
+    fn default() -> Self {
+        Self {
+            eof_char: 0,
+            error_char: 0,
+            break_char: 0,
+            event_char: 0,
+            // DC1 and DC3
+            xon_char: 0x11,
+            xoff_char: 0x13,
+        }
+    }
+}
+
+/// SERIAL_QUEUE_SIZE
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SerialQueueSize {
+    pub in_size: u32,
+    pub out_size: u32,
+}
+
+impl SerialQueueSize {
+    const FIXED_PART_SIZE: usize = size_of::<u32>() * 2;
+
+    pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_fixed_part_size!(in: dst);
+        dst.write_u32(self.in_size);
+        dst.write_u32(self.out_size);
+        Ok(())
+    }
+
+    pub fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+        Ok(Self {
+            in_size: src.read_u32(),
+            out_size: src.read_u32(),
+        })
+    }
+}
+
+/// SERIAL_STATUS
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct SerialStatus {
+    pub errors: u32,
+    pub hold_reasons: u32,
+    pub amount_in_in_queue: u32,
+    pub amount_in_out_queue: u32,
+    pub eof_received: bool,
+    pub wait_for_immediate: bool,
+}
+
+impl SerialStatus {
+    const FIXED_PART_SIZE: usize = size_of::<u32>() * 4 + size_of::<u8>() * 2;
+
+    pub fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_fixed_part_size!(in: dst);
+        dst.write_u32(self.errors);
+        dst.write_u32(self.hold_reasons);
+        dst.write_u32(self.amount_in_in_queue);
+        dst.write_u32(self.amount_in_out_queue);
+        dst.write_u8(u8::from(self.eof_received));
+        dst.write_u8(u8::from(self.wait_for_immediate));
+        Ok(())
+    }
+
+    pub fn decode(src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+        Ok(Self {
+            errors: src.read_u32(),
+            hold_reasons: src.read_u32(),
+            amount_in_in_queue: src.read_u32(),
+            amount_in_out_queue: src.read_u32(),
+            eof_received: src.read_u8() != 0,
+            wait_for_immediate: src.read_u8() != 0,
+        })
+    }
+}
+
+bitflags! {
+    /// Events of IOCTL_SERIAL_SET_WAIT_MASK and IOCTL_SERIAL_WAIT_ON_MASK.
+    #[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+    pub struct WaitMask: u32 {
+        /// SERIAL_EV_RXCHAR
+        const RXCHAR = 0x0000_0001;
+        /// SERIAL_EV_RXFLAG
+        const RXFLAG = 0x0000_0002;
+        /// SERIAL_EV_TXEMPTY
+        const TXEMPTY = 0x0000_0004;
+        /// SERIAL_EV_CTS
+        const CTS = 0x0000_0008;
+        /// SERIAL_EV_DSR
+        const DSR = 0x0000_0010;
+        /// SERIAL_EV_RLSD
+        const RLSD = 0x0000_0020;
+        /// SERIAL_EV_BREAK
+        const BREAK = 0x0000_0040;
+        /// SERIAL_EV_ERR
+        const ERR = 0x0000_0080;
+        /// SERIAL_EV_RING
+        const RING = 0x0000_0100;
+        /// SERIAL_EV_PERR
+        const PERR = 0x0000_0200;
+        /// SERIAL_EV_RX80FULL
+        const RX80FULL = 0x0000_0400;
+        /// SERIAL_EV_EVENT1
+        const EVENT1 = 0x0000_0800;
+        /// SERIAL_EV_EVENT2
+        const EVENT2 = 0x0000_1000;
+    }
+}
+
+bitflags! {
+    /// Input buffer of IOCTL_SERIAL_PURGE.
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub struct PurgeMask: u32 {
+        /// SERIAL_PURGE_TXABORT
+        const TXABORT = 0x0000_0001;
+        /// SERIAL_PURGE_RXABORT
+        const RXABORT = 0x0000_0002;
+        /// SERIAL_PURGE_TXCLEAR
+        const TXCLEAR = 0x0000_0004;
+        /// SERIAL_PURGE_RXCLEAR
+        const RXCLEAR = 0x0000_0008;
+    }
+}
+
+bitflags! {
+    /// Output buffer of IOCTL_SERIAL_GET_MODEMSTATUS.
+    #[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+    pub struct ModemStatus: u32 {
+        /// SERIAL_MSR_CTS
+        const CTS = 0x0000_0010;
+        /// SERIAL_MSR_DSR
+        const DSR = 0x0000_0020;
+        /// SERIAL_MSR_RI
+        const RI = 0x0000_0040;
+        /// SERIAL_MSR_DCD
+        const DCD = 0x0000_0080;
+    }
+}
+
+bitflags! {
+    /// Output buffer of IOCTL_SERIAL_GET_DTRRTS.
+    #[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+    pub struct DtrRts: u32 {
+        /// SERIAL_DTR_STATE
+        const DTR = 0x0000_0001;
+        /// SERIAL_RTS_STATE
+        const RTS = 0x0000_0002;
+    }
+}
@@ -9,14 +9,19 @@ use ironrdp_core::{Decode, Encode};
 use ironrdp_svc::SvcEncode;
 
 use self::efs::{
-    ClientDeviceListAnnounce, ClientDriveQueryDirectoryResponse, ClientDriveQueryInformationResponse,
+    ClientDeviceListAnnounce, ClientDeviceListRemove, ClientDriveLockControlResponse,
+    ClientDriveNotifyChangeDirectoryResponse, ClientDriveQueryDirectoryResponse, ClientDriveQueryInformationResponse,
     ClientDriveQueryVolumeInformationResponse, ClientDriveSetInformationResponse, ClientNameRequest, CoreCapability,
     CoreCapabilityKind, DeviceCloseResponse, DeviceControlResponse, DeviceCreateResponse, DeviceIoRequest,
-    DeviceReadResponse, DeviceWriteResponse, ServerDeviceAnnounceResponse, VersionAndIdPdu, VersionAndIdPduKind,
+    DeviceIoResponse, DeviceReadResponse, DeviceWriteResponse, ServerDeviceAnnounceResponse, ServerDeviceIoRequest,
+    VersionAndIdPdu, VersionAndIdPduKind,
 };
+use self::epc::{PrinterCacheData, PrinterUsingXps};
 
 pub mod efs;
+pub mod epc;
 pub mod esc;
+pub mod esp;
 
 /// All available RDPDR PDUs.
 pub enum RdpdrPdu {
@@ -24,8 +29,12 @@ pub enum RdpdrPdu {
     ClientNameRequest(ClientNameRequest),
     CoreCapability(CoreCapability),
     ClientDeviceListAnnounce(ClientDeviceListAnnounce),
+    ClientDeviceListRemove(ClientDeviceListRemove),
     ServerDeviceAnnounceResponse(ServerDeviceAnnounceResponse),
+    /// 2.2.2.5 Server User Logged On (DR_CORE_USER_LOGGEDON), which has no body.
+    UserLoggedOn,
     DeviceIoRequest(DeviceIoRequest),
+    ServerDeviceIoRequest(ServerDeviceIoRequest),
     DeviceControlResponse(DeviceControlResponse),
     DeviceCreateResponse(DeviceCreateResponse),
     ClientDriveQueryInformationResponse(ClientDriveQueryInformationResponse),
@@ -35,10 +44,43 @@ pub enum RdpdrPdu {
     DeviceReadResponse(DeviceReadResponse),
     DeviceWriteResponse(DeviceWriteResponse),
     ClientDriveSetInformationResponse(ClientDriveSetInformationResponse),
+    ClientDriveNotifyChangeDirectoryResponse(ClientDriveNotifyChangeDirectoryResponse),
+    ClientDriveLockControlResponse(ClientDriveLockControlResponse),
+    PrinterCacheData(PrinterCacheData),
+    PrinterUsingXps(PrinterUsingXps),
     EmptyResponse,
 }
 
 impl RdpdrPdu {
+    /// Returns the reply header of a device I/O response, which identifies the request it completes.
+    pub fn device_io_response(&self) -> Option<&DeviceIoResponse> {
+        match self {
+            RdpdrPdu::DeviceControlResponse(pdu) => Some(&pdu.device_io_reply),
+            RdpdrPdu::DeviceCreateResponse(pdu) => Some(&pdu.device_io_reply),
+            RdpdrPdu::ClientDriveQueryInformationResponse(pdu) => Some(&pdu.device_io_response),
+            RdpdrPdu::DeviceCloseResponse(pdu) => Some(&pdu.device_io_response),
+            RdpdrPdu::ClientDriveQueryDirectoryResponse(pdu) => Some(&pdu.device_io_reply),
+            RdpdrPdu::ClientDriveQueryVolumeInformationResponse(pdu) => Some(&pdu.device_io_reply),
+            RdpdrPdu::DeviceReadResponse(pdu) => Some(&pdu.device_io_reply),
+            RdpdrPdu::DeviceWriteResponse(pdu) => Some(&pdu.device_io_reply),
+            RdpdrPdu::ClientDriveSetInformationResponse(pdu) => Some(&pdu.device_io_reply),
+            RdpdrPdu::ClientDriveNotifyChangeDirectoryResponse(pdu) => Some(&pdu.device_io_reply),
+            RdpdrPdu::ClientDriveLockControlResponse(pdu) => Some(&pdu.device_io_reply),
+            RdpdrPdu::VersionAndIdPdu(_)
+            | RdpdrPdu::ClientNameRequest(_)
+            | RdpdrPdu::CoreCapability(_)
+            | RdpdrPdu::ClientDeviceListAnnounce(_)
+            | RdpdrPdu::ClientDeviceListRemove(_)
+            | RdpdrPdu::ServerDeviceAnnounceResponse(_)
+            | RdpdrPdu::UserLoggedOn
+            | RdpdrPdu::DeviceIoRequest(_)
+            | RdpdrPdu::ServerDeviceIoRequest(_)
+            | RdpdrPdu::PrinterCacheData(_)
+            | RdpdrPdu::PrinterUsingXps(_)
+            | RdpdrPdu::EmptyResponse => None,
+        }
+    }
+
     /// Returns the [`SharedHeader`] of the PDU.
     fn header(&self) -> SharedHeader {
         match self {
@@ -74,11 +116,19 @@ impl RdpdrPdu {
                 component: Component::RdpdrCtypCore,
                 packet_id: PacketId::CoreDevicelistAnnounce,
             },
+            RdpdrPdu::ClientDeviceListRemove(_) => SharedHeader {
+                component: Component::RdpdrCtypCore,
+                packet_id: PacketId::CoreDevicelistRemove,
+            },
             RdpdrPdu::ServerDeviceAnnounceResponse(_) => SharedHeader {
                 component: Component::RdpdrCtypCore,
                 packet_id: PacketId::CoreDeviceReply,
             },
-            RdpdrPdu::DeviceIoRequest(_) => SharedHeader {
+            RdpdrPdu::UserLoggedOn => SharedHeader {
+                component: Component::RdpdrCtypCore,
+                packet_id: PacketId::CoreUserLoggedon,
+            },
+            RdpdrPdu::DeviceIoRequest(_) | RdpdrPdu::ServerDeviceIoRequest(_) => SharedHeader {
                 component: Component::RdpdrCtypCore,
                 packet_id: PacketId::CoreDeviceIoRequest,
             },
@@ -91,10 +141,20 @@ impl RdpdrPdu {
             | RdpdrPdu::DeviceReadResponse(_)
             | RdpdrPdu::DeviceWriteResponse(_)
             | RdpdrPdu::ClientDriveSetInformationResponse(_)
+            | RdpdrPdu::ClientDriveNotifyChangeDirectoryResponse(_)
+            | RdpdrPdu::ClientDriveLockControlResponse(_)
             | RdpdrPdu::EmptyResponse => SharedHeader {
                 component: Component::RdpdrCtypCore,
                 packet_id: PacketId::CoreDeviceIoCompletion,
             },
+            RdpdrPdu::PrinterCacheData(_) => SharedHeader {
+                component: Component::RdpdrCtypPrn,
+                packet_id: PacketId::PrnCacheData,
+            },
+            RdpdrPdu::PrinterUsingXps(_) => SharedHeader {
+                component: Component::RdpdrCtypPrn,
+                packet_id: PacketId::PrnUsingXps,
+            },
         }
     }
 }
@@ -109,7 +169,10 @@ impl Decode<'_> for RdpdrPdu {
             PacketId::CoreDeviceReply => Ok(RdpdrPdu::ServerDeviceAnnounceResponse(
                 ServerDeviceAnnounceResponse::decode(src)?,
             )),
+            PacketId::CoreUserLoggedon => Ok(RdpdrPdu::UserLoggedOn),
             PacketId::CoreDeviceIoRequest => Ok(RdpdrPdu::DeviceIoRequest(DeviceIoRequest::decode(src)?)),
+            PacketId::PrnCacheData => Ok(RdpdrPdu::PrinterCacheData(PrinterCacheData::decode(src)?)),
+            PacketId::PrnUsingXps => Ok(RdpdrPdu::PrinterUsingXps(PrinterUsingXps::decode(src)?)),
             _ => Err(unsupported_value_err!(
                 "RdpdrPdu",
                 "PacketId",
@@ -128,8 +191,10 @@ impl Encode for RdpdrPdu {
             RdpdrPdu::ClientNameRequest(pdu) => pdu.encode(dst),
             RdpdrPdu::CoreCapability(pdu) => pdu.encode(dst),
             RdpdrPdu::ClientDeviceListAnnounce(pdu) => pdu.encode(dst),
+            RdpdrPdu::ClientDeviceListRemove(pdu) => pdu.encode(dst),
             RdpdrPdu::ServerDeviceAnnounceResponse(pdu) => pdu.encode(dst),
             RdpdrPdu::DeviceIoRequest(pdu) => pdu.encode(dst),
+            RdpdrPdu::ServerDeviceIoRequest(pdu) => pdu.encode(dst),
             RdpdrPdu::DeviceControlResponse(pdu) => pdu.encode(dst),
             RdpdrPdu::DeviceCreateResponse(pdu) => pdu.encode(dst),
             RdpdrPdu::ClientDriveQueryInformationResponse(pdu) => pdu.encode(dst),
@@ -139,6 +204,11 @@ impl Encode for RdpdrPdu {
             RdpdrPdu::DeviceReadResponse(pdu) => pdu.encode(dst),
             RdpdrPdu::DeviceWriteResponse(pdu) => pdu.encode(dst),
             RdpdrPdu::ClientDriveSetInformationResponse(pdu) => pdu.encode(dst),
+            RdpdrPdu::ClientDriveNotifyChangeDirectoryResponse(pdu) => pdu.encode(dst),
+            RdpdrPdu::ClientDriveLockControlResponse(pdu) => pdu.encode(dst),
+            RdpdrPdu::PrinterCacheData(pdu) => pdu.encode(dst),
+            RdpdrPdu::PrinterUsingXps(pdu) => pdu.encode(dst),
+            RdpdrPdu::UserLoggedOn => Ok(()),
             RdpdrPdu::EmptyResponse => {
                 // https://github.com/FreeRDP/FreeRDP/blob/dfa231c0a55b005af775b833f92f6bcd30363d77/channels/drive/client/drive_main.c#L601
                 dst.write_u32(0);
@@ -153,8 +223,10 @@ impl Encode for RdpdrPdu {
             RdpdrPdu::ClientNameRequest(pdu) => pdu.name(),
             RdpdrPdu::CoreCapability(pdu) => pdu.name(),
             RdpdrPdu::ClientDeviceListAnnounce(pdu) => pdu.name(),
+            RdpdrPdu::ClientDeviceListRemove(pdu) => pdu.name(),
             RdpdrPdu::ServerDeviceAnnounceResponse(pdu) => pdu.name(),
             RdpdrPdu::DeviceIoRequest(pdu) => pdu.name(),
+            RdpdrPdu::ServerDeviceIoRequest(pdu) => pdu.name(),
             RdpdrPdu::DeviceControlResponse(pdu) => pdu.name(),
             RdpdrPdu::DeviceCreateResponse(pdu) => pdu.name(),
             RdpdrPdu::ClientDriveQueryInformationResponse(pdu) => pdu.name(),
@@ -164,6 +236,11 @@ impl Encode for RdpdrPdu {
             RdpdrPdu::DeviceReadResponse(pdu) => pdu.name(),
             RdpdrPdu::DeviceWriteResponse(pdu) => pdu.name(),
             RdpdrPdu::ClientDriveSetInformationResponse(pdu) => pdu.name(),
+            RdpdrPdu::ClientDriveNotifyChangeDirectoryResponse(pdu) => pdu.name(),
+            RdpdrPdu::ClientDriveLockControlResponse(pdu) => pdu.name(),
+            RdpdrPdu::PrinterCacheData(pdu) => pdu.name(),
+            RdpdrPdu::PrinterUsingXps(pdu) => pdu.name(),
+            RdpdrPdu::UserLoggedOn => "DR_CORE_USER_LOGGEDON",
             RdpdrPdu::EmptyResponse => "EmptyResponse",
         }
     }
@@ -175,8 +252,10 @@ impl Encode for RdpdrPdu {
                 RdpdrPdu::ClientNameRequest(pdu) => pdu.size(),
                 RdpdrPdu::CoreCapability(pdu) => pdu.size(),
                 RdpdrPdu::ClientDeviceListAnnounce(pdu) => pdu.size(),
+                RdpdrPdu::ClientDeviceListRemove(pdu) => pdu.size(),
                 RdpdrPdu::ServerDeviceAnnounceResponse(pdu) => pdu.size(),
                 RdpdrPdu::DeviceIoRequest(pdu) => pdu.size(),
+                RdpdrPdu::ServerDeviceIoRequest(pdu) => pdu.size(),
                 RdpdrPdu::DeviceControlResponse(pdu) => pdu.size(),
                 RdpdrPdu::DeviceCreateResponse(pdu) => pdu.size(),
                 RdpdrPdu::ClientDriveQueryInformationResponse(pdu) => pdu.size(),
@@ -186,6 +265,11 @@ impl Encode for RdpdrPdu {
                 RdpdrPdu::DeviceReadResponse(pdu) => pdu.size(),
                 RdpdrPdu::DeviceWriteResponse(pdu) => pdu.size(),
                 RdpdrPdu::ClientDriveSetInformationResponse(pdu) => pdu.size(),
+                RdpdrPdu::ClientDriveNotifyChangeDirectoryResponse(pdu) => pdu.size(),
+                RdpdrPdu::ClientDriveLockControlResponse(pdu) => pdu.size(),
+                RdpdrPdu::PrinterCacheData(pdu) => pdu.size(),
+                RdpdrPdu::PrinterUsingXps(pdu) => pdu.size(),
+                RdpdrPdu::UserLoggedOn => 0,
                 RdpdrPdu::EmptyResponse => size_of::<u32>(),
             }
     }
@@ -208,12 +292,21 @@ impl fmt::Debug for RdpdrPdu {
             Self::ClientDeviceListAnnounce(it) => {
                 write!(f, "RdpdrPdu({:?})", it)
             }
+            Self::ClientDeviceListRemove(it) => {
+                write!(f, "RdpdrPdu({:?})", it)
+            }
             Self::ServerDeviceAnnounceResponse(it) => {
                 write!(f, "RdpdrPdu({:?})", it)
             }
+            Self::UserLoggedOn => {
+                write!(f, "RdpdrPdu(UserLoggedOn)")
+            }
             Self::DeviceIoRequest(it) => {
                 write!(f, "RdpdrPdu({:?})", it)
             }
+            Self::ServerDeviceIoRequest(it) => {
+                write!(f, "RdpdrPdu({:?})", it)
+            }
             Self::DeviceControlResponse(it) => {
                 write!(f, "RdpdrPdu({:?})", it)
             }
@@ -241,6 +334,18 @@ impl fmt::Debug for RdpdrPdu {
             Self::ClientDriveSetInformationResponse(it) => {
                 write!(f, "RdpdrPdu({:?})", it)
             }
+            Self::ClientDriveNotifyChangeDirectoryResponse(it) => {
+                write!(f, "RdpdrPdu({:?})", it)
+            }
+            Self::ClientDriveLockControlResponse(it) => {
+                write!(f, "RdpdrPdu({:?})", it)
+            }
+            Self::PrinterCacheData(it) => {
+                write!(f, "RdpdrPdu({:?})", it)
+            }
+            Self::PrinterUsingXps(it) => {
+                write!(f, "RdpdrPdu({:?})", it)
+            }
             Self::EmptyResponse => {
                 write!(f, "RdpdrPdu(EmptyResponse)")
             }
@@ -248,6 +353,18 @@ impl fmt::Debug for RdpdrPdu {
     }
 }
 
+impl From<ClientDeviceListRemove> for RdpdrPdu {
+    fn from(value: ClientDeviceListRemove) -> Self {
+        Self::ClientDeviceListRemove(value)
+    }
+}
+
+impl From<ServerDeviceIoRequest> for RdpdrPdu {
+    fn from(value: ServerDeviceIoRequest) -> Self {
+        Self::ServerDeviceIoRequest(value)
+    }
+}
+
 impl From<DeviceControlResponse> for RdpdrPdu {
     fn from(value: DeviceControlResponse) -> Self {
         Self::DeviceControlResponse(value)
@@ -302,6 +419,30 @@ impl From<ClientDriveSetInformationResponse> for RdpdrPdu {
     }
 }
 
+impl From<ClientDriveNotifyChangeDirectoryResponse> for RdpdrPdu {
+    fn from(value: ClientDriveNotifyChangeDirectoryResponse) -> Self {
+        Self::ClientDriveNotifyChangeDirectoryResponse(value)
+    }
+}
+
+impl From<ClientDriveLockControlResponse> for RdpdrPdu {
+    fn from(value: ClientDriveLockControlResponse) -> Self {
+        Self::ClientDriveLockControlResponse(value)
+    }
+}
+
+impl From<PrinterCacheData> for RdpdrPdu {
+    fn from(value: PrinterCacheData) -> Self {
+        Self::PrinterCacheData(value)
+    }
+}
+
+impl From<PrinterUsingXps> for RdpdrPdu {
+    fn from(value: PrinterUsingXps) -> Self {
+        Self::PrinterUsingXps(value)
+    }
+}
+
 /// [2.2.1.1] Shared Header (RDPDR_HEADER), a header that is shared by all RDPDR PDUs.
 ///
 /// [2.2.1.1]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpefs/29d4108f-8163-4a67-8271-e48c4b9c2a7c
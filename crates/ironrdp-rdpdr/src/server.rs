@@ -0,0 +1,407 @@
+use std::collections::HashMap;
+
+use ironrdp_core::impl_as_any;
+use ironrdp_core::{ensure_size, DecodeResult, ReadCursor};
+use ironrdp_pdu::decode_err;
+use ironrdp_pdu::gcc::ChannelName;
+use ironrdp_pdu::pdu_other_err;
+use ironrdp_pdu::PduResult;
+use ironrdp_svc::{CompressionCondition, SvcMessage, SvcProcessor, SvcProcessorMessages, SvcServerProcessor};
+
+use crate::pdu::efs::{
+    Capabilities, ClientDeviceListAnnounce, ClientDeviceListRemove, ClientDriveQueryDirectoryResponse,
+    ClientDriveQueryInformationResponse, ClientNameRequest, CoreCapability, DeviceAnnounceHeader, DeviceCloseResponse,
+    DeviceCreateResponse, DeviceIoResponse, DeviceReadResponse, DeviceWriteResponse, FileInformationClassLevel,
+    Information, NtStatus, ServerDeviceAnnounceResponse, ServerDeviceIoRequest, VersionAndIdPdu,
+};
+use crate::pdu::{PacketId, RdpdrPdu, SharedHeader};
+
+pub type RdpdrSvcMessages = SvcProcessorMessages<RdpdrServer>;
+
+/// Message sent by the event loop.
+#[derive(Debug)]
+pub enum RdpdrServerMessage {
+    /// Sends a request to one of the devices redirected by the client.
+    ///
+    /// The completion ID of the request is chosen by the sender, and must not be in use by an outstanding request.
+    IoRequest(ServerDeviceIoRequest),
+}
+
+/// The completion of a request sent with [`RdpdrServer::io_request`].
+///
+/// Failed requests only carry the status of their reply header, the other fields being left empty.
+#[derive(Debug, PartialEq, Clone)]
+pub enum DeviceIoCompletion {
+    Create(DeviceCreateResponse),
+    Close(DeviceCloseResponse),
+    Read(DeviceReadResponse),
+    Write(DeviceWriteResponse),
+    QueryInformation(ClientDriveQueryInformationResponse),
+    QueryDirectory(ClientDriveQueryDirectoryResponse),
+    DeviceControl {
+        device_io_reply: DeviceIoResponse,
+        output_buffer: Vec<u8>,
+    },
+}
+
+impl DeviceIoCompletion {
+    pub fn device_io_response(&self) -> &DeviceIoResponse {
+        match self {
+            Self::Create(rsp) => &rsp.device_io_reply,
+            Self::Close(rsp) => &rsp.device_io_response,
+            Self::Read(rsp) => &rsp.device_io_reply,
+            Self::Write(rsp) => &rsp.device_io_reply,
+            Self::QueryInformation(rsp) => &rsp.device_io_response,
+            Self::QueryDirectory(rsp) => &rsp.device_io_reply,
+            Self::DeviceControl { device_io_reply, .. } => device_io_reply,
+        }
+    }
+
+    fn failed(kind: RequestKind, device_io_reply: DeviceIoResponse) -> Self {
+        match kind {
+            RequestKind::Create => Self::Create(DeviceCreateResponse {
+                device_io_reply,
+                file_id: 0,
+                information: Information::empty(),
+            }),
+            RequestKind::Close => Self::Close(DeviceCloseResponse {
+                device_io_response: device_io_reply,
+            }),
+            RequestKind::Read => Self::Read(DeviceReadResponse {
+                device_io_reply,
+                read_data: Vec::new(),
+            }),
+            RequestKind::Write => Self::Write(DeviceWriteResponse {
+                device_io_reply,
+                length: 0,
+            }),
+            RequestKind::QueryInformation(_) => Self::QueryInformation(ClientDriveQueryInformationResponse {
+                device_io_response: device_io_reply,
+                buffer: None,
+            }),
+            RequestKind::QueryDirectory(_) => Self::QueryDirectory(ClientDriveQueryDirectoryResponse {
+                device_io_reply,
+                buffer: None,
+            }),
+            RequestKind::DeviceControl => Self::DeviceControl {
+                device_io_reply,
+                output_buffer: Vec::new(),
+            },
+        }
+    }
+
+    fn decode(kind: RequestKind, device_io_reply: DeviceIoResponse, src: &mut ReadCursor<'_>) -> DecodeResult<Self> {
+        // The body of a failed request is not always sent, and is meaningless anyway.
+        if device_io_reply.io_status != NtStatus::SUCCESS {
+            return Ok(Self::failed(kind, device_io_reply));
+        }
+
+        let completion = match kind {
+            RequestKind::Create => Self::Create(DeviceCreateResponse::decode(device_io_reply, src)?),
+            RequestKind::Close => Self::Close(DeviceCloseResponse::decode(device_io_reply)),
+            RequestKind::Read => Self::Read(DeviceReadResponse::decode(device_io_reply, src)?),
+            RequestKind::Write => Self::Write(DeviceWriteResponse::decode(device_io_reply, src)?),
+            RequestKind::QueryInformation(level) => Self::QueryInformation(
+                ClientDriveQueryInformationResponse::decode(device_io_reply, level, src)?,
+            ),
+            RequestKind::QueryDirectory(level) => {
+                Self::QueryDirectory(ClientDriveQueryDirectoryResponse::decode(device_io_reply, level, src)?)
+            }
+            RequestKind::DeviceControl => {
+                // OutputBufferLength, followed by the output buffer.
+                let length = if src.len() >= 4 { src.read_u32() as usize } else { 0 };
+                ensure_size!(ctx: "DR_CONTROL_RSP", in: src, size: length);
+                Self::DeviceControl {
+                    device_io_reply,
+                    output_buffer: src.read_slice(length).to_vec(),
+                }
+            }
+        };
+
+        Ok(completion)
+    }
+}
+
+pub trait RdpdrServerHandler: Send + std::fmt::Debug {
+    /// Called for each device announced by the client, which is only redirected if `true` is returned.
+    fn device_announced(&mut self, device: &DeviceAnnounceHeader) -> bool;
+
+    /// Called when the client stops redirecting a device.
+    ///
+    /// The requests still outstanding on the device are completed with [`NtStatus::CANCELLED`] beforehand.
+    fn device_removed(&mut self, device_id: u32);
+
+    /// Called when a request sent with [`RdpdrServer::io_request`] completes.
+    fn io_completed(&mut self, completion: DeviceIoCompletion);
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum RdpdrState {
+    Start,
+    WaitingForClientName,
+    WaitingForClientCapability,
+    Ready,
+}
+
+/// The kind of an outstanding request, which tells how to decode its completion.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum RequestKind {
+    Create,
+    Close,
+    Read,
+    Write,
+    QueryInformation(FileInformationClassLevel),
+    QueryDirectory(FileInformationClassLevel),
+    DeviceControl,
+}
+
+impl RequestKind {
+    fn of(request: &ServerDeviceIoRequest) -> Self {
+        match request {
+            ServerDeviceIoRequest::Create(_) => Self::Create,
+            ServerDeviceIoRequest::Close(_) => Self::Close,
+            ServerDeviceIoRequest::Read(_) => Self::Read,
+            ServerDeviceIoRequest::Write(_) => Self::Write,
+            ServerDeviceIoRequest::QueryInformation(req) => Self::QueryInformation(req.file_info_class_lvl),
+            ServerDeviceIoRequest::QueryDirectory(req) => Self::QueryDirectory(req.file_info_class_lvl),
+            ServerDeviceIoRequest::DeviceControl { .. } => Self::DeviceControl,
+        }
+    }
+}
+
+/// Server side of the device redirection channel.
+///
+/// Accepts the devices announced by the client through [`RdpdrServerHandler::device_announced`], and forwards
+/// the requests sent to them, reporting their completion through [`RdpdrServerHandler::io_completed`].
+#[derive(Debug)]
+pub struct RdpdrServer {
+    handler: Box<dyn RdpdrServerHandler>,
+    state: RdpdrState,
+    client_id: u32,
+    computer_name: Option<String>,
+    devices: HashMap<u32, DeviceAnnounceHeader>,
+    /// Maps the completion ID of each outstanding request to its device ID and kind.
+    outstanding_requests: HashMap<u32, (u32, RequestKind)>,
+}
+
+impl_as_any!(RdpdrServer);
+
+impl RdpdrServer {
+    pub const NAME: ChannelName = ChannelName::from_static(b"rdpdr\0\0\0");
+
+    /// The client ID proposed in the Server Announce Request.
+    const CLIENT_ID: u32 = 1;
+
+    pub fn new(handler: Box<dyn RdpdrServerHandler>) -> Self {
+        Self {
+            handler,
+            state: RdpdrState::Start,
+            client_id: Self::CLIENT_ID,
+            computer_name: None,
+            devices: HashMap::new(),
+            outstanding_requests: HashMap::new(),
+        }
+    }
+
+    /// The name of the client computer, once received.
+    pub fn computer_name(&self) -> Option<&str> {
+        self.computer_name.as_deref()
+    }
+
+    /// The devices currently redirected by the client.
+    pub fn devices(&self) -> impl Iterator<Item = &DeviceAnnounceHeader> {
+        self.devices.values()
+    }
+
+    /// Returns the message sending `request` to the client.
+    ///
+    /// A request for a device that is not redirected is completed right away with [`NtStatus::NO_SUCH_FILE`].
+    pub fn io_request(&mut self, request: ServerDeviceIoRequest) -> PduResult<RdpdrSvcMessages> {
+        let header = request.device_io_request();
+        let (device_id, completion_id) = (header.device_id, header.completion_id);
+        let kind = RequestKind::of(&request);
+
+        if !self.devices.contains_key(&device_id) {
+            debug!(device_id, completion_id, "Request for an unknown device");
+            let reply = DeviceIoResponse::new(header.clone(), NtStatus::NO_SUCH_FILE);
+            self.handler.io_completed(DeviceIoCompletion::failed(kind, reply));
+            return Ok(RdpdrSvcMessages::new(Vec::new()));
+        }
+
+        if self.outstanding_requests.contains_key(&completion_id) {
+            return Err(pdu_other_err!("Rdpdr", "completion ID already in use"));
+        }
+        self.outstanding_requests.insert(completion_id, (device_id, kind));
+
+        let pdu = RdpdrPdu::ServerDeviceIoRequest(request);
+        trace!("sending {:?}", pdu);
+        Ok(RdpdrSvcMessages::new(vec![SvcMessage::from(pdu)]))
+    }
+
+    fn handle_client_announce_reply(&mut self, pdu: VersionAndIdPdu) -> PduResult<Vec<SvcMessage>> {
+        self.expect_state(RdpdrState::WaitingForClientName)?;
+        self.client_id = pdu.client_id;
+        Ok(Vec::new())
+    }
+
+    fn handle_client_name(&mut self, pdu: ClientNameRequest) -> PduResult<Vec<SvcMessage>> {
+        self.expect_state(RdpdrState::WaitingForClientName)?;
+        self.computer_name = Some(pdu.computer_name().to_owned());
+        self.state = RdpdrState::WaitingForClientCapability;
+
+        let capability =
+            RdpdrPdu::CoreCapability(CoreCapability::new_request(Capabilities::new_server().clone_inner()));
+        let client_id_confirm =
+            RdpdrPdu::VersionAndIdPdu(VersionAndIdPdu::new_server_client_id_confirm(self.client_id));
+        trace!("sending {:?}", capability);
+        trace!("sending {:?}", client_id_confirm);
+
+        Ok(vec![SvcMessage::from(capability), SvcMessage::from(client_id_confirm)])
+    }
+
+    fn handle_client_capability(&mut self, pdu: CoreCapability) -> PduResult<Vec<SvcMessage>> {
+        self.expect_state(RdpdrState::WaitingForClientCapability)?;
+        self.state = RdpdrState::Ready;
+
+        if pdu.user_logged_on_supported() {
+            let res = RdpdrPdu::UserLoggedOn;
+            trace!("sending {:?}", res);
+            Ok(vec![SvcMessage::from(res)])
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    fn handle_device_list_announce(&mut self, pdu: ClientDeviceListAnnounce) -> PduResult<Vec<SvcMessage>> {
+        let mut messages = Vec::with_capacity(pdu.device_list.len());
+
+        for device in pdu.device_list {
+            let device_id = device.device_id();
+            let result_code = if self.handler.device_announced(&device) {
+                debug!(device_id, name = %device.name(), "Device redirected");
+                self.devices.insert(device_id, device);
+                NtStatus::SUCCESS
+            } else {
+                debug!(device_id, name = %device.name(), "Device rejected");
+                NtStatus::ACCESS_DENIED
+            };
+
+            let res = RdpdrPdu::ServerDeviceAnnounceResponse(ServerDeviceAnnounceResponse { device_id, result_code });
+            trace!("sending {:?}", res);
+            messages.push(SvcMessage::from(res));
+        }
+
+        Ok(messages)
+    }
+
+    fn handle_device_list_remove(&mut self, pdu: ClientDeviceListRemove) -> PduResult<Vec<SvcMessage>> {
+        for device_id in pdu.device_ids {
+            if self.devices.remove(&device_id).is_none() {
+                continue;
+            }
+
+            let mut cancelled = self
+                .outstanding_requests
+                .iter()
+                .filter(|(_, (id, _))| *id == device_id)
+                .map(|(completion_id, (_, kind))| (*completion_id, *kind))
+                .collect::<Vec<_>>();
+            cancelled.sort_unstable_by_key(|(completion_id, _)| *completion_id);
+
+            for (completion_id, kind) in cancelled {
+                self.outstanding_requests.remove(&completion_id);
+                let reply = DeviceIoResponse {
+                    device_id,
+                    completion_id,
+                    io_status: NtStatus::CANCELLED,
+                };
+                self.handler.io_completed(DeviceIoCompletion::failed(kind, reply));
+            }
+
+            debug!(device_id, "Device removed");
+            self.handler.device_removed(device_id);
+        }
+
+        Ok(Vec::new())
+    }
+
+    fn handle_device_io_completion(&mut self, src: &mut ReadCursor<'_>) -> PduResult<Vec<SvcMessage>> {
+        let reply = DeviceIoResponse::decode(src).map_err(|e| decode_err!(e))?;
+
+        let Some((_, kind)) = self.outstanding_requests.remove(&reply.completion_id) else {
+            warn!(completion_id = reply.completion_id, "Completion of an unknown request");
+            return Ok(Vec::new());
+        };
+
+        let completion = DeviceIoCompletion::decode(kind, reply, src).map_err(|e| decode_err!(e))?;
+        self.handler.io_completed(completion);
+
+        Ok(Vec::new())
+    }
+
+    fn expect_state(&self, state: RdpdrState) -> PduResult<()> {
+        if self.state == state {
+            Ok(())
+        } else {
+            Err(pdu_other_err!("Rdpdr", "received unexpected packet"))
+        }
+    }
+}
+
+impl SvcProcessor for RdpdrServer {
+    fn channel_name(&self) -> ChannelName {
+        Self::NAME
+    }
+
+    fn compression_condition(&self) -> CompressionCondition {
+        CompressionCondition::WhenRdpDataIsCompressed
+    }
+
+    fn start(&mut self) -> PduResult<Vec<SvcMessage>> {
+        if self.state != RdpdrState::Start {
+            return Ok(Vec::new());
+        }
+        self.state = RdpdrState::WaitingForClientName;
+
+        let res = RdpdrPdu::VersionAndIdPdu(VersionAndIdPdu::new_server_announce(self.client_id));
+        trace!("sending {:?}", res);
+        Ok(vec![SvcMessage::from(res)])
+    }
+
+    fn process(&mut self, payload: &[u8]) -> PduResult<Vec<SvcMessage>> {
+        let mut src = ReadCursor::new(payload);
+        let header = SharedHeader::decode(&mut src).map_err(|e| decode_err!(e))?;
+        debug!("Received {:?}", header);
+
+        // Only the packets sent by clients are expected, some of them sharing their ID with a server packet.
+        match header.packet_id {
+            PacketId::CoreClientidConfirm => {
+                let pdu = VersionAndIdPdu::decode_client_announce_reply(&mut src).map_err(|e| decode_err!(e))?;
+                self.handle_client_announce_reply(pdu)
+            }
+            PacketId::CoreClientName => {
+                let pdu = ClientNameRequest::decode(&mut src).map_err(|e| decode_err!(e))?;
+                self.handle_client_name(pdu)
+            }
+            PacketId::CoreClientCapability => {
+                let pdu = CoreCapability::decode(header, &mut src).map_err(|e| decode_err!(e))?;
+                self.handle_client_capability(pdu)
+            }
+            PacketId::CoreDevicelistAnnounce => {
+                let pdu = ClientDeviceListAnnounce::decode(&mut src).map_err(|e| decode_err!(e))?;
+                self.handle_device_list_announce(pdu)
+            }
+            PacketId::CoreDevicelistRemove => {
+                let pdu = ClientDeviceListRemove::decode(&mut src).map_err(|e| decode_err!(e))?;
+                self.handle_device_list_remove(pdu)
+            }
+            PacketId::CoreDeviceIoCompletion => self.handle_device_io_completion(&mut src),
+            _ => {
+                debug!(packet_id = %header.packet_id, "Ignoring unsupported packet");
+                Ok(Vec::new())
+            }
+        }
+    }
+}
+
+impl SvcServerProcessor for RdpdrServer {}
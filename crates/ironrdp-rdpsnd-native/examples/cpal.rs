@@ -1,9 +1,10 @@
-use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
 use anyhow::Context;
 use cpal::traits::StreamTrait;
+use ironrdp_rdpsnd::jitter_buffer::{JitterBuffer, JitterBufferConfig};
 use ironrdp_rdpsnd::pdu::{AudioFormat, WaveFormat};
 use ironrdp_rdpsnd_native::cpal::make_stream;
 use tracing::debug;
@@ -40,15 +41,24 @@ fn main() -> anyhow::Result<()> {
         bits_per_sample: 16,
         data: None,
     };
-    let (tx, rx) = mpsc::channel();
-    let stream = make_stream(&rx_format, rx).unwrap();
+    let buffer = Arc::new(Mutex::new(JitterBuffer::new(
+        rx_format.n_channels,
+        rx_format.n_samples_per_sec,
+        JitterBufferConfig::default(),
+    )));
+    let stream = make_stream(&rx_format, Arc::clone(&buffer), |chunk_no| {
+        debug!(chunk_no, "Played a chunk")
+    })
+    .unwrap();
 
     let producer = thread::spawn(move || {
-        let data_chunks = vec![vec![1u8, 2, 3], vec![4, 5, 6], vec![7, 8, 9]];
-        for chunk in data_chunks {
-            tx.send(chunk).expect("failed to send data chunk");
+        // 100 ms of a quiet square wave per chunk.
+        let chunk = (0..2205).flat_map(|i| if i % 50 < 25 { [2000, 2000] } else { [-2000, -2000] });
+        let chunk = chunk.collect::<Vec<i16>>();
+        for (chunk_no, ts) in (0..30u32).zip((0u16..).step_by(100)) {
+            buffer.lock().expect("poisoned").push(ts, chunk.clone(), chunk_no);
             debug!("Sent a chunk");
-            thread::sleep(Duration::from_secs(1)); // Simulating work
+            thread::sleep(Duration::from_millis(100)); // Simulating work
         }
     });
 
@@ -1,23 +1,32 @@
 use std::borrow::Cow;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{self, Receiver, Sender};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
+use std::time::Instant;
 
-use anyhow::{bail, Context};
-use cpal::traits::{DeviceTrait, HostTrait};
+use anyhow::{anyhow, bail, Context};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::{SampleFormat, Stream, StreamConfig};
-use ironrdp_rdpsnd::client::RdpsndClientHandler;
+use ironrdp_audin::client::{AudinClientHandler, AudinMessage, AudinMessageProxy};
+use ironrdp_audin::AudinError;
+use ironrdp_rdpsnd::client::{RdpsndClientHandler, RdpsndMessage, RdpsndMessageProxy, WaveId};
+use ironrdp_rdpsnd::codec::{Codec, Decoder, Encoder};
+use ironrdp_rdpsnd::jitter_buffer::{JitterBuffer, JitterBufferConfig};
 use ironrdp_rdpsnd::pdu::{AudioFormat, PitchPdu, VolumePdu, WaveFormat};
 
+type WaveTag = Option<(WaveId, Instant)>;
+
 #[derive(Debug)]
 pub struct RdpsndBackend {
     // Unfortunately, Stream is not `Send`, so we move it to a separate thread.
     stream_handle: Option<JoinHandle<()>>,
     stream_ended: Arc<AtomicBool>,
-    tx: Option<Sender<Vec<u8>>>,
+    buffer: Option<Arc<Mutex<JitterBuffer<WaveTag>>>>,
     format: Option<AudioFormat>,
+    decoder: Option<Decoder>,
+    proxy: Option<Arc<dyn RdpsndMessageProxy>>,
+    volume: Option<VolumePdu>,
+    pitch: Option<PitchPdu>,
 }
 
 impl Default for RdpsndBackend {
@@ -29,88 +38,332 @@ impl Default for RdpsndBackend {
 impl RdpsndBackend {
     pub fn new() -> Self {
         Self {
-            tx: None,
+            buffer: None,
             format: None,
+            decoder: None,
             stream_handle: None,
             stream_ended: Arc::new(AtomicBool::new(false)),
+            proxy: None,
+            volume: None,
+            pitch: None,
         }
     }
-}
 
-impl Drop for RdpsndBackend {
-    fn drop(&mut self) {
-        self.close();
+    /// Creates a backend confirming waves once they are played, through the given proxy.
+    ///
+    /// Without a proxy, waves are confirmed as soon as they are received.
+    pub fn with_message_proxy(proxy: impl RdpsndMessageProxy + 'static) -> Self {
+        let mut backend = Self::new();
+        backend.proxy = Some(Arc::new(proxy));
+        backend
     }
-}
 
-impl RdpsndClientHandler for RdpsndBackend {
-    fn wave(&mut self, format: &AudioFormat, _ts: u32, data: Cow<'_, [u8]>) {
+    fn start(&mut self, format: &AudioFormat) -> anyhow::Result<()> {
+        let decoder = Decoder::new(format).context("unsupported audio format")?;
+        // Waves are decoded to 16-bit PCM before being played.
+        let pcm_format = Codec::Pcm
+            .audio_format(format.n_channels, format.n_samples_per_sec)
+            .context("unsupported channel count")?;
+
+        let mut buffer = JitterBuffer::new(
+            pcm_format.n_channels,
+            pcm_format.n_samples_per_sec,
+            JitterBufferConfig::default(),
+        );
+        if let Some(volume) = self.volume.clone() {
+            buffer.set_volume(volume);
+        }
+        if let Some(pitch) = self.pitch.clone() {
+            buffer.set_pitch(pitch);
+        }
+        let buffer = Arc::new(Mutex::new(buffer));
+
+        self.buffer = Some(Arc::clone(&buffer));
+        self.format = Some(format.clone());
+        self.decoder = Some(decoder);
+        self.stream_ended.store(false, Ordering::Relaxed);
+        let stream_ended = Arc::clone(&self.stream_ended);
+        let proxy = self.proxy.clone();
+        self.stream_handle = Some(thread::spawn(move || {
+            let stream = match make_stream(&pcm_format, buffer, move |tag| confirm_played(proxy.as_deref(), tag)) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    error!(error = format!("{e:#}"));
+                    return;
+                }
+            };
+            debug!("Stream thread parking loop");
+            while !stream_ended.load(Ordering::Relaxed) {
+                thread::park();
+            }
+            debug!("Stream thread unparked");
+            drop(stream);
+        }));
+
+        Ok(())
+    }
+
+    /// Queues a wave for playback, returning `true` if it will be confirmed once played.
+    fn play(&mut self, format: &AudioFormat, ts: u32, data: &[u8], id: Option<WaveId>) -> bool {
         if Some(format) != self.format.as_ref() {
             debug!(?format, "New audio format");
             self.close();
         }
 
         if self.stream_handle.is_none() {
-            let (tx, rx) = mpsc::channel();
-            self.tx = Some(tx);
-            self.format = Some(format.clone());
-            let format = format.clone();
-            self.stream_ended.store(false, Ordering::Relaxed);
-            let stream_ended = Arc::clone(&self.stream_ended);
-            self.stream_handle = Some(thread::spawn(move || {
-                let stream = match make_stream(&format, rx) {
-                    Ok(stream) => stream,
-                    Err(e) => {
-                        error!(error = format!("{e:#}"));
-                        return;
-                    }
-                };
-                debug!("Stream thread parking loop");
-                while !stream_ended.load(Ordering::Relaxed) {
-                    thread::park();
-                }
-                debug!("Stream thread unparked");
-                drop(stream);
-            }));
+            if let Err(error) = self.start(format) {
+                error!(?format, error = format!("{error:#}"));
+                return false;
+            }
         }
 
-        if let Some(ref tx) = self.tx {
-            if let Err(error) = tx.send(data.to_vec()) {
-                error!(%error);
+        let (Some(buffer), Some(decoder)) = (self.buffer.as_ref(), self.decoder.as_mut()) else {
+            return false;
+        };
+
+        let samples = match decoder.decode(data) {
+            Ok(samples) => samples,
+            Err(error) => {
+                error!(%error, "Failed to decode wave");
+                return false;
             }
         };
+
+        let tag = id.map(|id| (id, Instant::now()));
+        // Wave timestamps are 16-bit
+        #[allow(clippy::cast_possible_truncation)]
+        let ts = ts as u16;
+        let dropped = buffer.lock().expect("poisoned").push(ts, samples, tag);
+        for tag in dropped {
+            confirm_played(self.proxy.as_deref(), tag);
+        }
+
+        self.proxy.is_some()
+    }
+}
+
+impl Drop for RdpsndBackend {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl RdpsndClientHandler for RdpsndBackend {
+    fn supported_formats(&self, server_formats: &[AudioFormat]) -> Vec<AudioFormat> {
+        server_formats
+            .iter()
+            .filter(|format| Decoder::new(format).is_some())
+            .cloned()
+            .collect()
+    }
+
+    fn wave(&mut self, format: &AudioFormat, ts: u32, data: Cow<'_, [u8]>) {
+        self.play(format, ts, &data, None);
+    }
+
+    fn queue_wave(&mut self, format: &AudioFormat, ts: u32, data: Cow<'_, [u8]>, id: WaveId) -> bool {
+        self.play(format, ts, &data, Some(id))
     }
 
     fn set_volume(&mut self, volume: VolumePdu) {
         debug!(?volume);
+        if let Some(buffer) = self.buffer.as_ref() {
+            buffer.lock().expect("poisoned").set_volume(volume.clone());
+        }
+        self.volume = Some(volume);
     }
 
     fn set_pitch(&mut self, pitch: PitchPdu) {
         debug!(?pitch);
+        if let Some(buffer) = self.buffer.as_ref() {
+            buffer.lock().expect("poisoned").set_pitch(pitch.clone());
+        }
+        self.pitch = Some(pitch);
     }
 
     fn close(&mut self) {
-        self.tx = None;
+        self.decoder = None;
+        self.format = None;
         if let Some(stream) = self.stream_handle.take() {
             self.stream_ended.store(true, Ordering::Relaxed);
             stream.thread().unpark();
             stream.join().unwrap();
         }
+        // The server still expects a confirm for the waves that will not be played.
+        if let Some(buffer) = self.buffer.take() {
+            let dropped = buffer.lock().expect("poisoned").clear();
+            for tag in dropped {
+                confirm_played(self.proxy.as_deref(), tag);
+            }
+        }
     }
 }
 
-#[doc(hidden)]
-pub fn make_stream(rx_format: &AudioFormat, rx: Receiver<Vec<u8>>) -> anyhow::Result<Stream> {
-    if rx_format.format != WaveFormat::PCM {
-        bail!("only PCM formats supported");
-    }
-    let sample_format = match rx_format.bits_per_sample {
-        8 => SampleFormat::U8,
-        16 => SampleFormat::I16,
-        _ => {
-            bail!("only PCM 8/16 bits formats supported");
+fn confirm_played(proxy: Option<&dyn RdpsndMessageProxy>, tag: WaveTag) {
+    if let (Some(proxy), Some((id, received))) = (proxy, tag) {
+        proxy.send_rdpsnd_message(RdpsndMessage::WavePlayed(id.confirm(received.elapsed())));
+    }
+}
+
+#[derive(Debug)]
+pub struct CaptureError(anyhow::Error);
+
+impl std::fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#}", self.0)
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+/// Audio input backend capturing from the default input device.
+#[derive(Debug)]
+pub struct AudinBackend {
+    proxy: Arc<dyn AudinMessageProxy>,
+    // Same as for `RdpsndBackend`, the input stream is kept in a separate thread.
+    stream_handle: Option<JoinHandle<()>>,
+    stream_ended: Arc<AtomicBool>,
+    frames_per_packet: u32,
+}
+
+impl AudinBackend {
+    pub fn new(proxy: impl AudinMessageProxy + 'static) -> Self {
+        Self {
+            proxy: Arc::new(proxy),
+            stream_handle: None,
+            stream_ended: Arc::new(AtomicBool::new(false)),
+            frames_per_packet: 0,
+        }
+    }
+
+    fn start(&mut self, format: &AudioFormat) -> Result<(), Box<dyn AudinError>> {
+        self.close();
+
+        // Fail early instead of in the stream thread, so the server is told the capture could not be opened.
+        if Encoder::new(format).is_none() {
+            return Err(Box::new(CaptureError(anyhow!("unsupported audio format"))));
+        }
+
+        let format = format.clone();
+        let proxy = Arc::clone(&self.proxy);
+        let frames_per_packet = self.frames_per_packet as usize;
+        self.stream_ended.store(false, Ordering::Relaxed);
+        let stream_ended = Arc::clone(&self.stream_ended);
+        self.stream_handle = Some(thread::spawn(move || {
+            let stream = match make_input_stream(&format, frames_per_packet, Arc::clone(&proxy)) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    error!(error = format!("{e:#}"));
+                    proxy.send_audin_message(AudinMessage::Error(Box::new(CaptureError(e))));
+                    return;
+                }
+            };
+            while !stream_ended.load(Ordering::Relaxed) {
+                thread::park();
+            }
+            drop(stream);
+        }));
+
+        Ok(())
+    }
+}
+
+impl Drop for AudinBackend {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl AudinClientHandler for AudinBackend {
+    fn supported_formats(&self, server_formats: &[AudioFormat]) -> Vec<AudioFormat> {
+        server_formats
+            .iter()
+            .filter(|format| Encoder::new(format).is_some())
+            .cloned()
+            .collect()
+    }
+
+    fn open(&mut self, format: &AudioFormat, frames_per_packet: u32) -> Result<(), Box<dyn AudinError>> {
+        self.frames_per_packet = frames_per_packet;
+        self.start(format)
+    }
+
+    fn set_format(&mut self, format: &AudioFormat) -> Result<(), Box<dyn AudinError>> {
+        self.start(format)
+    }
+
+    fn close(&mut self) {
+        if let Some(stream) = self.stream_handle.take() {
+            self.stream_ended.store(true, Ordering::Relaxed);
+            stream.thread().unpark();
+            stream.join().unwrap();
         }
+    }
+}
+
+/// Captures 16-bit PCM samples, and sends them encoded with `tx_format` in packets of `frames_per_packet` frames.
+#[doc(hidden)]
+pub fn make_input_stream(
+    tx_format: &AudioFormat,
+    frames_per_packet: usize,
+    proxy: Arc<dyn AudinMessageProxy>,
+) -> anyhow::Result<Stream> {
+    let mut encoder = Encoder::new(tx_format).context("unsupported audio format")?;
+
+    let host = cpal::default_host();
+    let device = host.default_input_device().context("no default input device")?;
+    let default_config = device.default_input_config()?;
+    debug!(?default_config);
+
+    let config = StreamConfig {
+        channels: tx_format.n_channels,
+        sample_rate: cpal::SampleRate(tx_format.n_samples_per_sec),
+        buffer_size: cpal::BufferSize::Default,
     };
+    debug!(?config);
+
+    let packet_len = frames_per_packet
+        .max(1)
+        .saturating_mul(usize::from(tx_format.n_channels));
+    let mut pending = Vec::with_capacity(packet_len);
+    let stream = device
+        .build_input_stream_raw(
+            &config,
+            SampleFormat::I16,
+            move |data, _info: &cpal::InputCallbackInfo| {
+                let Some(samples) = data.as_slice::<i16>() else {
+                    return;
+                };
+                pending.extend_from_slice(samples);
+                while pending.len() >= packet_len {
+                    let packet = pending.drain(..packet_len).collect::<Vec<_>>();
+                    let data = encoder.encode(&packet);
+                    // Block codecs keep the samples until a whole block is available.
+                    if !data.is_empty() {
+                        proxy.send_audin_message(AudinMessage::Data(data));
+                    }
+                }
+            },
+            |error| error!(%error),
+            None,
+        )
+        .context("failed to setup input stream")?;
+
+    stream.play().context("failed to start input stream")?;
+
+    Ok(stream)
+}
+
+/// Plays 16-bit PCM samples from `buffer`, calling `on_played` with the tag of each wave once played.
+#[doc(hidden)]
+pub fn make_stream<T: Send + 'static>(
+    rx_format: &AudioFormat,
+    buffer: Arc<Mutex<JitterBuffer<T>>>,
+    mut on_played: impl FnMut(T) + Send + 'static,
+) -> anyhow::Result<Stream> {
+    if rx_format.format != WaveFormat::PCM || rx_format.bits_per_sample != 16 {
+        bail!("only PCM 16 bits formats supported");
+    }
 
     let host = cpal::default_host();
     let device = host.default_output_device().context("no default output device")?;
@@ -120,7 +373,6 @@ pub fn make_stream(rx_format: &AudioFormat, rx: Receiver<Vec<u8>>) -> anyhow::Re
     let default_config = device.default_output_config()?;
     debug!(?default_config);
 
-    let mut rx = RxBuffer::new(rx);
     let config = StreamConfig {
         channels: rx_format.n_channels,
         sample_rate: cpal::SampleRate(rx_format.n_samples_per_sec),
@@ -131,10 +383,13 @@ pub fn make_stream(rx_format: &AudioFormat, rx: Receiver<Vec<u8>>) -> anyhow::Re
     let stream = device
         .build_output_stream_raw(
             &config,
-            sample_format,
+            SampleFormat::I16,
             move |data, _info: &cpal::OutputCallbackInfo| {
-                let data = data.bytes_mut();
-                rx.fill(data)
+                let Some(samples) = data.as_slice_mut::<i16>() else {
+                    return;
+                };
+                let played = buffer.lock().expect("poisoned").fill(samples);
+                played.into_iter().for_each(&mut on_played);
             },
             |error| error!(%error),
             None,
@@ -143,57 +398,3 @@ pub fn make_stream(rx_format: &AudioFormat, rx: Receiver<Vec<u8>>) -> anyhow::Re
 
     Ok(stream)
 }
-
-struct RxBuffer {
-    receiver: Receiver<Vec<u8>>,
-    last: Option<Vec<u8>>,
-    idx: usize,
-}
-
-impl RxBuffer {
-    fn new(receiver: Receiver<Vec<u8>>) -> Self {
-        Self {
-            receiver,
-            last: None,
-            idx: 0,
-        }
-    }
-
-    fn fill(&mut self, data: &mut [u8]) {
-        let mut filled = 0;
-
-        while filled < data.len() {
-            if self.last.is_none() {
-                match self.receiver.recv_timeout(Duration::from_millis(4000)) {
-                    Ok(rx) => {
-                        debug!(rx.len = rx.len());
-                        self.last = Some(rx);
-                    }
-                    Err(error) => {
-                        warn!(%error);
-                    }
-                }
-            }
-
-            let Some(ref last) = self.last else {
-                info!("Playback rx underrun");
-                return;
-            };
-
-            #[allow(clippy::arithmetic_side_effects)]
-            while self.idx < last.len() && filled < data.len() {
-                data[filled] = last[self.idx];
-                assert!(filled < usize::MAX);
-                assert!(self.idx < usize::MAX);
-                filled += 1;
-                self.idx += 1;
-            }
-
-            // If all elements from last have been consumed, clear `self.last`
-            if self.idx >= last.len() {
-                self.last = None;
-                self.idx = 0;
-            }
-        }
-    }
-}
@@ -0,0 +1,193 @@
+//! Headless audio input backend, generating captured data from a tone or a raw PCM file.
+
+use std::f64::consts::TAU;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use ironrdp_audin::client::{AudinClientHandler, AudinMessage, AudinMessageProxy};
+use ironrdp_audin::AudinError;
+use ironrdp_rdpsnd::pdu::{AudioFormat, WaveFormat};
+
+#[derive(Debug)]
+pub struct GeneratorError(String);
+
+impl std::fmt::Display for GeneratorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for GeneratorError {}
+
+fn generator_error(message: String) -> Box<dyn AudinError> {
+    Box::new(GeneratorError(message))
+}
+
+/// Source of the generated audio.
+#[derive(Debug, Clone)]
+pub enum Source {
+    Silence,
+    /// Sine wave of the given frequency, in Hz.
+    Tone(f64),
+    /// Raw PCM file, played in a loop.
+    ///
+    /// The file content is expected to be encoded with the opened format.
+    File(PathBuf),
+}
+
+/// Audio input backend producing data without any capture device.
+///
+/// Packets are sent at the pace of the opened format, which makes this backend suitable for headless testing.
+#[derive(Debug)]
+pub struct GeneratorBackend {
+    proxy: Arc<dyn AudinMessageProxy>,
+    source: Source,
+    handle: Option<JoinHandle<()>>,
+    ended: Arc<AtomicBool>,
+    frames_per_packet: u32,
+}
+
+impl GeneratorBackend {
+    pub fn new(proxy: impl AudinMessageProxy + 'static, source: Source) -> Self {
+        Self {
+            proxy: Arc::new(proxy),
+            source,
+            handle: None,
+            ended: Arc::new(AtomicBool::new(false)),
+            frames_per_packet: 0,
+        }
+    }
+
+    fn start(&mut self, format: &AudioFormat) -> Result<(), Box<dyn AudinError>> {
+        self.close();
+
+        if !is_supported(format) {
+            return Err(generator_error(format!("unsupported format: {format:?}")));
+        }
+
+        let frames_per_packet = self.frames_per_packet.max(1);
+        let packet_size = usize::from(format.n_block_align).saturating_mul(frames_per_packet as usize);
+        let samples = match &self.source {
+            Source::Silence => Samples::Silence,
+            Source::Tone(frequency) => Samples::Tone {
+                frequency: *frequency,
+                position: 0,
+            },
+            Source::File(path) => {
+                let data = fs::read(path).map_err(|e| generator_error(format!("{}: {e}", path.display())))?;
+                if data.is_empty() {
+                    return Err(generator_error(format!("{}: empty file", path.display())));
+                }
+                Samples::File { data, position: 0 }
+            }
+        };
+
+        let period = Duration::from_secs(u64::from(frames_per_packet))
+            .checked_div(format.n_samples_per_sec)
+            .unwrap_or_default();
+        let format = format.clone();
+        let proxy = Arc::clone(&self.proxy);
+        self.ended.store(false, Ordering::Relaxed);
+        let ended = Arc::clone(&self.ended);
+        self.handle = Some(thread::spawn(move || {
+            let mut samples = samples;
+            let mut deadline = Instant::now();
+            while !ended.load(Ordering::Relaxed) {
+                proxy.send_audin_message(AudinMessage::Data(samples.next_packet(&format, packet_size)));
+                deadline = deadline.checked_add(period).unwrap_or(deadline);
+                thread::park_timeout(deadline.saturating_duration_since(Instant::now()));
+            }
+        }));
+
+        Ok(())
+    }
+}
+
+impl Drop for GeneratorBackend {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl AudinClientHandler for GeneratorBackend {
+    fn supported_formats(&self, server_formats: &[AudioFormat]) -> Vec<AudioFormat> {
+        server_formats.iter().filter(|f| is_supported(f)).cloned().collect()
+    }
+
+    fn open(&mut self, format: &AudioFormat, frames_per_packet: u32) -> Result<(), Box<dyn AudinError>> {
+        self.frames_per_packet = frames_per_packet;
+        self.start(format)
+    }
+
+    fn set_format(&mut self, format: &AudioFormat) -> Result<(), Box<dyn AudinError>> {
+        self.start(format)
+    }
+
+    fn close(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            self.ended.store(true, Ordering::Relaxed);
+            handle.thread().unpark();
+            handle.join().unwrap();
+        }
+    }
+}
+
+fn is_supported(format: &AudioFormat) -> bool {
+    format.format == WaveFormat::PCM
+        && matches!(format.bits_per_sample, 8 | 16)
+        && format.n_channels > 0
+        && Some(format.n_block_align) == format.n_channels.checked_mul(format.bits_per_sample / 8)
+}
+
+enum Samples {
+    Silence,
+    Tone { frequency: f64, position: u64 },
+    File { data: Vec<u8>, position: usize },
+}
+
+impl Samples {
+    fn next_packet(&mut self, format: &AudioFormat, packet_size: usize) -> Vec<u8> {
+        match self {
+            Samples::Silence => {
+                // Unsigned 8-bit PCM is centered on 128.
+                let silence = if format.bits_per_sample == 8 { 0x80 } else { 0 };
+                vec![silence; packet_size]
+            }
+            Samples::Tone { frequency, position } => {
+                let frames = packet_size.checked_div(usize::from(format.n_block_align)).unwrap_or(0);
+                let mut packet = Vec::with_capacity(packet_size);
+                for _ in 0..frames {
+                    #[allow(clippy::cast_precision_loss)] // Precision loss is not noticeable for a test tone.
+                    let t = *position as f64 / f64::from(format.n_samples_per_sec);
+                    let value = (TAU * *frequency * t).sin() * 0.5;
+                    for _ in 0..format.n_channels {
+                        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                        // Value is within [-0.5, 0.5].
+                        if format.bits_per_sample == 8 {
+                            packet.push((value * 127.0 + 128.0) as u8);
+                        } else {
+                            packet.extend_from_slice(&((value * f64::from(i16::MAX)) as i16).to_le_bytes());
+                        }
+                    }
+                    *position = position.wrapping_add(1);
+                }
+                packet
+            }
+            Samples::File { data, position } => {
+                let mut packet = Vec::with_capacity(packet_size);
+                while packet.len() < packet_size {
+                    let end = data
+                        .len()
+                        .min(position.saturating_add(packet_size.saturating_sub(packet.len())));
+                    packet.extend_from_slice(&data[*position..end]);
+                    *position = if end == data.len() { 0 } else { end };
+                }
+                packet
+            }
+        }
+    }
+}
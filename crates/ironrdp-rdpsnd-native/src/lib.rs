@@ -7,3 +7,4 @@
 extern crate tracing;
 
 pub mod cpal;
+pub mod generator;
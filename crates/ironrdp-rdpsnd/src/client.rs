@@ -1,28 +1,97 @@
 use std::borrow::Cow;
+use std::time::Duration;
 
 use ironrdp_core::cast_length;
 use ironrdp_core::impl_as_any;
 use ironrdp_core::Decode;
 use ironrdp_core::EncodeResult;
 use ironrdp_core::ReadCursor;
+use ironrdp_dvc::{encode_dvc_messages, DvcClientProcessor, DvcMessage, DvcProcessor};
 use ironrdp_pdu::decode_err;
 use ironrdp_pdu::encode_err;
 use ironrdp_pdu::gcc::ChannelName;
 use ironrdp_pdu::pdu_other_err;
 use ironrdp_pdu::PduResult;
-use ironrdp_svc::{CompressionCondition, SvcClientProcessor, SvcMessage, SvcProcessor};
-use tracing::{debug, error};
+use ironrdp_svc::{ChannelFlags, CompressionCondition, SvcClientProcessor, SvcMessage, SvcProcessor};
+use tracing::{debug, error, warn};
 
-use crate::pdu::{self, AudioFormat, PitchPdu, ServerAudioFormatPdu, TrainingPdu, VolumePdu};
+use crate::pdu::{
+    self, AudioFormat, PitchPdu, ServerAudioFormatPdu, TrainingPdu, VolumePdu, WaveConfirmPdu, WaveEncryptPdu,
+    WaveInfoPdu,
+};
 use crate::server::RdpsndSvcMessages;
+use crate::{DVC_CHANNEL_NAME, LOSSY_DVC_CHANNEL_NAME};
+
+/// Identifies a received wave, to be confirmed once played.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WaveId {
+    /// Timestamp of the wave, as sent by the server.
+    pub timestamp: u16,
+    pub block_no: u8,
+}
+
+impl WaveId {
+    /// Returns the Wave Confirm PDU for this wave, played `latency` after it was received.
+    ///
+    /// As specified, the confirmed timestamp is the wave timestamp plus the time it took to play it,
+    /// which lets the server measure the latency of the client.
+    pub fn confirm(self, latency: Duration) -> WaveConfirmPdu {
+        let latency = u16::try_from(latency.as_millis()).unwrap_or(u16::MAX);
+        WaveConfirmPdu {
+            timestamp: self.timestamp.wrapping_add(latency),
+            block_no: self.block_no,
+        }
+    }
+}
+
+/// Message sent by the backend to the application.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RdpsndMessage {
+    /// A wave queued with [`RdpsndClientHandler::queue_wave`] has been played.
+    ///
+    /// The application should send the confirm to the server, see [`Rdpsnd::wave_confirm`].
+    WavePlayed(WaveConfirmPdu),
+}
+
+/// Proxy used by the backend to send messages to the application, from any thread.
+pub trait RdpsndMessageProxy: Send + Sync + std::fmt::Debug {
+    fn send_rdpsnd_message(&self, message: RdpsndMessage);
+}
 
 pub trait RdpsndClientHandler: Send + std::fmt::Debug {
+    /// Returns the subset of the server formats the client is able to play.
+    ///
+    /// The `format_no` of the waves refers to the returned list. By default, all formats are accepted.
+    fn supported_formats(&self, server_formats: &[AudioFormat]) -> Vec<AudioFormat> {
+        server_formats.to_vec()
+    }
+
     fn wave(&mut self, format: &AudioFormat, ts: u32, data: Cow<'_, [u8]>);
 
+    /// Called for each received wave, along with its identifier.
+    ///
+    /// Returns `true` if the handler takes care of confirming the wave once it has actually been played,
+    /// by sending [`RdpsndMessage::WavePlayed`]. Otherwise, the wave is confirmed right away.
+    ///
+    /// The default implementation forwards the wave to [`Self::wave`], and returns `false`.
+    fn queue_wave(&mut self, format: &AudioFormat, ts: u32, data: Cow<'_, [u8]>, id: WaveId) -> bool {
+        let _ = id;
+        self.wave(format, ts, data);
+        false
+    }
+
     fn set_volume(&mut self, volume: VolumePdu);
 
     fn set_pitch(&mut self, pitch: PitchPdu);
 
+    /// Called with a Wave Encrypt PDU, along with the seed received in the Crypt Key PDU.
+    ///
+    /// Decrypting the audio data is left to the implementation. The default implementation drops it.
+    fn encrypted_wave(&mut self, format: &AudioFormat, seed: &[u8; 32], pdu: WaveEncryptPdu) {
+        let _ = (format, seed);
+        warn!(block_no = pdu.block_no, "Dropping encrypted wave");
+    }
+
     fn close(&mut self);
 }
 
@@ -49,12 +118,24 @@ enum RdpsndState {
 
 /// Required for rdpdr to work: [\[MS-RDPEFS\] Appendix A<1>]
 ///
+/// The same processor can be used either as the `rdpsnd` static channel, or as one of the
+/// `AUDIO_PLAYBACK_DVC` and `AUDIO_PLAYBACK_LOSSY_DVC` dynamic channels.
+///
 /// [\[MS-RDPEFS\] Appendix A<1>]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpefs/fd28bfd9-dae2-4a78-abe1-b4efa208b7aa#Appendix_A_1
 #[derive(Debug)]
 pub struct Rdpsnd {
     handler: Box<dyn RdpsndClientHandler>,
     state: RdpsndState,
     server_format: Option<ServerAudioFormatPdu>,
+    /// Formats sent in the client Audio Formats PDU.
+    client_formats: Vec<AudioFormat>,
+    lossy: bool,
+    /// WaveInfo PDU waiting for the following Wave PDU.
+    wave_info: Option<WaveInfoPdu>,
+    crypt_key: Option<[u8; 32]>,
+    /// Fragments of the wave being received over UDP, with their fragment number.
+    udp_block_no: Option<u8>,
+    udp_fragments: Vec<(u16, Vec<u8>)>,
 }
 
 impl Rdpsnd {
@@ -65,18 +146,25 @@ impl Rdpsnd {
             handler,
             state: RdpsndState::Start,
             server_format: None,
+            client_formats: Vec::new(),
+            lossy: false,
+            wave_info: None,
+            crypt_key: None,
+            udp_block_no: None,
+            udp_fragments: Vec::new(),
         }
     }
 
-    pub fn get_format(&self, format_no: u16) -> PduResult<&AudioFormat> {
-        let server_format = self
-            .server_format
-            .as_ref()
-            .ok_or_else(|| pdu_other_err!("invalid state - no format"))?;
+    /// Uses the `AUDIO_PLAYBACK_LOSSY_DVC` channel instead of `AUDIO_PLAYBACK_DVC` when used as a dynamic channel.
+    #[must_use]
+    pub fn with_lossy_dvc(mut self) -> Self {
+        self.lossy = true;
+        self
+    }
 
-        server_format
-            .formats
-            .get(format_no as usize)
+    pub fn get_format(&self, format_no: u16) -> PduResult<&AudioFormat> {
+        self.client_formats
+            .get(usize::from(format_no))
             .ok_or_else(|| pdu_other_err!("invalid format"))
     }
 
@@ -90,72 +178,178 @@ impl Rdpsnd {
     }
 
     pub fn client_formats(&mut self) -> PduResult<RdpsndSvcMessages> {
-        let server_format = self
-            .server_format
-            .as_ref()
-            .ok_or_else(|| pdu_other_err!("invalid state - no format"))?;
+        Ok(RdpsndSvcMessages::new(vec![self.client_formats_pdu()?.into()]))
+    }
 
+    pub fn quality_mode(&mut self) -> PduResult<RdpsndSvcMessages> {
+        Ok(RdpsndSvcMessages::new(vec![Self::quality_mode_pdu().into()]))
+    }
+
+    pub fn training_confirm(&mut self, pdu: &TrainingPdu) -> PduResult<RdpsndSvcMessages> {
+        Ok(RdpsndSvcMessages::new(vec![Self::training_confirm_pdu(pdu)?.into()]))
+    }
+
+    pub fn wave_confirm(&mut self, timestamp: u16, block_no: u8) -> PduResult<RdpsndSvcMessages> {
+        Ok(RdpsndSvcMessages::new(vec![Self::wave_confirm_pdu(
+            timestamp, block_no,
+        )
+        .into()]))
+    }
+
+    /// Same as [`Self::wave_confirm`], but encoded for the `DRDYNVC` channel when used as a dynamic channel.
+    pub fn encode_dvc_wave_confirm(&self, channel_id: u32, timestamp: u16, block_no: u8) -> PduResult<Vec<SvcMessage>> {
+        encode_dvc_messages(
+            channel_id,
+            vec![Box::new(Self::wave_confirm_pdu(timestamp, block_no))],
+            ChannelFlags::empty(),
+        )
+        .map_err(|e| encode_err!(e))
+    }
+
+    fn client_formats_pdu(&self) -> PduResult<pdu::ClientAudioOutputPdu> {
         let pdu = pdu::ClientAudioFormatPdu {
             version: self.version()?,
             flags: pdu::AudioFormatFlags::empty(),
-            formats: server_format.formats.clone(),
+            formats: self.client_formats.clone(),
             volume_left: 0xFFFF,
             volume_right: 0xFFFF,
             pitch: 0x00010000,
             dgram_port: 0,
         };
-        Ok(RdpsndSvcMessages::new(vec![pdu::ClientAudioOutputPdu::AudioFormat(
-            pdu,
-        )
-        .into()]))
+        Ok(pdu::ClientAudioOutputPdu::AudioFormat(pdu))
     }
 
-    pub fn quality_mode(&mut self) -> PduResult<RdpsndSvcMessages> {
+    fn quality_mode_pdu() -> pdu::ClientAudioOutputPdu {
         let pdu = pdu::QualityModePdu {
             quality_mode: pdu::QualityMode::High,
         };
-        Ok(RdpsndSvcMessages::new(vec![pdu::ClientAudioOutputPdu::QualityMode(
-            pdu,
-        )
-        .into()]))
+        pdu::ClientAudioOutputPdu::QualityMode(pdu)
     }
 
-    pub fn training_confirm(&mut self, pdu: &TrainingPdu) -> PduResult<RdpsndSvcMessages> {
+    fn training_confirm_pdu(pdu: &TrainingPdu) -> PduResult<pdu::ClientAudioOutputPdu> {
         let pack_size: EncodeResult<_> = cast_length!("wPackSize", pdu.data.len());
         let pack_size = pack_size.map_err(|e| encode_err!(e))?;
         let pdu = pdu::TrainingConfirmPdu {
             timestamp: pdu.timestamp,
             pack_size,
         };
-        Ok(RdpsndSvcMessages::new(vec![
-            pdu::ClientAudioOutputPdu::TrainingConfirm(pdu).into(),
-        ]))
+        Ok(pdu::ClientAudioOutputPdu::TrainingConfirm(pdu))
     }
 
-    pub fn wave_confirm(&mut self, timestamp: u16, block_no: u8) -> PduResult<RdpsndSvcMessages> {
-        let pdu = pdu::WaveConfirmPdu { timestamp, block_no };
-        Ok(RdpsndSvcMessages::new(vec![pdu::ClientAudioOutputPdu::WaveConfirm(
-            pdu,
-        )
-        .into()]))
+    fn wave_confirm_pdu(timestamp: u16, block_no: u8) -> pdu::ClientAudioOutputPdu {
+        let pdu = WaveConfirmPdu { timestamp, block_no };
+        pdu::ClientAudioOutputPdu::WaveConfirm(pdu)
     }
-}
 
-impl_as_any!(Rdpsnd);
+    /// Processes a datagram received over the UDP transport.
+    ///
+    /// Returns the Wave Confirm PDUs to be sent back to the server.
+    pub fn process_udp(&mut self, payload: &[u8]) -> PduResult<Vec<pdu::ClientAudioOutputPdu>> {
+        let pdu = pdu::ServerAudioOutputUdpPdu::decode(&mut ReadCursor::new(payload)).map_err(|e| decode_err!(e))?;
 
-impl SvcProcessor for Rdpsnd {
-    fn channel_name(&self) -> ChannelName {
-        Self::NAME
+        debug!(?pdu, ?self.state);
+        if self.state != RdpsndState::Ready {
+            error!(?self.state, "Invalid state");
+            return Ok(vec![]);
+        }
+
+        match pdu {
+            pdu::ServerAudioOutputUdpPdu::Wave(pdu) => {
+                if self.udp_block_no != Some(pdu.block_no) {
+                    self.udp_block_no = Some(pdu.block_no);
+                    self.udp_fragments.clear();
+                }
+                self.udp_fragments.push((pdu.frag_no, pdu.data.into_owned()));
+                Ok(vec![])
+            }
+            pdu::ServerAudioOutputUdpPdu::WaveLast(pdu) => {
+                let mut fragments = core::mem::take(&mut self.udp_fragments);
+                if self.udp_block_no.take() != Some(pdu.block_no) {
+                    fragments.clear();
+                }
+                fragments.sort_by_key(|(frag_no, _)| *frag_no);
+
+                let mut data = Vec::with_capacity(usize::from(pdu.total_size));
+                for (_, fragment) in fragments {
+                    data.extend_from_slice(&fragment);
+                }
+                data.extend_from_slice(&pdu.data);
+                if data.len() != usize::from(pdu.total_size) {
+                    warn!(
+                        block_no = pdu.block_no,
+                        expected = pdu.total_size,
+                        received = data.len(),
+                        "Incomplete UDP wave"
+                    );
+                    return Ok(vec![]);
+                }
+
+                self.queue_wave(
+                    pdu.format_no,
+                    u32::from(pdu.timestamp),
+                    data.into(),
+                    pdu.timestamp,
+                    pdu.block_no,
+                )
+            }
+            pdu::ServerAudioOutputUdpPdu::WaveEncrypt(pdu) => self.encrypted_wave(pdu),
+        }
     }
 
-    fn compression_condition(&self) -> CompressionCondition {
-        CompressionCondition::Never
+    fn encrypted_wave(&mut self, pdu: WaveEncryptPdu) -> PduResult<Vec<pdu::ClientAudioOutputPdu>> {
+        let seed = self
+            .crypt_key
+            .ok_or_else(|| pdu_other_err!("invalid state - no crypt key"))?;
+        let fmt = self.get_format(pdu.format_no)?.clone();
+        let confirm = Self::wave_confirm_pdu(pdu.timestamp, pdu.block_no);
+        self.handler.encrypted_wave(&fmt, &seed, pdu);
+        Ok(vec![confirm])
     }
 
-    fn process(&mut self, payload: &[u8]) -> PduResult<Vec<SvcMessage>> {
+    /// Hands a wave over to the handler, and confirms it unless the handler does it once played.
+    fn queue_wave(
+        &mut self,
+        format_no: u16,
+        ts: u32,
+        data: Cow<'_, [u8]>,
+        timestamp: u16,
+        block_no: u8,
+    ) -> PduResult<Vec<pdu::ClientAudioOutputPdu>> {
+        let fmt = self.get_format(format_no)?.clone();
+        if self.handler.queue_wave(&fmt, ts, data, WaveId { timestamp, block_no }) {
+            Ok(vec![])
+        } else {
+            Ok(vec![Self::wave_confirm_pdu(timestamp, block_no)])
+        }
+    }
+
+    /// Decodes the payload received on the channel, and runs it through the state machine.
+    fn process_payload(&mut self, payload: &[u8]) -> PduResult<Vec<pdu::ClientAudioOutputPdu>> {
+        // The Wave PDU following a WaveInfo PDU has no header.
+        if let Some(info) = self.wave_info.take() {
+            let pdu = pdu::WavePdu::from_parts(info, payload).map_err(|e| decode_err!(e))?;
+            return self.process_pdu(pdu::ServerAudioOutputPdu::Wave(pdu));
+        }
+
         let pdu = pdu::ServerAudioOutputPdu::decode(&mut ReadCursor::new(payload)).map_err(|e| decode_err!(e))?;
+        self.process_pdu(pdu)
+    }
 
+    /// Runs the state machine shared by the static and dynamic channel transports.
+    fn process_pdu(&mut self, pdu: pdu::ServerAudioOutputPdu<'_>) -> PduResult<Vec<pdu::ClientAudioOutputPdu>> {
         debug!(?pdu, ?self.state);
+
+        // The crypt key is sent when the UDP transport is used, and may be renewed at any point.
+        if let pdu::ServerAudioOutputPdu::CryptKey(pdu) = &pdu {
+            if self.state == RdpsndState::Start {
+                error!("Invalid PDU");
+                self.state = RdpsndState::Stop;
+            } else {
+                self.crypt_key = Some(pdu.seed);
+            }
+            return Ok(vec![]);
+        }
+
         let msg = match self.state {
             RdpsndState::Start => {
                 let pdu::ServerAudioOutputPdu::AudioFormat(af) = pdu else {
@@ -163,12 +357,12 @@ impl SvcProcessor for Rdpsnd {
                     self.state = RdpsndState::Stop;
                     return Ok(vec![]);
                 };
+                self.client_formats = self.handler.supported_formats(&af.formats);
                 self.server_format = Some(af);
                 self.state = RdpsndState::WaitingForTraining;
-                let mut msgs: Vec<SvcMessage> = self.client_formats()?.into();
+                let mut msgs = vec![self.client_formats_pdu()?];
                 if self.version()? >= pdu::Version::V6 {
-                    let mut m = self.quality_mode()?.into();
-                    msgs.append(&mut m);
+                    msgs.push(Self::quality_mode_pdu());
                 }
                 msgs
             }
@@ -179,16 +373,23 @@ impl SvcProcessor for Rdpsnd {
                     return Ok(vec![]);
                 };
                 self.state = RdpsndState::Ready;
-                self.training_confirm(&pdu)?.into()
+                vec![Self::training_confirm_pdu(&pdu)?]
             }
             RdpsndState::Ready => {
                 match pdu {
-                    // TODO: handle WaveInfo for < v8
+                    pdu::ServerAudioOutputPdu::WaveInfo(pdu) => {
+                        self.wave_info = Some(pdu);
+                    }
+                    pdu::ServerAudioOutputPdu::Wave(pdu) => {
+                        let ts = u32::from(pdu.timestamp);
+                        return self.queue_wave(pdu.format_no, ts, pdu.data, pdu.timestamp, pdu.block_no);
+                    }
+                    pdu::ServerAudioOutputPdu::WaveEncrypt(pdu) => {
+                        return self.encrypted_wave(pdu);
+                    }
                     pdu::ServerAudioOutputPdu::Wave2(pdu) => {
-                        let fmt = self.get_format(pdu.format_no)?.clone();
                         let ts = pdu.audio_timestamp;
-                        self.handler.wave(&fmt, ts, pdu.data);
-                        return Ok(self.wave_confirm(pdu.timestamp, pdu.block_no)?.into());
+                        return self.queue_wave(pdu.format_no, ts, pdu.data, pdu.timestamp, pdu.block_no);
                     }
                     pdu::ServerAudioOutputPdu::Volume(pdu) => {
                         self.handler.set_volume(pdu);
@@ -217,10 +418,67 @@ impl SvcProcessor for Rdpsnd {
     }
 }
 
+impl_as_any!(Rdpsnd);
+
+impl SvcProcessor for Rdpsnd {
+    fn channel_name(&self) -> ChannelName {
+        Self::NAME
+    }
+
+    fn compression_condition(&self) -> CompressionCondition {
+        CompressionCondition::Never
+    }
+
+    fn process(&mut self, payload: &[u8]) -> PduResult<Vec<SvcMessage>> {
+        Ok(self
+            .process_payload(payload)?
+            .into_iter()
+            .map(SvcMessage::from)
+            .collect())
+    }
+}
+
+impl SvcClientProcessor for Rdpsnd {}
+
+impl DvcProcessor for Rdpsnd {
+    fn channel_name(&self) -> &str {
+        if self.lossy {
+            LOSSY_DVC_CHANNEL_NAME
+        } else {
+            DVC_CHANNEL_NAME
+        }
+    }
+
+    fn start(&mut self, _channel_id: u32) -> PduResult<Vec<DvcMessage>> {
+        self.state = RdpsndState::Start;
+
+        Ok(vec![])
+    }
+
+    fn process(&mut self, _channel_id: u32, payload: &[u8]) -> PduResult<Vec<DvcMessage>> {
+        Ok(self
+            .process_payload(payload)?
+            .into_iter()
+            .map(|pdu| Box::new(pdu) as DvcMessage)
+            .collect())
+    }
+
+    fn close(&mut self, _channel_id: u32) {
+        self.handler.close();
+        self.state = RdpsndState::Start;
+        self.server_format = None;
+        self.client_formats.clear();
+        self.wave_info = None;
+        self.crypt_key = None;
+        self.udp_block_no = None;
+        self.udp_fragments.clear();
+    }
+}
+
+impl DvcClientProcessor for Rdpsnd {}
+
 impl Drop for Rdpsnd {
     fn drop(&mut self) {
         self.handler.close();
     }
 }
-
-impl SvcClientProcessor for Rdpsnd {}
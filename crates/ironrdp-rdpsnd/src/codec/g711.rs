@@ -0,0 +1,91 @@
+//! ITU-T G.711 A-law and µ-law companding.
+
+const ULAW_BIAS: i32 = 0x84;
+const ULAW_CLIP: i32 = 32635;
+
+/// Upper bounds of the A-law segments, for 13-bit magnitudes.
+const ALAW_SEGMENT_END: [i32; 8] = [0x1F, 0x3F, 0x7F, 0xFF, 0x1FF, 0x3FF, 0x7FF, 0xFFF];
+
+// Every intermediate value below fits comfortably in an `i32`, and the results are masked to 8 bits.
+#[allow(clippy::arithmetic_side_effects)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_possible_wrap)]
+#[allow(clippy::cast_sign_loss)]
+pub(crate) fn linear_to_ulaw(sample: i16) -> u8 {
+    let mut pcm = i32::from(sample);
+    let sign = if pcm < 0 {
+        pcm = -pcm;
+        0x80
+    } else {
+        0x00
+    };
+
+    pcm = pcm.min(ULAW_CLIP) + ULAW_BIAS;
+
+    // `pcm >> 7` is in 1..=0xFF, so the exponent is its base 2 logarithm.
+    let exponent = 7 - ((pcm >> 7) as u8).leading_zeros() as i32;
+    let mantissa = (pcm >> (exponent + 3)) & 0x0F;
+
+    !((sign | (exponent << 4) | mantissa) as u8)
+}
+
+#[allow(clippy::arithmetic_side_effects)]
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn ulaw_to_linear(value: u8) -> i16 {
+    let value = !value;
+    let exponent = (value >> 4) & 0x07;
+    let mantissa = i32::from(value & 0x0F);
+
+    let magnitude = (((mantissa << 3) + ULAW_BIAS) << exponent) - ULAW_BIAS;
+
+    // The magnitude is at most 32124.
+    if value & 0x80 != 0 {
+        -magnitude as i16
+    } else {
+        magnitude as i16
+    }
+}
+
+#[allow(clippy::arithmetic_side_effects)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_possible_wrap)]
+#[allow(clippy::cast_sign_loss)]
+pub(crate) fn linear_to_alaw(sample: i16) -> u8 {
+    let mut pcm = i32::from(sample) >> 3;
+    let mask = if pcm >= 0 {
+        0xD5
+    } else {
+        pcm = -pcm - 1;
+        0x55
+    };
+
+    let Some(segment) = ALAW_SEGMENT_END.iter().position(|&end| pcm <= end) else {
+        return 0x7F ^ mask;
+    };
+
+    let shift = if segment < 2 { 1 } else { segment };
+    let value = ((segment as i32) << 4) | ((pcm >> shift) & 0x0F);
+
+    (value as u8) ^ mask
+}
+
+#[allow(clippy::arithmetic_side_effects)]
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn alaw_to_linear(value: u8) -> i16 {
+    let value = value ^ 0x55;
+    let segment = (value & 0x70) >> 4;
+    let mut magnitude = i32::from(value & 0x0F) << 4;
+
+    match segment {
+        0 => magnitude += 8,
+        1 => magnitude += 0x108,
+        _ => magnitude = (magnitude + 0x108) << (segment - 1),
+    }
+
+    // The magnitude is at most 32256.
+    if value & 0x80 != 0 {
+        magnitude as i16
+    } else {
+        -magnitude as i16
+    }
+}
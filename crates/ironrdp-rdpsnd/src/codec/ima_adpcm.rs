@@ -0,0 +1,173 @@
+//! IMA ADPCM (`WAVE_FORMAT_DVI_ADPCM`), with the block layout used in RIFF WAVE files.
+//!
+//! Each block starts with a 4-byte header per channel (initial sample and step index),
+//! followed by groups of 4 bytes (8 samples, low nibble first) for each channel in turn.
+
+use ironrdp_core::{invalid_field_err, DecodeResult};
+
+use super::clamp_sample;
+
+const STEP_TABLE: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66, 73, 80, 88, 97, 107,
+    118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449, 494, 544, 598, 658, 724, 796, 876, 963,
+    1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272, 2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894,
+    6484, 7132, 7845, 8630, 9493, 10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794,
+    32767,
+];
+
+const INDEX_TABLE: [i8; 8] = [-1, -1, -1, -1, 2, 4, 6, 8];
+
+const MAX_STEP_INDEX: u8 = 88;
+
+const HEADER_SIZE: usize = 4;
+
+/// Number of bytes per channel in each group following the block header.
+const GROUP_SIZE: usize = 4;
+
+#[derive(Debug, Copy, Clone, Default)]
+pub(crate) struct ChannelState {
+    predictor: i16,
+    step_index: u8,
+}
+
+impl ChannelState {
+    fn decode(&mut self, nibble: u8) -> i16 {
+        let step = STEP_TABLE[usize::from(self.step_index)];
+
+        // The step is at most 32767, so the difference is below 2^16.
+        #[allow(clippy::arithmetic_side_effects)]
+        let sample = {
+            let mut diff = step >> 3;
+            if nibble & 1 != 0 {
+                diff += step >> 2;
+            }
+            if nibble & 2 != 0 {
+                diff += step >> 1;
+            }
+            if nibble & 4 != 0 {
+                diff += step;
+            }
+            if nibble & 8 != 0 {
+                diff = -diff;
+            }
+            i32::from(self.predictor) + diff
+        };
+
+        self.predictor = clamp_sample(sample);
+        self.step_index = self
+            .step_index
+            .saturating_add_signed(INDEX_TABLE[usize::from(nibble & 0x07)])
+            .min(MAX_STEP_INDEX);
+
+        self.predictor
+    }
+
+    // The difference between two samples fits in 17 bits.
+    #[allow(clippy::arithmetic_side_effects)]
+    fn encode(&mut self, sample: i16) -> u8 {
+        let mut step = STEP_TABLE[usize::from(self.step_index)];
+        let mut diff = i32::from(sample) - i32::from(self.predictor);
+
+        let mut nibble = 0;
+        if diff < 0 {
+            nibble = 8;
+            diff = -diff;
+        }
+
+        let mut bit = 4;
+        while bit != 0 {
+            if diff >= step {
+                nibble |= bit;
+                diff -= step;
+            }
+            step >>= 1;
+            bit >>= 1;
+        }
+
+        // Keep the encoder in sync with what the decoder will reconstruct.
+        self.decode(nibble);
+
+        nibble
+    }
+}
+
+pub(crate) fn header_size(n_channels: usize) -> Option<usize> {
+    HEADER_SIZE.checked_mul(n_channels)
+}
+
+/// Returns the number of samples per channel encoded in a block of `block_align` bytes.
+pub(crate) fn samples_per_block(block_align: usize, n_channels: usize) -> Option<usize> {
+    let data_size = block_align.checked_sub(header_size(n_channels)?)?;
+    let group_size = GROUP_SIZE.checked_mul(n_channels)?;
+
+    if data_size.checked_rem(group_size)? != 0 {
+        return None;
+    }
+
+    // Two samples per byte, plus the one stored in the header.
+    data_size.checked_div(n_channels)?.checked_mul(2)?.checked_add(1)
+}
+
+#[allow(clippy::arithmetic_side_effects)] // `header_size` succeeded, so none of the offsets can overflow.
+pub(crate) fn decode_block(block: &[u8], n_channels: usize, out: &mut Vec<i16>) -> DecodeResult<()> {
+    let header_size = header_size(n_channels).ok_or_else(|| invalid_field_err!("nChannels", "too many channels"))?;
+    if block.len() < header_size {
+        return Err(invalid_field_err!("block", "truncated IMA ADPCM block header"));
+    }
+
+    let (header, data) = block.split_at(header_size);
+
+    let mut states = Vec::with_capacity(n_channels);
+    for header in header.chunks_exact(HEADER_SIZE) {
+        let step_index = header[2];
+        if step_index > MAX_STEP_INDEX {
+            return Err(invalid_field_err!("stepIndex", "invalid IMA ADPCM step index"));
+        }
+
+        let state = ChannelState {
+            predictor: i16::from_le_bytes([header[0], header[1]]),
+            step_index,
+        };
+        out.push(state.predictor);
+        states.push(state);
+    }
+
+    for group in data.chunks_exact(GROUP_SIZE * n_channels) {
+        let base = out.len();
+        out.resize(base + GROUP_SIZE * 2 * n_channels, 0);
+
+        for (channel, (bytes, state)) in group.chunks_exact(GROUP_SIZE).zip(states.iter_mut()).enumerate() {
+            for (i, byte) in bytes.iter().enumerate() {
+                out[base + 2 * i * n_channels + channel] = state.decode(byte & 0x0F);
+                out[base + (2 * i + 1) * n_channels + channel] = state.decode(byte >> 4);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Encodes one block worth of interleaved `frames` into `out`.
+///
+/// `frames` must contain exactly `samples_per_block * n_channels` samples.
+#[allow(clippy::arithmetic_side_effects)] // Offsets are bounded by the length of `frames`.
+pub(crate) fn encode_block(frames: &[i16], states: &mut [ChannelState], out: &mut Vec<u8>) {
+    let n_channels = states.len();
+
+    for (channel, state) in states.iter_mut().enumerate() {
+        state.predictor = frames[channel];
+        out.extend_from_slice(&state.predictor.to_le_bytes());
+        out.push(state.step_index);
+        out.push(0);
+    }
+
+    for group in frames[n_channels..].chunks_exact(GROUP_SIZE * 2 * n_channels) {
+        for (channel, state) in states.iter_mut().enumerate() {
+            for pair in group.chunks_exact(2 * n_channels) {
+                let low = state.encode(pair[channel]);
+                let high = state.encode(pair[n_channels + channel]);
+                out.push(low | (high << 4));
+            }
+        }
+    }
+}
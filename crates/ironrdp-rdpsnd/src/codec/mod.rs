@@ -0,0 +1,393 @@
+//! Audio codecs for the compressed formats most commonly negotiated over RDPSND and AUDIO_INPUT.
+//!
+//! Encoders take, and decoders produce, interleaved signed 16-bit PCM samples. Sample rate and
+//! channel count are left untouched: the PCM samples use the ones of the encoded format.
+
+mod g711;
+mod ima_adpcm;
+mod ms_adpcm;
+
+use ironrdp_core::{invalid_field_err, DecodeResult};
+
+use crate::pdu::{AudioFormat, WaveFormat};
+
+/// Audio codecs supported by [`Encoder`] and [`Decoder`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Codec {
+    /// Uncompressed 8-bit or 16-bit PCM (`WAVE_FORMAT_PCM`).
+    Pcm,
+    /// Microsoft ADPCM (`WAVE_FORMAT_ADPCM`), 4 bits per sample.
+    MsAdpcm,
+    /// IMA ADPCM (`WAVE_FORMAT_DVI_ADPCM`), 4 bits per sample.
+    ImaAdpcm,
+    /// ITU-T G.711 A-law (`WAVE_FORMAT_ALAW`), 8 bits per sample.
+    ALaw,
+    /// ITU-T G.711 µ-law (`WAVE_FORMAT_MULAW`), 8 bits per sample.
+    MuLaw,
+}
+
+impl Codec {
+    /// Returns the codec for `format`, if it is supported and its parameters are consistent.
+    pub fn from_audio_format(format: &AudioFormat) -> Option<Self> {
+        Params::new(format).map(|params| params.codec())
+    }
+
+    pub fn wave_format(self) -> WaveFormat {
+        match self {
+            Codec::Pcm => WaveFormat::PCM,
+            Codec::MsAdpcm => WaveFormat::ADPCM,
+            Codec::ImaAdpcm => WaveFormat::DVI_ADPCM,
+            Codec::ALaw => WaveFormat::ALAW,
+            Codec::MuLaw => WaveFormat::MULAW,
+        }
+    }
+
+    /// Builds a format description for this codec, using the block sizes Windows uses.
+    ///
+    /// PCM formats use 16 bits per sample. Returns `None` if `n_channels` is zero or too large for the codec.
+    pub fn audio_format(self, n_channels: u16, n_samples_per_sec: u32) -> Option<AudioFormat> {
+        if n_channels == 0 {
+            return None;
+        }
+
+        let (n_block_align, bits_per_sample, samples_per_block, data) = match self {
+            Codec::Pcm => (n_channels.checked_mul(2)?, 16, 1, None),
+            Codec::ALaw | Codec::MuLaw => (n_channels, 8, 1, None),
+            Codec::MsAdpcm | Codec::ImaAdpcm => {
+                // 256 bytes per channel at 11.025 kHz, doubling with the sample rate up to 44.1 kHz.
+                let factor = (n_samples_per_sec / 11025).clamp(1, 4);
+                let n_block_align = u16::try_from(u32::from(n_channels).checked_mul(256)?.checked_mul(factor)?).ok()?;
+
+                let samples_per_block = if self == Codec::MsAdpcm {
+                    ms_adpcm::samples_per_block(n_block_align.into(), n_channels.into())?
+                } else {
+                    ima_adpcm::samples_per_block(n_block_align.into(), n_channels.into())?
+                };
+                let samples_per_block = u16::try_from(samples_per_block).ok()?;
+
+                let data = if self == Codec::MsAdpcm {
+                    ms_adpcm::extra_data(samples_per_block)
+                } else {
+                    samples_per_block.to_le_bytes().to_vec()
+                };
+
+                (n_block_align, 4, samples_per_block, Some(data))
+            }
+        };
+
+        let n_avg_bytes_per_sec = u64::from(n_samples_per_sec)
+            .checked_mul(u64::from(n_block_align))?
+            .checked_div(u64::from(samples_per_block))?;
+
+        Some(AudioFormat {
+            format: self.wave_format(),
+            n_channels,
+            n_samples_per_sec,
+            n_avg_bytes_per_sec: u32::try_from(n_avg_bytes_per_sec).ok()?,
+            n_block_align,
+            bits_per_sample,
+            data,
+        })
+    }
+
+    /// Preference when negotiating: the higher, the better.
+    ///
+    /// Compressed formats come first, as bandwidth matters more than quality for audio redirection.
+    fn preference(self) -> u8 {
+        match self {
+            Codec::MsAdpcm => 4,
+            Codec::ImaAdpcm => 3,
+            Codec::MuLaw | Codec::ALaw => 2,
+            Codec::Pcm => 1,
+        }
+    }
+}
+
+/// Returns the index of the best supported format in `formats`.
+///
+/// Formats are ranked by codec first (compressed ones before PCM), then by sample rate and channel count.
+/// When several formats rank the same, the first one wins.
+///
+/// On the server side, `formats` are the ones listed in the client Audio Formats PDU: the returned index
+/// is suitable for [`RdpsndServerHandler::start`](crate::server::RdpsndServerHandler::start).
+pub fn best_format(formats: &[AudioFormat]) -> Option<u16> {
+    let mut best: Option<(usize, (u8, u64))> = None;
+
+    for (idx, format) in formats.iter().enumerate() {
+        let Some(codec) = Codec::from_audio_format(format) else {
+            continue;
+        };
+
+        let rank = (
+            codec.preference(),
+            u64::from(format.n_samples_per_sec).saturating_mul(u64::from(format.n_channels)),
+        );
+        if best.map_or(true, |(_, best_rank)| rank > best_rank) {
+            best = Some((idx, rank));
+        }
+    }
+
+    best.and_then(|(idx, _)| u16::try_from(idx).ok())
+}
+
+#[derive(Debug, Clone)]
+enum Params {
+    Pcm {
+        bits_per_sample: u16,
+    },
+    MsAdpcm {
+        block_align: usize,
+        samples_per_block: usize,
+        coefficients: Vec<(i16, i16)>,
+    },
+    ImaAdpcm {
+        block_align: usize,
+        samples_per_block: usize,
+    },
+    ALaw,
+    MuLaw,
+}
+
+impl Params {
+    fn new(format: &AudioFormat) -> Option<Self> {
+        let n_channels = usize::from(format.n_channels);
+        if n_channels == 0 {
+            return None;
+        }
+
+        let block_align = usize::from(format.n_block_align);
+
+        let params = match format.format {
+            WaveFormat::PCM => {
+                if !matches!(format.bits_per_sample, 8 | 16)
+                    || block_align != n_channels.checked_mul(usize::from(format.bits_per_sample / 8))?
+                {
+                    return None;
+                }
+                Params::Pcm {
+                    bits_per_sample: format.bits_per_sample,
+                }
+            }
+            WaveFormat::ALAW | WaveFormat::MULAW => {
+                if format.bits_per_sample != 8 || block_align != n_channels {
+                    return None;
+                }
+                if format.format == WaveFormat::ALAW {
+                    Params::ALaw
+                } else {
+                    Params::MuLaw
+                }
+            }
+            WaveFormat::ADPCM => {
+                if format.bits_per_sample != 4 {
+                    return None;
+                }
+                Params::MsAdpcm {
+                    block_align,
+                    samples_per_block: ms_adpcm::samples_per_block(block_align, n_channels)?,
+                    coefficients: ms_adpcm::parse_coefficients(format.data.as_deref())?,
+                }
+            }
+            WaveFormat::DVI_ADPCM => {
+                if format.bits_per_sample != 4 {
+                    return None;
+                }
+                Params::ImaAdpcm {
+                    block_align,
+                    samples_per_block: ima_adpcm::samples_per_block(block_align, n_channels)?,
+                }
+            }
+            _ => return None,
+        };
+
+        Some(params)
+    }
+
+    fn codec(&self) -> Codec {
+        match self {
+            Params::Pcm { .. } => Codec::Pcm,
+            Params::MsAdpcm { .. } => Codec::MsAdpcm,
+            Params::ImaAdpcm { .. } => Codec::ImaAdpcm,
+            Params::ALaw => Codec::ALaw,
+            Params::MuLaw => Codec::MuLaw,
+        }
+    }
+}
+
+/// Decodes wave data received in a given [`AudioFormat`].
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    params: Params,
+    n_channels: usize,
+}
+
+impl Decoder {
+    /// Returns `None` if the format is not supported.
+    pub fn new(format: &AudioFormat) -> Option<Self> {
+        Some(Self {
+            params: Params::new(format)?,
+            n_channels: usize::from(format.n_channels),
+        })
+    }
+
+    pub fn codec(&self) -> Codec {
+        self.params.codec()
+    }
+
+    /// Decodes `data` to interleaved 16-bit samples.
+    ///
+    /// ADPCM data is expected to be made of whole blocks, except for the last one which may be shorter.
+    pub fn decode(&mut self, data: &[u8]) -> DecodeResult<Vec<i16>> {
+        let samples = match &self.params {
+            Params::Pcm { bits_per_sample: 8 } => data.iter().map(|&sample| pcm8_to_linear(sample)).collect(),
+            Params::Pcm { .. } => data
+                .chunks_exact(2)
+                .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+                .collect(),
+            Params::ALaw => data.iter().map(|&sample| g711::alaw_to_linear(sample)).collect(),
+            Params::MuLaw => data.iter().map(|&sample| g711::ulaw_to_linear(sample)).collect(),
+            Params::MsAdpcm {
+                block_align,
+                samples_per_block,
+                coefficients,
+            } => {
+                let mut samples = Vec::with_capacity(self.decoded_len(data.len(), *block_align, *samples_per_block));
+                for block in data.chunks(*block_align) {
+                    ms_adpcm::decode_block(block, self.n_channels, coefficients, &mut samples)?;
+                }
+                samples
+            }
+            Params::ImaAdpcm {
+                block_align,
+                samples_per_block,
+            } => {
+                let mut samples = Vec::with_capacity(self.decoded_len(data.len(), *block_align, *samples_per_block));
+                for block in data.chunks(*block_align) {
+                    ima_adpcm::decode_block(block, self.n_channels, &mut samples)?;
+                }
+                samples
+            }
+        };
+
+        if samples.len().checked_rem(self.n_channels) != Some(0) {
+            return Err(invalid_field_err!("data", "incomplete audio frame"));
+        }
+
+        Ok(samples)
+    }
+
+    fn decoded_len(&self, len: usize, block_align: usize, samples_per_block: usize) -> usize {
+        len.div_ceil(block_align)
+            .saturating_mul(samples_per_block)
+            .saturating_mul(self.n_channels)
+    }
+}
+
+/// Encodes interleaved 16-bit samples to a given [`AudioFormat`].
+///
+/// Block-based codecs (ADPCM) only output whole blocks: remaining samples are kept until the next call
+/// to [`Encoder::encode`], or until [`Encoder::flush`].
+#[derive(Debug, Clone)]
+pub struct Encoder {
+    params: Params,
+    n_channels: usize,
+    pending: Vec<i16>,
+    ima_states: Vec<ima_adpcm::ChannelState>,
+}
+
+impl Encoder {
+    /// Returns `None` if the format is not supported.
+    pub fn new(format: &AudioFormat) -> Option<Self> {
+        let n_channels = usize::from(format.n_channels);
+
+        Some(Self {
+            params: Params::new(format)?,
+            n_channels,
+            pending: Vec::new(),
+            ima_states: vec![ima_adpcm::ChannelState::default(); n_channels],
+        })
+    }
+
+    pub fn codec(&self) -> Codec {
+        self.params.codec()
+    }
+
+    /// Encodes interleaved 16-bit `samples`.
+    pub fn encode(&mut self, samples: &[i16]) -> Vec<u8> {
+        match &self.params {
+            Params::Pcm { bits_per_sample: 8 } => samples.iter().map(|&sample| linear_to_pcm8(sample)).collect(),
+            Params::Pcm { .. } => samples.iter().flat_map(|sample| sample.to_le_bytes()).collect(),
+            Params::ALaw => samples.iter().map(|&sample| g711::linear_to_alaw(sample)).collect(),
+            Params::MuLaw => samples.iter().map(|&sample| g711::linear_to_ulaw(sample)).collect(),
+            Params::MsAdpcm { .. } | Params::ImaAdpcm { .. } => {
+                self.pending.extend_from_slice(samples);
+                self.encode_blocks()
+            }
+        }
+    }
+
+    /// Encodes the remaining samples, if any, padding the last block with silence.
+    pub fn flush(&mut self) -> Vec<u8> {
+        let block_len = self.block_len();
+        if self.pending.is_empty() || block_len == 0 {
+            return Vec::new();
+        }
+
+        let padded_len = self.pending.len().div_ceil(block_len).saturating_mul(block_len);
+        self.pending.resize(padded_len, 0);
+        self.encode_blocks()
+    }
+
+    /// Number of samples, for all channels, in a block.
+    fn block_len(&self) -> usize {
+        match &self.params {
+            Params::MsAdpcm { samples_per_block, .. } | Params::ImaAdpcm { samples_per_block, .. } => {
+                samples_per_block.saturating_mul(self.n_channels)
+            }
+            _ => 0,
+        }
+    }
+
+    fn encode_blocks(&mut self) -> Vec<u8> {
+        let block_len = self.block_len();
+        if block_len == 0 {
+            return Vec::new();
+        }
+
+        let mut out = Vec::new();
+        let mut blocks = self.pending.chunks_exact(block_len);
+        for block in &mut blocks {
+            match &self.params {
+                Params::MsAdpcm { coefficients, .. } => {
+                    ms_adpcm::encode_block(block, self.n_channels, coefficients, &mut out);
+                }
+                Params::ImaAdpcm { .. } => ima_adpcm::encode_block(block, &mut self.ima_states, &mut out),
+                _ => unreachable!("not a block codec"),
+            }
+        }
+
+        let remaining = blocks.remainder().len();
+        let consumed = self.pending.len().saturating_sub(remaining);
+        self.pending.drain(..consumed);
+
+        out
+    }
+}
+
+fn clamp_sample(sample: i32) -> i16 {
+    #[allow(clippy::cast_possible_truncation)] // The value is clamped to the i16 range.
+    let sample = sample.clamp(i16::MIN.into(), i16::MAX.into()) as i16;
+    sample
+}
+
+// 8-bit PCM is unsigned.
+fn pcm8_to_linear(sample: u8) -> i16 {
+    #[allow(clippy::cast_possible_wrap)] // Reinterpreting the offset value as signed is intended.
+    let sample = sample.wrapping_sub(0x80) as i8;
+    i16::from(sample) << 8
+}
+
+fn linear_to_pcm8(sample: i16) -> u8 {
+    let [high, _] = sample.to_be_bytes();
+    high.wrapping_add(0x80)
+}
@@ -0,0 +1,276 @@
+//! Microsoft ADPCM (`WAVE_FORMAT_ADPCM`).
+//!
+//! Each block starts with a 7-byte header per channel (predictor index, initial delta and the two
+//! first samples), followed by interleaved samples, high nibble first.
+
+use ironrdp_core::{invalid_field_err, DecodeResult};
+
+use super::clamp_sample;
+
+/// Predictor coefficients defined for `WAVE_FORMAT_ADPCM`, as stored in the format extra data.
+pub(crate) const DEFAULT_COEFFICIENTS: [(i16, i16); 7] = [
+    (256, 0),
+    (512, -256),
+    (0, 0),
+    (192, 64),
+    (240, 0),
+    (460, -208),
+    (392, -232),
+];
+
+const ADAPTATION_TABLE: [i32; 16] = [
+    230, 230, 230, 230, 307, 409, 512, 614, 768, 614, 512, 409, 307, 230, 230, 230,
+];
+
+const MIN_DELTA: i32 = 16;
+
+const HEADER_SIZE: usize = 7;
+
+#[derive(Debug, Copy, Clone)]
+struct ChannelState {
+    coefficients: (i16, i16),
+    delta: i32,
+    sample1: i16,
+    sample2: i16,
+}
+
+impl ChannelState {
+    // Products of two `i16` fit in an `i32`, and the delta is kept within `i16` range.
+    #[allow(clippy::arithmetic_side_effects)]
+    #[allow(clippy::cast_possible_truncation)]
+    fn predict(&self) -> i32 {
+        let (c1, c2) = self.coefficients;
+        let prediction = i64::from(self.sample1) * i64::from(c1) + i64::from(self.sample2) * i64::from(c2);
+
+        // Bounded by 2 * 2^30 / 2^8.
+        (prediction >> 8) as i32
+    }
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn decode(&mut self, nibble: u8) -> i16 {
+        let signed = if nibble & 0x08 != 0 {
+            i32::from(nibble) - 0x10
+        } else {
+            i32::from(nibble)
+        };
+
+        let sample = clamp_sample(self.predict() + signed * self.delta);
+
+        self.sample2 = self.sample1;
+        self.sample1 = sample;
+        self.delta = ((ADAPTATION_TABLE[usize::from(nibble)] * self.delta) >> 8).clamp(MIN_DELTA, i16::MAX.into());
+
+        sample
+    }
+
+    #[allow(clippy::arithmetic_side_effects)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    fn encode(&mut self, sample: i16) -> u8 {
+        let error = i32::from(sample) - self.predict();
+
+        // Round to the nearest multiple of delta.
+        let half = self.delta / 2;
+        let signed = if error >= 0 {
+            (error + half) / self.delta
+        } else {
+            (error - half) / self.delta
+        };
+        let nibble = (signed.clamp(-8, 7) & 0x0F) as u8;
+
+        // Keep the encoder in sync with what the decoder will reconstruct.
+        self.decode(nibble);
+
+        nibble
+    }
+}
+
+pub(crate) fn header_size(n_channels: usize) -> Option<usize> {
+    HEADER_SIZE.checked_mul(n_channels)
+}
+
+/// Returns the number of samples per channel encoded in a block of `block_align` bytes.
+pub(crate) fn samples_per_block(block_align: usize, n_channels: usize) -> Option<usize> {
+    // Two samples per byte.
+    let data_samples = block_align.checked_sub(header_size(n_channels)?)?.checked_mul(2)?;
+
+    if data_samples.checked_rem(n_channels)? != 0 {
+        return None;
+    }
+
+    // Plus the two stored in the header.
+    data_samples.checked_div(n_channels)?.checked_add(2)
+}
+
+/// Parses the coefficient table from the `WAVE_FORMAT_ADPCM` extra data, if any.
+pub(crate) fn parse_coefficients(extra: Option<&[u8]>) -> Option<Vec<(i16, i16)>> {
+    let Some(extra) = extra else {
+        return Some(DEFAULT_COEFFICIENTS.to_vec());
+    };
+
+    // wSamplesPerBlock, wNumCoef, then aCoef.
+    let num_coefficients = usize::from(u16::from_le_bytes([*extra.get(2)?, *extra.get(3)?]));
+    let coefficients = extra
+        .get(4..)?
+        .chunks_exact(4)
+        .take(num_coefficients)
+        .map(|c| (i16::from_le_bytes([c[0], c[1]]), i16::from_le_bytes([c[2], c[3]])))
+        .collect::<Vec<_>>();
+
+    // The first seven coefficient pairs must be the standard ones.
+    if coefficients.len() != num_coefficients || coefficients.get(..DEFAULT_COEFFICIENTS.len())? != DEFAULT_COEFFICIENTS
+    {
+        return None;
+    }
+
+    Some(coefficients)
+}
+
+/// Builds the `WAVE_FORMAT_ADPCM` extra data for the standard coefficient table.
+pub(crate) fn extra_data(samples_per_block: u16) -> Vec<u8> {
+    let mut extra = Vec::new();
+    extra.extend_from_slice(&samples_per_block.to_le_bytes());
+    #[allow(clippy::cast_possible_truncation)] // There are seven coefficients.
+    extra.extend_from_slice(&(DEFAULT_COEFFICIENTS.len() as u16).to_le_bytes());
+    for (c1, c2) in DEFAULT_COEFFICIENTS {
+        extra.extend_from_slice(&c1.to_le_bytes());
+        extra.extend_from_slice(&c2.to_le_bytes());
+    }
+    extra
+}
+
+#[allow(clippy::arithmetic_side_effects)] // `header_size` succeeded, so none of the offsets can overflow.
+pub(crate) fn decode_block(
+    block: &[u8],
+    n_channels: usize,
+    coefficients: &[(i16, i16)],
+    out: &mut Vec<i16>,
+) -> DecodeResult<()> {
+    let header_size = header_size(n_channels).ok_or_else(|| invalid_field_err!("nChannels", "too many channels"))?;
+    if block.len() < header_size {
+        return Err(invalid_field_err!("block", "truncated MS ADPCM block header"));
+    }
+
+    let (header, data) = block.split_at(header_size);
+    let read_i16 = |field: usize, channel: usize| {
+        let offset = n_channels + (field * n_channels + channel) * 2;
+        i16::from_le_bytes([header[offset], header[offset + 1]])
+    };
+
+    let mut states = Vec::with_capacity(n_channels);
+    for (channel, &predictor) in header[..n_channels].iter().enumerate() {
+        let coefficients = *coefficients
+            .get(usize::from(predictor))
+            .ok_or_else(|| invalid_field_err!("bPredictor", "invalid MS ADPCM predictor index"))?;
+
+        states.push(ChannelState {
+            coefficients,
+            delta: i32::from(read_i16(0, channel)),
+            sample1: read_i16(1, channel),
+            sample2: read_i16(2, channel),
+        });
+    }
+
+    out.extend(states.iter().map(|s| s.sample2));
+    out.extend(states.iter().map(|s| s.sample1));
+
+    for (i, nibble) in data.iter().flat_map(|byte| [byte >> 4, byte & 0x0F]).enumerate() {
+        out.push(states[i % n_channels].decode(nibble));
+    }
+
+    Ok(())
+}
+
+/// Encodes one block worth of interleaved `frames` into `out`.
+///
+/// `frames` must contain exactly `samples_per_block * n_channels` samples, with at least two samples per channel.
+#[allow(clippy::arithmetic_side_effects)] // Offsets are bounded by the length of `frames`.
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn encode_block(frames: &[i16], n_channels: usize, coefficients: &[(i16, i16)], out: &mut Vec<u8>) {
+    let mut states = Vec::with_capacity(n_channels);
+    let mut predictors = Vec::with_capacity(n_channels);
+
+    for channel in 0..n_channels {
+        let samples = frames
+            .iter()
+            .skip(channel)
+            .step_by(n_channels)
+            .copied()
+            .collect::<Vec<_>>();
+
+        // Pick the predictor that gives the smallest error over the block.
+        let (predictor, state, _) = coefficients
+            .iter()
+            .take(DEFAULT_COEFFICIENTS.len())
+            .enumerate()
+            .map(|(predictor, &coefficients)| {
+                let state = initial_state(&samples, coefficients);
+                let mut simulated = state;
+                let error = samples[2..]
+                    .iter()
+                    .map(|&sample| {
+                        simulated.encode(sample);
+                        (i64::from(sample) - i64::from(simulated.sample1)).pow(2)
+                    })
+                    .sum::<i64>();
+                (predictor, state, error)
+            })
+            .min_by_key(|(_, _, error)| *error)
+            .expect("the standard coefficients are always present");
+
+        predictors.push(predictor as u8);
+        states.push(state);
+    }
+
+    out.extend_from_slice(&predictors);
+    for state in &states {
+        out.extend_from_slice(&(state.delta as i16).to_le_bytes());
+    }
+    for state in &states {
+        out.extend_from_slice(&state.sample1.to_le_bytes());
+    }
+    for state in &states {
+        out.extend_from_slice(&state.sample2.to_le_bytes());
+    }
+
+    let mut nibbles = frames[2 * n_channels..]
+        .iter()
+        .enumerate()
+        .map(|(i, &sample)| states[i % n_channels].encode(sample));
+    while let Some(high) = nibbles.next() {
+        let low = nibbles.next().unwrap_or(0);
+        out.push((high << 4) | low);
+    }
+}
+
+#[allow(clippy::arithmetic_side_effects)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_possible_wrap)]
+fn initial_state(samples: &[i16], coefficients: (i16, i16)) -> ChannelState {
+    let mut state = ChannelState {
+        coefficients,
+        delta: MIN_DELTA,
+        sample1: samples[1],
+        sample2: samples[0],
+    };
+
+    // Start with a delta matching the average prediction error of the first few samples.
+    let mut probe = state;
+    let count = samples.len().saturating_sub(2).min(16);
+    let total = samples[2..2 + count]
+        .iter()
+        .map(|&sample| {
+            let error = (i32::from(sample) - probe.predict()).abs();
+            probe.sample2 = probe.sample1;
+            probe.sample1 = sample;
+            i64::from(error)
+        })
+        .sum::<i64>();
+
+    if count > 0 {
+        let average = total / count as i64;
+        state.delta = (average / 4).clamp(MIN_DELTA.into(), i16::MAX.into()) as i32;
+    }
+
+    state
+}
@@ -0,0 +1,358 @@
+//! Playout buffer for received waves.
+//!
+//! Waves are scheduled on the timeline given by their timestamp: gaps are filled with silence, and
+//! overlapping audio is trimmed. The buffer then plays out at a slightly adjusted rate to keep the latency
+//! close to the configured target, so that audio does not drift away from video in long sessions.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use tracing::{debug, trace};
+
+use crate::pdu::{PitchPdu, VolumePdu};
+
+/// Fixed-point unit used for the volume, the pitch and the playback position.
+const ONE: u32 = 0x1_0000;
+
+/// A timestamp jump above this is treated as a discontinuity.
+const MAX_TIMESTAMP_JUMP_MS: u16 = 10_000;
+
+/// Playback rate correction applied when the latency is off target, as a shift of the current rate.
+const DRIFT_CORRECTION_SHIFT: u32 = 6;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct JitterBufferConfig {
+    /// Amount of buffered audio before starting playback, and latency the buffer steers towards.
+    pub target_latency: Duration,
+    /// Above this amount of buffered audio, the oldest waves are dropped.
+    pub max_latency: Duration,
+    /// Gaps and overlaps between waves smaller than this are ignored.
+    pub tolerance: Duration,
+}
+
+impl Default for JitterBufferConfig {
+    fn default() -> Self {
+        Self {
+            target_latency: Duration::from_millis(150),
+            max_latency: Duration::from_millis(500),
+            tolerance: Duration::from_millis(20),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct JitterBufferStats {
+    /// Number of times the buffer ran dry during playback.
+    pub underruns: u64,
+    /// Number of waves dropped, because they arrived too late or the buffer was full.
+    pub dropped_waves: u64,
+    /// Number of silent frames inserted to fill gaps between waves.
+    pub inserted_silence_frames: u64,
+    /// Number of frames removed from waves overlapping already buffered audio.
+    pub trimmed_frames: u64,
+}
+
+#[derive(Debug)]
+struct Chunk<T> {
+    /// Interleaved samples.
+    samples: Vec<i16>,
+    /// Position of the next frame to play.
+    pos: usize,
+    /// `None` for inserted silence.
+    tag: Option<T>,
+}
+
+impl<T> Chunk<T> {
+    fn remaining_frames(&self, n_channels: usize) -> usize {
+        frame_count(self.samples.len(), n_channels).saturating_sub(self.pos)
+    }
+}
+
+/// Jitter buffer for 16-bit PCM audio.
+///
+/// Each wave is pushed along with a tag (typically identifying the wave to confirm), which is given back
+/// once the wave has been entirely played, or dropped.
+#[derive(Debug)]
+pub struct JitterBuffer<T> {
+    config: JitterBufferConfig,
+    n_channels: usize,
+    sample_rate: u32,
+    chunks: VecDeque<Chunk<T>>,
+    /// Number of frames in `chunks` left to play.
+    buffered_frames: usize,
+    /// Timestamp of the last pushed wave, and the position (in frames) it was scheduled at.
+    last: Option<(u16, u64)>,
+    /// Position (in frames) of the end of the buffered audio.
+    end_frame: u64,
+    playing: bool,
+    /// Fractional part of the playback position, in 16.16 fixed point.
+    frac: u32,
+    volume: [u32; 2],
+    pitch: u32,
+    stats: JitterBufferStats,
+}
+
+impl<T> JitterBuffer<T> {
+    pub fn new(n_channels: u16, sample_rate: u32, config: JitterBufferConfig) -> Self {
+        Self {
+            config,
+            n_channels: usize::from(n_channels.max(1)),
+            sample_rate: sample_rate.max(1),
+            chunks: VecDeque::new(),
+            buffered_frames: 0,
+            last: None,
+            end_frame: 0,
+            playing: false,
+            frac: 0,
+            volume: [ONE; 2],
+            pitch: ONE,
+            stats: JitterBufferStats::default(),
+        }
+    }
+
+    pub fn n_channels(&self) -> u16 {
+        u16::try_from(self.n_channels).unwrap_or(u16::MAX)
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn stats(&self) -> JitterBufferStats {
+        self.stats
+    }
+
+    /// Returns the amount of audio waiting to be played.
+    #[allow(clippy::arithmetic_side_effects)] // The sample rate is never zero.
+    pub fn buffered(&self) -> Duration {
+        let micros = u64::try_from(self.buffered_frames)
+            .unwrap_or(u64::MAX)
+            .saturating_mul(1_000_000)
+            / u64::from(self.sample_rate);
+        Duration::from_micros(micros)
+    }
+
+    /// Applies the volume sent by the server, `0xFFFF` being full volume.
+    pub fn set_volume(&mut self, volume: VolumePdu) {
+        let scale = |v: u16| if v == u16::MAX { ONE } else { u32::from(v) };
+        self.volume = [scale(volume.volume_left), scale(volume.volume_right)];
+    }
+
+    /// Applies the pitch sent by the server, a 16.16 fixed-point multiplier of the playback rate.
+    ///
+    /// A pitch of zero is ignored, and the multiplier is clamped between ¼ and 4.
+    pub fn set_pitch(&mut self, pitch: PitchPdu) {
+        if pitch.pitch != 0 {
+            self.pitch = pitch.pitch.clamp(ONE / 4, ONE * 4);
+        }
+    }
+
+    /// Drops all buffered audio, returning the tags of the dropped waves.
+    pub fn clear(&mut self) -> Vec<T> {
+        let dropped = self.chunks.drain(..).filter_map(|chunk| chunk.tag).collect::<Vec<_>>();
+        self.stats.dropped_waves = self.stats.dropped_waves.saturating_add(dropped.len() as u64);
+        self.buffered_frames = 0;
+        self.last = None;
+        self.playing = false;
+        self.frac = 0;
+        dropped
+    }
+
+    /// Schedules a wave of interleaved `samples` according to its 16-bit timestamp `ts`, in milliseconds.
+    ///
+    /// The timestamp wraps around every 65.536 seconds.
+    ///
+    /// Returns the tags of the waves dropped in the process, which will never be played.
+    pub fn push(&mut self, ts: u16, mut samples: Vec<i16>, tag: T) -> Vec<T> {
+        let mut dropped = Vec::new();
+
+        let frames = frame_count(samples.len(), self.n_channels);
+        samples.truncate(frames.saturating_mul(self.n_channels));
+
+        let mut expected = match self.last {
+            Some((last_ts, last_frame)) if ts.wrapping_sub(last_ts) <= MAX_TIMESTAMP_JUMP_MS => {
+                last_frame.saturating_add(self.ms_to_frames(ts.wrapping_sub(last_ts)))
+            }
+            // Wave older than the previous one, scheduled before it.
+            Some((last_ts, last_frame)) if last_ts.wrapping_sub(ts) <= MAX_TIMESTAMP_JUMP_MS => {
+                last_frame.saturating_sub(self.ms_to_frames(last_ts.wrapping_sub(ts)))
+            }
+            // First wave, or discontinuity: play right after the buffered audio.
+            _ => self.end_frame,
+        };
+        let tolerance = self.duration_to_frames(self.config.tolerance);
+        if expected > self.end_frame {
+            let gap = expected.saturating_sub(self.end_frame);
+            if gap > tolerance {
+                let max = self.duration_to_frames(self.config.max_latency);
+                let silence = usize::try_from(gap.min(max)).unwrap_or(usize::MAX);
+                trace!(silence, "Filling gap between waves");
+                self.stats.inserted_silence_frames = self.stats.inserted_silence_frames.saturating_add(silence as u64);
+                self.push_chunk(vec![0; silence.saturating_mul(self.n_channels)], None);
+                // The gap may have been capped, so anchor the timeline on the actual position.
+                expected = self.end_frame;
+            }
+        } else {
+            let overlap = self.end_frame.saturating_sub(expected);
+            if overlap > tolerance {
+                let trimmed = usize::try_from(overlap).unwrap_or(usize::MAX).min(frames);
+                trace!(trimmed, "Trimming overlapping wave");
+                self.stats.trimmed_frames = self.stats.trimmed_frames.saturating_add(trimmed as u64);
+                samples.drain(..trimmed.saturating_mul(self.n_channels));
+            }
+        }
+
+        self.last = Some((ts, expected));
+
+        if samples.is_empty() {
+            self.stats.dropped_waves = self.stats.dropped_waves.saturating_add(1);
+            dropped.push(tag);
+        } else {
+            self.push_chunk(samples, Some(tag));
+        }
+
+        // Overrun: drop the oldest audio to get back to the target latency.
+        let max = self.duration_to_frames(self.config.max_latency);
+        if self.buffered_frames as u64 > max {
+            let target = self.duration_to_frames(self.config.target_latency);
+            debug!(buffered = ?self.buffered(), "Jitter buffer overrun");
+            while self.buffered_frames as u64 > target && self.chunks.len() > 1 {
+                let chunk = self.chunks.pop_front().expect("at least two chunks");
+                self.buffered_frames = self
+                    .buffered_frames
+                    .saturating_sub(chunk.remaining_frames(self.n_channels));
+                self.frac = 0;
+                if let Some(tag) = chunk.tag {
+                    self.stats.dropped_waves = self.stats.dropped_waves.saturating_add(1);
+                    dropped.push(tag);
+                }
+            }
+        }
+
+        if !self.playing && self.buffered_frames as u64 >= self.duration_to_frames(self.config.target_latency) {
+            self.playing = true;
+        }
+
+        dropped
+    }
+
+    /// Fills `out` with interleaved samples, returning the tags of the waves that finished playing.
+    ///
+    /// Outputs silence until enough audio is buffered, and when the buffer runs dry.
+    pub fn fill(&mut self, out: &mut [i16]) -> Vec<T> {
+        let mut played = Vec::new();
+        out.fill(0);
+
+        if !self.playing {
+            return played;
+        }
+
+        let step = self.step();
+        let n_channels = self.n_channels;
+
+        for frame in out.chunks_exact_mut(n_channels) {
+            let Some(current) = self.chunks.front() else {
+                debug!("Jitter buffer underrun");
+                self.stats.underruns = self.stats.underruns.saturating_add(1);
+                self.playing = false;
+                // Resynchronize on the next wave, the timeline is lost.
+                self.last = None;
+                self.frac = 0;
+                break;
+            };
+
+            let offset = current.pos.saturating_mul(n_channels);
+            let next = if current.remaining_frames(n_channels) > 1 {
+                current.samples.get(offset.saturating_add(n_channels)..)
+            } else {
+                self.chunks
+                    .get(1)
+                    .map(|chunk| &chunk.samples[chunk.pos.saturating_mul(n_channels)..])
+            };
+
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                let a = current.samples[offset.saturating_add(channel)];
+                let b = next.map_or(a, |next| next[channel]);
+                let value = interpolate(a, b, self.frac);
+                *sample = apply_volume(value, self.volume[channel.min(1)]);
+            }
+
+            self.frac = self.frac.saturating_add(step);
+            while self.frac >= ONE {
+                self.frac = self.frac.saturating_sub(ONE);
+                if let Some(tag) = self.advance() {
+                    played.push(tag);
+                }
+            }
+        }
+
+        played
+    }
+
+    /// Moves the playback position to the next frame, returning the tag of the wave if it finished playing.
+    fn advance(&mut self) -> Option<T> {
+        let n_channels = self.n_channels;
+        let chunk = self.chunks.front_mut()?;
+        chunk.pos = chunk.pos.saturating_add(1);
+        self.buffered_frames = self.buffered_frames.saturating_sub(1);
+
+        if chunk.remaining_frames(n_channels) == 0 {
+            self.chunks.pop_front().and_then(|chunk| chunk.tag)
+        } else {
+            None
+        }
+    }
+
+    fn push_chunk(&mut self, samples: Vec<i16>, tag: Option<T>) {
+        let frames = frame_count(samples.len(), self.n_channels);
+        self.buffered_frames = self.buffered_frames.saturating_add(frames);
+        self.end_frame = self.end_frame.saturating_add(frames as u64);
+        self.chunks.push_back(Chunk { samples, pos: 0, tag });
+    }
+
+    /// Returns the playback step, in 16.16 fixed point.
+    ///
+    /// The pitch is adjusted by a small amount when the latency drifts away from the target.
+    fn step(&self) -> u32 {
+        let target = self.duration_to_frames(self.config.target_latency);
+        let buffered = self.buffered_frames as u64;
+        let correction = self.pitch >> DRIFT_CORRECTION_SHIFT;
+
+        if buffered > target.saturating_add(target / 2) {
+            self.pitch.saturating_add(correction)
+        } else if buffered < target / 2 {
+            self.pitch.saturating_sub(correction)
+        } else {
+            self.pitch
+        }
+    }
+
+    fn ms_to_frames(&self, ms: u16) -> u64 {
+        u64::from(ms).saturating_mul(u64::from(self.sample_rate)) / 1000
+    }
+
+    fn duration_to_frames(&self, duration: Duration) -> u64 {
+        let frames = duration.as_micros().saturating_mul(u128::from(self.sample_rate)) / 1_000_000;
+        u64::try_from(frames).unwrap_or(u64::MAX)
+    }
+}
+
+#[allow(clippy::arithmetic_side_effects)] // The channel count is never zero.
+fn frame_count(n_samples: usize, n_channels: usize) -> usize {
+    n_samples / n_channels
+}
+
+// The interpolated value lies between `a` and `b`.
+#[allow(clippy::arithmetic_side_effects)]
+#[allow(clippy::cast_possible_truncation)]
+fn interpolate(a: i16, b: i16, frac: u32) -> i16 {
+    let (a, b) = (i64::from(a), i64::from(b));
+    (a + (((b - a) * i64::from(frac)) >> 16)) as i16
+}
+
+// The gain is at most `ONE`, so the scaled value stays within range.
+#[allow(clippy::arithmetic_side_effects)]
+#[allow(clippy::cast_possible_truncation)]
+fn apply_volume(sample: i16, gain: u32) -> i16 {
+    ((i64::from(sample) * i64::from(gain)) >> 16) as i16
+}
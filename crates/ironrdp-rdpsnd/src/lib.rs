@@ -4,5 +4,17 @@
 )]
 
 pub mod client;
+pub mod codec;
+pub mod jitter_buffer;
 pub mod pdu;
 pub mod server;
+
+/// Name of the reliable dynamic virtual channel used for audio output, as described in [\[MS-RDPEA\]].
+///
+/// [\[MS-RDPEA\]]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpea/bea2d5cf-e3b9-4419-92e5-0e074ff9bc5b
+pub const DVC_CHANNEL_NAME: &str = "AUDIO_PLAYBACK_DVC";
+
+/// Name of the lossy dynamic virtual channel used for audio output, as described in [\[MS-RDPEA\]].
+///
+/// [\[MS-RDPEA\]]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-rdpea/bea2d5cf-e3b9-4419-92e5-0e074ff9bc5b
+pub const LOSSY_DVC_CHANNEL_NAME: &str = "AUDIO_PLAYBACK_LOSSY_DVC";
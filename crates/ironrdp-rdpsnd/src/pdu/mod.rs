@@ -11,6 +11,7 @@ use ironrdp_core::{
     EncodeResult, ReadCursor, WriteCursor,
 };
 use ironrdp_core::{Decode, Encode};
+use ironrdp_dvc::DvcEncode;
 use ironrdp_pdu::{read_padding, write_padding};
 use ironrdp_svc::SvcEncode;
 
@@ -25,8 +26,8 @@ const SNDC_CLOSE: u8 = 0x01;
 const SNDC_WAVE2: u8 = 0x0D;
 const SNDC_VOLUME: u8 = 0x03;
 const SNDC_PITCH: u8 = 0x04;
-
-// TODO: UDP PDUs
+const SNDC_UDPWAVE: u8 = 0x0A;
+const SNDC_UDPWAVELAST: u8 = 0x0B;
 
 #[repr(u16)]
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Eq)]
@@ -679,7 +680,12 @@ pub struct WaveInfoPdu {
     pub timestamp: u16,
     pub format_no: u16,
     pub block_no: u8,
+    /// First four bytes of the audio data, the remaining ones are sent in the following [`SndWavePdu`].
     pub data: [u8; 4],
+    /// Total size of the audio data, including the four bytes of `data`.
+    ///
+    /// This is carried by the `bodySize` field of the PDU header.
+    pub wave_size: usize,
 }
 
 impl WaveInfoPdu {
@@ -691,6 +697,33 @@ impl WaveInfoPdu {
         + 1 /* cBlockNo */
         + 3 /* bPad */
         + 4 /* data */;
+
+    fn body_size(&self) -> usize {
+        (Self::FIXED_PART_SIZE - 4)
+            .checked_add(self.wave_size)
+            .expect("never overflow")
+    }
+
+    fn decode(src: &mut ReadCursor<'_>, body_size: u16) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let timestamp = src.read_u16();
+        let format_no = src.read_u16();
+        let block_no = src.read_u8();
+        read_padding!(src, 3);
+        let data = src.read_array();
+        let wave_size = usize::from(body_size)
+            .checked_sub(Self::FIXED_PART_SIZE - 4)
+            .ok_or_else(|| invalid_field_err!("Length", "WaveInfo body_size is too small"))?;
+
+        Ok(Self {
+            timestamp,
+            format_no,
+            block_no,
+            data,
+            wave_size,
+        })
+    }
 }
 
 impl Encode for WaveInfoPdu {
@@ -715,25 +748,7 @@ impl Encode for WaveInfoPdu {
     }
 }
 
-impl<'de> Decode<'de> for WaveInfoPdu {
-    fn decode(src: &mut ReadCursor<'de>) -> DecodeResult<Self> {
-        ensure_fixed_part_size!(in: src);
-
-        let timestamp = src.read_u16();
-        let format_no = src.read_u16();
-        let block_no = src.read_u8();
-        read_padding!(src, 3);
-        let data = src.read_array();
-
-        Ok(Self {
-            timestamp,
-            format_no,
-            block_no,
-            data,
-        })
-    }
-}
-
+/// Wave PDU, sent right after a [`WaveInfoPdu`] without `SNDPROLOG` header.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SndWavePdu {
     pub data: Vec<u8>,
@@ -778,6 +793,10 @@ impl<'de> SndWavePdu {
     }
 }
 
+impl SvcEncode for SndWavePdu {}
+
+impl DvcEncode for SndWavePdu {}
+
 // combines WaveInfoPdu + WavePdu
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct WavePdu<'a> {
@@ -795,21 +814,52 @@ impl WavePdu<'_> {
             .checked_add(self.data.len())
             .expect("never overflow")
     }
-}
 
-impl Encode for WavePdu<'_> {
-    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+    /// Splits the PDU into the WaveInfo and Wave PDUs, which are sent as two separate messages.
+    pub fn split(&self) -> EncodeResult<(WaveInfoPdu, SndWavePdu)> {
+        if self.data.len() < 4 {
+            return Err(other_err!("invalid data", "wave data is shorter than 4 bytes"));
+        }
+        let (head, tail) = self.data.split_at(4);
+
         let info = WaveInfoPdu {
             timestamp: self.timestamp,
             format_no: self.format_no,
             block_no: self.block_no,
-            data: self.data[0..4]
-                .try_into()
-                .map_err(|e| other_err!("invalid data", source: e))?,
-        };
-        let wave = SndWavePdu {
-            data: self.data[4..].into(),
+            data: head.try_into().map_err(|e| other_err!("invalid data", source: e))?,
+            wave_size: self.data.len(),
         };
+        let wave = SndWavePdu { data: tail.into() };
+
+        Ok((info, wave))
+    }
+
+    /// Reassembles the audio data of a WaveInfo PDU and of the following Wave PDU.
+    ///
+    /// The four padding bytes starting the Wave PDU are replaced by the four bytes carried by the WaveInfo PDU.
+    pub fn from_parts(info: WaveInfoPdu, wave: &[u8]) -> DecodeResult<WavePdu<'static>> {
+        let data_len = info
+            .wave_size
+            .checked_sub(4)
+            .ok_or_else(|| invalid_field_err!("Length", "WaveInfo body_size is too small"))?;
+        let wave = SndWavePdu::decode(&mut ReadCursor::new(wave), data_len)?;
+
+        let mut data = Vec::with_capacity(info.wave_size);
+        data.extend_from_slice(&info.data);
+        data.extend_from_slice(&wave.data);
+
+        Ok(WavePdu {
+            timestamp: info.timestamp,
+            format_no: info.format_no,
+            block_no: info.block_no,
+            data: data.into(),
+        })
+    }
+}
+
+impl Encode for WavePdu<'_> {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        let (info, wave) = self.split()?;
         info.encode(dst)?;
         wave.encode(dst)?;
         Ok(())
@@ -828,10 +878,10 @@ impl Encode for WavePdu<'_> {
 
 impl<'de> WavePdu<'_> {
     fn decode(src: &mut ReadCursor<'de>, body_size: u16) -> DecodeResult<Self> {
-        let info = WaveInfoPdu::decode(src)?;
-        let body_size = body_size as usize;
-        let data_len = body_size
-            .checked_sub(info.size())
+        let info = WaveInfoPdu::decode(src, body_size)?;
+        let data_len = info
+            .wave_size
+            .checked_sub(4)
             .ok_or_else(|| invalid_field_err!("Length", "WaveInfo body_size is too small"))?;
         let wave = SndWavePdu::decode(src, data_len)?;
 
@@ -1132,12 +1182,266 @@ impl<'de> Decode<'de> for PitchPdu {
     }
 }
 
+/// UDP Wave PDU, carrying a fragment of the audio data
+#[derive(Clone, PartialEq, Eq)]
+pub struct UdpWavePdu<'a> {
+    pub block_no: u8,
+    pub frag_no: u16,
+    pub data: Cow<'a, [u8]>,
+}
+
+impl fmt::Debug for UdpWavePdu<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UdpWavePdu")
+            .field("block_no", &self.block_no)
+            .field("frag_no", &self.frag_no)
+            .field("data.len()", &self.data.len())
+            .finish()
+    }
+}
+
+impl UdpWavePdu<'_> {
+    const NAME: &'static str = "SNDUDPWAVE";
+
+    /// Fragment numbers above this value are encoded on two bytes.
+    const MAX_SHORT_FRAG_NO: u16 = 0x7F;
+
+    pub const MAX_FRAG_NO: u16 = 0x7FFF;
+
+    const FIXED_PART_SIZE: usize =
+        1 /* Type */
+        + 1 /* cBlockNo */
+        + 1 /* cFragNo */;
+}
+
+impl Encode for UdpWavePdu<'_> {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(in: dst, size: self.size());
+
+        dst.write_u8(SNDC_UDPWAVE);
+        dst.write_u8(self.block_no);
+        if self.frag_no <= Self::MAX_SHORT_FRAG_NO {
+            dst.write_u8(cast_length!("cFragNo", self.frag_no)?);
+        } else if self.frag_no <= Self::MAX_FRAG_NO {
+            dst.write_u16_be(self.frag_no | 0x8000);
+        } else {
+            return Err(invalid_field_err!("wFragNo", "fragment number is too large"));
+        }
+        dst.write_slice(&self.data);
+
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        let frag_no_size = if self.frag_no <= Self::MAX_SHORT_FRAG_NO { 0 } else { 1 };
+
+        Self::FIXED_PART_SIZE
+            .checked_add(frag_no_size)
+            .and_then(|size| size.checked_add(self.data.len()))
+            .expect("never overflow")
+    }
+}
+
+impl<'de> Decode<'de> for UdpWavePdu<'de> {
+    fn decode(src: &mut ReadCursor<'de>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let msg_type = src.read_u8();
+        if msg_type != SNDC_UDPWAVE {
+            return Err(invalid_field_err!("Type", "not a UDP Wave PDU"));
+        }
+        let block_no = src.read_u8();
+        let frag_no = src.read_u8();
+        let frag_no = if frag_no & 0x80 != 0 {
+            ensure_size!(in: src, size: 1);
+            u16::from_be_bytes([frag_no & 0x7F, src.read_u8()])
+        } else {
+            u16::from(frag_no)
+        };
+        let data = src.read_slice(src.len()).into();
+
+        Ok(Self {
+            block_no,
+            frag_no,
+            data,
+        })
+    }
+}
+
+/// UDP Wave Last PDU, carrying the last fragment of the audio data
+#[derive(Clone, PartialEq, Eq)]
+pub struct UdpWaveLastPdu<'a> {
+    /// Total size of the audio data, including the previous fragments.
+    pub total_size: u16,
+    pub timestamp: u16,
+    pub format_no: u16,
+    pub block_no: u8,
+    pub data: Cow<'a, [u8]>,
+}
+
+impl fmt::Debug for UdpWaveLastPdu<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UdpWaveLastPdu")
+            .field("total_size", &self.total_size)
+            .field("timestamp", &self.timestamp)
+            .field("format_no", &self.format_no)
+            .field("block_no", &self.block_no)
+            .field("data.len()", &self.data.len())
+            .finish()
+    }
+}
+
+impl UdpWaveLastPdu<'_> {
+    const NAME: &'static str = "SNDUDPWAVELAST";
+
+    const FIXED_PART_SIZE: usize =
+        2 /* TotalSize */
+        + 2 /* wTimeStamp */
+        + 2 /* wFormatNo */
+        + 1 /* cBlockNo */
+        + 3 /* bPad */;
+}
+
+impl Encode for UdpWaveLastPdu<'_> {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(in: dst, size: self.size());
+
+        dst.write_u16(self.total_size);
+        dst.write_u16(self.timestamp);
+        dst.write_u16(self.format_no);
+        dst.write_u8(self.block_no);
+        write_padding!(dst, 3);
+        dst.write_slice(&self.data);
+
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        Self::FIXED_PART_SIZE
+            .checked_add(self.data.len())
+            .expect("never overflow")
+    }
+}
+
+impl<'de> Decode<'de> for UdpWaveLastPdu<'de> {
+    fn decode(src: &mut ReadCursor<'de>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        let total_size = src.read_u16();
+        let timestamp = src.read_u16();
+        let format_no = src.read_u16();
+        let block_no = src.read_u8();
+        read_padding!(src, 3);
+        let data = src.read_slice(src.len()).into();
+
+        Ok(Self {
+            total_size,
+            timestamp,
+            format_no,
+            block_no,
+            data,
+        })
+    }
+}
+
+/// Server Audio Output message sent over the UDP transport
+///
+/// Unlike the other variants, the UDP Wave PDU is not prefixed with `SNDPROLOG`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerAudioOutputUdpPdu<'a> {
+    Wave(UdpWavePdu<'a>),
+    WaveLast(UdpWaveLastPdu<'a>),
+    WaveEncrypt(WaveEncryptPdu),
+}
+
+impl ServerAudioOutputUdpPdu<'_> {
+    const NAME: &'static str = "ServerAudioOutputUdpPdu";
+
+    const FIXED_PART_SIZE: usize = 1 /* msgType */;
+
+    const PROLOG_SIZE: usize = 1 /* msgType */ + 1 /* padding*/ + 2 /* bodySize */;
+}
+
+impl Encode for ServerAudioOutputUdpPdu<'_> {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        let (msg_type, body_size) = match self {
+            Self::Wave(pdu) => return pdu.encode(dst),
+            Self::WaveLast(pdu) => (SNDC_UDPWAVELAST, pdu.size()),
+            Self::WaveEncrypt(pdu) => (SNDC_WAVEENCRYPT, pdu.size()),
+        };
+
+        ensure_size!(in: dst, size: Self::PROLOG_SIZE);
+        dst.write_u8(msg_type);
+        write_padding!(dst, 1);
+        dst.write_u16(cast_length!("ServerAudioOutputUdpPdu::bodySize", body_size)?);
+
+        match self {
+            Self::Wave(_) => Ok(()),
+            Self::WaveLast(pdu) => pdu.encode(dst),
+            Self::WaveEncrypt(pdu) => pdu.encode(dst),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Self::Wave(pdu) => pdu.size(),
+            Self::WaveLast(pdu) => Self::PROLOG_SIZE.checked_add(pdu.size()).expect("never overflow"),
+            Self::WaveEncrypt(pdu) => Self::PROLOG_SIZE.checked_add(pdu.size()).expect("never overflow"),
+        }
+    }
+}
+
+impl<'de> Decode<'de> for ServerAudioOutputUdpPdu<'de> {
+    fn decode(src: &mut ReadCursor<'de>) -> DecodeResult<Self> {
+        ensure_fixed_part_size!(in: src);
+
+        if src.peek_u8() == SNDC_UDPWAVE {
+            return Ok(Self::Wave(UdpWavePdu::decode(src)?));
+        }
+
+        ensure_size!(in: src, size: Self::PROLOG_SIZE);
+        let msg_type = src.read_u8();
+        read_padding!(src, 1);
+        let _body_size = src.read_u16();
+
+        match msg_type {
+            SNDC_UDPWAVELAST => {
+                let pdu = UdpWaveLastPdu::decode(src)?;
+                Ok(Self::WaveLast(pdu))
+            }
+            SNDC_WAVEENCRYPT => {
+                let pdu = WaveEncryptPdu::decode(src, Version::V5)?;
+                Ok(Self::WaveEncrypt(pdu))
+            }
+            _ => Err(invalid_field_err!(
+                "ServerAudioOutputUdpPdu::msgType",
+                "Unknown audio output UDP PDU type"
+            )),
+        }
+    }
+}
+
 /// Server Audio Output Channel message (PDU prefixed with `SNDPROLOG`)
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ServerAudioOutputPdu<'a> {
     AudioFormat(ServerAudioFormatPdu),
     CryptKey(CryptKeyPdu),
     Training(TrainingPdu),
+    /// WaveInfo PDU alone, the Wave PDU being sent as a separate message.
+    WaveInfo(WaveInfoPdu),
+    /// WaveInfo PDU immediately followed by the Wave PDU, in the same message.
     Wave(WavePdu<'a>),
     WaveEncrypt(WaveEncryptPdu),
     Close,
@@ -1160,6 +1464,7 @@ impl Encode for ServerAudioOutputPdu<'_> {
             Self::AudioFormat(pdu) => (SNDC_FORMATS, pdu.size()),
             Self::CryptKey(pdu) => (SNDC_CRYPTKEY, pdu.size()),
             Self::Training(pdu) => (SNDC_TRAINING, pdu.size()),
+            Self::WaveInfo(pdu) => (SNDC_WAVE, pdu.body_size()),
             Self::Wave(pdu) => (SNDC_WAVE, pdu.body_size()),
             Self::WaveEncrypt(pdu) => (SNDC_WAVEENCRYPT, pdu.size()),
             Self::Close => (SNDC_CLOSE, 0),
@@ -1176,6 +1481,7 @@ impl Encode for ServerAudioOutputPdu<'_> {
             Self::AudioFormat(pdu) => pdu.encode(dst),
             Self::CryptKey(pdu) => pdu.encode(dst),
             Self::Training(pdu) => pdu.encode(dst),
+            Self::WaveInfo(pdu) => pdu.encode(dst),
             Self::Wave(pdu) => pdu.encode(dst),
             Self::WaveEncrypt(pdu) => pdu.encode(dst),
             Self::Close => Ok(()),
@@ -1197,6 +1503,7 @@ impl Encode for ServerAudioOutputPdu<'_> {
                 Self::AudioFormat(pdu) => pdu.size(),
                 Self::CryptKey(pdu) => pdu.size(),
                 Self::Training(pdu) => pdu.size(),
+                Self::WaveInfo(pdu) => pdu.size(),
                 Self::Wave(pdu) => pdu.size(),
                 Self::WaveEncrypt(pdu) => pdu.size(),
                 Self::Close => 0,
@@ -1229,6 +1536,11 @@ impl<'de> Decode<'de> for ServerAudioOutputPdu<'_> {
                 let pdu = TrainingPdu::decode(src)?;
                 Ok(Self::Training(pdu))
             }
+            // The Wave PDU is usually received as a separate message, see `WavePdu::from_parts`.
+            SNDC_WAVE if src.len() == WaveInfoPdu::FIXED_PART_SIZE => {
+                let pdu = WaveInfoPdu::decode(src, body_size)?;
+                Ok(Self::WaveInfo(pdu))
+            }
             SNDC_WAVE => {
                 let pdu = WavePdu::decode(src, body_size)?;
                 Ok(Self::Wave(pdu))
@@ -1260,6 +1572,8 @@ impl<'de> Decode<'de> for ServerAudioOutputPdu<'_> {
 
 impl SvcEncode for ServerAudioOutputPdu<'_> {}
 
+impl DvcEncode for ServerAudioOutputPdu<'static> {}
+
 /// Client Audio Output Channel message (PDU prefixed with `SNDPROLOG`)
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ClientAudioOutputPdu {
@@ -1350,3 +1664,5 @@ impl<'de> Decode<'de> for ClientAudioOutputPdu {
 }
 
 impl SvcEncode for ClientAudioOutputPdu {}
+
+impl DvcEncode for ClientAudioOutputPdu {}
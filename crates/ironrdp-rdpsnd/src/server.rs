@@ -1,14 +1,23 @@
+use ironrdp_core::cast_length;
+use ironrdp_core::encode_vec;
 use ironrdp_core::impl_as_any;
 use ironrdp_core::Decode;
+use ironrdp_core::EncodeResult;
 use ironrdp_core::ReadCursor;
+use ironrdp_dvc::{encode_dvc_messages, DvcMessage, DvcProcessor, DvcServerProcessor};
 use ironrdp_pdu::decode_err;
+use ironrdp_pdu::encode_err;
 use ironrdp_pdu::gcc::ChannelName;
 use ironrdp_pdu::pdu_other_err;
 use ironrdp_pdu::PduResult;
-use ironrdp_svc::{CompressionCondition, SvcMessage, SvcProcessor, SvcProcessorMessages, SvcServerProcessor};
+use ironrdp_svc::{
+    ChannelFlags, CompressionCondition, SvcMessage, SvcProcessor, SvcProcessorMessages, SvcServerProcessor,
+};
 use tracing::{debug, error};
 
+use crate::codec::Encoder;
 use crate::pdu::{self, ClientAudioFormatPdu, QualityMode};
+use crate::{DVC_CHANNEL_NAME, LOSSY_DVC_CHANNEL_NAME};
 
 pub type RdpsndSvcMessages = SvcProcessorMessages<RdpsndServer>;
 
@@ -21,6 +30,10 @@ impl<T> RdpsndError for T where T: std::error::Error + Send + Sync + 'static {}
 pub enum RdpsndServerMessage {
     /// Wave data, with timestamp
     Wave(Vec<u8>, u32),
+    /// Interleaved 16-bit PCM samples, with timestamp, to be encoded in the negotiated format
+    ///
+    /// The samples must use the sample rate and channel count of the negotiated format.
+    PcmWave(Vec<i16>, u32),
     Close,
     /// Failure received from the OS event loop.
     ///
@@ -46,6 +59,10 @@ enum RdpsndState {
     Stop,
 }
 
+/// Server side of the audio output channel.
+///
+/// The same processor can be used either as the `rdpsnd` static channel, or as one of the
+/// `AUDIO_PLAYBACK_DVC` and `AUDIO_PLAYBACK_LOSSY_DVC` dynamic channels.
 #[derive(Debug)]
 pub struct RdpsndServer {
     handler: Box<dyn RdpsndServerHandler>,
@@ -54,6 +71,10 @@ pub struct RdpsndServer {
     quality_mode: Option<QualityMode>,
     block_no: u8,
     format_no: Option<u16>,
+    /// Encoder for the negotiated format, if supported by [`crate::codec`].
+    encoder: Option<Encoder>,
+    lossy: bool,
+    channel_id: Option<u32>,
 }
 
 impl RdpsndServer {
@@ -66,10 +87,20 @@ impl RdpsndServer {
             client_format: None,
             quality_mode: None,
             format_no: None,
+            encoder: None,
             block_no: 0,
+            lossy: false,
+            channel_id: None,
         }
     }
 
+    /// Uses the `AUDIO_PLAYBACK_LOSSY_DVC` channel instead of `AUDIO_PLAYBACK_DVC` when used as a dynamic channel.
+    #[must_use]
+    pub fn with_lossy_dvc(mut self) -> Self {
+        self.lossy = true;
+        self
+    }
+
     pub fn version(&self) -> PduResult<pdu::Version> {
         let client_format = self
             .client_format
@@ -80,23 +111,197 @@ impl RdpsndServer {
     }
 
     pub fn training_pdu(&mut self) -> PduResult<RdpsndSvcMessages> {
+        Ok(RdpsndSvcMessages::new(vec![Self::training().into()]))
+    }
+
+    /// Returns the messages for the given wave data.
+    ///
+    /// For clients older than version 8, the wave is sent as a WaveInfo PDU followed by a separate Wave PDU.
+    pub fn wave(&mut self, data: Vec<u8>, ts: u32) -> PduResult<RdpsndSvcMessages> {
+        let (pdu, wave) = self.wave_pdu(data, ts)?;
+        let mut msgs = vec![SvcMessage::from(pdu)];
+        msgs.extend(wave.map(SvcMessage::from));
+        Ok(RdpsndSvcMessages::new(msgs))
+    }
+
+    /// Encodes PCM `samples` in the negotiated format, and returns the messages for the resulting wave data.
+    ///
+    /// With ADPCM formats, samples not filling a whole block are kept for the next call.
+    pub fn pcm_wave(&mut self, samples: &[i16], ts: u32) -> PduResult<RdpsndSvcMessages> {
+        let data = self.encode_pcm(samples)?;
+        if data.is_empty() {
+            return Ok(RdpsndSvcMessages::new(vec![]));
+        }
+        self.wave(data, ts)
+    }
+
+    /// Sends the seed used to encrypt the data of the Wave Encrypt PDUs.
+    pub fn crypt_key(&mut self, seed: [u8; 32]) -> PduResult<RdpsndSvcMessages> {
+        Ok(RdpsndSvcMessages::new(vec![Self::crypt_key_pdu(seed).into()]))
+    }
+
+    /// Returns a Wave Encrypt PDU for wave `data` already encrypted by the caller.
+    ///
+    /// The `signature` must be provided for clients of version 5 and later.
+    pub fn wave_encrypt(&mut self, data: Vec<u8>, signature: Option<[u8; 8]>) -> PduResult<RdpsndSvcMessages> {
+        let pdu = self.wave_encrypt_pdu(data, signature)?;
+        Ok(RdpsndSvcMessages::new(vec![pdu::ServerAudioOutputPdu::WaveEncrypt(
+            pdu,
+        )
+        .into()]))
+    }
+
+    pub fn close(&mut self) -> PduResult<RdpsndSvcMessages> {
+        Ok(RdpsndSvcMessages::new(vec![pdu::ServerAudioOutputPdu::Close.into()]))
+    }
+
+    /// Same as [`Self::wave`], but encoded for the `DRDYNVC` channel when used as a dynamic channel.
+    pub fn encode_dvc_wave(&mut self, data: Vec<u8>, ts: u32) -> PduResult<Vec<SvcMessage>> {
+        let (pdu, wave) = self.wave_pdu(data, ts)?;
+        let mut msgs: Vec<DvcMessage> = vec![Box::new(pdu)];
+        if let Some(wave) = wave {
+            msgs.push(Box::new(wave));
+        }
+        self.encode_dvc(msgs)
+    }
+
+    /// Same as [`Self::pcm_wave`], but encoded for the `DRDYNVC` channel when used as a dynamic channel.
+    pub fn encode_dvc_pcm_wave(&mut self, samples: &[i16], ts: u32) -> PduResult<Vec<SvcMessage>> {
+        let data = self.encode_pcm(samples)?;
+        if data.is_empty() {
+            return Ok(vec![]);
+        }
+        self.encode_dvc_wave(data, ts)
+    }
+
+    /// Same as [`Self::crypt_key`], but encoded for the `DRDYNVC` channel when used as a dynamic channel.
+    pub fn encode_dvc_crypt_key(&mut self, seed: [u8; 32]) -> PduResult<Vec<SvcMessage>> {
+        self.encode_dvc(vec![Box::new(Self::crypt_key_pdu(seed))])
+    }
+
+    /// Same as [`Self::wave_encrypt`], but encoded for the `DRDYNVC` channel when used as a dynamic channel.
+    pub fn encode_dvc_wave_encrypt(&mut self, data: Vec<u8>, signature: Option<[u8; 8]>) -> PduResult<Vec<SvcMessage>> {
+        let pdu = self.wave_encrypt_pdu(data, signature)?;
+        self.encode_dvc(vec![Box::new(pdu::ServerAudioOutputPdu::WaveEncrypt(pdu))])
+    }
+
+    /// Same as [`Self::close`], but encoded for the `DRDYNVC` channel when used as a dynamic channel.
+    pub fn encode_dvc_close(&mut self) -> PduResult<Vec<SvcMessage>> {
+        self.encode_dvc(vec![Box::new(pdu::ServerAudioOutputPdu::Close)])
+    }
+
+    /// Returns the datagrams to send over the UDP transport for the given wave data.
+    ///
+    /// The data is split into UDP Wave PDUs of at most `fragment_size` bytes of data, followed by a UDP Wave Last PDU.
+    pub fn udp_wave(&mut self, data: &[u8], ts: u16, fragment_size: usize) -> PduResult<Vec<Vec<u8>>> {
+        let format_no = self
+            .format_no
+            .ok_or_else(|| pdu_other_err!("invalid state - no format"))?;
+        let total_size: EncodeResult<_> = cast_length!("TotalSize", data.len());
+        let total_size = total_size.map_err(|e| encode_err!(e))?;
+        let block_no = self.block_no;
+
+        let mut fragments = data.chunks(fragment_size.max(1));
+        let last = fragments.next_back().unwrap_or_default();
+
+        let mut datagrams = Vec::new();
+        for (frag_no, fragment) in fragments.enumerate() {
+            let frag_no = u16::try_from(frag_no)
+                .ok()
+                .filter(|frag_no| *frag_no <= pdu::UdpWavePdu::MAX_FRAG_NO)
+                .ok_or_else(|| pdu_other_err!("too many UDP wave fragments"))?;
+            let pdu = pdu::ServerAudioOutputUdpPdu::Wave(pdu::UdpWavePdu {
+                block_no,
+                frag_no,
+                data: fragment.into(),
+            });
+            datagrams.push(encode_vec(&pdu).map_err(|e| encode_err!(e))?);
+        }
+
+        let pdu = pdu::ServerAudioOutputUdpPdu::WaveLast(pdu::UdpWaveLastPdu {
+            total_size,
+            timestamp: ts,
+            format_no,
+            block_no,
+            data: last.into(),
+        });
+        datagrams.push(encode_vec(&pdu).map_err(|e| encode_err!(e))?);
+
+        self.block_no = self.block_no.overflowing_add(1).0;
+
+        Ok(datagrams)
+    }
+
+    /// Same as [`Self::wave_encrypt`], but encoded as a datagram for the UDP transport.
+    pub fn udp_wave_encrypt(&mut self, data: Vec<u8>, signature: Option<[u8; 8]>) -> PduResult<Vec<u8>> {
+        let pdu = pdu::ServerAudioOutputUdpPdu::WaveEncrypt(self.wave_encrypt_pdu(data, signature)?);
+        encode_vec(&pdu).map_err(|e| encode_err!(e))
+    }
+
+    fn encode_dvc(&self, msgs: Vec<DvcMessage>) -> PduResult<Vec<SvcMessage>> {
+        let channel_id = self
+            .channel_id
+            .ok_or_else(|| pdu_other_err!("invalid state - no channel ID"))?;
+
+        encode_dvc_messages(channel_id, msgs, ChannelFlags::SHOW_PROTOCOL).map_err(|e| encode_err!(e))
+    }
+
+    fn encode_pcm(&mut self, samples: &[i16]) -> PduResult<Vec<u8>> {
+        let encoder = self
+            .encoder
+            .as_mut()
+            .ok_or_else(|| pdu_other_err!("invalid state - no encoder for the negotiated format"))?;
+
+        Ok(encoder.encode(samples))
+    }
+
+    fn crypt_key_pdu(seed: [u8; 32]) -> pdu::ServerAudioOutputPdu<'static> {
+        pdu::ServerAudioOutputPdu::CryptKey(pdu::CryptKeyPdu { seed })
+    }
+
+    fn wave_encrypt_pdu(&mut self, data: Vec<u8>, signature: Option<[u8; 8]>) -> PduResult<pdu::WaveEncryptPdu> {
+        let version = self.version()?;
+        let format_no = self
+            .format_no
+            .ok_or_else(|| pdu_other_err!("invalid state - no format"))?;
+
+        if signature.is_some() != (version >= pdu::Version::V5) {
+            return Err(pdu_other_err!("signature is required from version 5 only"));
+        }
+
+        let pdu = pdu::WaveEncryptPdu {
+            timestamp: 0,
+            format_no,
+            block_no: self.block_no,
+            signature,
+            data,
+        };
+
+        self.block_no = self.block_no.overflowing_add(1).0;
+
+        Ok(pdu)
+    }
+
+    fn training() -> pdu::ServerAudioOutputPdu<'static> {
         let pdu = pdu::TrainingPdu {
             timestamp: 4231, // a random number
             data: vec![],
         };
-        Ok(RdpsndSvcMessages::new(vec![
-            pdu::ServerAudioOutputPdu::Training(pdu).into()
-        ]))
+        pdu::ServerAudioOutputPdu::Training(pdu)
     }
 
-    pub fn wave(&mut self, data: Vec<u8>, ts: u32) -> PduResult<RdpsndSvcMessages> {
+    fn wave_pdu(
+        &mut self,
+        data: Vec<u8>,
+        ts: u32,
+    ) -> PduResult<(pdu::ServerAudioOutputPdu<'static>, Option<pdu::SndWavePdu>)> {
         let version = self.version()?;
         let format_no = self
             .format_no
             .ok_or_else(|| pdu_other_err!("invalid state - no format"))?;
 
         // The server doesn't wait for wave confirm, apparently FreeRDP neither.
-        let msg = if version >= pdu::Version::V8 {
+        let msgs = if version >= pdu::Version::V8 {
             let pdu = pdu::Wave2Pdu {
                 block_no: self.block_no,
                 timestamp: 0,
@@ -104,7 +309,7 @@ impl RdpsndServer {
                 format_no,
                 data: data.into(),
             };
-            RdpsndSvcMessages::new(vec![pdu::ServerAudioOutputPdu::Wave2(pdu).into()])
+            (pdu::ServerAudioOutputPdu::Wave2(pdu), None)
         } else {
             let pdu = pdu::WavePdu {
                 block_no: self.block_no,
@@ -112,32 +317,30 @@ impl RdpsndServer {
                 timestamp: 0,
                 data: data.into(),
             };
-            RdpsndSvcMessages::new(vec![pdu::ServerAudioOutputPdu::Wave(pdu).into()])
+            let (info, wave) = pdu.split().map_err(|e| encode_err!(e))?;
+            (pdu::ServerAudioOutputPdu::WaveInfo(info), Some(wave))
         };
 
         self.block_no = self.block_no.overflowing_add(1).0;
 
-        Ok(msg)
+        Ok(msgs)
     }
 
-    pub fn close(&mut self) -> PduResult<RdpsndSvcMessages> {
-        Ok(RdpsndSvcMessages::new(vec![pdu::ServerAudioOutputPdu::Close.into()]))
-    }
-}
-
-impl_as_any!(RdpsndServer);
+    fn start_pdu(&mut self) -> pdu::ServerAudioOutputPdu<'static> {
+        if self.state != RdpsndState::Start {
+            error!("Attempted to start rdpsnd channel in invalid state");
+        }
 
-impl SvcProcessor for RdpsndServer {
-    fn channel_name(&self) -> ChannelName {
-        Self::NAME
-    }
+        self.state = RdpsndState::WaitingForClientFormats;
 
-    fn compression_condition(&self) -> CompressionCondition {
-        CompressionCondition::Never
+        pdu::ServerAudioOutputPdu::AudioFormat(pdu::ServerAudioFormatPdu {
+            version: pdu::Version::V8,
+            formats: self.handler.get_formats().into(),
+        })
     }
 
-    fn process(&mut self, payload: &[u8]) -> PduResult<Vec<SvcMessage>> {
-        let pdu = pdu::ClientAudioOutputPdu::decode(&mut ReadCursor::new(payload)).map_err(|e| decode_err!(e))?;
+    /// Runs the state machine shared by the static and dynamic channel transports.
+    fn process_pdu(&mut self, pdu: pdu::ClientAudioOutputPdu) -> PduResult<Vec<pdu::ServerAudioOutputPdu<'static>>> {
         debug!(?pdu);
         let msg = match self.state {
             RdpsndState::WaitingForClientFormats => {
@@ -152,7 +355,7 @@ impl SvcProcessor for RdpsndServer {
                     vec![]
                 } else {
                     self.state = RdpsndState::WaitingForTrainingConfirm;
-                    self.training_pdu()?.into()
+                    vec![Self::training()]
                 }
             }
             RdpsndState::WaitingForQualityMode => {
@@ -163,7 +366,7 @@ impl SvcProcessor for RdpsndServer {
                 };
                 self.quality_mode = Some(pdu.quality_mode);
                 self.state = RdpsndState::WaitingForTrainingConfirm;
-                self.training_pdu()?.into()
+                vec![Self::training()]
             }
             RdpsndState::WaitingForTrainingConfirm => {
                 let pdu::ClientAudioOutputPdu::TrainingConfirm(_) = pdu else {
@@ -174,6 +377,10 @@ impl SvcProcessor for RdpsndServer {
                 let client_format = self.client_format.as_ref().expect("available in this state");
                 self.state = RdpsndState::Ready;
                 self.format_no = self.handler.start(client_format);
+                self.encoder = self
+                    .format_no
+                    .and_then(|format_no| client_format.formats.get(usize::from(format_no)))
+                    .and_then(Encoder::new);
                 vec![]
             }
             RdpsndState::Ready => {
@@ -189,26 +396,70 @@ impl SvcProcessor for RdpsndServer {
         };
         Ok(msg)
     }
+}
+
+impl_as_any!(RdpsndServer);
+
+impl SvcProcessor for RdpsndServer {
+    fn channel_name(&self) -> ChannelName {
+        Self::NAME
+    }
+
+    fn compression_condition(&self) -> CompressionCondition {
+        CompressionCondition::Never
+    }
+
+    fn process(&mut self, payload: &[u8]) -> PduResult<Vec<SvcMessage>> {
+        let pdu = pdu::ClientAudioOutputPdu::decode(&mut ReadCursor::new(payload)).map_err(|e| decode_err!(e))?;
+
+        Ok(self.process_pdu(pdu)?.into_iter().map(SvcMessage::from).collect())
+    }
 
     fn start(&mut self) -> PduResult<Vec<SvcMessage>> {
-        if self.state != RdpsndState::Start {
-            error!("Attempted to start rdpsnd channel in invalid state");
+        Ok(vec![SvcMessage::from(self.start_pdu())])
+    }
+}
+
+impl SvcServerProcessor for RdpsndServer {}
+
+impl DvcProcessor for RdpsndServer {
+    fn channel_name(&self) -> &str {
+        if self.lossy {
+            LOSSY_DVC_CHANNEL_NAME
+        } else {
+            DVC_CHANNEL_NAME
         }
+    }
 
-        let pdu = pdu::ServerAudioOutputPdu::AudioFormat(pdu::ServerAudioFormatPdu {
-            version: pdu::Version::V8,
-            formats: self.handler.get_formats().into(),
-        });
+    fn start(&mut self, channel_id: u32) -> PduResult<Vec<DvcMessage>> {
+        self.channel_id = Some(channel_id);
 
-        self.state = RdpsndState::WaitingForClientFormats;
-        Ok(vec![SvcMessage::from(pdu)])
+        Ok(vec![Box::new(self.start_pdu())])
+    }
+
+    fn process(&mut self, _channel_id: u32, payload: &[u8]) -> PduResult<Vec<DvcMessage>> {
+        let pdu = pdu::ClientAudioOutputPdu::decode(&mut ReadCursor::new(payload)).map_err(|e| decode_err!(e))?;
+
+        Ok(self
+            .process_pdu(pdu)?
+            .into_iter()
+            .map(|pdu| Box::new(pdu) as DvcMessage)
+            .collect())
+    }
+
+    fn close(&mut self, _channel_id: u32) {
+        self.handler.stop();
+        self.state = RdpsndState::Start;
+        self.channel_id = None;
+        self.format_no = None;
+        self.encoder = None;
     }
 }
 
+impl DvcServerProcessor for RdpsndServer {}
+
 impl Drop for RdpsndServer {
     fn drop(&mut self) {
         self.handler.stop();
     }
 }
-
-impl SvcServerProcessor for RdpsndServer {}
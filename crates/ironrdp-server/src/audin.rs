@@ -0,0 +1,5 @@
+pub use ironrdp_audin::server::AudinServerHandler;
+
+pub trait AudinServerFactory: Send {
+    fn build_backend(&self) -> Box<dyn AudinServerHandler>;
+}
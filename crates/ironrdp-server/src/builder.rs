@@ -1,13 +1,16 @@
 use std::net::SocketAddr;
+use std::sync::Arc;
 
 use anyhow::Result;
 use tokio_rustls::TlsAcceptor;
 
+use super::audin::AudinServerFactory;
 use super::clipboard::CliprdrServerFactory;
+use super::connection::{ConnectionHandlerFactory, SharedHandlers};
 use super::display::{DesktopSize, RdpServerDisplay};
 use super::handler::{KeyboardEvent, MouseEvent, RdpServerInputHandler};
 use super::server::*;
-use crate::{DisplayUpdate, RdpServerDisplayUpdates, SoundServerFactory};
+use crate::{DisplayUpdate, H264EncoderFactory, RdpServerDisplayUpdates, RdpdrServerFactory, SoundServerFactory};
 
 pub struct WantsAddr {}
 pub struct WantsSecurity {
@@ -26,10 +29,16 @@ pub struct BuilderDone {
     addr: SocketAddr,
     security: RdpServerSecurity,
     with_remote_fx: bool,
-    handler: Box<dyn RdpServerInputHandler>,
-    display: Box<dyn RdpServerDisplay>,
+    with_gfx: bool,
+    with_damage_tracking: bool,
+    with_scroll_detection: bool,
+    h264: Option<Arc<dyn H264EncoderFactory>>,
+    max_connections: Option<usize>,
+    handlers: Box<dyn ConnectionHandlerFactory>,
     cliprdr_factory: Option<Box<dyn CliprdrServerFactory>>,
     sound_factory: Option<Box<dyn SoundServerFactory>>,
+    audin_factory: Option<Box<dyn AudinServerFactory>>,
+    rdpdr_factory: Option<Box<dyn RdpdrServerFactory>>,
 }
 
 pub struct RdpServerBuilder<State> {
@@ -109,6 +118,16 @@ impl RdpServerBuilder<WantsHandler> {
             },
         }
     }
+
+    /// Builds the input and display handlers of each connection with `factory`, instead of sharing them.
+    pub fn with_handler_factory<F>(self, factory: F) -> RdpServerBuilder<BuilderDone>
+    where
+        F: ConnectionHandlerFactory + 'static,
+    {
+        RdpServerBuilder {
+            state: BuilderDone::new(self.state.addr, self.state.security, Box::new(factory)),
+        }
+    }
 }
 
 impl RdpServerBuilder<WantsDisplay> {
@@ -117,29 +136,41 @@ impl RdpServerBuilder<WantsDisplay> {
         D: RdpServerDisplay + 'static,
     {
         RdpServerBuilder {
-            state: BuilderDone {
-                addr: self.state.addr,
-                security: self.state.security,
-                handler: self.state.handler,
-                display: Box::new(display),
-                sound_factory: None,
-                cliprdr_factory: None,
-                with_remote_fx: true,
-            },
+            state: BuilderDone::new(
+                self.state.addr,
+                self.state.security,
+                Box::new(SharedHandlers::new(self.state.handler, Box::new(display))),
+            ),
         }
     }
 
     pub fn with_no_display(self) -> RdpServerBuilder<BuilderDone> {
         RdpServerBuilder {
-            state: BuilderDone {
-                addr: self.state.addr,
-                security: self.state.security,
-                handler: self.state.handler,
-                display: Box::new(NoopDisplay),
-                sound_factory: None,
-                cliprdr_factory: None,
-                with_remote_fx: true,
-            },
+            state: BuilderDone::new(
+                self.state.addr,
+                self.state.security,
+                Box::new(SharedHandlers::new(self.state.handler, Box::new(NoopDisplay))),
+            ),
+        }
+    }
+}
+
+impl BuilderDone {
+    fn new(addr: SocketAddr, security: RdpServerSecurity, handlers: Box<dyn ConnectionHandlerFactory>) -> Self {
+        Self {
+            addr,
+            security,
+            with_remote_fx: true,
+            with_gfx: true,
+            with_damage_tracking: false,
+            with_scroll_detection: false,
+            h264: None,
+            max_connections: None,
+            handlers,
+            cliprdr_factory: None,
+            sound_factory: None,
+            audin_factory: None,
+            rdpdr_factory: None,
         }
     }
 }
@@ -155,22 +186,71 @@ impl RdpServerBuilder<BuilderDone> {
         self
     }
 
+    pub fn with_audin_factory(mut self, audin: Option<Box<dyn AudinServerFactory>>) -> Self {
+        self.state.audin_factory = audin;
+        self
+    }
+
+    /// Redirects the devices of the client, such as [`crate::ClientDevices`].
+    pub fn with_rdpdr_factory(mut self, rdpdr: Option<Box<dyn RdpdrServerFactory>>) -> Self {
+        self.state.rdpdr_factory = rdpdr;
+        self
+    }
+
     pub fn with_remote_fx(mut self, enabled: bool) -> Self {
         self.state.with_remote_fx = enabled;
         self
     }
 
+    /// Sends display updates through the graphics pipeline to the clients supporting it, see
+    /// [`RdpServerOptions::with_gfx`].
+    pub fn with_gfx(mut self, enabled: bool) -> Self {
+        self.state.with_gfx = enabled;
+        self
+    }
+
+    /// Only sends the parts of bitmap updates that changed, see [`RdpServerOptions::with_damage_tracking`].
+    pub fn with_damage_tracking(mut self, enabled: bool) -> Self {
+        self.state.with_damage_tracking = enabled;
+        self
+    }
+
+    /// Sends scrolled regions as copies, see [`RdpServerOptions::with_scroll_detection`].
+    pub fn with_scroll_detection(mut self, enabled: bool) -> Self {
+        self.state.with_scroll_detection = enabled;
+        self
+    }
+
+    /// Encodes graphics pipeline updates with H.264 (AVC420) for the clients supporting it, see
+    /// [`RdpServerOptions::h264`].
+    pub fn with_h264_encoder_factory(mut self, factory: Option<Box<dyn H264EncoderFactory>>) -> Self {
+        self.state.h264 = factory.map(Arc::from);
+        self
+    }
+
+    /// Limits the number of concurrent connections, see [`RdpServerOptions::max_connections`].
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.state.max_connections = Some(max_connections);
+        self
+    }
+
     pub fn build(self) -> RdpServer {
-        RdpServer::new(
+        RdpServer::new_with_handler_factory(
             RdpServerOptions {
                 addr: self.state.addr,
                 security: self.state.security,
                 with_remote_fx: self.state.with_remote_fx,
+                with_gfx: self.state.with_gfx,
+                with_damage_tracking: self.state.with_damage_tracking,
+                with_scroll_detection: self.state.with_scroll_detection,
+                h264: self.state.h264,
+                max_connections: self.state.max_connections,
             },
-            self.state.handler,
-            self.state.display,
+            self.state.handlers,
             self.state.sound_factory,
             self.state.cliprdr_factory,
+            self.state.audin_factory,
+            self.state.rdpdr_factory,
         )
     }
 }
@@ -1,5 +1,224 @@
-use ironrdp_cliprdr::backend::CliprdrBackendFactory;
+use std::sync::{Arc, Mutex};
 
-use crate::ServerEventSender;
+use ironrdp_cliprdr::backend::{ClipboardMessage, ClipboardMessageProxy, CliprdrBackend, CliprdrBackendFactory};
+use ironrdp_cliprdr::policy::{ClipboardPolicy, PolicyBackend};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+use crate::{ServerEvent, ServerEventSender};
 
 pub trait CliprdrServerFactory: CliprdrBackendFactory + ServerEventSender {}
+
+/// [`CliprdrServerFactory`] enforcing a [`ClipboardPolicy`] on the backends of another factory.
+///
+/// Messages sent by the backends through their server event sender are filtered with
+/// [`ClipboardPolicy::filter_message`], and the built backends are wrapped into
+/// [`PolicyBackend`]s. Each connection has its own [`ClipboardPolicy::new_session`], so that the
+/// formats offered by a client only allow requests of that client.
+pub struct PolicyCliprdrServerFactory {
+    inner: Box<dyn CliprdrServerFactory>,
+    policy: Arc<ClipboardPolicy>,
+    /// Session of the connection given by the last [`ServerEventSender::set_sender`] call.
+    session: Mutex<Option<PolicySession>>,
+}
+
+/// Policy of a connection, along with the events sent by its backend, not filtered yet.
+struct PolicySession {
+    policy: Arc<ClipboardPolicy>,
+    sender: mpsc::UnboundedSender<ServerEvent>,
+    events: Option<mpsc::UnboundedReceiver<ServerEvent>>,
+}
+
+impl PolicyCliprdrServerFactory {
+    pub fn new(inner: Box<dyn CliprdrServerFactory>, policy: Arc<ClipboardPolicy>) -> Self {
+        Self {
+            inner,
+            policy,
+            session: Mutex::new(None),
+        }
+    }
+}
+
+impl CliprdrBackendFactory for PolicyCliprdrServerFactory {
+    fn build_cliprdr_backend(&self) -> Box<dyn CliprdrBackend> {
+        let mut session = self.session.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(session) = session.as_mut() else {
+            warn!("Clipboard backend built without server event sender");
+            return Box::new(PolicyBackend::new(
+                self.inner.build_cliprdr_backend(),
+                Arc::new(self.policy.new_session()),
+                Box::new(ServerEventProxy(None)),
+            ));
+        };
+
+        // The events are filtered once the server runs, which is not the case yet when the sender is set
+        if let Some(mut events) = session.events.take() {
+            let policy = Arc::clone(&session.policy);
+            let sender = session.sender.clone();
+
+            tokio::spawn(async move {
+                while let Some(event) = events.recv().await {
+                    let event = match event {
+                        ServerEvent::Clipboard(message) => match policy.filter_message(message) {
+                            Some(message) => ServerEvent::Clipboard(message),
+                            None => continue,
+                        },
+                        event => event,
+                    };
+
+                    if sender.send(event).is_err() {
+                        break;
+                    }
+                }
+
+                debug!("Clipboard policy event forwarding stopped");
+            });
+        }
+
+        Box::new(PolicyBackend::new(
+            self.inner.build_cliprdr_backend(),
+            Arc::clone(&session.policy),
+            Box::new(ServerEventProxy(Some(session.sender.clone()))),
+        ))
+    }
+}
+
+impl ServerEventSender for PolicyCliprdrServerFactory {
+    fn set_sender(&mut self, sender: mpsc::UnboundedSender<ServerEvent>) {
+        let (filtered_sender, events) = ServerEvent::create_channel();
+        self.inner.set_sender(filtered_sender);
+
+        *self.session.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(PolicySession {
+            policy: Arc::new(self.policy.new_session()),
+            sender,
+            events: Some(events),
+        });
+    }
+}
+
+impl CliprdrServerFactory for PolicyCliprdrServerFactory {}
+
+/// Sends the failure responses of denied requests, bypassing the policy.
+#[derive(Debug)]
+struct ServerEventProxy(Option<mpsc::UnboundedSender<ServerEvent>>);
+
+impl ClipboardMessageProxy for ServerEventProxy {
+    fn send_clipboard_message(&self, message: ClipboardMessage) {
+        match &self.0 {
+            Some(sender) => {
+                let _ = sender.send(ServerEvent::Clipboard(message));
+            }
+            None => warn!("Clipboard policy reply dropped, no server event sender"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ironrdp_cliprdr::pdu::{
+        ClipboardFormat, ClipboardFormatId, ClipboardGeneralCapabilityFlags, FileContentsRequest, FileContentsResponse,
+        FormatDataRequest, FormatDataResponse, LockDataId,
+    };
+    use ironrdp_core::impl_as_any;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct NoopBackend;
+
+    impl_as_any!(NoopBackend);
+
+    impl CliprdrBackend for NoopBackend {
+        fn temporary_directory(&self) -> &str {
+            ".cliprdr"
+        }
+
+        fn client_capabilities(&self) -> ClipboardGeneralCapabilityFlags {
+            ClipboardGeneralCapabilityFlags::empty()
+        }
+
+        fn on_request_format_list(&mut self) {}
+
+        fn on_process_negotiated_capabilities(&mut self, _: ClipboardGeneralCapabilityFlags) {}
+
+        fn on_remote_copy(&mut self, _: &[ClipboardFormat]) {}
+
+        fn on_format_data_request(&mut self, _: FormatDataRequest) {}
+
+        fn on_format_data_response(&mut self, _: FormatDataResponse<'_>) {}
+
+        fn on_file_contents_request(&mut self, _: FileContentsRequest) {}
+
+        fn on_file_contents_response(&mut self, _: FileContentsResponse<'_>) {}
+
+        fn on_lock(&mut self, _: LockDataId) {}
+
+        fn on_unlock(&mut self, _: LockDataId) {}
+    }
+
+    /// Factory keeping the sender of the last connection, as a backend would.
+    #[derive(Default)]
+    struct NoopFactory(Arc<Mutex<Option<mpsc::UnboundedSender<ServerEvent>>>>);
+
+    impl CliprdrBackendFactory for NoopFactory {
+        fn build_cliprdr_backend(&self) -> Box<dyn CliprdrBackend> {
+            Box::new(NoopBackend)
+        }
+    }
+
+    impl ServerEventSender for NoopFactory {
+        fn set_sender(&mut self, sender: mpsc::UnboundedSender<ServerEvent>) {
+            *self.0.lock().unwrap() = Some(sender);
+        }
+    }
+
+    impl CliprdrServerFactory for NoopFactory {}
+
+    #[tokio::test]
+    async fn connections_have_their_own_policy_session() {
+        let inner = NoopFactory::default();
+        let inner_sender = Arc::clone(&inner.0);
+        let mut factory = PolicyCliprdrServerFactory::new(Box::new(inner), Arc::new(ClipboardPolicy::new()));
+
+        let mut connections = Vec::new();
+        for _ in 0..2 {
+            let (sender, receiver) = ServerEvent::create_channel();
+            factory.set_sender(sender);
+            let backend = factory.build_cliprdr_backend();
+            let backend_sender = inner_sender.lock().unwrap().clone().unwrap();
+            connections.push((backend, backend_sender, receiver));
+        }
+
+        // Only the first client offers text, which only its own connection may then request
+        let formats = [ClipboardFormat::new(ClipboardFormatId::CF_UNICODETEXT)];
+        connections[0].0.on_remote_copy(&formats);
+
+        for (_, sender, _) in &connections {
+            let paste = ClipboardMessage::SendInitiatePaste(ClipboardFormatId::CF_UNICODETEXT);
+            sender.send(ServerEvent::Clipboard(paste)).unwrap();
+            sender.send(ServerEvent::Quit(String::new())).unwrap();
+        }
+
+        let mut received = Vec::new();
+        for (_, _, receiver) in &mut connections {
+            let mut events = Vec::new();
+            while let Some(event) = receiver.recv().await {
+                let is_quit = matches!(event, ServerEvent::Quit(_));
+                events.push(event);
+                if is_quit {
+                    break;
+                }
+            }
+            received.push(events);
+        }
+
+        assert!(matches!(
+            received[0].as_slice(),
+            [
+                ServerEvent::Clipboard(ClipboardMessage::SendInitiatePaste(ClipboardFormatId::CF_UNICODETEXT)),
+                ServerEvent::Quit(_)
+            ]
+        ));
+        assert!(matches!(received[1].as_slice(), [ServerEvent::Quit(_)]));
+    }
+}
@@ -0,0 +1,113 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::Result;
+use ironrdp_displaycontrol::pdu::DisplayControlMonitorLayout;
+
+use crate::{
+    AudinServerFactory, CliprdrServerFactory, DesktopSize, KeyboardEvent, MouseEvent, RdpServerDisplay,
+    RdpServerDisplayUpdates, RdpServerInputHandler, RdpdrServerFactory, SoundServerFactory,
+};
+
+/// Identifier of a connection, unique for the lifetime of the server.
+///
+/// It can be used to send an event to a single connection with [`ServerEvent::Connection`](crate::ServerEvent::Connection).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ConnectionId(pub(crate) u64);
+
+impl core::fmt::Display for ConnectionId {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Factories building the channel backends of a connection.
+#[derive(Default)]
+pub struct ChannelFactories {
+    pub sound: Option<Box<dyn SoundServerFactory>>,
+    pub cliprdr: Option<Box<dyn CliprdrServerFactory>>,
+    pub audin: Option<Box<dyn AudinServerFactory>>,
+    pub rdpdr: Option<Box<dyn RdpdrServerFactory>>,
+}
+
+/// Builds the display and input handlers of each connection
+///
+/// The server calls these methods when a client connects, with the identifier of the connection and the address of
+/// the client, so that concurrent connections can be given their own session.
+///
+/// Handlers given to the builder with `with_input_handler` and `with_display_handler` are instead shared by all
+/// connections.
+pub trait ConnectionHandlerFactory {
+    fn build_input_handler(&self, id: ConnectionId, peer: SocketAddr) -> Box<dyn RdpServerInputHandler>;
+
+    fn build_display(&self, id: ConnectionId, peer: SocketAddr) -> Box<dyn RdpServerDisplay>;
+
+    /// Builds the channel factories of the connection, which are given the event sender of this connection only.
+    ///
+    /// The factories given to the builder are used when `None` is returned, which is the default. They are shared by
+    /// all the connections, and given the event sender of each connection right before building its backends.
+    fn build_channel_factories(&self, _id: ConnectionId, _peer: SocketAddr) -> Option<ChannelFactories> {
+        None
+    }
+}
+
+/// Factory handing out the same handlers to every connection.
+pub(crate) struct SharedHandlers {
+    handler: Arc<std::sync::Mutex<Box<dyn RdpServerInputHandler>>>,
+    display: Arc<tokio::sync::Mutex<Box<dyn RdpServerDisplay>>>,
+}
+
+impl SharedHandlers {
+    pub(crate) fn new(handler: Box<dyn RdpServerInputHandler>, display: Box<dyn RdpServerDisplay>) -> Self {
+        Self {
+            handler: Arc::new(std::sync::Mutex::new(handler)),
+            display: Arc::new(tokio::sync::Mutex::new(display)),
+        }
+    }
+}
+
+impl ConnectionHandlerFactory for SharedHandlers {
+    fn build_input_handler(&self, _: ConnectionId, _: SocketAddr) -> Box<dyn RdpServerInputHandler> {
+        Box::new(SharedInputHandler(Arc::clone(&self.handler)))
+    }
+
+    fn build_display(&self, _: ConnectionId, _: SocketAddr) -> Box<dyn RdpServerDisplay> {
+        Box::new(SharedDisplay(Arc::clone(&self.display)))
+    }
+}
+
+struct SharedInputHandler(Arc<std::sync::Mutex<Box<dyn RdpServerInputHandler>>>);
+
+impl SharedInputHandler {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Box<dyn RdpServerInputHandler>> {
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl RdpServerInputHandler for SharedInputHandler {
+    fn keyboard(&mut self, event: KeyboardEvent) {
+        self.lock().keyboard(event);
+    }
+
+    fn mouse(&mut self, event: MouseEvent) {
+        self.lock().mouse(event);
+    }
+}
+
+struct SharedDisplay(Arc<tokio::sync::Mutex<Box<dyn RdpServerDisplay>>>);
+
+#[async_trait::async_trait]
+impl RdpServerDisplay for SharedDisplay {
+    async fn size(&mut self) -> DesktopSize {
+        self.0.lock().await.size().await
+    }
+
+    async fn updates(&mut self) -> Result<Box<dyn RdpServerDisplayUpdates>> {
+        self.0.lock().await.updates().await
+    }
+
+    fn request_layout(&mut self, layout: DisplayControlMonitorLayout) {
+        // Layout requests are forwarded from a blocking task
+        self.0.blocking_lock().request_layout(layout);
+    }
+}
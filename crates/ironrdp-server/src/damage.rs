@@ -0,0 +1,777 @@
+//! Damage tracking of display updates
+//!
+//! Display backends often only provide captures of the whole screen. The damage tracker keeps a copy of the desktop
+//! and compares updates with it tile by tile, so that only the tiles that changed are encoded and sent.
+//!
+//! It can also detect vertical scrolls between two captures, which are then sent as a copy of the moved region
+//! followed by the few tiles that are really new.
+
+use std::cmp::{max, min};
+use std::collections::HashMap;
+use std::mem;
+use std::num::NonZeroU16;
+use std::ops::Range;
+
+use ironrdp_pdu::geometry::InclusiveRectangle;
+use ironrdp_pdu::pointer::Point16;
+
+use crate::gfx::{copy_fits, Framebuffer};
+use crate::{BitmapUpdate, DesktopSize, DisplayUpdate, PixelOrder};
+
+/// Size of the compared tiles, matching the RemoteFX ones.
+const TILE_SIZE: usize = 64;
+
+/// Minimum number of rows a scrolled region must have to be sent as a copy.
+const MIN_SCROLL_ROWS: usize = 32;
+
+/// Rows found more often than this in the previous capture, such as blank ones, don't tell where content moved.
+const MAX_ROW_MATCHES: usize = 4;
+
+/// Finds the parts of bitmap updates that changed since they were last sent
+pub(crate) struct DamageTracker {
+    framebuffer: Framebuffer,
+    columns: usize,
+    /// Whether each tile was sent, the client having garbage in the others.
+    known: Vec<bool>,
+    /// Previous content of the updated region, compared with the update.
+    previous: Vec<u8>,
+    scroll_detection: bool,
+    /// Whether updates are compared with the desktop, instead of only being drawn on it.
+    tracking: bool,
+}
+
+impl DamageTracker {
+    pub(crate) fn new(size: DesktopSize) -> Self {
+        let columns = usize::from(size.width).div_ceil(TILE_SIZE);
+        let rows = usize::from(size.height).div_ceil(TILE_SIZE);
+
+        Self {
+            framebuffer: Framebuffer::new(size),
+            columns,
+            known: vec![false; columns * rows],
+            previous: Vec::new(),
+            scroll_detection: false,
+            tracking: true,
+        }
+    }
+
+    /// Disables damage tracking, the desktop being only kept to send copies as bitmaps.
+    #[must_use]
+    pub(crate) fn with_damage_tracking(mut self, enabled: bool) -> Self {
+        self.tracking = enabled;
+        self
+    }
+
+    /// Enables scroll detection, which requires damage tracking.
+    #[must_use]
+    pub(crate) fn with_scroll_detection(mut self, enabled: bool) -> Self {
+        self.scroll_detection = enabled && self.tracking;
+        self
+    }
+
+    pub(crate) fn tracks_damage(&self) -> bool {
+        self.tracking
+    }
+
+    /// Returns the updates bringing the desktop sent so far to its content after `bitmap`.
+    ///
+    /// These are the parts of `bitmap` covering the tiles that changed, adjacent changed tiles of a row being merged
+    /// into a single update, preceded by a copy of the scrolled region if one is detected. Without damage tracking,
+    /// this is `bitmap` itself.
+    pub(crate) fn damage(&mut self, bitmap: BitmapUpdate) -> Vec<DisplayUpdate> {
+        if !self.tracking {
+            self.framebuffer.update(&bitmap);
+            return vec![DisplayUpdate::Bitmap(bitmap)];
+        }
+
+        let left = usize::from(bitmap.left);
+        let top = usize::from(bitmap.top);
+        let right = min(left + usize::from(bitmap.width.get()), self.framebuffer.width);
+        let bottom = min(top + usize::from(bitmap.height.get()), self.framebuffer.height);
+        if left >= right || top >= bottom {
+            return Vec::new();
+        }
+
+        let mut updates = Vec::new();
+        let mut previous = mem::take(&mut self.previous);
+        self.save(left..right, top..bottom, &mut previous);
+
+        self.framebuffer.update(&bitmap);
+
+        if self.scroll_detection {
+            if let Some((src, dst)) = self.detect_scroll(left..right, top..bottom, &previous) {
+                // The copy is applied to the desktop as the client has it, before drawing what is left of `bitmap`
+                self.restore(left..right, top..bottom, &previous);
+                self.copy_rect(&src, dst);
+                self.save(left..right, top..bottom, &mut previous);
+                self.framebuffer.update(&bitmap);
+
+                updates.push(DisplayUpdate::CopyRect { src, dst });
+            }
+        }
+
+        updates.extend(
+            self.changed_tiles(left..right, top..bottom, &previous)
+                .into_iter()
+                .map(DisplayUpdate::Bitmap),
+        );
+        self.previous = previous;
+        updates
+    }
+
+    /// Applies a copy of the `src` region sent to the client, returning `false` if it doesn't lie within the desktop.
+    pub(crate) fn copy_rect(&mut self, src: &InclusiveRectangle, dst: Point16) -> bool {
+        if !self.framebuffer.copy_rect(src, dst) {
+            return false;
+        }
+
+        let tiles = |left: u16, top: u16, right: u16, bottom: u16| {
+            let columns = usize::from(left) / TILE_SIZE..=usize::from(right) / TILE_SIZE;
+            (usize::from(top) / TILE_SIZE..=usize::from(bottom) / TILE_SIZE)
+                .flat_map(move |row| columns.clone().map(move |column| (column, row)))
+        };
+        let width = src.right - src.left;
+        let height = src.bottom - src.top;
+
+        // The client copied garbage if part of the source was never sent
+        let known = tiles(src.left, src.top, src.right, src.bottom)
+            .all(|(column, row)| self.known[row * self.columns + column]);
+
+        if !known {
+            for (column, row) in tiles(dst.x, dst.y, dst.x + width, dst.y + height) {
+                self.known[row * self.columns + column] = false;
+            }
+        }
+
+        true
+    }
+
+    /// Returns the destination of a copy of the `src` region, as the desktop now has it.
+    pub(crate) fn copied_bitmap(&self, src: &InclusiveRectangle, dst: Point16) -> Option<BitmapUpdate> {
+        if !copy_fits(self.framebuffer.width, self.framebuffer.height, src, dst) {
+            return None;
+        }
+
+        let left = usize::from(dst.x);
+        let top = usize::from(dst.y);
+        let width = usize::from(src.right - src.left) + 1;
+        let height = usize::from(src.bottom - src.top) + 1;
+        self.bitmap(left..left + width, top..top + height)
+    }
+
+    /// Returns the parts of the region that changed from its `previous` content, or that were never sent.
+    fn changed_tiles(&mut self, x: Range<usize>, y: Range<usize>, previous: &[u8]) -> Vec<BitmapUpdate> {
+        let mut updates = Vec::new();
+        for row in y.start / TILE_SIZE..=(y.end - 1) / TILE_SIZE {
+            let tile_y = max(y.start, row * TILE_SIZE)..min(y.end, (row + 1) * TILE_SIZE);
+            let mut changed: Option<(usize, usize)> = None;
+
+            for column in x.start / TILE_SIZE..=(x.end - 1) / TILE_SIZE {
+                let tile_x = max(x.start, column * TILE_SIZE)..min(x.end, (column + 1) * TILE_SIZE);
+
+                if self.tile_changed(column, row, &x, &y, previous) {
+                    changed = Some(changed.map_or((tile_x.start, tile_x.end), |(start, _)| (start, tile_x.end)));
+                } else if let Some((start, end)) = changed.take() {
+                    updates.extend(self.bitmap(start..end, tile_y.clone()));
+                }
+            }
+
+            if let Some((start, end)) = changed {
+                updates.extend(self.bitmap(start..end, tile_y));
+            }
+        }
+
+        updates
+    }
+
+    /// Finds the longest run of rows of the region that moved vertically, comparing it with its `previous` content.
+    ///
+    /// Returns the moved region and its new top-left corner.
+    fn detect_scroll(
+        &self,
+        x: Range<usize>,
+        y: Range<usize>,
+        previous: &[u8],
+    ) -> Option<(InclusiveRectangle, Point16)> {
+        if y.len() < MIN_SCROLL_ROWS + 1 {
+            return None;
+        }
+
+        let bpp = usize::from(Framebuffer::FORMAT.bytes_per_pixel());
+        let stride = self.framebuffer.width * bpp;
+        let row_len = x.len() * bpp;
+
+        let old_row = |i: usize| &previous[i * row_len..(i + 1) * row_len];
+        let new_row = |i: usize| {
+            let start = (y.start + i) * stride + x.start * bpp;
+            &self.framebuffer.data[start..start + row_len]
+        };
+        let old_hashes: Vec<u64> = (0..y.len()).map(|i| hash_row(0, old_row(i))).collect();
+        let new_hashes: Vec<u64> = (0..y.len()).map(|i| hash_row(0, new_row(i))).collect();
+
+        let mut old_rows: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (i, &hash) in old_hashes.iter().enumerate() {
+            old_rows.entry(hash).or_default().push(i);
+        }
+
+        // Each changed row found elsewhere in the previous content votes for the offset it moved by
+        let mut votes: HashMap<isize, usize> = HashMap::new();
+        for (i, hash) in new_hashes.iter().enumerate() {
+            if *hash == old_hashes[i] {
+                continue;
+            }
+
+            let Some(rows) = old_rows.get(hash).filter(|rows| rows.len() <= MAX_ROW_MATCHES) else {
+                continue;
+            };
+            for &j in rows {
+                let dy = isize::try_from(i).ok()? - isize::try_from(j).ok()?;
+                *votes.entry(dy).or_default() += 1;
+            }
+        }
+        // Ties go to the smallest offset, then to content moving up, as when scrolling down a document
+        let (dy, _) = votes
+            .into_iter()
+            .max_by_key(|&(dy, count)| (count, -dy.abs(), dy < 0))?;
+
+        // Longest run of rows found `dy` rows above in the previous content
+        let matching = |i: usize| {
+            i.checked_add_signed(-dy)
+                .filter(|&j| j < y.len())
+                .is_some_and(|j| new_hashes[i] == old_hashes[j] && new_row(i) == old_row(j))
+        };
+        let (mut run_start, mut run_len) = (0, 0);
+        let mut start = None;
+        for i in 0..=y.len() {
+            if i < y.len() && matching(i) {
+                start.get_or_insert(i);
+            } else if let Some(start) = start.take() {
+                if i - start > run_len {
+                    (run_start, run_len) = (start, i - start);
+                }
+            }
+        }
+        if run_len < MIN_SCROLL_ROWS {
+            return None;
+        }
+
+        let source_top = y.start + run_start.checked_add_signed(-dy)?;
+        let src = InclusiveRectangle {
+            left: u16::try_from(x.start).ok()?,
+            top: u16::try_from(source_top).ok()?,
+            right: u16::try_from(x.end - 1).ok()?,
+            bottom: u16::try_from(source_top + run_len - 1).ok()?,
+        };
+        let dst = Point16 {
+            x: src.left,
+            y: u16::try_from(y.start + run_start).ok()?,
+        };
+
+        Some((src, dst))
+    }
+
+    /// Copies a region of the desktop to `buffer`.
+    fn save(&self, x: Range<usize>, y: Range<usize>, buffer: &mut Vec<u8>) {
+        let bpp = usize::from(Framebuffer::FORMAT.bytes_per_pixel());
+        let stride = self.framebuffer.width * bpp;
+
+        buffer.clear();
+        for y in y {
+            buffer.extend_from_slice(&self.framebuffer.data[y * stride + x.start * bpp..y * stride + x.end * bpp]);
+        }
+    }
+
+    /// Copies a region of the desktop back from `buffer`.
+    fn restore(&mut self, x: Range<usize>, y: Range<usize>, buffer: &[u8]) {
+        let bpp = usize::from(Framebuffer::FORMAT.bytes_per_pixel());
+        let stride = self.framebuffer.width * bpp;
+
+        for (y, row) in y.zip(buffer.chunks_exact(x.len() * bpp)) {
+            self.framebuffer.data[y * stride + x.start * bpp..y * stride + x.end * bpp].copy_from_slice(row);
+        }
+    }
+
+    /// Returns whether the part of a tile covered by the `x` and `y` region changed from its `previous` content,
+    /// marking the tile as sent.
+    ///
+    /// The bytes are compared, as a hash collision would leave the client with a stale tile.
+    fn tile_changed(&mut self, column: usize, row: usize, x: &Range<usize>, y: &Range<usize>, previous: &[u8]) -> bool {
+        if !mem::replace(&mut self.known[row * self.columns + column], true) {
+            return true;
+        }
+
+        let tile_x = max(x.start, column * TILE_SIZE)..min(x.end, (column + 1) * TILE_SIZE);
+        let mut tile_y = max(y.start, row * TILE_SIZE)..min(y.end, (row + 1) * TILE_SIZE);
+
+        let bpp = usize::from(Framebuffer::FORMAT.bytes_per_pixel());
+        let stride = self.framebuffer.width * bpp;
+        let row_len = x.len() * bpp;
+        let len = tile_x.len() * bpp;
+
+        tile_y.any(|line| {
+            let new = line * stride + tile_x.start * bpp;
+            let old = (line - y.start) * row_len + (tile_x.start - x.start) * bpp;
+            self.framebuffer.data[new..new + len] != previous[old..old + len]
+        })
+    }
+
+    /// Copies a part of the desktop.
+    fn bitmap(&self, x: Range<usize>, y: Range<usize>) -> Option<BitmapUpdate> {
+        let bpp = usize::from(Framebuffer::FORMAT.bytes_per_pixel());
+        let stride = self.framebuffer.width * bpp;
+
+        let data = y
+            .clone()
+            .flat_map(|y| &self.framebuffer.data[y * stride + x.start * bpp..y * stride + x.end * bpp])
+            .copied()
+            .collect();
+
+        Some(BitmapUpdate {
+            top: u16::try_from(y.start).ok()?,
+            left: u16::try_from(x.start).ok()?,
+            width: NonZeroU16::new(u16::try_from(x.len()).ok()?)?,
+            height: NonZeroU16::new(u16::try_from(y.len()).ok()?)?,
+            format: Framebuffer::FORMAT,
+            order: PixelOrder::TopToBottom,
+            data,
+            stride: x.len() * bpp,
+        })
+    }
+}
+
+/// Folds `row` into `hash`, 8 bytes at a time (FxHash).
+fn hash_row(hash: u64, row: &[u8]) -> u64 {
+    const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+    let mut words = row.chunks_exact(8);
+    let hash = words.by_ref().fold(hash, |hash, word| {
+        let word = u64::from_le_bytes(word.try_into().unwrap());
+        (hash.rotate_left(5) ^ word).wrapping_mul(SEED)
+    });
+
+    words.remainder().iter().fold(hash, |hash, &byte| {
+        (hash.rotate_left(5) ^ u64::from(byte)).wrapping_mul(SEED)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ironrdp_graphics::image_processing::PixelFormat;
+
+    use super::*;
+
+    const BPP: usize = 4;
+
+    /// Desktop captures, in the pixel format of the framebuffer.
+    struct Screen {
+        width: usize,
+        height: usize,
+        data: Vec<u8>,
+    }
+
+    impl Screen {
+        fn new(width: u16, height: u16) -> Self {
+            let width = usize::from(width);
+            let height = usize::from(height);
+
+            Self {
+                width,
+                height,
+                data: vec![0; width * height * BPP],
+            }
+        }
+
+        fn size(&self) -> DesktopSize {
+            DesktopSize {
+                width: u16::try_from(self.width).unwrap(),
+                height: u16::try_from(self.height).unwrap(),
+            }
+        }
+
+        fn set(&mut self, x: usize, y: usize, pixel: [u8; 4]) {
+            let start = (y * self.width + x) * BPP;
+            self.data[start..start + BPP].copy_from_slice(&pixel);
+        }
+
+        fn fill(&mut self, x: Range<usize>, y: Range<usize>, pixel: [u8; 4]) {
+            for y in y {
+                for x in x.clone() {
+                    self.set(x, y, pixel);
+                }
+            }
+        }
+
+        /// Returns a capture of the `x` by `y` region.
+        fn bitmap(&self, x: Range<usize>, y: Range<usize>) -> BitmapUpdate {
+            let data = y
+                .clone()
+                .flat_map(|y| &self.data[(y * self.width + x.start) * BPP..(y * self.width + x.end) * BPP])
+                .copied()
+                .collect();
+
+            BitmapUpdate {
+                top: u16::try_from(y.start).unwrap(),
+                left: u16::try_from(x.start).unwrap(),
+                width: NonZeroU16::new(u16::try_from(x.len()).unwrap()).unwrap(),
+                height: NonZeroU16::new(u16::try_from(y.len()).unwrap()).unwrap(),
+                format: PixelFormat::BgrX32,
+                order: PixelOrder::TopToBottom,
+                data,
+                stride: x.len() * BPP,
+            }
+        }
+
+        /// Draws the `line`-th line of a document on row `y`, each line being different.
+        fn line(&mut self, y: usize, line: usize) {
+            for x in 0..self.width {
+                let [line_low, line_high, ..] = line.to_le_bytes();
+                self.set(x, y, [line_low, line_high, x.to_le_bytes()[0], 0]);
+            }
+        }
+
+        fn capture(&self) -> BitmapUpdate {
+            self.bitmap(0..self.width, 0..self.height)
+        }
+    }
+
+    /// Returns the `(left, top, width, height)` of the bitmap updates, checking they match `screen`.
+    fn bitmaps(screen: &Screen, updates: &[DisplayUpdate]) -> Vec<(u16, u16, u16, u16)> {
+        updates
+            .iter()
+            .map(|update| {
+                let DisplayUpdate::Bitmap(bitmap) = update else {
+                    panic!("unexpected update: {update:?}");
+                };
+                let x = usize::from(bitmap.left)..usize::from(bitmap.left) + usize::from(bitmap.width.get());
+                let y = usize::from(bitmap.top)..usize::from(bitmap.top) + usize::from(bitmap.height.get());
+                assert_eq!(bitmap.data, screen.bitmap(x, y).data);
+
+                (bitmap.left, bitmap.top, bitmap.width.get(), bitmap.height.get())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn first_capture_is_sent_whole_and_unchanged_ones_are_not() {
+        let mut screen = Screen::new(192, 128);
+        screen.fill(10..20, 10..20, [1, 2, 3, 0]);
+        let mut tracker = DamageTracker::new(screen.size());
+
+        let updates = tracker.damage(screen.capture());
+        assert_eq!(bitmaps(&screen, &updates), [(0, 0, 192, 64), (0, 64, 192, 64)]);
+
+        assert!(tracker.damage(screen.capture()).is_empty());
+    }
+
+    #[test]
+    fn single_changed_tile_is_sent() {
+        let mut screen = Screen::new(192, 128);
+        let mut tracker = DamageTracker::new(screen.size());
+        tracker.damage(screen.capture());
+
+        screen.set(70, 100, [0xFF; 4]);
+        let updates = tracker.damage(screen.capture());
+
+        assert_eq!(bitmaps(&screen, &updates), [(64, 64, 64, 64)]);
+    }
+
+    #[test]
+    fn changed_tiles_are_sent_even_if_their_hash_collides() {
+        const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+        // Rows of two words hashing the same: (rotl(a * SEED, 5) ^ b) * SEED
+        let words = |first: u64, second: u64| [first.to_le_bytes(), second.to_le_bytes()].concat();
+        let old = words(1, 0);
+        let new = words(2, SEED.rotate_left(5) ^ 2u64.wrapping_mul(SEED).rotate_left(5));
+        assert_eq!(hash_row(0, &old), hash_row(0, &new));
+
+        let mut screen = Screen::new(64, 64);
+        screen.data[..16].copy_from_slice(&old);
+        let mut tracker = DamageTracker::new(screen.size());
+        tracker.damage(screen.capture());
+
+        screen.data[..16].copy_from_slice(&new);
+        let updates = tracker.damage(screen.capture());
+
+        assert_eq!(bitmaps(&screen, &updates), [(0, 0, 64, 64)]);
+    }
+
+    #[test]
+    fn partial_tiles_on_the_edges_are_sent() {
+        let mut screen = Screen::new(100, 70);
+        let mut tracker = DamageTracker::new(screen.size());
+        tracker.damage(screen.capture());
+
+        // Right column
+        screen.set(99, 0, [0xFF; 4]);
+        assert_eq!(bitmaps(&screen, &tracker.damage(screen.capture())), [(64, 0, 36, 64)]);
+
+        // Bottom row
+        screen.set(0, 69, [0xFF; 4]);
+        assert_eq!(bitmaps(&screen, &tracker.damage(screen.capture())), [(0, 64, 64, 6)]);
+
+        // Bottom-right corner
+        screen.set(99, 69, [0xFF; 4]);
+        assert_eq!(bitmaps(&screen, &tracker.damage(screen.capture())), [(64, 64, 36, 6)]);
+    }
+
+    #[test]
+    fn partial_updates_are_clipped_to_their_region() {
+        let mut screen = Screen::new(192, 128);
+        let mut tracker = DamageTracker::new(screen.size());
+
+        // The tiles were never sent, only the updated region of them is
+        let updates = tracker.damage(screen.bitmap(10..80, 20..30));
+        assert_eq!(bitmaps(&screen, &updates), [(10, 20, 70, 10)]);
+
+        tracker.damage(screen.capture());
+        screen.fill(100..110, 70..80, [0xFF; 4]);
+        let updates = tracker.damage(screen.bitmap(96..120, 60..90));
+        assert_eq!(bitmaps(&screen, &updates), [(96, 64, 24, 26)]);
+
+        // Unchanged regions are not sent
+        assert!(tracker.damage(screen.bitmap(0..50, 0..50)).is_empty());
+    }
+
+    #[test]
+    fn partial_updates_out_of_the_desktop_are_ignored() {
+        let screen = Screen::new(192, 128);
+        let mut tracker = DamageTracker::new(DesktopSize { width: 64, height: 64 });
+
+        assert!(tracker.damage(screen.bitmap(64..128, 0..64)).is_empty());
+        assert!(tracker.damage(screen.bitmap(0..64, 64..128)).is_empty());
+    }
+
+    #[test]
+    fn adjacent_changed_tiles_of_a_row_are_merged() {
+        let mut screen = Screen::new(320, 128);
+        let mut tracker = DamageTracker::new(screen.size());
+        tracker.damage(screen.capture());
+
+        for x in [0, 64, 192, 256] {
+            screen.set(x + 1, 1, [0xFF; 4]);
+        }
+        screen.set(1, 65, [0xFF; 4]);
+        let updates = tracker.damage(screen.capture());
+
+        assert_eq!(
+            bitmaps(&screen, &updates),
+            [(0, 0, 128, 64), (192, 0, 128, 64), (0, 64, 64, 64)]
+        );
+    }
+
+    /// Returns a tracker detecting scrolls, to which `screen` was sent.
+    fn scroll_tracker(screen: &Screen) -> DamageTracker {
+        let mut tracker = DamageTracker::new(screen.size()).with_scroll_detection(true);
+        tracker.damage(screen.capture());
+        tracker
+    }
+
+    fn rect(left: u16, top: u16, right: u16, bottom: u16) -> InclusiveRectangle {
+        InclusiveRectangle {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    #[test]
+    fn scrolling_down_is_sent_as_a_copy_followed_by_the_new_lines() {
+        let mut screen = Screen::new(128, 128);
+        for y in 0..128 {
+            screen.line(y, y);
+        }
+        let mut tracker = scroll_tracker(&screen);
+
+        for y in 0..128 {
+            screen.line(y, y + 10);
+        }
+        let updates = tracker.damage(screen.capture());
+
+        let [DisplayUpdate::CopyRect { src, dst }, bitmaps @ ..] = updates.as_slice() else {
+            panic!("unexpected updates: {updates:?}");
+        };
+        assert_eq!(*src, rect(0, 10, 127, 127));
+        assert_eq!(*dst, Point16 { x: 0, y: 0 });
+        assert_eq!(self::bitmaps(&screen, bitmaps), [(0, 64, 128, 64)]);
+    }
+
+    #[test]
+    fn scrolling_up_is_sent_as_a_copy_followed_by_the_new_lines() {
+        let mut screen = Screen::new(128, 128);
+        for y in 0..128 {
+            screen.line(y, y + 10);
+        }
+        let mut tracker = scroll_tracker(&screen);
+
+        for y in 0..128 {
+            screen.line(y, y);
+        }
+        let updates = tracker.damage(screen.capture());
+
+        let [DisplayUpdate::CopyRect { src, dst }, bitmaps @ ..] = updates.as_slice() else {
+            panic!("unexpected updates: {updates:?}");
+        };
+        assert_eq!(*src, rect(0, 0, 127, 117));
+        assert_eq!(*dst, Point16 { x: 0, y: 10 });
+        assert_eq!(self::bitmaps(&screen, bitmaps), [(0, 0, 128, 64)]);
+    }
+
+    #[test]
+    fn scrolling_part_of_the_region_copies_only_that_part() {
+        let mut screen = Screen::new(192, 128);
+        for y in 0..128 {
+            screen.line(y, y);
+        }
+        let mut tracker = scroll_tracker(&screen);
+
+        // The rows below 100 are a status bar, not scrolling with the rest
+        for y in 0..90 {
+            screen.line(y, y + 10);
+        }
+        for y in 90..100 {
+            screen.line(y, y + 1000);
+        }
+        let updates = tracker.damage(screen.capture());
+
+        let [DisplayUpdate::CopyRect { src, dst }, bitmaps @ ..] = updates.as_slice() else {
+            panic!("unexpected updates: {updates:?}");
+        };
+        assert_eq!(*src, rect(0, 10, 191, 99));
+        assert_eq!(*dst, Point16 { x: 0, y: 0 });
+        assert_eq!(self::bitmaps(&screen, bitmaps), [(0, 64, 192, 64)]);
+    }
+
+    #[test]
+    fn blank_or_repeated_rows_are_not_sent_as_copies() {
+        // A blank area growing above a uniform one
+        let mut screen = Screen::new(128, 128);
+        screen.fill(0..128, 64..128, [0x80; 4]);
+        let mut tracker = scroll_tracker(&screen);
+
+        screen.fill(0..128, 54..64, [0x80; 4]);
+        let updates = tracker.damage(screen.capture());
+        assert_eq!(bitmaps(&screen, &updates), [(0, 0, 128, 64)]);
+
+        // Stripes moving by one row
+        for y in 0..128 {
+            screen.line(y, y % 2);
+        }
+        let mut tracker = scroll_tracker(&screen);
+
+        for y in 0..128 {
+            screen.line(y, (y + 1) % 2);
+        }
+        let updates = tracker.damage(screen.capture());
+        assert_eq!(bitmaps(&screen, &updates), [(0, 0, 128, 64), (0, 64, 128, 64)]);
+    }
+
+    #[test]
+    fn scrolls_too_small_are_not_sent_as_copies() {
+        let mut screen = Screen::new(128, 128);
+        for y in 0..128 {
+            screen.line(y, y);
+        }
+        let mut tracker = scroll_tracker(&screen);
+
+        for y in 0..31 {
+            screen.line(y, y + 10);
+        }
+        for y in 31..128 {
+            screen.line(y, y + 1000);
+        }
+        let updates = tracker.damage(screen.capture());
+        assert_eq!(bitmaps(&screen, &updates), [(0, 0, 128, 64), (0, 64, 128, 64)]);
+    }
+
+    #[test]
+    fn ties_between_opposite_scrolls_favor_content_moving_up() {
+        let mut screen = Screen::new(128, 128);
+        for y in 0..128 {
+            screen.line(y, y);
+        }
+        let mut tracker = scroll_tracker(&screen);
+
+        // As many rows moved up as down by 10 rows
+        for y in 0..40 {
+            screen.line(y, y + 10);
+        }
+        for y in 60..100 {
+            screen.line(y, y - 10);
+        }
+        let updates = tracker.damage(screen.capture());
+
+        let [DisplayUpdate::CopyRect { src, dst }, ..] = updates.as_slice() else {
+            panic!("unexpected updates: {updates:?}");
+        };
+        assert_eq!(*src, rect(0, 10, 127, 49));
+        assert_eq!(*dst, Point16 { x: 0, y: 0 });
+    }
+
+    #[test]
+    fn copies_of_sent_content_are_not_sent_again() {
+        let mut screen = Screen::new(128, 128);
+        screen.fill(10..20, 10..20, [0xFF; 4]);
+        let mut tracker = DamageTracker::new(screen.size());
+        tracker.damage(screen.capture());
+
+        assert!(tracker.copy_rect(&rect(0, 0, 63, 63), Point16 { x: 64, y: 64 }));
+        screen.fill(74..84, 74..84, [0xFF; 4]);
+
+        assert!(tracker.damage(screen.capture()).is_empty());
+    }
+
+    #[test]
+    fn copies_of_unknown_content_invalidate_their_destination() {
+        let mut screen = Screen::new(128, 128);
+        screen.fill(10..20, 74..84, [0xFF; 4]);
+        let mut tracker = DamageTracker::new(screen.size());
+        tracker.damage(screen.bitmap(0..128, 0..64));
+
+        // The client copies garbage from the bottom row of tiles, which it never received
+        assert!(tracker.copy_rect(&rect(0, 64, 63, 127), Point16 { x: 64, y: 0 }));
+
+        let updates = tracker.damage(screen.capture());
+        assert_eq!(bitmaps(&screen, &updates), [(64, 0, 64, 64), (0, 64, 128, 64)]);
+    }
+
+    #[test]
+    fn copies_out_of_the_desktop_are_rejected() {
+        let screen = Screen::new(128, 128);
+        let mut tracker = DamageTracker::new(screen.size());
+        tracker.damage(screen.capture());
+
+        assert!(!tracker.copy_rect(&rect(0, 0, 63, 63), Point16 { x: 65, y: 0 }));
+        assert!(!tracker.copy_rect(&rect(0, 100, 63, 128), Point16 { x: 0, y: 0 }));
+        assert!(tracker
+            .copied_bitmap(&rect(0, 0, 63, 63), Point16 { x: 0, y: 65 })
+            .is_none());
+    }
+
+    #[test]
+    fn desktop_is_only_mirrored_without_damage_tracking() {
+        let mut screen = Screen::new(128, 128);
+        for y in 0..128 {
+            screen.line(y, y);
+        }
+        let mut tracker = DamageTracker::new(screen.size())
+            .with_damage_tracking(false)
+            .with_scroll_detection(true);
+
+        // Updates are sent as they are, even unchanged ones
+        for _ in 0..2 {
+            let updates = tracker.damage(screen.capture());
+            assert_eq!(bitmaps(&screen, &updates), [(0, 0, 128, 128)]);
+        }
+
+        // Copies are sent as the bitmap of their destination
+        let (src, dst) = (rect(0, 10, 127, 127), Point16 { x: 0, y: 0 });
+        assert!(tracker.copy_rect(&src, dst));
+        for y in 0..118 {
+            screen.line(y, y + 10);
+        }
+        let bitmap = tracker.copied_bitmap(&src, dst).unwrap();
+        assert_eq!(bitmaps(&screen, &[DisplayUpdate::Bitmap(bitmap)]), [(0, 0, 128, 118)]);
+    }
+}
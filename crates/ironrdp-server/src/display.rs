@@ -2,7 +2,8 @@ use std::num::NonZeroU16;
 
 use anyhow::Result;
 use ironrdp_displaycontrol::pdu::DisplayControlMonitorLayout;
-use ironrdp_pdu::pointer::PointerPositionAttribute;
+use ironrdp_pdu::geometry::InclusiveRectangle;
+use ironrdp_pdu::pointer::{Point16, PointerPositionAttribute};
 
 #[rustfmt::skip]
 pub use ironrdp_acceptor::DesktopSize;
@@ -17,6 +18,14 @@ pub use ironrdp_graphics::image_processing::PixelFormat;
 pub enum DisplayUpdate {
     Resize(DesktopSize),
     Bitmap(BitmapUpdate),
+    /// Region of the desktop moved elsewhere, such as scrolled content
+    ///
+    /// The `src` region is copied with its top-left corner at `dst`, both lying within the desktop.
+    /// Clients not supporting copies receive the bitmap of the destination instead.
+    CopyRect {
+        src: InclusiveRectangle,
+        dst: Point16,
+    },
     PointerPosition(PointerPositionAttribute),
     ColorPointer(ColorPointer),
     RGBAPointer(RGBAPointer),
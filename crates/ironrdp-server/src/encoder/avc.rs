@@ -0,0 +1,303 @@
+use anyhow::{Context, Result};
+use ironrdp_core::encode_vec;
+use ironrdp_graphics::color_conversion::{to_yuv420, Yuv420Buffer};
+use ironrdp_pdu::geometry::InclusiveRectangle;
+use ironrdp_pdu::pointer::Point16;
+use ironrdp_pdu::rdp::vc::dvc::gfx::{Avc420BitmapStream, QuantQuality};
+
+use crate::gfx::Framebuffer;
+use crate::{BitmapUpdate, DesktopSize, FrameAcknowledgement, H264Encoder, Yuv420Frame};
+
+/// Encodes bitmap updates with the AVC420 codec
+///
+/// Each picture covers the whole surface: a copy of the desktop is kept, and only the regions of the updates are
+/// converted to YUV and listed in the bitmap stream, letting the client leave the rest of the surface untouched.
+pub(crate) struct Avc420Encoder {
+    encoder: Box<dyn H264Encoder>,
+    framebuffer: Framebuffer,
+    y: Vec<u8>,
+    u: Vec<u8>,
+    v: Vec<u8>,
+}
+
+impl Avc420Encoder {
+    /// Returns `None` if the surface can't be encoded, H.264 pictures having even dimensions.
+    pub(crate) fn new(mut encoder: Box<dyn H264Encoder>, size: DesktopSize) -> Option<Self> {
+        if size.width % 2 != 0 || size.height % 2 != 0 {
+            return None;
+        }
+
+        let luma_len = usize::from(size.width) * usize::from(size.height);
+        encoder.request_keyframe();
+
+        Some(Self {
+            encoder,
+            framebuffer: Framebuffer::new(size),
+            y: vec![0; luma_len],
+            u: vec![128; luma_len / 4],
+            v: vec![128; luma_len / 4],
+        })
+    }
+
+    /// Copies `bitmap` to the desktop, whatever codec it is sent with.
+    pub(crate) fn update(&mut self, bitmap: &BitmapUpdate) {
+        self.framebuffer.update(bitmap);
+    }
+
+    /// Applies a copy made on the surface to the desktop.
+    ///
+    /// The YUV planes are left as is, the copied region being converted again if a later update covers it.
+    pub(crate) fn copy_rect(&mut self, src: &InclusiveRectangle, dst: Point16) {
+        self.framebuffer.copy_rect(src, dst);
+    }
+
+    pub(crate) fn frame_acknowledged(&mut self, acknowledgement: FrameAcknowledgement) {
+        self.encoder.frame_acknowledged(acknowledgement);
+    }
+
+    /// Starts a new stream with the next picture, for a client decoding a new surface.
+    pub(crate) fn request_keyframe(&mut self) {
+        self.encoder.request_keyframe();
+    }
+
+    /// Returns the destination rectangle and the AVC420 bitmap stream redrawing `region` of the desktop, or `None` if
+    /// the encoder skipped the picture.
+    pub(crate) fn encode(&mut self, region: &InclusiveRectangle) -> Result<Option<(InclusiveRectangle, Vec<u8>)>> {
+        let width = self.framebuffer.width;
+        let height = self.framebuffer.height;
+
+        // Chroma samples are shared by 2x2 blocks, so the converted area is aligned on them
+        let left = usize::from(region.left) & !1;
+        let top = usize::from(region.top) & !1;
+        let right = (usize::from(region.right) + 1).next_multiple_of(2).min(width);
+        let bottom = (usize::from(region.bottom) + 1).next_multiple_of(2).min(height);
+        if left >= right || top >= bottom {
+            return Ok(None);
+        }
+
+        let bpp = usize::from(Framebuffer::FORMAT.bytes_per_pixel());
+        let stride = width * bpp;
+        to_yuv420(
+            &self.framebuffer.data[top * stride + left * bpp..],
+            right - left,
+            bottom - top,
+            stride,
+            Framebuffer::FORMAT,
+            Yuv420Buffer {
+                y: &mut self.y[top * width + left..],
+                u: &mut self.u[top / 2 * width / 2 + left / 2..],
+                v: &mut self.v[top / 2 * width / 2 + left / 2..],
+                y_stride: width,
+                uv_stride: width / 2,
+            },
+        );
+
+        let frame = self
+            .encoder
+            .encode(Yuv420Frame {
+                width,
+                height,
+                y: &self.y,
+                u: &self.u,
+                v: &self.v,
+            })
+            .context("H.264 encoding")?;
+        if frame.data.is_empty() {
+            return Ok(None);
+        }
+
+        let stream = Avc420BitmapStream {
+            rectangles: vec![region.clone()],
+            quant_qual_vals: vec![QuantQuality {
+                quantization_parameter: frame.quantization_parameter,
+                progressive: false,
+                quality: frame.quality,
+            }],
+            data: &frame.data,
+        };
+        let surface = InclusiveRectangle {
+            left: 0,
+            top: 0,
+            right: u16::try_from(width - 1)?,
+            bottom: u16::try_from(height - 1)?,
+        };
+
+        Ok(Some((surface, encode_vec(&stream)?)))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::num::NonZeroU16;
+    use std::sync::{Arc, Mutex};
+
+    use ironrdp_core::decode;
+    use ironrdp_graphics::image_processing::PixelFormat;
+
+    use super::*;
+    use crate::{H264Frame, PixelOrder};
+
+    /// Pictures given to a [`TestH264Encoder`], and what it returns.
+    #[derive(Default)]
+    pub(crate) struct TestH264State {
+        pub(crate) data: Vec<u8>,
+        pub(crate) keyframes_requested: usize,
+        pub(crate) acknowledgements: Vec<FrameAcknowledgement>,
+        /// Luma plane of the last encoded picture.
+        pub(crate) y: Vec<u8>,
+    }
+
+    /// H.264 encoder returning the same bitstream for every picture.
+    pub(crate) struct TestH264Encoder(pub(crate) Arc<Mutex<TestH264State>>);
+
+    impl TestH264Encoder {
+        pub(crate) fn build(data: &[u8]) -> (Box<dyn H264Encoder>, Arc<Mutex<TestH264State>>) {
+            let state = Arc::new(Mutex::new(TestH264State {
+                data: data.to_vec(),
+                ..Default::default()
+            }));
+            (Box::new(Self(Arc::clone(&state))), state)
+        }
+    }
+
+    impl H264Encoder for TestH264Encoder {
+        fn encode(&mut self, frame: Yuv420Frame<'_>) -> Result<H264Frame> {
+            let mut state = self.0.lock().unwrap();
+            state.y = frame.y.to_vec();
+
+            Ok(H264Frame {
+                data: state.data.clone(),
+                quantization_parameter: 30,
+                quality: 75,
+            })
+        }
+
+        fn request_keyframe(&mut self) {
+            self.0.lock().unwrap().keyframes_requested += 1;
+        }
+
+        fn frame_acknowledged(&mut self, acknowledgement: FrameAcknowledgement) {
+            self.0.lock().unwrap().acknowledgements.push(acknowledgement);
+        }
+    }
+
+    const SIZE: DesktopSize = DesktopSize { width: 32, height: 16 };
+
+    fn white(left: u16, top: u16, width: u16, height: u16) -> BitmapUpdate {
+        BitmapUpdate {
+            top,
+            left,
+            width: NonZeroU16::new(width).unwrap(),
+            height: NonZeroU16::new(height).unwrap(),
+            format: PixelFormat::BgrX32,
+            order: PixelOrder::TopToBottom,
+            stride: usize::from(width) * 4,
+            data: vec![0xFF; usize::from(width) * usize::from(height) * 4],
+        }
+    }
+
+    #[test]
+    fn odd_surfaces_are_not_encoded() {
+        let (h264, _) = TestH264Encoder::build(&[1]);
+        assert!(Avc420Encoder::new(h264, DesktopSize { width: 33, height: 16 }).is_none());
+
+        let (h264, _) = TestH264Encoder::build(&[1]);
+        assert!(Avc420Encoder::new(h264, DesktopSize { width: 32, height: 15 }).is_none());
+    }
+
+    #[test]
+    fn first_picture_is_a_keyframe() {
+        let (h264, state) = TestH264Encoder::build(&[1]);
+        let _encoder = Avc420Encoder::new(h264, SIZE).unwrap();
+
+        assert_eq!(state.lock().unwrap().keyframes_requested, 1);
+    }
+
+    #[test]
+    fn stream_lists_the_region_and_its_quality() {
+        let (h264, _) = TestH264Encoder::build(&[0, 0, 0, 1, 0x65]);
+        let mut encoder = Avc420Encoder::new(h264, SIZE).unwrap();
+        let region = InclusiveRectangle {
+            left: 3,
+            top: 5,
+            right: 20,
+            bottom: 9,
+        };
+
+        let (surface, stream) = encoder.encode(&region).unwrap().unwrap();
+
+        // The picture covers the whole surface, only the region is listed
+        assert_eq!(
+            surface,
+            InclusiveRectangle {
+                left: 0,
+                top: 0,
+                right: 31,
+                bottom: 15,
+            }
+        );
+        let stream: Avc420BitmapStream<'_> = decode(&stream).unwrap();
+        assert_eq!(stream.rectangles, [region]);
+        assert_eq!(
+            stream.quant_qual_vals,
+            [QuantQuality {
+                quantization_parameter: 30,
+                progressive: false,
+                quality: 75,
+            }]
+        );
+        assert_eq!(stream.data, [0, 0, 0, 1, 0x65]);
+    }
+
+    #[test]
+    fn skipped_pictures_are_not_sent() {
+        let (h264, _) = TestH264Encoder::build(&[]);
+        let mut encoder = Avc420Encoder::new(h264, SIZE).unwrap();
+        let region = InclusiveRectangle {
+            left: 0,
+            top: 0,
+            right: 31,
+            bottom: 15,
+        };
+
+        assert!(encoder.encode(&region).unwrap().is_none());
+    }
+
+    #[test]
+    fn only_the_region_is_converted_on_chroma_boundaries() {
+        let (h264, state) = TestH264Encoder::build(&[1]);
+        let mut encoder = Avc420Encoder::new(h264, SIZE).unwrap();
+        encoder.update(&white(0, 0, 32, 16));
+
+        // The 2x2 region starting at odd coordinates is extended to the 4x4 chroma blocks covering it
+        let region = InclusiveRectangle {
+            left: 1,
+            top: 1,
+            right: 2,
+            bottom: 2,
+        };
+        encoder.encode(&region).unwrap().unwrap();
+
+        let y = state.lock().unwrap().y.clone();
+        for (row, luma) in y.chunks(32).enumerate() {
+            for (column, &luma) in luma.iter().enumerate() {
+                assert_eq!(luma > 128, row < 4 && column < 4, "luma at {column}x{row}");
+            }
+        }
+    }
+
+    #[test]
+    fn acknowledgements_are_forwarded() {
+        let (h264, state) = TestH264Encoder::build(&[1]);
+        let mut encoder = Avc420Encoder::new(h264, SIZE).unwrap();
+        let acknowledgement = FrameAcknowledgement {
+            latency: std::time::Duration::from_millis(20),
+            frames_in_flight: 1,
+            queued_bytes: None,
+        };
+
+        encoder.frame_acknowledged(acknowledgement);
+
+        assert_eq!(state.lock().unwrap().acknowledgements, [acknowledgement]);
+    }
+}
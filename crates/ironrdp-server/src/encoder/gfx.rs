@@ -0,0 +1,380 @@
+use anyhow::{bail, Context, Result};
+use ironrdp_core::EncodeErrorKind;
+use ironrdp_graphics::image_processing::PixelFormat;
+use ironrdp_graphics::rdp6::{
+    ABgrChannels, ARgbChannels, BgrAChannels, BitmapEncodeError, BitmapStreamEncoder, RgbAChannels, RleEncodeError,
+};
+use ironrdp_pdu::geometry::InclusiveRectangle;
+use ironrdp_pdu::pointer::Point16;
+use ironrdp_pdu::rdp::capability_sets::EntropyBits;
+use ironrdp_pdu::rdp::vc::dvc::gfx::{self, Codec1Type, Color, ServerPdu, SolidFillPdu, WireToSurface1Pdu};
+
+use super::avc::Avc420Encoder;
+use super::rfx::RfxEncoder;
+use crate::gfx::bitmap_data_fits;
+use crate::{BitmapUpdate, FrameAcknowledgement, PixelOrder};
+
+/// Updates smaller than this are encoded with the planar codec, which is cheaper and lossless.
+const MIN_AREA: usize = 64 * 64;
+
+/// Encodes bitmap updates into graphics pipeline surface commands
+pub(crate) struct GfxEncoder {
+    buffer: Vec<u8>,
+    remotefx: Option<RfxEncoder>,
+    avc: Option<Avc420Encoder>,
+}
+
+impl GfxEncoder {
+    pub(crate) fn new(remotefx: bool) -> Self {
+        Self {
+            buffer: vec![0; 16384],
+            remotefx: remotefx.then(|| RfxEncoder::new(EntropyBits::Rlgr1)),
+            avc: None,
+        }
+    }
+
+    #[must_use]
+    pub(crate) fn with_avc(mut self, avc: Option<Avc420Encoder>) -> Self {
+        self.avc = avc;
+        self
+    }
+
+    pub(crate) fn frame_acknowledged(&mut self, acknowledgement: FrameAcknowledgement) {
+        if let Some(avc) = self.avc.as_mut() {
+            avc.frame_acknowledged(acknowledgement);
+        }
+    }
+
+    /// Makes the next AVC420 picture a keyframe, the client having no earlier picture of the surface to refer to.
+    pub(crate) fn request_keyframe(&mut self) {
+        if let Some(avc) = self.avc.as_mut() {
+            avc.request_keyframe();
+        }
+    }
+
+    /// Applies a copy of the `src` region made on the surface.
+    pub(crate) fn copy_rect(&mut self, src: &InclusiveRectangle, dst: Point16) {
+        if let Some(avc) = self.avc.as_mut() {
+            avc.copy_rect(src, dst);
+        }
+    }
+
+    /// Returns the command drawing `bitmap` on the surface.
+    ///
+    /// Uniform updates are sent as a solid fill. Otherwise, large updates use AVC420 when `avc` is allowed by the
+    /// client, or RemoteFX when enabled, and the others use the planar codec.
+    pub(crate) fn encode(&mut self, surface_id: u16, bitmap: &BitmapUpdate, avc: bool) -> Result<ServerPdu> {
+        if !bitmap_data_fits(bitmap) {
+            bail!("bitmap data doesn't match its size");
+        }
+
+        let destination_rectangle = InclusiveRectangle {
+            left: bitmap.left,
+            top: bitmap.top,
+            right: bitmap
+                .left
+                .checked_add(bitmap.width.get() - 1)
+                .context("bitmap out of the surface")?,
+            bottom: bitmap
+                .top
+                .checked_add(bitmap.height.get() - 1)
+                .context("bitmap out of the surface")?,
+        };
+
+        let mut avc = self.avc.as_mut().filter(|_| avc);
+        if let Some(avc) = avc.as_deref_mut() {
+            avc.update(bitmap);
+        }
+
+        if let Some(fill_pixel) = solid_color(bitmap) {
+            return Ok(ServerPdu::SolidFill(SolidFillPdu {
+                surface_id,
+                fill_pixel,
+                rectangles: vec![destination_rectangle],
+            }));
+        }
+
+        let area = usize::from(bitmap.width.get()) * usize::from(bitmap.height.get());
+        if let Some(avc) = avc.filter(|_| area >= MIN_AREA) {
+            if let Some((surface_rectangle, bitmap_data)) = avc.encode(&destination_rectangle)? {
+                return Ok(ServerPdu::WireToSurface1(WireToSurface1Pdu {
+                    surface_id,
+                    codec_id: Codec1Type::Avc420,
+                    pixel_format: gfx::PixelFormat::XRgb,
+                    destination_rectangle: surface_rectangle,
+                    bitmap_data,
+                }));
+            }
+        }
+
+        let (codec_id, bitmap_data) = match self.remotefx.as_mut() {
+            Some(remotefx) if area >= MIN_AREA => (
+                Codec1Type::RemoteFx,
+                remotefx.encode(bitmap).context("RemoteFX encoding")?,
+            ),
+            _ => (Codec1Type::Planar, self.planar(bitmap).context("planar encoding")?),
+        };
+
+        Ok(ServerPdu::WireToSurface1(WireToSurface1Pdu {
+            surface_id,
+            codec_id,
+            pixel_format: gfx::PixelFormat::XRgb,
+            destination_rectangle,
+            bitmap_data,
+        }))
+    }
+
+    fn planar(&mut self, bitmap: &BitmapUpdate) -> Result<Vec<u8>, BitmapEncodeError> {
+        let width = usize::from(bitmap.width.get());
+        let height = usize::from(bitmap.height.get());
+        let bytes_per_pixel = usize::from(bitmap.format.bytes_per_pixel());
+        let row_len = width * bytes_per_pixel;
+
+        // Unlike bitmap updates, planar surface commands are top-down
+        let rows = bitmap.data.chunks(bitmap.stride).map(|row| &row[..row_len]);
+
+        loop {
+            let encoder = BitmapStreamEncoder::new(width, height);
+            let res = match bitmap.order {
+                PixelOrder::TopToBottom => {
+                    let pixels = rows.clone().flat_map(|row| row.chunks(bytes_per_pixel));
+                    encode_pixels(encoder, bitmap.format, pixels, &mut self.buffer)
+                }
+                PixelOrder::BottomToTop => {
+                    let pixels = rows.clone().rev().flat_map(|row| row.chunks(bytes_per_pixel));
+                    encode_pixels(encoder, bitmap.format, pixels, &mut self.buffer)
+                }
+            };
+
+            match res {
+                Ok(len) => return Ok(self.buffer[..len].to_vec()),
+                Err(BitmapEncodeError::Rle(RleEncodeError::BufferTooSmall)) => {}
+                Err(BitmapEncodeError::Encode(e)) if matches!(e.kind(), EncodeErrorKind::NotEnoughBytes { .. }) => {}
+                Err(e) => return Err(e),
+            }
+
+            self.buffer.resize(self.buffer.len() * 2, 0);
+            debug!("GFX encoder buffer resized to: {}", self.buffer.len());
+        }
+    }
+}
+
+fn encode_pixels<'a, P>(
+    mut encoder: BitmapStreamEncoder,
+    format: PixelFormat,
+    src: P,
+    dst: &mut [u8],
+) -> Result<usize, BitmapEncodeError>
+where
+    P: Iterator<Item = &'a [u8]> + Clone,
+{
+    match format {
+        PixelFormat::ARgb32 | PixelFormat::XRgb32 => encoder.encode_pixels_stream::<_, ARgbChannels>(src, dst, true),
+        PixelFormat::RgbA32 | PixelFormat::RgbX32 => encoder.encode_pixels_stream::<_, RgbAChannels>(src, dst, true),
+        PixelFormat::ABgr32 | PixelFormat::XBgr32 => encoder.encode_pixels_stream::<_, ABgrChannels>(src, dst, true),
+        PixelFormat::BgrA32 | PixelFormat::BgrX32 => encoder.encode_pixels_stream::<_, BgrAChannels>(src, dst, true),
+    }
+}
+
+/// Returns the color of `bitmap` if all its pixels have the same one.
+fn solid_color(bitmap: &BitmapUpdate) -> Option<Color> {
+    let bytes_per_pixel = usize::from(bitmap.format.bytes_per_pixel());
+    let row_len = usize::from(bitmap.width.get()) * bytes_per_pixel;
+    let first = bitmap.data.get(..bytes_per_pixel)?;
+
+    let solid = bitmap
+        .data
+        .chunks(bitmap.stride)
+        .take(usize::from(bitmap.height.get()))
+        .all(|row| row[..row_len].chunks_exact(bytes_per_pixel).all(|pixel| pixel == first));
+    if !solid {
+        return None;
+    }
+
+    let color = bitmap.format.read_color(first).ok()?;
+
+    Some(Color {
+        b: color.b,
+        g: color.g,
+        r: color.r,
+        xa: 0xff,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU16;
+
+    use super::super::avc::tests::TestH264Encoder;
+    use super::*;
+    use crate::DesktopSize;
+
+    const SURFACE_ID: u16 = 3;
+
+    /// Returns a bitmap with noisy pixels, so that it isn't sent as a solid fill.
+    fn noisy(width: u16, height: u16) -> BitmapUpdate {
+        let mut state = 0x1234_5678u32;
+        let data = (0..usize::from(width) * usize::from(height) * 4)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                state.to_be_bytes()[0]
+            })
+            .collect();
+
+        BitmapUpdate {
+            top: 0,
+            left: 0,
+            width: NonZeroU16::new(width).unwrap(),
+            height: NonZeroU16::new(height).unwrap(),
+            format: PixelFormat::BgrX32,
+            order: PixelOrder::TopToBottom,
+            stride: usize::from(width) * 4,
+            data,
+        }
+    }
+
+    fn avc_encoder(remotefx: bool, h264_data: &[u8]) -> GfxEncoder {
+        let (h264, _) = TestH264Encoder::build(h264_data);
+        let avc = Avc420Encoder::new(
+            h264,
+            DesktopSize {
+                width: 128,
+                height: 128,
+            },
+        );
+
+        GfxEncoder::new(remotefx).with_avc(avc)
+    }
+
+    fn codec(pdu: &ServerPdu) -> Codec1Type {
+        match pdu {
+            ServerPdu::WireToSurface1(pdu) => {
+                assert_eq!(pdu.surface_id, SURFACE_ID);
+                pdu.codec_id
+            }
+            pdu => panic!("unexpected PDU: {pdu:?}"),
+        }
+    }
+
+    #[test]
+    fn large_updates_use_avc_when_allowed() {
+        let mut encoder = avc_encoder(true, &[1]);
+
+        let pdu = encoder.encode(SURFACE_ID, &noisy(64, 64), true).unwrap();
+
+        assert_eq!(codec(&pdu), Codec1Type::Avc420);
+        let ServerPdu::WireToSurface1(pdu) = pdu else {
+            unreachable!()
+        };
+        assert_eq!(
+            pdu.destination_rectangle,
+            InclusiveRectangle {
+                left: 0,
+                top: 0,
+                right: 127,
+                bottom: 127,
+            }
+        );
+    }
+
+    #[test]
+    fn large_updates_use_remotefx_without_avc() {
+        let mut encoder = avc_encoder(true, &[1]);
+        assert_eq!(
+            codec(&encoder.encode(SURFACE_ID, &noisy(64, 64), false).unwrap()),
+            Codec1Type::RemoteFx
+        );
+
+        let mut encoder = GfxEncoder::new(true);
+        assert_eq!(
+            codec(&encoder.encode(SURFACE_ID, &noisy(64, 64), true).unwrap()),
+            Codec1Type::RemoteFx
+        );
+    }
+
+    #[test]
+    fn large_updates_use_planar_without_avc_and_remotefx() {
+        let mut encoder = avc_encoder(false, &[1]);
+
+        assert_eq!(
+            codec(&encoder.encode(SURFACE_ID, &noisy(64, 64), false).unwrap()),
+            Codec1Type::Planar
+        );
+    }
+
+    #[test]
+    fn small_updates_use_planar() {
+        let mut encoder = avc_encoder(true, &[1]);
+
+        // Just below the minimum area of AVC and RemoteFX
+        assert_eq!(
+            codec(&encoder.encode(SURFACE_ID, &noisy(64, 63), true).unwrap()),
+            Codec1Type::Planar
+        );
+    }
+
+    #[test]
+    fn skipped_avc_pictures_fall_back_to_another_codec() {
+        let mut encoder = avc_encoder(true, &[]);
+        assert_eq!(
+            codec(&encoder.encode(SURFACE_ID, &noisy(64, 64), true).unwrap()),
+            Codec1Type::RemoteFx
+        );
+
+        let mut encoder = avc_encoder(false, &[]);
+        assert_eq!(
+            codec(&encoder.encode(SURFACE_ID, &noisy(64, 64), true).unwrap()),
+            Codec1Type::Planar
+        );
+    }
+
+    #[test]
+    fn uniform_updates_are_solid_fills() {
+        let mut encoder = avc_encoder(true, &[1]);
+        let mut bitmap = noisy(64, 64);
+        bitmap.left = 10;
+        bitmap.top = 20;
+        bitmap.data = [0x30, 0x20, 0x10, 0x00].repeat(64 * 64);
+
+        let pdu = encoder.encode(SURFACE_ID, &bitmap, true).unwrap();
+
+        assert_eq!(
+            pdu,
+            ServerPdu::SolidFill(SolidFillPdu {
+                surface_id: SURFACE_ID,
+                fill_pixel: Color {
+                    b: 0x30,
+                    g: 0x20,
+                    r: 0x10,
+                    xa: 0xff,
+                },
+                rectangles: vec![InclusiveRectangle {
+                    left: 10,
+                    top: 20,
+                    right: 73,
+                    bottom: 83,
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_bitmaps_are_rejected() {
+        let mut encoder = avc_encoder(true, &[1]);
+
+        // Data shorter than the size, uniform or not
+        for mut bitmap in [noisy(64, 64), noisy(2, 2)] {
+            bitmap.data.truncate(bitmap.data.len() - 1);
+            assert!(encoder.encode(SURFACE_ID, &bitmap, true).is_err());
+        }
+
+        let mut bitmap = noisy(64, 64);
+        bitmap.stride = 4;
+        assert!(encoder.encode(SURFACE_ID, &bitmap, true).is_err());
+
+        // Edges beyond the largest coordinates
+        let mut bitmap = noisy(64, 64);
+        bitmap.left = u16::MAX - 10;
+        assert!(encoder.encode(SURFACE_ID, &bitmap, true).is_err());
+    }
+}
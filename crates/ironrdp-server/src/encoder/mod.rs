@@ -1,4 +1,6 @@
+pub(crate) mod avc;
 mod bitmap;
+pub(crate) mod gfx;
 pub(crate) mod rfx;
 
 use std::{cmp, mem};
@@ -7,7 +9,8 @@ use anyhow::{Context, Result};
 use ironrdp_core::Encode;
 use ironrdp_core::WriteCursor;
 use ironrdp_pdu::fast_path::{EncryptionFlags, FastPathHeader, FastPathUpdatePdu, Fragmentation, UpdateCode};
-use ironrdp_pdu::geometry::ExclusiveRectangle;
+use ironrdp_pdu::geometry::{ExclusiveRectangle, InclusiveRectangle};
+use ironrdp_pdu::orders::{DrawingOrdersPdu, PrimaryDrawingOrder, ScrBltOrder, ROP_SRCCOPY};
 use ironrdp_pdu::pointer::{ColorPointerAttribute, Point16, PointerAttribute, PointerPositionAttribute};
 use ironrdp_pdu::rdp::capability_sets::{CmdFlags, EntropyBits};
 use ironrdp_pdu::surface_commands::{ExtendedBitmapDataPdu, SurfaceBitsPdu, SurfaceCommand};
@@ -32,6 +35,8 @@ pub(crate) struct UpdateEncoder {
     buffer: Vec<u8>,
     bitmap: BitmapEncoder,
     remotefx: Option<(RfxEncoder, u8)>,
+    /// The client accepts ScrBlt primary drawing orders.
+    scr_blt: bool,
     update: for<'a> fn(&'a mut UpdateEncoder, BitmapUpdate) -> Result<UpdateFragmenter<'a>>,
 }
 
@@ -49,10 +54,17 @@ impl UpdateEncoder {
             buffer: vec![0; 16384],
             bitmap: BitmapEncoder::new(),
             remotefx: remotefx.map(|(algo, id)| (RfxEncoder::new(algo), id)),
+            scr_blt: false,
             update,
         }
     }
 
+    #[must_use]
+    pub(crate) fn with_scr_blt(mut self, scr_blt: bool) -> Self {
+        self.scr_blt = scr_blt;
+        self
+    }
+
     fn encode_pdu(&mut self, pdu: impl Encode) -> Result<usize> {
         loop {
             let mut cursor = WriteCursor::new(self.buffer.as_mut_slice());
@@ -125,6 +137,28 @@ impl UpdateEncoder {
         Ok(UpdateFragmenter::new(UpdateCode::PositionPointer, &self.buffer[..len]))
     }
 
+    /// Returns the ScrBlt order copying the `src` region to `dst`, or `None` if the client doesn't support it.
+    pub(crate) fn copy_rect(&mut self, src: &InclusiveRectangle, dst: Point16) -> Result<Option<UpdateFragmenter<'_>>> {
+        if !self.scr_blt {
+            return Ok(None);
+        }
+
+        let order = ScrBltOrder {
+            left: i16::try_from(dst.x)?,
+            top: i16::try_from(dst.y)?,
+            width: i16::try_from(src.right - src.left + 1)?,
+            height: i16::try_from(src.bottom - src.top + 1)?,
+            rop: ROP_SRCCOPY,
+            src_x: i16::try_from(src.left)?,
+            src_y: i16::try_from(src.top)?,
+        };
+        let pdu = DrawingOrdersPdu {
+            orders: vec![PrimaryDrawingOrder::ScrBlt(order)],
+        };
+        let len = self.encode_pdu(pdu)?;
+        Ok(Some(UpdateFragmenter::new(UpdateCode::Orders, &self.buffer[..len])))
+    }
+
     pub(crate) fn bitmap(&mut self, bitmap: BitmapUpdate) -> Result<UpdateFragmenter<'_>> {
         let update = self.update;
 
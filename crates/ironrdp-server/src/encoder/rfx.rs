@@ -11,6 +11,7 @@ use crate::BitmapUpdate;
 #[derive(Debug)]
 pub(crate) struct RfxEncoder {
     entropy_algorithm: rfx::EntropyAlgorithm,
+    data: UpdateEncoderData,
 }
 
 impl RfxEncoder {
@@ -19,7 +20,10 @@ impl RfxEncoder {
             EntropyBits::Rlgr1 => rfx::EntropyAlgorithm::Rlgr1,
             EntropyBits::Rlgr3 => rfx::EntropyAlgorithm::Rlgr3,
         };
-        Self { entropy_algorithm }
+        Self {
+            entropy_algorithm,
+            data: UpdateEncoderData::default(),
+        }
     }
 
     // FIXME: rewrite to use WriteCursor
@@ -60,8 +64,8 @@ impl RfxEncoder {
         let region = rfx::RegionPdu { rectangles };
         let quant = rfx::Quant::default();
 
-        let (encoder, mut data) = UpdateEncoder::new(bitmap, quant.clone(), entropy_algorithm);
-        let tiles = encoder.encode(&mut data)?;
+        let encoder = UpdateEncoder::new(bitmap, quant.clone(), entropy_algorithm);
+        let tiles = encoder.encode(&mut self.data)?;
 
         let quants = vec![quant];
         let tile_set = rfx::TileSetPdu {
@@ -106,8 +110,29 @@ pub(crate) struct UpdateEncoder<'a> {
     entropy_algorithm: rfx::EntropyAlgorithm,
 }
 
+/// Output buffer of the tiles, reused from one update to the next
+#[derive(Debug, Default)]
 struct UpdateEncoderData(Vec<u8>);
 
+/// Scratch buffers used to encode a tile
+struct TileScratch {
+    y: [i16; 4096],
+    cb: [i16; 4096],
+    cr: [i16; 4096],
+    temp: [i16; 4096],
+}
+
+impl TileScratch {
+    fn new() -> Box<Self> {
+        Box::new(Self {
+            y: [0; 4096],
+            cb: [0; 4096],
+            cr: [0; 4096],
+            temp: [0; 4096],
+        })
+    }
+}
+
 struct EncodedTile<'a> {
     y_data: &'a [u8],
     cb_data: &'a [u8],
@@ -115,25 +140,22 @@ struct EncodedTile<'a> {
 }
 
 impl<'a> UpdateEncoder<'a> {
-    fn new(
-        bitmap: &'a BitmapUpdate,
-        quant: rfx::Quant,
-        entropy_algorithm: rfx::EntropyAlgorithm,
-    ) -> (Self, UpdateEncoderData) {
-        let this = Self {
+    fn new(bitmap: &'a BitmapUpdate, quant: rfx::Quant, entropy_algorithm: rfx::EntropyAlgorithm) -> Self {
+        Self {
             bitmap,
             quant,
             entropy_algorithm,
-        };
-        let data = this.alloc_data();
-
-        (this, data)
+        }
     }
 
-    fn alloc_data(&self) -> UpdateEncoderData {
+    /// Grows `data` to hold the output of all the tiles, if needed.
+    fn alloc_data(&self, data: &mut UpdateEncoderData) {
         let (tiles_x, tiles_y) = self.tiles_xy();
+        let len = 64 * 64 * 3 * tiles_x * tiles_y;
 
-        UpdateEncoderData(vec![0u8; 64 * 64 * 3 * tiles_x * tiles_y])
+        if data.0.len() < len {
+            data.0.resize(len, 0);
+        }
     }
 
     fn tiles_xy(&self) -> (usize, usize) {
@@ -147,6 +169,7 @@ impl<'a> UpdateEncoder<'a> {
         #[cfg(feature = "rayon")]
         use rayon::prelude::*;
 
+        self.alloc_data(data);
         let (tiles_x, tiles_y) = self.tiles_xy();
 
         #[cfg(not(feature = "rayon"))]
@@ -156,33 +179,49 @@ impl<'a> UpdateEncoder<'a> {
 
         let tiles: Vec<_> = (0..tiles_y).flat_map(|y| (0..tiles_x).map(move |x| (x, y))).collect();
 
-        chunks
-            .zip(tiles)
-            .map(|(buf, (tile_x, tile_y))| {
-                let EncodedTile {
-                    y_data,
-                    cb_data,
-                    cr_data,
-                } = self
-                    .encode_tile(tile_x, tile_y, buf)
-                    .map_err(|e| other_err!("rfxenc", source: e))?;
-
-                let tile = rfx::Tile {
-                    y_quant_index: 0,
-                    cb_quant_index: 0,
-                    cr_quant_index: 0,
-                    x: u16::try_from(tile_x).unwrap(),
-                    y: u16::try_from(tile_y).unwrap(),
-                    y_data,
-                    cb_data,
-                    cr_data,
-                };
-                Ok(tile)
+        let encode = |scratch: &mut TileScratch, (buf, (tile_x, tile_y)): (&'a mut [u8], (usize, usize))| {
+            let EncodedTile {
+                y_data,
+                cb_data,
+                cr_data,
+            } = self
+                .encode_tile(tile_x, tile_y, buf, scratch)
+                .map_err(|e| other_err!("rfxenc", source: e))?;
+
+            Ok(rfx::Tile {
+                y_quant_index: 0,
+                cb_quant_index: 0,
+                cr_quant_index: 0,
+                x: u16::try_from(tile_x).unwrap(),
+                y: u16::try_from(tile_y).unwrap(),
+                y_data,
+                cb_data,
+                cr_data,
             })
-            .collect()
+        };
+
+        // Each worker thread gets its own scratch buffers
+        #[cfg(feature = "rayon")]
+        let tiles = chunks
+            .zip(tiles)
+            .map_init(TileScratch::new, |scratch, tile| encode(scratch, tile))
+            .collect();
+        #[cfg(not(feature = "rayon"))]
+        let tiles = {
+            let mut scratch = TileScratch::new();
+            chunks.zip(tiles).map(|tile| encode(&mut scratch, tile)).collect()
+        };
+
+        tiles
     }
 
-    fn encode_tile<'b>(&self, tile_x: usize, tile_y: usize, buf: &'b mut [u8]) -> Result<EncodedTile<'b>, RlgrError> {
+    fn encode_tile<'b>(
+        &self,
+        tile_x: usize,
+        tile_y: usize,
+        buf: &'b mut [u8],
+        scratch: &mut TileScratch,
+    ) -> Result<EncodedTile<'b>, RlgrError> {
         assert!(buf.len() >= 4096 * 3);
 
         let bpp: usize = self.bitmap.format.bytes_per_pixel().into();
@@ -195,9 +234,7 @@ impl<'a> UpdateEncoder<'a> {
         let tile_height = std::cmp::min(height - y, 64);
         let input = &self.bitmap.data[y * self.bitmap.stride + x * bpp..];
 
-        let y = &mut [0i16; 4096];
-        let cb = &mut [0i16; 4096];
-        let cr = &mut [0i16; 4096];
+        let TileScratch { y, cb, cr, temp } = scratch;
         to_64x64_ycbcr_tile(
             input,
             tile_width,
@@ -212,11 +249,11 @@ impl<'a> UpdateEncoder<'a> {
         let (y_data, buf) = buf.split_at_mut(4096);
         let (cb_data, cr_data) = buf.split_at_mut(4096);
 
-        let len = rfx_encode_component(y, y_data, &self.quant, self.entropy_algorithm)?;
+        let len = rfx_encode_component(y, y_data, temp, &self.quant, self.entropy_algorithm)?;
         let y_data = &y_data[..len];
-        let len = rfx_encode_component(cb, cb_data, &self.quant, self.entropy_algorithm)?;
+        let len = rfx_encode_component(cb, cb_data, temp, &self.quant, self.entropy_algorithm)?;
         let cb_data = &cb_data[..len];
-        let len = rfx_encode_component(cr, cr_data, &self.quant, self.entropy_algorithm)?;
+        let len = rfx_encode_component(cr, cr_data, temp, &self.quant, self.entropy_algorithm)?;
         let cr_data = &cr_data[..len];
 
         Ok(EncodedTile {
@@ -238,14 +275,30 @@ pub(crate) mod bench {
         tile_x: usize,
         tile_y: usize,
     ) {
-        let (enc, mut data) = UpdateEncoder::new(bitmap, quant.clone(), algo);
+        let enc = UpdateEncoder::new(bitmap, quant.clone(), algo);
+        let mut data = UpdateEncoderData::default();
+        enc.alloc_data(&mut data);
+
+        enc.encode_tile(tile_x, tile_y, &mut data.0, &mut TileScratch::new())
+            .unwrap();
+    }
+
+    /// Encodes whole RemoteFX frames, reusing its buffers from one frame to the next
+    pub struct RfxFrameEncoder(RfxEncoder);
 
-        enc.encode_tile(tile_x, tile_y, &mut data.0).unwrap();
+    impl RfxFrameEncoder {
+        pub fn new(entropy_bits: EntropyBits) -> Self {
+            Self(RfxEncoder::new(entropy_bits))
+        }
+
+        pub fn encode(&mut self, bitmap: &BitmapUpdate) -> usize {
+            self.0.encode(bitmap).unwrap().len()
+        }
     }
 
     pub fn rfx_enc(bitmap: &BitmapUpdate, quant: &rfx::Quant, algo: rfx::EntropyAlgorithm) {
-        let (enc, mut data) = UpdateEncoder::new(bitmap, quant.clone(), algo);
+        let enc = UpdateEncoder::new(bitmap, quant.clone(), algo);
 
-        enc.encode(&mut data).unwrap();
+        enc.encode(&mut UpdateEncoderData::default()).unwrap();
     }
 }
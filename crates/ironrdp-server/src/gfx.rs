@@ -0,0 +1,1207 @@
+//! Graphics pipeline (MS-RDPEGFX) output
+//!
+//! Once the client opens the channel and advertises its capabilities, the server creates a surface covering the
+//! desktop and draws bitmap updates on it, in frames paced by the acknowledgements of the client.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use ironrdp_async::FramedWrite;
+use ironrdp_core::{decode, encode_vec, ensure_size, impl_as_any, other_err, Encode, EncodeResult, WriteCursor};
+use ironrdp_dvc::{encode_dvc_messages, DvcEncode, DvcMessage, DvcProcessor, DvcServerProcessor};
+use ironrdp_graphics::image_processing::PixelFormat;
+use ironrdp_graphics::zgfx;
+use ironrdp_pdu::gcc::{Monitor, MonitorFlags};
+use ironrdp_pdu::geometry::InclusiveRectangle;
+use ironrdp_pdu::pointer::Point16;
+use ironrdp_pdu::rdp::vc::dvc::gfx::{
+    self, CacheImportReplyPdu, CapabilitiesConfirmPdu, CapabilitiesV103Flags, CapabilitiesV104Flags,
+    CapabilitiesV107Flags, CapabilitiesV10Flags, CapabilitiesV81Flags, CapabilitySet, ClientPdu, CreateSurfacePdu,
+    EndFramePdu, FrameAcknowledgePdu, MapSurfaceToOutputPdu, Point, QueueDepth, ResetGraphicsPdu, ServerPdu,
+    StartFramePdu, SurfaceToSurfacePdu, Timestamp,
+};
+use ironrdp_pdu::PduResult;
+use ironrdp_svc::{server_encode_svc_messages, ChannelFlags};
+use tokio::sync::Notify;
+
+use crate::encoder::avc::Avc420Encoder;
+use crate::encoder::gfx::GfxEncoder;
+use crate::{time_warn, BitmapUpdate, DesktopSize, FrameAcknowledgement, H264Encoder, PixelOrder};
+
+pub(crate) const CHANNEL_NAME: &str = "Microsoft::Windows::RDS::Graphics";
+
+/// The single surface, mapped to the whole desktop.
+pub(crate) const SURFACE_ID: u16 = 0;
+
+/// Maximum number of frames sent and not yet acknowledged by the client.
+const MAX_FRAMES_IN_FLIGHT: u32 = 3;
+
+/// Maximum number of frames whose acknowledgement is tracked, while the client doesn't send them.
+const MAX_TRACKED_FRAMES: usize = 32;
+
+/// Shared state of the graphics pipeline of a connection
+///
+/// It is updated by the channel processor, and read by the display loop to send bitmap updates.
+#[derive(Clone)]
+pub(crate) struct GfxHandle(Arc<Shared>);
+
+struct Shared {
+    state: Mutex<GfxState>,
+    acknowledged: Notify,
+    surface_created: Notify,
+}
+
+struct GfxState {
+    size: DesktopSize,
+    channel_id: Option<u32>,
+    /// Capability set confirmed to the client, once the surface is created.
+    caps: Option<CapabilitySet>,
+    next_frame_id: u32,
+    last_acknowledged_frame_id: u32,
+    /// The client stopped acknowledging frames, which must not be waited for anymore.
+    acknowledgements_suspended: bool,
+    /// Identifiers and sending times of the frames not yet acknowledged.
+    sent_frames: VecDeque<(u32, Instant)>,
+    /// Acknowledgements not yet given to the encoder.
+    acknowledgements: Vec<FrameAcknowledgement>,
+    /// Copy of the desktop, sent as the initial content of each created surface.
+    framebuffer: Framebuffer,
+    /// The surface was created, and its initial content is not sent yet.
+    surface_content_pending: bool,
+}
+
+impl GfxHandle {
+    pub(crate) fn new(size: DesktopSize) -> Self {
+        Self(Arc::new(Shared {
+            state: Mutex::new(GfxState {
+                size,
+                channel_id: None,
+                caps: None,
+                next_frame_id: 0,
+                last_acknowledged_frame_id: u32::MAX,
+                acknowledgements_suspended: false,
+                sent_frames: VecDeque::new(),
+                acknowledgements: Vec::new(),
+                framebuffer: Framebuffer::new(size),
+                surface_content_pending: false,
+            }),
+            acknowledged: Notify::new(),
+            surface_created: Notify::new(),
+        }))
+    }
+
+    fn state(&self) -> MutexGuard<'_, GfxState> {
+        self.0.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Returns whether bitmap updates are sent through the graphics pipeline.
+    ///
+    /// `bitmap` is copied to the desktop in any case, to be part of the initial content of the surfaces created later.
+    pub(crate) fn handles(&self, bitmap: &BitmapUpdate) -> bool {
+        let mut state = self.state();
+        state.framebuffer.update(bitmap);
+
+        state.caps.is_some()
+    }
+
+    /// Returns whether moved regions are sent through the graphics pipeline.
+    ///
+    /// The copy is applied to the desktop in any case, like bitmaps in [`Self::handles`].
+    pub(crate) fn handles_copy(&self, src: &InclusiveRectangle, dst: Point16) -> bool {
+        let mut state = self.state();
+        state.framebuffer.copy_rect(src, dst);
+
+        state.caps.is_some()
+    }
+
+    /// Returns the initial content of the surface, if it was just created and the content is not sent yet.
+    fn take_surface_content(&self) -> Option<BitmapUpdate> {
+        let mut state = self.state();
+        if !std::mem::take(&mut state.surface_content_pending) {
+            return None;
+        }
+
+        state.framebuffer.to_bitmap()
+    }
+
+    /// Returns whether the client accepts AVC420 surface commands.
+    fn avc_enabled(&self) -> bool {
+        match self.state().caps.as_ref() {
+            Some(CapabilitySet::V8_1 { flags }) => flags.contains(CapabilitiesV81Flags::AVC420_ENABLED),
+            Some(CapabilitySet::V10 { flags } | CapabilitySet::V10_2 { flags }) => {
+                !flags.contains(CapabilitiesV10Flags::AVC_DISABLED)
+            }
+            Some(CapabilitySet::V10_1) => true,
+            Some(CapabilitySet::V10_3 { flags }) => !flags.contains(CapabilitiesV103Flags::AVC_DISABLED),
+            Some(
+                CapabilitySet::V10_4 { flags }
+                | CapabilitySet::V10_5 { flags }
+                | CapabilitySet::V10_6 { flags }
+                | CapabilitySet::V10_6Err { flags },
+            ) => !flags.contains(CapabilitiesV104Flags::AVC_DISABLED),
+            Some(CapabilitySet::V10_7 { flags }) => !flags.contains(CapabilitiesV107Flags::AVC_DISABLED),
+            Some(CapabilitySet::V8 { .. } | CapabilitySet::Unknown(_)) | None => false,
+        }
+    }
+
+    fn take_acknowledgements(&self) -> Vec<FrameAcknowledgement> {
+        std::mem::take(&mut self.state().acknowledgements)
+    }
+
+    /// Waits until the client acknowledged enough frames to send a new one.
+    async fn wait_for_frame_slot(&self) {
+        loop {
+            // Created before checking the state, so that acknowledgements received meanwhile are not missed
+            let acknowledged = self.0.acknowledged.notified();
+
+            if self.state().can_send_frame() {
+                return;
+            }
+
+            acknowledged.await;
+        }
+    }
+
+    /// Wraps `commands` in a frame, returning the messages to send on the channel, or `None` if it is closed.
+    fn frame(&self, commands: Vec<ServerPdu>) -> EncodeResult<Option<(u32, Vec<DvcMessage>)>> {
+        let mut state = self.state();
+        let Some(channel_id) = state.channel_id.filter(|_| state.caps.is_some()) else {
+            return Ok(None);
+        };
+
+        Ok(Some((channel_id, state.frame(commands)?)))
+    }
+}
+
+impl GfxState {
+    fn can_send_frame(&self) -> bool {
+        let in_flight = self
+            .next_frame_id
+            .wrapping_sub(self.last_acknowledged_frame_id.wrapping_add(1));
+
+        // Frames are not paced while the pipeline is not ready, they are dropped instead
+        self.caps.is_none() || self.acknowledgements_suspended || in_flight < MAX_FRAMES_IN_FLIGHT
+    }
+
+    fn frame(&mut self, commands: Vec<ServerPdu>) -> EncodeResult<Vec<DvcMessage>> {
+        let frame_id = self.next_frame_id;
+        self.next_frame_id = frame_id.wrapping_add(1);
+
+        if self.sent_frames.len() == MAX_TRACKED_FRAMES {
+            self.sent_frames.pop_front();
+        }
+        self.sent_frames.push_back((frame_id, Instant::now()));
+
+        let start = ServerPdu::StartFrame(StartFramePdu {
+            timestamp: timestamp(),
+            frame_id,
+        });
+        let end = ServerPdu::EndFrame(EndFramePdu { frame_id });
+
+        std::iter::once(start)
+            .chain(commands)
+            .chain(std::iter::once(end))
+            .map(|pdu| SegmentedPdu::new(&pdu).map(|pdu| Box::new(pdu) as DvcMessage))
+            .collect()
+    }
+
+    fn acknowledge(&mut self, ack: FrameAcknowledgePdu) {
+        let in_flight = self
+            .next_frame_id
+            .wrapping_sub(self.last_acknowledged_frame_id.wrapping_add(1));
+        let sent_after = self.next_frame_id.wrapping_sub(ack.frame_id.wrapping_add(1));
+        if sent_after >= in_flight {
+            // Frames acknowledged again may still tell that the client suspends or resumes acknowledgements
+            let sent = ack.frame_id.wrapping_sub(self.next_frame_id) > u32::MAX / 2;
+            if sent {
+                self.acknowledgements_suspended = ack.queue_depth == QueueDepth::Suspend;
+            } else {
+                debug!(
+                    frame_id = ack.frame_id,
+                    "Ignoring acknowledgement of a frame never sent"
+                );
+            }
+            return;
+        }
+
+        self.last_acknowledged_frame_id = ack.frame_id;
+        self.acknowledgements_suspended = ack.queue_depth == QueueDepth::Suspend;
+
+        // Acknowledgements come in order, the frames sent before won't be acknowledged anymore
+        while let Some((frame_id, sent)) = self.sent_frames.pop_front() {
+            if frame_id != ack.frame_id {
+                continue;
+            }
+
+            if self.acknowledgements.len() == MAX_TRACKED_FRAMES {
+                self.acknowledgements.remove(0);
+            }
+            self.acknowledgements.push(FrameAcknowledgement {
+                latency: sent.elapsed(),
+                frames_in_flight: u32::try_from(self.sent_frames.len()).unwrap_or(u32::MAX),
+                queued_bytes: match ack.queue_depth {
+                    QueueDepth::AvailableBytes(bytes) => Some(bytes),
+                    QueueDepth::Unavailable | QueueDepth::Suspend => None,
+                },
+            });
+            break;
+        }
+    }
+
+    /// Confirms `caps` and creates the surface.
+    ///
+    /// Its initial content, the desktop copied so far, is encoded and sent by the display loop, see
+    /// [`GfxOutput::send_surface_content`].
+    fn create_surface(&mut self, caps: CapabilitySet) -> Result<Vec<DvcMessage>> {
+        let DesktopSize { width, height } = self.size;
+
+        let messages = [
+            ServerPdu::CapabilitiesConfirm(CapabilitiesConfirmPdu(caps.clone())),
+            ServerPdu::ResetGraphics(ResetGraphicsPdu {
+                width: u32::from(width),
+                height: u32::from(height),
+                monitors: vec![Monitor {
+                    left: 0,
+                    top: 0,
+                    right: i32::from(width) - 1,
+                    bottom: i32::from(height) - 1,
+                    flags: MonitorFlags::PRIMARY,
+                }],
+            }),
+            ServerPdu::CreateSurface(CreateSurfacePdu {
+                surface_id: SURFACE_ID,
+                width,
+                height,
+                pixel_format: gfx::PixelFormat::XRgb,
+            }),
+            ServerPdu::MapSurfaceToOutput(MapSurfaceToOutputPdu {
+                surface_id: SURFACE_ID,
+                output_origin_x: 0,
+                output_origin_y: 0,
+            }),
+        ]
+        .iter()
+        .map(|pdu| SegmentedPdu::new(pdu).map(|pdu| Box::new(pdu) as DvcMessage))
+        .collect::<EncodeResult<Vec<_>>>()?;
+
+        self.caps = Some(caps);
+        self.surface_content_pending = true;
+
+        Ok(messages)
+    }
+
+    /// Forgets about the surface and the frames sent on it, once the channel is closed.
+    fn close(&mut self) {
+        self.channel_id = None;
+        self.caps = None;
+        self.surface_content_pending = false;
+        self.last_acknowledged_frame_id = self.next_frame_id.wrapping_sub(1);
+        self.acknowledgements_suspended = false;
+        self.sent_frames.clear();
+        self.acknowledgements.clear();
+    }
+}
+
+/// Sends bitmap updates through the graphics pipeline
+pub(crate) struct GfxOutput {
+    handle: GfxHandle,
+    encoder: Option<GfxEncoder>,
+    drdynvc_channel_id: u16,
+}
+
+impl GfxOutput {
+    pub(crate) fn new(
+        handle: GfxHandle,
+        drdynvc_channel_id: u16,
+        remotefx: bool,
+        h264: Option<Box<dyn H264Encoder>>,
+    ) -> Self {
+        let size = handle.state().size;
+        let avc = h264.and_then(|h264| Avc420Encoder::new(h264, size));
+
+        Self {
+            handle,
+            encoder: Some(GfxEncoder::new(remotefx).with_avc(avc)),
+            drdynvc_channel_id,
+        }
+    }
+
+    pub(crate) fn handles(&self, bitmap: &BitmapUpdate) -> bool {
+        self.handle.handles(bitmap)
+    }
+
+    pub(crate) fn handles_copy(&self, src: &InclusiveRectangle, dst: Point16) -> bool {
+        self.handle.handles_copy(src, dst)
+    }
+
+    /// Waits until a surface is created, the initial content of which is then sent with
+    /// [`Self::send_surface_content`].
+    pub(crate) async fn surface_created(&self) {
+        loop {
+            // Created before checking the state, so that surfaces created meanwhile are not missed
+            let created = self.handle.0.surface_created.notified();
+
+            if self.handle.state().surface_content_pending {
+                return;
+            }
+
+            created.await;
+        }
+    }
+
+    /// Draws the initial content of the surface if it was just created, returning whether it did.
+    pub(crate) async fn send_surface_content(
+        &mut self,
+        writer: &mut impl FramedWrite,
+        user_channel_id: u16,
+    ) -> Result<bool> {
+        let Some(bitmap) = self.handle.take_surface_content() else {
+            return Ok(false);
+        };
+
+        // A new surface starts a new H.264 stream
+        if let Some(encoder) = self.encoder.as_mut() {
+            encoder.request_keyframe();
+        }
+
+        self.handle.wait_for_frame_slot().await;
+        let command = self.encode(bitmap).await?;
+        self.send_frame(vec![command], writer, user_channel_id).await?;
+
+        Ok(true)
+    }
+
+    /// Draws `bitmap` on the surface, in a frame of its own.
+    pub(crate) async fn bitmap(
+        &mut self,
+        bitmap: BitmapUpdate,
+        writer: &mut impl FramedWrite,
+        user_channel_id: u16,
+    ) -> Result<()> {
+        // The initial content of a new surface already includes the bitmap
+        if self.send_surface_content(writer, user_channel_id).await? {
+            return Ok(());
+        }
+
+        self.handle.wait_for_frame_slot().await;
+        let command = self.encode(bitmap).await?;
+
+        self.send_frame(vec![command], writer, user_channel_id).await
+    }
+
+    async fn encode(&mut self, bitmap: BitmapUpdate) -> Result<ServerPdu> {
+        let mut encoder = self.encoder.take().context("GFX encoder is gone")?;
+        for acknowledgement in self.handle.take_acknowledgements() {
+            encoder.frame_acknowledged(acknowledgement);
+        }
+
+        let avc = self.handle.avc_enabled();
+        let (encoder, command) = tokio::task::spawn_blocking(move || {
+            let command = time_warn!("Encoding GFX bitmap", 10, encoder.encode(SURFACE_ID, &bitmap, avc));
+            (encoder, command)
+        })
+        .await?;
+        self.encoder = Some(encoder);
+
+        command
+    }
+
+    /// Copies the `source` region of the surface to `destination`, its new top-left corner.
+    pub(crate) async fn copy_rect(
+        &mut self,
+        source: InclusiveRectangle,
+        destination: Point16,
+        writer: &mut impl FramedWrite,
+        user_channel_id: u16,
+    ) -> Result<()> {
+        let DesktopSize { width, height } = self.handle.state().size;
+        if !copy_fits(usize::from(width), usize::from(height), &source, destination) {
+            warn!(?source, ?destination, "Copied region outside of the surface");
+            return Ok(());
+        }
+
+        // The initial content of a new surface already includes the copy
+        if self.send_surface_content(writer, user_channel_id).await? {
+            return Ok(());
+        }
+
+        self.handle.wait_for_frame_slot().await;
+
+        if let Some(encoder) = self.encoder.as_mut() {
+            encoder.copy_rect(&source, destination);
+        }
+
+        let destination = Point {
+            x: destination.x,
+            y: destination.y,
+        };
+        let command = ServerPdu::SurfaceToSurface(SurfaceToSurfacePdu {
+            source_surface_id: SURFACE_ID,
+            destination_surface_id: SURFACE_ID,
+            source_rectangle: source,
+            destination_points: vec![destination],
+        });
+
+        self.send_frame(vec![command], writer, user_channel_id).await
+    }
+
+    async fn send_frame(
+        &mut self,
+        commands: Vec<ServerPdu>,
+        writer: &mut impl FramedWrite,
+        user_channel_id: u16,
+    ) -> Result<()> {
+        let Some((channel_id, messages)) = self.handle.frame(commands)? else {
+            debug!("GFX channel closed, dropping display update");
+            return Ok(());
+        };
+
+        let messages = encode_dvc_messages(channel_id, messages, ChannelFlags::SHOW_PROTOCOL)?;
+        let data = server_encode_svc_messages(messages, self.drdynvc_channel_id, user_channel_id)?;
+        writer.write_all(&data).await.context("failed to write GFX frame")?;
+
+        Ok(())
+    }
+}
+
+/// Server side of the graphics pipeline channel
+pub(crate) struct GfxServer {
+    handle: GfxHandle,
+}
+
+impl GfxServer {
+    pub(crate) fn new(handle: GfxHandle) -> Self {
+        Self { handle }
+    }
+}
+
+impl_as_any!(GfxServer);
+
+impl DvcProcessor for GfxServer {
+    fn channel_name(&self) -> &str {
+        CHANNEL_NAME
+    }
+
+    fn start(&mut self, channel_id: u32) -> PduResult<Vec<DvcMessage>> {
+        // The client advertises its capabilities first
+        self.handle.state().channel_id = Some(channel_id);
+
+        Ok(Vec::new())
+    }
+
+    fn close(&mut self, _channel_id: u32) {
+        self.handle.state().close();
+
+        self.handle.0.acknowledged.notify_waiters();
+    }
+
+    fn process(&mut self, _channel_id: u32, payload: &[u8]) -> PduResult<Vec<DvcMessage>> {
+        let pdu = match decode::<ClientPdu>(payload) {
+            Ok(pdu) => pdu,
+            Err(error) => {
+                debug!(%error, "Ignoring unsupported GFX PDU");
+                return Ok(Vec::new());
+            }
+        };
+
+        match pdu {
+            ClientPdu::CapabilitiesAdvertise(advertise) => {
+                debug!(?advertise, "Received GFX capabilities");
+
+                let Some(caps) = advertise
+                    .0
+                    .into_iter()
+                    .max_by_key(version_rank)
+                    .filter(|c| version_rank(c) > 0)
+                else {
+                    warn!("No supported GFX capability set");
+                    return Ok(Vec::new());
+                };
+
+                debug!(?caps, "Confirming GFX capabilities");
+                let messages = self
+                    .handle
+                    .state()
+                    .create_surface(caps)
+                    .map_err(|e| ironrdp_pdu::pdu_other_err!("GFX", source: std::io::Error::other(e)))?;
+                self.handle.0.surface_created.notify_waiters();
+
+                Ok(messages)
+            }
+            ClientPdu::FrameAcknowledge(ack) => {
+                self.handle.state().acknowledge(ack);
+
+                self.handle.0.acknowledged.notify_waiters();
+
+                Ok(Vec::new())
+            }
+            ClientPdu::CacheImportOffer(offer) => {
+                // Persistent cache entries are not used: none of the offered ones are imported
+                debug!(entries = offer.cache_entries.len(), "Declining GFX cache import offer");
+
+                let reply = ServerPdu::CacheImportReply(CacheImportReplyPdu {
+                    cache_slots: Vec::new(),
+                });
+                let reply = SegmentedPdu::new(&reply).map_err(|e| ironrdp_pdu::encode_err!(e))?;
+
+                Ok(vec![Box::new(reply)])
+            }
+        }
+    }
+}
+
+impl DvcServerProcessor for GfxServer {}
+
+/// Returns how preferred a capability set is, 0 if it is not supported.
+fn version_rank(caps: &CapabilitySet) -> u8 {
+    match caps {
+        CapabilitySet::V8 { .. } => 1,
+        CapabilitySet::V8_1 { .. } => 2,
+        CapabilitySet::V10 { .. } => 3,
+        CapabilitySet::V10_1 => 4,
+        CapabilitySet::V10_2 { .. } => 5,
+        CapabilitySet::V10_3 { .. } => 6,
+        CapabilitySet::V10_4 { .. } => 7,
+        CapabilitySet::V10_5 { .. } => 8,
+        CapabilitySet::V10_6Err { .. } => 9,
+        CapabilitySet::V10_6 { .. } => 10,
+        CapabilitySet::V10_7 { .. } => 11,
+        CapabilitySet::Unknown(_) => 0,
+    }
+}
+
+fn timestamp() -> Timestamp {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = now.as_secs();
+
+    Timestamp {
+        milliseconds: u16::try_from(now.subsec_millis()).unwrap(),
+        seconds: u8::try_from(seconds % 60).unwrap(),
+        minutes: u8::try_from(seconds / 60 % 60).unwrap(),
+        hours: u16::try_from(seconds / 3600 % 24).unwrap(),
+    }
+}
+
+/// GFX PDU wrapped in an uncompressed RDP_SEGMENTED_DATA structure, as sent by the server
+struct SegmentedPdu(Vec<u8>);
+
+impl SegmentedPdu {
+    const NAME: &'static str = "RDP_SEGMENTED_DATA";
+
+    fn new(pdu: &ServerPdu) -> EncodeResult<Self> {
+        let data = encode_vec(pdu)?;
+
+        let mut segmented = Vec::with_capacity(data.len() + 16);
+        zgfx::wrap_uncompressed(&data, &mut segmented).map_err(|e| other_err!(Self::NAME, source: e))?;
+
+        Ok(Self(segmented))
+    }
+}
+
+impl Encode for SegmentedPdu {
+    fn encode(&self, dst: &mut WriteCursor<'_>) -> EncodeResult<()> {
+        ensure_size!(in: dst, size: self.size());
+
+        dst.write_slice(&self.0);
+
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn size(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl DvcEncode for SegmentedPdu {}
+
+/// Copy of the desktop, in the pixel format of the surface
+pub(crate) struct Framebuffer {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) data: Vec<u8>,
+}
+
+impl Framebuffer {
+    pub(crate) const FORMAT: PixelFormat = PixelFormat::BgrX32;
+
+    pub(crate) fn new(size: DesktopSize) -> Self {
+        let width = usize::from(size.width);
+        let height = usize::from(size.height);
+
+        Self {
+            width,
+            height,
+            data: vec![0; width * height * usize::from(Self::FORMAT.bytes_per_pixel())],
+        }
+    }
+
+    /// Draws the part of `bitmap` within the framebuffer, ignoring it if its data doesn't cover its size.
+    pub(crate) fn update(&mut self, bitmap: &BitmapUpdate) {
+        let left = usize::from(bitmap.left);
+        let top = usize::from(bitmap.top);
+        if left >= self.width || top >= self.height {
+            return;
+        }
+
+        let height = usize::from(bitmap.height.get());
+        let width = usize::from(bitmap.width.get()).min(self.width - left);
+
+        let src_bpp = usize::from(bitmap.format.bytes_per_pixel());
+        let dst_bpp = usize::from(Self::FORMAT.bytes_per_pixel());
+
+        if !bitmap_data_fits(bitmap) {
+            warn!(?bitmap, "Bitmap data doesn't match its size, ignoring it");
+            return;
+        }
+
+        for (i, row) in bitmap.data.chunks(bitmap.stride).take(height).enumerate() {
+            let y = match bitmap.order {
+                PixelOrder::TopToBottom => top + i,
+                PixelOrder::BottomToTop => top + height - 1 - i,
+            };
+            if y >= self.height {
+                continue;
+            }
+
+            let start = (y * self.width + left) * dst_bpp;
+            let dst = &mut self.data[start..start + width * dst_bpp];
+            let src = &row[..width * src_bpp];
+
+            if bitmap.format.eq_no_alpha(Self::FORMAT) {
+                dst.copy_from_slice(src);
+            } else {
+                for (src, dst) in src.chunks_exact(src_bpp).zip(dst.chunks_exact_mut(dst_bpp)) {
+                    if let Ok(color) = bitmap.format.read_color(src) {
+                        let _ = Self::FORMAT.write_color(color, dst);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Copies the `src` region with its top-left corner at `dst`, returning `false` if either doesn't lie within the
+    /// framebuffer.
+    pub(crate) fn copy_rect(&mut self, src: &InclusiveRectangle, dst: Point16) -> bool {
+        if !copy_fits(self.width, self.height, src, dst) {
+            return false;
+        }
+
+        let bpp = usize::from(Self::FORMAT.bytes_per_pixel());
+        let stride = self.width * bpp;
+        let row_len = (usize::from(src.right) - usize::from(src.left) + 1) * bpp;
+        let height = usize::from(src.bottom) - usize::from(src.top) + 1;
+
+        let mut copy_row = |i: usize| {
+            let start = (usize::from(src.top) + i) * stride + usize::from(src.left) * bpp;
+            let dst = (usize::from(dst.y) + i) * stride + usize::from(dst.x) * bpp;
+            self.data.copy_within(start..start + row_len, dst);
+        };
+
+        // Overlapping rows must be copied before being overwritten
+        if dst.y > src.top {
+            (0..height).rev().for_each(&mut copy_row);
+        } else {
+            (0..height).for_each(&mut copy_row);
+        }
+
+        true
+    }
+
+    fn to_bitmap(&self) -> Option<BitmapUpdate> {
+        Some(BitmapUpdate {
+            top: 0,
+            left: 0,
+            width: u16::try_from(self.width).ok()?.try_into().ok()?,
+            height: u16::try_from(self.height).ok()?.try_into().ok()?,
+            format: Self::FORMAT,
+            order: PixelOrder::TopToBottom,
+            stride: self.width * usize::from(Self::FORMAT.bytes_per_pixel()),
+            data: self.data.clone(),
+        })
+    }
+}
+
+/// Returns whether the data of `bitmap` covers its size, each row being `stride` bytes after the previous one.
+pub(crate) fn bitmap_data_fits(bitmap: &BitmapUpdate) -> bool {
+    let row_len = usize::from(bitmap.width.get()) * usize::from(bitmap.format.bytes_per_pixel());
+    let len = bitmap
+        .stride
+        .checked_mul(usize::from(bitmap.height.get()) - 1)
+        .and_then(|len| len.checked_add(row_len));
+
+    bitmap.stride >= row_len && len.is_some_and(|len| bitmap.data.len() >= len)
+}
+
+/// Returns whether the `src` region and its copy at `dst` lie within a desktop of `width` by `height` pixels.
+pub(crate) fn copy_fits(width: usize, height: usize, src: &InclusiveRectangle, dst: Point16) -> bool {
+    src.left <= src.right
+        && src.top <= src.bottom
+        && usize::from(src.right) < width
+        && usize::from(src.bottom) < height
+        && usize::from(dst.x) + usize::from(src.right - src.left) < width
+        && usize::from(dst.y) + usize::from(src.bottom - src.top) < height
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU16;
+
+    use ironrdp_pdu::rdp::vc::dvc::gfx::{
+        CacheEntryMetadata, CacheImportOfferPdu, CapabilitiesAdvertisePdu, CapabilitiesV8Flags,
+    };
+
+    use super::*;
+    use crate::encoder::avc::tests::TestH264Encoder;
+
+    const SIZE: DesktopSize = DesktopSize { width: 64, height: 32 };
+
+    const CHANNEL_ID: u32 = 7;
+
+    fn server() -> (GfxHandle, GfxServer) {
+        let handle = GfxHandle::new(SIZE);
+        let mut server = GfxServer::new(handle.clone());
+        assert!(server.start(CHANNEL_ID).unwrap().is_empty());
+        (handle, server)
+    }
+
+    /// Decodes the GFX PDUs sent by the server.
+    fn decode_messages(messages: Vec<DvcMessage>) -> Vec<ServerPdu> {
+        let mut decompressor = zgfx::Decompressor::new();
+
+        messages
+            .iter()
+            .map(|message| {
+                let segmented = encode_vec(message.as_ref()).unwrap();
+                let mut data = Vec::new();
+                decompressor.decompress(&segmented, &mut data).unwrap();
+                decode(&data).unwrap()
+            })
+            .collect()
+    }
+
+    fn process(server: &mut GfxServer, pdu: ClientPdu) -> Vec<ServerPdu> {
+        let payload = encode_vec(&pdu).unwrap();
+        decode_messages(server.process(CHANNEL_ID, &payload).unwrap())
+    }
+
+    fn advertise(server: &mut GfxServer, caps: Vec<CapabilitySet>) -> Vec<ServerPdu> {
+        process(server, ClientPdu::CapabilitiesAdvertise(CapabilitiesAdvertisePdu(caps)))
+    }
+
+    fn acknowledge(server: &mut GfxServer, frame_id: u32, queue_depth: QueueDepth) {
+        let ack = FrameAcknowledgePdu {
+            queue_depth,
+            frame_id,
+            total_frames_decoded: frame_id,
+        };
+        assert!(process(server, ClientPdu::FrameAcknowledge(ack)).is_empty());
+    }
+
+    fn v8() -> CapabilitySet {
+        CapabilitySet::V8 {
+            flags: CapabilitiesV8Flags::empty(),
+        }
+    }
+
+    fn v10_7() -> CapabilitySet {
+        CapabilitySet::V10_7 {
+            flags: CapabilitiesV107Flags::empty(),
+        }
+    }
+
+    fn bitmap(width: u16, height: u16, order: PixelOrder, data: Vec<u8>) -> BitmapUpdate {
+        BitmapUpdate {
+            top: 0,
+            left: 0,
+            width: NonZeroU16::new(width).unwrap(),
+            height: NonZeroU16::new(height).unwrap(),
+            format: PixelFormat::BgrX32,
+            order,
+            stride: usize::from(width) * 4,
+            data,
+        }
+    }
+
+    /// Returns the identifiers of the frames in `pdus`.
+    fn frames(pdus: &[ServerPdu]) -> Vec<u32> {
+        pdus.iter()
+            .filter_map(|pdu| match pdu {
+                ServerPdu::StartFrame(start) => Some(start.frame_id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn in_flight(handle: &GfxHandle) -> bool {
+        !handle.state().can_send_frame()
+    }
+
+    fn send_frame(handle: &GfxHandle) -> u32 {
+        let (channel_id, messages) = handle.frame(Vec::new()).unwrap().expect("frame");
+        assert_eq!(channel_id, CHANNEL_ID);
+        frames(&decode_messages(messages))[0]
+    }
+
+    #[test]
+    fn version_rank_prefers_the_latest_versions() {
+        let caps = [
+            v8(),
+            CapabilitySet::V8_1 {
+                flags: CapabilitiesV81Flags::empty(),
+            },
+            CapabilitySet::V10 {
+                flags: CapabilitiesV10Flags::empty(),
+            },
+            CapabilitySet::V10_1,
+            CapabilitySet::V10_2 {
+                flags: CapabilitiesV10Flags::empty(),
+            },
+            CapabilitySet::V10_3 {
+                flags: CapabilitiesV103Flags::empty(),
+            },
+            CapabilitySet::V10_4 {
+                flags: CapabilitiesV104Flags::empty(),
+            },
+            CapabilitySet::V10_5 {
+                flags: CapabilitiesV104Flags::empty(),
+            },
+            CapabilitySet::V10_6Err {
+                flags: CapabilitiesV104Flags::empty(),
+            },
+            CapabilitySet::V10_6 {
+                flags: CapabilitiesV104Flags::empty(),
+            },
+            v10_7(),
+        ];
+
+        assert_eq!(version_rank(&CapabilitySet::Unknown(Vec::new())), 0);
+        assert!(caps
+            .windows(2)
+            .all(|pair| version_rank(&pair[0]) < version_rank(&pair[1])));
+        assert!(version_rank(&caps[0]) > 0);
+    }
+
+    #[test]
+    fn latest_capability_set_is_confirmed_and_surface_created() {
+        let (handle, mut server) = server();
+
+        let pdus = advertise(&mut server, vec![v8(), v10_7(), CapabilitySet::Unknown(Vec::new())]);
+
+        assert_eq!(pdus[0], ServerPdu::CapabilitiesConfirm(CapabilitiesConfirmPdu(v10_7())));
+        assert_eq!(
+            pdus[1],
+            ServerPdu::ResetGraphics(ResetGraphicsPdu {
+                width: 64,
+                height: 32,
+                monitors: vec![Monitor {
+                    left: 0,
+                    top: 0,
+                    right: 63,
+                    bottom: 31,
+                    flags: MonitorFlags::PRIMARY,
+                }],
+            })
+        );
+        assert_eq!(
+            pdus[2],
+            ServerPdu::CreateSurface(CreateSurfacePdu {
+                surface_id: SURFACE_ID,
+                width: 64,
+                height: 32,
+                pixel_format: gfx::PixelFormat::XRgb,
+            })
+        );
+        assert_eq!(
+            pdus[3],
+            ServerPdu::MapSurfaceToOutput(MapSurfaceToOutputPdu {
+                surface_id: SURFACE_ID,
+                output_origin_x: 0,
+                output_origin_y: 0,
+            })
+        );
+
+        // The initial content of the surface is sent by the display loop
+        assert_eq!(pdus.len(), 4);
+        assert!(handle.state().surface_content_pending);
+
+        assert!(handle.avc_enabled());
+        assert!(handle.handles(&bitmap(1, 1, PixelOrder::TopToBottom, vec![0; 4])));
+    }
+
+    #[test]
+    fn unsupported_capability_sets_are_not_confirmed() {
+        let (handle, mut server) = server();
+
+        assert!(advertise(&mut server, vec![CapabilitySet::Unknown(Vec::new())]).is_empty());
+
+        assert!(!handle.handles(&bitmap(1, 1, PixelOrder::TopToBottom, vec![0; 4])));
+        assert!(handle.frame(Vec::new()).unwrap().is_none());
+    }
+
+    #[test]
+    fn avc_is_disabled_by_the_capabilities() {
+        let (handle, mut server) = server();
+
+        advertise(
+            &mut server,
+            vec![CapabilitySet::V10_7 {
+                flags: CapabilitiesV107Flags::AVC_DISABLED,
+            }],
+        );
+
+        assert!(!handle.avc_enabled());
+    }
+
+    #[test]
+    fn initial_content_of_the_surface_is_the_desktop() {
+        let (handle, mut server) = server();
+        assert!(handle.take_surface_content().is_none());
+
+        assert!(!handle.handles(&bitmap(1, 1, PixelOrder::TopToBottom, vec![1; 4])));
+        advertise(&mut server, vec![v10_7()]);
+        assert!(handle.handles(&bitmap(2, 1, PixelOrder::TopToBottom, vec![2; 8])));
+
+        // Sent once, including the updates drawn since the surface was created
+        let content = handle.take_surface_content().unwrap();
+        assert_eq!((content.width.get(), content.height.get()), (SIZE.width, SIZE.height));
+        assert_eq!(content.data[..8], [2; 8]);
+        assert!(content.data[8..].iter().all(|&byte| byte == 0));
+        assert!(handle.take_surface_content().is_none());
+    }
+
+    #[test]
+    fn surface_is_reset_when_the_channel_is_reopened() {
+        let (handle, mut server) = server();
+        advertise(&mut server, vec![v10_7()]);
+        handle.take_surface_content();
+        for _ in 0..MAX_FRAMES_IN_FLIGHT {
+            send_frame(&handle);
+        }
+        acknowledge(&mut server, 0, QueueDepth::Unavailable);
+
+        server.close(CHANNEL_ID);
+        assert!(handle.frame(Vec::new()).unwrap().is_none());
+        assert!(!handle.handles(&bitmap(1, 1, PixelOrder::TopToBottom, vec![3; 4])));
+
+        assert!(server.start(CHANNEL_ID).unwrap().is_empty());
+        let pdus = advertise(&mut server, vec![v8()]);
+
+        assert_eq!(pdus[0], ServerPdu::CapabilitiesConfirm(CapabilitiesConfirmPdu(v8())));
+        assert!(matches!(pdus[1], ServerPdu::ResetGraphics(_)));
+        assert!(matches!(pdus[2], ServerPdu::CreateSurface(_)));
+        assert!(matches!(pdus[3], ServerPdu::MapSurfaceToOutput(_)));
+        assert!(!handle.avc_enabled());
+
+        // The new surface gets the whole desktop, and none of the frames of the previous one are in flight
+        assert_eq!(handle.take_surface_content().unwrap().data[..4], [3; 4]);
+        assert!(handle.take_acknowledgements().is_empty());
+        assert!(!in_flight(&handle));
+        assert_eq!(send_frame(&handle), MAX_FRAMES_IN_FLIGHT);
+
+        // Frames sent on the previous surface are not acknowledged anymore
+        acknowledge(&mut server, 1, QueueDepth::Unavailable);
+        assert!(handle.take_acknowledgements().is_empty());
+    }
+
+    #[test]
+    fn frames_are_paced_by_acknowledgements() {
+        let (handle, mut server) = server();
+        advertise(&mut server, vec![v10_7()]);
+
+        for frame_id in 0..MAX_FRAMES_IN_FLIGHT {
+            assert!(!in_flight(&handle));
+            assert_eq!(send_frame(&handle), frame_id);
+        }
+        assert!(in_flight(&handle));
+
+        acknowledge(&mut server, 0, QueueDepth::AvailableBytes(1024));
+        assert!(!in_flight(&handle));
+        assert_eq!(send_frame(&handle), MAX_FRAMES_IN_FLIGHT);
+        assert!(in_flight(&handle));
+
+        let acknowledgements = handle.take_acknowledgements();
+        assert_eq!(acknowledgements.len(), 1);
+        assert_eq!(acknowledgements[0].frames_in_flight, MAX_FRAMES_IN_FLIGHT - 1);
+        assert_eq!(acknowledgements[0].queued_bytes, Some(1024));
+        assert!(handle.take_acknowledgements().is_empty());
+
+        // Acknowledging a later frame also releases the ones sent before
+        acknowledge(&mut server, 2, QueueDepth::Unavailable);
+        let acknowledgements = handle.take_acknowledgements();
+        assert_eq!(acknowledgements.len(), 1);
+        assert_eq!(acknowledgements[0].frames_in_flight, 1);
+        assert_eq!(acknowledgements[0].queued_bytes, None);
+        assert!(!in_flight(&handle));
+    }
+
+    #[test]
+    fn acknowledgements_of_frames_never_sent_are_ignored() {
+        let (handle, mut server) = server();
+        advertise(&mut server, vec![v10_7()]);
+        for _ in 0..MAX_FRAMES_IN_FLIGHT {
+            send_frame(&handle);
+        }
+
+        acknowledge(&mut server, MAX_FRAMES_IN_FLIGHT, QueueDepth::Unavailable);
+        acknowledge(&mut server, 10, QueueDepth::Suspend);
+
+        assert!(in_flight(&handle));
+        assert!(handle.take_acknowledgements().is_empty());
+    }
+
+    #[test]
+    fn suspended_acknowledgements_stop_pacing() {
+        let (handle, mut server) = server();
+        advertise(&mut server, vec![v10_7()]);
+        send_frame(&handle);
+
+        acknowledge(&mut server, 0, QueueDepth::Suspend);
+        for _ in 0..2 * MAX_FRAMES_IN_FLIGHT {
+            assert!(!in_flight(&handle));
+            send_frame(&handle);
+        }
+        assert_eq!(handle.take_acknowledgements()[0].queued_bytes, None);
+
+        // Frames are paced again once the client resumes acknowledging them
+        acknowledge(&mut server, 1, QueueDepth::Unavailable);
+        assert!(in_flight(&handle));
+    }
+
+    #[tokio::test]
+    async fn acknowledgement_releases_a_frame_slot() {
+        let (handle, mut server) = server();
+        advertise(&mut server, vec![v10_7()]);
+        for _ in 0..MAX_FRAMES_IN_FLIGHT {
+            send_frame(&handle);
+        }
+
+        tokio::join!(handle.wait_for_frame_slot(), async {
+            acknowledge(&mut server, 0, QueueDepth::Unavailable);
+        });
+    }
+
+    #[tokio::test]
+    async fn close_releases_the_frame_slots() {
+        let (handle, mut server) = server();
+        advertise(&mut server, vec![v10_7()]);
+        for _ in 0..MAX_FRAMES_IN_FLIGHT {
+            send_frame(&handle);
+        }
+
+        tokio::join!(handle.wait_for_frame_slot(), async {
+            server.close(CHANNEL_ID);
+        });
+    }
+
+    /// Writer keeping the data sent to the client.
+    #[derive(Default)]
+    struct TestWriter(Vec<u8>);
+
+    impl FramedWrite for TestWriter {
+        type WriteAllFut<'write> = std::future::Ready<std::io::Result<()>>;
+
+        fn write_all<'a>(&'a mut self, buf: &'a [u8]) -> Self::WriteAllFut<'a> {
+            self.0.extend_from_slice(buf);
+            std::future::ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn surface_content_starts_with_a_keyframe() {
+        let (handle, mut server) = server();
+        let (h264, state) = TestH264Encoder::build(&[1]);
+        let mut output = GfxOutput::new(handle.clone(), 1, false, Some(h264));
+        let mut writer = TestWriter::default();
+        assert_eq!(state.lock().unwrap().keyframes_requested, 1);
+
+        advertise(&mut server, vec![v10_7()]);
+        assert!(output.send_surface_content(&mut writer, 1002).await.unwrap());
+        assert!(!writer.0.is_empty());
+        assert_eq!(state.lock().unwrap().keyframes_requested, 2);
+
+        // Not requested again until the client creates another surface
+        assert!(!output.send_surface_content(&mut writer, 1002).await.unwrap());
+        assert_eq!(state.lock().unwrap().keyframes_requested, 2);
+
+        server.close(CHANNEL_ID);
+        assert!(server.start(CHANNEL_ID).unwrap().is_empty());
+        advertise(&mut server, vec![v10_7()]);
+        assert!(output.send_surface_content(&mut writer, 1002).await.unwrap());
+        assert_eq!(state.lock().unwrap().keyframes_requested, 3);
+    }
+
+    #[test]
+    fn cache_import_offer_is_declined() {
+        let (_, mut server) = server();
+
+        let offer = CacheImportOfferPdu {
+            cache_entries: vec![CacheEntryMetadata {
+                cache_key: 42,
+                bitmap_length: 64,
+            }],
+        };
+        let pdus = process(&mut server, ClientPdu::CacheImportOffer(offer));
+
+        assert_eq!(
+            pdus,
+            [ServerPdu::CacheImportReply(CacheImportReplyPdu {
+                cache_slots: Vec::new()
+            })]
+        );
+    }
+
+    #[test]
+    fn framebuffer_copies_bitmaps_in_both_orders() {
+        let pixels: Vec<u8> = (0..16).collect();
+
+        let mut framebuffer = Framebuffer::new(DesktopSize { width: 2, height: 2 });
+        framebuffer.update(&bitmap(2, 2, PixelOrder::TopToBottom, pixels.clone()));
+        assert_eq!(framebuffer.data, pixels);
+
+        framebuffer.update(&bitmap(2, 2, PixelOrder::BottomToTop, pixels.clone()));
+        assert_eq!(framebuffer.data[..8], pixels[8..]);
+        assert_eq!(framebuffer.data[8..], pixels[..8]);
+    }
+
+    #[test]
+    fn framebuffer_ignores_bitmaps_with_short_data() {
+        let mut framebuffer = Framebuffer::new(DesktopSize { width: 4, height: 2 });
+
+        // The last row is short
+        framebuffer.update(&bitmap(4, 2, PixelOrder::TopToBottom, vec![0xFF; 28]));
+        // The stride is shorter than a row
+        let mut short_stride = bitmap(4, 2, PixelOrder::TopToBottom, vec![0xFF; 32]);
+        short_stride.stride = 8;
+        framebuffer.update(&short_stride);
+
+        assert!(framebuffer.data.iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn framebuffer_clips_bitmaps_to_its_size() {
+        let mut framebuffer = Framebuffer::new(DesktopSize { width: 2, height: 2 });
+
+        // Beyond the right and bottom edges
+        let mut outside = bitmap(2, 2, PixelOrder::TopToBottom, vec![0xFF; 16]);
+        outside.left = 3;
+        framebuffer.update(&outside);
+        outside.left = 0;
+        outside.top = 2;
+        framebuffer.update(&outside);
+        assert!(framebuffer.data.iter().all(|&byte| byte == 0));
+
+        // Across the bottom-right corner
+        let mut across = bitmap(2, 2, PixelOrder::BottomToTop, (0..16).collect());
+        across.left = 1;
+        across.top = 1;
+        framebuffer.update(&across);
+        assert_eq!(framebuffer.data[12..], [8, 9, 10, 11]);
+        assert!(framebuffer.data[..12].iter().all(|&byte| byte == 0));
+    }
+}
@@ -0,0 +1,70 @@
+//! H.264 encoding, used by the AVC420 codec of the graphics pipeline
+
+#[cfg(feature = "openh264")]
+mod openh264;
+
+use std::time::Duration;
+
+use anyhow::Result;
+
+#[cfg(feature = "openh264")]
+pub use self::openh264::{OpenH264Encoder, OpenH264EncoderFactory};
+
+/// Picture in planar YUV 4:2:0, with even dimensions
+///
+/// The luma plane has `width` bytes per row, and the chroma planes `width / 2`.
+#[derive(Debug, Clone, Copy)]
+pub struct Yuv420Frame<'a> {
+    pub width: usize,
+    pub height: usize,
+    pub y: &'a [u8],
+    pub u: &'a [u8],
+    pub v: &'a [u8],
+}
+
+/// Encoded picture
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct H264Frame {
+    /// H.264 bitstream in the Annex B format, empty if the encoder skipped the picture.
+    pub data: Vec<u8>,
+    /// Quantization parameter used to encode the picture, between 0 and 51.
+    pub quantization_parameter: u8,
+    /// Quality level of the picture, between 0 (lowest) and 100 (lossless).
+    pub quality: u8,
+}
+
+/// Acknowledgement of a frame by the client, used to adapt the rate of the encoder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameAcknowledgement {
+    /// Time elapsed between sending the frame and receiving its acknowledgement.
+    pub latency: Duration,
+    /// Number of frames sent after this one and not yet acknowledged.
+    pub frames_in_flight: u32,
+    /// Number of bytes waiting to be decoded by the client, if reported.
+    pub queued_bytes: Option<u32>,
+}
+
+/// H.264 encoder of the pictures sent with the AVC420 codec
+///
+/// A separate encoder is built for each connection, the same one encoding all the frames of its surface.
+pub trait H264Encoder: Send {
+    /// Encodes `frame`, predicted from the previously encoded ones unless a keyframe was requested.
+    ///
+    /// When the returned bitstream is empty, the update is sent with another codec instead.
+    fn encode(&mut self, frame: Yuv420Frame<'_>) -> Result<H264Frame>;
+
+    /// Requests the next picture to be encoded as a keyframe (IDR).
+    fn request_keyframe(&mut self);
+
+    /// Called for each frame acknowledged by the client, before encoding the next picture.
+    ///
+    /// This is the hook for rate control: the default implementation ignores acknowledgements.
+    fn frame_acknowledged(&mut self, acknowledgement: FrameAcknowledgement) {
+        let _ = acknowledgement;
+    }
+}
+
+/// Builds the H.264 encoder of each connection
+pub trait H264EncoderFactory: Send + Sync {
+    fn build_encoder(&self) -> Result<Box<dyn H264Encoder>>;
+}
@@ -0,0 +1,300 @@
+use std::time::Duration;
+
+use ::openh264::encoder::{Encoder, EncoderConfig, FrameType, RateControlMode};
+use ::openh264::formats::YUVSlices;
+use ::openh264::OpenH264API;
+use anyhow::{ensure, Context, Result};
+use openh264_sys2::{SBitrateInfo, ENCODER_OPTION_BITRATE, SPATIAL_LAYER_ALL};
+
+use super::{FrameAcknowledgement, H264Encoder, H264EncoderFactory, H264Frame, Yuv420Frame};
+
+/// Acknowledgements slower than this mean the client or the network can't keep up.
+const CONGESTION_LATENCY: Duration = Duration::from_millis(150);
+
+/// Acknowledgements faster than this leave room for a higher bitrate.
+const IDLE_LATENCY: Duration = Duration::from_millis(50);
+
+/// Range of quantization parameters reported for the lowest and highest bitrates.
+const MAX_QP: u32 = 40;
+const MIN_QP: u32 = 22;
+
+/// Software H.264 encoder, built on the OpenH264 library
+///
+/// The bitrate starts at its maximum, is lowered when acknowledgements show that frames pile up, and raised back when
+/// they are acknowledged quickly.
+pub struct OpenH264Encoder {
+    encoder: Encoder,
+    max_bitrate: u32,
+    min_bitrate: u32,
+    bitrate: u32,
+    /// Bitrate currently set in the encoder, unknown until it is initialized by the first picture.
+    applied_bitrate: Option<u32>,
+    dimensions: (usize, usize),
+    keyframe_requested: bool,
+}
+
+impl OpenH264Encoder {
+    /// Creates an encoder whose bitrate is at most `max_bitrate`, in bits per second.
+    pub fn new(max_bitrate: u32) -> Result<Self> {
+        let config = EncoderConfig::new()
+            .set_bitrate_bps(max_bitrate)
+            .rate_control_mode(RateControlMode::Bitrate)
+            // Required to meet the bitrate, the regions of skipped pictures are sent with another codec
+            .enable_skip_frame(true);
+        let encoder = Encoder::with_api_config(OpenH264API::from_source(), config).context("OpenH264 encoder")?;
+
+        Ok(Self {
+            encoder,
+            max_bitrate,
+            min_bitrate: max_bitrate / 8,
+            bitrate: max_bitrate,
+            applied_bitrate: None,
+            dimensions: (0, 0),
+            keyframe_requested: false,
+        })
+    }
+
+    fn apply_bitrate(&mut self) -> Result<()> {
+        if self.applied_bitrate.map_or(true, |applied| applied == self.bitrate) {
+            return Ok(());
+        }
+
+        let mut info = SBitrateInfo {
+            iLayer: SPATIAL_LAYER_ALL,
+            iBitrate: i32::try_from(self.bitrate)?,
+        };
+        // SAFETY: the encoder is initialized once a picture was encoded, and the bitrate option takes a
+        // `SBitrateInfo`, which the encoder doesn't keep a reference to.
+        let res = unsafe {
+            self.encoder
+                .raw_api()
+                .set_option(ENCODER_OPTION_BITRATE, std::ptr::addr_of_mut!(info).cast())
+        };
+        ensure!(res == 0, "failed to set the OpenH264 bitrate: {res}");
+
+        self.applied_bitrate = Some(self.bitrate);
+
+        Ok(())
+    }
+
+    fn quality(&self) -> u8 {
+        let quality = u64::from(self.bitrate) * 100 / u64::from(self.max_bitrate.max(1));
+        u8::try_from(quality.min(100)).unwrap()
+    }
+}
+
+impl H264Encoder for OpenH264Encoder {
+    fn encode(&mut self, frame: Yuv420Frame<'_>) -> Result<H264Frame> {
+        let Yuv420Frame { width, height, y, u, v } = frame;
+        ensure!(
+            width % 2 == 0 && height % 2 == 0,
+            "odd picture dimensions: {width}x{height}"
+        );
+        ensure!(
+            y.len() == width * height && u.len() == width * height / 4 && v.len() == u.len(),
+            "YUV planes don't match the picture dimensions"
+        );
+
+        if self.dimensions != (width, height) {
+            // The encoder is reinitialized with its configured bitrate when encoding the picture
+            self.dimensions = (width, height);
+            self.applied_bitrate = None;
+        }
+        self.apply_bitrate()?;
+
+        if self.keyframe_requested {
+            self.encoder.force_intra_frame();
+            self.keyframe_requested = false;
+        }
+
+        let source = YUVSlices::new((y, u, v), (width, height), (width, width / 2, width / 2));
+        let bitstream = self.encoder.encode(&source).context("OpenH264 encoding")?;
+
+        let mut data = Vec::new();
+        if !matches!(bitstream.frame_type(), FrameType::Skip | FrameType::Invalid) {
+            bitstream.write_vec(&mut data);
+        }
+        self.applied_bitrate.get_or_insert(self.max_bitrate);
+
+        // OpenH264 doesn't report them, they are estimated from the bitrate
+        let quality = self.quality();
+
+        Ok(H264Frame {
+            data,
+            quantization_parameter: quantization_parameter(quality),
+            quality,
+        })
+    }
+
+    fn request_keyframe(&mut self) {
+        self.keyframe_requested = true;
+    }
+
+    fn frame_acknowledged(&mut self, acknowledgement: FrameAcknowledgement) {
+        if acknowledgement.frames_in_flight > 1 || acknowledgement.latency > CONGESTION_LATENCY {
+            self.bitrate = (self.bitrate / 4 * 3).max(self.min_bitrate);
+        } else if acknowledgement.latency < IDLE_LATENCY {
+            self.bitrate = self.bitrate.saturating_add(self.max_bitrate / 20).min(self.max_bitrate);
+        }
+    }
+}
+
+/// Returns the quantization parameter reported for a picture of the given `quality`.
+fn quantization_parameter(quality: u8) -> u8 {
+    let quantization_parameter = MAX_QP - (MAX_QP - MIN_QP) * u32::from(quality.min(100)) / 100;
+    u8::try_from(quantization_parameter).unwrap()
+}
+
+/// Builds an [`OpenH264Encoder`] for each connection
+#[derive(Debug, Clone, Copy)]
+pub struct OpenH264EncoderFactory {
+    max_bitrate: u32,
+}
+
+impl OpenH264EncoderFactory {
+    /// Creates a factory of encoders whose bitrate is at most `max_bitrate`, in bits per second.
+    pub fn new(max_bitrate: u32) -> Self {
+        Self { max_bitrate }
+    }
+}
+
+impl Default for OpenH264EncoderFactory {
+    fn default() -> Self {
+        Self::new(10_000_000)
+    }
+}
+
+impl H264EncoderFactory for OpenH264EncoderFactory {
+    fn build_encoder(&self) -> Result<Box<dyn H264Encoder>> {
+        Ok(Box::new(OpenH264Encoder::new(self.max_bitrate)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_BITRATE: u32 = 8_000_000;
+
+    fn acknowledgement(latency_ms: u64, frames_in_flight: u32) -> FrameAcknowledgement {
+        FrameAcknowledgement {
+            latency: Duration::from_millis(latency_ms),
+            frames_in_flight,
+            queued_bytes: None,
+        }
+    }
+
+    fn gray(width: usize, height: usize) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        (
+            vec![128; width * height],
+            vec![128; width * height / 4],
+            vec![128; width * height / 4],
+        )
+    }
+
+    #[test]
+    fn congestion_lowers_the_bitrate_down_to_its_minimum() {
+        let mut encoder = OpenH264Encoder::new(MAX_BITRATE).unwrap();
+
+        encoder.frame_acknowledged(acknowledgement(200, 0));
+        assert_eq!(encoder.bitrate, MAX_BITRATE / 4 * 3);
+
+        encoder.frame_acknowledged(acknowledgement(100, 2));
+        assert_eq!(encoder.bitrate, MAX_BITRATE / 4 * 3 / 4 * 3);
+
+        for _ in 0..20 {
+            encoder.frame_acknowledged(acknowledgement(200, 2));
+        }
+        assert_eq!(encoder.bitrate, MAX_BITRATE / 8);
+    }
+
+    #[test]
+    fn fast_acknowledgements_raise_the_bitrate_up_to_its_maximum() {
+        let mut encoder = OpenH264Encoder::new(MAX_BITRATE).unwrap();
+        encoder.bitrate = MAX_BITRATE / 2;
+
+        encoder.frame_acknowledged(acknowledgement(10, 1));
+        assert_eq!(encoder.bitrate, MAX_BITRATE / 2 + MAX_BITRATE / 20);
+
+        for _ in 0..20 {
+            encoder.frame_acknowledged(acknowledgement(10, 0));
+        }
+        assert_eq!(encoder.bitrate, MAX_BITRATE);
+    }
+
+    #[test]
+    fn moderate_latency_keeps_the_bitrate() {
+        let mut encoder = OpenH264Encoder::new(MAX_BITRATE).unwrap();
+        encoder.bitrate = MAX_BITRATE / 2;
+
+        encoder.frame_acknowledged(acknowledgement(100, 1));
+
+        assert_eq!(encoder.bitrate, MAX_BITRATE / 2);
+    }
+
+    #[test]
+    fn quality_follows_the_bitrate() {
+        let mut encoder = OpenH264Encoder::new(MAX_BITRATE).unwrap();
+        assert_eq!(encoder.quality(), 100);
+
+        encoder.bitrate = MAX_BITRATE / 2;
+        assert_eq!(encoder.quality(), 50);
+
+        encoder.bitrate = MAX_BITRATE / 8;
+        assert_eq!(encoder.quality(), 12);
+    }
+
+    #[test]
+    fn quantization_parameter_decreases_with_the_quality() {
+        assert_eq!(quantization_parameter(0), 40);
+        assert_eq!(quantization_parameter(50), 31);
+        assert_eq!(quantization_parameter(100), 22);
+        assert_eq!(quantization_parameter(u8::MAX), 22);
+    }
+
+    #[test]
+    fn pictures_report_the_estimated_quality() {
+        let mut encoder = OpenH264Encoder::new(MAX_BITRATE).unwrap();
+        encoder.request_keyframe();
+        let (y, u, v) = gray(64, 64);
+
+        let frame = encoder
+            .encode(Yuv420Frame {
+                width: 64,
+                height: 64,
+                y: &y,
+                u: &u,
+                v: &v,
+            })
+            .unwrap();
+
+        assert!(frame.data.starts_with(&[0, 0, 0, 1]));
+        assert_eq!(frame.quality, 100);
+        assert_eq!(frame.quantization_parameter, 22);
+        assert_eq!(encoder.applied_bitrate, Some(MAX_BITRATE));
+    }
+
+    #[test]
+    fn invalid_pictures_are_rejected() {
+        let mut encoder = OpenH264Encoder::new(MAX_BITRATE).unwrap();
+
+        let (y, u, v) = gray(64, 64);
+        let odd = Yuv420Frame {
+            width: 63,
+            height: 64,
+            y: &y,
+            u: &u,
+            v: &v,
+        };
+        assert!(encoder.encode(odd).is_err());
+
+        let short = Yuv420Frame {
+            width: 64,
+            height: 64,
+            y: &y[..64],
+            u: &u,
+            v: &v,
+        };
+        assert!(encoder.encode(short).is_err());
+    }
+}
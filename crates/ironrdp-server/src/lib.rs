@@ -10,22 +10,32 @@ pub use tokio_rustls;
 #[macro_use]
 extern crate tracing;
 
+mod audin;
 mod builder;
 mod capabilities;
 mod clipboard;
+mod connection;
+mod damage;
 mod display;
 mod encoder;
+mod gfx;
+mod h264;
 mod handler;
 #[cfg(feature = "helper")]
 mod helper;
+mod rdpdr;
 mod server;
 mod sound;
 
+pub use audin::*;
 pub use clipboard::*;
+pub use connection::{ChannelFactories, ConnectionHandlerFactory, ConnectionId};
 pub use display::*;
+pub use h264::*;
 pub use handler::*;
 #[cfg(feature = "helper")]
 pub use helper::*;
+pub use rdpdr::*;
 pub use server::*;
 pub use sound::*;
 
@@ -33,7 +43,7 @@ pub use sound::*;
 pub mod bench {
     pub mod encoder {
         pub mod rfx {
-            pub use crate::encoder::rfx::bench::{rfx_enc, rfx_enc_tile};
+            pub use crate::encoder::rfx::bench::{rfx_enc, rfx_enc_tile, RfxFrameEncoder};
         }
     }
 }
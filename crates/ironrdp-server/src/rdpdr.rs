@@ -0,0 +1,557 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use ironrdp_rdpdr::pdu::efs::{
+    AnyIoCtlCode, DeviceCloseRequest, DeviceControlRequest, DeviceCreateRequest, DeviceIoRequest, DeviceReadRequest,
+    DeviceWriteRequest, FileAttributes, MajorFunction, MinorFunction, ServerDeviceIoRequest,
+    ServerDriveQueryDirectoryRequest, ServerDriveQueryInformationRequest, SharedAccess,
+};
+pub use ironrdp_rdpdr::pdu::efs::{
+    CreateDisposition, CreateOptions, DesiredAccess, DeviceAnnounceHeader, DeviceType, FileBothDirectoryInformation,
+    FileInformationClass, FileInformationClassLevel, NtStatus,
+};
+pub use ironrdp_rdpdr::server::{DeviceIoCompletion, RdpdrServerHandler, RdpdrServerMessage};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{ConnectionId, ServerEvent, ServerEventSender};
+
+pub trait RdpdrServerFactory: ServerEventSender {
+    /// Builds the handler of connection `id`.
+    fn build_backend(&self, id: ConnectionId) -> Box<dyn RdpdrServerHandler>;
+}
+
+/// Gives access to the devices redirected by the connected clients.
+///
+/// Clones share the same state, so that one of them can be given to the server builder as the RDPDR factory, while
+/// the others are used to issue requests, through the [`ConnectionDevices`] of each client.
+#[derive(Debug, Clone, Default)]
+pub struct ClientDevices {
+    shared: Arc<Mutex<Shared>>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    /// Sender of the connection building its handler next.
+    sender: Option<mpsc::UnboundedSender<ServerEvent>>,
+    clients: BTreeMap<ConnectionId, Client>,
+    next_handler_id: u64,
+}
+
+/// Devices of a client, along with its requests waiting for their completion.
+#[derive(Debug)]
+struct Client {
+    /// Handler owning the entry, which is replaced when the connection is reactivated.
+    handler_id: u64,
+    sender: Option<mpsc::UnboundedSender<ServerEvent>>,
+    devices: Vec<DeviceAnnounceHeader>,
+    pending: HashMap<u32, oneshot::Sender<DeviceIoCompletion>>,
+    next_completion_id: u32,
+}
+
+impl ClientDevices {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The connections of the clients which support device redirection.
+    pub fn connections(&self) -> Vec<ConnectionId> {
+        lock(&self.shared).clients.keys().copied().collect()
+    }
+
+    /// Gives access to the devices redirected by the client of connection `id`.
+    pub fn connection(&self, id: ConnectionId) -> ConnectionDevices {
+        ConnectionDevices {
+            shared: Arc::clone(&self.shared),
+            id,
+        }
+    }
+}
+
+/// Gives access to the devices redirected by a client, see [`ClientDevices::connection`].
+///
+/// Requests are forwarded to the client as I/O requests, and complete once the client replies. They fail with
+/// [`io::ErrorKind::NotFound`] if the device is not redirected, and with [`io::ErrorKind::NotConnected`] if the client
+/// is not connected or disconnects in the meantime.
+#[derive(Debug, Clone)]
+pub struct ConnectionDevices {
+    shared: Arc<Mutex<Shared>>,
+    id: ConnectionId,
+}
+
+impl ConnectionDevices {
+    pub fn id(&self) -> ConnectionId {
+        self.id
+    }
+
+    /// The devices currently redirected by the client.
+    pub fn devices(&self) -> Vec<DeviceAnnounceHeader> {
+        lock(&self.shared)
+            .clients
+            .get(&self.id)
+            .map(|client| client.devices.clone())
+            .unwrap_or_default()
+    }
+
+    /// The drives currently redirected by the client.
+    pub fn drives(&self) -> Vec<DeviceAnnounceHeader> {
+        let mut devices = self.devices();
+        devices.retain(|device| device.device_type() == DeviceType::Filesystem);
+        devices
+    }
+
+    /// Opens or creates the file at `path`, relative to the root of the drive, and returns its file ID.
+    pub async fn create(
+        &self,
+        device_id: u32,
+        path: &str,
+        desired_access: DesiredAccess,
+        create_disposition: CreateDisposition,
+        create_options: CreateOptions,
+    ) -> io::Result<u32> {
+        let completion = self
+            .request(device_id, |completion_id| {
+                ServerDeviceIoRequest::Create(DeviceCreateRequest {
+                    device_io_request: io_request(device_id, 0, completion_id, MajorFunction::Create),
+                    desired_access,
+                    allocation_size: 0,
+                    file_attributes: FileAttributes::empty(),
+                    shared_access: SharedAccess::FILE_SHARE_READ | SharedAccess::FILE_SHARE_WRITE,
+                    create_disposition,
+                    create_options,
+                    path: path.to_owned(),
+                })
+            })
+            .await?;
+
+        match completion {
+            DeviceIoCompletion::Create(rsp) => Ok(rsp.file_id),
+            _ => Err(unexpected_completion()),
+        }
+    }
+
+    pub async fn close(&self, device_id: u32, file_id: u32) -> io::Result<()> {
+        self.request(device_id, |completion_id| {
+            ServerDeviceIoRequest::Close(DeviceCloseRequest {
+                device_io_request: io_request(device_id, file_id, completion_id, MajorFunction::Close),
+            })
+        })
+        .await
+        .map(|_| ())
+    }
+
+    /// Reads up to `length` bytes at `offset`, an empty buffer meaning the end of the file.
+    pub async fn read(&self, device_id: u32, file_id: u32, offset: u64, length: u32) -> io::Result<Vec<u8>> {
+        let completion = self
+            .request(device_id, |completion_id| {
+                ServerDeviceIoRequest::Read(DeviceReadRequest {
+                    device_io_request: io_request(device_id, file_id, completion_id, MajorFunction::Read),
+                    length,
+                    offset,
+                })
+            })
+            .await?;
+
+        match completion {
+            DeviceIoCompletion::Read(rsp) => Ok(rsp.read_data),
+            _ => Err(unexpected_completion()),
+        }
+    }
+
+    /// Writes `data` at `offset`, and returns the number of bytes written.
+    pub async fn write(&self, device_id: u32, file_id: u32, offset: u64, data: Vec<u8>) -> io::Result<u32> {
+        let completion = self
+            .request(device_id, |completion_id| {
+                ServerDeviceIoRequest::Write(DeviceWriteRequest {
+                    device_io_request: io_request(device_id, file_id, completion_id, MajorFunction::Write),
+                    offset,
+                    write_data: data,
+                })
+            })
+            .await?;
+
+        match completion {
+            DeviceIoCompletion::Write(rsp) => Ok(rsp.length),
+            _ => Err(unexpected_completion()),
+        }
+    }
+
+    pub async fn query_information(
+        &self,
+        device_id: u32,
+        file_id: u32,
+        file_info_class_lvl: FileInformationClassLevel,
+    ) -> io::Result<FileInformationClass> {
+        let completion = self
+            .request(device_id, |completion_id| {
+                ServerDeviceIoRequest::QueryInformation(ServerDriveQueryInformationRequest {
+                    device_io_request: io_request(device_id, file_id, completion_id, MajorFunction::QueryInformation),
+                    file_info_class_lvl,
+                })
+            })
+            .await?;
+
+        match completion {
+            DeviceIoCompletion::QueryInformation(rsp) => rsp
+                .buffer
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "empty information buffer")),
+            _ => Err(unexpected_completion()),
+        }
+    }
+
+    /// Returns the next entry of the directory opened as `file_id`, or `None` once all entries were returned.
+    ///
+    /// The first query must give the `pattern` of the entries to return, such as `\dir\*`.
+    pub async fn query_directory(
+        &self,
+        device_id: u32,
+        file_id: u32,
+        file_info_class_lvl: FileInformationClassLevel,
+        pattern: Option<&str>,
+    ) -> io::Result<Option<FileInformationClass>> {
+        let mut device_io_request = io_request(device_id, file_id, 0, MajorFunction::DirectoryControl);
+        device_io_request.minor_function = MinorFunction::IRP_MN_QUERY_DIRECTORY;
+
+        let completion = self
+            .submit(device_id, |completion_id| {
+                device_io_request.completion_id = completion_id;
+                ServerDeviceIoRequest::QueryDirectory(ServerDriveQueryDirectoryRequest {
+                    device_io_request,
+                    file_info_class_lvl,
+                    initial_query: u8::from(pattern.is_some()),
+                    path: pattern.unwrap_or_default().to_owned(),
+                })
+            })
+            .await?;
+
+        match completion {
+            DeviceIoCompletion::QueryDirectory(rsp) => match rsp.device_io_reply.io_status {
+                NtStatus::SUCCESS => Ok(rsp.buffer),
+                NtStatus::NO_MORE_FILES => Ok(None),
+                status => Err(status_error(status)),
+            },
+            _ => Err(unexpected_completion()),
+        }
+    }
+
+    /// Lists the entries of the directory at `path`, relative to the root of the drive.
+    pub async fn list_directory(&self, device_id: u32, path: &str) -> io::Result<Vec<FileBothDirectoryInformation>> {
+        let file_id = self
+            .create(
+                device_id,
+                path,
+                DesiredAccess::FILE_READ_DATA_OR_FILE_LIST_DIRECTORY,
+                CreateDisposition::FILE_OPEN,
+                CreateOptions::FILE_DIRECTORY_FILE,
+            )
+            .await?;
+
+        let pattern = format!("{}\\*", path.trim_end_matches('\\'));
+        let mut entries = Vec::new();
+        let mut result = Ok(());
+        loop {
+            let initial = entries.is_empty().then_some(pattern.as_str());
+            match self
+                .query_directory(
+                    device_id,
+                    file_id,
+                    FileInformationClassLevel::FILE_BOTH_DIRECTORY_INFORMATION,
+                    initial,
+                )
+                .await
+            {
+                Ok(Some(FileInformationClass::BothDirectory(entry))) => entries.push(entry),
+                Ok(Some(_)) => {
+                    result = Err(unexpected_completion());
+                    break;
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        // The directory is closed even when the enumeration failed.
+        self.close(device_id, file_id).await?;
+        result.map(|()| entries)
+    }
+
+    /// Sends a device control request, such as a smart card call, and returns the output buffer.
+    pub async fn device_control(
+        &self,
+        device_id: u32,
+        file_id: u32,
+        io_control_code: u32,
+        input_buffer: Vec<u8>,
+        output_buffer_length: u32,
+    ) -> io::Result<Vec<u8>> {
+        let completion = self
+            .request(device_id, |completion_id| ServerDeviceIoRequest::DeviceControl {
+                request: DeviceControlRequest {
+                    header: io_request(device_id, file_id, completion_id, MajorFunction::DeviceControl),
+                    output_buffer_length,
+                    input_buffer_length: 0,
+                    io_control_code: AnyIoCtlCode(io_control_code),
+                },
+                input_buffer,
+            })
+            .await?;
+
+        match completion {
+            DeviceIoCompletion::DeviceControl { output_buffer, .. } => Ok(output_buffer),
+            _ => Err(unexpected_completion()),
+        }
+    }
+
+    /// Sends the request built for the allocated completion ID, and fails unless it succeeds.
+    async fn request(
+        &self,
+        device_id: u32,
+        build: impl FnOnce(u32) -> ServerDeviceIoRequest,
+    ) -> io::Result<DeviceIoCompletion> {
+        let completion = self.submit(device_id, build).await?;
+        let status = completion.device_io_response().io_status;
+        if status == NtStatus::SUCCESS {
+            Ok(completion)
+        } else {
+            Err(status_error(status))
+        }
+    }
+
+    /// Sends the request built for the allocated completion ID, and waits for its completion.
+    async fn submit(
+        &self,
+        device_id: u32,
+        build: impl FnOnce(u32) -> ServerDeviceIoRequest,
+    ) -> io::Result<DeviceIoCompletion> {
+        let receiver = {
+            let mut shared = lock(&self.shared);
+            let client = shared.clients.get_mut(&self.id).ok_or_else(not_connected)?;
+            if !client.devices.iter().any(|device| device.device_id() == device_id) {
+                return Err(io::Error::new(io::ErrorKind::NotFound, "unknown device"));
+            }
+            let sender = client.sender.clone().ok_or_else(not_connected)?;
+
+            let completion_id = client.next_completion_id;
+            client.next_completion_id = completion_id.wrapping_add(1);
+
+            let (completion_sender, receiver) = oneshot::channel();
+            client.pending.insert(completion_id, completion_sender);
+
+            let request = build(completion_id);
+            if sender
+                .send(ServerEvent::Rdpdr(RdpdrServerMessage::IoRequest(request)))
+                .is_err()
+            {
+                client.pending.remove(&completion_id);
+                return Err(not_connected());
+            }
+
+            receiver
+        };
+
+        receiver.await.map_err(|_| not_connected())
+    }
+}
+
+impl ServerEventSender for ClientDevices {
+    fn set_sender(&mut self, sender: mpsc::UnboundedSender<ServerEvent>) {
+        lock(&self.shared).sender = Some(sender);
+    }
+}
+
+impl RdpdrServerFactory for ClientDevices {
+    fn build_backend(&self, id: ConnectionId) -> Box<dyn RdpdrServerHandler> {
+        let mut shared = lock(&self.shared);
+        let handler_id = shared.next_handler_id;
+        shared.next_handler_id += 1;
+
+        let client = Client {
+            handler_id,
+            sender: shared.sender.clone(),
+            devices: Vec::new(),
+            pending: HashMap::new(),
+            next_completion_id: 0,
+        };
+        shared.clients.insert(id, client);
+
+        Box::new(ClientDevicesHandler {
+            shared: Arc::clone(&self.shared),
+            id,
+            handler_id,
+        })
+    }
+}
+
+/// The handler of a connection, which forgets about its devices and fails its pending requests when dropped.
+#[derive(Debug)]
+struct ClientDevicesHandler {
+    shared: Arc<Mutex<Shared>>,
+    id: ConnectionId,
+    handler_id: u64,
+}
+
+impl ClientDevicesHandler {
+    fn with_client(&self, f: impl FnOnce(&mut Client)) {
+        let mut shared = lock(&self.shared);
+        if let Some(client) = shared
+            .clients
+            .get_mut(&self.id)
+            .filter(|client| client.handler_id == self.handler_id)
+        {
+            f(client);
+        }
+    }
+}
+
+impl RdpdrServerHandler for ClientDevicesHandler {
+    fn device_announced(&mut self, device: &DeviceAnnounceHeader) -> bool {
+        self.with_client(|client| {
+            client.devices.retain(|d| d.device_id() != device.device_id());
+            client.devices.push(device.clone());
+        });
+        true
+    }
+
+    fn device_removed(&mut self, device_id: u32) {
+        self.with_client(|client| client.devices.retain(|d| d.device_id() != device_id));
+    }
+
+    fn io_completed(&mut self, completion: DeviceIoCompletion) {
+        let completion_id = completion.device_io_response().completion_id;
+        let mut sender = None;
+        self.with_client(|client| sender = client.pending.remove(&completion_id));
+        if let Some(sender) = sender {
+            // The caller may have given up on the request.
+            let _ = sender.send(completion);
+        }
+    }
+}
+
+impl Drop for ClientDevicesHandler {
+    fn drop(&mut self) {
+        let mut shared = lock(&self.shared);
+        if shared
+            .clients
+            .get(&self.id)
+            .is_some_and(|client| client.handler_id == self.handler_id)
+        {
+            shared.clients.remove(&self.id);
+        }
+    }
+}
+
+fn lock(shared: &Mutex<Shared>) -> std::sync::MutexGuard<'_, Shared> {
+    shared.lock().expect("poisoned")
+}
+
+fn io_request(device_id: u32, file_id: u32, completion_id: u32, major_function: MajorFunction) -> DeviceIoRequest {
+    DeviceIoRequest {
+        device_id,
+        file_id,
+        completion_id,
+        major_function,
+        minor_function: MinorFunction::from(0),
+    }
+}
+
+/// Maps the status of a failed request to an I/O error.
+fn status_error(status: NtStatus) -> io::Error {
+    let kind = match status {
+        NtStatus::NO_SUCH_FILE => io::ErrorKind::NotFound,
+        NtStatus::ACCESS_DENIED => io::ErrorKind::PermissionDenied,
+        NtStatus::OBJECT_NAME_COLLISION => io::ErrorKind::AlreadyExists,
+        NtStatus::NOT_SUPPORTED | NtStatus::NOT_IMPLEMENTED => io::ErrorKind::Unsupported,
+        NtStatus::INVALID_PARAMETER => io::ErrorKind::InvalidInput,
+        NtStatus::TIMEOUT => io::ErrorKind::TimedOut,
+        NtStatus::CANCELLED => io::ErrorKind::Interrupted,
+        _ => io::ErrorKind::Other,
+    };
+    io::Error::new(kind, format!("request failed with {status:?}"))
+}
+
+fn not_connected() -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, "no client connected")
+}
+
+fn unexpected_completion() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "unexpected completion")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds the handler of connection `id`, returning the receiver of its requests.
+    fn connect(
+        devices: &mut ClientDevices,
+        id: ConnectionId,
+    ) -> (Box<dyn RdpdrServerHandler>, mpsc::UnboundedReceiver<ServerEvent>) {
+        let (sender, receiver) = ServerEvent::create_channel();
+        devices.set_sender(sender);
+        (devices.build_backend(id), receiver)
+    }
+
+    fn requested_device(receiver: &mut mpsc::UnboundedReceiver<ServerEvent>) -> Option<u32> {
+        match receiver.try_recv().ok()? {
+            ServerEvent::Rdpdr(RdpdrServerMessage::IoRequest(ServerDeviceIoRequest::Close(request))) => {
+                Some(request.device_io_request.device_id)
+            }
+            event => panic!("unexpected event: {event:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn devices_of_each_connection_are_kept_apart() {
+        let mut devices = ClientDevices::new();
+        let (first, second) = (ConnectionId(0), ConnectionId(1));
+        let (mut first_handler, mut first_requests) = connect(&mut devices, first);
+        let (mut second_handler, mut second_requests) = connect(&mut devices, second);
+
+        // Both clients use the same device ID
+        first_handler.device_announced(&DeviceAnnounceHeader::new_smartcard(1));
+        second_handler.device_announced(&DeviceAnnounceHeader::new_smartcard(1));
+        second_handler.device_announced(&DeviceAnnounceHeader::new_smartcard(2));
+
+        assert_eq!(devices.connections(), [first, second]);
+        assert_eq!(devices.connection(first).devices().len(), 1);
+        assert_eq!(devices.connection(second).devices().len(), 2);
+
+        // Requests are sent to the client owning the device
+        let request = tokio::spawn({
+            let devices = devices.connection(first);
+            async move { devices.close(1, 0).await }
+        });
+        tokio::task::yield_now().await;
+        assert_eq!(requested_device(&mut first_requests), Some(1));
+        assert_eq!(requested_device(&mut second_requests), None);
+
+        let error = devices.connection(first).close(2, 0).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+
+        // A disconnection only forgets about the devices and requests of that client
+        drop(first_handler);
+        assert_eq!(request.await.unwrap().unwrap_err().kind(), io::ErrorKind::NotConnected);
+        assert_eq!(devices.connections(), [second]);
+        assert!(devices.connection(first).devices().is_empty());
+        assert_eq!(devices.connection(second).devices().len(), 2);
+
+        let error = devices.connection(first).close(1, 0).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotConnected);
+    }
+
+    #[test]
+    fn reactivated_connections_keep_the_devices_of_their_new_handler() {
+        let mut devices = ClientDevices::new();
+        let id = ConnectionId(0);
+        let (old_handler, _old_requests) = connect(&mut devices, id);
+        let (mut new_handler, _new_requests) = connect(&mut devices, id);
+
+        new_handler.device_announced(&DeviceAnnounceHeader::new_smartcard(1));
+        drop(old_handler);
+
+        assert_eq!(devices.connection(id).devices().len(), 1);
+    }
+}
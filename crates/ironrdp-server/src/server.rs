@@ -1,44 +1,71 @@
-use std::net::SocketAddr;
+use std::cell::RefCell;
+use std::net::{Shutdown, SocketAddr};
 use std::rc::Rc;
 use std::sync::Arc;
 
 use anyhow::{anyhow, bail, Context, Result};
 use ironrdp_acceptor::{self, Acceptor, AcceptorResult, BeginResult, DesktopSize};
 use ironrdp_async::{bytes, Framed};
+use ironrdp_audin::server::AudinServer;
 use ironrdp_cliprdr::backend::ClipboardMessage;
 use ironrdp_cliprdr::CliprdrServer;
 use ironrdp_core::{decode, encode_vec, impl_as_any};
 use ironrdp_displaycontrol::pdu::DisplayControlMonitorLayout;
 use ironrdp_displaycontrol::server::{DisplayControlHandler, DisplayControlServer};
+use ironrdp_pdu::geometry::InclusiveRectangle;
 use ironrdp_pdu::input::fast_path::{FastPathInput, FastPathInputEvent};
 use ironrdp_pdu::input::InputEventPdu;
 use ironrdp_pdu::mcs::{SendDataIndication, SendDataRequest};
-use ironrdp_pdu::rdp::capability_sets::{BitmapCodecs, CapabilitySet, CmdFlags, GeneralExtraFlags};
+use ironrdp_pdu::pointer::Point16;
+use ironrdp_pdu::rdp::capability_sets::{BitmapCodecs, CapabilitySet, CmdFlags, GeneralExtraFlags, OrderSupportIndex};
 pub use ironrdp_pdu::rdp::client_info::Credentials;
 use ironrdp_pdu::rdp::headers::{ServerDeactivateAll, ShareControlPdu};
 use ironrdp_pdu::x224::X224;
 use ironrdp_pdu::{self, decode_err, mcs, nego, rdp, Action, PduResult};
+use ironrdp_rdpdr::server::RdpdrServer;
 use ironrdp_svc::{server_encode_svc_messages, StaticChannelId, StaticChannelSet, SvcProcessor};
 use ironrdp_tokio::{split_tokio_framed, unsplit_tokio_framed, FramedRead, FramedWrite, TokioFramed};
 use rdpsnd::server::{RdpsndServer, RdpsndServerMessage};
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::{mpsc, Mutex};
-use tokio::task;
+use tokio::task::{self, JoinSet};
 use tokio_rustls::TlsAcceptor;
 use {ironrdp_dvc as dvc, ironrdp_rdpsnd as rdpsnd};
 
+use crate::audin::AudinServerFactory;
 use crate::clipboard::CliprdrServerFactory;
-use crate::display::{DisplayUpdate, RdpServerDisplay};
-use crate::encoder::UpdateEncoder;
+use crate::connection::{ChannelFactories, ConnectionHandlerFactory, ConnectionId, SharedHandlers};
+use crate::damage::DamageTracker;
+use crate::display::{BitmapUpdate, DisplayUpdate, RdpServerDisplay};
+use crate::encoder::{UpdateEncoder, UpdateFragmenter};
+use crate::gfx::{copy_fits, GfxHandle, GfxOutput, GfxServer};
 use crate::handler::RdpServerInputHandler;
-use crate::{builder, capabilities, time_warn, SoundServerFactory};
+use crate::rdpdr::{RdpdrServerFactory, RdpdrServerMessage};
+use crate::{builder, capabilities, time_warn, H264EncoderFactory, SoundServerFactory};
 
 #[derive(Clone)]
 pub struct RdpServerOptions {
     pub addr: SocketAddr,
     pub security: RdpServerSecurity,
     pub with_remote_fx: bool,
+    /// Whether display updates are sent through the graphics pipeline (MS-RDPEGFX) to the clients supporting it.
+    pub with_gfx: bool,
+    /// Whether bitmap updates are compared with the previous ones, to only send the 64x64 tiles that changed.
+    ///
+    /// This is useful for display handlers capturing the whole screen on each update, at the cost of keeping a copy of
+    /// the desktop.
+    pub with_damage_tracking: bool,
+    /// Whether vertical scrolls between bitmap updates are detected, implying damage tracking.
+    ///
+    /// Scrolled regions are sent as copies, with the graphics pipeline or ScrBlt orders, instead of being encoded again.
+    pub with_scroll_detection: bool,
+    /// Builds the H.264 encoder used for AVC420 graphics pipeline surface commands, if any.
+    pub h264: Option<Arc<dyn H264EncoderFactory>>,
+    /// Maximum number of concurrent connections, unlimited if `None`.
+    ///
+    /// Clients connecting while the limit is reached are refused, their connection being shut down right away.
+    pub max_connections: Option<usize>,
 }
 
 #[derive(Clone)]
@@ -177,15 +204,12 @@ impl DisplayControlHandler for DisplayControlBackend {
 /// ```
 pub struct RdpServer {
     opts: RdpServerOptions,
-    // FIXME: replace with a channel and poll/process the handler?
-    handler: Arc<Mutex<Box<dyn RdpServerInputHandler>>>,
-    display: Arc<Mutex<Box<dyn RdpServerDisplay>>>,
-    static_channels: StaticChannelSet,
-    sound_factory: Option<Box<dyn SoundServerFactory>>,
-    cliprdr_factory: Option<Box<dyn CliprdrServerFactory>>,
+    handlers: Box<dyn ConnectionHandlerFactory>,
+    factories: Rc<RefCell<ChannelFactories>>,
     ev_sender: mpsc::UnboundedSender<ServerEvent>,
     ev_receiver: Arc<Mutex<mpsc::UnboundedReceiver<ServerEvent>>>,
     creds: Option<Credentials>,
+    next_connection_id: u64,
 }
 
 #[derive(Debug)]
@@ -193,7 +217,10 @@ pub enum ServerEvent {
     Quit(String),
     Clipboard(ClipboardMessage),
     Rdpsnd(RdpsndServerMessage),
+    Rdpdr(RdpdrServerMessage),
     SetCredentials(Credentials),
+    /// Sends the event to the given connection only.
+    Connection(ConnectionId, Box<ServerEvent>),
 }
 
 pub trait ServerEventSender {
@@ -214,12 +241,34 @@ enum RunState {
 }
 
 impl RdpServer {
+    /// Creates a server whose connections share the same input and display handlers.
     pub fn new(
         opts: RdpServerOptions,
         handler: Box<dyn RdpServerInputHandler>,
         display: Box<dyn RdpServerDisplay>,
+        sound_factory: Option<Box<dyn SoundServerFactory>>,
+        cliprdr_factory: Option<Box<dyn CliprdrServerFactory>>,
+        audin_factory: Option<Box<dyn AudinServerFactory>>,
+        rdpdr_factory: Option<Box<dyn RdpdrServerFactory>>,
+    ) -> Self {
+        Self::new_with_handler_factory(
+            opts,
+            Box::new(SharedHandlers::new(handler, display)),
+            sound_factory,
+            cliprdr_factory,
+            audin_factory,
+            rdpdr_factory,
+        )
+    }
+
+    /// Creates a server building the handlers of each connection with `handlers`.
+    pub fn new_with_handler_factory(
+        opts: RdpServerOptions,
+        handlers: Box<dyn ConnectionHandlerFactory>,
         mut sound_factory: Option<Box<dyn SoundServerFactory>>,
         mut cliprdr_factory: Option<Box<dyn CliprdrServerFactory>>,
+        audin_factory: Option<Box<dyn AudinServerFactory>>,
+        mut rdpdr_factory: Option<Box<dyn RdpdrServerFactory>>,
     ) -> Self {
         let (ev_sender, ev_receiver) = ServerEvent::create_channel();
         if let Some(cliprdr) = cliprdr_factory.as_mut() {
@@ -228,16 +277,22 @@ impl RdpServer {
         if let Some(snd) = sound_factory.as_mut() {
             snd.set_sender(ev_sender.clone());
         }
+        if let Some(rdpdr) = rdpdr_factory.as_mut() {
+            rdpdr.set_sender(ev_sender.clone());
+        }
         Self {
             opts,
-            handler: Arc::new(Mutex::new(handler)),
-            display: Arc::new(Mutex::new(display)),
-            static_channels: StaticChannelSet::new(),
-            sound_factory,
-            cliprdr_factory,
+            handlers,
+            factories: Rc::new(RefCell::new(ChannelFactories {
+                sound: sound_factory,
+                cliprdr: cliprdr_factory,
+                audin: audin_factory,
+                rdpdr: rdpdr_factory,
+            })),
             ev_sender,
             ev_receiver: Arc::new(Mutex::new(ev_receiver)),
             creds: None,
+            next_connection_id: 0,
         }
     }
 
@@ -245,12 +300,208 @@ impl RdpServer {
         builder::RdpServerBuilder::new()
     }
 
+    /// Returns the sender of the server events.
+    ///
+    /// `Quit` stops the server and all its connections, and `SetCredentials` applies to the next connections.
+    /// `Connection` events are forwarded to the given connection, while the other channel events are forwarded to
+    /// the connection when there is a single one, and dropped otherwise.
+    ///
+    /// The channel backends of a connection send their events to this connection directly, see
+    /// [`ConnectionHandlerFactory::build_channel_factories`].
     pub fn event_sender(&self) -> &mpsc::UnboundedSender<ServerEvent> {
         &self.ev_sender
     }
 
-    fn attach_channels(&mut self, acceptor: &mut Acceptor) {
-        if let Some(cliprdr_factory) = self.cliprdr_factory.as_deref() {
+    fn new_connection(&mut self, peer: SocketAddr) -> Connection {
+        let id = ConnectionId(self.next_connection_id);
+        self.next_connection_id += 1;
+
+        let (ev_sender, ev_receiver) = ServerEvent::create_channel();
+
+        let factories = match self.handlers.build_channel_factories(id, peer) {
+            Some(factories) => Rc::new(RefCell::new(factories)),
+            None => Rc::clone(&self.factories),
+        };
+
+        Connection {
+            id,
+            opts: self.opts.clone(),
+            handler: Arc::new(Mutex::new(self.handlers.build_input_handler(id, peer))),
+            display: Arc::new(Mutex::new(self.handlers.build_display(id, peer))),
+            static_channels: StaticChannelSet::new(),
+            gfx: None,
+            damage: None,
+            size: DesktopSize { width: 0, height: 0 },
+            factories,
+            ev_sender,
+            ev_receiver: Arc::new(Mutex::new(ev_receiver)),
+            creds: self.creds.clone(),
+        }
+    }
+
+    /// Runs a single connection until the client disconnects.
+    ///
+    /// The server events are not processed, see [`Self::run`].
+    pub async fn run_connection(&mut self, stream: TcpStream) -> Result<()> {
+        let peer = stream.peer_addr()?;
+
+        self.new_connection(peer).run(stream).await
+    }
+
+    /// Listens for connections, each of which runs in its own task, until a `Quit` event is received.
+    ///
+    /// Connections are not `Send`: all of them run concurrently on the current thread, so that the display and input
+    /// handlers, as well as the channel backends, must not block it. Encoding is done on the blocking thread pool.
+    pub async fn run(&mut self) -> Result<()> {
+        let listener = TcpListener::bind(self.opts.addr).await?;
+
+        task::LocalSet::new().run_until(self.accept_loop(listener)).await
+    }
+
+    async fn accept_loop(&mut self, listener: TcpListener) -> Result<()> {
+        let mut tasks = JoinSet::new();
+        let mut connections = Connections::default();
+
+        debug!("Listening for connections");
+        loop {
+            let ev_receiver = Arc::clone(&self.ev_receiver);
+            let mut ev_receiver = ev_receiver.lock().await;
+            tokio::select! {
+                Some(event) = ev_receiver.recv() => {
+                    match event {
+                        ServerEvent::Quit(reason) => {
+                            debug!("Got quit event {reason}");
+                            connections.quit(&reason);
+                            break;
+                        }
+                        ServerEvent::SetCredentials(creds) => {
+                            self.set_credentials(Some(creds));
+                        }
+                        ev => connections.dispatch(ev),
+                    }
+                },
+                Ok((stream, peer)) = listener.accept() => {
+                    debug!(?peer, "Received connection");
+                    drop(ev_receiver);
+
+                    if let Some(max) = self.opts.max_connections.filter(|&max| connections.len() >= max) {
+                        warn!(?peer, max, "Connection limit reached, refusing connection");
+                        if let Err(error) = stream.into_std().and_then(|stream| stream.shutdown(Shutdown::Both)) {
+                            debug!(?error, ?peer, "Failed to shut down refused connection");
+                        }
+                        continue;
+                    }
+
+                    let connection = self.new_connection(peer);
+                    let id = connection.id;
+                    connections.add(id, connection.ev_sender.clone());
+
+                    tasks.spawn_local(async move {
+                        if let Err(error) = connection.run(stream).await {
+                            error!(?error, ?peer, %id, "Connection error");
+                        }
+                        debug!(?peer, %id, "Connection closed");
+                        id
+                    });
+                }
+                Some(result) = tasks.join_next() => {
+                    match result {
+                        Ok(id) => connections.remove(id),
+                        Err(error) => {
+                            error!(?error, "Connection task failed");
+                            connections.remove_closed();
+                        }
+                    }
+                }
+                else => break,
+            }
+        }
+
+        while tasks.join_next().await.is_some() {}
+
+        Ok(())
+    }
+
+    pub fn set_credentials(&mut self, creds: Option<Credentials>) {
+        debug!(?creds, "Changing credentials");
+        self.creds = creds
+    }
+}
+
+/// Event senders of the running connections, used to route the server events.
+#[derive(Default)]
+struct Connections {
+    senders: Vec<(ConnectionId, mpsc::UnboundedSender<ServerEvent>)>,
+}
+
+impl Connections {
+    fn len(&self) -> usize {
+        self.senders.len()
+    }
+
+    fn add(&mut self, id: ConnectionId, sender: mpsc::UnboundedSender<ServerEvent>) {
+        self.senders.push((id, sender));
+    }
+
+    fn remove(&mut self, id: ConnectionId) {
+        self.senders.retain(|(connection_id, _)| *connection_id != id);
+    }
+
+    fn remove_closed(&mut self) {
+        self.senders.retain(|(_, sender)| !sender.is_closed());
+    }
+
+    fn quit(&self, reason: &str) {
+        for (_, sender) in &self.senders {
+            let _ = sender.send(ServerEvent::Quit(reason.to_owned()));
+        }
+    }
+
+    /// Forwards `event` to the connection it is addressed to, or to the only connection.
+    fn dispatch(&self, event: ServerEvent) {
+        match (event, self.senders.as_slice()) {
+            (ServerEvent::Connection(id, event), senders) => {
+                match senders.iter().find(|(connection_id, _)| *connection_id == id) {
+                    Some((_, sender)) => {
+                        let _ = sender.send(*event);
+                    }
+                    None => debug!(%id, "Dropping event {:?} for a closed connection", event),
+                }
+            }
+            (event, [(_, sender)]) => {
+                let _ = sender.send(event);
+            }
+            (event, senders) => debug!(connections = senders.len(), "Dropping event {:?}", event),
+        }
+    }
+}
+
+/// State of a single connection, with its own channels and handlers.
+struct Connection {
+    id: ConnectionId,
+    opts: RdpServerOptions,
+    // FIXME: replace with a channel and poll/process the handler?
+    handler: Arc<Mutex<Box<dyn RdpServerInputHandler>>>,
+    display: Arc<Mutex<Box<dyn RdpServerDisplay>>>,
+    static_channels: StaticChannelSet,
+    gfx: Option<GfxHandle>,
+    damage: Option<DamageTracker>,
+    /// Size of the desktop, as last sent to the client.
+    size: DesktopSize,
+    factories: Rc<RefCell<ChannelFactories>>,
+    ev_sender: mpsc::UnboundedSender<ServerEvent>,
+    ev_receiver: Arc<Mutex<mpsc::UnboundedReceiver<ServerEvent>>>,
+    creds: Option<Credentials>,
+}
+
+impl Connection {
+    fn attach_channels(&mut self, acceptor: &mut Acceptor, size: DesktopSize) {
+        let mut factories = self.factories.borrow_mut();
+
+        // The factories may be shared with other connections: they are given the sender of this connection right
+        // before building its backends, without yielding in between.
+        if let Some(cliprdr_factory) = factories.cliprdr.as_deref_mut() {
+            cliprdr_factory.set_sender(self.ev_sender.clone());
             let backend = cliprdr_factory.build_cliprdr_backend();
 
             let cliprdr = CliprdrServer::new(backend);
@@ -258,29 +509,58 @@ impl RdpServer {
             acceptor.attach_static_channel(cliprdr);
         }
 
-        if let Some(factory) = self.sound_factory.as_deref() {
+        if let Some(factory) = factories.sound.as_deref_mut() {
+            factory.set_sender(self.ev_sender.clone());
             let backend = factory.build_backend();
 
             acceptor.attach_static_channel(RdpsndServer::new(backend));
         }
 
+        if let Some(factory) = factories.rdpdr.as_deref_mut() {
+            factory.set_sender(self.ev_sender.clone());
+            let backend = factory.build_backend(self.id);
+
+            acceptor.attach_static_channel(RdpdrServer::new(backend));
+        }
+
         let dcs_backend = DisplayControlBackend::new(Arc::clone(&self.display));
-        let dvc = dvc::DrdynvcServer::new()
+        let mut dvc = dvc::DrdynvcServer::new()
             .with_dynamic_channel(AInputHandler {
                 handler: Arc::clone(&self.handler),
             })
             .with_dynamic_channel(DisplayControlServer::new(Box::new(dcs_backend)));
+
+        if let Some(factory) = factories.audin.as_deref() {
+            let backend = factory.build_backend();
+
+            dvc = dvc.with_dynamic_channel(AudinServer::new(backend));
+        }
+
+        // The desktop is also kept for the clients not supporting ScrBlt orders, to send their copies as bitmaps. It is
+        // dropped once the client is known to support them, unless damage tracking is enabled.
+        self.damage = Some(
+            DamageTracker::new(size)
+                .with_damage_tracking(self.opts.with_damage_tracking || self.opts.with_scroll_detection)
+                .with_scroll_detection(self.opts.with_scroll_detection),
+        );
+        self.size = size;
+
+        self.gfx = self.opts.with_gfx.then(|| GfxHandle::new(size));
+        if let Some(gfx) = self.gfx.as_ref() {
+            dvc = dvc.with_dynamic_channel(GfxServer::new(gfx.clone()));
+        }
+
         acceptor.attach_static_channel(dvc);
     }
 
-    pub async fn run_connection(&mut self, stream: TcpStream) -> Result<()> {
+    async fn run(mut self, stream: TcpStream) -> Result<()> {
         let framed = TokioFramed::new(stream);
 
         let size = self.display.lock().await.size().await;
         let capabilities = capabilities::capabilities(&self.opts, size);
         let mut acceptor = Acceptor::new(self.opts.security.flag(), size, capabilities, self.creds.clone());
 
-        self.attach_channels(&mut acceptor);
+        self.attach_channels(&mut acceptor, size);
 
         let res = ironrdp_acceptor::accept_begin(framed, &mut acceptor)
             .await
@@ -330,50 +610,13 @@ impl RdpServer {
         Ok(())
     }
 
-    pub async fn run(&mut self) -> Result<()> {
-        let listener = TcpListener::bind(self.opts.addr).await?;
-
-        debug!("Listening for connections");
-        loop {
-            let ev_receiver = Arc::clone(&self.ev_receiver);
-            let mut ev_receiver = ev_receiver.lock().await;
-            tokio::select! {
-                Some(event) = ev_receiver.recv() => {
-                    match event {
-                        ServerEvent::Quit(reason) => {
-                            debug!("Got quit event {reason}");
-                            break;
-                        }
-                        ServerEvent::SetCredentials(creds) => {
-                            self.set_credentials(Some(creds));
-                        }
-                        ev => {
-                            debug!("Unexpected event {:?}", ev);
-                        }
-                    }
-                },
-                Ok((stream, peer)) = listener.accept() => {
-                    debug!(?peer, "Received connection");
-                    drop(ev_receiver);
-                    if let Err(error) = self.run_connection(stream).await {
-                        error!(?error, "Connection error");
-                    }
-                    self.static_channels = StaticChannelSet::new();
-                }
-                else => break,
-            }
-        }
-
-        Ok(())
-    }
-
-    pub fn get_svc_processor<T: SvcProcessor + 'static>(&mut self) -> Option<&mut T> {
+    fn get_svc_processor<T: SvcProcessor + 'static>(&mut self) -> Option<&mut T> {
         self.static_channels
             .get_by_type_mut::<T>()
             .and_then(|svc| svc.channel_processor_downcast_mut())
     }
 
-    pub fn get_channel_id_by_type<T: SvcProcessor + 'static>(&self) -> Option<StaticChannelId> {
+    fn get_channel_id_by_type<T: SvcProcessor + 'static>(&self) -> Option<StaticChannelId> {
         self.static_channels.get_channel_id_by_type::<T>()
     }
 
@@ -406,6 +649,7 @@ impl RdpServer {
         Ok(RunState::Continue)
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn dispatch_display_update(
         update: DisplayUpdate,
         writer: &mut impl FramedWrite,
@@ -413,16 +657,54 @@ impl RdpServer {
         io_channel_id: u16,
         buffer: &mut Vec<u8>,
         mut encoder: UpdateEncoder,
+        gfx: &mut Option<GfxOutput>,
+        damage: &mut Option<DamageTracker>,
+        size: DesktopSize,
     ) -> Result<(RunState, UpdateEncoder)> {
-        let mut fragmenter = match update {
+        let fragmenter = match update {
             DisplayUpdate::Bitmap(bitmap) => {
-                let (enc, res) = task::spawn_blocking(move || {
-                    let res = time_warn!("Encoding bitmap", 10, encoder.bitmap(bitmap).map(|r| r.into_owned()));
-                    (encoder, res)
-                })
-                .await?;
-                encoder = enc;
-                res.map(|r| encoder.fragmenter_from_owned(r))
+                let updates = match damage.take() {
+                    Some(mut tracker) => {
+                        let (tracker, updates) = task::spawn_blocking(move || {
+                            let updates = time_warn!("Tracking damage", 10, tracker.damage(bitmap));
+                            (tracker, updates)
+                        })
+                        .await?;
+                        *damage = Some(tracker);
+                        updates
+                    }
+                    None => vec![DisplayUpdate::Bitmap(bitmap)],
+                };
+
+                for update in updates {
+                    encoder = match update {
+                        DisplayUpdate::CopyRect { src, dst } => {
+                            Self::dispatch_copy_rect(src, dst, writer, user_channel_id, buffer, encoder, gfx, damage)
+                                .await?
+                        }
+                        DisplayUpdate::Bitmap(bitmap) => {
+                            Self::dispatch_bitmap(bitmap, writer, user_channel_id, buffer, encoder, gfx).await?
+                        }
+                        _ => encoder,
+                    };
+                }
+
+                return Ok((RunState::Continue, encoder));
+            }
+            DisplayUpdate::CopyRect { src, dst } => {
+                let valid = match damage.as_mut() {
+                    Some(tracker) => tracker.copy_rect(&src, dst),
+                    None => copy_fits(usize::from(size.width), usize::from(size.height), &src, dst),
+                };
+                if !valid {
+                    warn!(?src, ?dst, "Invalid copied region");
+                    return Ok((RunState::Continue, encoder));
+                }
+
+                let encoder =
+                    Self::dispatch_copy_rect(src, dst, writer, user_channel_id, buffer, encoder, gfx, damage).await?;
+
+                return Ok((RunState::Continue, encoder));
             }
             DisplayUpdate::PointerPosition(pos) => encoder.pointer_position(pos),
             DisplayUpdate::Resize(desktop_size) => {
@@ -450,6 +732,79 @@ impl RdpServer {
         }
         .context("error during update encoding")?;
 
+        Self::write_update(fragmenter, writer, buffer).await?;
+
+        Ok((RunState::Continue, encoder))
+    }
+
+    async fn dispatch_bitmap(
+        bitmap: BitmapUpdate,
+        writer: &mut impl FramedWrite,
+        user_channel_id: u16,
+        buffer: &mut Vec<u8>,
+        mut encoder: UpdateEncoder,
+        gfx: &mut Option<GfxOutput>,
+    ) -> Result<UpdateEncoder> {
+        if let Some(gfx) = gfx.as_mut().filter(|gfx| gfx.handles(&bitmap)) {
+            gfx.bitmap(bitmap, writer, user_channel_id).await?;
+            return Ok(encoder);
+        }
+
+        let (enc, res) = task::spawn_blocking(move || {
+            let res = time_warn!("Encoding bitmap", 10, encoder.bitmap(bitmap).map(|r| r.into_owned()));
+            (encoder, res)
+        })
+        .await?;
+        encoder = enc;
+        let fragmenter = res
+            .map(|r| encoder.fragmenter_from_owned(r))
+            .context("error during update encoding")?;
+
+        Self::write_update(fragmenter, writer, buffer).await?;
+
+        Ok(encoder)
+    }
+
+    /// Sends a copy of the `src` region, or the bitmap of its destination for clients not supporting copies.
+    #[allow(clippy::too_many_arguments)]
+    async fn dispatch_copy_rect(
+        src: InclusiveRectangle,
+        dst: Point16,
+        writer: &mut impl FramedWrite,
+        user_channel_id: u16,
+        buffer: &mut Vec<u8>,
+        mut encoder: UpdateEncoder,
+        gfx: &mut Option<GfxOutput>,
+        damage: &Option<DamageTracker>,
+    ) -> Result<UpdateEncoder> {
+        if let Some(gfx) = gfx.as_mut().filter(|gfx| gfx.handles_copy(&src, dst)) {
+            gfx.copy_rect(src, dst, writer, user_channel_id).await?;
+            return Ok(encoder);
+        }
+
+        if let Some(fragmenter) = encoder.copy_rect(&src, dst).context("error during update encoding")? {
+            Self::write_update(fragmenter, writer, buffer).await?;
+            return Ok(encoder);
+        }
+
+        match damage.as_ref().and_then(|tracker| tracker.copied_bitmap(&src, dst)) {
+            Some(bitmap) => Self::dispatch_bitmap(bitmap, writer, user_channel_id, buffer, encoder, gfx).await,
+            None => {
+                warn!(
+                    ?src,
+                    ?dst,
+                    "Copies are not supported by the client, dropping display update"
+                );
+                Ok(encoder)
+            }
+        }
+    }
+
+    async fn write_update(
+        mut fragmenter: UpdateFragmenter<'_>,
+        writer: &mut impl FramedWrite,
+        buffer: &mut Vec<u8>,
+    ) -> Result<()> {
         if fragmenter.size_hint() > buffer.len() {
             buffer.resize(fragmenter.size_hint(), 0);
         }
@@ -461,7 +816,7 @@ impl RdpServer {
                 .context("failed to write display update")?;
         }
 
-        Ok((RunState::Continue, encoder))
+        Ok(())
     }
 
     async fn dispatch_server_events(
@@ -483,6 +838,9 @@ impl RdpServer {
                 ServerEvent::SetCredentials(creds) => {
                     self.set_credentials(Some(creds));
                 }
+                ServerEvent::Connection(id, event) => {
+                    warn!(%id, "Dropping event {:?} addressed to a connection", event);
+                }
                 ServerEvent::Rdpsnd(s) => {
                     let Some(rdpsnd) = self.get_svc_processor::<RdpsndServer>() else {
                         warn!("No rdpsnd channel, dropping event");
@@ -497,6 +855,14 @@ impl RdpServer {
                             wave_limit -= 1;
                             rdpsnd.wave(data, ts)
                         }
+                        RdpsndServerMessage::PcmWave(samples, ts) => {
+                            if wave_limit == 0 {
+                                debug!("Dropping wave");
+                                continue;
+                            }
+                            wave_limit -= 1;
+                            rdpsnd.pcm_wave(&samples, ts)
+                        }
                         RdpsndServerMessage::Close => rdpsnd.close(),
                         RdpsndServerMessage::Error(error) => {
                             error!(?error, "Handling rdpsnd event");
@@ -510,6 +876,21 @@ impl RdpServer {
                     let data = server_encode_svc_messages(msgs.into(), channel_id, user_channel_id)?;
                     writer.write_all(&data).await?;
                 }
+                ServerEvent::Rdpdr(msg) => {
+                    let Some(rdpdr) = self.get_svc_processor::<RdpdrServer>() else {
+                        warn!("No rdpdr channel, dropping event");
+                        continue;
+                    };
+                    let msgs = match msg {
+                        RdpdrServerMessage::IoRequest(request) => rdpdr.io_request(request),
+                    }
+                    .context("failed to send rdpdr event")?;
+                    let channel_id = self
+                        .get_channel_id_by_type::<RdpdrServer>()
+                        .ok_or_else(|| anyhow!("SVC channel not found"))?;
+                    let data = server_encode_svc_messages(msgs.into(), channel_id, user_channel_id)?;
+                    writer.write_all(&data).await?;
+                }
                 ServerEvent::Clipboard(c) => {
                     let Some(cliprdr) = self.get_svc_processor::<CliprdrServer>() else {
                         warn!("No clipboard channel, dropping event");
@@ -519,6 +900,8 @@ impl RdpServer {
                         ClipboardMessage::SendInitiateCopy(formats) => cliprdr.initiate_copy(&formats),
                         ClipboardMessage::SendFormatData(data) => cliprdr.submit_format_data(data),
                         ClipboardMessage::SendInitiatePaste(format) => cliprdr.initiate_paste(format),
+                        ClipboardMessage::SendFileContentsRequest(request) => cliprdr.request_file_contents(request),
+                        ClipboardMessage::SendFileContentsResponse(response) => cliprdr.submit_file_contents(response),
                         ClipboardMessage::Error(error) => {
                             error!(?error, "Handling clipboard event");
                             continue;
@@ -544,6 +927,7 @@ impl RdpServer {
         io_channel_id: u16,
         user_channel_id: u16,
         mut encoder: UpdateEncoder,
+        mut gfx: Option<GfxOutput>,
     ) -> Result<RunState>
     where
         R: FramedRead,
@@ -551,6 +935,8 @@ impl RdpServer {
     {
         debug!("Starting client loop");
         let mut display_updates = self.display.lock().await.updates().await?;
+        let mut damage = self.damage.take();
+        let size = self.size;
         let mut writer = SharedWriter::new(writer);
         let mut display_writer = writer.clone();
         let mut event_writer = writer.clone();
@@ -575,7 +961,24 @@ impl RdpServer {
         let dispatch_display = async move {
             let mut buffer = vec![0u8; 4096];
             loop {
-                if let Some(update) = display_updates.next_update().await {
+                let update = match gfx.as_ref() {
+                    // The initial content of the graphics pipeline surfaces is sent as soon as they are created
+                    Some(output) => tokio::select! {
+                        update = display_updates.next_update() => Some(update),
+                        () = output.surface_created() => None,
+                    },
+                    None => Some(display_updates.next_update().await),
+                };
+                let Some(update) = update else {
+                    if let Some(output) = gfx.as_mut() {
+                        output
+                            .send_surface_content(&mut display_writer, user_channel_id)
+                            .await?;
+                    }
+                    continue;
+                };
+
+                if let Some(update) = update {
                     match Self::dispatch_display_update(
                         update,
                         &mut display_writer,
@@ -583,6 +986,9 @@ impl RdpServer {
                         io_channel_id,
                         &mut buffer,
                         encoder,
+                        &mut gfx,
+                        &mut damage,
+                        size,
                     )
                     .await?
                     {
@@ -670,6 +1076,7 @@ impl RdpServer {
 
         let mut rfxcodec = None;
         let mut surface_flags = CmdFlags::empty();
+        let mut scr_blt = false;
         for c in result.capabilities {
             match c {
                 CapabilitySet::General(c) => {
@@ -681,6 +1088,9 @@ impl RdpServer {
                 CapabilitySet::SurfaceCommands(c) => {
                     surface_flags = c.flags;
                 }
+                CapabilitySet::Order(mut c) => {
+                    scr_blt = c.get_support_flag(OrderSupportIndex::ScrBlt);
+                }
                 CapabilitySet::BitmapCodecs(BitmapCodecs(codecs)) => {
                     for codec in codecs {
                         match codec.property {
@@ -716,10 +1126,37 @@ impl RdpServer {
             }
         }
 
-        let encoder = UpdateEncoder::new(surface_flags, rfxcodec);
+        let encoder = UpdateEncoder::new(surface_flags, rfxcodec).with_scr_blt(scr_blt);
+        if scr_blt && self.damage.as_ref().is_some_and(|tracker| !tracker.tracks_damage()) {
+            self.damage = None;
+        }
+
+        let h264 = self
+            .opts
+            .h264
+            .as_deref()
+            .and_then(|factory| match factory.build_encoder() {
+                Ok(encoder) => Some(encoder),
+                Err(error) => {
+                    warn!(?error, "Failed to build H.264 encoder");
+                    None
+                }
+            });
+        let gfx = self
+            .gfx
+            .clone()
+            .zip(self.get_channel_id_by_type::<dvc::DrdynvcServer>())
+            .map(|(gfx, channel_id)| GfxOutput::new(gfx, channel_id, self.opts.with_remote_fx, h264));
 
         let state = self
-            .client_loop(reader, writer, result.io_channel_id, result.user_channel_id, encoder)
+            .client_loop(
+                reader,
+                writer,
+                result.io_channel_id,
+                result.user_channel_id,
+                encoder,
+                gfx,
+            )
             .await
             .context("client loop failure")?;
 
@@ -914,7 +1351,7 @@ impl RdpServer {
                 RunState::DeactivationReactivation { desktop_size } => {
                     other_pdus = Some(Vec::new());
                     acceptor = Acceptor::new_deactivation_reactivation(acceptor, desktop_size);
-                    self.attach_channels(&mut acceptor);
+                    self.attach_channels(&mut acceptor, desktop_size);
                     framed = unsplit_tokio_framed(reader, writer);
                     continue;
                 }
@@ -925,7 +1362,7 @@ impl RdpServer {
         Ok(())
     }
 
-    pub fn set_credentials(&mut self, creds: Option<Credentials>) {
+    fn set_credentials(&mut self, creds: Option<Credentials>) {
         debug!(?creds, "Changing credentials");
         self.creds = creds
     }
@@ -968,3 +1405,244 @@ impl<'a, W: FramedWrite> SharedWriter<'a, W> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ironrdp_rdpsnd::pdu::{AudioFormat, ClientAudioFormatPdu};
+    use ironrdp_rdpsnd::server::RdpsndServerHandler;
+    use tokio::io::AsyncReadExt as _;
+
+    use super::*;
+    use crate::{KeyboardEvent, MouseEvent, RdpServerDisplayUpdates, ServerEventSender};
+
+    struct TestInputHandler;
+
+    impl RdpServerInputHandler for TestInputHandler {
+        fn keyboard(&mut self, _: KeyboardEvent) {}
+
+        fn mouse(&mut self, _: MouseEvent) {}
+    }
+
+    struct TestDisplay;
+
+    #[async_trait::async_trait]
+    impl RdpServerDisplay for TestDisplay {
+        async fn size(&mut self) -> DesktopSize {
+            DesktopSize { width: 64, height: 64 }
+        }
+
+        async fn updates(&mut self) -> Result<Box<dyn RdpServerDisplayUpdates>> {
+            bail!("no display updates")
+        }
+    }
+
+    /// Reports the event sender of each built backend.
+    struct TestSoundFactory {
+        sender: Option<mpsc::UnboundedSender<ServerEvent>>,
+        built: mpsc::UnboundedSender<mpsc::UnboundedSender<ServerEvent>>,
+    }
+
+    impl ServerEventSender for TestSoundFactory {
+        fn set_sender(&mut self, sender: mpsc::UnboundedSender<ServerEvent>) {
+            self.sender = Some(sender);
+        }
+    }
+
+    impl SoundServerFactory for TestSoundFactory {
+        fn build_backend(&self) -> Box<dyn RdpsndServerHandler> {
+            let sender = self.sender.clone().expect("sender");
+            self.built.send(sender).expect("built");
+            Box::new(TestSound)
+        }
+    }
+
+    #[derive(Debug)]
+    struct TestSound;
+
+    impl RdpsndServerHandler for TestSound {
+        fn get_formats(&self) -> &[AudioFormat] {
+            &[]
+        }
+
+        fn start(&mut self, _: &ClientAudioFormatPdu) -> Option<u16> {
+            None
+        }
+
+        fn stop(&mut self) {}
+    }
+
+    /// Builds a sound factory of its own for each connection.
+    struct TestHandlerFactory {
+        built: mpsc::UnboundedSender<mpsc::UnboundedSender<ServerEvent>>,
+    }
+
+    impl ConnectionHandlerFactory for TestHandlerFactory {
+        fn build_input_handler(&self, _: ConnectionId, _: SocketAddr) -> Box<dyn RdpServerInputHandler> {
+            Box::new(TestInputHandler)
+        }
+
+        fn build_display(&self, _: ConnectionId, _: SocketAddr) -> Box<dyn RdpServerDisplay> {
+            Box::new(TestDisplay)
+        }
+
+        fn build_channel_factories(&self, _: ConnectionId, _: SocketAddr) -> Option<ChannelFactories> {
+            Some(ChannelFactories {
+                sound: Some(Box::new(TestSoundFactory {
+                    sender: None,
+                    built: self.built.clone(),
+                })),
+                ..Default::default()
+            })
+        }
+    }
+
+    fn options(max_connections: Option<usize>) -> RdpServerOptions {
+        RdpServerOptions {
+            addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+            security: RdpServerSecurity::None,
+            with_remote_fx: false,
+            with_gfx: false,
+            with_damage_tracking: false,
+            with_scroll_detection: false,
+            h264: None,
+            max_connections,
+        }
+    }
+
+    /// Returns a server sharing its sound factory between connections, and the event senders of the built backends.
+    fn shared_server(
+        max_connections: Option<usize>,
+    ) -> (RdpServer, mpsc::UnboundedReceiver<mpsc::UnboundedSender<ServerEvent>>) {
+        let (built, backends) = mpsc::unbounded_channel();
+        let server = RdpServer::new(
+            options(max_connections),
+            Box::new(TestInputHandler),
+            Box::new(TestDisplay),
+            Some(Box::new(TestSoundFactory { sender: None, built })),
+            None,
+            None,
+            None,
+        );
+        (server, backends)
+    }
+
+    /// Runs the accept loop of `server` until `clients` is done, then stops it.
+    async fn serve<F>(mut server: RdpServer, clients: impl FnOnce(SocketAddr) -> F)
+    where
+        F: std::future::Future<Output = ()>,
+    {
+        let listener = TcpListener::bind(server.opts.addr).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let events = server.event_sender().clone();
+
+        let clients = async move {
+            clients(addr).await;
+            events.send(ServerEvent::Quit("test".to_owned())).unwrap();
+        };
+
+        let (result, ()) = task::LocalSet::new()
+            .run_until(async { tokio::join!(server.accept_loop(listener), clients) })
+            .await;
+        result.unwrap();
+    }
+
+    /// Returns whether the server closed `stream`, waiting for it.
+    async fn is_closed(stream: &mut TcpStream) -> bool {
+        matches!(stream.read(&mut [0; 1]).await, Ok(0) | Err(_))
+    }
+
+    async fn connect_two(addr: SocketAddr, backends: &mut mpsc::UnboundedReceiver<mpsc::UnboundedSender<ServerEvent>>) {
+        let first = TcpStream::connect(addr).await.unwrap();
+        let first_sender = backends.recv().await.unwrap();
+
+        let second = TcpStream::connect(addr).await.unwrap();
+        let second_sender = backends.recv().await.unwrap();
+
+        assert!(!first_sender.same_channel(&second_sender));
+        assert!(!first_sender.is_closed());
+        assert!(!second_sender.is_closed());
+
+        drop((first, second));
+    }
+
+    #[tokio::test]
+    async fn shared_factories_build_backends_with_the_sender_of_their_connection() {
+        let (server, mut backends) = shared_server(None);
+
+        serve(server, |addr| async move { connect_two(addr, &mut backends).await }).await;
+    }
+
+    #[tokio::test]
+    async fn connection_factories_build_backends_with_the_sender_of_their_connection() {
+        let (built, mut backends) = mpsc::unbounded_channel();
+        let server = RdpServer::new_with_handler_factory(
+            options(None),
+            Box::new(TestHandlerFactory { built }),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        serve(server, |addr| async move { connect_two(addr, &mut backends).await }).await;
+    }
+
+    #[tokio::test]
+    async fn connections_over_the_limit_are_closed() {
+        let (server, mut backends) = shared_server(Some(1));
+
+        serve(server, |addr| async move {
+            let first = TcpStream::connect(addr).await.unwrap();
+            backends.recv().await.unwrap();
+
+            let mut second = TcpStream::connect(addr).await.unwrap();
+            assert!(is_closed(&mut second).await);
+            assert!(backends.try_recv().is_err());
+
+            // The next client is accepted once the first connection is closed
+            drop(first);
+            loop {
+                let mut third = TcpStream::connect(addr).await.unwrap();
+                tokio::select! {
+                    _ = backends.recv() => break,
+                    closed = is_closed(&mut third) => assert!(closed),
+                }
+            }
+        })
+        .await;
+    }
+
+    #[test]
+    fn events_are_routed_by_connection_id() {
+        let mut connections = Connections::default();
+        let (first, mut first_events) = ServerEvent::create_channel();
+        let (second, mut second_events) = ServerEvent::create_channel();
+        let close = || ServerEvent::Rdpsnd(RdpsndServerMessage::Close);
+
+        // Events are forwarded to the only connection
+        connections.add(ConnectionId(0), first);
+        connections.dispatch(close());
+        assert!(matches!(
+            first_events.try_recv(),
+            Ok(ServerEvent::Rdpsnd(RdpsndServerMessage::Close))
+        ));
+
+        // With several connections, only the events addressed to one of them are forwarded
+        connections.add(ConnectionId(1), second);
+        connections.dispatch(close());
+        connections.dispatch(ServerEvent::Connection(ConnectionId(1), Box::new(close())));
+        assert!(first_events.try_recv().is_err());
+        assert!(matches!(
+            second_events.try_recv(),
+            Ok(ServerEvent::Rdpsnd(RdpsndServerMessage::Close))
+        ));
+        assert!(second_events.try_recv().is_err());
+
+        connections.remove(ConnectionId(1));
+        connections.dispatch(ServerEvent::Connection(ConnectionId(1), Box::new(close())));
+        assert!(second_events.try_recv().is_err());
+
+        connections.quit("test");
+        assert!(matches!(first_events.try_recv(), Ok(ServerEvent::Quit(_))));
+    }
+}
@@ -1,3 +1,5 @@
+/// Audio codecs for the formats returned by [`RdpsndServerHandler::get_formats`].
+pub use ironrdp_rdpsnd::codec as audio_codec;
 pub use ironrdp_rdpsnd::server::{RdpsndServerHandler, RdpsndServerMessage};
 
 use crate::ServerEventSender;
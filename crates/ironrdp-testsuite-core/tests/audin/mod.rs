@@ -0,0 +1,211 @@
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use ironrdp_audin::client::{AudinClient, AudinClientHandler};
+use ironrdp_audin::pdu;
+use ironrdp_audin::server::{AudinServer, AudinServerHandler};
+use ironrdp_audin::AudinError;
+use ironrdp_core::encode_vec;
+use ironrdp_dvc::{DvcMessage, DvcProcessor};
+use ironrdp_testsuite_core::encode_decode_test;
+
+fn pcm_format() -> pdu::AudioFormat {
+    pdu::AudioFormat {
+        format: pdu::WaveFormat::PCM,
+        n_channels: 2,
+        n_samples_per_sec: 44100,
+        n_avg_bytes_per_sec: 176400,
+        n_block_align: 4,
+        bits_per_sample: 16,
+        data: None,
+    }
+}
+
+encode_decode_test! {
+    server_version: pdu::ServerAudioInputPdu::Version(pdu::VersionPdu {
+        version: pdu::Version::V2,
+    }),
+    [
+        0x01, 0x02, 0x00, 0x00, 0x00,
+    ];
+    client_formats: pdu::ClientAudioInputPdu::Formats(pdu::FormatsPdu {
+        formats: vec![pcm_format()],
+    }),
+    [
+        0x02, 0x01, 0x00, 0x00, 0x00, 0x1b, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x02, 0x00, 0x44, 0xac, 0x00, 0x00, 0x10, 0xb1, 0x02, 0x00, 0x04, 0x00, 0x10, 0x00, 0x00, 0x00,
+    ];
+    open: pdu::ServerAudioInputPdu::Open(pdu::OpenPdu {
+        frames_per_packet: 882,
+        initial_format: 0,
+        format: pcm_format(),
+    }),
+    [
+        0x03, 0x72, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x02, 0x00, 0x44, 0xac, 0x00, 0x00, 0x10, 0xb1, 0x02, 0x00, 0x04, 0x00, 0x10, 0x00, 0x00, 0x00,
+    ];
+    open_reply: pdu::ClientAudioInputPdu::OpenReply(pdu::OpenReplyPdu {
+        result: pdu::E_FAIL,
+    }),
+    [
+        0x04, 0x05, 0x40, 0x00, 0x80,
+    ];
+    data_incoming: pdu::ClientAudioInputPdu::DataIncoming,
+    [
+        0x05,
+    ];
+    data: pdu::ClientAudioInputPdu::Data(pdu::DataPdu {
+        data: Cow::Borrowed(&[0x01, 0x02, 0x03, 0x04]),
+    }),
+    [
+        0x06, 0x01, 0x02, 0x03, 0x04,
+    ];
+    format_change: pdu::ServerAudioInputPdu::FormatChange(pdu::FormatChangePdu {
+        new_format: 1,
+    }),
+    [
+        0x07, 0x01, 0x00, 0x00, 0x00,
+    ];
+}
+
+#[derive(Debug)]
+struct TestCapture {
+    opened: Arc<Mutex<Option<(pdu::AudioFormat, u32)>>>,
+}
+
+impl AudinClientHandler for TestCapture {
+    fn supported_formats(&self, server_formats: &[pdu::AudioFormat]) -> Vec<pdu::AudioFormat> {
+        server_formats
+            .iter()
+            .filter(|format| format.format == pdu::WaveFormat::PCM)
+            .cloned()
+            .collect()
+    }
+
+    fn open(&mut self, format: &pdu::AudioFormat, frames_per_packet: u32) -> Result<(), Box<dyn AudinError>> {
+        *self.opened.lock().unwrap() = Some((format.clone(), frames_per_packet));
+        Ok(())
+    }
+
+    fn set_format(&mut self, _format: &pdu::AudioFormat) -> Result<(), Box<dyn AudinError>> {
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        *self.opened.lock().unwrap() = None;
+    }
+}
+
+#[derive(Debug)]
+struct TestSink {
+    formats: Vec<pdu::AudioFormat>,
+    received: Arc<Mutex<Vec<u8>>>,
+    stops: Arc<AtomicUsize>,
+}
+
+impl AudinServerHandler for TestSink {
+    fn get_formats(&self) -> &[pdu::AudioFormat] {
+        &self.formats
+    }
+
+    fn start(&mut self, client_formats: &[pdu::AudioFormat]) -> Option<u32> {
+        assert_eq!(client_formats, [pcm_format()]);
+        Some(0)
+    }
+
+    fn data(&mut self, format: &pdu::AudioFormat, data: &[u8]) {
+        assert_eq!(*format, pcm_format());
+        self.received.lock().unwrap().extend_from_slice(data);
+    }
+
+    fn stop(&mut self) {
+        self.stops.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn exchange(to: &mut dyn DvcProcessor, messages: Vec<DvcMessage>) -> Vec<DvcMessage> {
+    messages
+        .into_iter()
+        .flat_map(|message| {
+            let payload = encode_vec(message.as_ref()).unwrap();
+            to.process(1, &payload).unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn client_server_handshake() {
+    let opened = Arc::new(Mutex::new(None));
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let stops = Arc::new(AtomicUsize::new(0));
+
+    let mut client = AudinClient::new(Box::new(TestCapture {
+        opened: Arc::clone(&opened),
+    }));
+    let mut server = AudinServer::new(Box::new(TestSink {
+        formats: vec![
+            pdu::AudioFormat {
+                format: pdu::WaveFormat::ALAW,
+                n_channels: 1,
+                n_samples_per_sec: 8000,
+                n_avg_bytes_per_sec: 8000,
+                n_block_align: 1,
+                bits_per_sample: 8,
+                data: None,
+            },
+            pcm_format(),
+        ],
+        received: Arc::clone(&received),
+        stops: Arc::clone(&stops),
+    }));
+
+    assert!(client.start(1).unwrap().is_empty());
+    let mut to_client = server.start(1).unwrap();
+
+    // Version, formats and open round trips.
+    while !to_client.is_empty() {
+        let to_server = exchange(&mut client, to_client);
+        to_client = exchange(&mut server, to_server);
+    }
+
+    assert!(client.is_opened());
+    assert_eq!(*opened.lock().unwrap(), Some((pcm_format(), 882)));
+    assert_eq!(client.current_format(), Some(&pcm_format()));
+
+    let svc_messages = client.encode_data(vec![0xAA, 0xBB]).unwrap();
+    assert_eq!(svc_messages.len(), 2);
+
+    let to_server: Vec<DvcMessage> = vec![
+        Box::new(pdu::ClientAudioInputPdu::DataIncoming),
+        Box::new(pdu::ClientAudioInputPdu::Data(pdu::DataPdu {
+            data: Cow::Owned(vec![0xAA, 0xBB]),
+        })),
+    ];
+    assert!(exchange(&mut server, to_server).is_empty());
+    assert_eq!(*received.lock().unwrap(), [0xAA, 0xBB]);
+
+    client.close(1);
+    assert!(opened.lock().unwrap().is_none());
+
+    // The handler is stopped once, whether the channel is closed or dropped.
+    server.close(1);
+    drop(server);
+    assert_eq!(stops.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn server_not_started_does_not_stop_handler() {
+    let stops = Arc::new(AtomicUsize::new(0));
+
+    let mut server = AudinServer::new(Box::new(TestSink {
+        formats: vec![pcm_format()],
+        received: Arc::new(Mutex::new(Vec::new())),
+        stops: Arc::clone(&stops),
+    }));
+    server.start(1).unwrap();
+    server.close(1);
+    drop(server);
+
+    assert_eq!(stops.load(Ordering::SeqCst), 0);
+}
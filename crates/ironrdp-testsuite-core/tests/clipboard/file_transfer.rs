@@ -0,0 +1,300 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ironrdp_cliprdr::file_transfer::{FileDownload, FileUpload, LocalFileList, TransferProgress, MAX_DIRECTORY_DEPTH};
+use ironrdp_cliprdr::pdu::{
+    ClipboardFileAttributes, FileContentsFlags, FileContentsRequest, FileContentsResponse, FileDescriptor, LockDataId,
+    PackedFileList,
+};
+
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("ironrdp-cliprdr-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Creates `docs/a.txt`, `docs/sub/b.bin` and `top.txt` in `root`.
+fn copied_files(root: &Path) -> Vec<PathBuf> {
+    fs::create_dir_all(root.join("docs/sub")).unwrap();
+    fs::write(root.join("docs/a.txt"), b"hello").unwrap();
+    fs::write(
+        root.join("docs/sub/b.bin"),
+        (0..1000u32).map(|i| u8::try_from(i % 251).unwrap()).collect::<Vec<_>>(),
+    )
+    .unwrap();
+    fs::write(root.join("top.txt"), b"").unwrap();
+    vec![root.join("docs"), root.join("top.txt")]
+}
+
+fn request(index: u32, flags: FileContentsFlags, position: u64, requested_size: u32) -> FileContentsRequest {
+    FileContentsRequest {
+        stream_id: 7,
+        index,
+        flags,
+        position,
+        requested_size,
+        data_id: None,
+    }
+}
+
+#[test]
+fn local_file_list_describes_directories_recursively() {
+    let root = TempDir::new("list");
+    let list = LocalFileList::from_paths(copied_files(&root.0)).unwrap();
+
+    let names = list
+        .files()
+        .iter()
+        .map(|file| file.descriptor.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        ["docs", "docs\\a.txt", "docs\\sub", "docs\\sub\\b.bin", "top.txt"]
+    );
+
+    let directory = &list.files()[2].descriptor;
+    assert_eq!(directory.attributes, Some(ClipboardFileAttributes::DIRECTORY));
+    assert_eq!(list.files()[3].descriptor.file_size, Some(1000));
+    assert_eq!(list.files()[3].path, root.0.join("docs/sub/b.bin"));
+
+    let response = list.format_data().unwrap();
+    assert_eq!(response.to_file_list().unwrap(), list.to_packed());
+}
+
+#[cfg(unix)]
+#[test]
+fn local_file_list_does_not_follow_links_to_directories() {
+    let root = TempDir::new("links");
+    fs::create_dir_all(root.0.join("docs")).unwrap();
+    fs::write(root.0.join("docs/a.txt"), b"hello").unwrap();
+    std::os::unix::fs::symlink(&root.0, root.0.join("docs/loop")).unwrap();
+    std::os::unix::fs::symlink(root.0.join("docs/a.txt"), root.0.join("docs/b.txt")).unwrap();
+
+    let list = LocalFileList::from_paths([root.0.join("docs")]).unwrap();
+
+    let names = list
+        .files()
+        .iter()
+        .map(|file| file.descriptor.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["docs", "docs\\a.txt", "docs\\b.txt"]);
+    assert_eq!(list.files()[2].descriptor.file_size, Some(5));
+}
+
+#[test]
+fn local_file_list_rejects_directories_nested_too_deeply() {
+    let root = TempDir::new("deep");
+    let mut path = root.0.join("top");
+    for _ in 0..MAX_DIRECTORY_DEPTH {
+        path.push("d");
+    }
+    fs::create_dir_all(&path).unwrap();
+    assert!(LocalFileList::from_paths([root.0.join("top")]).is_ok());
+
+    fs::create_dir(path.join("d")).unwrap();
+    let error = LocalFileList::from_paths([root.0.join("top")]).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn upload_answers_size_and_range_requests() {
+    let root = TempDir::new("upload");
+    let mut upload = FileUpload::new();
+    upload.set_files(LocalFileList::from_paths(copied_files(&root.0)).unwrap());
+
+    let response = upload.handle_request(&request(1, FileContentsFlags::SIZE, 0, 8));
+    assert_eq!(response.stream_id(), 7);
+    assert_eq!(response.data_as_size().unwrap(), 5);
+
+    let response = upload.handle_request(&request(1, FileContentsFlags::DATA, 1, 3));
+    assert_eq!(response.data(), b"ell");
+
+    let response = upload.handle_request(&request(1, FileContentsFlags::DATA, 4, 100));
+    assert_eq!(response.data(), b"o");
+    assert_eq!(upload.progress().transferred_bytes, 4);
+    assert_eq!(upload.progress().completed_files, 1);
+    assert_eq!(upload.progress().total_bytes, 1005);
+
+    // Directories have no contents, and indices must be in range.
+    assert!(upload
+        .handle_request(&request(0, FileContentsFlags::SIZE, 0, 8))
+        .is_error());
+    assert!(upload
+        .handle_request(&request(5, FileContentsFlags::SIZE, 0, 8))
+        .is_error());
+}
+
+#[test]
+fn upload_serves_locked_files() {
+    let root = TempDir::new("lock");
+    let mut upload = FileUpload::new();
+    fs::write(root.0.join("a.txt"), b"first").unwrap();
+    upload.set_files(LocalFileList::from_paths([root.0.join("a.txt")]).unwrap());
+
+    upload.lock(&LockDataId(3));
+    upload.clear();
+
+    let mut locked = request(0, FileContentsFlags::DATA, 0, 100);
+    locked.data_id = Some(3);
+    assert_eq!(upload.handle_request(&locked).data(), b"first");
+    assert!(upload
+        .handle_request(&request(0, FileContentsFlags::DATA, 0, 100))
+        .is_error());
+
+    upload.unlock(&LockDataId(3));
+    assert!(upload.handle_request(&locked).is_error());
+}
+
+#[test]
+fn upload_cancel_fails_requests() {
+    let root = TempDir::new("cancel");
+    let mut upload = FileUpload::new();
+    upload.set_files(LocalFileList::from_paths(copied_files(&root.0)).unwrap());
+
+    upload.cancel();
+
+    assert!(upload
+        .handle_request(&request(1, FileContentsFlags::SIZE, 0, 8))
+        .is_error());
+}
+
+/// Runs `download` against `upload` until it is finished.
+fn transfer(upload: &mut FileUpload, download: &mut FileDownload) -> usize {
+    let mut requests = Vec::new();
+    let mut next = download.start().unwrap();
+    while let Some(request) = next {
+        let response = upload.handle_request(&request);
+        next = download.handle_response(&response).unwrap();
+        requests.push(request);
+    }
+    requests.len()
+}
+
+#[test]
+fn download_copies_remote_files() {
+    let source = TempDir::new("download-source");
+    let destination = TempDir::new("download-destination");
+
+    let list = LocalFileList::from_paths(copied_files(&source.0)).unwrap();
+    let packed = list.to_packed();
+    let mut upload = FileUpload::new();
+    upload.set_files(list);
+
+    let mut download = FileDownload::new(&destination.0, packed).with_chunk_size(128);
+    let requests = transfer(&mut upload, &mut download);
+
+    // One request for `a.txt`, eight for `b.bin`, none for the empty `top.txt`.
+    assert_eq!(requests, 9);
+    assert!(download.is_finished());
+    assert_eq!(
+        download.progress(),
+        TransferProgress {
+            transferred_bytes: 1005,
+            total_bytes: 1005,
+            completed_files: 5,
+            total_files: 5,
+        }
+    );
+    assert_eq!(download.paths().len(), 5);
+    assert_eq!(fs::read(destination.0.join("docs/a.txt")).unwrap(), b"hello");
+    assert_eq!(
+        fs::read(destination.0.join("docs/sub/b.bin")).unwrap(),
+        fs::read(source.0.join("docs/sub/b.bin")).unwrap()
+    );
+    assert!(destination.0.join("top.txt").is_file());
+}
+
+#[test]
+fn download_requests_unknown_sizes() {
+    let source = TempDir::new("size-source");
+    let destination = TempDir::new("size-destination");
+
+    let list = LocalFileList::from_paths(copied_files(&source.0)).unwrap();
+    let mut packed = list.to_packed();
+    for file in &mut packed.files {
+        file.file_size = None;
+    }
+    let mut upload = FileUpload::new();
+    upload.set_files(list);
+
+    let mut download = FileDownload::new(&destination.0, packed).with_clip_data_id(1);
+    upload.lock(&download.clip_data_id().unwrap());
+
+    let first = download.start().unwrap().unwrap();
+    assert_eq!(first.flags, FileContentsFlags::SIZE);
+    assert_eq!(first.index, 1);
+    assert_eq!(first.data_id, Some(1));
+
+    let response = upload.handle_request(&first);
+    let mut next = download.handle_response(&response).unwrap();
+    while let Some(request) = next {
+        let response = upload.handle_request(&request);
+        next = download.handle_response(&response).unwrap();
+    }
+
+    assert!(download.is_finished());
+    assert_eq!(download.progress().total_bytes, 1005);
+    assert_eq!(fs::read(destination.0.join("docs/a.txt")).unwrap(), b"hello");
+}
+
+#[test]
+fn download_refuses_names_escaping_the_directory() {
+    let destination = TempDir::new("escape");
+
+    for name in ["..\\evil.txt", "docs\\..\\..\\evil.txt", "C:\\evil.txt", ""] {
+        let packed = PackedFileList {
+            files: vec![FileDescriptor {
+                attributes: None,
+                last_write_time: None,
+                file_size: Some(1),
+                name: name.to_owned(),
+            }],
+        };
+
+        let mut download = FileDownload::new(&destination.0, packed);
+        download.start().unwrap_err();
+    }
+}
+
+#[test]
+fn download_error_response_removes_partial_file() {
+    let destination = TempDir::new("error");
+    let packed = PackedFileList {
+        files: vec![FileDescriptor {
+            attributes: None,
+            last_write_time: None,
+            file_size: Some(10),
+            name: "partial.txt".to_owned(),
+        }],
+    };
+
+    let mut download = FileDownload::new(&destination.0, packed).with_chunk_size(4);
+    let request = download.start().unwrap().unwrap();
+    let request = download
+        .handle_response(&FileContentsResponse::new_data_response(
+            request.stream_id,
+            &b"abcd"[..],
+        ))
+        .unwrap()
+        .unwrap();
+    assert_eq!(request.position, 4);
+    assert!(destination.0.join("partial.txt").exists());
+
+    download
+        .handle_response(&FileContentsResponse::new_error(request.stream_id))
+        .unwrap_err();
+
+    assert!(!destination.0.join("partial.txt").exists());
+    assert!(!download.is_finished());
+}
@@ -1,5 +1,16 @@
+use std::path::Path;
+
+use ironrdp_cliprdr::pdu::{FileDescriptor, PackedFileList};
 use ironrdp_cliprdr_format::bitmap::{dib_to_png, dibv5_to_png, png_to_cf_dib, png_to_cf_dibv5};
+use ironrdp_cliprdr_format::file_list::{
+    file_list_to_uri_list, paths_to_uri_list, uri_list_to_file_list, uri_list_to_paths,
+};
 use ironrdp_cliprdr_format::html::{cf_html_to_plain_html, plain_html_to_cf_html};
+use ironrdp_cliprdr_format::rtf::{plain_text_to_rtf, rtf_to_plain_text};
+use ironrdp_cliprdr_format::text::{
+    cf_text_to_string, cf_unicodetext_to_string, lcid_from_cf_locale, lcid_to_cf_locale, string_to_cf_text,
+    string_to_cf_unicodetext, CodePage,
+};
 
 #[test]
 fn dib_to_png_conversion_1() {
@@ -52,3 +63,331 @@ fn test_cf_html_to_text() {
     let roundtrip_html_text = cf_html_to_plain_html(&cf_html).unwrap();
     assert_eq!(actual, roundtrip_html_text);
 }
+
+#[test]
+fn cf_unicodetext_normalization() {
+    let data = string_to_cf_unicodetext("hello\nworld\r\n!\0ignored");
+    let expected = "hello\r\nworld\r\n!\0"
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect::<Vec<_>>();
+    assert_eq!(data, expected);
+
+    // Trailing padding after the terminator is ignored
+    let mut padded = data;
+    padded.extend_from_slice(&[0xFF; 5]);
+    assert_eq!(cf_unicodetext_to_string(&padded), "hello\nworld\n!");
+
+    // Not terminated, and lone carriage return
+    assert_eq!(cf_unicodetext_to_string(&string_to_cf_unicodetext("a")[..2]), "a");
+    assert_eq!(cf_unicodetext_to_string(b"a\0\r\0b\0"), "a\nb");
+}
+
+#[test]
+fn cf_text_code_pages() {
+    let lcid = lcid_from_cf_locale(&lcid_to_cf_locale(0x0419)).unwrap();
+    assert_eq!(lcid, 0x0419);
+    assert_eq!(CodePage::ansi_for_locale(lcid), CodePage(1251));
+    assert_eq!(CodePage::oem_for_locale(lcid), CodePage(866));
+    assert_eq!(CodePage::oem_for_locale(0x0409), CodePage::IBM437);
+    assert_eq!(CodePage::oem_for_locale(0x0809), CodePage::IBM850);
+    assert!(lcid_from_cf_locale(&[0x09, 0x04]).is_err());
+
+    let data = string_to_cf_text("Привет\nмир", CodePage(1251)).unwrap();
+    assert_eq!(data, b"\xCF\xF0\xE8\xE2\xE5\xF2\r\n\xEC\xE8\xF0\0");
+    assert_eq!(cf_text_to_string(&data, CodePage(1251)).unwrap(), "Привет\nмир");
+
+    assert_eq!(
+        string_to_cf_text("€ ½ ✓", CodePage::WINDOWS_1252).unwrap(),
+        b"\x80 \xBD ?\0"
+    );
+    assert_eq!(cf_text_to_string(b"\xC9t\xE9\0\xFF", CodePage::IBM850).unwrap(), "╔tÚ");
+    assert_eq!(cf_text_to_string("日本".as_bytes(), CodePage::UTF8).unwrap(), "日本");
+
+    assert!(cf_text_to_string(b"text", CodePage(932)).is_err());
+    assert!(!CodePage(932).is_supported());
+}
+
+#[test]
+fn rtf_to_text() {
+    let rtf = br#"{\rtf1\ansi\ansicpg1252\deff0{\fonttbl{\f0\fswiss Arial;}}{\colortbl;\red255\green0\blue0;}
+{\*\generator Riched20 10.0.19041}\viewkind4\uc1\pard\f0\fs22 Hello, \b world\b0 !\par
+Caf\'e9 \{braces\} and a back\\slash\tab tab\par
+{\field{\*\fldinst HYPERLINK "https://example.com"}{\fldrslt link}}\par
+\u8364?\u-10179?\u-8694? done}
+trailing"#;
+
+    assert_eq!(
+        rtf_to_plain_text(rtf).unwrap(),
+        "Hello, world!\nCafé {braces} and a back\\slash\ttab\nlink\n€😊 done"
+    );
+
+    // Code page from the header
+    assert_eq!(
+        rtf_to_plain_text(br"{\rtf1\ansi\ansicpg1251 \'cf\'f0\'e8}").unwrap(),
+        "При"
+    );
+
+    // Fallback characters of `\uN` are skipped, as many as set with `\ucN`
+    assert_eq!(rtf_to_plain_text(br"{\rtf1\uc2\u233\'e9\'e9!}").unwrap(), "é!");
+
+    assert!(rtf_to_plain_text(b"hello").is_err());
+}
+
+#[test]
+fn text_to_rtf_roundtrip() {
+    let text = "Hello {world}\\\n\tCafé 😊";
+    let rtf = plain_text_to_rtf(text);
+    assert!(rtf.starts_with(r"{\rtf1"));
+    assert_eq!(rtf_to_plain_text(rtf.as_bytes()).unwrap(), text);
+}
+
+#[test]
+fn uri_list_conversions() {
+    let uri_list = b"# comment\r\nfile:///tmp/a%20file.txt\r\nfile://host/tmp/dir\r\nhttps://example.com\r\n";
+    let paths = uri_list_to_paths(uri_list);
+    assert_eq!(paths, [Path::new("/tmp/a file.txt"), Path::new("/tmp/dir")]);
+    assert_eq!(
+        paths_to_uri_list(&paths),
+        "file:///tmp/a%20file.txt\r\nfile:///tmp/dir\r\n"
+    );
+
+    let list = PackedFileList {
+        files: ["dir", "dir\\nested.txt", "ü.txt"]
+            .into_iter()
+            .map(|name| FileDescriptor {
+                attributes: None,
+                last_write_time: None,
+                file_size: None,
+                name: name.to_owned(),
+            })
+            .collect(),
+    };
+    assert_eq!(
+        file_list_to_uri_list(&list, Path::new("/tmp/paste")).unwrap(),
+        "file:///tmp/paste/dir\r\nfile:///tmp/paste/%C3%BC.txt\r\n"
+    );
+
+    let escaping = PackedFileList {
+        files: vec![FileDescriptor {
+            attributes: None,
+            last_write_time: None,
+            file_size: None,
+            name: "..".to_owned(),
+        }],
+    };
+    assert!(file_list_to_uri_list(&escaping, Path::new("/tmp/paste")).is_err());
+}
+
+#[test]
+fn uri_list_to_descriptors() {
+    let directory = std::env::temp_dir().join(format!("ironrdp-uri-list-{}", std::process::id()));
+    std::fs::create_dir_all(directory.join("dir")).unwrap();
+    std::fs::write(directory.join("dir").join("file.txt"), b"hello").unwrap();
+
+    let files = uri_list_to_file_list(paths_to_uri_list([directory.join("dir")]).as_bytes()).unwrap();
+    let names = files
+        .to_packed()
+        .files
+        .into_iter()
+        .map(|file| file.name)
+        .collect::<Vec<_>>();
+
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(names, ["dir", "dir\\file.txt"]);
+}
+
+fn bitmap_info_header(width: i32, height: i32, bit_count: u16, compression: u32, clr_used: u32) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend_from_slice(&40u32.to_le_bytes());
+    header.extend_from_slice(&width.to_le_bytes());
+    header.extend_from_slice(&height.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&bit_count.to_le_bytes());
+    header.extend_from_slice(&compression.to_le_bytes());
+    header.extend_from_slice(&[0; 12]);
+    header.extend_from_slice(&clr_used.to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes());
+    header
+}
+
+/// Builds a `BITMAPV5HEADER` from a `BITMAPINFOHEADER`, followed by the masks, the color space
+/// type, the endpoints and the gamma values.
+fn bitmap_v5_header(v1: &[u8], masks: [u32; 4], color_space: u32, endpoints: [u32; 9], gamma: [u32; 3]) -> Vec<u8> {
+    let mut header = v1.to_vec();
+    header[..4].copy_from_slice(&124u32.to_le_bytes());
+    for value in masks
+        .into_iter()
+        .chain([color_space])
+        .chain(endpoints)
+        .chain(gamma)
+        .chain([4, 0, 0, 0])
+    {
+        header.extend_from_slice(&value.to_le_bytes());
+    }
+    header
+}
+
+fn decode_png_pixels(png: &[u8]) -> (png::ColorType, Vec<u8>) {
+    let mut reader = png::Decoder::new(png).read_info().unwrap();
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).unwrap();
+    buffer.truncate(info.buffer_size());
+    (info.color_type, buffer)
+}
+
+const RED: [u8; 3] = [0xFF, 0x00, 0x00];
+const GREEN: [u8; 3] = [0x00, 0xFF, 0x00];
+const BLUE: [u8; 3] = [0x00, 0x00, 0xFF];
+const WHITE: [u8; 3] = [0xFF, 0xFF, 0xFF];
+const BLACK: [u8; 3] = [0x00, 0x00, 0x00];
+
+#[test]
+fn dib_1bpp_paletted() {
+    // 3x2, bottom-up, rows padded to 4 bytes
+    let mut dib = bitmap_info_header(3, 2, 1, 0, 2);
+    dib.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0x00]);
+    dib.extend_from_slice(&[0b1010_0000, 0, 0, 0]);
+    dib.extend_from_slice(&[0b0110_0000, 0, 0, 0]);
+
+    let (color_type, pixels) = decode_png_pixels(&dib_to_png(&dib).unwrap());
+    assert_eq!(color_type, png::ColorType::Rgb);
+    assert_eq!(pixels, [BLACK, WHITE, WHITE, WHITE, BLACK, WHITE].concat());
+}
+
+#[test]
+fn dib_8bpp_full_palette_top_down() {
+    // A zero `biClrUsed` means the maximum number of colors, and out-of-range indices are black.
+    let mut dib = bitmap_info_header(2, -1, 8, 0, 0);
+    let mut palette = vec![0u8; 256 * 4];
+    palette[4 * 7..][..4].copy_from_slice(&[0xFF, 0x00, 0x00, 0x00]);
+    dib.extend_from_slice(&palette);
+    dib.extend_from_slice(&[7, 1, 0, 0]);
+
+    let (_, pixels) = decode_png_pixels(&dib_to_png(&dib).unwrap());
+    assert_eq!(pixels, [BLUE, BLACK].concat());
+
+    // Color table larger than the data
+    let mut dib = bitmap_info_header(2, 1, 8, 0, 16);
+    dib.extend_from_slice(&[0; 8]);
+    assert!(dib_to_png(&dib).is_err());
+}
+
+#[test]
+fn dib_rle8() {
+    // 4x3, bottom-up: a run, an absolute run, an end of line, a delta, and an end of bitmap
+    let mut dib = bitmap_info_header(4, 3, 8, 1, 3);
+    dib.extend_from_slice(&[0x00, 0x00, 0xFF, 0x00, 0x00, 0xFF, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00]);
+    dib.extend_from_slice(&[2, 0, 0, 3, 1, 2, 1, 0]);
+    dib.extend_from_slice(&[0, 0, 0, 2, 1, 1]);
+    dib.extend_from_slice(&[1, 2, 0, 1]);
+
+    let (_, pixels) = decode_png_pixels(&dib_to_png(&dib).unwrap());
+    assert_eq!(
+        pixels,
+        [
+            BLACK, BLUE, BLACK, BLACK, // top row, after the delta
+            BLACK, BLACK, BLACK, BLACK, // middle row, skipped
+            RED, RED, GREEN, BLUE, // bottom row, the last pixel being dropped
+        ]
+        .concat()
+    );
+}
+
+#[test]
+fn dib_rle4() {
+    // 6x1: a run alternating two colors, then an absolute run of three pixels
+    let mut dib = bitmap_info_header(6, 1, 4, 2, 2);
+    dib.extend_from_slice(&[0x00, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0x00, 0x00]);
+    dib.extend_from_slice(&[3, 0x01, 0, 3, 0x10, 0x10, 0, 1]);
+
+    let (_, pixels) = decode_png_pixels(&dib_to_png(&dib).unwrap());
+    assert_eq!(pixels, [RED, BLUE, RED, BLUE, RED, BLUE].concat());
+}
+
+#[test]
+fn dib_16bpp() {
+    // BI_RGB, 5-5-5
+    let mut dib = bitmap_info_header(2, 1, 16, 0, 0);
+    dib.extend_from_slice(&0x7C00u16.to_le_bytes());
+    dib.extend_from_slice(&0x001Fu16.to_le_bytes());
+
+    let (_, pixels) = decode_png_pixels(&dib_to_png(&dib).unwrap());
+    assert_eq!(pixels, [RED, BLUE].concat());
+
+    // BI_BITFIELDS, 5-6-5, with the masks following the header
+    let mut dib = bitmap_info_header(2, 1, 16, 3, 0);
+    for mask in [0xF800u32, 0x07E0, 0x001F] {
+        dib.extend_from_slice(&mask.to_le_bytes());
+    }
+    dib.extend_from_slice(&0x07E0u16.to_le_bytes());
+    dib.extend_from_slice(&0xFFFFu16.to_le_bytes());
+
+    let (_, pixels) = decode_png_pixels(&dib_to_png(&dib).unwrap());
+    assert_eq!(pixels, [GREEN, WHITE].concat());
+}
+
+#[test]
+fn dibv5_bitfields_arbitrary_masks() {
+    // RGBA byte order, instead of the usual BGRA
+    let v1 = bitmap_info_header(2, 1, 32, 3, 0);
+    let masks = [0x0000_00FF, 0x0000_FF00, 0x00FF_0000, 0xFF00_0000];
+    let mut dib = bitmap_v5_header(&v1, masks, 0x7352_4742, [0; 9], [0; 3]);
+    dib.extend_from_slice(&[0xFF, 0x00, 0x00, 0x80, 0x00, 0x00, 0xFF, 0xFF]);
+
+    let (color_type, pixels) = decode_png_pixels(&dibv5_to_png(&dib).unwrap());
+    assert_eq!(color_type, png::ColorType::Rgba);
+    assert_eq!(pixels, [0xFF, 0x00, 0x00, 0x80, 0x00, 0x00, 0xFF, 0xFF]);
+
+    // Masks repeated after the header, as synthesized by Windows, and no alpha mask
+    let masks = [0x00FF_0000, 0x0000_FF00, 0x0000_00FF, 0];
+    let mut dib = bitmap_v5_header(&v1, masks, 0x7352_4742, [0; 9], [0; 3]);
+    for mask in &masks[..3] {
+        dib.extend_from_slice(&mask.to_le_bytes());
+    }
+    dib.extend_from_slice(&[0xFF, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00]);
+
+    let (_, pixels) = decode_png_pixels(&dibv5_to_png(&dib).unwrap());
+    assert_eq!(pixels, [0x00, 0x00, 0xFF, 0xFF, 0x00, 0xFF, 0x00, 0xFF]);
+}
+
+#[test]
+fn dibv5_calibrated_rgb() {
+    // sRGB primaries, as FXPT2DOT30 values (0.4124, 0.2126 and 0.0193 for red, etc.), with a
+    // linear (1.0) gamma
+    let endpoints = [
+        0x1A64C2F8, 0x0D9B3D08, 0x013C3611, // red
+        0x16E2EB1C, 0x2DC5D639, 0x07A0F909, // green
+        0x0B8D4FDF, 0x049EECC0, 0x3CD4FDF4, // blue
+    ];
+
+    let v1 = bitmap_info_header(1, 1, 24, 0, 0);
+    let mut dib = bitmap_v5_header(&v1, [0; 4], 0, endpoints, [0x0001_0000; 3]);
+    dib.extend_from_slice(&[0x80, 0x80, 0x80, 0x00]);
+
+    let (_, pixels) = decode_png_pixels(&dibv5_to_png(&dib).unwrap());
+
+    // Linear 50% gray is about 188 in sRGB.
+    for channel in &pixels[..3] {
+        assert!((185..=191).contains(channel), "unexpected pixel: {pixels:?}");
+    }
+    assert_eq!(pixels[3], 0xFF);
+}
+
+#[test]
+fn dibv5_invalid_embedded_profile_is_ignored() {
+    let v1 = bitmap_info_header(1, 1, 24, 0, 0);
+    let mut dib = bitmap_v5_header(&v1, [0; 4], 0x4D42_4544, [0; 9], [0; 3]);
+    dib.extend_from_slice(&[0x10, 0x20, 0x30, 0x00]);
+
+    // Profile following the pixels
+    let profile = b"not an ICC profile";
+    let offset = u32::try_from(dib.len()).unwrap();
+    dib[112..116].copy_from_slice(&offset.to_le_bytes());
+    dib[116..120].copy_from_slice(&u32::try_from(profile.len()).unwrap().to_le_bytes());
+    dib.extend_from_slice(profile);
+
+    let (_, pixels) = decode_png_pixels(&dibv5_to_png(&dib).unwrap());
+    assert_eq!(pixels, [0x30, 0x20, 0x10, 0xFF]);
+}
@@ -1,4 +1,8 @@
+mod file_transfer;
 mod format;
+mod policy;
+#[cfg(target_os = "linux")]
+mod x11;
 
 use expect_test::expect;
 use ironrdp_cliprdr::pdu::{
@@ -0,0 +1,396 @@
+use std::sync::{mpsc, Arc, Mutex};
+
+use ironrdp_cliprdr::backend::{ClipboardMessage, ClipboardMessageProxy, CliprdrBackend};
+use ironrdp_cliprdr::pdu::{
+    ClipboardFormat, ClipboardFormatId, ClipboardFormatName, ClipboardGeneralCapabilityFlags, FileContentsFlags,
+    FileContentsRequest, FileContentsResponse, FormatDataRequest, FormatDataResponse, LockDataId,
+};
+use ironrdp_cliprdr::policy::{
+    ClipboardAuditEvent, ClipboardAuditor, ClipboardPolicy, DenialReason, PolicyBackend, PolicyMessageProxy,
+    PolicyOutcome, TransferDirection,
+};
+use ironrdp_core::impl_as_any;
+
+/// Calls received by [`MockBackend`].
+#[derive(Debug, PartialEq, Eq)]
+enum Call {
+    RemoteCopy(Vec<ClipboardFormat>),
+    FormatDataRequest(ClipboardFormatId),
+    FormatDataResponse { is_error: bool, data: Vec<u8> },
+    FileContentsRequest(u32),
+    FileContentsResponse { is_error: bool, stream_id: u32 },
+}
+
+#[derive(Debug, Default)]
+struct MockBackend {
+    calls: Arc<Mutex<Vec<Call>>>,
+}
+
+impl_as_any!(MockBackend);
+
+impl CliprdrBackend for MockBackend {
+    fn temporary_directory(&self) -> &str {
+        ".cliprdr"
+    }
+
+    fn client_capabilities(&self) -> ClipboardGeneralCapabilityFlags {
+        ClipboardGeneralCapabilityFlags::USE_LONG_FORMAT_NAMES
+            | ClipboardGeneralCapabilityFlags::STREAM_FILECLIP_ENABLED
+            | ClipboardGeneralCapabilityFlags::FILECLIP_NO_FILE_PATHS
+    }
+
+    fn on_request_format_list(&mut self) {}
+
+    fn on_process_negotiated_capabilities(&mut self, _: ClipboardGeneralCapabilityFlags) {}
+
+    fn on_remote_copy(&mut self, available_formats: &[ClipboardFormat]) {
+        self.record(Call::RemoteCopy(available_formats.to_vec()));
+    }
+
+    fn on_format_data_request(&mut self, request: FormatDataRequest) {
+        self.record(Call::FormatDataRequest(request.format));
+    }
+
+    fn on_format_data_response(&mut self, response: FormatDataResponse<'_>) {
+        self.record(Call::FormatDataResponse {
+            is_error: response.is_error(),
+            data: response.data().to_vec(),
+        });
+    }
+
+    fn on_file_contents_request(&mut self, request: FileContentsRequest) {
+        self.record(Call::FileContentsRequest(request.stream_id));
+    }
+
+    fn on_file_contents_response(&mut self, response: FileContentsResponse<'_>) {
+        self.record(Call::FileContentsResponse {
+            is_error: response.is_error(),
+            stream_id: response.stream_id(),
+        });
+    }
+
+    fn on_lock(&mut self, _: LockDataId) {}
+
+    fn on_unlock(&mut self, _: LockDataId) {}
+}
+
+impl MockBackend {
+    fn record(&self, call: Call) {
+        self.calls.lock().unwrap().push(call);
+    }
+}
+
+#[derive(Debug)]
+struct ChannelProxy(mpsc::Sender<ClipboardMessage>);
+
+impl ClipboardMessageProxy for ChannelProxy {
+    fn send_clipboard_message(&self, message: ClipboardMessage) {
+        let _ = self.0.send(message);
+    }
+}
+
+#[derive(Debug, Default)]
+struct Recorder(Mutex<Vec<ClipboardAuditEvent>>);
+
+impl ClipboardAuditor for Recorder {
+    fn audit(&self, event: &ClipboardAuditEvent) {
+        self.0.lock().unwrap().push(event.clone());
+    }
+}
+
+struct Setup {
+    backend: PolicyBackend,
+    calls: Arc<Mutex<Vec<Call>>>,
+    /// Messages sent by the policy to the remote.
+    replies: mpsc::Receiver<ClipboardMessage>,
+    /// Proxy used by the backend to send messages to the remote.
+    proxy: PolicyMessageProxy<ChannelProxy>,
+    sent: mpsc::Receiver<ClipboardMessage>,
+    audit: Arc<Recorder>,
+}
+
+impl Setup {
+    fn new(policy: ClipboardPolicy) -> Self {
+        let audit = Arc::new(Recorder::default());
+        let policy = Arc::new(policy.with_auditor(Arc::clone(&audit) as Arc<dyn ClipboardAuditor>));
+
+        let mock = MockBackend::default();
+        let calls = Arc::clone(&mock.calls);
+
+        let (replies_sender, replies) = mpsc::channel();
+        let backend = PolicyBackend::new(
+            Box::new(mock),
+            Arc::clone(&policy),
+            Box::new(ChannelProxy(replies_sender)),
+        );
+
+        let (sent_sender, sent) = mpsc::channel();
+        let proxy = PolicyMessageProxy::new(ChannelProxy(sent_sender), policy);
+
+        Self {
+            backend,
+            calls,
+            replies,
+            proxy,
+            sent,
+            audit,
+        }
+    }
+
+    fn take_calls(&self) -> Vec<Call> {
+        core::mem::take(&mut *self.calls.lock().unwrap())
+    }
+
+    fn take_audit(&self) -> Vec<ClipboardAuditEvent> {
+        core::mem::take(&mut *self.audit.0.lock().unwrap())
+    }
+}
+
+fn text_formats() -> Vec<ClipboardFormat> {
+    vec![
+        ClipboardFormat::new(ClipboardFormatId::CF_UNICODETEXT),
+        ClipboardFormat::new(ClipboardFormatId::new(0xC0A0)).with_name(ClipboardFormatName::HTML),
+    ]
+}
+
+fn file_formats() -> Vec<ClipboardFormat> {
+    vec![
+        ClipboardFormat::new(ClipboardFormatId::new(0xC0B0)).with_name(ClipboardFormatName::FILE_LIST),
+        ClipboardFormat::new(ClipboardFormatId::CF_HDROP),
+    ]
+}
+
+fn file_contents_request(stream_id: u32) -> FileContentsRequest {
+    FileContentsRequest {
+        stream_id,
+        index: 0,
+        flags: FileContentsFlags::DATA,
+        position: 0,
+        requested_size: 1024,
+        data_id: None,
+    }
+}
+
+#[test]
+fn policy_allows_everything_by_default() {
+    let mut setup = Setup::new(ClipboardPolicy::new());
+
+    setup.backend.on_remote_copy(&text_formats());
+    setup
+        .proxy
+        .send_clipboard_message(ClipboardMessage::SendInitiatePaste(ClipboardFormatId::CF_UNICODETEXT));
+    setup
+        .backend
+        .on_format_data_response(FormatDataResponse::new_data(b"h\0i\0\0\0".as_slice()));
+
+    assert_eq!(
+        setup.take_calls(),
+        [
+            Call::RemoteCopy(text_formats()),
+            Call::FormatDataResponse {
+                is_error: false,
+                data: b"h\0i\0\0\0".to_vec()
+            },
+        ]
+    );
+    assert!(matches!(
+        setup.sent.try_recv(),
+        Ok(ClipboardMessage::SendInitiatePaste(ClipboardFormatId::CF_UNICODETEXT))
+    ));
+}
+
+#[test]
+fn policy_remote_to_local_only() {
+    let mut setup = Setup::new(ClipboardPolicy::new().with_direction(TransferDirection::LocalToRemote, false));
+
+    // Local copies are advertised as empty
+    setup
+        .proxy
+        .send_clipboard_message(ClipboardMessage::SendInitiateCopy(text_formats()));
+    match setup.sent.try_recv() {
+        Ok(ClipboardMessage::SendInitiateCopy(formats)) => assert!(formats.is_empty()),
+        other => panic!("unexpected message: {other:?}"),
+    }
+
+    // Requests of the remote are answered with failures, without reaching the backend
+    setup.backend.on_format_data_request(FormatDataRequest {
+        format: ClipboardFormatId::CF_UNICODETEXT,
+    });
+    match setup.replies.try_recv() {
+        Ok(ClipboardMessage::SendFormatData(response)) => assert!(response.is_error()),
+        other => panic!("unexpected message: {other:?}"),
+    }
+
+    // Format data sent by the backend anyway is replaced with a failure
+    setup
+        .proxy
+        .send_clipboard_message(ClipboardMessage::SendFormatData(FormatDataResponse::new_data(
+            b"secret".to_vec(),
+        )));
+    match setup.sent.try_recv() {
+        Ok(ClipboardMessage::SendFormatData(response)) => assert!(response.is_error()),
+        other => panic!("unexpected message: {other:?}"),
+    }
+
+    // The other direction is still allowed
+    setup.backend.on_remote_copy(&text_formats());
+    assert_eq!(setup.take_calls(), [Call::RemoteCopy(text_formats())]);
+
+    let audit = setup.take_audit();
+    assert!(audit.contains(&ClipboardAuditEvent::FormatData {
+        direction: TransferDirection::LocalToRemote,
+        format: Some(ClipboardFormatId::CF_UNICODETEXT),
+        size: 0,
+        outcome: PolicyOutcome::Denied(DenialReason::Direction),
+    }));
+}
+
+#[test]
+fn policy_blocks_files() {
+    let mut setup = Setup::new(ClipboardPolicy::new().with_files(false));
+
+    assert!(!setup
+        .backend
+        .client_capabilities()
+        .contains(ClipboardGeneralCapabilityFlags::STREAM_FILECLIP_ENABLED));
+
+    let mut formats = text_formats();
+    formats.extend(file_formats());
+    setup.backend.on_remote_copy(&formats);
+    assert_eq!(setup.take_calls(), [Call::RemoteCopy(text_formats())]);
+
+    let audit = setup.take_audit();
+    assert_eq!(
+        audit,
+        [ClipboardAuditEvent::FormatList {
+            direction: TransferDirection::RemoteToLocal,
+            allowed: text_formats(),
+            denied: file_formats()
+                .into_iter()
+                .map(|format| (format, DenialReason::Files))
+                .collect(),
+        }]
+    );
+
+    setup.backend.on_file_contents_request(file_contents_request(7));
+    match setup.replies.try_recv() {
+        Ok(ClipboardMessage::SendFileContentsResponse(response)) => {
+            assert!(response.is_error());
+            assert_eq!(response.stream_id(), 7);
+        }
+        other => panic!("unexpected message: {other:?}"),
+    }
+
+    setup
+        .proxy
+        .send_clipboard_message(ClipboardMessage::SendFileContentsRequest(file_contents_request(8)));
+    assert!(setup.sent.try_recv().is_err());
+
+    setup
+        .backend
+        .on_file_contents_response(FileContentsResponse::new_data_response(8, b"data".as_slice()));
+    assert_eq!(
+        setup.take_calls(),
+        [Call::FileContentsResponse {
+            is_error: true,
+            stream_id: 8
+        }]
+    );
+}
+
+#[test]
+fn policy_caps_payload_size() {
+    let mut setup = Setup::new(ClipboardPolicy::new().with_max_data_size(4));
+
+    setup.backend.on_remote_copy(&text_formats());
+    setup.take_calls();
+
+    setup
+        .proxy
+        .send_clipboard_message(ClipboardMessage::SendInitiatePaste(ClipboardFormatId::CF_UNICODETEXT));
+    setup
+        .backend
+        .on_format_data_response(FormatDataResponse::new_data(b"too large".as_slice()));
+    setup.sent.try_recv().unwrap();
+    assert_eq!(
+        setup.take_calls(),
+        [Call::FormatDataResponse {
+            is_error: true,
+            data: Vec::new()
+        }]
+    );
+
+    setup
+        .proxy
+        .send_clipboard_message(ClipboardMessage::SendInitiateCopy(text_formats()));
+    setup.sent.try_recv().unwrap();
+    setup.backend.on_format_data_request(FormatDataRequest {
+        format: ClipboardFormatId::CF_UNICODETEXT,
+    });
+    assert_eq!(
+        setup.take_calls(),
+        [Call::FormatDataRequest(ClipboardFormatId::CF_UNICODETEXT)]
+    );
+
+    setup
+        .proxy
+        .send_clipboard_message(ClipboardMessage::SendFormatData(FormatDataResponse::new_data(
+            b"ok".to_vec(),
+        )));
+    match setup.sent.try_recv() {
+        Ok(ClipboardMessage::SendFormatData(response)) => assert_eq!(response.data(), b"ok"),
+        other => panic!("unexpected message: {other:?}"),
+    }
+}
+
+#[test]
+fn policy_allowed_formats() {
+    let mut setup = Setup::new(ClipboardPolicy::new().with_allowed_format_name("html format"));
+
+    setup.backend.on_remote_copy(&text_formats());
+    assert_eq!(setup.take_calls(), [Call::RemoteCopy(text_formats()[1..].to_vec())]);
+
+    // Formats which were filtered out can't be requested
+    setup
+        .proxy
+        .send_clipboard_message(ClipboardMessage::SendInitiatePaste(ClipboardFormatId::CF_UNICODETEXT));
+    assert!(setup.sent.try_recv().is_err());
+
+    let mut setup = Setup::new(ClipboardPolicy::new().with_allowed_format_id(ClipboardFormatId::CF_UNICODETEXT));
+
+    setup
+        .proxy
+        .send_clipboard_message(ClipboardMessage::SendInitiateCopy(text_formats()));
+    match setup.sent.try_recv() {
+        Ok(ClipboardMessage::SendInitiateCopy(formats)) => assert_eq!(formats, text_formats()[..1]),
+        other => panic!("unexpected message: {other:?}"),
+    }
+
+    setup.backend.on_format_data_request(FormatDataRequest {
+        format: ClipboardFormatId::new(0xC0A0),
+    });
+    assert!(setup.take_calls().is_empty());
+    assert!(matches!(
+        setup.replies.try_recv(),
+        Ok(ClipboardMessage::SendFormatData(response)) if response.is_error()
+    ));
+}
+
+#[test]
+fn policy_sessions_track_their_own_formats() {
+    let policy = Arc::new(ClipboardPolicy::new());
+    let (replies_sender, _replies) = mpsc::channel();
+    let mut backend = PolicyBackend::new(
+        Box::new(MockBackend::default()),
+        Arc::clone(&policy),
+        Box::new(ChannelProxy(replies_sender)),
+    );
+    let other = policy.new_session();
+
+    backend.on_remote_copy(&text_formats());
+
+    // The formats were offered to the first session only
+    let paste = || ClipboardMessage::SendInitiatePaste(ClipboardFormatId::CF_UNICODETEXT);
+    assert!(other.filter_message(paste()).is_none());
+    assert!(policy.filter_message(paste()).is_some());
+}
@@ -0,0 +1,305 @@
+//! These tests need an X server, e.g. `xvfb-run cargo test`, and are skipped when `DISPLAY` is unset.
+
+use std::sync::{mpsc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use ironrdp_cliprdr::backend::{ClipboardMessage, ClipboardMessageProxy, CliprdrBackend};
+use ironrdp_cliprdr::pdu::{
+    ClipboardFormat, ClipboardFormatId, ClipboardGeneralCapabilityFlags, FormatDataRequest, FormatDataResponse,
+};
+use ironrdp_cliprdr_native::X11Clipboard;
+use x11rb::connection::Connection as _;
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, PropMode, Property, SelectionNotifyEvent, Window,
+    WindowClass, SELECTION_NOTIFY_EVENT,
+};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::{CURRENT_TIME, NONE};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Clipboard instances would compete for the selection if tests were run concurrently.
+static DISPLAY_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug)]
+struct ChannelProxy(mpsc::Sender<ClipboardMessage>);
+
+impl ClipboardMessageProxy for ChannelProxy {
+    fn send_clipboard_message(&self, message: ClipboardMessage) {
+        let _ = self.0.send(message);
+    }
+}
+
+struct Setup {
+    _guard: MutexGuard<'static, ()>,
+    _clipboard: X11Clipboard,
+    backend: Box<dyn CliprdrBackend>,
+    messages: mpsc::Receiver<ClipboardMessage>,
+}
+
+fn setup() -> Option<Setup> {
+    std::env::var_os("DISPLAY")?;
+
+    let guard = DISPLAY_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    let (tx, messages) = mpsc::channel();
+    let clipboard = X11Clipboard::new(ChannelProxy(tx)).unwrap();
+    let mut backend = clipboard.backend_factory().build_cliprdr_backend();
+    backend.on_process_negotiated_capabilities(ClipboardGeneralCapabilityFlags::STREAM_FILECLIP_ENABLED);
+
+    Some(Setup {
+        _guard: guard,
+        _clipboard: clipboard,
+        backend,
+        messages,
+    })
+}
+
+/// Another X client, on its own connection.
+struct Client {
+    conn: RustConnection,
+    window: Window,
+}
+
+impl Client {
+    fn new() -> Self {
+        let (conn, screen_num) = x11rb::connect(None).unwrap();
+        let root = conn.setup().roots[screen_num].root;
+        let window = conn.generate_id().unwrap();
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            window,
+            root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            x11rb::COPY_FROM_PARENT,
+            &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )
+        .unwrap();
+        conn.flush().unwrap();
+
+        Self { conn, window }
+    }
+
+    fn atom(&self, name: &str) -> Atom {
+        self.conn
+            .intern_atom(false, name.as_bytes())
+            .unwrap()
+            .reply()
+            .unwrap()
+            .atom
+    }
+
+    fn wait_for_owner(&self, selection: Atom, owned: impl Fn(Window) -> bool) {
+        let start = Instant::now();
+        while !owned(self.conn.get_selection_owner(selection).unwrap().reply().unwrap().owner) {
+            assert!(start.elapsed() < TIMEOUT, "selection owner did not change");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Requests the clipboard in `target`, answering to `on_request` while waiting, and supporting INCR.
+    fn paste(&self, target: &str, mut on_request: impl FnMut()) -> Option<Vec<u8>> {
+        let clipboard = self.atom("CLIPBOARD");
+        let property = self.atom("IRONRDP_TEST");
+        let incr = self.atom("INCR");
+
+        self.conn
+            .convert_selection(self.window, clipboard, self.atom(target), property, CURRENT_TIME)
+            .unwrap();
+        self.conn.flush().unwrap();
+
+        let mut data = None::<Vec<u8>>;
+
+        loop {
+            on_request();
+
+            let Some(event) = self.conn.poll_for_event().unwrap() else {
+                std::thread::sleep(Duration::from_millis(10));
+                continue;
+            };
+
+            match event {
+                Event::SelectionNotify(event) if event.property == NONE => return None,
+                Event::SelectionNotify(event) => {
+                    let reply = self.take_property(event.property);
+                    if reply.0 != incr {
+                        return Some(reply.1);
+                    }
+                    data = Some(Vec::new());
+                }
+                Event::PropertyNotify(event)
+                    if event.atom == property && event.state == Property::NEW_VALUE && data.is_some() =>
+                {
+                    let (_, chunk) = self.take_property(property);
+                    let buffer = data.as_mut().unwrap();
+                    if chunk.is_empty() {
+                        return data;
+                    }
+                    buffer.extend_from_slice(&chunk);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn take_property(&self, property: Atom) -> (Atom, Vec<u8>) {
+        let reply = self
+            .conn
+            .get_property(true, self.window, property, AtomEnum::ANY, 0, u32::MAX / 4)
+            .unwrap()
+            .reply()
+            .unwrap();
+        self.conn.flush().unwrap();
+        (reply.type_, reply.value)
+    }
+
+    /// Takes the clipboard over, see [`Self::serve`].
+    fn copy(&self) {
+        self.conn
+            .set_selection_owner(self.window, self.atom("CLIPBOARD"), CURRENT_TIME)
+            .unwrap();
+        self.conn.flush().unwrap();
+    }
+
+    /// Answers pending selection requests for the clipboard taken over with [`Self::copy`], offering `text` as
+    /// `UTF8_STRING`.
+    fn serve(&self, text: &str) {
+        let targets = self.atom("TARGETS");
+        let utf8_string = self.atom("UTF8_STRING");
+
+        while let Some(event) = self.conn.poll_for_event().unwrap() {
+            let Event::SelectionRequest(request) = event else {
+                continue;
+            };
+
+            let property = if request.target == targets {
+                self.conn
+                    .change_property32(
+                        PropMode::REPLACE,
+                        request.requestor,
+                        request.property,
+                        AtomEnum::ATOM,
+                        &[targets, utf8_string],
+                    )
+                    .unwrap();
+                request.property
+            } else if request.target == utf8_string {
+                self.conn
+                    .change_property8(
+                        PropMode::REPLACE,
+                        request.requestor,
+                        request.property,
+                        utf8_string,
+                        text.as_bytes(),
+                    )
+                    .unwrap();
+                request.property
+            } else {
+                NONE
+            };
+
+            let notify = SelectionNotifyEvent {
+                response_type: SELECTION_NOTIFY_EVENT,
+                sequence: 0,
+                time: request.time,
+                requestor: request.requestor,
+                selection: request.selection,
+                target: request.target,
+                property,
+            };
+            self.conn
+                .send_event(false, request.requestor, EventMask::NO_EVENT, notify)
+                .unwrap();
+            self.conn.flush().unwrap();
+        }
+    }
+}
+
+fn next_message(messages: &mpsc::Receiver<ClipboardMessage>, on_wait: impl Fn()) -> ClipboardMessage {
+    let start = Instant::now();
+    loop {
+        on_wait();
+        match messages.recv_timeout(Duration::from_millis(10)) {
+            Ok(message) => return message,
+            Err(_) => assert!(start.elapsed() < TIMEOUT, "no clipboard message received"),
+        }
+    }
+}
+
+fn remote_text_round_trip(text: &str) {
+    let Some(mut setup) = setup() else {
+        return;
+    };
+    let client = Client::new();
+    let clipboard = client.atom("CLIPBOARD");
+
+    setup
+        .backend
+        .on_remote_copy(&[ClipboardFormat::new(ClipboardFormatId::CF_UNICODETEXT)]);
+    client.wait_for_owner(clipboard, |owner| owner != NONE && owner != client.window);
+
+    let mut answered = false;
+    let pasted = client.paste("UTF8_STRING", || {
+        if answered {
+            return;
+        }
+
+        if let Ok(message) = setup.messages.try_recv() {
+            let ClipboardMessage::SendInitiatePaste(format) = message else {
+                panic!("unexpected message: {message:?}");
+            };
+            assert_eq!(format, ClipboardFormatId::CF_UNICODETEXT);
+
+            let remote_text = text.replace('\n', "\r\n");
+            setup
+                .backend
+                .on_format_data_response(FormatDataResponse::new_unicode_string(&remote_text));
+            answered = true;
+        }
+    });
+
+    assert_eq!(pasted.as_deref(), Some(text.as_bytes()));
+}
+
+#[test]
+fn remote_text_is_pasted_by_x_clients() {
+    remote_text_round_trip("hello\nworld");
+}
+
+#[test]
+fn large_remote_text_is_pasted_with_incr() {
+    remote_text_round_trip(&"0123456789abcdef\n".repeat(128 * 1024));
+}
+
+#[test]
+fn local_text_is_copied_to_remote() {
+    let Some(mut setup) = setup() else {
+        return;
+    };
+    let client = Client::new();
+
+    client.copy();
+
+    let message = next_message(&setup.messages, || client.serve("hello\nworld"));
+    let ClipboardMessage::SendInitiateCopy(formats) = message else {
+        panic!("unexpected message: {message:?}");
+    };
+    assert_eq!(formats, [ClipboardFormat::new(ClipboardFormatId::CF_UNICODETEXT)]);
+
+    setup.backend.on_format_data_request(FormatDataRequest {
+        format: ClipboardFormatId::CF_UNICODETEXT,
+    });
+
+    let message = next_message(&setup.messages, || client.serve("hello\nworld"));
+    let ClipboardMessage::SendFormatData(response) = message else {
+        panic!("unexpected message: {message:?}");
+    };
+    assert_eq!(response.to_unicode_string().unwrap(), "hello\r\nworld");
+}
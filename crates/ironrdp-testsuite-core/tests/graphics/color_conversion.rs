@@ -10,6 +10,74 @@ fn to_64x64_ycbcr() {
     to_64x64_ycbcr_tile(&input, 1, 1, 4, PixelFormat::ABgr32, &mut y, &mut cb, &mut cr);
 }
 
+#[test]
+fn to_64x64_ycbcr_tile_matches_ycbcr_from_rgb_for_all_colors() {
+    let mut y = [0; 64 * 64];
+    let mut cb = [0; 64 * 64];
+    let mut cr = [0; 64 * 64];
+
+    for first_color in (0..1u32 << 24).step_by(64 * 64) {
+        let colors = first_color..first_color + 64 * 64;
+        let input: Vec<u8> = colors.clone().flat_map(|color| color.to_le_bytes()).collect();
+        to_64x64_ycbcr_tile(&input, 64, 64, 64 * 4, PixelFormat::BgrX32, &mut y, &mut cb, &mut cr);
+
+        for (i, color) in colors.enumerate() {
+            let [b, g, r, _] = color.to_le_bytes();
+            let expected = YCbCr::from(Rgb { r, g, b });
+
+            assert_eq!(
+                expected,
+                YCbCr {
+                    y: y[i],
+                    cb: cb[i],
+                    cr: cr[i]
+                }
+            );
+        }
+    }
+}
+
+#[test]
+fn to_64x64_ycbcr_tile_repeats_last_column_and_row() {
+    let (width, height, stride) = (3, 2, 16);
+    let input: Vec<u8> = (0..=u8::MAX).cycle().step_by(37).take(stride * height).collect();
+
+    let mut y = [0; 64 * 64];
+    let mut cb = [0; 64 * 64];
+    let mut cr = [0; 64 * 64];
+    to_64x64_ycbcr_tile(
+        &input,
+        width,
+        height,
+        stride,
+        PixelFormat::XRgb32,
+        &mut y,
+        &mut cb,
+        &mut cr,
+    );
+
+    for row in 0..64 {
+        for col in 0..64 {
+            let pos = row.min(height - 1) * stride + col.min(width - 1) * 4;
+            let rgb = Rgb {
+                r: input[pos + 1],
+                g: input[pos + 2],
+                b: input[pos + 3],
+            };
+            let i = row * 64 + col;
+
+            assert_eq!(
+                YCbCr::from(rgb),
+                YCbCr {
+                    y: y[i],
+                    cb: cb[i],
+                    cr: cr[i]
+                }
+            );
+        }
+    }
+}
+
 #[test]
 fn ycbcr_from_rgb_works_for_zeros() {
     let rgb = Rgb { r: 0, g: 0, b: 0 };
@@ -1653,3 +1721,68 @@ const RGB_BUFFER: [u8; 4 * 64 * 64] = [
     0xFF, 0xF6, 0x9D, 0x13, 0xFF, 0xF6, 0x9C, 0x12, 0xFF, 0xF5, 0x9A, 0x11, 0xFF, 0xF5, 0x9A, 0x11, 0xFF, 0xF5, 0x9A,
     0x11, 0xFF, 0xF5, 0x9A, 0x11, 0xFF,
 ];
+
+#[test]
+fn to_yuv420_converts_primary_colors() {
+    // 2x2 XRGB image: white, red, green and blue
+    #[rustfmt::skip]
+    let input = [
+        0, 255, 255, 255,   0, 255, 0, 0,
+        0, 0, 255, 0,       0, 0, 0, 255,
+    ];
+
+    let mut y = [0; 4];
+    let mut u = [0; 1];
+    let mut v = [0; 1];
+    to_yuv420(
+        &input,
+        2,
+        2,
+        8,
+        PixelFormat::XRgb32,
+        Yuv420Buffer {
+            y: &mut y,
+            u: &mut u,
+            v: &mut v,
+            y_stride: 2,
+            uv_stride: 1,
+        },
+    );
+
+    assert_eq!(y, [254, 53, 182, 17]);
+    assert_eq!(u, [127]);
+    assert_eq!(v, [127]);
+}
+
+#[test]
+fn to_yuv420_repeats_last_column_and_row() {
+    let input = [10, 20, 30, 0].repeat(3 * 3);
+
+    let mut y = [0; 4 * 4];
+    let mut u = [0; 2 * 2];
+    let mut v = [0; 2 * 2];
+    to_yuv420(
+        &input,
+        3,
+        3,
+        3 * 4,
+        PixelFormat::BgrX32,
+        Yuv420Buffer {
+            y: &mut y,
+            u: &mut u,
+            v: &mut v,
+            y_stride: 4,
+            uv_stride: 2,
+        },
+    );
+
+    #[rustfmt::skip]
+    assert_eq!(y, [
+        21, 21, 21, 0,
+        21, 21, 21, 0,
+        21, 21, 21, 0,
+        0, 0, 0, 0,
+    ]);
+    assert_eq!(u, [121, 121, 121, 121]);
+    assert_eq!(v, [133, 133, 133, 133]);
+}
@@ -11,6 +11,7 @@
 //! Cargo will run all tests from a single binary in parallel, but
 //! binaries themselves are run sequentally.
 
+mod audin;
 mod clipboard;
 mod displaycontrol;
 mod dvc;
@@ -20,6 +21,7 @@ mod input;
 mod pcb;
 mod pdu;
 mod rdcleanpath;
+mod rdpdr;
 mod rdpsnd;
 mod server_name;
 mod session;
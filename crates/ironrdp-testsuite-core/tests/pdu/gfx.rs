@@ -445,3 +445,45 @@ fn to_buffer_consume_correctly_serializes_avc_444_message() {
 
     assert_eq!(expected, buffer.as_slice());
 }
+
+#[test]
+fn cache_import_offer_round_trips() {
+    use ironrdp_pdu::rdp::vc::dvc::gfx::{CacheEntryMetadata, CacheImportOfferPdu, ClientPdu};
+
+    #[rustfmt::skip]
+    let buffer = [
+        0x10, 0x00, 0x00, 0x00, 0x22, 0x00, 0x00, 0x00, // header
+        0x02, 0x00, // cacheEntriesCount
+        0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01, 0x00, 0x10, 0x00, 0x00,
+        0x18, 0x17, 0x16, 0x15, 0x14, 0x13, 0x12, 0x11, 0x40, 0x00, 0x00, 0x00,
+    ];
+    let pdu = ClientPdu::CacheImportOffer(CacheImportOfferPdu {
+        cache_entries: vec![
+            CacheEntryMetadata {
+                cache_key: 0x0102_0304_0506_0708,
+                bitmap_length: 0x1000,
+            },
+            CacheEntryMetadata {
+                cache_key: 0x1112_1314_1516_1718,
+                bitmap_length: 0x40,
+            },
+        ],
+    });
+
+    assert_eq!(pdu, decode(&buffer).unwrap());
+    assert_eq!(encode_vec(&pdu).unwrap(), buffer);
+    assert_eq!(pdu.size(), buffer.len());
+}
+
+#[test]
+fn cache_import_offer_with_too_many_entries_is_rejected() {
+    use ironrdp_pdu::rdp::vc::dvc::gfx::{CacheImportOfferPdu, ClientPdu};
+
+    let mut buffer = vec![0x10, 0x00, 0x00, 0x00];
+    buffer.extend_from_slice(&(8u32 + 2 + 5463 * 12).to_le_bytes());
+    buffer.extend_from_slice(&5463u16.to_le_bytes());
+    buffer.resize(8 + 2 + 5463 * 12, 0);
+
+    assert!(decode::<ClientPdu>(&buffer).is_err());
+    assert_eq!(CacheImportOfferPdu::MAX_CACHE_ENTRIES, 5462);
+}
@@ -0,0 +1,989 @@
+use std::sync::{Arc, Mutex};
+
+use ironrdp_core::{encode_vec, impl_as_any, ReadCursor};
+use ironrdp_pdu::PduResult;
+use ironrdp_rdpdr::pdu::efs::{
+    ClientDriveLockControlResponse, ClientDriveNotifyChangeDirectoryResponse, CompletionFilter, DeviceCloseResponse,
+    DeviceControlRequest, DeviceIoRequest, DeviceIoResponse, DeviceReadResponse, FileAction, FileNotifyInformation,
+    LockOperation, MajorFunction, MinorFunction, NtStatus, RdpLockInfo, ServerDeviceAnnounceResponse,
+    ServerDriveIoRequest, ServerDriveLockControlRequest, ServerDriveNotifyChangeDirectoryRequest,
+};
+use ironrdp_rdpdr::pdu::esc::{ScardCall, ScardIoCtlCode};
+use ironrdp_rdpdr::pdu::RdpdrPdu;
+use ironrdp_rdpdr::{Rdpdr, RdpdrBackend};
+use ironrdp_svc::SvcProcessor as _;
+
+use super::{io_request, io_response};
+
+fn file_notify_information() -> Vec<FileNotifyInformation> {
+    vec![
+        FileNotifyInformation::new(FileAction::RENAMED_OLD_NAME, "a".to_owned()),
+        FileNotifyInformation::new(FileAction::RENAMED_NEW_NAME, "bc".to_owned()),
+    ]
+}
+
+#[test]
+fn notify_change_directory_response_encode() {
+    let mut response = ClientDriveNotifyChangeDirectoryResponse::new(
+        &ServerDriveNotifyChangeDirectoryRequest {
+            device_io_request: DeviceIoRequest {
+                device_id: 1,
+                file_id: 2,
+                completion_id: 3,
+                major_function: MajorFunction::DirectoryControl,
+                minor_function: MinorFunction::IRP_MN_NOTIFY_CHANGE_DIRECTORY,
+            },
+            watch_tree: 0,
+            completion_filter: CompletionFilter::FILE_NOTIFY_CHANGE_FILE_NAME,
+        },
+        NtStatus::SUCCESS,
+    );
+    response.buffer = file_notify_information();
+
+    let encoded = encode_vec(&RdpdrPdu::ClientDriveNotifyChangeDirectoryResponse(response.clone())).unwrap();
+    assert_eq!(
+        encoded,
+        [
+            0x72, 0x44, 0x43, 0x49, // RDPDR_CTYP_CORE, PAKID_CORE_DEVICE_IOCOMPLETION
+            0x01, 0x00, 0x00, 0x00, // DeviceId
+            0x03, 0x00, 0x00, 0x00, // CompletionId
+            0x00, 0x00, 0x00, 0x00, // IoStatus
+            0x20, 0x00, 0x00, 0x00, // Length
+            0x10, 0x00, 0x00, 0x00, // NextEntryOffset
+            0x04, 0x00, 0x00, 0x00, // Action
+            0x02, 0x00, 0x00, 0x00, // FileNameLength
+            b'a', 0x00, 0x00, 0x00, // FileName, Padding
+            0x00, 0x00, 0x00, 0x00, // NextEntryOffset
+            0x05, 0x00, 0x00, 0x00, // Action
+            0x04, 0x00, 0x00, 0x00, // FileNameLength
+            b'b', 0x00, b'c', 0x00, // FileName
+        ]
+    );
+
+    let mut src = ReadCursor::new(&encoded[4..]);
+    let device_io_reply = DeviceIoResponse::decode(&mut src).unwrap();
+    assert_eq!(
+        ClientDriveNotifyChangeDirectoryResponse::decode(device_io_reply, &mut src).unwrap(),
+        response
+    );
+}
+
+#[test]
+fn notify_change_directory_cancelled_response_encode() {
+    let response = ClientDriveNotifyChangeDirectoryResponse {
+        device_io_reply: DeviceIoResponse {
+            device_id: 1,
+            completion_id: 3,
+            io_status: NtStatus::CANCELLED,
+        },
+        buffer: Vec::new(),
+    };
+
+    let encoded = encode_vec(&RdpdrPdu::ClientDriveNotifyChangeDirectoryResponse(response)).unwrap();
+    assert_eq!(
+        encoded,
+        [
+            0x72, 0x44, 0x43, 0x49, // RDPDR_CTYP_CORE, PAKID_CORE_DEVICE_IOCOMPLETION
+            0x01, 0x00, 0x00, 0x00, // DeviceId
+            0x03, 0x00, 0x00, 0x00, // CompletionId
+            0x20, 0x01, 0x00, 0xC0, // IoStatus
+            0x00, 0x00, 0x00, 0x00, // Length
+            0x00, // Padding
+        ]
+    );
+}
+
+#[test]
+fn lock_control_request_decode() {
+    #[rustfmt::skip]
+    let body = [
+        0x03, 0x00, 0x00, 0x00, // Operation
+        0x01, 0x00, 0x00, 0x00, // F, Padding
+        0x02, 0x00, 0x00, 0x00, // NumLocks
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Padding2
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x0A, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Length
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Offset
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Length
+        0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Offset
+    ];
+    let device_io_request = DeviceIoRequest {
+        device_id: 1,
+        file_id: 2,
+        completion_id: 3,
+        major_function: MajorFunction::LockControl,
+        minor_function: MinorFunction::from(0),
+    };
+
+    let req = ServerDriveIoRequest::decode(device_io_request.clone(), &mut ReadCursor::new(&body)).unwrap();
+    assert_eq!(
+        req,
+        ServerDriveIoRequest::ServerDriveLockControlRequest(ServerDriveLockControlRequest {
+            device_io_request,
+            operation: LockOperation::EXCLUSIVE_LOCK,
+            wait: true,
+            locks: vec![
+                RdpLockInfo { length: 10, offset: 0 },
+                RdpLockInfo {
+                    length: 1,
+                    offset: 0x100
+                },
+            ],
+        })
+    );
+
+    let ServerDriveIoRequest::ServerDriveLockControlRequest(req) = req else {
+        unreachable!()
+    };
+    let encoded = encode_vec(&RdpdrPdu::ClientDriveLockControlResponse(
+        ClientDriveLockControlResponse::new(&req, NtStatus::LOCK_NOT_GRANTED),
+    ))
+    .unwrap();
+    assert_eq!(
+        encoded,
+        [
+            0x72, 0x44, 0x43, 0x49, // RDPDR_CTYP_CORE, PAKID_CORE_DEVICE_IOCOMPLETION
+            0x01, 0x00, 0x00, 0x00, // DeviceId
+            0x03, 0x00, 0x00, 0x00, // CompletionId
+            0x55, 0x00, 0x00, 0xC0, // IoStatus
+            0x00, 0x00, 0x00, 0x00, 0x00, // Padding
+        ]
+    );
+}
+
+const DRIVE_ID: u32 = 1;
+
+/// STATUS_CANCELLED
+const CANCELLED: u32 = 0xC000_0120;
+
+/// Leaves every drive request pending, except for closes.
+#[derive(Debug, Default)]
+struct DeferringBackend {
+    cancelled: Arc<Mutex<Vec<u32>>>,
+}
+
+impl_as_any!(DeferringBackend);
+
+impl RdpdrBackend for DeferringBackend {
+    fn handle_server_device_announce_response(&mut self, _pdu: ServerDeviceAnnounceResponse) -> PduResult<()> {
+        Ok(())
+    }
+
+    fn handle_scard_call(
+        &mut self,
+        _req: DeviceControlRequest<ScardIoCtlCode>,
+        _call: ScardCall,
+    ) -> PduResult<Vec<RdpdrPdu>> {
+        Ok(Vec::new())
+    }
+
+    fn handle_drive_io_request(&mut self, req: ServerDriveIoRequest) -> PduResult<Vec<RdpdrPdu>> {
+        match req {
+            ServerDriveIoRequest::DeviceCloseRequest(req) => {
+                Ok(vec![RdpdrPdu::DeviceCloseResponse(DeviceCloseResponse {
+                    device_io_response: DeviceIoResponse::new(req.device_io_request, NtStatus::SUCCESS),
+                })])
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    fn cancel_io_request(&mut self, req: &DeviceIoRequest) {
+        self.cancelled.lock().unwrap().push(req.completion_id);
+    }
+}
+
+fn deferring_rdpdr() -> (Rdpdr, Arc<Mutex<Vec<u32>>>) {
+    let backend = DeferringBackend::default();
+    let cancelled = Arc::clone(&backend.cancelled);
+    let rdpdr =
+        Rdpdr::new(Box::new(backend), "client".to_owned()).with_drives(Some(vec![(DRIVE_ID, "drive".to_owned())]));
+    (rdpdr, cancelled)
+}
+
+fn read(rdpdr: &mut Rdpdr, file_id: u32, completion_id: u32) {
+    let mut body = Vec::new();
+    body.extend_from_slice(&5u32.to_le_bytes()); // Length
+    body.extend_from_slice(&[0; 8]); // Offset
+    body.extend_from_slice(&[0; 20]); // Padding
+
+    let msgs = rdpdr
+        .process(&io_request(
+            DRIVE_ID,
+            file_id,
+            completion_id,
+            MajorFunction::Read,
+            &body,
+        ))
+        .unwrap();
+    assert!(msgs.is_empty());
+}
+
+fn read_response(file_id: u32, completion_id: u32, data: &[u8]) -> RdpdrPdu {
+    let req = DeviceIoRequest {
+        device_id: DRIVE_ID,
+        file_id,
+        completion_id,
+        major_function: MajorFunction::Read,
+        minor_function: MinorFunction::from(0),
+    };
+    RdpdrPdu::DeviceReadResponse(DeviceReadResponse {
+        device_io_reply: DeviceIoResponse::new(req, NtStatus::SUCCESS),
+        read_data: data.to_vec(),
+    })
+}
+
+#[test]
+fn io_request_completed_later() {
+    let (mut rdpdr, _) = deferring_rdpdr();
+
+    read(&mut rdpdr, 7, 1);
+    assert_eq!(rdpdr.outstanding_io_requests().count(), 1);
+
+    let mut msgs = rdpdr.io_completed(read_response(7, 1, b"hello"));
+    assert_eq!(msgs.len(), 1);
+    let (completion_id, status, body) = io_response(DRIVE_ID, msgs.remove(0));
+    assert_eq!((completion_id, status), (1, 0));
+    // Length, ReadData
+    assert_eq!(body, [5, 0, 0, 0, b'h', b'e', b'l', b'l', b'o']);
+    assert_eq!(rdpdr.outstanding_io_requests().count(), 0);
+
+    // A request is answered only once.
+    assert!(rdpdr.io_completed(read_response(7, 1, b"hello")).is_empty());
+}
+
+#[test]
+fn io_request_cancelled() {
+    let (mut rdpdr, cancelled) = deferring_rdpdr();
+
+    read(&mut rdpdr, 7, 1);
+    let mut msgs = rdpdr.cancel_io_request(1);
+    assert_eq!(msgs.len(), 1);
+    let (completion_id, status, body) = io_response(DRIVE_ID, msgs.remove(0));
+    assert_eq!((completion_id, status), (1, CANCELLED));
+    // Length
+    assert_eq!(body, [0, 0, 0, 0]);
+    assert_eq!(*cancelled.lock().unwrap(), [1]);
+
+    // The backend completing it late is not an issue.
+    assert!(rdpdr.io_completed(read_response(7, 1, b"hello")).is_empty());
+    assert!(rdpdr.cancel_io_request(1).is_empty());
+}
+
+#[test]
+fn io_requests_cancelled_on_close() {
+    let (mut rdpdr, cancelled) = deferring_rdpdr();
+
+    read(&mut rdpdr, 7, 1);
+    read(&mut rdpdr, 8, 2);
+
+    let msgs = rdpdr
+        .process(&io_request(DRIVE_ID, 7, 3, MajorFunction::Close, &[0; 32]))
+        .unwrap();
+    let responses = msgs
+        .into_iter()
+        .map(|msg| {
+            let (completion_id, status, _) = io_response(DRIVE_ID, msg);
+            (completion_id, status)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(responses, [(1, CANCELLED), (3, 0)]);
+    assert_eq!(*cancelled.lock().unwrap(), [1]);
+
+    let outstanding = rdpdr
+        .outstanding_io_requests()
+        .map(|req| req.completion_id)
+        .collect::<Vec<_>>();
+    assert_eq!(outstanding, [2]);
+}
+
+#[cfg(target_os = "linux")]
+mod native {
+    use std::path::{Path, PathBuf};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use ironrdp_core::ReadCursor;
+    use ironrdp_rdpdr::pdu::efs::{
+        Boolean, CompletionFilter, CreateDisposition, CreateOptions, DesiredAccess, DeviceCloseRequest,
+        DeviceCreateRequest, DeviceIoRequest, DeviceReadRequest, DeviceWriteRequest, FileAction,
+        FileAllocationInformation, FileAttributes, FileBasicInformation, FileDispositionInformation,
+        FileEndOfFileInformation, FileInformationClass, FileInformationClassLevel, FileNotifyInformation,
+        FileRenameInformation, LockOperation, MajorFunction, MinorFunction, NtStatus, RdpLockInfo,
+        ServerDriveIoRequest, ServerDriveLockControlRequest, ServerDriveNotifyChangeDirectoryRequest,
+        ServerDriveQueryDirectoryRequest, ServerDriveSetInformationRequest, SharedAccess,
+    };
+    use ironrdp_rdpdr::pdu::RdpdrPdu;
+    use ironrdp_rdpdr::{RdpdrBackend as _, RdpdrMessage, RdpdrMessageProxy};
+    use ironrdp_rdpdr_native::backend::NixRdpdrBackend;
+    use ironrdp_rdpdr_native::fs::{
+        DriveFilesystem, HostFilesystem, MemoryFilesystem, ReadOnlyFilesystem, SandboxedFilesystem,
+    };
+    use ironrdp_svc::SvcMessage;
+
+    use super::super::io_response;
+    use super::{CANCELLED, DRIVE_ID};
+
+    #[derive(Debug)]
+    struct ChannelProxy(mpsc::Sender<RdpdrMessage>);
+
+    impl RdpdrMessageProxy for ChannelProxy {
+        fn send_rdpdr_message(&self, message: RdpdrMessage) {
+            let _ = self.0.send(message);
+        }
+    }
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("ironrdp-{name}-{}", std::process::id()));
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    struct Drive {
+        backend: NixRdpdrBackend,
+        messages: mpsc::Receiver<RdpdrMessage>,
+        root: TempDir,
+    }
+
+    impl Drive {
+        fn new(name: &str) -> Self {
+            Self::with_filesystem(name, |root| HostFilesystem::new(root))
+        }
+
+        /// Creates a drive exposing the file system made from the temporary directory `name`.
+        fn with_filesystem<F: DriveFilesystem + 'static>(name: &str, fs: impl FnOnce(&Path) -> F) -> Self {
+            let root = TempDir::new(name);
+            let (sender, messages) = mpsc::channel();
+            let backend = NixRdpdrBackend::from_filesystem(fs(&root.0)).with_message_proxy(ChannelProxy(sender));
+            Self {
+                backend,
+                messages,
+                root,
+            }
+        }
+
+        fn device_io_request(file_id: u32, completion_id: u32, major_function: MajorFunction) -> DeviceIoRequest {
+            DeviceIoRequest {
+                device_id: DRIVE_ID,
+                file_id,
+                completion_id,
+                major_function,
+                minor_function: MinorFunction::from(0),
+            }
+        }
+
+        fn open(&mut self, path: &str, create_disposition: CreateDisposition, create_options: CreateOptions) -> u32 {
+            self.try_open(path, create_disposition, create_options).unwrap()
+        }
+
+        fn try_open(
+            &mut self,
+            path: &str,
+            create_disposition: CreateDisposition,
+            create_options: CreateOptions,
+        ) -> Result<u32, NtStatus> {
+            let mut responses = self
+                .backend
+                .handle_drive_io_request(ServerDriveIoRequest::ServerCreateDriveRequest(DeviceCreateRequest {
+                    device_io_request: Self::device_io_request(0, 1, MajorFunction::Create),
+                    desired_access: DesiredAccess::FILE_READ_DATA_OR_FILE_LIST_DIRECTORY,
+                    allocation_size: 0,
+                    file_attributes: FileAttributes::empty(),
+                    shared_access: SharedAccess::FILE_SHARE_READ,
+                    create_disposition,
+                    create_options,
+                    path: path.to_owned(),
+                }))
+                .unwrap();
+            assert_eq!(responses.len(), 1);
+            match responses.remove(0) {
+                RdpdrPdu::DeviceCreateResponse(res) if res.device_io_reply.io_status == NtStatus::SUCCESS => {
+                    Ok(res.file_id)
+                }
+                RdpdrPdu::DeviceCreateResponse(res) => Err(res.device_io_reply.io_status),
+                pdu => panic!("unexpected response: {pdu:?}"),
+            }
+        }
+
+        fn open_root(&mut self) -> u32 {
+            self.open("\\", CreateDisposition::FILE_OPEN, CreateOptions::FILE_DIRECTORY_FILE)
+        }
+
+        /// Opens a file for writing, creating it if needed.
+        fn open_file(&mut self, name: &str) -> u32 {
+            self.open(
+                &format!("\\{name}"),
+                CreateDisposition::FILE_OPEN_IF,
+                CreateOptions::FILE_NON_DIRECTORY_FILE,
+            )
+        }
+
+        fn path(&self, name: &str) -> PathBuf {
+            self.root.0.join(name)
+        }
+
+        /// Returns the status of the only response, if any.
+        fn status(responses: Vec<RdpdrPdu>) -> Option<NtStatus> {
+            assert!(responses.len() <= 1);
+            let response = responses.into_iter().next()?;
+            Some(response.device_io_response().unwrap().io_status)
+        }
+
+        fn write(&mut self, file_id: u32, offset: u64, data: &[u8]) -> NtStatus {
+            let responses = self
+                .backend
+                .handle_drive_io_request(ServerDriveIoRequest::DeviceWriteRequest(DeviceWriteRequest {
+                    device_io_request: Self::device_io_request(file_id, 2, MajorFunction::Write),
+                    offset,
+                    write_data: data.to_vec(),
+                }))
+                .unwrap();
+            Self::status(responses).unwrap()
+        }
+
+        fn read(&mut self, file_id: u32, offset: u64, length: u32) -> Vec<u8> {
+            let mut responses = self
+                .backend
+                .handle_drive_io_request(ServerDriveIoRequest::DeviceReadRequest(DeviceReadRequest {
+                    device_io_request: Self::device_io_request(file_id, 2, MajorFunction::Read),
+                    length,
+                    offset,
+                }))
+                .unwrap();
+            assert_eq!(responses.len(), 1);
+            match responses.remove(0) {
+                RdpdrPdu::DeviceReadResponse(res) => {
+                    assert_eq!(res.device_io_reply.io_status, NtStatus::SUCCESS);
+                    res.read_data
+                }
+                pdu => panic!("unexpected response: {pdu:?}"),
+            }
+        }
+
+        /// Returns the names of the entries of the directory `path` opened as `file_id`, the root being empty.
+        fn list(&mut self, file_id: u32, path: &str) -> Vec<String> {
+            let mut names = Vec::new();
+            loop {
+                let mut device_io_request = Self::device_io_request(file_id, 2, MajorFunction::DirectoryControl);
+                device_io_request.minor_function = MinorFunction::IRP_MN_QUERY_DIRECTORY;
+                let responses = self
+                    .backend
+                    .handle_drive_io_request(ServerDriveIoRequest::ServerDriveQueryDirectoryRequest(
+                        ServerDriveQueryDirectoryRequest {
+                            device_io_request,
+                            file_info_class_lvl: FileInformationClassLevel::FILE_NAMES_INFORMATION,
+                            initial_query: u8::from(names.is_empty()),
+                            path: format!("{path}\\*"),
+                        },
+                    ))
+                    .unwrap();
+                match responses.into_iter().next() {
+                    Some(RdpdrPdu::ClientDriveQueryDirectoryResponse(res)) => match res.buffer {
+                        Some(FileInformationClass::Names(info)) => names.push(info.file_name),
+                        _ => break,
+                    },
+                    pdu => panic!("unexpected response: {pdu:?}"),
+                }
+            }
+            names.sort();
+            names
+        }
+
+        fn set_information(&mut self, file_id: u32, set_buffer: FileInformationClass) -> NtStatus {
+            let responses = self
+                .backend
+                .handle_drive_io_request(ServerDriveIoRequest::ServerDriveSetInformationRequest(
+                    ServerDriveSetInformationRequest {
+                        device_io_request: Self::device_io_request(file_id, 2, MajorFunction::SetInformation),
+                        set_buffer,
+                    },
+                ))
+                .unwrap();
+            Self::status(responses).unwrap()
+        }
+
+        fn lock(
+            &mut self,
+            file_id: u32,
+            completion_id: u32,
+            operation: LockOperation,
+            wait: bool,
+            offset: u64,
+            length: u64,
+        ) -> Option<NtStatus> {
+            let responses = self
+                .backend
+                .handle_drive_io_request(ServerDriveIoRequest::ServerDriveLockControlRequest(
+                    ServerDriveLockControlRequest {
+                        device_io_request: Self::device_io_request(file_id, completion_id, MajorFunction::LockControl),
+                        operation,
+                        wait,
+                        locks: vec![RdpLockInfo { length, offset }],
+                    },
+                ))
+                .unwrap();
+            Self::status(responses)
+        }
+
+        fn notify(&mut self, file_id: u32, completion_id: u32, watch_tree: bool) -> Vec<RdpdrPdu> {
+            let mut device_io_request =
+                Self::device_io_request(file_id, completion_id, MajorFunction::DirectoryControl);
+            device_io_request.minor_function = MinorFunction::IRP_MN_NOTIFY_CHANGE_DIRECTORY;
+            self.backend
+                .handle_drive_io_request(ServerDriveIoRequest::ServerDriveNotifyChangeDirectoryRequest(
+                    ServerDriveNotifyChangeDirectoryRequest {
+                        device_io_request,
+                        watch_tree: u8::from(watch_tree),
+                        completion_filter: CompletionFilter::FILE_NOTIFY_CHANGE_FILE_NAME
+                            | CompletionFilter::FILE_NOTIFY_CHANGE_DIR_NAME,
+                    },
+                ))
+                .unwrap()
+        }
+
+        fn close(&mut self, file_id: u32, completion_id: u32) -> Vec<RdpdrPdu> {
+            self.backend
+                .handle_drive_io_request(ServerDriveIoRequest::DeviceCloseRequest(DeviceCloseRequest {
+                    device_io_request: Self::device_io_request(file_id, completion_id, MajorFunction::Close),
+                }))
+                .unwrap()
+        }
+
+        /// Waits for the notification request `completion_id`, answered right away or later through the proxy.
+        fn changes(&mut self, mut responses: Vec<RdpdrPdu>, completion_id: u32) -> Vec<FileNotifyInformation> {
+            let response = match responses.pop() {
+                Some(response) => SvcMessage::from(response),
+                None => match self.messages.recv_timeout(Duration::from_secs(5)).unwrap() {
+                    RdpdrMessage::IoCompleted(pdu) => SvcMessage::from(pdu),
+                },
+            };
+            assert!(responses.is_empty());
+
+            let (id, status, body) = io_response(DRIVE_ID, response);
+            assert_eq!(id, completion_id);
+            assert_eq!(status, 0);
+            let length = usize::try_from(u32::from_le_bytes(body[..4].try_into().unwrap())).unwrap();
+            FileNotifyInformation::decode_list(&mut ReadCursor::new(&body[4..][..length])).unwrap()
+        }
+    }
+
+    #[test]
+    fn notify_change_directory() {
+        let mut drive = Drive::new("notify-change-directory");
+        let file_id = drive.open_root();
+
+        let responses = drive.notify(file_id, 2, false);
+        assert!(responses.is_empty());
+        std::fs::write(drive.root.0.join("new.txt"), b"hello").unwrap();
+        assert_eq!(
+            drive.changes(responses, 2),
+            [FileNotifyInformation::new(FileAction::ADDED, "new.txt".to_owned())]
+        );
+
+        // Changes happening while no request is pending are reported on the next one.
+        std::fs::rename(drive.root.0.join("new.txt"), drive.root.0.join("renamed.txt")).unwrap();
+        let responses = drive.notify(file_id, 3, false);
+        assert_eq!(
+            drive.changes(responses, 3),
+            [
+                FileNotifyInformation::new(FileAction::RENAMED_OLD_NAME, "new.txt".to_owned()),
+                FileNotifyInformation::new(FileAction::RENAMED_NEW_NAME, "renamed.txt".to_owned()),
+            ]
+        );
+
+        std::fs::remove_file(drive.root.0.join("renamed.txt")).unwrap();
+        let responses = drive.notify(file_id, 4, false);
+        assert_eq!(
+            drive.changes(responses, 4),
+            [FileNotifyInformation::new(
+                FileAction::REMOVED,
+                "renamed.txt".to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn notify_change_directory_tree() {
+        let mut drive = Drive::new("notify-change-directory-tree");
+        std::fs::create_dir(drive.root.0.join("sub")).unwrap();
+        let file_id = drive.open_root();
+
+        let responses = drive.notify(file_id, 2, true);
+        std::fs::write(drive.root.0.join("sub").join("inner.txt"), b"hello").unwrap();
+        assert_eq!(
+            drive.changes(responses, 2),
+            [FileNotifyInformation::new(
+                FileAction::ADDED,
+                "sub\\inner.txt".to_owned()
+            )]
+        );
+
+        // Directories created afterwards are watched too.
+        std::fs::create_dir(drive.root.0.join("new")).unwrap();
+        let responses = drive.notify(file_id, 3, true);
+        assert_eq!(
+            drive.changes(responses, 3),
+            [FileNotifyInformation::new(FileAction::ADDED, "new".to_owned())]
+        );
+        std::fs::write(drive.root.0.join("new").join("file.txt"), b"hello").unwrap();
+        let responses = drive.notify(file_id, 4, true);
+        assert_eq!(
+            drive.changes(responses, 4),
+            [FileNotifyInformation::new(
+                FileAction::ADDED,
+                "new\\file.txt".to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn notify_change_directory_cancelled_on_close() {
+        let mut drive = Drive::new("notify-change-directory-close");
+        let file_id = drive.open_root();
+
+        assert!(drive.notify(file_id, 2, false).is_empty());
+        let mut responses = drive.close(file_id, 3).into_iter();
+
+        let (completion_id, status, _) = io_response(DRIVE_ID, SvcMessage::from(responses.next().unwrap()));
+        assert_eq!((completion_id, status), (2, CANCELLED));
+        let (completion_id, status, _) = io_response(DRIVE_ID, SvcMessage::from(responses.next().unwrap()));
+        assert_eq!((completion_id, status), (3, 0));
+        assert!(responses.next().is_none());
+
+        // Nothing is reported once closed.
+        std::fs::write(drive.root.0.join("new.txt"), b"hello").unwrap();
+        assert!(drive.messages.recv_timeout(Duration::from_millis(200)).is_err());
+    }
+
+    #[test]
+    fn set_end_of_file_and_allocation() {
+        let mut drive = Drive::new("set-end-of-file");
+        let file_id = drive.open_file("file.txt");
+
+        let status = drive.set_information(
+            file_id,
+            FileInformationClass::EndOfFile(FileEndOfFileInformation { end_of_file: 10 }),
+        );
+        assert_eq!(status, NtStatus::SUCCESS);
+        assert_eq!(std::fs::metadata(drive.path("file.txt")).unwrap().len(), 10);
+
+        // Shrinking the allocation truncates the file.
+        let status = drive.set_information(
+            file_id,
+            FileInformationClass::Allocation(FileAllocationInformation { allocation_size: 4 }),
+        );
+        assert_eq!(status, NtStatus::SUCCESS);
+        assert_eq!(std::fs::metadata(drive.path("file.txt")).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn set_basic_information() {
+        let mut drive = Drive::new("set-basic-information");
+        let file_id = drive.open_file("file.txt");
+
+        // 2020-01-01T00:00:00Z
+        let last_write_time = 132_223_104_000_000_000;
+        let status = drive.set_information(
+            file_id,
+            FileInformationClass::Basic(FileBasicInformation {
+                creation_time: 0,
+                last_access_time: 0,
+                last_write_time,
+                change_time: 0,
+                file_attributes: FileAttributes::FILE_ATTRIBUTE_READONLY,
+            }),
+        );
+        assert_eq!(status, NtStatus::SUCCESS);
+
+        let meta = std::fs::metadata(drive.path("file.txt")).unwrap();
+        let modified = meta.modified().unwrap().duration_since(std::time::UNIX_EPOCH).unwrap();
+        assert_eq!(modified.as_secs(), 1_577_836_800);
+        assert!(meta.permissions().readonly());
+    }
+
+    #[test]
+    fn delete_on_close() {
+        let mut drive = Drive::new("delete-on-close");
+        let file_id = drive.open_file("file.txt");
+
+        let status = drive.set_information(
+            file_id,
+            FileInformationClass::Disposition(FileDispositionInformation { delete_pending: 1 }),
+        );
+        assert_eq!(status, NtStatus::SUCCESS);
+        assert!(drive.path("file.txt").exists());
+
+        drive.close(file_id, 3);
+        assert!(!drive.path("file.txt").exists());
+
+        // Only empty directories can be deleted.
+        std::fs::create_dir(drive.path("dir")).unwrap();
+        std::fs::write(drive.path("dir").join("file.txt"), b"hello").unwrap();
+        let file_id = drive.open(
+            "\\dir",
+            CreateDisposition::FILE_OPEN,
+            CreateOptions::FILE_DIRECTORY_FILE,
+        );
+        let status = drive.set_information(
+            file_id,
+            FileInformationClass::Disposition(FileDispositionInformation { delete_pending: 1 }),
+        );
+        assert_eq!(status, NtStatus::DIRECTORY_NOT_EMPTY);
+    }
+
+    #[test]
+    fn rename() {
+        let mut drive = Drive::new("rename");
+        std::fs::write(drive.path("a.txt"), b"a").unwrap();
+        std::fs::write(drive.path("b.txt"), b"b").unwrap();
+        let file_id = drive.open_file("a.txt");
+
+        let rename = |replace_if_exists| {
+            FileInformationClass::Rename(FileRenameInformation {
+                replace_if_exists,
+                file_name: "\\b.txt".to_owned(),
+            })
+        };
+        assert_eq!(
+            drive.set_information(file_id, rename(Boolean::False)),
+            NtStatus::OBJECT_NAME_COLLISION
+        );
+        assert_eq!(drive.set_information(file_id, rename(Boolean::True)), NtStatus::SUCCESS);
+        assert!(!drive.path("a.txt").exists());
+        assert_eq!(std::fs::read(drive.path("b.txt")).unwrap(), b"a");
+
+        // The handle follows the file.
+        let status = drive.set_information(
+            file_id,
+            FileInformationClass::Disposition(FileDispositionInformation { delete_pending: 1 }),
+        );
+        assert_eq!(status, NtStatus::SUCCESS);
+        drive.close(file_id, 3);
+        assert!(!drive.path("b.txt").exists());
+    }
+
+    #[test]
+    fn lock_control() {
+        let mut drive = Drive::new("lock-control");
+        let first = drive.open_file("file.txt");
+        let second = drive.open_file("file.txt");
+
+        assert_eq!(
+            drive.lock(first, 2, LockOperation::EXCLUSIVE_LOCK, false, 0, 10),
+            Some(NtStatus::SUCCESS)
+        );
+        assert_eq!(
+            drive.lock(second, 3, LockOperation::SHARED_LOCK, false, 5, 1),
+            Some(NtStatus::LOCK_NOT_GRANTED)
+        );
+        assert_eq!(
+            drive.lock(second, 4, LockOperation::SHARED_LOCK, false, 10, 5),
+            Some(NtStatus::SUCCESS)
+        );
+
+        // Waiting for the lock to be released.
+        assert_eq!(drive.lock(second, 5, LockOperation::SHARED_LOCK, true, 5, 1), None);
+        assert_eq!(
+            drive.lock(first, 6, LockOperation::UNLOCK, false, 0, 5),
+            Some(NtStatus::RANGE_NOT_LOCKED)
+        );
+        assert_eq!(
+            drive.lock(first, 7, LockOperation::UNLOCK, false, 0, 10),
+            Some(NtStatus::SUCCESS)
+        );
+
+        let RdpdrMessage::IoCompleted(pdu) = drive.messages.recv_timeout(Duration::from_secs(5)).unwrap();
+        let reply = pdu.device_io_response().unwrap();
+        assert_eq!((reply.completion_id, reply.io_status), (5, NtStatus::SUCCESS));
+    }
+
+    #[test]
+    fn lock_control_cancelled() {
+        let mut drive = Drive::new("lock-control-cancelled");
+        let first = drive.open_file("file.txt");
+        let second = drive.open_file("file.txt");
+
+        assert_eq!(
+            drive.lock(first, 2, LockOperation::EXCLUSIVE_LOCK, false, 0, 10),
+            Some(NtStatus::SUCCESS)
+        );
+        assert_eq!(drive.lock(second, 3, LockOperation::EXCLUSIVE_LOCK, true, 0, 10), None);
+
+        drive
+            .backend
+            .cancel_io_request(&Drive::device_io_request(second, 3, MajorFunction::LockControl));
+        drive.close(first, 4);
+        assert!(drive.messages.recv_timeout(Duration::from_millis(200)).is_err());
+    }
+
+    #[test]
+    fn memory_drive() {
+        let fs = MemoryFilesystem::new();
+        let mut drive = Drive::with_filesystem("memory-drive", |_| fs.clone());
+        let root = drive.open_root();
+
+        let file_id = drive.open_file("file.txt");
+        assert_eq!(drive.write(file_id, 0, b"hello"), NtStatus::SUCCESS);
+        assert_eq!(drive.write(file_id, 7, b"world"), NtStatus::SUCCESS);
+        assert_eq!(drive.read(file_id, 5, 10), b"\0\0world");
+        assert_eq!(fs.stat("/file.txt").unwrap().size, 12);
+
+        drive.open(
+            "\\dir",
+            CreateDisposition::FILE_CREATE,
+            CreateOptions::FILE_DIRECTORY_FILE,
+        );
+        assert_eq!(drive.list(root, ""), ["dir", "file.txt"]);
+
+        let status = drive.set_information(
+            file_id,
+            FileInformationClass::Rename(FileRenameInformation {
+                replace_if_exists: Boolean::False,
+                file_name: "\\dir\\renamed.txt".to_owned(),
+            }),
+        );
+        assert_eq!(status, NtStatus::SUCCESS);
+        let dir = drive.open(
+            "\\dir",
+            CreateDisposition::FILE_OPEN,
+            CreateOptions::FILE_DIRECTORY_FILE,
+        );
+        assert_eq!(drive.list(dir, "\\dir"), ["renamed.txt"]);
+
+        // Locks are granted without being enforced.
+        assert_eq!(
+            drive.lock(file_id, 3, LockOperation::EXCLUSIVE_LOCK, false, 0, 10),
+            Some(NtStatus::SUCCESS)
+        );
+
+        let status = drive.set_information(
+            file_id,
+            FileInformationClass::Disposition(FileDispositionInformation { delete_pending: 1 }),
+        );
+        assert_eq!(status, NtStatus::SUCCESS);
+        drive.close(file_id, 4);
+        assert!(fs.read_dir("/dir").unwrap().is_empty());
+    }
+
+    #[test]
+    fn read_only_drive() {
+        let fs = MemoryFilesystem::new();
+        let file = fs
+            .open(
+                "/file.txt",
+                &ironrdp_rdpdr_native::fs::OpenOptions {
+                    write: true,
+                    create: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        file.write_at(0, b"hello").unwrap();
+        let mut drive = Drive::with_filesystem("read-only-drive", |_| ReadOnlyFilesystem::new(fs));
+
+        let status = drive.try_open(
+            "\\file.txt",
+            CreateDisposition::FILE_OPEN_IF,
+            CreateOptions::FILE_NON_DIRECTORY_FILE,
+        );
+        assert_eq!(status, Err(NtStatus::ACCESS_DENIED));
+        let status = drive.try_open(
+            "\\dir",
+            CreateDisposition::FILE_CREATE,
+            CreateOptions::FILE_DIRECTORY_FILE,
+        );
+        assert_eq!(status, Err(NtStatus::UNSUCCESSFUL));
+
+        let file_id = drive.open(
+            "\\file.txt",
+            CreateDisposition::FILE_OPEN,
+            CreateOptions::FILE_NON_DIRECTORY_FILE,
+        );
+        assert_eq!(drive.read(file_id, 0, 10), b"hello");
+        assert_eq!(drive.write(file_id, 0, b"bye"), NtStatus::ACCESS_DENIED);
+    }
+
+    #[test]
+    fn host_drive_rejects_relative_components() {
+        let mut drive = Drive::new("host-drive-relative");
+        let outside = drive.root.0.with_extension("outside");
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("secret.txt"), b"secret").unwrap();
+        let escape = format!("\\..\\{}\\secret.txt", outside.file_name().unwrap().to_str().unwrap());
+
+        for path in [escape.as_str(), "\\.\\file.txt", "\\dir\\..\\file.txt"] {
+            let status = drive.try_open(
+                path,
+                CreateDisposition::FILE_OPEN_IF,
+                CreateOptions::FILE_NON_DIRECTORY_FILE,
+            );
+            assert_eq!(status, Err(NtStatus::ACCESS_DENIED), "{path}");
+        }
+
+        // Renaming out of the drive is rejected as well.
+        let file_id = drive.open_file("file.txt");
+        let status = drive.set_information(
+            file_id,
+            FileInformationClass::Rename(FileRenameInformation {
+                replace_if_exists: Boolean::True,
+                file_name: "\\..\\moved.txt".to_owned(),
+            }),
+        );
+        assert_eq!(status, NtStatus::ACCESS_DENIED);
+        assert!(drive.path("file.txt").exists());
+
+        // The file system rejects them too, when used directly.
+        let fs = HostFilesystem::new(&drive.root.0);
+        let error = fs.stat(&format!(
+            "/../{}/secret.txt",
+            outside.file_name().unwrap().to_str().unwrap()
+        ));
+        assert_eq!(error.unwrap_err().kind(), std::io::ErrorKind::PermissionDenied);
+
+        std::fs::remove_dir_all(outside).unwrap();
+    }
+
+    #[test]
+    fn sandboxed_drive() {
+        let mut drive = Drive::with_filesystem("sandboxed-drive", |root| {
+            std::fs::create_dir(root.join("sandbox")).unwrap();
+            std::fs::write(root.join("secret.txt"), b"secret").unwrap();
+            std::os::unix::fs::symlink(root, root.join("sandbox").join("escape")).unwrap();
+            std::os::unix::fs::symlink(root.join("created.txt"), root.join("sandbox").join("dangling")).unwrap();
+            std::os::unix::fs::symlink(root.join("sandbox"), root.join("sandbox").join("inside")).unwrap();
+            SandboxedFilesystem::new(HostFilesystem::new(root), "/sandbox").unwrap()
+        });
+
+        let denied = [
+            "\\..\\secret.txt",
+            "\\escape\\secret.txt",
+            "\\dangling",
+            "\\escape\\new.txt",
+        ];
+        for path in denied {
+            let status = drive.try_open(
+                path,
+                CreateDisposition::FILE_OPEN_IF,
+                CreateOptions::FILE_NON_DIRECTORY_FILE,
+            );
+            assert_eq!(status, Err(NtStatus::ACCESS_DENIED), "{path}");
+        }
+        assert!(!drive.path("created.txt").exists());
+        assert!(!drive.path("new.txt").exists());
+
+        // Links staying within the sandbox are followed.
+        let file_id = drive.open_file("inside\\file.txt");
+        assert_eq!(drive.write(file_id, 0, b"hello"), NtStatus::SUCCESS);
+        assert_eq!(std::fs::read(drive.path("sandbox/file.txt")).unwrap(), b"hello");
+    }
+}
@@ -0,0 +1,135 @@
+use std::io;
+
+use ironrdp_rdpdr_native::fs::{
+    DriveFilesystem, MemoryFilesystem, OpenOptions, ReadOnlyFilesystem, SandboxedFilesystem,
+};
+
+const CREATE: OpenOptions = OpenOptions {
+    read: true,
+    write: true,
+    create: true,
+    create_new: false,
+    truncate: false,
+};
+
+const READ: OpenOptions = OpenOptions {
+    read: true,
+    write: false,
+    create: false,
+    create_new: false,
+    truncate: false,
+};
+
+fn write_file(fs: &dyn DriveFilesystem, path: &str, data: &[u8]) {
+    fs.open(path, &CREATE).unwrap().write_at(0, data).unwrap();
+}
+
+fn read_file(fs: &dyn DriveFilesystem, path: &str) -> Vec<u8> {
+    let mut buf = vec![0; 64];
+    let length = fs.open(path, &READ).unwrap().read_at(0, &mut buf).unwrap();
+    buf.truncate(length);
+    buf
+}
+
+#[test]
+fn memory_filesystem() {
+    let fs = MemoryFilesystem::new();
+    fs.create_dir("/dir").unwrap();
+    fs.create_dir("/dir/sub").unwrap();
+    write_file(&fs, "/dir/sub/file.txt", b"hello");
+    write_file(&fs, "/top.txt", b"top");
+
+    assert_eq!(read_file(&fs, "/dir/sub/file.txt"), b"hello");
+    assert_eq!(fs.stat("/dir/sub/file.txt").unwrap().size, 5);
+    assert!(fs.stat("/dir").unwrap().is_dir);
+    assert_eq!(fs.read_dir("/").unwrap(), ["dir", "top.txt"]);
+    assert_eq!(
+        fs.create_dir("/missing/dir").unwrap_err().kind(),
+        io::ErrorKind::NotFound
+    );
+    assert_eq!(
+        fs.open(
+            "/top.txt",
+            &OpenOptions {
+                create_new: true,
+                ..CREATE
+            }
+        )
+        .unwrap_err()
+        .kind(),
+        io::ErrorKind::AlreadyExists
+    );
+
+    // Renaming a directory moves its content.
+    fs.rename("/dir", "/moved").unwrap();
+    assert_eq!(read_file(&fs, "/moved/sub/file.txt"), b"hello");
+    assert_eq!(fs.stat("/dir/sub").unwrap_err().kind(), io::ErrorKind::NotFound);
+    assert!(fs.rename("/moved", "/moved/sub/inner").is_err());
+
+    assert!(fs.remove_dir("/moved/sub").is_err());
+    fs.remove_file("/moved/sub/file.txt").unwrap();
+    fs.remove_dir("/moved/sub").unwrap();
+    assert!(fs.read_dir("/moved").unwrap().is_empty());
+}
+
+#[test]
+fn memory_file_outlives_its_path() {
+    let fs = MemoryFilesystem::new();
+    write_file(&fs, "/file.txt", b"hello");
+    let file = fs.open("/file.txt", &READ).unwrap();
+    fs.remove_file("/file.txt").unwrap();
+
+    let mut buf = [0; 5];
+    assert_eq!(file.read_at(0, &mut buf).unwrap(), 5);
+    assert_eq!(&buf, b"hello");
+    assert_eq!(
+        file.write_at(0, b"bye").unwrap_err().kind(),
+        io::ErrorKind::PermissionDenied
+    );
+}
+
+#[test]
+fn read_only_filesystem() {
+    let inner = MemoryFilesystem::new();
+    write_file(&inner, "/file.txt", b"hello");
+    let fs = ReadOnlyFilesystem::new(inner);
+
+    assert_eq!(read_file(&fs, "/file.txt"), b"hello");
+    assert!(fs.stat("/file.txt").unwrap().readonly);
+
+    let denied = [
+        fs.open("/file.txt", &CREATE).map(drop),
+        fs.create_dir("/dir"),
+        fs.rename("/file.txt", "/renamed.txt"),
+        fs.remove_file("/file.txt"),
+        fs.open("/file.txt", &READ).unwrap().set_len(0),
+    ];
+    for result in denied {
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+    }
+    assert_eq!(fs.into_inner().read_dir("/").unwrap(), ["file.txt"]);
+}
+
+#[test]
+fn sandboxed_filesystem() {
+    let inner = MemoryFilesystem::new();
+    inner.create_dir("/home").unwrap();
+    inner.create_dir("/home/alice").unwrap();
+    write_file(&inner, "/secret.txt", b"secret");
+    let fs = SandboxedFilesystem::new(inner.clone(), "/home/alice").unwrap();
+
+    write_file(&fs, "/file.txt", b"hello");
+    assert_eq!(read_file(&inner, "/home/alice/file.txt"), b"hello");
+    assert_eq!(fs.read_dir("/").unwrap(), ["file.txt"]);
+    assert_eq!(fs.canonicalize("/file.txt").unwrap(), "/file.txt");
+
+    for path in ["/../../secret.txt", "/./file.txt", "/sub/../file.txt"] {
+        assert_eq!(
+            fs.stat(path).unwrap_err().kind(),
+            io::ErrorKind::PermissionDenied,
+            "{path}"
+        );
+    }
+    assert_eq!(fs.remove_dir("/").unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+    assert!(SandboxedFilesystem::new(inner, "/secret.txt").is_err());
+}
@@ -0,0 +1,535 @@
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use ironrdp_core::{decode, encode_vec, impl_as_any, ReadCursor, WriteCursor};
+use ironrdp_rdpdr::pdu::efs::{DeviceIoRequest, MajorFunction, MinorFunction};
+use ironrdp_rdpdr::pdu::epc::{
+    AddPrinterCacheData, DeletePrinterCacheData, PrinterAnnounceFlags, PrinterCacheData, PrinterDeviceAnnounce,
+    PrinterUsingXps, RenamePrinterCacheData, UpdatePrinterCacheData,
+};
+use ironrdp_rdpdr::pdu::esp::{
+    ControlHandshake, DtrRts, FlowReplace, ModemStatus, Parity, PurgeMask, SerialChars, SerialHandflow,
+    SerialIoCtlCode, SerialLineControl, SerialRequest, SerialResponse, SerialStatus, SerialTimeouts, StopBits,
+    WaitMask,
+};
+use ironrdp_rdpdr::pdu::RdpdrPdu;
+use ironrdp_rdpdr::{NoopRdpdrBackend, PrinterBackend, Rdpdr};
+use ironrdp_svc::{StaticVirtualChannel, SvcMessage, SvcProcessor};
+use rstest::rstest;
+
+mod drive;
+mod fs;
+mod ndr;
+mod scard;
+mod server;
+
+const PRINTER_ID: u32 = 3;
+const FIRST_JOB_ID: u32 = 101;
+
+#[test]
+fn printer_device_announce_encode() {
+    let announce = PrinterDeviceAnnounce {
+        flags: PrinterAnnounceFlags::DEFAULT_PRINTER,
+        pnp_name: String::new(),
+        driver_name: "PS".to_owned(),
+        printer_name: "P".to_owned(),
+        cached_config: vec![0xAA, 0xBB],
+    };
+
+    #[rustfmt::skip]
+    let expected = [
+        0x02, 0x00, 0x00, 0x00, // Flags
+        0x00, 0x00, 0x00, 0x00, // CodePage
+        0x00, 0x00, 0x00, 0x00, // PnPNameLen
+        0x06, 0x00, 0x00, 0x00, // DriverNameLen
+        0x04, 0x00, 0x00, 0x00, // PrintNameLen
+        0x02, 0x00, 0x00, 0x00, // CachedFieldsLen
+        b'P', 0x00, b'S', 0x00, 0x00, 0x00, // DriverName
+        b'P', 0x00, 0x00, 0x00, // PrintName
+        0xAA, 0xBB, // CachedPrinterConfigData
+    ];
+
+    let mut encoded = vec![0; announce.size()];
+    announce.encode(&mut WriteCursor::new(&mut encoded)).unwrap();
+    assert_eq!(encoded, expected);
+
+    let decoded = PrinterDeviceAnnounce::decode(&mut ReadCursor::new(&expected)).unwrap();
+    assert_eq!(decoded, announce);
+}
+
+#[rstest]
+#[case::add(PrinterCacheData::Add(AddPrinterCacheData {
+    port_dos_name: "PRN3".to_owned(),
+    pnp_name: String::new(),
+    driver_name: "MS Publisher Imagesetter".to_owned(),
+    printer_name: "Office".to_owned(),
+    cached_config: vec![1, 2, 3],
+}))]
+#[case::update(PrinterCacheData::Update(UpdatePrinterCacheData {
+    printer_name: "Office".to_owned(),
+    config_data: vec![4, 5, 6, 7],
+}))]
+#[case::delete(PrinterCacheData::Delete(DeletePrinterCacheData {
+    printer_name: "Office".to_owned(),
+}))]
+#[case::rename(PrinterCacheData::Rename(RenamePrinterCacheData {
+    old_printer_name: "Office".to_owned(),
+    new_printer_name: "Home".to_owned(),
+}))]
+fn printer_cache_data_roundtrip(#[case] data: PrinterCacheData) {
+    let encoded = encode_vec(&RdpdrPdu::PrinterCacheData(data.clone())).unwrap();
+
+    // RDPDR_CTYP_PRN, PAKID_PRN_CACHE_DATA
+    assert_eq!(encoded[..4], [0x52, 0x50, 0x43, 0x50]);
+
+    match decode::<RdpdrPdu>(&encoded).unwrap() {
+        RdpdrPdu::PrinterCacheData(decoded) => assert_eq!(decoded, data),
+        pdu => panic!("unexpected PDU: {pdu:?}"),
+    }
+}
+
+#[test]
+fn printer_using_xps_decode() {
+    let encoded = [
+        0x52, 0x50, 0x43, 0x55, // RDPDR_CTYP_PRN, PAKID_PRN_USING_XPS
+        0x03, 0x00, 0x00, 0x00, // PrinterId
+        0x00, 0x00, 0x00, 0x00, // Flags
+    ];
+
+    match decode::<RdpdrPdu>(&encoded).unwrap() {
+        RdpdrPdu::PrinterUsingXps(pdu) => assert_eq!(pdu, PrinterUsingXps { printer_id: PRINTER_ID }),
+        pdu => panic!("unexpected PDU: {pdu:?}"),
+    }
+    assert_eq!(
+        encode_vec(&RdpdrPdu::PrinterUsingXps(PrinterUsingXps { printer_id: PRINTER_ID })).unwrap(),
+        encoded
+    );
+}
+
+#[derive(Debug, Default)]
+struct PrintLog {
+    jobs: Vec<(u32, Vec<u8>, bool)>,
+    cache: Vec<PrinterCacheData>,
+    xps: Vec<u32>,
+}
+
+impl PrintLog {
+    fn job(&mut self, job_id: u32) -> io::Result<&mut (u32, Vec<u8>, bool)> {
+        job_id
+            .checked_sub(FIRST_JOB_ID)
+            .and_then(|index| self.jobs.get_mut(usize::try_from(index).unwrap()))
+            .ok_or_else(|| io::ErrorKind::NotFound.into())
+    }
+}
+
+#[derive(Debug)]
+struct TestPrinterBackend(Arc<Mutex<PrintLog>>);
+
+impl_as_any!(TestPrinterBackend);
+
+impl PrinterBackend for TestPrinterBackend {
+    fn start_job(&mut self, device_id: u32) -> io::Result<u32> {
+        let mut log = self.0.lock().unwrap();
+        let job_id = FIRST_JOB_ID
+            .checked_add(u32::try_from(log.jobs.len()).unwrap())
+            .unwrap();
+        log.jobs.push((device_id, Vec::new(), false));
+        Ok(job_id)
+    }
+
+    fn write_job(&mut self, job_id: u32, data: &[u8]) -> io::Result<()> {
+        let mut log = self.0.lock().unwrap();
+        log.job(job_id)?.1.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn end_job(&mut self, job_id: u32) -> io::Result<()> {
+        let mut log = self.0.lock().unwrap();
+        log.job(job_id)?.2 = true;
+        Ok(())
+    }
+
+    fn handle_cache_data(&mut self, data: PrinterCacheData) -> io::Result<()> {
+        self.0.lock().unwrap().cache.push(data);
+        Ok(())
+    }
+
+    fn set_xps_mode(&mut self, device_id: u32) {
+        self.0.lock().unwrap().xps.push(device_id);
+    }
+}
+
+fn printer_rdpdr() -> (Rdpdr, Arc<Mutex<PrintLog>>) {
+    let log = Arc::new(Mutex::new(PrintLog::default()));
+    let rdpdr = Rdpdr::new(Box::new(NoopRdpdrBackend), "client".to_owned())
+        .with_printers(
+            Box::new(TestPrinterBackend(Arc::clone(&log))),
+            vec![(
+                PRINTER_ID,
+                PrinterDeviceAnnounce::new("MS Publisher Imagesetter".to_owned(), "Office".to_owned()),
+            )],
+        )
+        .unwrap();
+    (rdpdr, log)
+}
+
+fn io_request(device_id: u32, file_id: u32, completion_id: u32, major_function: MajorFunction, body: &[u8]) -> Vec<u8> {
+    let mut encoded = encode_vec(&RdpdrPdu::DeviceIoRequest(DeviceIoRequest {
+        device_id,
+        file_id,
+        completion_id,
+        major_function,
+        minor_function: MinorFunction::from(0),
+    }))
+    .unwrap();
+    encoded.extend_from_slice(body);
+    encoded
+}
+
+/// Returns the completion ID, status and body of a device I/O response.
+fn io_response(device_id: u32, msg: SvcMessage) -> (u32, u32, Vec<u8>) {
+    let payload = StaticVirtualChannel::chunkify(vec![msg])
+        .unwrap()
+        .iter()
+        .flat_map(|chunk| chunk.filled()[8..].to_vec())
+        .collect::<Vec<u8>>();
+
+    // RDPDR_CTYP_CORE, PAKID_CORE_DEVICE_IOCOMPLETION
+    assert_eq!(payload[..4], [0x72, 0x44, 0x43, 0x49]);
+    let read_u32 = |offset: usize| u32::from_le_bytes(payload[offset..][..4].try_into().unwrap());
+    assert_eq!(read_u32(4), device_id);
+
+    (read_u32(8), read_u32(12), payload[16..].to_vec())
+}
+
+#[test]
+fn printer_job_flow() {
+    let (mut rdpdr, log) = printer_rdpdr();
+
+    #[rustfmt::skip]
+    let create = [
+        0x00, 0x00, 0x00, 0x40, // DesiredAccess
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // AllocationSize
+        0x00, 0x00, 0x00, 0x00, // FileAttributes
+        0x00, 0x00, 0x00, 0x00, // SharedAccess
+        0x02, 0x00, 0x00, 0x00, // CreateDisposition
+        0x00, 0x00, 0x00, 0x00, // CreateOptions
+        0x00, 0x00, 0x00, 0x00, // PathLength
+    ];
+    let mut msgs = rdpdr
+        .process(&io_request(PRINTER_ID, 0, 1, MajorFunction::Create, &create))
+        .unwrap();
+    assert_eq!(msgs.len(), 1);
+    let (completion_id, status, body) = io_response(PRINTER_ID, msgs.remove(0));
+    assert_eq!((completion_id, status), (1, 0));
+    // FileId, Information
+    assert_eq!(body, [101, 0, 0, 0, 0]);
+
+    for (completion_id, data) in [(2, &b"%!PS-Adobe"[..]), (3, &b"\nshowpage\n"[..])] {
+        let mut write = Vec::new();
+        write.extend_from_slice(&u32::try_from(data.len()).unwrap().to_le_bytes()); // Length
+        write.extend_from_slice(&[0; 8]); // Offset
+        write.extend_from_slice(&[0; 20]); // Padding
+        write.extend_from_slice(data);
+
+        let mut msgs = rdpdr
+            .process(&io_request(
+                PRINTER_ID,
+                101,
+                completion_id,
+                MajorFunction::Write,
+                &write,
+            ))
+            .unwrap();
+        let (id, status, body) = io_response(PRINTER_ID, msgs.remove(0));
+        assert_eq!((id, status), (completion_id, 0));
+        // Length, Padding
+        assert_eq!(body[..4], u32::try_from(data.len()).unwrap().to_le_bytes());
+    }
+
+    let mut msgs = rdpdr
+        .process(&io_request(PRINTER_ID, 101, 4, MajorFunction::Close, &[0; 32]))
+        .unwrap();
+    let (completion_id, status, _) = io_response(PRINTER_ID, msgs.remove(0));
+    assert_eq!((completion_id, status), (4, 0));
+
+    let log = log.lock().unwrap();
+    assert_eq!(log.jobs, [(PRINTER_ID, b"%!PS-Adobe\nshowpage\n".to_vec(), true)]);
+}
+
+#[test]
+fn printer_write_to_unknown_job_fails() {
+    let (mut rdpdr, _log) = printer_rdpdr();
+
+    let mut write = vec![1, 0, 0, 0];
+    write.extend_from_slice(&[0; 28]);
+    write.push(0xFF);
+
+    let mut msgs = rdpdr
+        .process(&io_request(PRINTER_ID, 7, 1, MajorFunction::Write, &write))
+        .unwrap();
+    let (_, status, body) = io_response(PRINTER_ID, msgs.remove(0));
+    assert_eq!(status, 0xC000_0001); // STATUS_UNSUCCESSFUL
+    assert_eq!(body[..4], [0, 0, 0, 0]);
+}
+
+#[test]
+fn printer_cache_data_and_xps_mode_reach_backend() {
+    let (mut rdpdr, log) = printer_rdpdr();
+
+    let update = PrinterCacheData::Update(UpdatePrinterCacheData {
+        printer_name: "Office".to_owned(),
+        config_data: vec![9, 9],
+    });
+    let msgs = rdpdr
+        .process(&encode_vec(&RdpdrPdu::PrinterCacheData(update.clone())).unwrap())
+        .unwrap();
+    assert!(msgs.is_empty());
+
+    let msgs = rdpdr
+        .process(&encode_vec(&RdpdrPdu::PrinterUsingXps(PrinterUsingXps { printer_id: PRINTER_ID })).unwrap())
+        .unwrap();
+    assert!(msgs.is_empty());
+
+    let log = log.lock().unwrap();
+    assert_eq!(log.cache, [update]);
+    assert_eq!(log.xps, [PRINTER_ID]);
+}
+
+#[rstest]
+#[case(SerialRequest::SetBaudRate(115_200))]
+#[case(SerialRequest::SetLineControl(SerialLineControl { stop_bits: StopBits::Two, parity: Parity::Even, word_length: 7 }))]
+#[case(SerialRequest::SetHandflow(SerialHandflow {
+    control_handshake: ControlHandshake::CTS_HANDSHAKE,
+    flow_replace: FlowReplace::RTS_HANDSHAKE,
+    xon_limit: 1024,
+    xoff_limit: 1024,
+}))]
+#[case(SerialRequest::SetTimeouts(SerialTimeouts { read_interval_timeout: u32::MAX, ..SerialTimeouts::default() }))]
+#[case(SerialRequest::SetChars(SerialChars::default()))]
+#[case(SerialRequest::SetWaitMask(WaitMask::RXCHAR | WaitMask::CTS))]
+#[case(SerialRequest::Purge(PurgeMask::RXCLEAR | PurgeMask::TXCLEAR))]
+#[case(SerialRequest::ImmediateChar(0x1B))]
+#[case(SerialRequest::GetModemStatus)]
+fn serial_request_roundtrip(#[case] request: SerialRequest) {
+    let mut encoded = vec![0; request.size()];
+    request.encode(&mut WriteCursor::new(&mut encoded)).unwrap();
+
+    let decoded = SerialRequest::decode(request.io_control_code(), &mut ReadCursor::new(&encoded)).unwrap();
+    assert_eq!(decoded, request);
+}
+
+#[rstest]
+#[case(SerialIoCtlCode::GetBaudRate, SerialResponse::BaudRate(9600))]
+#[case(SerialIoCtlCode::GetTimeouts, SerialResponse::Timeouts(SerialTimeouts { read_total_timeout_constant: 100, ..SerialTimeouts::default() }))]
+#[case(SerialIoCtlCode::WaitOnMask, SerialResponse::WaitMask(WaitMask::RXCHAR))]
+#[case(SerialIoCtlCode::GetModemStatus, SerialResponse::ModemStatus(ModemStatus::CTS | ModemStatus::DSR))]
+#[case(SerialIoCtlCode::GetDtrRts, SerialResponse::DtrRts(DtrRts::DTR))]
+#[case(SerialIoCtlCode::GetCommStatus, SerialResponse::CommStatus(SerialStatus { amount_in_in_queue: 5, ..SerialStatus::default() }))]
+fn serial_response_roundtrip(#[case] code: SerialIoCtlCode, #[case] response: SerialResponse) {
+    let encoded = encode_vec(&response).unwrap();
+    let decoded = SerialResponse::decode(code, &mut ReadCursor::new(&encoded)).unwrap();
+    assert_eq!(decoded, response);
+}
+
+#[test]
+fn serial_line_control_decode() {
+    // StopBits = TWO_STOP_BITS, Parity = ODD_PARITY, WordLength = 8
+    let line_control = SerialLineControl::decode(&mut ReadCursor::new(&[2, 1, 8])).unwrap();
+    assert_eq!(
+        line_control,
+        SerialLineControl {
+            stop_bits: StopBits::Two,
+            parity: Parity::Odd,
+            word_length: 8,
+        }
+    );
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod tty {
+    use std::fs::File;
+    use std::io::{Read as _, Write as _};
+
+    use ironrdp_rdpdr::PortKind;
+    use ironrdp_rdpdr_native::tty::TtyPortBackend;
+    use nix::pty::openpty;
+
+    use super::*;
+
+    const PORT_ID: u32 = 5;
+
+    struct Port {
+        rdpdr: Rdpdr,
+        master: File,
+        next_completion_id: u32,
+        // Keeps the pty open.
+        _slave: std::os::fd::OwnedFd,
+    }
+
+    impl Port {
+        fn open() -> (Self, u32) {
+            let pty = openpty(None, None).unwrap();
+            let path = nix::unistd::ttyname(&pty.slave).unwrap();
+
+            let rdpdr = Rdpdr::new(Box::new(NoopRdpdrBackend), "client".to_owned()).with_ports(
+                Box::new(TtyPortBackend::new(vec![(PORT_ID, path)])),
+                vec![(PORT_ID, PortKind::Serial, "COM1".to_owned())],
+            );
+
+            let mut port = Self {
+                rdpdr,
+                master: File::from(pty.master),
+                next_completion_id: 1,
+                _slave: pty.slave,
+            };
+
+            #[rustfmt::skip]
+            let create = [
+                0x00, 0x00, 0x00, 0xC0, // DesiredAccess
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // AllocationSize
+                0x00, 0x00, 0x00, 0x00, // FileAttributes
+                0x00, 0x00, 0x00, 0x00, // SharedAccess
+                0x03, 0x00, 0x00, 0x00, // CreateDisposition
+                0x00, 0x00, 0x00, 0x00, // CreateOptions
+                0x00, 0x00, 0x00, 0x00, // PathLength
+            ];
+            let (status, body) = port.request(0, MajorFunction::Create, &create);
+            assert_eq!(status, 0);
+            let file_id = u32::from_le_bytes(body[..4].try_into().unwrap());
+
+            (port, file_id)
+        }
+
+        fn request(&mut self, file_id: u32, major_function: MajorFunction, body: &[u8]) -> (u32, Vec<u8>) {
+            let completion_id = self.next_completion_id;
+            self.next_completion_id = completion_id.checked_add(1).unwrap();
+
+            let mut msgs = self
+                .rdpdr
+                .process(&io_request(PORT_ID, file_id, completion_id, major_function, body))
+                .unwrap();
+            assert_eq!(msgs.len(), 1);
+
+            let (id, status, body) = io_response(PORT_ID, msgs.remove(0));
+            assert_eq!(id, completion_id);
+            (status, body)
+        }
+
+        /// Sends a serial IOCTL, and returns the status and output buffer.
+        fn control(&mut self, file_id: u32, code: u32, input: &[u8]) -> (u32, Vec<u8>) {
+            let mut body = Vec::new();
+            body.extend_from_slice(&64u32.to_le_bytes()); // OutputBufferLength
+            body.extend_from_slice(&u32::try_from(input.len()).unwrap().to_le_bytes()); // InputBufferLength
+            body.extend_from_slice(&code.to_le_bytes()); // IoControlCode
+            body.extend_from_slice(&[0; 20]); // Padding
+            body.extend_from_slice(input);
+
+            let (status, output) = self.request(file_id, MajorFunction::DeviceControl, &body);
+            // OutputBufferLength, OutputBuffer
+            let length = usize::try_from(u32::from_le_bytes(output[..4].try_into().unwrap())).unwrap();
+            (status, output[4..][..length].to_vec())
+        }
+
+        fn serial(&mut self, file_id: u32, request: &SerialRequest) -> SerialResponse {
+            let mut input = vec![0; request.size()];
+            request.encode(&mut WriteCursor::new(&mut input)).unwrap();
+
+            let code = request.io_control_code();
+            let (status, output) = self.control(file_id, u32::from(code), &input);
+            assert_eq!(status, 0, "{request:?}");
+            SerialResponse::decode(code, &mut ReadCursor::new(&output)).unwrap()
+        }
+    }
+
+    #[test]
+    fn tty_port_settings() {
+        let (mut port, file_id) = Port::open();
+
+        port.serial(file_id, &SerialRequest::SetBaudRate(19200));
+        assert_eq!(
+            port.serial(file_id, &SerialRequest::GetBaudRate),
+            SerialResponse::BaudRate(19200)
+        );
+
+        // Linux pseudo-terminals always use 8 data bits without parity.
+        let line_control = SerialLineControl {
+            stop_bits: StopBits::Two,
+            parity: Parity::None,
+            word_length: 8,
+        };
+        port.serial(file_id, &SerialRequest::SetLineControl(line_control));
+        assert_eq!(
+            port.serial(file_id, &SerialRequest::GetLineControl),
+            SerialResponse::LineControl(line_control)
+        );
+
+        port.serial(file_id, &SerialRequest::ClrRts);
+        assert_eq!(
+            port.serial(file_id, &SerialRequest::GetDtrRts),
+            SerialResponse::DtrRts(DtrRts::DTR)
+        );
+
+        port.serial(file_id, &SerialRequest::Purge(PurgeMask::RXCLEAR | PurgeMask::TXCLEAR));
+
+        let (status, _) = port.request(file_id, MajorFunction::Close, &[0; 32]);
+        assert_eq!(status, 0);
+    }
+
+    #[test]
+    fn tty_port_read_write() {
+        let (mut port, file_id) = Port::open();
+
+        let mut write = Vec::new();
+        write.extend_from_slice(&5u32.to_le_bytes()); // Length
+        write.extend_from_slice(&[0; 8]); // Offset
+        write.extend_from_slice(&[0; 20]); // Padding
+        write.extend_from_slice(b"ATZ\r\n");
+        let (status, body) = port.request(file_id, MajorFunction::Write, &write);
+        assert_eq!(status, 0);
+        assert_eq!(body[..4], 5u32.to_le_bytes());
+
+        let mut received = [0; 5];
+        port.master.read_exact(&mut received).unwrap();
+        assert_eq!(&received, b"ATZ\r\n");
+
+        // Reads return what has been received so far.
+        port.serial(
+            file_id,
+            &SerialRequest::SetTimeouts(SerialTimeouts {
+                read_interval_timeout: u32::MAX,
+                ..SerialTimeouts::default()
+            }),
+        );
+        port.master.write_all(b"OK\r\n").unwrap();
+        port.master.flush().unwrap();
+
+        let mut read = Vec::new();
+        read.extend_from_slice(&16u32.to_le_bytes()); // Length
+        read.extend_from_slice(&[0; 8]); // Offset
+        read.extend_from_slice(&[0; 20]); // Padding
+
+        let mut data = Vec::new();
+        for _ in 0..50 {
+            let (status, body) = port.request(file_id, MajorFunction::Read, &read);
+            assert_eq!(status, 0);
+            let length = usize::try_from(u32::from_le_bytes(body[..4].try_into().unwrap())).unwrap();
+            data.extend_from_slice(&body[4..][..length]);
+            if data.len() >= 4 {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(data, b"OK\r\n");
+    }
+
+    #[test]
+    fn tty_port_unsupported_ioctl() {
+        let (mut port, file_id) = Port::open();
+
+        // IOCTL_SERIAL_GET_PROPERTIES
+        let (status, output) = port.control(file_id, 0x001B_0074, &[]);
+        assert_eq!(status, 0xC000_00BB); // STATUS_NOT_SUPPORTED
+        assert!(output.is_empty());
+    }
+}
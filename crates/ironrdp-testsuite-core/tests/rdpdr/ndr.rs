@@ -0,0 +1,297 @@
+use ironrdp_core::{encode_vec, ReadCursor};
+use ironrdp_pdu::utils::CharacterSet;
+use ironrdp_rdpdr::pdu::esc::rpce::Endianness;
+use ironrdp_rdpdr::pdu::esc::{
+    CardProtocol, CardState, ConnectCall, ListReadersReturn, LongReturn, ReturnCode, ScardCall, ScardIoCtlCode,
+    StatusReturn,
+};
+use rstest::rstest;
+
+const CONTEXT: u32 = 0x0102_0304;
+const HANDLE: u32 = 0x0A0B_0C0D;
+const READER: &str = "IronRDP Virtual Reader 0";
+const UUID: &[u8] = &[0x11; 16];
+
+/// An NDR field, serialized in the data representation of the stream.
+#[derive(Clone, Copy)]
+enum Field {
+    U32(u32),
+    /// Byte arrays are not subject to the data representation.
+    Bytes(&'static [u8]),
+    /// Conformant varying UTF-16 string, with its NULL terminator and alignment padding.
+    Str(&'static str),
+    /// UTF-16 multi-string.
+    MultiStr(&'static [&'static str]),
+}
+
+use Field::*;
+
+fn u32_bytes(endianness: Endianness, value: u32) -> [u8; 4] {
+    match endianness {
+        Endianness::LittleEndian => value.to_le_bytes(),
+        Endianness::BigEndian => value.to_be_bytes(),
+    }
+}
+
+fn utf16_bytes(endianness: Endianness, value: &str) -> Vec<u8> {
+    value
+        .encode_utf16()
+        .chain([0])
+        .flat_map(|code_unit| match endianness {
+            Endianness::LittleEndian => code_unit.to_le_bytes(),
+            Endianness::BigEndian => code_unit.to_be_bytes(),
+        })
+        .collect()
+}
+
+/// Encodes `fields` as an RPCE stream, with its stream and type headers.
+fn stream(endianness: Endianness, fields: &[Field]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for field in fields {
+        match *field {
+            U32(value) => body.extend_from_slice(&u32_bytes(endianness, value)),
+            Bytes(bytes) => body.extend_from_slice(bytes),
+            Str(value) => {
+                let length = u32::try_from(value.encode_utf16().chain([0]).count()).unwrap();
+                body.extend_from_slice(&u32_bytes(endianness, length));
+                body.extend_from_slice(&u32_bytes(endianness, 0));
+                body.extend_from_slice(&u32_bytes(endianness, length));
+                body.extend(utf16_bytes(endianness, value));
+                if length % 2 != 0 {
+                    body.extend_from_slice(&[0, 0]);
+                }
+            }
+            MultiStr(values) => {
+                for value in values {
+                    body.extend(utf16_bytes(endianness, value));
+                }
+                body.extend(utf16_bytes(endianness, ""));
+            }
+        }
+    }
+
+    let common_header_length = match endianness {
+        Endianness::LittleEndian => 8u16.to_le_bytes(),
+        Endianness::BigEndian => 8u16.to_be_bytes(),
+    };
+    let mut stream = vec![0x01, endianness.into()];
+    stream.extend_from_slice(&common_header_length);
+    stream.extend_from_slice(&[0xCC; 4]);
+    stream.extend_from_slice(&u32_bytes(endianness, u32::try_from(body.len()).unwrap()));
+    stream.extend_from_slice(&[0; 4]);
+    stream.extend(body);
+    stream
+}
+
+fn decode(io_ctl_code: ScardIoCtlCode, endianness: Endianness, fields: &[Field]) -> ScardCall {
+    ScardCall::decode(io_ctl_code, &mut ReadCursor::new(&stream(endianness, fields))).unwrap()
+}
+
+/// cbContext and pbContext
+const CONTEXT_PTR: [Field; 2] = [U32(4), U32(0x0002_0000)];
+/// cbContext and the context value
+const CONTEXT_VALUE: [Field; 2] = [U32(4), U32(CONTEXT)];
+
+#[rstest]
+#[case::establish_context(ScardIoCtlCode::EstablishContext, &[U32(2)])]
+#[case::list_readers(
+    ScardIoCtlCode::ListReadersW,
+    &[
+        CONTEXT_PTR[0], CONTEXT_PTR[1],
+        U32(44), U32(0x0002_0004), // cBytes, mszGroups
+        U32(0), U32(0xFFFF_FFFF), // fmszReadersIsNULL, cchReaders
+        CONTEXT_VALUE[0], CONTEXT_VALUE[1],
+        U32(44), MultiStr(&["SCard$DefaultReaders"]),
+    ],
+)]
+#[case::get_status_change(
+    ScardIoCtlCode::GetStatusChangeW,
+    &[
+        CONTEXT_PTR[0], CONTEXT_PTR[1],
+        U32(1000), U32(1), U32(0x0002_0004), // dwTimeOut, cReaders, rgReaderStates
+        CONTEXT_VALUE[0], CONTEXT_VALUE[1],
+        U32(1), // cReaders
+        U32(0x0002_0008), U32(0x10), U32(0x20), U32(3), Bytes(&[0x3B; 36]), // szReader, dwCurrentState, dwEventState, cbAtr, rgbAtr
+        Str(READER),
+    ],
+)]
+#[case::connect(
+    ScardIoCtlCode::ConnectW,
+    &[
+        U32(0x0002_0000), // szReader
+        U32(4), U32(0x0002_0004), U32(2), U32(3), // cbContext, pbContext, dwShareMode, dwPreferredProtocols
+        Str(READER),
+        CONTEXT_VALUE[0], CONTEXT_VALUE[1],
+    ],
+)]
+#[case::hcard_and_disposition(
+    ScardIoCtlCode::BeginTransaction,
+    &[
+        CONTEXT_PTR[0], CONTEXT_PTR[1], U32(4), U32(0x0002_0004), // hCard
+        U32(1), // dwDisposition
+        CONTEXT_VALUE[0], CONTEXT_VALUE[1], U32(4), U32(HANDLE),
+    ],
+)]
+#[case::transmit(
+    ScardIoCtlCode::Transmit,
+    &[
+        CONTEXT_PTR[0], CONTEXT_PTR[1], U32(4), U32(0x0002_0004), // hCard
+        U32(2), U32(0), U32(0), // ioSendPci: dwProtocol, cbExtraBytes, pbExtraBytes
+        U32(5), U32(0x0002_0008), // cbSendLength, pbSendBuffer
+        U32(0), U32(0), U32(258), // pioRecvPci, fpbRecvBufferIsNULL, cbRecvLength
+        CONTEXT_VALUE[0], CONTEXT_VALUE[1], U32(4), U32(HANDLE),
+        U32(5), Bytes(&[0x00, 0xCB, 0x3F, 0xFF, 0x05]),
+    ],
+)]
+#[case::status(
+    ScardIoCtlCode::StatusW,
+    &[
+        CONTEXT_PTR[0], CONTEXT_PTR[1], U32(4), U32(0x0002_0004), // hCard
+        U32(0), U32(0xFFFF_FFFF), U32(36), // fmszReaderNamesIsNULL, cchReaderLen, cbAtrLen
+        CONTEXT_VALUE[0], CONTEXT_VALUE[1], U32(4), U32(HANDLE),
+    ],
+)]
+#[case::context(
+    ScardIoCtlCode::IsValidContext,
+    &[CONTEXT_PTR[0], CONTEXT_PTR[1], CONTEXT_VALUE[0], CONTEXT_VALUE[1]],
+)]
+#[case::get_device_type_id(
+    ScardIoCtlCode::GetDeviceTypeId,
+    &[
+        CONTEXT_PTR[0], CONTEXT_PTR[1], U32(0x0002_0004), // szReaderName
+        CONTEXT_VALUE[0], CONTEXT_VALUE[1],
+        Str(READER),
+    ],
+)]
+#[case::read_cache(
+    ScardIoCtlCode::ReadCacheW,
+    &[
+        U32(0x0002_0000), // szLookupName
+        U32(4), U32(0x0002_0004), U32(0x0002_0008), // cbContext, pbContext, CardIdentifier
+        U32(1), U32(0), U32(0x1000), // FreshnessCounter, fPbDataIsNULL, cbDataLen
+        Str("Cached_CardProperty_Read Only Mode_0"),
+        CONTEXT_VALUE[0], CONTEXT_VALUE[1],
+        Bytes(UUID),
+    ],
+)]
+#[case::write_cache(
+    ScardIoCtlCode::WriteCacheW,
+    &[
+        U32(0x0002_0000), // szLookupName
+        U32(4), U32(0x0002_0004), U32(0x0002_0008), // cbContext, pbContext, CardIdentifier
+        U32(1), U32(3), U32(0x0002_000C), // FreshnessCounter, cbDataLen, pbData
+        Str("Cached_ContainerProperty_PinIdentifier_0"),
+        CONTEXT_VALUE[0], CONTEXT_VALUE[1],
+        Bytes(UUID),
+        U32(3), Bytes(&[1, 2, 3]),
+    ],
+)]
+#[case::get_reader_icon(
+    ScardIoCtlCode::GetReaderIcon,
+    &[
+        CONTEXT_PTR[0], CONTEXT_PTR[1], U32(0x0002_0004), // szReaderName
+        CONTEXT_VALUE[0], CONTEXT_VALUE[1],
+        Str(READER),
+    ],
+)]
+fn scard_call_decodes_in_both_endiannesses(#[case] io_ctl_code: ScardIoCtlCode, #[case] fields: &[Field]) {
+    let little_endian = decode(io_ctl_code, Endianness::LittleEndian, fields);
+    let big_endian = decode(io_ctl_code, Endianness::BigEndian, fields);
+
+    assert_ne!(little_endian, ScardCall::Unsupported);
+    assert_eq!(little_endian, big_endian);
+}
+
+#[test]
+fn big_endian_connect_call() {
+    let fields = [
+        U32(0x0002_0000),
+        U32(4),
+        U32(0x0002_0004),
+        U32(2),
+        U32(3),
+        Str(READER),
+        CONTEXT_VALUE[0],
+        CONTEXT_VALUE[1],
+    ];
+    let bytes = stream(Endianness::BigEndian, &fields);
+
+    let call = ConnectCall::decode(&mut ReadCursor::new(&bytes), Some(CharacterSet::Unicode)).unwrap();
+
+    assert_eq!(call.reader, READER);
+    assert_eq!(call.common.context.value, CONTEXT);
+    assert_eq!(call.common.share_mode, 2);
+    assert_eq!(call.common.preferred_protocols.bits(), 3);
+}
+
+#[test]
+fn mismatched_pointer_is_rejected_in_big_endian() {
+    // The pointer is written little-endian in a big-endian stream.
+    let fields = [
+        U32(4),
+        Bytes(&[0x00, 0x00, 0x02, 0x00]),
+        CONTEXT_VALUE[0],
+        CONTEXT_VALUE[1],
+    ];
+    let bytes = stream(Endianness::BigEndian, &fields);
+
+    ScardCall::decode(ScardIoCtlCode::ReleaseContext, &mut ReadCursor::new(&bytes)).unwrap_err();
+}
+
+#[test]
+fn return_encodes_in_requested_endianness() {
+    let ret = LongReturn::new(ReturnCode::InvalidHandle).with_endianness(Endianness::BigEndian);
+
+    assert_eq!(
+        encode_vec(&ret).unwrap(),
+        [
+            0x01, 0x00, 0x00, 0x08, 0xCC, 0xCC, 0xCC, 0xCC, // stream header
+            0x00, 0x00, 0x00, 0x18, 0x00, 0x00, 0x00, 0x00, // type header
+            0x80, 0x10, 0x00, 0x03, // ReturnCode
+            0x00, 0x00, 0x00, 0x00, // padding
+        ]
+    );
+}
+
+#[test]
+fn multistring_return_roundtrips_in_both_endiannesses() {
+    let readers = vec![READER.to_owned(), "Another Reader".to_owned()];
+
+    for endianness in [Endianness::LittleEndian, Endianness::BigEndian] {
+        let ret = ListReadersReturn::new(ReturnCode::Success, readers.clone()).with_endianness(endianness);
+        let expected = stream(
+            endianness,
+            &[
+                U32(0), // ReturnCode
+                U32(82),
+                U32(0x0002_0000),
+                U32(82),
+                MultiStr(&[READER, "Another Reader"]),
+            ],
+        );
+
+        let encoded = encode_vec(&ret).unwrap();
+        // Skip the type header, the object buffer length of which accounts for the headers and the padding.
+        assert_eq!(encoded[..8], expected[..8]);
+        assert_eq!(encoded[16..expected.len()], expected[16..]);
+    }
+}
+
+#[test]
+fn status_return_encodes_atr_as_is() {
+    let atr = [0x3B; 32];
+    let ret = StatusReturn::new(
+        ReturnCode::Success,
+        vec![READER.to_owned()],
+        CardState::Powered,
+        CardProtocol::SCARD_PROTOCOL_T1,
+        atr,
+        32,
+        CharacterSet::Unicode,
+    )
+    .with_endianness(Endianness::BigEndian);
+
+    let encoded = encode_vec(&ret).unwrap();
+    assert!(encoded.windows(32).any(|window| window == atr));
+    assert_eq!(encoded[1], u8::from(Endianness::BigEndian));
+}
@@ -0,0 +1,384 @@
+use std::sync::OnceLock;
+
+use ironrdp_rdpdr::pdu::efs::MajorFunction;
+use ironrdp_rdpdr::Rdpdr;
+use ironrdp_rdpdr_native::scard::{PivCard, VirtualSmartCardBackend};
+use ironrdp_svc::{SvcMessage, SvcProcessor as _};
+use rsa::pkcs1::EncodeRsaPrivateKey as _;
+use rsa::rand_core::OsRng;
+use rsa::{Pkcs1v15Sign, RsaPrivateKey};
+
+use super::{io_request, io_response};
+
+const SCARD_ID: u32 = 1;
+const READER: &str = "IronRDP Virtual Reader 0";
+const PIN: &str = "123456";
+
+/// SCARD_IOCTL_ESTABLISHCONTEXT
+const ESTABLISH_CONTEXT: u32 = 0x0009_0014;
+/// SCARD_IOCTL_ISVALIDCONTEXT
+const IS_VALID_CONTEXT: u32 = 0x0009_001C;
+/// SCARD_IOCTL_LISTREADERSW
+const LIST_READERS_W: u32 = 0x0009_002C;
+/// SCARD_IOCTL_GETSTATUSCHANGEW
+const GET_STATUS_CHANGE_W: u32 = 0x0009_00A4;
+/// SCARD_IOCTL_CANCEL
+const CANCEL: u32 = 0x0009_00A8;
+
+/// SCARD_E_CANCELLED
+const CANCELLED: u32 = 0x8010_0002;
+/// SCARD_E_INVALID_HANDLE
+const INVALID_HANDLE: u32 = 0x8010_0003;
+
+fn private_key() -> &'static RsaPrivateKey {
+    static KEY: OnceLock<RsaPrivateKey> = OnceLock::new();
+    KEY.get_or_init(|| RsaPrivateKey::new(&mut OsRng, 2048).unwrap())
+}
+
+fn certificate() -> Vec<u8> {
+    // The card does not interpret the certificate.
+    (0..1000u32).map(|i| u8::try_from(i % 251).unwrap()).collect()
+}
+
+fn piv_card() -> PivCard {
+    let der = private_key().to_pkcs1_der().unwrap();
+    PivCard::new(certificate(), der.as_bytes(), PIN).unwrap()
+}
+
+/// Sends a command, following up with GET RESPONSE until the whole response is received.
+fn transmit(card: &mut PivCard, apdu: &[u8]) -> (Vec<u8>, [u8; 2]) {
+    let mut data = Vec::new();
+    let mut response = card.transmit(apdu);
+
+    loop {
+        let (body, sw) = response.split_at(response.len().checked_sub(2).unwrap());
+        data.extend_from_slice(body);
+        if sw[0] != 0x61 {
+            return (data, [sw[0], sw[1]]);
+        }
+        response = card.transmit(&[0x00, 0xC0, 0x00, 0x00, sw[1]]);
+    }
+}
+
+fn select(card: &mut PivCard) {
+    let (data, sw) = transmit(
+        card,
+        &[0x00, 0xA4, 0x04, 0x00, 0x05, 0xA0, 0x00, 0x00, 0x03, 0x08, 0x00],
+    );
+    assert_eq!(sw, [0x90, 0x00]);
+    // Application property template, with the PIV application identifier.
+    assert_eq!(data[..2], [0x61, 0x11]);
+    assert_eq!(data[2..10], [0x4F, 0x06, 0x00, 0x00, 0x10, 0x00, 0x01, 0x00]);
+}
+
+fn verify(card: &mut PivCard, pin: &[u8]) -> [u8; 2] {
+    let mut apdu = vec![0x00, 0x20, 0x00, 0x80];
+    if !pin.is_empty() {
+        apdu.push(0x08);
+        apdu.extend(pin.iter().copied().chain(core::iter::repeat(0xFF)).take(8));
+    }
+    transmit(card, &apdu).1
+}
+
+#[test]
+fn piv_commands_require_selection() {
+    let mut card = piv_card();
+
+    let (_, sw) = transmit(&mut card, &[0x00, 0xCB, 0x3F, 0xFF, 0x03, 0x5C, 0x01, 0x7E, 0x00]);
+    assert_eq!(sw, [0x69, 0x85]);
+
+    let (_, sw) = transmit(
+        &mut card,
+        &[0x00, 0xA4, 0x04, 0x00, 0x05, 0xA0, 0x00, 0x00, 0x00, 0x01, 0x00],
+    );
+    assert_eq!(sw, [0x6A, 0x82]);
+}
+
+#[test]
+fn piv_get_data() {
+    let mut card = piv_card();
+    select(&mut card);
+
+    // X.509 Certificate for PIV Authentication, spanning several responses.
+    let (data, sw) = transmit(
+        &mut card,
+        &[0x00, 0xCB, 0x3F, 0xFF, 0x05, 0x5C, 0x03, 0x5F, 0xC1, 0x05, 0x00],
+    );
+    assert_eq!(sw, [0x90, 0x00]);
+    let mut expected = vec![0x53, 0x82, 0x03, 0xF1, 0x70, 0x82, 0x03, 0xE8];
+    expected.extend_from_slice(&certificate());
+    expected.extend_from_slice(&[0x71, 0x01, 0x00, 0xFE, 0x00]);
+    assert_eq!(data, expected);
+
+    // Discovery object.
+    let (data, sw) = transmit(&mut card, &[0x00, 0xCB, 0x3F, 0xFF, 0x03, 0x5C, 0x01, 0x7E, 0x00]);
+    assert_eq!(sw, [0x90, 0x00]);
+    assert_eq!(data[..4], [0x7E, 0x12, 0x4F, 0x0B]);
+
+    // CHUID, with a GUID.
+    let (data, sw) = transmit(
+        &mut card,
+        &[0x00, 0xCB, 0x3F, 0xFF, 0x05, 0x5C, 0x03, 0x5F, 0xC1, 0x02, 0x00],
+    );
+    assert_eq!(sw, [0x90, 0x00]);
+    assert_eq!(data[0], 0x53);
+    assert!(data.windows(2).any(|tag| tag == [0x34, 0x10]));
+
+    // Key history object, which is not present.
+    let (_, sw) = transmit(
+        &mut card,
+        &[0x00, 0xCB, 0x3F, 0xFF, 0x05, 0x5C, 0x03, 0x5F, 0xC1, 0x0C, 0x00],
+    );
+    assert_eq!(sw, [0x6A, 0x82]);
+}
+
+#[test]
+fn piv_card_rejects_oversized_certificate() {
+    let der = private_key().to_pkcs1_der().unwrap();
+
+    let error = PivCard::new(vec![0x30; 65527], der.as_bytes(), PIN).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+    // The largest certificate still fits in the certificate object.
+    let mut card = PivCard::new(vec![0x30; 65526], der.as_bytes(), PIN).unwrap();
+    select(&mut card);
+    let (data, sw) = transmit(
+        &mut card,
+        &[0x00, 0xCB, 0x3F, 0xFF, 0x05, 0x5C, 0x03, 0x5F, 0xC1, 0x05, 0x00],
+    );
+    assert_eq!(sw, [0x90, 0x00]);
+    assert_eq!(data[..8], [0x53, 0x82, 0xFF, 0xFF, 0x70, 0x82, 0xFF, 0xF6]);
+    assert_eq!(data.len(), 4 + 0xFFFF);
+}
+
+#[test]
+fn piv_pin_verification() {
+    let mut card = piv_card();
+    select(&mut card);
+
+    assert_eq!(verify(&mut card, &[]), [0x63, 0xC3]);
+    assert_eq!(verify(&mut card, b"000000"), [0x63, 0xC2]);
+    assert_eq!(verify(&mut card, PIN.as_bytes()), [0x90, 0x00]);
+    assert_eq!(verify(&mut card, &[]), [0x90, 0x00]);
+
+    // Reset of the security status.
+    assert_eq!(transmit(&mut card, &[0x00, 0x20, 0xFF, 0x80]).1, [0x90, 0x00]);
+    assert_eq!(verify(&mut card, &[]), [0x63, 0xC3]);
+
+    for expected in [[0x63, 0xC2], [0x63, 0xC1], [0x69, 0x83]] {
+        assert_eq!(verify(&mut card, b"999999"), expected);
+    }
+    assert_eq!(verify(&mut card, PIN.as_bytes()), [0x69, 0x83]);
+}
+
+#[test]
+fn piv_general_authenticate() {
+    let mut card = piv_card();
+    select(&mut card);
+
+    // PKCS#1 v1.5 padding of a digest, as done by the host.
+    let digest = [0x5A; 32];
+    let mut block = vec![0x00, 0x01];
+    block.resize(256 - digest.len() - 1, 0xFF);
+    block.push(0x00);
+    block.extend_from_slice(&digest);
+
+    // Dynamic authentication template: response requested, challenge.
+    let mut template = vec![0x7C, 0x82, 0x01, 0x06, 0x82, 0x00, 0x81, 0x82, 0x01, 0x00];
+    template.extend_from_slice(&block);
+
+    // Too long for a single short APDU, send it with command chaining.
+    let (first, last) = template.split_at(255);
+    let authenticate = |card: &mut PivCard| {
+        let mut apdu = vec![0x10, 0x87, 0x07, 0x9A, 0xFF];
+        apdu.extend_from_slice(first);
+        assert_eq!(card.transmit(&apdu), [0x90, 0x00]);
+
+        let mut apdu = vec![0x00, 0x87, 0x07, 0x9A, u8::try_from(last.len()).unwrap()];
+        apdu.extend_from_slice(last);
+        apdu.push(0x00);
+        transmit(card, &apdu)
+    };
+
+    let (_, sw) = authenticate(&mut card);
+    assert_eq!(sw, [0x69, 0x82]);
+
+    assert_eq!(verify(&mut card, PIN.as_bytes()), [0x90, 0x00]);
+    let (data, sw) = authenticate(&mut card);
+    assert_eq!(sw, [0x90, 0x00]);
+    assert_eq!(data[..8], [0x7C, 0x82, 0x01, 0x04, 0x82, 0x82, 0x01, 0x00]);
+
+    private_key()
+        .to_public_key()
+        .verify(Pkcs1v15Sign::new_unprefixed(), &digest, &data[8..])
+        .unwrap();
+}
+
+fn scard_rdpdr() -> Rdpdr {
+    let backend = VirtualSmartCardBackend::new(READER.to_owned(), piv_card());
+    Rdpdr::new(Box::new(backend), "client".to_owned()).with_smartcard(SCARD_ID)
+}
+
+/// Encodes an RPCE call, with its stream and type headers, as the input of an SCard IOCTL.
+fn scard_call(completion_id: u32, io_control_code: u32, call: &[u8]) -> Vec<u8> {
+    let mut input = vec![0x01, 0x10, 0x08, 0x00, 0xCC, 0xCC, 0xCC, 0xCC];
+    input.extend_from_slice(&u32::try_from(call.len()).unwrap().to_le_bytes());
+    input.extend_from_slice(&[0; 4]);
+    input.extend_from_slice(call);
+
+    scard_io_control(completion_id, io_control_code, &input)
+}
+
+fn scard_io_control(completion_id: u32, io_control_code: u32, input: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&2048u32.to_le_bytes()); // OutputBufferLength
+    body.extend_from_slice(&u32::try_from(input.len()).unwrap().to_le_bytes()); // InputBufferLength
+    body.extend_from_slice(&io_control_code.to_le_bytes()); // IoControlCode
+    body.extend_from_slice(&[0; 20]); // Padding
+    body.extend_from_slice(input);
+
+    io_request(SCARD_ID, 0, completion_id, MajorFunction::DeviceControl, &body)
+}
+
+/// Returns the completion ID and the RPCE return, without its headers.
+fn scard_return(msg: SvcMessage) -> (u32, Vec<u8>) {
+    let (completion_id, status, body) = io_response(SCARD_ID, msg);
+    assert_eq!(status, 0);
+    // OutputBufferLength, stream header, type header
+    (completion_id, body[20..].to_vec())
+}
+
+fn return_code(ret: &[u8]) -> u32 {
+    u32::from_le_bytes(ret[..4].try_into().unwrap())
+}
+
+/// Encodes a REDIR_SCARDCONTEXT pointer, followed by the given fields and the context value.
+fn context_call(context: u32, fields: &[u8]) -> Vec<u8> {
+    let mut call = vec![];
+    call.extend_from_slice(&4u32.to_le_bytes()); // cbContext
+    call.extend_from_slice(&0x0002_0000u32.to_le_bytes()); // pbContext
+    call.extend_from_slice(fields);
+    call.extend_from_slice(&4u32.to_le_bytes());
+    call.extend_from_slice(&context.to_le_bytes());
+    call
+}
+
+fn establish_context(rdpdr: &mut Rdpdr) -> u32 {
+    let msgs = rdpdr
+        .process(&scard_call(1, ESTABLISH_CONTEXT, &2u32.to_le_bytes()))
+        .unwrap();
+    let (_, ret) = scard_return(msgs.into_iter().next().unwrap());
+    assert_eq!(return_code(&ret), 0);
+    // ReturnCode, cbContext, pbContext, cbContext, then the context.
+    u32::from_le_bytes(ret[16..20].try_into().unwrap())
+}
+
+#[test]
+fn scard_returns_mirror_big_endian_calls() {
+    let mut rdpdr = scard_rdpdr();
+
+    // Stream header, type header and dwScope (SCARD_SCOPE_SYSTEM), all big-endian.
+    let input = [
+        0x01, 0x00, 0x00, 0x08, 0xCC, 0xCC, 0xCC, 0xCC, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x02,
+    ];
+    let msgs = rdpdr.process(&scard_io_control(1, ESTABLISH_CONTEXT, &input)).unwrap();
+    let (_, status, body) = io_response(SCARD_ID, msgs.into_iter().next().unwrap());
+    assert_eq!(status, 0);
+
+    // OutputBufferLength, then the stream header announcing big-endian data.
+    assert_eq!(body[4..8], [0x01, 0x00, 0x00, 0x08]);
+    // ReturnCode and cbContext
+    assert_eq!(body[20..28], [0, 0, 0, 0, 0, 0, 0, 4]);
+}
+
+#[test]
+fn scard_list_readers() {
+    let mut rdpdr = scard_rdpdr();
+    let context = establish_context(&mut rdpdr);
+
+    // cBytes, mszGroups (null), fmszReadersIsNULL, cchReaders
+    let fields = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF];
+    let msgs = rdpdr
+        .process(&scard_call(2, LIST_READERS_W, &context_call(context, &fields)))
+        .unwrap();
+    let (completion_id, ret) = scard_return(msgs.into_iter().next().unwrap());
+    assert_eq!(completion_id, 2);
+    assert_eq!(return_code(&ret), 0);
+
+    let readers = READER
+        .encode_utf16()
+        .chain([0, 0])
+        .flat_map(u16::to_le_bytes)
+        .collect::<Vec<u8>>();
+    let length = u32::try_from(readers.len()).unwrap().to_le_bytes();
+    assert_eq!(ret[4..8], length);
+    assert_eq!(ret[12..16], length);
+    assert_eq!(ret[16..][..readers.len()], readers);
+
+    let msgs = rdpdr
+        .process(&scard_call(
+            3,
+            IS_VALID_CONTEXT,
+            &context_call(context.wrapping_add(1), &[]),
+        ))
+        .unwrap();
+    let (_, ret) = scard_return(msgs.into_iter().next().unwrap());
+    assert_eq!(return_code(&ret), INVALID_HANDLE);
+}
+
+#[test]
+fn scard_get_status_change_waits_until_cancelled() {
+    let mut rdpdr = scard_rdpdr();
+    let context = establish_context(&mut rdpdr);
+
+    let get_status_change = |current_state: u32| {
+        // dwTimeout (INFINITE), cReaders, rgReaderStates
+        let mut fields = vec![0xFF, 0xFF, 0xFF, 0xFF, 1, 0, 0, 0];
+        fields.extend_from_slice(&0x0002_0004u32.to_le_bytes());
+        let mut call = context_call(context, &fields);
+
+        call.extend_from_slice(&1u32.to_le_bytes());
+        call.extend_from_slice(&0x0002_0008u32.to_le_bytes()); // szReader
+        call.extend_from_slice(&current_state.to_le_bytes()); // dwCurrentState
+        call.extend_from_slice(&[0; 4]); // dwEventState
+        call.extend_from_slice(&[0; 4]); // cbAtr
+        call.extend_from_slice(&[0; 36]); // rgbAtr
+
+        let reader = READER.encode_utf16().chain([0]).collect::<Vec<u16>>();
+        let length = u32::try_from(reader.len()).unwrap().to_le_bytes();
+        call.extend_from_slice(&length); // MaximumCount
+        call.extend_from_slice(&[0; 4]); // Offset
+        call.extend_from_slice(&length); // ActualCount
+        call.extend(reader.iter().flat_map(|c| c.to_le_bytes()));
+        if reader.len() % 2 != 0 {
+            call.extend_from_slice(&[0; 2]);
+        }
+        call
+    };
+
+    // The card is reported as present right away when the caller does not know about it yet.
+    let msgs = rdpdr
+        .process(&scard_call(2, GET_STATUS_CHANGE_W, &get_status_change(0)))
+        .unwrap();
+    let (_, ret) = scard_return(msgs.into_iter().next().unwrap());
+    assert_eq!(return_code(&ret), 0);
+    // ReturnCode, cReaders, rgReaderStates, cReaders, then dwCurrentState, dwEventState and cbAtr.
+    let event_state = u32::from_le_bytes(ret[20..24].try_into().unwrap());
+    assert_eq!(event_state, 0x22); // SCARD_STATE_PRESENT | SCARD_STATE_CHANGED
+    assert_eq!(ret[24..28], 5u32.to_le_bytes());
+
+    // Nothing will change anymore, the call is only answered once cancelled.
+    let msgs = rdpdr
+        .process(&scard_call(3, GET_STATUS_CHANGE_W, &get_status_change(0x20)))
+        .unwrap();
+    assert!(msgs.is_empty());
+
+    let msgs = rdpdr
+        .process(&scard_call(4, CANCEL, &context_call(context, &[])))
+        .unwrap();
+    let returns = msgs.into_iter().map(scard_return).collect::<Vec<_>>();
+    assert_eq!(returns.len(), 2);
+    assert_eq!((returns[0].0, return_code(&returns[0].1)), (3, CANCELLED));
+    assert_eq!((returns[1].0, return_code(&returns[1].1)), (4, 0));
+}
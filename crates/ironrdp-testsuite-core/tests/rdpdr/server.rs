@@ -0,0 +1,482 @@
+use std::sync::{Arc, Mutex};
+
+use ironrdp_core::{decode, encode_vec, ReadCursor};
+use ironrdp_rdpdr::pdu::efs::{
+    AnyIoCtlCode, ClientDeviceListRemove, ClientDriveQueryDirectoryResponse, ClientDriveQueryInformationResponse,
+    CreateDisposition, CreateOptions, DesiredAccess, DeviceAnnounceHeader, DeviceCloseRequest, DeviceControlRequest,
+    DeviceCreateRequest, DeviceIoRequest, DeviceIoResponse, DeviceReadRequest, DeviceWriteRequest, FileAttributes,
+    FileBothDirectoryInformation, FileInformationClass, FileInformationClassLevel, FileNamesInformation,
+    FileStandardInformation, MajorFunction, MinorFunction, NtStatus, ServerDeviceIoRequest, ServerDriveIoRequest,
+    ServerDriveQueryDirectoryRequest, ServerDriveQueryInformationRequest, SharedAccess,
+};
+use ironrdp_rdpdr::pdu::RdpdrPdu;
+use ironrdp_rdpdr::server::{DeviceIoCompletion, RdpdrServer, RdpdrServerHandler};
+use ironrdp_rdpdr::{NoopRdpdrBackend, Rdpdr, RdpdrBackend};
+use ironrdp_svc::{StaticVirtualChannel, SvcMessage, SvcProcessor};
+use rstest::rstest;
+
+const SMARTCARD_ID: u32 = 1;
+const DRIVE_ID: u32 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Event {
+    Announced(u32, String),
+    Removed(u32),
+    Completed(DeviceIoCompletion),
+}
+
+/// Accepts every device but smart cards, and records the events.
+#[derive(Debug)]
+struct TestHandler(Arc<Mutex<Vec<Event>>>);
+
+impl RdpdrServerHandler for TestHandler {
+    fn device_announced(&mut self, device: &DeviceAnnounceHeader) -> bool {
+        self.0
+            .lock()
+            .unwrap()
+            .push(Event::Announced(device.device_id(), device.name()));
+        device.device_id() != SMARTCARD_ID
+    }
+
+    fn device_removed(&mut self, device_id: u32) {
+        self.0.lock().unwrap().push(Event::Removed(device_id));
+    }
+
+    fn io_completed(&mut self, completion: DeviceIoCompletion) {
+        self.0.lock().unwrap().push(Event::Completed(completion));
+    }
+}
+
+/// Returns the payload of `msg`, as received on the other side of the channel.
+fn payload(msg: SvcMessage) -> Vec<u8> {
+    StaticVirtualChannel::chunkify(vec![msg])
+        .unwrap()
+        .iter()
+        .flat_map(|chunk| chunk.filled()[8..].to_vec())
+        .collect()
+}
+
+/// Delivers the messages sent to the client, and then the replies of each side until there are none left.
+fn exchange(server: &mut RdpdrServer, client: &mut Rdpdr, mut to_client: Vec<SvcMessage>) {
+    while !to_client.is_empty() {
+        let mut to_server = Vec::new();
+        for msg in to_client {
+            to_server.extend(client.process(&payload(msg)).unwrap());
+        }
+
+        to_client = Vec::new();
+        for msg in to_server {
+            to_client.extend(server.process(&payload(msg)).unwrap());
+        }
+    }
+}
+
+/// Connects a server to a client redirecting a smart card and a drive.
+fn connect(backend: Box<dyn RdpdrBackend>) -> (RdpdrServer, Rdpdr, Arc<Mutex<Vec<Event>>>) {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let mut server = RdpdrServer::new(Box::new(TestHandler(Arc::clone(&events))));
+    let mut client = Rdpdr::new(backend, "client".to_owned())
+        .with_smartcard(SMARTCARD_ID)
+        .with_drives(Some(vec![(DRIVE_ID, "share".to_owned())]));
+
+    let start = server.start().unwrap();
+    exchange(&mut server, &mut client, start);
+
+    (server, client, events)
+}
+
+fn take_events(events: &Mutex<Vec<Event>>) -> Vec<Event> {
+    std::mem::take(&mut *events.lock().unwrap())
+}
+
+fn device_io_request(file_id: u32, completion_id: u32, major_function: MajorFunction) -> DeviceIoRequest {
+    DeviceIoRequest {
+        device_id: DRIVE_ID,
+        file_id,
+        completion_id,
+        major_function,
+        minor_function: MinorFunction::from(0),
+    }
+}
+
+fn read_request(file_id: u32, completion_id: u32) -> ServerDeviceIoRequest {
+    ServerDeviceIoRequest::Read(DeviceReadRequest {
+        device_io_request: device_io_request(file_id, completion_id, MajorFunction::Read),
+        length: 16,
+        offset: 0,
+    })
+}
+
+#[test]
+fn handshake_accepts_devices() {
+    let (server, _client, events) = connect(Box::new(NoopRdpdrBackend));
+
+    assert_eq!(server.computer_name(), Some("client"));
+    assert_eq!(
+        take_events(&events),
+        [
+            Event::Announced(SMARTCARD_ID, "SCARD".to_owned()),
+            Event::Announced(DRIVE_ID, "share".to_owned()),
+        ]
+    );
+    let devices = server.devices().map(|device| device.device_id()).collect::<Vec<_>>();
+    assert_eq!(devices, [DRIVE_ID]);
+}
+
+#[test]
+fn request_to_unknown_device_fails() {
+    let (mut server, _client, events) = connect(Box::new(NoopRdpdrBackend));
+    take_events(&events);
+
+    let mut request = read_request(1, 7);
+    if let ServerDeviceIoRequest::Read(req) = &mut request {
+        req.device_io_request.device_id = SMARTCARD_ID;
+    }
+    let msgs = server.io_request(request).unwrap();
+    assert!(Vec::<SvcMessage>::from(msgs).is_empty());
+
+    let events = take_events(&events);
+    let [Event::Completed(DeviceIoCompletion::Read(rsp))] = events.as_slice() else {
+        panic!("unexpected events: {events:?}");
+    };
+    assert_eq!(rsp.device_io_reply.completion_id, 7);
+    assert_eq!(rsp.device_io_reply.io_status, NtStatus::NO_SUCH_FILE);
+}
+
+#[test]
+fn completion_id_must_be_unique() {
+    let (mut server, _client, _events) = connect(Box::new(NoopRdpdrBackend));
+
+    server.io_request(read_request(1, 7)).unwrap();
+    assert!(server.io_request(read_request(1, 7)).is_err());
+}
+
+#[test]
+fn device_removal_cancels_requests() {
+    let (mut server, _client, events) = connect(Box::new(NoopRdpdrBackend));
+    take_events(&events);
+
+    server.io_request(read_request(1, 7)).unwrap();
+    let remove = encode_vec(&RdpdrPdu::ClientDeviceListRemove(ClientDeviceListRemove {
+        device_ids: vec![DRIVE_ID],
+    }))
+    .unwrap();
+    assert!(server.process(&remove).unwrap().is_empty());
+
+    let events = take_events(&events);
+    let [Event::Completed(DeviceIoCompletion::Read(rsp)), Event::Removed(DRIVE_ID)] = events.as_slice() else {
+        panic!("unexpected events: {events:?}");
+    };
+    assert_eq!(rsp.device_io_reply.completion_id, 7);
+    assert_eq!(rsp.device_io_reply.io_status, NtStatus::CANCELLED);
+    assert_eq!(server.devices().count(), 0);
+}
+
+#[test]
+fn device_list_remove_roundtrip() {
+    let pdu = RdpdrPdu::ClientDeviceListRemove(ClientDeviceListRemove { device_ids: vec![1, 2] });
+
+    #[rustfmt::skip]
+    let expected = [
+        0x72, 0x44, 0x4D, 0x44, // RDPDR_CTYP_CORE, PAKID_CORE_DEVICELIST_REMOVE
+        0x02, 0x00, 0x00, 0x00, // DeviceCount
+        0x01, 0x00, 0x00, 0x00, // DeviceIds
+        0x02, 0x00, 0x00, 0x00,
+    ];
+    assert_eq!(encode_vec(&pdu).unwrap(), expected);
+
+    let decoded = ClientDeviceListRemove::decode(&mut ReadCursor::new(&expected[4..])).unwrap();
+    assert_eq!(decoded.device_ids, [1, 2]);
+}
+
+#[test]
+fn user_logged_on_roundtrip() {
+    let encoded = encode_vec(&RdpdrPdu::UserLoggedOn).unwrap();
+    assert_eq!(encoded, [0x72, 0x44, 0x4C, 0x55]);
+    assert!(matches!(decode::<RdpdrPdu>(&encoded).unwrap(), RdpdrPdu::UserLoggedOn));
+}
+
+#[rstest]
+#[case::create(ServerDeviceIoRequest::Create(DeviceCreateRequest {
+    device_io_request: device_io_request(0, 1, MajorFunction::Create),
+    desired_access: DesiredAccess::FILE_READ_DATA_OR_FILE_LIST_DIRECTORY,
+    allocation_size: 0,
+    file_attributes: FileAttributes::empty(),
+    shared_access: SharedAccess::FILE_SHARE_READ,
+    create_disposition: CreateDisposition::FILE_OPEN,
+    create_options: CreateOptions::FILE_NON_DIRECTORY_FILE,
+    path: "\\dir\\file.txt".to_owned(),
+}))]
+#[case::close(ServerDeviceIoRequest::Close(DeviceCloseRequest {
+    device_io_request: device_io_request(3, 2, MajorFunction::Close),
+}))]
+#[case::read(read_request(3, 3))]
+#[case::write(ServerDeviceIoRequest::Write(DeviceWriteRequest {
+    device_io_request: device_io_request(3, 4, MajorFunction::Write),
+    offset: 10,
+    write_data: b"hello".to_vec(),
+}))]
+#[case::query_information(ServerDeviceIoRequest::QueryInformation(ServerDriveQueryInformationRequest {
+    device_io_request: device_io_request(3, 5, MajorFunction::QueryInformation),
+    file_info_class_lvl: FileInformationClassLevel::FILE_STANDARD_INFORMATION,
+}))]
+#[case::initial_query_directory(ServerDeviceIoRequest::QueryDirectory(ServerDriveQueryDirectoryRequest {
+    device_io_request: DeviceIoRequest {
+        minor_function: MinorFunction::IRP_MN_QUERY_DIRECTORY,
+        ..device_io_request(3, 6, MajorFunction::DirectoryControl)
+    },
+    file_info_class_lvl: FileInformationClassLevel::FILE_BOTH_DIRECTORY_INFORMATION,
+    initial_query: 1,
+    path: "\\dir\\*".to_owned(),
+}))]
+#[case::next_query_directory(ServerDeviceIoRequest::QueryDirectory(ServerDriveQueryDirectoryRequest {
+    device_io_request: DeviceIoRequest {
+        minor_function: MinorFunction::IRP_MN_QUERY_DIRECTORY,
+        ..device_io_request(3, 7, MajorFunction::DirectoryControl)
+    },
+    file_info_class_lvl: FileInformationClassLevel::FILE_BOTH_DIRECTORY_INFORMATION,
+    initial_query: 0,
+    path: String::new(),
+}))]
+fn server_io_request_is_decoded_by_client(#[case] request: ServerDeviceIoRequest) {
+    let encoded = encode_vec(&RdpdrPdu::ServerDeviceIoRequest(request.clone())).unwrap();
+    let mut src = ReadCursor::new(&encoded);
+    let RdpdrPdu::DeviceIoRequest(header) = ironrdp_core::decode_cursor::<RdpdrPdu>(&mut src).unwrap() else {
+        panic!("not a device I/O request");
+    };
+    let decoded = ServerDriveIoRequest::decode(header, &mut src).unwrap();
+
+    let expected = match request {
+        ServerDeviceIoRequest::Create(req) => ServerDriveIoRequest::from(req),
+        ServerDeviceIoRequest::Close(req) => req.into(),
+        ServerDeviceIoRequest::Read(req) => req.into(),
+        ServerDeviceIoRequest::Write(req) => req.into(),
+        ServerDeviceIoRequest::QueryInformation(req) => req.into(),
+        ServerDeviceIoRequest::QueryDirectory(req) => req.into(),
+        ServerDeviceIoRequest::DeviceControl { .. } => unreachable!(),
+    };
+    assert_eq!(decoded, expected);
+}
+
+#[test]
+fn device_control_request_encode() {
+    let request = ServerDeviceIoRequest::DeviceControl {
+        request: DeviceControlRequest {
+            header: DeviceIoRequest {
+                device_id: SMARTCARD_ID,
+                ..device_io_request(0, 8, MajorFunction::DeviceControl)
+            },
+            output_buffer_length: 2048,
+            input_buffer_length: 0,
+            io_control_code: AnyIoCtlCode(0x0009_00A0),
+        },
+        input_buffer: vec![1, 2, 3],
+    };
+    let encoded = encode_vec(&RdpdrPdu::ServerDeviceIoRequest(request)).unwrap();
+
+    let mut src = ReadCursor::new(&encoded);
+    let RdpdrPdu::DeviceIoRequest(header) = ironrdp_core::decode_cursor::<RdpdrPdu>(&mut src).unwrap() else {
+        panic!("not a device I/O request");
+    };
+    let decoded = DeviceControlRequest::<AnyIoCtlCode>::decode(header, &mut src).unwrap();
+    assert_eq!(decoded.header.completion_id, 8);
+    assert_eq!(decoded.output_buffer_length, 2048);
+    assert_eq!(decoded.input_buffer_length, 3);
+    assert_eq!(decoded.io_control_code, AnyIoCtlCode(0x0009_00A0));
+    assert_eq!(src.remaining(), [1, 2, 3]);
+}
+
+#[rstest]
+#[case::standard(FileInformationClass::Standard(FileStandardInformation {
+    allocation_size: 4096,
+    end_of_file: 12,
+    number_of_links: 1,
+    delete_pending: ironrdp_rdpdr::pdu::efs::Boolean::False,
+    directory: ironrdp_rdpdr::pdu::efs::Boolean::False,
+}))]
+#[case::both_directory(FileInformationClass::BothDirectory(FileBothDirectoryInformation::new(
+    1,
+    2,
+    3,
+    4,
+    12,
+    FileAttributes::FILE_ATTRIBUTE_ARCHIVE,
+    "file.txt".to_owned(),
+)))]
+#[case::names(FileInformationClass::Names(FileNamesInformation::new("file.txt".to_owned())))]
+fn information_response_roundtrip(#[case] info: FileInformationClass) {
+    let level = match &info {
+        FileInformationClass::Standard(_) => FileInformationClassLevel::FILE_STANDARD_INFORMATION,
+        FileInformationClass::BothDirectory(_) => FileInformationClassLevel::FILE_BOTH_DIRECTORY_INFORMATION,
+        FileInformationClass::Names(_) => FileInformationClassLevel::FILE_NAMES_INFORMATION,
+        _ => unreachable!(),
+    };
+    let reply = DeviceIoResponse {
+        device_id: DRIVE_ID,
+        completion_id: 1,
+        io_status: NtStatus::SUCCESS,
+    };
+
+    let query_information = ClientDriveQueryInformationResponse {
+        device_io_response: reply.clone(),
+        buffer: Some(info.clone()),
+    };
+    let encoded = encode_vec(&RdpdrPdu::ClientDriveQueryInformationResponse(
+        query_information.clone(),
+    ))
+    .unwrap();
+    // Skips the RDPDR header and the reply header.
+    let mut src = ReadCursor::new(&encoded[16..]);
+    let decoded = ClientDriveQueryInformationResponse::decode(reply.clone(), level, &mut src).unwrap();
+    assert_eq!(decoded, query_information);
+
+    let query_directory = ClientDriveQueryDirectoryResponse {
+        device_io_reply: reply.clone(),
+        buffer: Some(info),
+    };
+    let encoded = encode_vec(&RdpdrPdu::ClientDriveQueryDirectoryResponse(query_directory.clone())).unwrap();
+    let mut src = ReadCursor::new(&encoded[16..]);
+    let decoded = ClientDriveQueryDirectoryResponse::decode(reply, level, &mut src).unwrap();
+    assert_eq!(decoded, query_directory);
+}
+
+#[cfg(target_os = "linux")]
+mod native {
+    use ironrdp_rdpdr_native::backend::NixRdpdrBackend;
+    use ironrdp_rdpdr_native::fs::MemoryFilesystem;
+
+    use super::*;
+
+    /// Sends `request` to the client, and returns its completion.
+    fn complete(
+        server: &mut RdpdrServer,
+        client: &mut Rdpdr,
+        events: &Mutex<Vec<Event>>,
+        request: ServerDeviceIoRequest,
+    ) -> DeviceIoCompletion {
+        let msgs = server.io_request(request).unwrap();
+        exchange(server, client, msgs.into());
+
+        let mut events = take_events(events);
+        assert_eq!(events.len(), 1, "{events:?}");
+        match events.remove(0) {
+            Event::Completed(completion) => completion,
+            event => panic!("unexpected event: {event:?}"),
+        }
+    }
+
+    fn create(
+        path: &str,
+        completion_id: u32,
+        disposition: CreateDisposition,
+        options: CreateOptions,
+    ) -> ServerDeviceIoRequest {
+        ServerDeviceIoRequest::Create(DeviceCreateRequest {
+            device_io_request: device_io_request(0, completion_id, MajorFunction::Create),
+            desired_access: DesiredAccess::FILE_READ_DATA_OR_FILE_LIST_DIRECTORY
+                | DesiredAccess::FILE_WRITE_DATA_OR_FILE_ADD_FILE,
+            allocation_size: 0,
+            file_attributes: FileAttributes::empty(),
+            shared_access: SharedAccess::FILE_SHARE_READ,
+            create_disposition: disposition,
+            create_options: options,
+            path: path.to_owned(),
+        })
+    }
+
+    fn query_directory(file_id: u32, completion_id: u32, pattern: Option<&str>) -> ServerDeviceIoRequest {
+        ServerDeviceIoRequest::QueryDirectory(ServerDriveQueryDirectoryRequest {
+            device_io_request: DeviceIoRequest {
+                minor_function: MinorFunction::IRP_MN_QUERY_DIRECTORY,
+                ..device_io_request(file_id, completion_id, MajorFunction::DirectoryControl)
+            },
+            file_info_class_lvl: FileInformationClassLevel::FILE_BOTH_DIRECTORY_INFORMATION,
+            initial_query: u8::from(pattern.is_some()),
+            path: pattern.unwrap_or_default().to_owned(),
+        })
+    }
+
+    #[test]
+    fn client_drive_access() {
+        let (mut server, mut client, events) =
+            connect(Box::new(NixRdpdrBackend::from_filesystem(MemoryFilesystem::new())));
+        take_events(&events);
+
+        let request = create(
+            "\\file.txt",
+            1,
+            CreateDisposition::FILE_CREATE,
+            CreateOptions::FILE_NON_DIRECTORY_FILE,
+        );
+        let DeviceIoCompletion::Create(rsp) = complete(&mut server, &mut client, &events, request) else {
+            panic!("unexpected completion");
+        };
+        assert_eq!(rsp.device_io_reply.io_status, NtStatus::SUCCESS);
+        let file_id = rsp.file_id;
+
+        let request = ServerDeviceIoRequest::Write(DeviceWriteRequest {
+            device_io_request: device_io_request(file_id, 2, MajorFunction::Write),
+            offset: 0,
+            write_data: b"hello".to_vec(),
+        });
+        let DeviceIoCompletion::Write(rsp) = complete(&mut server, &mut client, &events, request) else {
+            panic!("unexpected completion");
+        };
+        assert_eq!(rsp.length, 5);
+
+        let DeviceIoCompletion::Read(rsp) = complete(&mut server, &mut client, &events, read_request(file_id, 3))
+        else {
+            panic!("unexpected completion");
+        };
+        assert_eq!(rsp.read_data, b"hello");
+
+        let request = ServerDeviceIoRequest::QueryInformation(ServerDriveQueryInformationRequest {
+            device_io_request: device_io_request(file_id, 4, MajorFunction::QueryInformation),
+            file_info_class_lvl: FileInformationClassLevel::FILE_STANDARD_INFORMATION,
+        });
+        let DeviceIoCompletion::QueryInformation(rsp) = complete(&mut server, &mut client, &events, request) else {
+            panic!("unexpected completion");
+        };
+        let Some(FileInformationClass::Standard(info)) = rsp.buffer else {
+            panic!("unexpected information: {:?}", rsp.buffer);
+        };
+        assert_eq!(info.end_of_file, 5);
+
+        let request = ServerDeviceIoRequest::Close(DeviceCloseRequest {
+            device_io_request: device_io_request(file_id, 5, MajorFunction::Close),
+        });
+        let DeviceIoCompletion::Close(rsp) = complete(&mut server, &mut client, &events, request) else {
+            panic!("unexpected completion");
+        };
+        assert_eq!(rsp.device_io_response.io_status, NtStatus::SUCCESS);
+
+        let request = create(
+            "\\",
+            6,
+            CreateDisposition::FILE_OPEN,
+            CreateOptions::FILE_DIRECTORY_FILE,
+        );
+        let DeviceIoCompletion::Create(rsp) = complete(&mut server, &mut client, &events, request) else {
+            panic!("unexpected completion");
+        };
+        let dir_id = rsp.file_id;
+
+        let request = query_directory(dir_id, 7, Some("\\*"));
+        let DeviceIoCompletion::QueryDirectory(rsp) = complete(&mut server, &mut client, &events, request) else {
+            panic!("unexpected completion");
+        };
+        let Some(FileInformationClass::BothDirectory(entry)) = rsp.buffer else {
+            panic!("unexpected entry: {:?}", rsp.buffer);
+        };
+        assert_eq!(entry.file_name, "file.txt");
+        assert_eq!(entry.end_of_file, 5);
+
+        let DeviceIoCompletion::QueryDirectory(rsp) =
+            complete(&mut server, &mut client, &events, query_directory(dir_id, 8, None))
+        else {
+            panic!("unexpected completion");
+        };
+        assert_eq!(rsp.device_io_reply.io_status, NtStatus::NO_MORE_FILES);
+        assert_eq!(rsp.buffer, None);
+    }
+}
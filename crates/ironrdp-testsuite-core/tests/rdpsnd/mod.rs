@@ -1,7 +1,17 @@
 use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
+use ironrdp_core::{decode, encode_vec};
+use ironrdp_dvc::{DvcMessage, DvcProcessor};
+use ironrdp_rdpsnd::client::{Rdpsnd, RdpsndClientHandler, WaveId};
+use ironrdp_rdpsnd::codec::{self, Codec, Decoder, Encoder};
+use ironrdp_rdpsnd::jitter_buffer::{JitterBuffer, JitterBufferConfig, JitterBufferStats};
 use ironrdp_rdpsnd::pdu;
+use ironrdp_rdpsnd::server::{RdpsndServer, RdpsndServerHandler, RdpsndSvcMessages};
+use ironrdp_svc::{StaticVirtualChannel, SvcMessage, SvcProcessor};
 use ironrdp_testsuite_core::encode_decode_test;
+use rstest::rstest;
 
 encode_decode_test! {
     server_format: pdu::ServerAudioOutputPdu::AudioFormat(pdu::ServerAudioFormatPdu {
@@ -169,6 +179,42 @@ encode_decode_test! {
         // Wave
         0x0, 0x0, 0x0, 0x0, 0x5, 0x6, 0x7, 0x8,
     ];
+    wave_info: pdu::ServerAudioOutputPdu::WaveInfo(pdu::WaveInfoPdu {
+        timestamp: 0xadd7,
+        format_no: 0xf,
+        block_no: 8,
+        data: [0x1, 0x2, 0x3, 0x4],
+        wave_size: 8,
+    }),
+    [
+        0x02, 0x00, 0x10, 0x00, 0xd7, 0xad, 0x0f, 0x00, 0x08, 0x00, 0x00, 0x00, 0x1, 0x2, 0x3, 0x4,
+    ];
+    udp_wave: pdu::ServerAudioOutputUdpPdu::Wave(pdu::UdpWavePdu {
+        block_no: 3,
+        frag_no: 0x12,
+        data: Cow::Borrowed(&[0x1, 0x2, 0x3]),
+    }),
+    [
+        0x0A, 0x03, 0x12, 0x1, 0x2, 0x3,
+    ];
+    udp_wave_long_frag_no: pdu::ServerAudioOutputUdpPdu::Wave(pdu::UdpWavePdu {
+        block_no: 3,
+        frag_no: 0x1234,
+        data: Cow::Borrowed(&[0x1]),
+    }),
+    [
+        0x0A, 0x03, 0x92, 0x34, 0x1,
+    ];
+    udp_wave_last: pdu::ServerAudioOutputUdpPdu::WaveLast(pdu::UdpWaveLastPdu {
+        total_size: 0x104,
+        timestamp: 0xadd7,
+        format_no: 0x2,
+        block_no: 3,
+        data: Cow::Borrowed(&[0x5, 0x6]),
+    }),
+    [
+        0x0B, 0x00, 0x0C, 0x00, 0x04, 0x01, 0xd7, 0xad, 0x02, 0x00, 0x03, 0x00, 0x00, 0x00, 0x5, 0x6,
+    ];
     wave_confirm: pdu::ClientAudioOutputPdu::WaveConfirm(pdu::WaveConfirmPdu {
         timestamp: 0x5ab7,
         block_no: 8
@@ -187,3 +233,639 @@ encode_decode_test! {
         0x0D, 0x00, 0x14, 0x00, 0x16, 0xA1, 0x03, 0x00, 0x02, 0x00, 0x00, 0x00, 0xC2, 0xB8, 0xAC, 0x0D, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
     ];
 }
+
+fn pcm_format() -> pdu::AudioFormat {
+    pdu::AudioFormat {
+        format: pdu::WaveFormat::PCM,
+        n_channels: 2,
+        n_samples_per_sec: 44100,
+        n_avg_bytes_per_sec: 176400,
+        n_block_align: 4,
+        bits_per_sample: 16,
+        data: None,
+    }
+}
+
+#[derive(Debug)]
+struct TestPlayback {
+    waves: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl RdpsndClientHandler for TestPlayback {
+    fn wave(&mut self, _format: &pdu::AudioFormat, _ts: u32, data: Cow<'_, [u8]>) {
+        self.waves.lock().unwrap().push(data.into_owned());
+    }
+
+    fn set_volume(&mut self, _volume: pdu::VolumePdu) {}
+
+    fn set_pitch(&mut self, _pitch: pdu::PitchPdu) {}
+
+    fn close(&mut self) {}
+}
+
+#[derive(Debug)]
+struct TestSource {
+    formats: Vec<pdu::AudioFormat>,
+    started: Arc<Mutex<bool>>,
+}
+
+impl RdpsndServerHandler for TestSource {
+    fn get_formats(&self) -> &[pdu::AudioFormat] {
+        &self.formats
+    }
+
+    fn start(&mut self, client_format: &pdu::ClientAudioFormatPdu) -> Option<u16> {
+        *self.started.lock().unwrap() = true;
+        codec::best_format(&client_format.formats)
+    }
+
+    fn stop(&mut self) {
+        *self.started.lock().unwrap() = false;
+    }
+}
+
+fn exchange(to: &mut dyn DvcProcessor, messages: Vec<DvcMessage>) -> Vec<DvcMessage> {
+    messages
+        .into_iter()
+        .flat_map(|message| {
+            let payload = encode_vec(message.as_ref()).unwrap();
+            to.process(1, &payload).unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn dvc_client_server_handshake() {
+    let waves = Arc::new(Mutex::new(Vec::new()));
+    let started = Arc::new(Mutex::new(false));
+
+    let mut client = Rdpsnd::new(Box::new(TestPlayback {
+        waves: Arc::clone(&waves),
+    }));
+    let mut server = RdpsndServer::new(Box::new(TestSource {
+        formats: vec![pcm_format()],
+        started: Arc::clone(&started),
+    }));
+
+    assert_eq!(DvcProcessor::channel_name(&client), "AUDIO_PLAYBACK_DVC");
+    assert_eq!(DvcProcessor::channel_name(&server), "AUDIO_PLAYBACK_DVC");
+
+    assert!(DvcProcessor::start(&mut client, 1).unwrap().is_empty());
+    let mut to_client = DvcProcessor::start(&mut server, 1).unwrap();
+
+    // Formats, quality mode and training round trips.
+    while !to_client.is_empty() {
+        let to_server = exchange(&mut client, to_client);
+        to_client = exchange(&mut server, to_server);
+    }
+
+    assert!(*started.lock().unwrap());
+    assert_eq!(server.encode_dvc_wave(vec![1, 2, 3, 4], 0).unwrap().len(), 1);
+
+    let wave: Vec<DvcMessage> = vec![Box::new(pdu::ServerAudioOutputPdu::Wave2(pdu::Wave2Pdu {
+        block_no: 0,
+        timestamp: 0,
+        audio_timestamp: 0,
+        format_no: 0,
+        data: Cow::Owned(vec![1, 2, 3, 4]),
+    }))];
+    let confirm = exchange(&mut client, wave);
+    assert_eq!(confirm.len(), 1);
+    assert_eq!(*waves.lock().unwrap(), [vec![1, 2, 3, 4]]);
+
+    DvcProcessor::close(&mut server, 1);
+    assert!(!*started.lock().unwrap());
+}
+
+#[test]
+fn lossy_dvc_channel_name() {
+    let client = Rdpsnd::new(Box::new(ironrdp_rdpsnd::client::NoopRdpsndBackend)).with_lossy_dvc();
+
+    assert_eq!(DvcProcessor::channel_name(&client), "AUDIO_PLAYBACK_LOSSY_DVC");
+}
+
+#[test]
+fn wave_info_reassembly() {
+    let waves = Arc::new(Mutex::new(Vec::new()));
+    let mut client = Rdpsnd::new(Box::new(TestPlayback {
+        waves: Arc::clone(&waves),
+    }));
+
+    let server_format = pdu::ServerAudioOutputPdu::AudioFormat(pdu::ServerAudioFormatPdu {
+        version: pdu::Version::V5,
+        formats: vec![pcm_format()],
+    });
+    let training = pdu::ServerAudioOutputPdu::Training(pdu::TrainingPdu {
+        timestamp: 0,
+        data: vec![],
+    });
+    SvcProcessor::process(&mut client, &encode_vec(&server_format).unwrap()).unwrap();
+    SvcProcessor::process(&mut client, &encode_vec(&training).unwrap()).unwrap();
+
+    let wave = pdu::WavePdu {
+        timestamp: 0x10,
+        format_no: 0,
+        block_no: 4,
+        data: Cow::Borrowed(&[1, 2, 3, 4, 5, 6, 7, 8]),
+    };
+    let (info, wave) = wave.split().unwrap();
+
+    let confirm = SvcProcessor::process(
+        &mut client,
+        &encode_vec(&pdu::ServerAudioOutputPdu::WaveInfo(info)).unwrap(),
+    )
+    .unwrap();
+    assert!(confirm.is_empty());
+    assert!(waves.lock().unwrap().is_empty());
+
+    let confirm = SvcProcessor::process(&mut client, &encode_vec(&wave).unwrap()).unwrap();
+    assert_eq!(confirm.len(), 1);
+    assert_eq!(*waves.lock().unwrap(), [vec![1, 2, 3, 4, 5, 6, 7, 8]]);
+}
+
+#[test]
+fn udp_wave_reassembly() {
+    let waves = Arc::new(Mutex::new(Vec::new()));
+    let started = Arc::new(Mutex::new(false));
+
+    let mut client = Rdpsnd::new(Box::new(TestPlayback {
+        waves: Arc::clone(&waves),
+    }));
+    let mut server = RdpsndServer::new(Box::new(TestSource {
+        formats: vec![pcm_format()],
+        started: Arc::clone(&started),
+    }));
+
+    assert!(DvcProcessor::start(&mut client, 1).unwrap().is_empty());
+    let mut to_client = DvcProcessor::start(&mut server, 1).unwrap();
+    while !to_client.is_empty() {
+        let to_server = exchange(&mut client, to_client);
+        to_client = exchange(&mut server, to_server);
+    }
+
+    let datagrams = server.udp_wave(&[1, 2, 3, 4, 5, 6, 7], 0x20, 3).unwrap();
+    assert_eq!(datagrams.len(), 3);
+
+    // Fragments may be received out of order.
+    assert!(client.process_udp(&datagrams[1]).unwrap().is_empty());
+    assert!(client.process_udp(&datagrams[0]).unwrap().is_empty());
+    let confirm = client.process_udp(&datagrams[2]).unwrap();
+    assert_eq!(
+        confirm,
+        [pdu::ClientAudioOutputPdu::WaveConfirm(pdu::WaveConfirmPdu {
+            timestamp: 0x20,
+            block_no: 0,
+        })]
+    );
+    assert_eq!(*waves.lock().unwrap(), [vec![1, 2, 3, 4, 5, 6, 7]]);
+}
+
+/// Interleaved stereo sine wave, with a different frequency on each channel.
+#[allow(clippy::cast_possible_truncation)]
+fn stereo_sine(frames: usize, sample_rate: f64) -> Vec<i16> {
+    (0..frames)
+        .flat_map(|n| {
+            let t = n as f64 / sample_rate;
+            let left = (t * 440.0 * std::f64::consts::TAU).sin() * 12000.0;
+            let right = (t * 660.0 * std::f64::consts::TAU).sin() * 8000.0;
+            [left as i16, right as i16]
+        })
+        .collect()
+}
+
+/// Signal to noise ratio, in dB.
+fn snr(reference: &[i16], decoded: &[i16]) -> f64 {
+    let (signal, noise) = reference
+        .iter()
+        .zip(decoded)
+        .fold((0.0, 0.0), |(signal, noise), (&r, &d)| {
+            let error = f64::from(r) - f64::from(d);
+            (signal + f64::from(r).powi(2), noise + error.powi(2))
+        });
+    10.0 * (signal / noise).log10()
+}
+
+#[test]
+fn g711_reference_values() {
+    let mut ulaw = Encoder::new(&Codec::MuLaw.audio_format(1, 8000).unwrap()).unwrap();
+    let mut alaw = Encoder::new(&Codec::ALaw.audio_format(1, 8000).unwrap()).unwrap();
+
+    assert_eq!(ulaw.encode(&[0, -1, 32767, -32768]), [0xFF, 0x7F, 0x80, 0x00]);
+    assert_eq!(alaw.encode(&[0, -1, 32767, -32768]), [0xD5, 0x55, 0xAA, 0x2A]);
+
+    let mut ulaw = Decoder::new(&Codec::MuLaw.audio_format(1, 8000).unwrap()).unwrap();
+    let mut alaw = Decoder::new(&Codec::ALaw.audio_format(1, 8000).unwrap()).unwrap();
+
+    assert_eq!(ulaw.decode(&[0xFF, 0x80, 0x00]).unwrap(), [0, 32124, -32124]);
+    assert_eq!(alaw.decode(&[0xD5, 0xAA, 0x2A]).unwrap(), [8, 32256, -32256]);
+}
+
+#[rstest]
+#[case(Codec::Pcm, 90.0)]
+#[case(Codec::MuLaw, 35.0)]
+#[case(Codec::ALaw, 35.0)]
+#[case(Codec::MsAdpcm, 40.0)]
+#[case(Codec::ImaAdpcm, 30.0)]
+fn codec_round_trip(#[case] codec: Codec, #[case] min_snr: f64) {
+    let format = codec.audio_format(2, 22050).unwrap();
+    assert_eq!(Codec::from_audio_format(&format), Some(codec));
+
+    let samples = stereo_sine(22050, 22050.0);
+
+    let mut encoder = Encoder::new(&format).unwrap();
+    // Feed uneven chunks, to exercise block buffering.
+    let mut encoded = Vec::new();
+    for chunk in samples.chunks(2 * 1000 + 2) {
+        encoded.extend(encoder.encode(chunk));
+    }
+    encoded.extend(encoder.flush());
+    assert_eq!(encoded.len().checked_rem(usize::from(format.n_block_align)), Some(0));
+
+    let decoded = Decoder::new(&format).unwrap().decode(&encoded).unwrap();
+    assert!(decoded.len() >= samples.len());
+
+    let snr = snr(&samples, &decoded[..samples.len()]);
+    assert!(snr > min_snr, "{codec:?}: SNR {snr:.1} dB");
+}
+
+#[test]
+fn adpcm_block_sizes() {
+    let ms_adpcm = Codec::MsAdpcm.audio_format(2, 44100).unwrap();
+    assert_eq!(ms_adpcm.n_block_align, 2048);
+    assert_eq!(&ms_adpcm.data.as_ref().unwrap()[..4], [0xF4, 0x07, 0x07, 0x00]);
+    assert_eq!(ms_adpcm.n_avg_bytes_per_sec, 44359);
+
+    let ima_adpcm = Codec::ImaAdpcm.audio_format(1, 22050).unwrap();
+    assert_eq!(ima_adpcm.n_block_align, 512);
+    assert_eq!(ima_adpcm.data.as_deref(), Some([0xF9, 0x03].as_slice()));
+
+    // The number of data bytes per block must be a multiple of 4 bytes per channel.
+    let invalid = pdu::AudioFormat {
+        n_block_align: 514,
+        ..ima_adpcm
+    };
+    assert_eq!(Codec::from_audio_format(&invalid), None);
+}
+
+#[test]
+fn best_format_prefers_compressed_codecs() {
+    let mp3 = pdu::AudioFormat {
+        format: pdu::WaveFormat::MPEGLAYER3,
+        ..pcm_format()
+    };
+    let formats = [
+        mp3,
+        pcm_format(),
+        Codec::ALaw.audio_format(2, 44100).unwrap(),
+        Codec::ImaAdpcm.audio_format(2, 44100).unwrap(),
+        Codec::MsAdpcm.audio_format(1, 22050).unwrap(),
+        Codec::MsAdpcm.audio_format(2, 44100).unwrap(),
+        Codec::MsAdpcm.audio_format(2, 44100).unwrap(),
+    ];
+
+    assert_eq!(codec::best_format(&formats), Some(5));
+    assert_eq!(codec::best_format(&formats[..3]), Some(2));
+    assert_eq!(codec::best_format(&formats[..1]), None);
+}
+
+/// Returns the payload of each message, sans channel PDU headers.
+fn svc_payloads(msgs: RdpsndSvcMessages) -> Vec<Vec<u8>> {
+    Vec::<SvcMessage>::from(msgs)
+        .into_iter()
+        .map(|msg| {
+            StaticVirtualChannel::chunkify(vec![msg])
+                .unwrap()
+                .iter()
+                .flat_map(|chunk| chunk.filled()[8..].to_vec())
+                .collect()
+        })
+        .collect()
+}
+
+fn decode_wave2(payload: &[u8]) -> pdu::Wave2Pdu<'_> {
+    match decode::<pdu::ServerAudioOutputPdu<'_>>(payload).unwrap() {
+        pdu::ServerAudioOutputPdu::Wave2(wave) => wave,
+        pdu => panic!("unexpected PDU: {pdu:?}"),
+    }
+}
+
+fn handshake(client: &mut Rdpsnd, server: &mut RdpsndServer) {
+    let mut to_client = DvcProcessor::start(server, 1).unwrap();
+    while !to_client.is_empty() {
+        let to_server = exchange(client, to_client);
+        to_client = exchange(server, to_server);
+    }
+}
+
+#[test]
+fn pcm_wave_uses_negotiated_codec() {
+    let ms_adpcm = Codec::MsAdpcm.audio_format(2, 44100).unwrap();
+    let waves = Arc::new(Mutex::new(Vec::new()));
+    let mut server = RdpsndServer::new(Box::new(TestSource {
+        formats: vec![pcm_format(), ms_adpcm.clone()],
+        started: Arc::new(Mutex::new(false)),
+    }));
+    let mut client = Rdpsnd::new(Box::new(TestPlayback {
+        waves: Arc::clone(&waves),
+    }));
+    handshake(&mut client, &mut server);
+
+    // Not enough samples for a whole block yet.
+    let samples = stereo_sine(4096, 44100.0);
+    assert!(svc_payloads(server.pcm_wave(&samples[..1000], 0).unwrap()).is_empty());
+
+    let payloads = svc_payloads(server.pcm_wave(&samples[1000..], 0).unwrap());
+    assert_eq!(payloads.len(), 1);
+    let wave = decode_wave2(&payloads[0]);
+    assert_eq!(wave.format_no, 1);
+    // Two blocks of 2036 frames, the remaining ones being kept for the next wave.
+    assert_eq!(wave.data.len(), 2 * 2048);
+
+    SvcProcessor::process(&mut client, &payloads[0]).unwrap();
+    let decoded = Decoder::new(&ms_adpcm)
+        .unwrap()
+        .decode(&waves.lock().unwrap()[0])
+        .unwrap();
+    assert_eq!(decoded.len(), 2 * 2036 * 2);
+}
+
+#[derive(Debug)]
+struct PcmOnlyPlayback {
+    waves: Arc<Mutex<Vec<FormattedWave>>>,
+}
+
+type FormattedWave = (pdu::AudioFormat, Vec<u8>);
+
+impl RdpsndClientHandler for PcmOnlyPlayback {
+    fn supported_formats(&self, server_formats: &[pdu::AudioFormat]) -> Vec<pdu::AudioFormat> {
+        server_formats
+            .iter()
+            .filter(|format| format.format == pdu::WaveFormat::PCM)
+            .cloned()
+            .collect()
+    }
+
+    fn wave(&mut self, format: &pdu::AudioFormat, _ts: u32, data: Cow<'_, [u8]>) {
+        self.waves.lock().unwrap().push((format.clone(), data.into_owned()));
+    }
+
+    fn set_volume(&mut self, _volume: pdu::VolumePdu) {}
+
+    fn set_pitch(&mut self, _pitch: pdu::PitchPdu) {}
+
+    fn close(&mut self) {}
+}
+
+#[test]
+fn client_supported_formats() {
+    let waves = Arc::new(Mutex::new(Vec::new()));
+    let mut server = RdpsndServer::new(Box::new(TestSource {
+        formats: vec![Codec::MsAdpcm.audio_format(2, 44100).unwrap(), pcm_format()],
+        started: Arc::new(Mutex::new(false)),
+    }));
+    let mut client = Rdpsnd::new(Box::new(PcmOnlyPlayback {
+        waves: Arc::clone(&waves),
+    }));
+    handshake(&mut client, &mut server);
+
+    // The format number refers to the client list, which only has PCM.
+    let payloads = svc_payloads(server.pcm_wave(&[1, -1, 2, -2], 0).unwrap());
+    assert_eq!(decode_wave2(&payloads[0]).format_no, 0);
+
+    SvcProcessor::process(&mut client, &payloads[0]).unwrap();
+    assert_eq!(
+        *waves.lock().unwrap(),
+        [(pcm_format(), vec![0x01, 0x00, 0xFF, 0xFF, 0x02, 0x00, 0xFE, 0xFF])]
+    );
+}
+
+#[derive(Debug)]
+struct DeferredPlayback {
+    queued: Arc<Mutex<Vec<WaveId>>>,
+}
+
+impl RdpsndClientHandler for DeferredPlayback {
+    fn wave(&mut self, _format: &pdu::AudioFormat, _ts: u32, _data: Cow<'_, [u8]>) {}
+
+    fn queue_wave(&mut self, _format: &pdu::AudioFormat, _ts: u32, _data: Cow<'_, [u8]>, id: WaveId) -> bool {
+        self.queued.lock().unwrap().push(id);
+        true
+    }
+
+    fn set_volume(&mut self, _volume: pdu::VolumePdu) {}
+
+    fn set_pitch(&mut self, _pitch: pdu::PitchPdu) {}
+
+    fn close(&mut self) {}
+}
+
+#[test]
+fn wave_confirmed_once_played() {
+    let queued = Arc::new(Mutex::new(Vec::new()));
+    let mut server = RdpsndServer::new(Box::new(TestSource {
+        formats: vec![pcm_format()],
+        started: Arc::new(Mutex::new(false)),
+    }));
+    let mut client = Rdpsnd::new(Box::new(DeferredPlayback {
+        queued: Arc::clone(&queued),
+    }));
+    handshake(&mut client, &mut server);
+
+    let wave: Vec<DvcMessage> = vec![Box::new(pdu::ServerAudioOutputPdu::Wave2(pdu::Wave2Pdu {
+        block_no: 7,
+        timestamp: 0xFFF0,
+        audio_timestamp: 0,
+        format_no: 0,
+        data: Cow::Owned(vec![1, 2, 3, 4]),
+    }))];
+    assert!(exchange(&mut client, wave).is_empty());
+
+    let id = queued.lock().unwrap()[0];
+    assert_eq!(
+        id,
+        WaveId {
+            timestamp: 0xFFF0,
+            block_no: 7
+        }
+    );
+
+    // The confirmed timestamp accounts for the playback latency, and wraps around.
+    let confirm = id.confirm(Duration::from_millis(0x20));
+    assert_eq!(
+        confirm,
+        pdu::WaveConfirmPdu {
+            timestamp: 0x10,
+            block_no: 7
+        }
+    );
+
+    let messages = client
+        .encode_dvc_wave_confirm(1, confirm.timestamp, confirm.block_no)
+        .unwrap();
+    assert_eq!(messages.len(), 1);
+    let payloads = svc_payloads(client.wave_confirm(confirm.timestamp, confirm.block_no).unwrap());
+    assert_eq!(
+        decode::<pdu::ClientAudioOutputPdu>(&payloads[0]).unwrap(),
+        pdu::ClientAudioOutputPdu::WaveConfirm(confirm)
+    );
+}
+
+/// Jitter buffer at 1 kHz, so that one frame lasts one millisecond.
+fn jitter_buffer<T>(n_channels: u16) -> JitterBuffer<T> {
+    JitterBuffer::new(
+        n_channels,
+        1000,
+        JitterBufferConfig {
+            target_latency: Duration::from_millis(20),
+            max_latency: Duration::from_millis(50),
+            tolerance: Duration::from_millis(2),
+        },
+    )
+}
+
+fn fill<T>(buffer: &mut JitterBuffer<T>, len: usize) -> (Vec<i16>, Vec<T>) {
+    let mut out = vec![i16::MAX; len];
+    let played = buffer.fill(&mut out);
+    (out, played)
+}
+
+#[test]
+fn jitter_buffer_waits_for_target_latency() {
+    let mut buffer = jitter_buffer(1);
+
+    assert!(buffer.push(0, vec![1; 10], 'a').is_empty());
+    assert_eq!(fill(&mut buffer, 10), (vec![0; 10], vec![]));
+
+    assert!(buffer.push(10, vec![2; 10], 'b').is_empty());
+    assert_eq!(buffer.buffered(), Duration::from_millis(20));
+
+    let (out, played) = fill(&mut buffer, 20);
+    assert_eq!(out, [[1; 10], [2; 10]].concat());
+    assert_eq!(played, ['a', 'b']);
+}
+
+#[test]
+fn jitter_buffer_fills_gaps_with_silence() {
+    let mut buffer = jitter_buffer(1);
+
+    buffer.push(0, vec![1; 10], 'a');
+    // A gap smaller than the tolerance is ignored.
+    buffer.push(11, vec![2; 5], 'b');
+    buffer.push(25, vec![3; 5], 'c');
+    assert_eq!(buffer.buffered(), Duration::from_millis(30));
+
+    let (out, played) = fill(&mut buffer, 30);
+    assert_eq!(out, [vec![1; 10], vec![2; 5], vec![0; 10], vec![3; 5]].concat());
+    assert_eq!(played, ['a', 'b', 'c']);
+    assert_eq!(buffer.stats().inserted_silence_frames, 10);
+}
+
+#[test]
+fn jitter_buffer_trims_overlapping_waves() {
+    let mut buffer = jitter_buffer(1);
+
+    buffer.push(0, vec![1; 10], 'a');
+    buffer.push(5, vec![2; 10], 'b');
+    assert_eq!(buffer.buffered(), Duration::from_millis(15));
+
+    // Entirely overlapping wave.
+    assert_eq!(buffer.push(2, vec![3; 5], 'c'), ['c']);
+    buffer.push(15, vec![4; 5], 'd');
+
+    let (out, played) = fill(&mut buffer, 20);
+    assert_eq!(out, [vec![1; 10], vec![2; 5], vec![4; 5]].concat());
+    assert_eq!(played, ['a', 'b', 'd']);
+    assert_eq!(
+        buffer.stats(),
+        JitterBufferStats {
+            underruns: 0,
+            dropped_waves: 1,
+            inserted_silence_frames: 0,
+            trimmed_frames: 10,
+        }
+    );
+}
+
+#[test]
+fn jitter_buffer_follows_wrapping_timestamps() {
+    let mut buffer = jitter_buffer(1);
+
+    buffer.push(65_530, vec![1; 10], 'a');
+    // 20 ms after the first wave, leaving a 10 ms gap.
+    buffer.push(14, vec![2; 10], 'b');
+
+    assert_eq!(buffer.stats().inserted_silence_frames, 10);
+    let (out, played) = fill(&mut buffer, 30);
+    assert_eq!(out, [[1; 10], [0; 10], [2; 10]].concat());
+    assert_eq!(played, ['a', 'b']);
+}
+
+#[test]
+fn jitter_buffer_overrun_drops_oldest_waves() {
+    let mut buffer = jitter_buffer(1);
+
+    let mut dropped = Vec::new();
+    for (i, ts) in (0..6).zip((0..).step_by(10)) {
+        dropped.extend(buffer.push(ts, vec![i; 10], i));
+    }
+
+    // Back to the target latency.
+    assert_eq!(dropped, [0, 1, 2, 3]);
+    assert_eq!(buffer.buffered(), Duration::from_millis(20));
+    assert_eq!(buffer.stats().dropped_waves, 4);
+
+    let (out, played) = fill(&mut buffer, 20);
+    assert_eq!(out, [[4; 10], [5; 10]].concat());
+    assert_eq!(played, [4, 5]);
+}
+
+#[test]
+fn jitter_buffer_underrun_rebuffers() {
+    let mut buffer = jitter_buffer(1);
+
+    buffer.push(0, vec![1; 20], 'a');
+    let (out, played) = fill(&mut buffer, 25);
+    assert_eq!(out, [vec![1; 20], vec![0; 5]].concat());
+    assert_eq!(played, ['a']);
+    assert_eq!(buffer.stats().underruns, 1);
+
+    // The timeline restarts from the next wave, and playback waits for the target latency again.
+    buffer.push(500, vec![2; 10], 'b');
+    assert_eq!(fill(&mut buffer, 10), (vec![0; 10], vec![]));
+    buffer.push(510, vec![3; 10], 'c');
+    assert_eq!(buffer.stats().inserted_silence_frames, 0);
+
+    let (out, played) = fill(&mut buffer, 20);
+    assert_eq!(out, [[2; 10], [3; 10]].concat());
+    assert_eq!(played, ['b', 'c']);
+}
+
+#[test]
+fn jitter_buffer_applies_volume() {
+    let mut buffer = jitter_buffer(2);
+    buffer.set_volume(pdu::VolumePdu {
+        volume_left: 0x8000,
+        volume_right: 0xFFFF,
+    });
+
+    buffer.push(0, [1000, -1000].repeat(20), ());
+    let (out, _) = fill(&mut buffer, 4);
+    assert_eq!(out, [500, -1000, 500, -1000]);
+}
+
+#[test]
+fn jitter_buffer_applies_pitch() {
+    let mut buffer = jitter_buffer(1);
+    buffer.set_pitch(pdu::PitchPdu { pitch: 0x2_0000 });
+
+    buffer.push(0, (0..20).map(|i| i * 100).collect(), 'a');
+    let (out, played) = fill(&mut buffer, 10);
+    assert_eq!(out, (0..10).map(|i| i * 200).collect::<Vec<_>>());
+    assert_eq!(played, ['a']);
+
+    // Half speed, interpolating between frames.
+    buffer.set_pitch(pdu::PitchPdu { pitch: 0x8000 });
+    buffer.push(20, (0..20).map(|i| i * 100).collect(), 'b');
+    let (out, _) = fill(&mut buffer, 4);
+    assert_eq!(out, [0, 50, 100, 150]);
+}
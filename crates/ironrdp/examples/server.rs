@@ -238,8 +238,17 @@ impl ServerEventSender for StubSoundServerFactory {
 
 impl SoundServerFactory for StubSoundServerFactory {
     fn build_backend(&self) -> Box<dyn RdpsndServerHandler> {
+        use ironrdp::server::audio_codec::Codec;
+
+        // Compressed formats first, falling back to plain PCM.
+        let formats = [Codec::MsAdpcm, Codec::ImaAdpcm, Codec::MuLaw, Codec::ALaw, Codec::Pcm]
+            .into_iter()
+            .filter_map(|codec| codec.audio_format(2, 44100))
+            .collect();
+
         Box::new(SndHandler {
             inner: Arc::clone(&self.inner),
+            formats,
             task: None,
         })
     }
@@ -248,33 +257,24 @@ impl SoundServerFactory for StubSoundServerFactory {
 #[derive(Debug)]
 struct SndHandler {
     inner: Arc<Mutex<Inner>>,
+    formats: Vec<ironrdp_rdpsnd::pdu::AudioFormat>,
     task: Option<tokio::task::JoinHandle<()>>,
 }
 
 impl RdpsndServerHandler for SndHandler {
     fn get_formats(&self) -> &[ironrdp_rdpsnd::pdu::AudioFormat] {
-        use ironrdp_rdpsnd::pdu::{AudioFormat, WaveFormat};
-
-        &[AudioFormat {
-            format: WaveFormat::PCM,
-            n_channels: 2,
-            n_samples_per_sec: 44100,
-            n_avg_bytes_per_sec: 176400,
-            n_block_align: 4,
-            bits_per_sample: 16,
-            data: None,
-        }]
+        &self.formats
     }
 
     fn start(&mut self, client_format: &ClientAudioFormatPdu) -> Option<u16> {
-        async fn generate_sine_wave(sample_rate: u32, frequency: f32, duration_ms: u64) -> Vec<u8> {
+        async fn generate_sine_wave(sample_rate: u32, frequency: f32, duration_ms: u64) -> Vec<i16> {
             use std::f32::consts::PI;
 
             let total_samples = u64::from(sample_rate / 1000).checked_mul(duration_ms).unwrap();
             let samples_per_wave_length = sample_rate as f32 / frequency;
             let amplitude = 32767.0; // Max amplitude for 16-bit audio
 
-            let capacity = total_samples.checked_mul(2 + 2).unwrap();
+            let capacity = total_samples.checked_mul(2).unwrap();
             let mut samples = Vec::with_capacity(usize::try_from(capacity).unwrap());
 
             for n in 0..total_samples {
@@ -282,13 +282,16 @@ impl RdpsndServerHandler for SndHandler {
                 let sample = (t * 2.0 * PI).sin();
                 #[allow(clippy::cast_possible_truncation)]
                 let sample = (sample * amplitude) as i16;
-                samples.extend_from_slice(&sample.to_le_bytes());
-                samples.extend_from_slice(&sample.to_le_bytes());
+                samples.push(sample);
+                samples.push(sample);
             }
 
             samples
         }
 
+        debug!(?client_format);
+        let format_no = ironrdp::server::audio_codec::best_format(&client_format.formats)?;
+
         let inner = Arc::clone(&self.inner);
         self.task = Some(tokio::spawn(async move {
             let mut interval = time::interval(Duration::from_millis(100));
@@ -298,14 +301,13 @@ impl RdpsndServerHandler for SndHandler {
                 let data = generate_sine_wave(44100, 440.0, 100).await;
                 let inner = inner.lock().unwrap();
                 if let Some(sender) = inner.ev_sender.as_ref() {
-                    let _ = sender.send(ServerEvent::Rdpsnd(RdpsndServerMessage::Wave(data, ts)));
+                    let _ = sender.send(ServerEvent::Rdpsnd(RdpsndServerMessage::PcmWave(data, ts)));
                 }
                 ts = ts.wrapping_add(100);
             }
         }));
 
-        debug!(?client_format);
-        Some(0)
+        Some(format_no)
     }
 
     fn stop(&mut self) {
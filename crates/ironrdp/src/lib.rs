@@ -5,6 +5,8 @@
 
 #[cfg(feature = "acceptor")]
 pub use ironrdp_acceptor as acceptor;
+#[cfg(feature = "audin")]
+pub use ironrdp_audin as audin;
 #[cfg(feature = "cliprdr")]
 pub use ironrdp_cliprdr as cliprdr;
 #[cfg(feature = "connector")]
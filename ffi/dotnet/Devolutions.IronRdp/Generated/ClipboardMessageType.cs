@@ -17,4 +17,6 @@ public enum ClipboardMessageType
     SendFormatData = 1,
     SendInitiatePaste = 2,
     Error = 3,
+    SendFileContentsRequest = 4,
+    SendFileContentsResponse = 5,
 }
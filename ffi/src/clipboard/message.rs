@@ -14,6 +14,12 @@ pub mod ffi {
                 ironrdp::cliprdr::backend::ClipboardMessage::SendInitiatePaste(_) => {
                     ClipboardMessageType::SendInitiatePaste
                 }
+                ironrdp::cliprdr::backend::ClipboardMessage::SendFileContentsRequest(_) => {
+                    ClipboardMessageType::SendFileContentsRequest
+                }
+                ironrdp::cliprdr::backend::ClipboardMessage::SendFileContentsResponse(_) => {
+                    ClipboardMessageType::SendFileContentsResponse
+                }
                 ironrdp::cliprdr::backend::ClipboardMessage::Error(_) => ClipboardMessageType::Error,
             }
         }
@@ -52,6 +58,8 @@ pub mod ffi {
         SendFormatData,
         SendInitiatePaste,
         Error,
+        SendFileContentsRequest,
+        SendFileContentsResponse,
     }
 
     #[diplomat::opaque]